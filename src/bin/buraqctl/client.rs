@@ -0,0 +1,267 @@
+use crate::profile::Profile;
+use anyhow::{Context, anyhow, bail};
+use reqwest::Method;
+use reqwest::blocking::{self, RequestBuilder};
+use reqwest::header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderName, HeaderValue};
+use reqwest::tls::{Certificate, Identity};
+use serde_json::Value;
+use std::fs;
+use std::time::Duration;
+use url::Url;
+
+/// How long to wait for the server to accept a connection or send data.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Blocking client for the Buraq API.
+pub struct Client {
+    base: Url,
+    http: blocking::Client,
+    headers: HeaderMap,
+}
+
+impl Client {
+    pub fn new(profile: &Profile) -> anyhow::Result<Self> {
+        let mut base = Url::parse(&profile.url)
+            .with_context(|| format!("Invalid server URL `{}`", profile.url))?;
+        if !base.path().ends_with('/') {
+            let path = format!("{}/", base.path());
+            base.set_path(&path);
+        }
+        if !matches!(base.scheme(), "http" | "https") {
+            bail!("Unsupported URL scheme `{}`", base.scheme());
+        }
+
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        if let Some(token) = &profile.token {
+            let mut value = HeaderValue::try_from(format!("Bearer {}", token))
+                .context("The token is not a valid header value")?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+        if let Some(actor) = &profile.actor {
+            headers.insert(
+                "x-actor",
+                HeaderValue::try_from(actor).context("The actor is not a valid header value")?,
+            );
+        }
+
+        let mut builder = blocking::Client::builder()
+            .user_agent(concat!("buraqctl/", env!("CARGO_PKG_VERSION")))
+            .timeout(TIMEOUT)
+            .connect_timeout(TIMEOUT);
+        if let Some(ca_cert) = &profile.ca_cert {
+            let pem = fs::read(ca_cert)
+                .with_context(|| format!("Failed to read {}", ca_cert.display()))?;
+            for certificate in Certificate::from_pem_bundle(&pem).with_context(|| {
+                format!("Failed to load CA certificates from {}", ca_cert.display())
+            })? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        match (&profile.client_cert, &profile.client_key) {
+            (Some(cert), Some(key)) => {
+                let mut pem =
+                    fs::read(cert).with_context(|| format!("Failed to read {}", cert.display()))?;
+                pem.push(b'\n');
+                pem.extend(
+                    fs::read(key).with_context(|| format!("Failed to read {}", key.display()))?,
+                );
+                let identity = Identity::from_pem(&pem).with_context(|| {
+                    format!(
+                        "Failed to load client certificate {} with key {}",
+                        cert.display(),
+                        key.display()
+                    )
+                })?;
+                builder = builder.identity(identity);
+            }
+            (None, None) => {}
+            _ => bail!("client_cert and client_key must be set together"),
+        }
+
+        Ok(Self {
+            base,
+            http: builder
+                .build()
+                .context("Failed to set up the HTTP client")?,
+            headers,
+        })
+    }
+
+    /// Sends `name: value` with every request.
+    pub fn with_header(mut self, name: &'static str, value: &str) -> anyhow::Result<Self> {
+        let value = HeaderValue::try_from(value)
+            .with_context(|| format!("Invalid value for header {}", name))?;
+        self.headers.insert(HeaderName::from_static(name), value);
+        Ok(self)
+    }
+
+    pub fn get(&self, path: &str, query: &[(String, String)]) -> anyhow::Result<Value> {
+        self.send(self.request(Method::GET, path)?.query(query))
+    }
+
+    pub fn post(
+        &self,
+        path: &str,
+        query: &[(String, String)],
+        body: Option<&Value>,
+    ) -> anyhow::Result<Value> {
+        let request = self.request(Method::POST, path)?.query(query);
+        self.send(match body {
+            Some(body) => request.json(body),
+            None => request,
+        })
+    }
+
+    pub fn patch(&self, path: &str, body: &Value) -> anyhow::Result<Value> {
+        self.send(self.request(Method::PATCH, path)?.json(body))
+    }
+
+    pub fn delete(&self, path: &str) -> anyhow::Result<Value> {
+        self.send(self.request(Method::DELETE, path)?)
+    }
+
+    fn request(&self, method: Method, path: &str) -> anyhow::Result<RequestBuilder> {
+        let url = self
+            .base
+            .join(path.trim_start_matches('/'))
+            .with_context(|| format!("Invalid path `{}`", path))?;
+        Ok(self.http.request(method, url).headers(self.headers.clone()))
+    }
+
+    /// Sends a request and parses the JSON response, turning error statuses
+    /// into errors carrying the problem details the server returned.
+    fn send(&self, request: RequestBuilder) -> anyhow::Result<Value> {
+        let response = request.send().context("Failed to reach the server")?;
+        let status = response.status().as_u16();
+        let body = response.bytes().context("Failed to read the response")?;
+
+        if !(200..300).contains(&status) {
+            return Err(api_error(status, &body));
+        }
+        if body.is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_slice(&body).context("The server returned invalid JSON")
+    }
+}
+
+/// Error for a response with an error status, using the problem details
+/// (RFC 9457) the server answers with.
+fn api_error(status: u16, body: &[u8]) -> anyhow::Error {
+    let problem: Option<Value> = serde_json::from_slice(body).ok();
+    let field = |name: &str| {
+        problem
+            .as_ref()
+            .and_then(|problem| problem.get(name))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    // Problem-specific members, such as the conflicts of an import
+    let extensions: String = problem
+        .as_ref()
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter(|(name, _)| !["type", "title", "status", "detail"].contains(&name.as_str()))
+        .map(|(name, value)| match value {
+            Value::Array(items) => items
+                .iter()
+                .map(|item| format!("\n  {}: {}", name, item))
+                .collect(),
+            value => format!("\n  {}: {}", name, value),
+        })
+        .collect();
+    match (field("title"), field("detail")) {
+        (Some(title), Some(detail)) => {
+            anyhow!("{} {}: {}{}", status, title, detail, extensions)
+        }
+        (Some(title), None) => anyhow!("{} {}{}", status, title, extensions),
+        _ => anyhow!("{} {}", status, String::from_utf8_lossy(body).trim()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{App, HttpResponse, HttpServer, web};
+
+    #[test]
+    fn test_api_error_uses_problem_details() {
+        let body = br#"{"type":"about:blank","title":"Not Found","status":404,"detail":"Project not found"}"#;
+        assert_eq!(
+            api_error(404, body).to_string(),
+            "404 Not Found: Project not found"
+        );
+
+        let body = br#"{"type":"about:blank","title":"Conflict","status":409,"detail":"Nothing was imported","conflicts":[{"kind":"project"},{"kind":"service_account"}]}"#;
+        assert_eq!(
+            api_error(409, body).to_string(),
+            "409 Conflict: Nothing was imported\n  conflicts: {\"kind\":\"project\"}\n  conflicts: {\"kind\":\"service_account\"}"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_round_trip() {
+        let server = HttpServer::new(|| {
+            App::new().route(
+                "/api/echo",
+                web::post().to(
+                    |request: actix_web::HttpRequest, body: web::Json<Value>| async move {
+                        let header = |name: &str| {
+                            request
+                                .headers()
+                                .get(name)
+                                .and_then(|value| value.to_str().ok())
+                                .map(str::to_string)
+                        };
+                        HttpResponse::Created().json(serde_json::json!({
+                            "body": body.into_inner(),
+                            "query": request.query_string(),
+                            "authorization": header("authorization"),
+                            "actor": header("x-actor"),
+                            "passphrase": header("x-bundle-passphrase"),
+                        }))
+                    },
+                ),
+            )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let profile = Profile {
+            url: format!("http://{}/api", address),
+            token: Some("token".to_string()),
+            actor: Some("oncall".to_string()),
+            ..Default::default()
+        };
+        let response = actix_web::rt::task::spawn_blocking(move || {
+            let client = Client::new(&profile)
+                .unwrap()
+                .with_header("x-bundle-passphrase", "correct horse")
+                .unwrap();
+            let query = [("dry_run".to_string(), "true".to_string())];
+            client.post(
+                "echo",
+                &query,
+                Some(&serde_json::json!({ "name": "billing" })),
+            )
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(response["body"]["name"], "billing");
+        assert_eq!(response["authorization"], "Bearer token");
+        assert_eq!(response["actor"], "oncall");
+        assert_eq!(response["passphrase"], "correct horse");
+        assert_eq!(response["query"], "dry_run=true");
+        handle.stop(true).await;
+    }
+}
@@ -0,0 +1,847 @@
+//! `buraqctl`: command line administration of a Buraq server.
+//!
+//! Every resource of the API has a subcommand with `list`, `get`, `create`,
+//! `update`, `delete` and `restore` actions, projects can also be exported
+//! and imported as bundles, and `apply` brings the server in line with a
+//! manifest declaring resources by name. The server to talk to, and the
+//! credentials to present, come from a profile in the buraqctl config file,
+//! overridden by command line options and `BURAQCTL_*` environment variables.
+
+mod client;
+mod output;
+mod profile;
+mod resources;
+
+use anyhow::{Context, anyhow, bail};
+use clap::builder::PossibleValuesParser;
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command, ValueHint, ValueSource, value_parser};
+use clap_complete::Shell;
+use client::Client;
+use output::{FORMATS, Format};
+use profile::{Profile, ProfileFile, SETTINGS};
+use resources::{Column, RESOURCES, Resource};
+use serde_json::{Map, Value};
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Options selecting the server and credentials, named after the profile
+/// settings they override.
+const CONNECTION_OPTIONS: &[(&str, &str, &str)] = &[
+    ("url", "BURAQCTL_URL", "Base URL of the Buraq server"),
+    (
+        "token",
+        "BURAQCTL_TOKEN",
+        "Bearer token sent with every request",
+    ),
+    ("actor", "BURAQCTL_ACTOR", "Actor recorded on deletions"),
+    (
+        "ca-cert",
+        "BURAQCTL_CA_CERT",
+        "PEM file of CAs to verify the server with",
+    ),
+    (
+        "client-cert",
+        "BURAQCTL_CLIENT_CERT",
+        "PEM client certificate for mTLS",
+    ),
+    (
+        "client-key",
+        "BURAQCTL_CLIENT_KEY",
+        "PEM key of the client certificate",
+    ),
+];
+
+const PROFILE_COLUMNS: &[Column] = &[
+    Column {
+        header: "NAME",
+        field: "name",
+    },
+    Column {
+        header: "URL",
+        field: "url",
+    },
+    Column {
+        header: "DEFAULT",
+        field: "default",
+    },
+];
+
+/// The buraqctl command tree.
+pub fn cli() -> Command<'static> {
+    let mut command = Command::new("buraqctl")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Administer a Buraq server")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("profile")
+                .short('p')
+                .long("profile")
+                .env("BURAQCTL_PROFILE")
+                .global(true)
+                .action(ArgAction::Set)
+                .help("Profile of the config file to use"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .env("BURAQCTL_CONFIG")
+                .global(true)
+                .action(ArgAction::Set)
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .help("Config file holding the profiles"),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .global(true)
+                .action(ArgAction::Set)
+                .value_parser(PossibleValuesParser::new(FORMATS.iter().copied()))
+                .default_value("table")
+                .help("Output format"),
+        );
+    for (name, env, help) in CONNECTION_OPTIONS {
+        let mut arg = Arg::new(*name)
+            .long(name)
+            .env(env)
+            .global(true)
+            .action(ArgAction::Set)
+            .help(*help);
+        if *name == "token" {
+            arg = arg.hide_env_values(true);
+        }
+        if name.ends_with("-cert") || name.ends_with("-key") {
+            arg = arg.value_hint(ValueHint::FilePath);
+        }
+        command = command.arg(arg);
+    }
+
+    command
+        .subcommands(RESOURCES.iter().map(resource_command))
+        .subcommand(
+            Command::new("apply")
+                .about("Create, update and optionally prune resources to match a manifest")
+                .arg(
+                    Arg::new("file")
+                        .short('f')
+                        .long("file")
+                        .required(true)
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(PathBuf))
+                        .value_hint(ValueHint::FilePath)
+                        .help("JSON or YAML manifest, `-` for standard input"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("Show the changes without making them"),
+                )
+                .arg(
+                    Arg::new("prune")
+                        .long("prune")
+                        .action(ArgAction::SetTrue)
+                        .requires("prune-scope")
+                        .help("Delete resources the manifest does not declare"),
+                )
+                .arg(
+                    Arg::new("selector")
+                        .long("selector")
+                        .action(ArgAction::Set)
+                        .requires("prune")
+                        .help("Only prune resources whose labels match, e.g. `team=payments`"),
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .action(ArgAction::SetTrue)
+                        .requires("prune")
+                        .help("Prune every resource the manifest does not declare"),
+                )
+                .group(ArgGroup::new("prune-scope").args(&["selector", "all"])),
+        )
+        .subcommand(
+            Command::new("profiles")
+                .about("Manage the profiles of the config file")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List profiles"))
+                .subcommand(
+                    Command::new("set")
+                        .about("Create or update a profile from the connection options given")
+                        .arg(Arg::new("name").required(true).help("Profile name"))
+                        .arg(
+                            Arg::new("default")
+                                .long("default")
+                                .action(ArgAction::SetTrue)
+                                .help("Also make it the default profile"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("use")
+                        .about("Make a profile the default")
+                        .arg(Arg::new("name").required(true).help("Profile name")),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_parser(value_parser!(Shell))
+                        .help("Shell to complete in"),
+                ),
+        )
+}
+
+fn resource_command(resource: &'static Resource) -> Command<'static> {
+    let id = || Arg::new("id").required(true).help("Id of the record");
+    let payload = |command: Command<'static>| {
+        command
+            .arg(
+                Arg::new("file")
+                    .short('f')
+                    .long("file")
+                    .action(ArgAction::Set)
+                    .value_parser(value_parser!(PathBuf))
+                    .value_hint(ValueHint::FilePath)
+                    .help("JSON or YAML payload, `-` for standard input"),
+            )
+            .arg(
+                Arg::new("set")
+                    .long("set")
+                    .action(ArgAction::Append)
+                    .value_name("FIELD=VALUE")
+                    .help("Set a field; dotted fields nest, values are JSON or strings"),
+            )
+            .group(
+                ArgGroup::new("payload")
+                    .args(&["file", "set"])
+                    .multiple(true)
+                    .required(true),
+            )
+    };
+
+    let mut delete = Command::new("delete")
+        .about("Delete a record; it can be restored until purged")
+        .arg(id());
+    if resource.cascade {
+        delete = delete.arg(
+            Arg::new("cascade")
+                .long("cascade")
+                .action(ArgAction::SetTrue)
+                .help("Also delete the records that depend on it"),
+        );
+    }
+
+    let command = Command::new(resource.name)
+        .about(resource.about)
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("list")
+                .about("List records")
+                .arg(
+                    Arg::new("filter")
+                        .long("filter")
+                        .action(ArgAction::Append)
+                        .value_name("FIELD=VALUE")
+                        .help("Only list records matching a filter"),
+                )
+                .arg(
+                    Arg::new("sort")
+                        .long("sort")
+                        .action(ArgAction::Set)
+                        .help("Sort, e.g. `name:asc,created_at:desc`"),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32))
+                        .help("Page size"),
+                )
+                .arg(
+                    Arg::new("page")
+                        .long("page")
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32))
+                        .help("Page number"),
+                )
+                .arg(
+                    Arg::new("cursor")
+                        .long("cursor")
+                        .action(ArgAction::Set)
+                        .help("Cursor returned by a previous page"),
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(&["page", "cursor"])
+                        .help("Fetch every page"),
+                ),
+        )
+        .subcommand(Command::new("get").about("Show a record").arg(id()))
+        .subcommand(payload(Command::new("create").about("Create a record")))
+        .subcommand(payload(
+            Command::new("update")
+                .about("Update fields of a record")
+                .arg(id()),
+        ))
+        .subcommand(delete)
+        .subcommand(
+            Command::new("restore")
+                .about("Restore a deleted record")
+                .arg(id()),
+        );
+    if !resource.bundles {
+        return command;
+    }
+
+    let passphrase = || {
+        Arg::new("passphrase")
+            .long("passphrase")
+            .env("BURAQCTL_BUNDLE_PASSPHRASE")
+            .hide_env_values(true)
+            .action(ArgAction::Set)
+    };
+    command
+        .subcommand(
+            Command::new("export")
+                .about("Print a record with its dependents as a bundle to import elsewhere")
+                .arg(id())
+                .arg(
+                    passphrase().help("Include secrets and keys, encrypted under this passphrase"),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Recreate a record from a bundle, with new ids")
+                .arg(
+                    Arg::new("file")
+                        .short('f')
+                        .long("file")
+                        .required(true)
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(PathBuf))
+                        .value_hint(ValueHint::FilePath)
+                        .help("Bundle, `-` for standard input"),
+                )
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .action(ArgAction::Set)
+                        .help("Name to import it under"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("Only check for conflicts"),
+                )
+                .arg(passphrase().help("Passphrase the bundle was exported with")),
+        )
+}
+
+fn main() -> ExitCode {
+    match run(&cli().get_matches()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(matches: &ArgMatches) -> anyhow::Result<()> {
+    let (name, command) = matches
+        .subcommand()
+        .ok_or_else(|| anyhow!("No command given"))?;
+    match name {
+        "completions" => {
+            let shell = *command
+                .get_one::<Shell>("shell")
+                .ok_or_else(|| anyhow!("No shell given"))?;
+            clap_complete::generate(shell, &mut cli(), "buraqctl", &mut std::io::stdout());
+            Ok(())
+        }
+        "profiles" => run_profiles(command),
+        "apply" => apply(command),
+        name => {
+            let resource =
+                resources::find(name).ok_or_else(|| anyhow!("Unknown command `{}`", name))?;
+            let (action, arguments) = command
+                .subcommand()
+                .ok_or_else(|| anyhow!("No action given for {}", name))?;
+            let format: Format = string(arguments, "output")
+                .unwrap_or_else(|| "table".to_string())
+                .parse()?;
+            let client = Client::new(&profile(arguments)?)?;
+            run_action(client, resource, action, arguments, format)
+        }
+    }
+}
+
+fn run_action(
+    client: Client,
+    resource: &Resource,
+    action: &str,
+    arguments: &ArgMatches,
+    format: Format,
+) -> anyhow::Result<()> {
+    let id = || string(arguments, "id").unwrap_or_default();
+    let response = match action {
+        "list" => return list(&client, resource, arguments, format),
+        "get" => client.get(&resource.item_path(&id()), &[])?,
+        "create" => client.post(resource.path, &[], Some(&payload(arguments)?))?,
+        "update" => client.patch(&resource.item_path(&id()), &payload(arguments)?)?,
+        "delete" => {
+            let mut path = resource.item_path(&id());
+            if arguments.try_get_one::<bool>("cascade").ok().flatten() == Some(&true) {
+                path.push_str("?cascade=true");
+            }
+            match client.delete(&path)? {
+                Value::Null => {
+                    println!("Deleted {} {}", resource.name, id());
+                    return Ok(());
+                }
+                outcome => outcome,
+            }
+        }
+        "restore" => client.post(&resource.restore_path(&id()), &[], None)?,
+        "export" => {
+            let bundle = with_passphrase(client, arguments)?
+                .get(&format!("{}/export", resource.item_path(&id())), &[])?;
+            // A bundle is meant to be saved and imported, not read as a table
+            let format = match format {
+                Format::Table => Format::Json,
+                format => format,
+            };
+            print!("{}", output::render(&bundle, format, &[]));
+            return Ok(());
+        }
+        "import" => {
+            let bundle = match arguments.get_one::<PathBuf>("file") {
+                Some(path) => document(path)?,
+                None => bail!("No bundle given"),
+            };
+            let mut query = Vec::new();
+            if let Some(name) = string(arguments, "name") {
+                query.push(("name".to_string(), name));
+            }
+            if arguments.get_flag("dry-run") {
+                query.push(("dry_run".to_string(), "true".to_string()));
+            }
+            let report = with_passphrase(client, arguments)?.post(
+                &format!("{}/import", resource.path),
+                &query,
+                Some(&bundle),
+            )?;
+            match format {
+                Format::Table => print!("{}", output::import_report(&report)),
+                format => print!("{}", output::render(&report, format, &[])),
+            }
+            return Ok(());
+        }
+        _ => bail!("Unknown action `{}`", action),
+    };
+    print!("{}", output::render(&response, format, resource.columns));
+    Ok(())
+}
+
+fn list(
+    client: &Client,
+    resource: &Resource,
+    arguments: &ArgMatches,
+    format: Format,
+) -> anyhow::Result<()> {
+    let mut query = key_values(arguments, "filter")?
+        .into_iter()
+        .map(|(field, value)| (field.to_string(), value.to_string()))
+        .collect::<Vec<_>>();
+    for name in ["sort", "cursor"] {
+        if let Some(value) = string(arguments, name) {
+            query.push((name.to_string(), value));
+        }
+    }
+    for name in ["limit", "page"] {
+        if let Some(value) = arguments.get_one::<u32>(name) {
+            query.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    if !arguments.get_flag("all") {
+        let page = client.get(resource.path, &query)?;
+        print!("{}", output::render(&page, format, resource.columns));
+        if let (Format::Table, Some(cursor)) = (format, page["next_cursor"].as_str()) {
+            eprintln!("More records follow, list them with --cursor {}", cursor);
+        }
+        return Ok(());
+    }
+
+    let mut items = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut page_query = query.clone();
+        if let Some(cursor) = &cursor {
+            page_query.push(("cursor".to_string(), cursor.clone()));
+        }
+        let mut page = client.get(resource.path, &page_query)?;
+        match page["items"].take() {
+            Value::Array(page_items) => items.extend(page_items),
+            _ => bail!("The server did not return a page of records"),
+        }
+        match page["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+    print!(
+        "{}",
+        output::render(&Value::Array(items), format, resource.columns)
+    );
+    Ok(())
+}
+
+/// The client, sending the `--passphrase` of a bundle if one was given.
+fn with_passphrase(client: Client, arguments: &ArgMatches) -> anyhow::Result<Client> {
+    match string(arguments, "passphrase") {
+        Some(passphrase) => client.with_header("x-bundle-passphrase", &passphrase),
+        None => Ok(client),
+    }
+}
+
+/// Sends the `--file` manifest to the server's apply endpoint and shows the
+/// changes it planned or made.
+fn apply(arguments: &ArgMatches) -> anyhow::Result<()> {
+    let format: Format = string(arguments, "output")
+        .unwrap_or_else(|| "table".to_string())
+        .parse()?;
+    let client = Client::new(&profile(arguments)?)?;
+    let manifest = match arguments.get_one::<PathBuf>("file") {
+        Some(path) => document(path)?,
+        None => bail!("No manifest given"),
+    };
+
+    let mut query = Vec::new();
+    for flag in ["dry-run", "prune", "all"] {
+        if arguments.get_flag(flag) {
+            query.push((flag.replace('-', "_"), "true".to_string()));
+        }
+    }
+    if let Some(selector) = string(arguments, "selector") {
+        query.push(("selector".to_string(), selector));
+    }
+    let response = client.post("/apply", &query, Some(&manifest))?;
+    match format {
+        Format::Table => print!("{}", output::plan(&response)),
+        format => print!("{}", output::render(&response, format, &[])),
+    }
+    Ok(())
+}
+
+/// The request body of `create` and `update`: the `--file` payload, if any,
+/// with the `--set` fields applied over it.
+fn payload(arguments: &ArgMatches) -> anyhow::Result<Value> {
+    let mut payload = match arguments.get_one::<PathBuf>("file") {
+        Some(path) => document(path)?,
+        None => Value::Object(Map::new()),
+    };
+    for (field, value) in key_values(arguments, "set")? {
+        set_field(&mut payload, field, value)?;
+    }
+    Ok(payload)
+}
+
+/// Reads a JSON or YAML document from a file, or from standard input for `-`.
+fn document(path: &PathBuf) -> anyhow::Result<Value> {
+    let content = if path.as_os_str() == "-" {
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .context("Failed to read standard input")?;
+        content
+    } else {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+    };
+    // JSON is a subset of YAML, so one parser reads both
+    serde_norway::from_str(&content)
+        .with_context(|| format!("{} is neither valid JSON nor YAML", path.display()))
+}
+
+/// Sets a possibly dotted field, e.g. `labels.team`, creating the objects on
+/// the way. The value is parsed as JSON, and kept as a string otherwise.
+fn set_field(payload: &mut Value, field: &str, value: &str) -> anyhow::Result<()> {
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+    let mut target = payload;
+    let mut parts = field.split('.').peekable();
+    while let Some(part) = parts.next() {
+        let object = target
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("Cannot set `{}`: its parent is not an object", field))?;
+        if parts.peek().is_none() {
+            object.insert(part.to_string(), value);
+            return Ok(());
+        }
+        target = object
+            .entry(part)
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    Ok(())
+}
+
+fn key_values<'a>(
+    arguments: &'a ArgMatches,
+    name: &str,
+) -> anyhow::Result<Vec<(&'a str, &'a str)>> {
+    arguments
+        .get_many::<String>(name)
+        .into_iter()
+        .flatten()
+        .map(|given| {
+            given
+                .split_once('=')
+                .filter(|(field, _)| !field.is_empty())
+                .ok_or_else(|| anyhow!("Expected FIELD=VALUE, got `{}`", given))
+        })
+        .collect()
+}
+
+fn string(arguments: &ArgMatches, name: &str) -> Option<String> {
+    arguments.get_one::<String>(name).cloned()
+}
+
+fn profile_file(arguments: &ArgMatches) -> anyhow::Result<ProfileFile> {
+    let path = arguments
+        .get_one::<PathBuf>("config")
+        .cloned()
+        .or_else(ProfileFile::default_path)
+        .ok_or_else(|| anyhow!("Cannot locate the config file, set --config"))?;
+    ProfileFile::load(&path)
+}
+
+/// The selected profile with the connection options applied over it.
+fn profile(arguments: &ArgMatches) -> anyhow::Result<Profile> {
+    let file = profile_file(arguments)?;
+    let mut profile = file.select(string(arguments, "profile").as_deref())?;
+    for (setting, value) in connection_options(arguments, false) {
+        profile.set(setting, &value)?;
+    }
+    Ok(profile)
+}
+
+/// Connection options given, as profile settings. With `command_line_only`,
+/// values taken from the environment are left out.
+fn connection_options(
+    arguments: &ArgMatches,
+    command_line_only: bool,
+) -> Vec<(&'static str, String)> {
+    SETTINGS
+        .iter()
+        .filter_map(|setting| {
+            let name = setting.replace('_', "-");
+            if command_line_only && arguments.value_source(&name) != Some(ValueSource::CommandLine)
+            {
+                return None;
+            }
+            string(arguments, &name).map(|value| (*setting, value))
+        })
+        .collect()
+}
+
+fn run_profiles(matches: &ArgMatches) -> anyhow::Result<()> {
+    let (action, arguments) = matches
+        .subcommand()
+        .ok_or_else(|| anyhow!("No action given for profiles"))?;
+    let mut file = profile_file(arguments)?;
+    match action {
+        "list" => {
+            let default = file.default_profile().to_string();
+            let mut profiles = Vec::new();
+            for name in file.names() {
+                let profile = file.get(&name)?.unwrap_or_default();
+                profiles.push(serde_json::json!({
+                    "name": name,
+                    "url": profile.url,
+                    "default": name == default,
+                }));
+            }
+            let format: Format = string(arguments, "output")
+                .unwrap_or_else(|| "table".to_string())
+                .parse()?;
+            print!(
+                "{}",
+                output::render(&Value::Array(profiles), format, PROFILE_COLUMNS)
+            );
+        }
+        "set" => {
+            let name = string(arguments, "name").unwrap_or_default();
+            let settings = connection_options(arguments, true);
+            if settings.is_empty() && !arguments.get_flag("default") {
+                bail!("Give the settings to save, e.g. --url https://buraq.internal:8443");
+            }
+            file.update(&name, &settings)?;
+            if arguments.get_flag("default") {
+                file.set_default_profile(&name);
+            }
+            file.save()?;
+            println!("Saved profile {} to {}", name, file.path().display());
+        }
+        "use" => {
+            let name = string(arguments, "name").unwrap_or_default();
+            if file.get(&name)?.is_none() {
+                bail!("No profile `{}` in {}", name, file.path().display());
+            }
+            file.set_default_profile(&name);
+            file.save()?;
+            println!("Default profile is now {}", name);
+        }
+        _ => bail!("Unknown action `{}`", action),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> ArgMatches {
+        cli().try_get_matches_from(args).unwrap()
+    }
+
+    fn leaf(matches: &ArgMatches) -> &ArgMatches {
+        match matches.subcommand() {
+            Some((_, matches)) => leaf(matches),
+            None => matches,
+        }
+    }
+
+    #[test]
+    fn test_cli() {
+        cli().debug_assert();
+
+        let matches = parse(&[
+            "buraqctl",
+            "service-accounts",
+            "list",
+            "--filter",
+            "enabled=true",
+            "-o",
+            "json",
+            "--url",
+            "https://buraq.internal:8443",
+        ]);
+        let list = leaf(&matches);
+        assert_eq!(key_values(list, "filter").unwrap(), [("enabled", "true")]);
+        assert_eq!(string(list, "output").as_deref(), Some("json"));
+        assert_eq!(
+            connection_options(list, true),
+            [("url", "https://buraq.internal:8443".to_string())]
+        );
+
+        for args in [
+            &["buraqctl", "projects", "create"][..],
+            &["buraqctl", "projects", "list", "--all", "--page", "2"],
+            &["buraqctl", "projects", "get", "1", "-o", "xml"],
+            &["buraqctl", "environments", "delete", "1", "--cascade"],
+            &["buraqctl", "apply", "--dry-run"],
+            &["buraqctl", "apply", "-f", "-", "--prune"],
+            &["buraqctl", "apply", "-f", "-", "--all"],
+            &[
+                "buraqctl",
+                "apply",
+                "-f",
+                "-",
+                "--prune",
+                "--all",
+                "--selector",
+                "team=payments",
+            ],
+            &["buraqctl", "environments", "export", "1"],
+            &["buraqctl", "projects", "import", "--name", "billing"],
+            &[
+                "buraqctl",
+                "apply",
+                "-f",
+                "-",
+                "--selector",
+                "team=payments",
+            ],
+        ] {
+            assert!(cli().try_get_matches_from(args).is_err(), "{:?}", args);
+        }
+        assert!(
+            leaf(&parse(&[
+                "buraqctl",
+                "projects",
+                "delete",
+                "1",
+                "--cascade"
+            ]))
+            .get_flag("cascade")
+        );
+        let apply = parse(&["buraqctl", "apply", "-f", "-", "--prune", "--all"]);
+        assert!(leaf(&apply).get_flag("all"));
+        let export = parse(&["buraqctl", "projects", "export", "1", "--passphrase", "pw"]);
+        assert_eq!(string(leaf(&export), "passphrase").as_deref(), Some("pw"));
+    }
+
+    #[test]
+    fn test_payload_from_set() {
+        let matches = parse(&[
+            "buraqctl",
+            "projects",
+            "create",
+            "--set",
+            "name=billing",
+            "--set",
+            "enabled=false",
+            "--set",
+            "labels.team=payments",
+            "--set",
+            "labels.tier=1",
+        ]);
+        assert_eq!(
+            payload(leaf(&matches)).unwrap(),
+            serde_json::json!({
+                "name": "billing",
+                "enabled": false,
+                "labels": { "team": "payments", "tier": 1 },
+            })
+        );
+
+        let mut payload = serde_json::json!({ "name": "billing" });
+        assert!(set_field(&mut payload, "name.first", "x").is_err());
+        let matches = parse(&["buraqctl", "projects", "create", "--set", "=x"]);
+        assert!(key_values(leaf(&matches), "set").is_err());
+    }
+
+    #[test]
+    fn test_document_is_json_or_yaml() {
+        let dir = std::env::temp_dir().join(format!("buraqctl-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let expected = serde_json::json!({
+            "projects": [{ "name": "billing", "labels": { "team": "payments" } }]
+        });
+
+        let json = dir.join("manifest.json");
+        std::fs::write(&json, serde_json::to_string_pretty(&expected).unwrap()).unwrap();
+        assert_eq!(document(&json).unwrap(), expected);
+
+        let yaml = dir.join("manifest.yaml");
+        std::fs::write(
+            &yaml,
+            "projects:\n- name: billing\n  labels:\n    team: payments\n",
+        )
+        .unwrap();
+        assert_eq!(document(&yaml).unwrap(), expected);
+
+        std::fs::write(&yaml, "projects: [\n").unwrap();
+        assert!(document(&yaml).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,348 @@
+use crate::resources::Column;
+use serde_json::Value;
+
+/// Output formats, as accepted by `--output`.
+pub const FORMATS: &[&str] = &["table", "json", "yaml"];
+
+/// How results are written to standard output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Aligned columns, one row per record
+    Table,
+    /// Pretty-printed JSON, as returned by the server
+    Json,
+    /// YAML, as also accepted by `-f`
+    Yaml,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "yaml" => Ok(Self::Yaml),
+            _ => Err(anyhow::anyhow!("Unknown output format `{}`", s)),
+        }
+    }
+}
+
+/// Renders a server response, ending with a newline.
+///
+/// In table format, a page or an array is shown one record per row and a
+/// record as a single row. Other objects, such as deletion outcomes, are
+/// shown as `field: value` lines.
+pub fn render(value: &Value, format: Format, columns: &[Column]) -> String {
+    match format {
+        Format::Json => {
+            let mut json = serde_json::to_string_pretty(value).unwrap_or_default();
+            json.push('\n');
+            json
+        }
+        Format::Yaml => serde_norway::to_string(value).unwrap_or_default(),
+        Format::Table => {
+            let records = match value {
+                Value::Array(records) => Some(records.as_slice()),
+                Value::Object(object) => match object.get("items") {
+                    Some(Value::Array(records)) => Some(records.as_slice()),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match records {
+                Some([]) => "No resources found.\n".to_string(),
+                Some(records) => table(records, columns),
+                None if is_record(value, columns) => table(std::slice::from_ref(value), columns),
+                None => fields(value),
+            }
+        }
+    }
+}
+
+fn is_record(value: &Value, columns: &[Column]) -> bool {
+    columns
+        .first()
+        .is_some_and(|id| value.get(id.field).is_some())
+}
+
+fn table(records: &[Value], columns: &[Column]) -> String {
+    let rows: Vec<Vec<String>> = records
+        .iter()
+        .map(|record| {
+            columns
+                .iter()
+                .map(|column| cell(record.get(column.field).unwrap_or(&Value::Null)))
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([column.header.len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let headers = columns.iter().map(|column| column.header.to_string());
+    let mut output = String::new();
+    for row in std::iter::once(headers.collect::<Vec<_>>()).chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("   ");
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+fn fields(value: &Value) -> String {
+    match value {
+        Value::Object(object) => object
+            .iter()
+            .map(|(field, value)| format!("{}: {}\n", field, cell(value)))
+            .collect(),
+        value => format!("{}\n", cell(value)),
+    }
+}
+
+/// Renders the response of an apply in table format: one line per change,
+/// marked `+` for creations, `~` for updates and `-` for deletions, followed
+/// by the fields that change and a summary.
+pub fn plan(response: &Value) -> String {
+    let changes = response["changes"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let mut output = String::new();
+    let mut counts = [0; 3];
+    for change in changes {
+        let action = change["action"].as_str().unwrap_or_default();
+        let (mark, count) = match action {
+            "create" => ('+', &mut counts[0]),
+            "update" => ('~', &mut counts[1]),
+            _ => ('-', &mut counts[2]),
+        };
+        *count += 1;
+        output.push_str(&format!(
+            "{} {} {}\n",
+            mark,
+            change["kind"]
+                .as_str()
+                .unwrap_or_default()
+                .replace('_', " "),
+            change["name"].as_str().unwrap_or_default()
+        ));
+        for field in change["diff"].as_array().into_iter().flatten() {
+            let name = field["field"].as_str().unwrap_or_default();
+            let line = match action {
+                "create" => format!("    {}: {}\n", name, field["to"]),
+                _ => format!("    {}: {} -> {}\n", name, field["from"], field["to"]),
+            };
+            output.push_str(&line);
+        }
+    }
+
+    if changes.is_empty() {
+        output.push_str("No changes.\n");
+    } else {
+        let [create, update, delete] = counts;
+        output.push_str(&if response["applied"] == true {
+            format!("Applied: {create} created, {update} updated, {delete} deleted.\n")
+        } else {
+            format!("Plan: {create} to create, {update} to update, {delete} to delete.\n")
+        });
+    }
+    output.push_str(&generated_secrets(response));
+    output
+}
+
+/// Renders the report of a bundle import in table format.
+pub fn import_report(report: &Value) -> String {
+    let mut output = match report["project_id"].as_str() {
+        Some(id) if report["imported"] == true => format!("Imported project {}\n", id),
+        _ => "No conflicts; nothing was imported.\n".to_string(),
+    };
+    for user in report["reused_service_accounts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+    {
+        output.push_str(&format!(
+            "Using the existing service account {}\n",
+            user.as_str().unwrap_or_default()
+        ));
+    }
+    output.push_str(&generated_secrets(report));
+    output
+}
+
+/// Lines showing the secrets the server generated for new service accounts.
+fn generated_secrets(response: &Value) -> String {
+    response["generated_secrets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|generated| {
+            format!(
+                "Secret of service account {}: {} (shown only once)\n",
+                generated["service_account"].as_str().unwrap_or_default(),
+                generated["secret"].as_str().unwrap_or_default()
+            )
+        })
+        .collect()
+}
+
+/// A value as shown in a table cell.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => "<none>".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Array(values) if values.is_empty() => "<none>".to_string(),
+        Value::Array(values) => values.iter().map(cell).collect::<Vec<_>>().join(","),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::find;
+    use serde_json::json;
+
+    fn project(id: &str, name: &str) -> Value {
+        json!({
+            "_id": id,
+            "name": name,
+            "description": "",
+            "enabled": true,
+            "labels": {},
+            "created_at": null,
+        })
+    }
+
+    #[test]
+    fn test_table() {
+        let columns = find("projects").unwrap().columns;
+        let page = json!({
+            "items": [project("1", "billing"), project("2", "ledger service")],
+            "total": 2,
+            "page": 1,
+            "limit": 10,
+            "next_cursor": null,
+        });
+        assert_eq!(
+            render(&page, Format::Table, columns),
+            "ID   NAME             ENABLED   DESCRIPTION   CREATED\n\
+             1    billing          true                    <none>\n\
+             2    ledger service   true                    <none>\n"
+        );
+        assert_eq!(
+            render(&project("1", "billing"), Format::Table, columns)
+                .lines()
+                .count(),
+            2
+        );
+        assert_eq!(
+            render(&json!({ "items": [] }), Format::Table, columns),
+            "No resources found.\n"
+        );
+        assert_eq!(
+            render(
+                &json!({ "environments": ["1", "2"], "project_scopes": [] }),
+                Format::Table,
+                columns
+            ),
+            "environments: 1,2\nproject_scopes: <none>\n"
+        );
+    }
+
+    #[test]
+    fn test_plan() {
+        let response = json!({
+            "applied": false,
+            "changes": [
+                {
+                    "action": "create",
+                    "kind": "service_account",
+                    "name": "billing-worker",
+                    "diff": [{"field": "user", "from": null, "to": "billing-worker"}]
+                },
+                {
+                    "action": "update",
+                    "kind": "environment",
+                    "name": "billing/production",
+                    "id": "5f0c…",
+                    "diff": [{"field": "enabled", "from": true, "to": false}]
+                },
+                {"action": "delete", "kind": "project", "name": "legacy", "id": "7a1d…"}
+            ]
+        });
+        assert_eq!(
+            plan(&response),
+            "+ service account billing-worker\n    user: \"billing-worker\"\n\
+             ~ environment billing/production\n    enabled: true -> false\n\
+             - project legacy\n\
+             Plan: 1 to create, 1 to update, 1 to delete.\n"
+        );
+
+        let response = json!({
+            "applied": true,
+            "changes": [],
+            "generated_secrets": [{"service_account": "billing-worker", "secret": "s3cr3t"}]
+        });
+        assert_eq!(
+            plan(&response),
+            "No changes.\nSecret of service account billing-worker: s3cr3t (shown only once)\n"
+        );
+    }
+
+    #[test]
+    fn test_import_report() {
+        let report = json!({
+            "imported": true,
+            "project_id": "0b6c…",
+            "reused_service_accounts": ["billing-worker"],
+            "generated_secrets": [{"service_account": "billing-reader", "secret": "s3cr3t"}]
+        });
+        assert_eq!(
+            import_report(&report),
+            "Imported project 0b6c…\n\
+             Using the existing service account billing-worker\n\
+             Secret of service account billing-reader: s3cr3t (shown only once)\n"
+        );
+        assert_eq!(
+            import_report(&json!({"imported": false})),
+            "No conflicts; nothing was imported.\n"
+        );
+    }
+
+    #[test]
+    fn test_yaml() {
+        let value = json!({
+            "_id": "0b6ef0e5-2d0c-4f0e-9d7e-0c1c5c4c0b1a",
+            "name": "billing",
+            "description": "Bills: monthly",
+            "enabled": true,
+            "labels": { "team": "payments", "tier": "1" },
+            "scopes": ["read", "write"],
+            "bindings": [{ "kind": "san_dns", "value": "billing.internal" }],
+            "deleted_at": null,
+            "tags": [],
+            "version": "yes",
+        });
+        let rendered = render(&value, Format::Yaml, &[]);
+        assert!(rendered.contains("\nname: billing\n"));
+        assert!(rendered.contains("\nlabels:\n  team: payments\n"));
+        // Strings YAML would read as other types stay strings
+        let parsed: Value = serde_norway::from_str(&rendered).unwrap();
+        assert_eq!(parsed, value);
+    }
+}
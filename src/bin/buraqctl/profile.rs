@@ -0,0 +1,319 @@
+use anyhow::{Context, anyhow, bail};
+use std::env;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table, value};
+
+/// URL used when no profile names a server.
+pub const DEFAULT_URL: &str = "http://127.0.0.1:8080";
+
+/// Profile used when none is selected and the config file names no default.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Where buraqctl finds a Buraq server and the credentials it presents.
+///
+/// # Fields
+/// - `url`: Base URL of the server, e.g. `https://buraq.internal:8443`
+/// - `token`: Sent as a bearer token, for servers behind an authenticating proxy
+/// - `actor`: Sent as `X-Actor`, recorded on deletions
+/// - `ca_cert`: PEM file of the CAs the server certificate is checked against
+/// - `client_cert`, `client_key`: PEM client certificate and key for mTLS
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub url: String,
+    pub token: Option<String>,
+    pub actor: Option<String>,
+    pub ca_cert: Option<PathBuf>,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            url: DEFAULT_URL.to_string(),
+            token: None,
+            actor: None,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+        }
+    }
+}
+
+/// Settings of a profile, in the order they are written to the config file.
+pub const SETTINGS: &[&str] = &[
+    "url",
+    "token",
+    "actor",
+    "ca_cert",
+    "client_cert",
+    "client_key",
+];
+
+impl Profile {
+    fn from_table(name: &str, table: &Table) -> anyhow::Result<Self> {
+        if let Some((key, _)) = table.iter().find(|(key, _)| !SETTINGS.contains(key)) {
+            bail!("Unknown setting `{}` in profile `{}`", key, name);
+        }
+        let string = |key: &str| -> anyhow::Result<Option<String>> {
+            match table.get(key) {
+                None => Ok(None),
+                Some(item) => item
+                    .as_str()
+                    .map(|value| Some(value.to_string()))
+                    .ok_or_else(|| anyhow!("`{}` of profile `{}` must be a string", key, name)),
+            }
+        };
+        Ok(Self {
+            url: string("url")?.unwrap_or_else(|| DEFAULT_URL.to_string()),
+            token: string("token")?,
+            actor: string("actor")?,
+            ca_cert: string("ca_cert")?.map(PathBuf::from),
+            client_cert: string("client_cert")?.map(PathBuf::from),
+            client_key: string("client_key")?.map(PathBuf::from),
+        })
+    }
+
+    /// Sets one of [`SETTINGS`] from its string form.
+    pub fn set(&mut self, setting: &str, given: &str) -> anyhow::Result<()> {
+        match setting {
+            "url" => self.url = given.to_string(),
+            "token" => self.token = Some(given.to_string()),
+            "actor" => self.actor = Some(given.to_string()),
+            "ca_cert" => self.ca_cert = Some(PathBuf::from(given)),
+            "client_cert" => self.client_cert = Some(PathBuf::from(given)),
+            "client_key" => self.client_key = Some(PathBuf::from(given)),
+            _ => bail!("Unknown profile setting `{}`", setting),
+        }
+        Ok(())
+    }
+}
+
+/// The buraqctl config file: named profiles and which one is the default.
+///
+/// ```toml
+/// default_profile = "production"
+///
+/// [profiles.production]
+/// url = "https://buraq.internal:8443"
+/// actor = "oncall"
+/// ca_cert = "/etc/buraq/ca.pem"
+/// ```
+pub struct ProfileFile {
+    path: PathBuf,
+    document: DocumentMut,
+}
+
+impl ProfileFile {
+    /// Location of the config file: `BURAQCTL_CONFIG`, or `buraq/buraqctl.toml`
+    /// under `XDG_CONFIG_HOME` or `~/.config`.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Ok(path) = env::var("BURAQCTL_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_home.join("buraq").join("buraqctl.toml"))
+    }
+
+    /// Reads the config file; a missing file has no profiles.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let document = match std::fs::read_to_string(path) {
+            Ok(content) => content
+                .parse::<DocumentMut>()
+                .with_context(|| format!("Failed to parse {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => DocumentMut::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            document,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Name of the default profile.
+    pub fn default_profile(&self) -> &str {
+        self.document
+            .get("default_profile")
+            .and_then(Item::as_str)
+            .unwrap_or(DEFAULT_PROFILE)
+    }
+
+    /// Names of the profiles, in file order.
+    pub fn names(&self) -> Vec<String> {
+        self.document
+            .get("profiles")
+            .and_then(Item::as_table)
+            .map(|profiles| profiles.iter().map(|(name, _)| name.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    /// The profile called `name`, if the file has one.
+    pub fn get(&self, name: &str) -> anyhow::Result<Option<Profile>> {
+        match self
+            .document
+            .get("profiles")
+            .and_then(|profiles| profiles.get(name))
+        {
+            None => Ok(None),
+            Some(item) => {
+                let table = item
+                    .as_table()
+                    .ok_or_else(|| anyhow!("Profile `{}` must be a table", name))?;
+                Profile::from_table(name, table).map(Some)
+            }
+        }
+    }
+
+    /// Selects a profile: `requested` if given, else the default one.
+    ///
+    /// A requested profile must exist; a missing default profile falls back
+    /// to [`Profile::default`], so buraqctl works against a local server
+    /// without any configuration.
+    pub fn select(&self, requested: Option<&str>) -> anyhow::Result<Profile> {
+        let name = requested.unwrap_or_else(|| self.default_profile());
+        match (self.get(name)?, requested) {
+            (Some(profile), _) => Ok(profile),
+            (None, Some(_)) => bail!("No profile `{}` in {}", name, self.path.display()),
+            (None, None) => Ok(Profile::default()),
+        }
+    }
+
+    /// Creates or updates profile `name` with the given settings, keeping the
+    /// rest of the file, comments included, as it was.
+    pub fn update(&mut self, name: &str, settings: &[(&str, String)]) -> anyhow::Result<()> {
+        if let Some((setting, _)) = settings
+            .iter()
+            .find(|(setting, _)| !SETTINGS.contains(setting))
+        {
+            bail!("Unknown profile setting `{}`", setting);
+        }
+        let profiles = self
+            .document
+            .entry("profiles")
+            .or_insert_with(|| {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            })
+            .as_table_mut()
+            .ok_or_else(|| anyhow!("`profiles` must be a table"))?;
+        let profile = profiles
+            .entry(name)
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| anyhow!("Profile `{}` must be a table", name))?;
+        for (setting, given) in settings {
+            profile.insert(setting, value(given.as_str()));
+        }
+        Ok(())
+    }
+
+    pub fn set_default_profile(&mut self, name: &str) {
+        self.document.insert("default_profile", value(name));
+    }
+
+    /// Writes the file, readable by its owner only since it may hold tokens.
+    pub fn save(&self) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options
+            .open(&self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        std::io::Write::write_all(&mut file, self.document.to_string().as_bytes())
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(content: &str) -> ProfileFile {
+        ProfileFile {
+            path: PathBuf::from("buraqctl.toml"),
+            document: content.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_select_profile() {
+        let profiles = file(
+            r#"
+            default_profile = "production"
+
+            [profiles.local]
+            url = "http://127.0.0.1:8080"
+
+            [profiles.production]
+            url = "https://buraq.internal:8443"
+            actor = "oncall"
+            ca_cert = "/etc/buraq/ca.pem"
+            "#,
+        );
+
+        assert_eq!(profiles.names(), ["local", "production"]);
+        let production = profiles.select(None).unwrap();
+        assert_eq!(production.url, "https://buraq.internal:8443");
+        assert_eq!(production.actor.as_deref(), Some("oncall"));
+        assert_eq!(production.ca_cert, Some(PathBuf::from("/etc/buraq/ca.pem")));
+        assert_eq!(production.token, None);
+        assert_eq!(
+            profiles.select(Some("local")).unwrap().url,
+            "http://127.0.0.1:8080"
+        );
+        assert!(profiles.select(Some("staging")).is_err());
+    }
+
+    #[test]
+    fn test_missing_default_profile_targets_local_server() {
+        assert_eq!(file("").select(None).unwrap(), Profile::default());
+    }
+
+    #[test]
+    fn test_unknown_setting_is_refused() {
+        let profiles = file("[profiles.default]\nurll = \"http://localhost\"");
+        assert!(profiles.select(None).is_err());
+    }
+
+    #[test]
+    fn test_update_keeps_the_rest_of_the_file() {
+        let mut profiles =
+            file("# Managed by hand\n[profiles.local]\nurl = \"http://127.0.0.1:8080\"\n");
+        profiles
+            .update(
+                "production",
+                &[("url", "https://buraq.internal:8443".to_string())],
+            )
+            .unwrap();
+        profiles.set_default_profile("production");
+
+        let content = profiles.document.to_string();
+        assert!(
+            content.contains("# Managed by hand\n[profiles.local]"),
+            "{}",
+            content
+        );
+        assert_eq!(profiles.default_profile(), "production");
+        assert_eq!(
+            profiles.select(None).unwrap().url,
+            "https://buraq.internal:8443"
+        );
+        assert!(
+            profiles
+                .update("production", &[("password", String::new())])
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,192 @@
+/// A column of table output: its header and the JSON field it shows.
+#[derive(Debug, Clone, Copy)]
+pub struct Column {
+    pub header: &'static str,
+    pub field: &'static str,
+}
+
+const fn column(header: &'static str, field: &'static str) -> Column {
+    Column { header, field }
+}
+
+/// A resource of the Buraq API and how buraqctl presents it.
+///
+/// # Fields
+/// - `name`: Subcommand name, e.g. `service-accounts`
+/// - `path`: Collection path on the server, e.g. `/service-accounts`
+/// - `about`: One-line help
+/// - `columns`: Columns of table output, the record id first
+/// - `cascade`: Whether deletion can take dependents along
+/// - `bundles`: Whether records can be exported and imported as bundles
+#[derive(Debug)]
+pub struct Resource {
+    pub name: &'static str,
+    pub path: &'static str,
+    pub about: &'static str,
+    pub columns: &'static [Column],
+    pub cascade: bool,
+    pub bundles: bool,
+}
+
+impl Resource {
+    /// Path of a single record.
+    pub fn item_path(&self, id: &str) -> String {
+        format!("{}/{}", self.path, id)
+    }
+
+    /// Path restoring a soft-deleted record.
+    pub fn restore_path(&self, id: &str) -> String {
+        format!("{}/{}:restore", self.path, id)
+    }
+}
+
+/// Every resource buraqctl manages, in the order `--help` lists them.
+pub const RESOURCES: &[Resource] = &[
+    Resource {
+        name: "projects",
+        path: "/projects",
+        about: "Manage projects",
+        columns: &[
+            column("ID", "_id"),
+            column("NAME", "name"),
+            column("ENABLED", "enabled"),
+            column("DESCRIPTION", "description"),
+            column("CREATED", "created_at"),
+        ],
+        cascade: true,
+        bundles: true,
+    },
+    Resource {
+        name: "environments",
+        path: "/environments",
+        about: "Manage the environments of projects",
+        columns: &[
+            column("ID", "_id"),
+            column("PROJECT", "project_id"),
+            column("NAME", "name"),
+            column("ENABLED", "enabled"),
+            column("CREATED", "created_at"),
+        ],
+        cascade: false,
+        bundles: false,
+    },
+    Resource {
+        name: "scopes",
+        path: "/project-scopes",
+        about: "Manage the scopes projects define",
+        columns: &[
+            column("ID", "_id"),
+            column("PROJECT", "project_id"),
+            column("NAME", "name"),
+            column("ENABLED", "enabled"),
+            column("CREATED", "created_at"),
+        ],
+        cascade: false,
+        bundles: false,
+    },
+    Resource {
+        name: "templates",
+        path: "/project-templates",
+        about: "Manage the templates new projects are scaffolded from",
+        columns: &[
+            column("ID", "_id"),
+            column("NAME", "name"),
+            column("ALGORITHM", "server_key_algorithm"),
+            column("DESCRIPTION", "description"),
+            column("CREATED", "created_at"),
+        ],
+        cascade: false,
+        bundles: false,
+    },
+    Resource {
+        name: "service-accounts",
+        path: "/service-accounts",
+        about: "Manage service accounts",
+        columns: &[
+            column("ID", "_id"),
+            column("USER", "user"),
+            column("EMAIL", "email"),
+            column("ENABLED", "enabled"),
+            column("LOCKED UNTIL", "locked_until"),
+        ],
+        cascade: false,
+        bundles: false,
+    },
+    Resource {
+        name: "keys",
+        path: "/service_account_keys",
+        about: "Manage the keys of service accounts",
+        columns: &[
+            column("ID", "_id"),
+            column("SERVICE ACCOUNT", "service_account_id"),
+            column("ALGORITHM", "algorithm"),
+            column("ENABLED", "enabled"),
+            column("EXPIRES", "expires_at"),
+        ],
+        cascade: false,
+        bundles: false,
+    },
+    Resource {
+        name: "access",
+        path: "/project-access",
+        about: "Manage the access service accounts have to environments",
+        columns: &[
+            column("ID", "_id"),
+            column("NAME", "name"),
+            column("ENVIRONMENT", "environment_id"),
+            column("SERVICE ACCOUNT", "service_account_id"),
+            column("ENABLED", "enabled"),
+        ],
+        cascade: false,
+        bundles: false,
+    },
+    Resource {
+        name: "tokens",
+        path: "/access-tokens",
+        about: "Manage access tokens",
+        columns: &[
+            column("ID", "_id"),
+            column("PROJECT ACCESS", "project_access_id"),
+            column("ALGORITHM", "algorithm"),
+            column("ENABLED", "enabled"),
+            column("EXPIRES", "expires_at"),
+        ],
+        cascade: false,
+        bundles: false,
+    },
+    Resource {
+        name: "server-keys",
+        path: "/server-keys",
+        about: "Manage the keys environments sign tokens with",
+        columns: &[
+            column("ID", "id"),
+            column("ENVIRONMENT", "environment_id"),
+            column("ALGORITHM", "algorithm"),
+            column("CREATED", "created_at"),
+        ],
+        cascade: false,
+        bundles: false,
+    },
+];
+
+/// The resource managed by subcommand `name`.
+pub fn find(name: &str) -> Option<&'static Resource> {
+    RESOURCES.iter().find(|resource| resource.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resource_paths() {
+        let keys = find("keys").unwrap();
+        assert_eq!(keys.item_path("42"), "/service_account_keys/42");
+        assert_eq!(keys.restore_path("42"), "/service_account_keys/42:restore");
+        assert!(find("users").is_none());
+
+        for resource in RESOURCES {
+            assert_eq!(resource.columns[0].header, "ID");
+        }
+    }
+}
@@ -1,7 +1,398 @@
+use crate::errors::AppError;
+use crate::repositories::registry::Repositories;
+#[cfg(feature = "sqlite")]
+use crate::repositories::sqlite::SqliteDatabase;
+use crate::utils::security::MasterKeySource;
+use chrono::{DateTime, Utc};
 use dotenvy;
+use jsonwebtoken::Algorithm;
 use mongodb;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
+use toml_edit::{DocumentMut, Item};
+
+/// Default number of days soft-deleted records are retained.
+pub const DEFAULT_SOFT_DELETE_RETENTION_DAYS: i64 = 30;
+/// Longest retention accepted for soft-deleted records (one hundred years).
+pub const MAX_SOFT_DELETE_RETENTION_DAYS: i64 = 36500;
+/// Default interval between purge runs (one hour).
+pub const DEFAULT_PURGE_INTERVAL_SECONDS: u64 = 3600;
+
+/// Longest lockout that can be configured (one year).
+pub const MAX_LOCKOUT_SECONDS: u64 = 365 * 24 * 3600;
+
+/// Environment variable naming the TOML config file to read.
+pub const CONFIG_FILE_ENV: &str = "BURAQ_CONFIG";
+
+/// Limits applied to authentication attempts to slow down brute-forcing of
+/// service account secrets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitConfig {
+    /// Length, in seconds, of the fixed window attempts are counted in.
+    pub window_seconds: u64,
+    /// Attempts allowed per client id within one window; `0` disables the limit.
+    pub per_client: u32,
+    /// Attempts allowed per source IP within one window; `0` disables the limit.
+    pub per_ip: u32,
+    /// Consecutive failures after which a service account is locked; `0`
+    /// disables lockouts.
+    pub lockout_threshold: u32,
+    /// Length, in seconds, of the first lockout; each further failure doubles it.
+    pub lockout_base_seconds: u64,
+    /// Longest lockout, in seconds.
+    pub lockout_max_seconds: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            window_seconds: 60,
+            per_client: 10,
+            per_ip: 100,
+            lockout_threshold: 5,
+            lockout_base_seconds: 30,
+            lockout_max_seconds: 3600,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// How long an account is locked after `failures` consecutive failed
+    /// authentications, or `None` while it is still under the threshold.
+    pub fn lockout_for(&self, failures: u32) -> Option<Duration> {
+        if self.lockout_threshold == 0 || failures < self.lockout_threshold {
+            return None;
+        }
+        let doublings = (failures - self.lockout_threshold).min(32);
+        let seconds = self
+            .lockout_base_seconds
+            .saturating_mul(1u64 << doublings)
+            .min(self.lockout_max_seconds);
+        Some(Duration::from_secs(seconds))
+    }
+
+    fn load(layers: &Layers) -> Result<Self, anyhow::Error> {
+        let default = Self::default();
+        let config = Self {
+            window_seconds: layers.get_or("rate_limit.window_seconds", default.window_seconds)?,
+            per_client: layers.get_or("rate_limit.per_client", default.per_client)?,
+            per_ip: layers.get_or("rate_limit.per_ip", default.per_ip)?,
+            lockout_threshold: layers.get_or("lockout.threshold", default.lockout_threshold)?,
+            lockout_base_seconds: layers
+                .get_or("lockout.base_seconds", default.lockout_base_seconds)?,
+            lockout_max_seconds: layers
+                .get_or("lockout.max_seconds", default.lockout_max_seconds)?,
+        };
+        if config.lockout_max_seconds > MAX_LOCKOUT_SECONDS {
+            return Err(invalid(
+                "lockout.max_seconds",
+                &format!("must be at most {}", MAX_LOCKOUT_SECONDS),
+            ));
+        }
+        Ok(config)
+    }
+}
+
+/// HTTP server tuning.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    /// Number of worker threads.
+    pub workers: usize,
+    /// How long, in seconds, idle connections are kept open.
+    pub keep_alive_seconds: u64,
+    /// How long, in seconds, in-flight requests get to finish on shutdown.
+    pub shutdown_timeout_seconds: u64,
+    /// How long, in seconds, a client gets to send the request head.
+    pub client_request_timeout_seconds: u64,
+    /// Largest JSON request body accepted, in bytes.
+    pub json_limit_bytes: usize,
+    /// Largest request body of any other type accepted, in bytes.
+    pub payload_limit_bytes: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            keep_alive_seconds: 75,
+            shutdown_timeout_seconds: 30,
+            client_request_timeout_seconds: 5,
+            json_limit_bytes: 2 * 1024 * 1024,
+            payload_limit_bytes: 256 * 1024,
+        }
+    }
+}
+
+impl ServerConfig {
+    fn load(layers: &Layers) -> Result<Self, anyhow::Error> {
+        let default = Self::default();
+        let config = Self {
+            workers: layers.get_or("server.workers", default.workers)?,
+            keep_alive_seconds: layers
+                .get_or("server.keep_alive_seconds", default.keep_alive_seconds)?,
+            shutdown_timeout_seconds: layers.get_or(
+                "server.shutdown_timeout_seconds",
+                default.shutdown_timeout_seconds,
+            )?,
+            client_request_timeout_seconds: layers.get_or(
+                "server.client_request_timeout_seconds",
+                default.client_request_timeout_seconds,
+            )?,
+            json_limit_bytes: layers.get_or("server.json_limit_bytes", default.json_limit_bytes)?,
+            payload_limit_bytes: layers
+                .get_or("server.payload_limit_bytes", default.payload_limit_bytes)?,
+        };
+        if config.workers == 0 {
+            return Err(invalid("server.workers", "must be at least 1"));
+        }
+        Ok(config)
+    }
+}
+
+/// Certificate and private key used to serve HTTPS.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsConfig {
+    /// PEM file with the server certificate, followed by any intermediates.
+    pub cert_path: PathBuf,
+    /// PEM file with the certificate's private key.
+    pub key_path: PathBuf,
+    /// PEM file with the CAs trusted to issue client certificates. When set,
+    /// clients may authenticate with a certificate (RFC 8705 `tls_client_auth`).
+    pub client_ca_path: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// HTTPS is enabled by setting both paths; setting only one is an error.
+    fn load(layers: &Layers) -> Result<Option<Self>, anyhow::Error> {
+        let cert_path: Option<PathBuf> = layers.get("tls.cert_path")?;
+        let key_path: Option<PathBuf> = layers.get("tls.key_path")?;
+        let client_ca_path: Option<PathBuf> = layers.get("tls.client_ca_path")?;
+        let (cert_path, key_path) = match (cert_path, key_path) {
+            (None, None) if client_ca_path.is_some() => {
+                return Err(invalid(
+                    "tls.client_ca_path",
+                    "requires tls.cert_path and tls.key_path",
+                ));
+            }
+            (None, None) => return Ok(None),
+            (Some(cert_path), Some(key_path)) => (cert_path, key_path),
+            (Some(_), None) => {
+                return Err(invalid("tls.key_path", "must be set when tls.cert_path is"));
+            }
+            (None, Some(_)) => {
+                return Err(invalid("tls.cert_path", "must be set when tls.key_path is"));
+            }
+        };
+        let paths = [
+            ("tls.cert_path", Some(&cert_path)),
+            ("tls.key_path", Some(&key_path)),
+            ("tls.client_ca_path", client_ca_path.as_ref()),
+        ];
+        for (key, path) in paths {
+            if let Some(path) = path
+                && !path.is_file()
+            {
+                return Err(invalid(key, &format!("{} does not exist", path.display())));
+            }
+        }
+        Ok(Some(Self {
+            cert_path,
+            key_path,
+            client_ca_path,
+        }))
+    }
+}
+
+/// Cross-origin requests allowed from browsers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API, or `*` for any. CORS is disabled when empty.
+    pub allowed_origins: Vec<String>,
+    /// Methods allowed in cross-origin requests.
+    pub allowed_methods: Vec<String>,
+    /// Request headers allowed in cross-origin requests.
+    pub allowed_headers: Vec<String>,
+    /// How long, in seconds, browsers may cache a preflight response.
+    pub max_age_seconds: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![],
+            allowed_methods: ["GET", "POST", "PUT", "PATCH", "DELETE"]
+                .map(String::from)
+                .to_vec(),
+            allowed_headers: [
+                "Authorization",
+                "Content-Type",
+                "DPoP",
+                "X-Actor",
+                "X-Bundle-Passphrase",
+                "X-Request-Id",
+            ]
+            .map(String::from)
+            .to_vec(),
+            max_age_seconds: 3600,
+        }
+    }
+}
+
+impl CorsConfig {
+    fn load(layers: &Layers) -> Result<Self, anyhow::Error> {
+        let default = Self::default();
+        let config = Self {
+            allowed_origins: layers.get_or("cors.allowed_origins", default.allowed_origins)?,
+            allowed_methods: layers.get_or("cors.allowed_methods", default.allowed_methods)?,
+            allowed_headers: layers.get_or("cors.allowed_headers", default.allowed_headers)?,
+            max_age_seconds: layers.get_or("cors.max_age_seconds", default.max_age_seconds)?,
+        };
+        for method in &config.allowed_methods {
+            if actix_web::http::Method::from_str(method).is_err() {
+                return Err(invalid(
+                    "cors.allowed_methods",
+                    &format!("{} is not an HTTP method", method),
+                ));
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Defaults applied to access tokens created without them, and how tokens
+/// are checked when presented.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenConfig {
+    /// Signing algorithm of tokens created without one.
+    pub default_algorithm: Algorithm,
+    /// Lifetime, in seconds, of tokens created without an expiry.
+    pub default_ttl_seconds: u64,
+    /// Longest lifetime, in seconds, a token may be created with.
+    pub max_ttl_seconds: u64,
+    /// How far, in seconds, the creation time of a DPoP proof may be from the
+    /// current time for the proof to be accepted.
+    pub dpop_max_age_seconds: u64,
+    /// Whether issued tokens carry the labels of their service account,
+    /// environment and project in the `meta` claim.
+    pub include_labels: bool,
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        Self {
+            default_algorithm: Algorithm::RS256,
+            default_ttl_seconds: 3600,
+            max_ttl_seconds: 365 * 24 * 3600,
+            dpop_max_age_seconds: 300,
+            include_labels: false,
+        }
+    }
+}
+
+impl TokenConfig {
+    /// Expiry of a token created at `now`: the requested one, or the default
+    /// lifetime when none was requested.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Validation` if the requested expiry is further away
+    /// than the longest lifetime allowed.
+    pub fn expires_at(
+        &self,
+        requested: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<DateTime<Utc>, crate::errors::AppError> {
+        let seconds = |value: u64| chrono::Duration::seconds(value.min(i64::MAX as u64) as i64);
+        match requested {
+            None => Ok(now + seconds(self.default_ttl_seconds)),
+            Some(expires_at) if expires_at > now + seconds(self.max_ttl_seconds) => {
+                Err(crate::errors::AppError::Validation(format!(
+                    "expires_at may be at most {} seconds away",
+                    self.max_ttl_seconds
+                )))
+            }
+            Some(expires_at) => Ok(expires_at),
+        }
+    }
+
+    fn load(layers: &Layers) -> Result<Self, anyhow::Error> {
+        let default = Self::default();
+        let config = Self {
+            default_algorithm: layers
+                .get_or("tokens.default_algorithm", default.default_algorithm)?,
+            default_ttl_seconds: layers
+                .get_or("tokens.default_ttl_seconds", default.default_ttl_seconds)?,
+            max_ttl_seconds: layers.get_or("tokens.max_ttl_seconds", default.max_ttl_seconds)?,
+            dpop_max_age_seconds: layers
+                .get_or("tokens.dpop_max_age_seconds", default.dpop_max_age_seconds)?,
+            include_labels: layers.get_or("tokens.include_labels", default.include_labels)?,
+        };
+        if config.default_ttl_seconds == 0 {
+            return Err(invalid("tokens.default_ttl_seconds", "must be at least 1"));
+        }
+        if config.default_ttl_seconds > config.max_ttl_seconds {
+            return Err(invalid(
+                "tokens.default_ttl_seconds",
+                "must not exceed tokens.max_ttl_seconds",
+            ));
+        }
+        Ok(config)
+    }
+}
+
+/// Reads the master key source from `secrets.master_key_source`, either
+/// `env` or `file`, the latter reading `secrets.master_key_file`.
+fn load_master_key_source(layers: &Layers) -> Result<MasterKeySource, anyhow::Error> {
+    let source: String = layers.get_or("secrets.master_key_source", "env".to_string())?;
+    let file: Option<PathBuf> = layers.get("secrets.master_key_file")?;
+    match (source.as_str(), file) {
+        ("env", _) => Ok(MasterKeySource::Env),
+        ("file", Some(file)) => Ok(MasterKeySource::File(file)),
+        ("file", None) => Err(invalid(
+            "secrets.master_key_file",
+            "must be set when secrets.master_key_source is file",
+        )),
+        (other, _) => Err(invalid(
+            "secrets.master_key_source",
+            &format!("expected env or file, got {}", other),
+        )),
+    }
+}
+
+/// Where records are stored.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Storage {
+    /// In the MongoDB database named by `database.uri` and `database.name`.
+    Mongo,
+    /// In the SQLite file at this path; needs a build with the `sqlite` feature.
+    Sqlite(PathBuf),
+}
+
+/// Reads the storage backend from `database.backend`, either `mongodb` or
+/// `sqlite`, the latter storing records in the file `database.path`.
+fn load_storage(layers: &Layers) -> Result<Storage, anyhow::Error> {
+    let backend: String = layers.get_or("database.backend", "mongodb".to_string())?;
+    let path: Option<PathBuf> = layers.get("database.path")?;
+    match (backend.as_str(), path) {
+        ("mongodb", _) => Ok(Storage::Mongo),
+        ("sqlite", _) if !cfg!(feature = "sqlite") => Err(invalid(
+            "database.backend",
+            "is sqlite but Buraq was built without the sqlite feature",
+        )),
+        ("sqlite", Some(path)) => Ok(Storage::Sqlite(path)),
+        ("sqlite", None) => Err(invalid(
+            "database.path",
+            "must be set when database.backend is sqlite",
+        )),
+        (other, _) => Err(invalid(
+            "database.backend",
+            &format!("expected mongodb or sqlite, got {}", other),
+        )),
+    }
+}
 
 /// Configuration for the application, including host, port, and database URI.
 #[derive(Debug, Clone)]
@@ -10,10 +401,31 @@ pub struct ApplicationConfig {
     pub host: String,
     /// The port number the application listens on.
     pub port: u16,
-    /// The URI for the application's database.
+    /// Where records are stored.
+    pub storage: Storage,
+    /// The URI for the application's database; empty when records are stored in SQLite.
     pub database_uri: String,
-    /// The name of the database.
+    /// The name of the database; empty when records are stored in SQLite.
     pub database_name: String,
+    /// How many days soft-deleted records are kept before being purged.
+    pub soft_delete_retention_days: i64,
+    /// How often, in seconds, the purge job runs.
+    pub purge_interval_seconds: u64,
+    /// OTLP/HTTP endpoint traces are exported to, e.g. `http://localhost:4318/v1/traces`.
+    /// Only used when built with the `otel` feature; no traces are exported when unset.
+    pub otlp_endpoint: Option<String>,
+    /// Rate limits and lockouts on authentication attempts.
+    pub rate_limit: RateLimitConfig,
+    /// HTTP server tuning.
+    pub server: ServerConfig,
+    /// HTTPS certificate and key; plain HTTP is served when unset.
+    pub tls: Option<TlsConfig>,
+    /// Cross-origin requests allowed from browsers.
+    pub cors: CorsConfig,
+    /// Defaults for newly created access tokens.
+    pub tokens: TokenConfig,
+    /// Where the secrets manager reads the master key from.
+    pub master_key_source: MasterKeySource,
 }
 
 /// Wrapper for application configuration.
@@ -23,7 +435,7 @@ pub struct AppConfig {
     pub application: ApplicationConfig,
 }
 
-/// Holds the application configuration and a MongoDB client.
+/// Holds the application configuration and the storage requests are served from.
 #[derive(Debug, Default)]
 pub struct AppData {
     /// The application configuration.
@@ -31,10 +443,37 @@ pub struct AppData {
     /// The MongoDB client wrapped in an `Arc`.
     pub mongo_client: Option<Arc<mongodb::Client>>,
     pub database: Option<Arc<mongodb::Database>>,
+    /// The SQLite database, when records are stored there.
+    #[cfg(feature = "sqlite")]
+    pub sqlite: Option<SqliteDatabase>,
+    /// Repositories services are built over; repositories over `database`
+    /// when unset.
+    pub repositories: Option<Repositories>,
+    /// When the server started, used to report uptime.
+    pub started_at: Option<DateTime<Utc>>,
+}
+
+impl AppData {
+    /// The repositories requests are served from.
+    ///
+    /// # Errors
+    ///
+    /// * `AppError::Unavailable` - Neither repositories nor a database were set up
+    pub fn repositories(&self) -> Result<Repositories, AppError> {
+        if let Some(repositories) = &self.repositories {
+            return Ok(repositories.clone());
+        }
+        let database = self
+            .database
+            .as_ref()
+            .ok_or_else(|| AppError::Unavailable("Database not initialized".to_string()))?;
+        Repositories::mongo(database.clone())
+    }
 }
 
 impl AppConfig {
-    /// Constructs an `AppConfig` from environment variables.
+    /// Constructs an `AppConfig` from the config file named by `BURAQ_CONFIG`,
+    /// if any, and environment variables.
     ///
     /// # Arguments
     ///
@@ -53,50 +492,317 @@ impl AppConfig {
         if load_env == Some(true) {
             dotenvy::dotenv().ok();
         }
+        let path = env::var(CONFIG_FILE_ENV).ok().map(PathBuf::from);
+        Self::load(path.as_deref())
+    }
 
-        let database_uri = match env::var("BURAQ_DATABASE_URI") {
-            Ok(uri) => uri,
-            Err(_) => {
-                return Err(anyhow::anyhow!(
-                    "BURAQ_DATABASE_URI environment variable is not set"
-                ));
-            }
+    /// Constructs an `AppConfig` from a TOML file, with environment variables
+    /// taking precedence over it.
+    ///
+    /// Every key `a.b` in the file can be overridden by the variable `BURAQ_A_B`,
+    /// e.g. `server.workers` by `BURAQ_SERVER_WORKERS`. Lists are given as
+    /// comma-separated values in the environment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the key when the file cannot be parsed, holds an
+    /// unknown key, or a value is missing or invalid.
+    pub fn load(path: Option<&Path>) -> Result<Self, anyhow::Error> {
+        let layers = Layers::load(path)?;
+        let storage = load_storage(&layers)?;
+        let (database_uri, database_name) = match storage {
+            Storage::Mongo => (
+                layers.require("database.uri")?,
+                layers.require("database.name")?,
+            ),
+            Storage::Sqlite(_) => (String::new(), String::new()),
         };
 
-        let database_name = match env::var("BURAQ_DATABASE_NAME") {
-            Ok(name) => name,
-            Err(_) => {
-                return Err(anyhow::anyhow!(
-                    "BURAQ_DATABASE_NAME environment variable is not set"
-                ));
+        let soft_delete_retention_days = layers.get_or(
+            "soft_delete_retention_days",
+            DEFAULT_SOFT_DELETE_RETENTION_DAYS,
+        )?;
+        if !(0..=MAX_SOFT_DELETE_RETENTION_DAYS).contains(&soft_delete_retention_days) {
+            return Err(invalid(
+                "soft_delete_retention_days",
+                &format!("must be between 0 and {}", MAX_SOFT_DELETE_RETENTION_DAYS),
+            ));
+        }
+        let purge_interval_seconds =
+            layers.get_or("purge_interval_seconds", DEFAULT_PURGE_INTERVAL_SECONDS)?;
+        if purge_interval_seconds == 0 {
+            return Err(invalid("purge_interval_seconds", "must be at least 1"));
+        }
+
+        Ok(Self {
+            application: ApplicationConfig {
+                host: layers.require("host")?,
+                port: layers.require("port")?,
+                storage,
+                database_uri,
+                database_name,
+                soft_delete_retention_days,
+                purge_interval_seconds,
+                otlp_endpoint: layers
+                    .get::<String>("otlp_endpoint")?
+                    .filter(|endpoint| !endpoint.is_empty()),
+                rate_limit: RateLimitConfig::load(&layers)?,
+                server: ServerConfig::load(&layers)?,
+                tls: TlsConfig::load(&layers)?,
+                cors: CorsConfig::load(&layers)?,
+                tokens: TokenConfig::load(&layers)?,
+                master_key_source: load_master_key_source(&layers)?,
+            },
+        })
+    }
+}
+
+/// Every key the config file may hold.
+const KNOWN_KEYS: &[&str] = &[
+    "host",
+    "port",
+    "soft_delete_retention_days",
+    "purge_interval_seconds",
+    "otlp_endpoint",
+    "database.backend",
+    "database.uri",
+    "database.name",
+    "database.path",
+    "server.workers",
+    "server.keep_alive_seconds",
+    "server.shutdown_timeout_seconds",
+    "server.client_request_timeout_seconds",
+    "server.json_limit_bytes",
+    "server.payload_limit_bytes",
+    "tls.cert_path",
+    "tls.key_path",
+    "tls.client_ca_path",
+    "cors.allowed_origins",
+    "cors.allowed_methods",
+    "cors.allowed_headers",
+    "cors.max_age_seconds",
+    "tokens.default_algorithm",
+    "tokens.default_ttl_seconds",
+    "tokens.max_ttl_seconds",
+    "tokens.dpop_max_age_seconds",
+    "tokens.include_labels",
+    "secrets.master_key_source",
+    "secrets.master_key_file",
+    "rate_limit.window_seconds",
+    "rate_limit.per_client",
+    "rate_limit.per_ip",
+    "lockout.threshold",
+    "lockout.base_seconds",
+    "lockout.max_seconds",
+];
+
+fn invalid(key: &str, reason: &str) -> anyhow::Error {
+    anyhow::anyhow!("Invalid configuration: `{}` {}", key, reason)
+}
+
+/// Environment variable overriding `key`.
+fn env_name(key: &str) -> String {
+    format!("BURAQ_{}", key.replace('.', "_").to_uppercase())
+}
+
+/// A value that can be read from the config file or the environment.
+trait ConfigValue: Sized {
+    /// What the value should look like, used in error messages.
+    const EXPECTED: &'static str;
+
+    fn from_item(item: &Item) -> Option<Self>;
+
+    fn from_env(value: &str) -> Option<Self>;
+}
+
+macro_rules! integer_config_value {
+    ($($type:ty),*) => {
+        $(impl ConfigValue for $type {
+            const EXPECTED: &'static str = "a non-negative integer";
+
+            fn from_item(item: &Item) -> Option<Self> {
+                item.as_integer().and_then(|value| Self::try_from(value).ok())
             }
-        };
 
-        let host = match env::var("BURAQ_HOST") {
-            Ok(h) => h,
-            Err(_) => {
-                return Err(anyhow::anyhow!(
-                    "BURAQ_HOST environment variable is not set"
-                ));
+            fn from_env(value: &str) -> Option<Self> {
+                value.trim().parse().ok()
             }
+        })*
+    };
+}
+
+integer_config_value!(u16, u32, u64, usize);
+
+impl ConfigValue for i64 {
+    const EXPECTED: &'static str = "an integer";
+
+    fn from_item(item: &Item) -> Option<Self> {
+        item.as_integer()
+    }
+
+    fn from_env(value: &str) -> Option<Self> {
+        value.trim().parse().ok()
+    }
+}
+
+impl ConfigValue for bool {
+    const EXPECTED: &'static str = "true or false";
+
+    fn from_item(item: &Item) -> Option<Self> {
+        item.as_bool()
+    }
+
+    fn from_env(value: &str) -> Option<Self> {
+        value.trim().parse().ok()
+    }
+}
+
+impl ConfigValue for String {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_item(item: &Item) -> Option<Self> {
+        item.as_str().map(str::to_string)
+    }
+
+    fn from_env(value: &str) -> Option<Self> {
+        Some(value.to_string())
+    }
+}
+
+impl ConfigValue for PathBuf {
+    const EXPECTED: &'static str = "a path";
+
+    fn from_item(item: &Item) -> Option<Self> {
+        item.as_str().map(PathBuf::from)
+    }
+
+    fn from_env(value: &str) -> Option<Self> {
+        Some(PathBuf::from(value))
+    }
+}
+
+impl ConfigValue for Vec<String> {
+    const EXPECTED: &'static str = "a list of strings";
+
+    fn from_item(item: &Item) -> Option<Self> {
+        item.as_array()?
+            .iter()
+            .map(|value| value.as_str().map(str::to_string))
+            .collect()
+    }
+
+    fn from_env(value: &str) -> Option<Self> {
+        Some(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+}
+
+impl ConfigValue for Algorithm {
+    const EXPECTED: &'static str = "a JWT algorithm such as RS256";
+
+    fn from_item(item: &Item) -> Option<Self> {
+        item.as_str()
+            .and_then(|value| Algorithm::from_str(value).ok())
+    }
+
+    fn from_env(value: &str) -> Option<Self> {
+        Algorithm::from_str(value.trim()).ok()
+    }
+}
+
+/// The config file, if any, overlaid with `BURAQ_*` environment variables.
+struct Layers {
+    path: Option<PathBuf>,
+    file: DocumentMut,
+}
+
+impl Layers {
+    fn load(path: Option<&Path>) -> Result<Self, anyhow::Error> {
+        let file = match path {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?
+                .parse::<DocumentMut>()
+                .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))?,
+            None => DocumentMut::new(),
         };
+        let layers = Self {
+            path: path.map(Path::to_path_buf),
+            file,
+        };
+        layers.check_keys()?;
+        Ok(layers)
+    }
 
-        let port = match env::var("BURAQ_PORT") {
-            Ok(p) => p,
-            Err(_) => {
-                return Err(anyhow::anyhow!(
-                    "BURAQ_PORT environment variable is not set"
-                ));
+    /// Rejects keys that are not recognised, which are most likely typos.
+    fn check_keys(&self) -> Result<(), anyhow::Error> {
+        fn visit(prefix: &str, item: &Item, keys: &mut Vec<String>) {
+            match item.as_table_like() {
+                Some(table) => {
+                    for (key, value) in table.iter() {
+                        let key = if prefix.is_empty() {
+                            key.to_string()
+                        } else {
+                            format!("{}.{}", prefix, key)
+                        };
+                        visit(&key, value, keys);
+                    }
+                }
+                None => keys.push(prefix.to_string()),
             }
-        };
+        }
 
-        Ok(Self {
-            application: ApplicationConfig {
-                host,
-                port: port.parse()?,
-                database_uri,
-                database_name,
-            },
+        let mut keys = Vec::new();
+        visit("", self.file.as_item(), &mut keys);
+        match keys.iter().find(|key| !KNOWN_KEYS.contains(&key.as_str())) {
+            Some(key) => Err(invalid(key, "is not a known setting")),
+            None => Ok(()),
+        }
+    }
+
+    fn file_item(&self, key: &str) -> Option<&Item> {
+        key.split('.')
+            .try_fold(self.file.as_item(), |item, part| item.get(part))
+    }
+
+    /// The value of `key`, from the environment first and the file second.
+    fn get<T: ConfigValue>(&self, key: &str) -> Result<Option<T>, anyhow::Error> {
+        let env_name = env_name(key);
+        if let Ok(value) = env::var(&env_name) {
+            return T::from_env(&value).map(Some).ok_or_else(|| {
+                invalid(key, &format!("must be {} (from {})", T::EXPECTED, env_name))
+            });
+        }
+        match self.file_item(key) {
+            Some(item) => T::from_item(item).map(Some).ok_or_else(|| {
+                let path = self.path.as_deref().unwrap_or(Path::new("config file"));
+                invalid(
+                    key,
+                    &format!("must be {} (in {})", T::EXPECTED, path.display()),
+                )
+            }),
+            None => Ok(None),
+        }
+    }
+
+    fn get_or<T: ConfigValue>(&self, key: &str, default: T) -> Result<T, anyhow::Error> {
+        Ok(self.get(key)?.unwrap_or(default))
+    }
+
+    fn require<T: ConfigValue>(&self, key: &str) -> Result<T, anyhow::Error> {
+        self.get(key)?.ok_or_else(|| match &self.path {
+            Some(path) => anyhow::anyhow!(
+                "Invalid configuration: `{}` is not set in {} and {} is not set",
+                key,
+                path.display(),
+                env_name(key)
+            ),
+            None => anyhow::anyhow!("{} environment variable is not set", env_name(key)),
         })
     }
 }
@@ -113,6 +819,7 @@ mod tests {
                 ("BURAQ_DATABASE_NAME", Some("test_db")),
                 ("BURAQ_HOST", Some("127.0.0.1")),
                 ("BURAQ_PORT", Some("8080")),
+                ("BURAQ_OTLP_ENDPOINT", None),
             ],
             || {
                 let config = AppConfig::from_env(Some(false)).expect("Failed to load config");
@@ -121,10 +828,183 @@ mod tests {
                 assert_eq!(config.application.port, 8080);
                 assert_eq!(config.application.database_uri, "mongodb://localhost:27017");
                 assert_eq!(config.application.database_name, "test_db");
+                assert_eq!(
+                    config.application.soft_delete_retention_days,
+                    DEFAULT_SOFT_DELETE_RETENTION_DAYS
+                );
+                assert_eq!(
+                    config.application.purge_interval_seconds,
+                    DEFAULT_PURGE_INTERVAL_SECONDS
+                );
+                assert_eq!(config.application.otlp_endpoint, None);
+            },
+        );
+    }
+
+    #[test]
+    fn test_app_config_soft_delete_retention() {
+        temp_env::with_vars(
+            vec![
+                ("BURAQ_DATABASE_URI", Some("mongodb://localhost:27017")),
+                ("BURAQ_DATABASE_NAME", Some("test_db")),
+                ("BURAQ_HOST", Some("127.0.0.1")),
+                ("BURAQ_PORT", Some("8080")),
+                ("BURAQ_SOFT_DELETE_RETENTION_DAYS", Some("7")),
+                ("BURAQ_PURGE_INTERVAL_SECONDS", Some("60")),
+            ],
+            || {
+                let config = AppConfig::from_env(Some(false)).expect("Failed to load config");
+
+                assert_eq!(config.application.soft_delete_retention_days, 7);
+                assert_eq!(config.application.purge_interval_seconds, 60);
             },
         );
     }
 
+    #[test]
+    fn test_app_config_rejects_invalid_purge_settings() {
+        let cases = [
+            (
+                "BURAQ_SOFT_DELETE_RETENTION_DAYS",
+                "-1",
+                "`soft_delete_retention_days` must be between 0 and 36500",
+            ),
+            (
+                "BURAQ_SOFT_DELETE_RETENTION_DAYS",
+                "1000000000000",
+                "`soft_delete_retention_days` must be between 0 and 36500",
+            ),
+            (
+                "BURAQ_PURGE_INTERVAL_SECONDS",
+                "0",
+                "`purge_interval_seconds` must be at least 1",
+            ),
+        ];
+        for (name, value, expected) in cases {
+            temp_env::with_vars(
+                vec![
+                    ("BURAQ_DATABASE_URI", Some("mongodb://localhost:27017")),
+                    ("BURAQ_DATABASE_NAME", Some("test_db")),
+                    ("BURAQ_HOST", Some("127.0.0.1")),
+                    ("BURAQ_PORT", Some("8080")),
+                    (name, Some(value)),
+                ],
+                || {
+                    let error = AppConfig::from_env(Some(false)).unwrap_err().to_string();
+                    assert!(error.contains(expected), "{}={}: {}", name, value, error);
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn test_app_config_otlp_endpoint() {
+        temp_env::with_vars(
+            vec![
+                ("BURAQ_DATABASE_URI", Some("mongodb://localhost:27017")),
+                ("BURAQ_DATABASE_NAME", Some("test_db")),
+                ("BURAQ_HOST", Some("127.0.0.1")),
+                ("BURAQ_PORT", Some("8080")),
+                (
+                    "BURAQ_OTLP_ENDPOINT",
+                    Some("http://localhost:4318/v1/traces"),
+                ),
+            ],
+            || {
+                let config = AppConfig::from_env(Some(false)).expect("Failed to load config");
+
+                assert_eq!(
+                    config.application.otlp_endpoint.as_deref(),
+                    Some("http://localhost:4318/v1/traces")
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_app_config_rate_limit() {
+        temp_env::with_vars(
+            vec![
+                ("BURAQ_DATABASE_URI", Some("mongodb://localhost:27017")),
+                ("BURAQ_DATABASE_NAME", Some("test_db")),
+                ("BURAQ_HOST", Some("127.0.0.1")),
+                ("BURAQ_PORT", Some("8080")),
+                ("BURAQ_RATE_LIMIT_PER_CLIENT", Some("3")),
+                ("BURAQ_LOCKOUT_THRESHOLD", Some("2")),
+            ],
+            || {
+                let config = AppConfig::from_env(Some(false)).expect("Failed to load config");
+
+                assert_eq!(config.application.rate_limit.per_client, 3);
+                assert_eq!(config.application.rate_limit.lockout_threshold, 2);
+                assert_eq!(
+                    config.application.rate_limit.per_ip,
+                    RateLimitConfig::default().per_ip
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_app_config_storage() {
+        let load = |backend: Option<&str>, path: Option<&str>| {
+            temp_env::with_vars(
+                vec![
+                    ("BURAQ_DATABASE_URI", None),
+                    ("BURAQ_DATABASE_NAME", None),
+                    ("BURAQ_HOST", Some("127.0.0.1")),
+                    ("BURAQ_PORT", Some("8080")),
+                    ("BURAQ_DATABASE_BACKEND", backend),
+                    ("BURAQ_DATABASE_PATH", path),
+                ],
+                || AppConfig::from_env(Some(false)).map(|config| config.application),
+            )
+        };
+
+        let error = load(Some("postgres"), None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid configuration: `database.backend` expected mongodb or sqlite, got postgres"
+        );
+        // MongoDB still needs its connection settings
+        assert!(load(Some("mongodb"), None).is_err());
+
+        let sqlite = load(Some("sqlite"), Some("/var/lib/buraq/buraq.db"));
+        if cfg!(feature = "sqlite") {
+            let application = sqlite.unwrap();
+            assert_eq!(
+                application.storage,
+                Storage::Sqlite(PathBuf::from("/var/lib/buraq/buraq.db"))
+            );
+            assert_eq!(application.database_uri, "");
+
+            let error = load(Some("sqlite"), None).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "Invalid configuration: `database.path` must be set when database.backend is sqlite"
+            );
+        } else {
+            assert!(sqlite.unwrap_err().to_string().contains("sqlite feature"));
+        }
+    }
+
+    #[test]
+    fn test_lockout_backoff_doubles_up_to_max() {
+        let config = RateLimitConfig {
+            lockout_threshold: 3,
+            lockout_base_seconds: 10,
+            lockout_max_seconds: 60,
+            ..Default::default()
+        };
+
+        assert_eq!(config.lockout_for(2), None);
+        assert_eq!(config.lockout_for(3), Some(Duration::from_secs(10)));
+        assert_eq!(config.lockout_for(4), Some(Duration::from_secs(20)));
+        assert_eq!(config.lockout_for(5), Some(Duration::from_secs(40)));
+        assert_eq!(config.lockout_for(6), Some(Duration::from_secs(60)));
+        assert_eq!(config.lockout_for(u32::MAX), Some(Duration::from_secs(60)));
+    }
+
     #[test]
     fn test_app_config_missing_database_uri() {
         temp_env::with_vars(
@@ -200,4 +1080,194 @@ mod tests {
             },
         );
     }
+
+    /// Writes `contents` to a fresh temporary TOML file.
+    fn config_file(contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "buraq-config-{}.toml",
+            mongodb::bson::uuid::Uuid::new()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    const REQUIRED_KEYS: &str = r#"
+        host = "0.0.0.0"
+        port = 9090
+
+        [database]
+        uri = "mongodb://db:27017"
+        name = "buraq"
+    "#;
+
+    /// Unsets every variable that could override the file.
+    fn without_overrides<R>(f: impl FnOnce() -> R) -> R {
+        let names: Vec<String> = KNOWN_KEYS.iter().map(|key| env_name(key)).collect();
+        temp_env::with_vars_unset(names, f)
+    }
+
+    #[test]
+    fn test_app_config_from_file() {
+        let path = config_file(&format!(
+            r#"{REQUIRED_KEYS}
+            [server]
+            workers = 8
+            keep_alive_seconds = 10
+
+            [cors]
+            allowed_origins = ["https://console.example.com"]
+
+            [tokens]
+            default_algorithm = "ES256"
+            dpop_max_age_seconds = 60
+            include_labels = true
+            "#
+        ));
+
+        let config = without_overrides(|| AppConfig::load(Some(&path))).unwrap();
+        let application = config.application;
+        assert_eq!(application.host, "0.0.0.0");
+        assert_eq!(application.port, 9090);
+        assert_eq!(application.database_uri, "mongodb://db:27017");
+        assert_eq!(application.server.workers, 8);
+        assert_eq!(application.server.keep_alive_seconds, 10);
+        assert_eq!(
+            application.server.shutdown_timeout_seconds,
+            ServerConfig::default().shutdown_timeout_seconds
+        );
+        assert_eq!(
+            application.cors.allowed_origins,
+            ["https://console.example.com"]
+        );
+        assert_eq!(application.tokens.default_algorithm, Algorithm::ES256);
+        assert_eq!(application.tokens.dpop_max_age_seconds, 60);
+        assert!(application.tokens.include_labels);
+        assert_eq!(application.tls, None);
+        assert_eq!(application.master_key_source, MasterKeySource::Env);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let path = config_file(&format!("{REQUIRED_KEYS}\n[server]\nworkers = 8\n"));
+
+        let config = without_overrides(|| {
+            temp_env::with_vars(
+                [
+                    ("BURAQ_PORT", Some("7070")),
+                    ("BURAQ_SERVER_WORKERS", Some("2")),
+                    (
+                        "BURAQ_CORS_ALLOWED_ORIGINS",
+                        Some("https://a.example, https://b.example"),
+                    ),
+                ],
+                || AppConfig::load(Some(&path)),
+            )
+        })
+        .unwrap();
+        assert_eq!(config.application.port, 7070);
+        assert_eq!(config.application.server.workers, 2);
+        assert_eq!(
+            config.application.cors.allowed_origins,
+            ["https://a.example", "https://b.example"]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_validation_errors_name_the_key() {
+        let cases = [
+            (
+                "[server]\nworkers = \"many\"",
+                "`server.workers` must be a non-negative integer",
+            ),
+            (
+                "[server]\nworkers = 0",
+                "`server.workers` must be at least 1",
+            ),
+            (
+                "[server]\nwrokers = 2",
+                "`server.wrokers` is not a known setting",
+            ),
+            (
+                "[lockout]\nmax_seconds = 99999999999",
+                "`lockout.max_seconds` must be at most 31536000",
+            ),
+            (
+                "[tls]\ncert_path = \"cert.pem\"",
+                "`tls.key_path` must be set",
+            ),
+            (
+                "[tls]\nclient_ca_path = \"ca.pem\"",
+                "`tls.client_ca_path` requires tls.cert_path and tls.key_path",
+            ),
+            (
+                "[secrets]\nmaster_key_source = \"file\"",
+                "`secrets.master_key_file` must be set",
+            ),
+            (
+                "[secrets]\nmaster_key_source = \"vault\"",
+                "`secrets.master_key_source` expected env or file",
+            ),
+            (
+                "[tokens]\ndefault_algorithm = \"XYZ\"",
+                "`tokens.default_algorithm` must be a JWT algorithm",
+            ),
+            (
+                "[cors]\nallowed_methods = [\"G T\"]",
+                "`cors.allowed_methods` G T is not an HTTP method",
+            ),
+        ];
+        for (section, expected) in cases {
+            let path = config_file(&format!("{REQUIRED_KEYS}\n{section}\n"));
+            let error = without_overrides(|| AppConfig::load(Some(&path)))
+                .unwrap_err()
+                .to_string();
+            assert!(error.contains(expected), "{}: {}", section, error);
+            std::fs::remove_file(path).unwrap();
+        }
+
+        let path = config_file(REQUIRED_KEYS);
+        let error = without_overrides(|| {
+            temp_env::with_var("BURAQ_SERVER_WORKERS", Some("lots"), || {
+                AppConfig::load(Some(&path))
+            })
+        })
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("(from BURAQ_SERVER_WORKERS)"), "{}", error);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_required_key_in_file() {
+        let path = config_file("host = \"0.0.0.0\"\nport = 9090\n");
+        let error = without_overrides(|| AppConfig::load(Some(&path)))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("`database.uri` is not set"), "{}", error);
+        assert!(error.contains("BURAQ_DATABASE_URI"), "{}", error);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_token_expiry_defaults() {
+        let tokens = TokenConfig {
+            default_ttl_seconds: 60,
+            max_ttl_seconds: 3600,
+            ..Default::default()
+        };
+        let now = Utc::now();
+
+        assert_eq!(
+            tokens.expires_at(None, now).unwrap(),
+            now + chrono::Duration::seconds(60)
+        );
+        let requested = now + chrono::Duration::seconds(600);
+        assert_eq!(tokens.expires_at(Some(requested), now).unwrap(), requested);
+        assert!(matches!(
+            tokens.expires_at(Some(now + chrono::Duration::days(1)), now),
+            Err(crate::errors::AppError::Validation(_))
+        ));
+    }
 }
@@ -0,0 +1,181 @@
+use crate::config::{AppData, CorsConfig};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::http::header::{
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_METHOD,
+    HeaderValue, ORIGIN, VARY,
+};
+use actix_web::middleware::Next;
+use actix_web::{HttpResponse, web};
+
+/// Response headers browsers may read from cross-origin responses.
+const EXPOSED_HEADERS: &str = "Link, Retry-After, X-Request-Id";
+
+impl CorsConfig {
+    /// The `Access-Control-Allow-Origin` value for `origin`, if it is allowed.
+    fn allow_origin<'a>(&'a self, origin: &'a str) -> Option<&'a str> {
+        if self.allowed_origins.iter().any(|allowed| allowed == "*") {
+            Some("*")
+        } else {
+            self.allowed_origins
+                .iter()
+                .find(|allowed| allowed.eq_ignore_ascii_case(origin))
+                .map(|_| origin)
+        }
+    }
+}
+
+/// Middleware answering CORS preflight requests and marking responses to
+/// allowed origins as readable, as configured in [`CorsConfig`].
+///
+/// Requests from origins that are not allowed are passed through untouched,
+/// leaving it to the browser to block them.
+pub async fn cors(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let cors = request
+        .app_data::<web::Data<AppData>>()
+        .and_then(|data| data.config.as_ref())
+        .map(|config| config.application.cors.clone())
+        .unwrap_or_default();
+    let allow_origin = request
+        .headers()
+        .get(ORIGIN)
+        .and_then(|origin| origin.to_str().ok())
+        .and_then(|origin| cors.allow_origin(origin))
+        .and_then(|origin| HeaderValue::from_str(origin).ok());
+    let Some(allow_origin) = allow_origin else {
+        return Ok(next.call(request).await?.map_into_boxed_body());
+    };
+
+    if request.method() == Method::OPTIONS
+        && request
+            .headers()
+            .contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+    {
+        let response = HttpResponse::NoContent()
+            .insert_header((ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin))
+            .insert_header((
+                ACCESS_CONTROL_ALLOW_METHODS,
+                cors.allowed_methods.join(", "),
+            ))
+            .insert_header((
+                ACCESS_CONTROL_ALLOW_HEADERS,
+                cors.allowed_headers.join(", "),
+            ))
+            .insert_header((ACCESS_CONTROL_MAX_AGE, cors.max_age_seconds.to_string()))
+            .insert_header((VARY, "Origin"))
+            .finish();
+        return Ok(request.into_response(response));
+    }
+
+    let mut response = next.call(request).await?;
+    let headers = response.headers_mut();
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+    headers.insert(
+        ACCESS_CONTROL_EXPOSE_HEADERS,
+        HeaderValue::from_static(EXPOSED_HEADERS),
+    );
+    headers.append(VARY, HeaderValue::from_static("Origin"));
+    Ok(response.map_into_boxed_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use actix_web::App;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+
+    fn app_data(allowed_origins: &[&str]) -> web::Data<AppData> {
+        let mut config = temp_env::with_vars(
+            [
+                ("BURAQ_DATABASE_URI", Some("mongodb://localhost:27017")),
+                ("BURAQ_DATABASE_NAME", Some("test_db")),
+                ("BURAQ_HOST", Some("127.0.0.1")),
+                ("BURAQ_PORT", Some("8080")),
+            ],
+            || AppConfig::load(None).unwrap(),
+        );
+        config.application.cors.allowed_origins = allowed_origins
+            .iter()
+            .map(|origin| origin.to_string())
+            .collect();
+        web::Data::new(AppData {
+            config: Some(config),
+            ..Default::default()
+        })
+    }
+
+    #[actix_web::test]
+    async fn test_preflight_from_allowed_origin() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(app_data(&["https://console.example.com"]))
+                .wrap(from_fn(cors))
+                .route("/projects", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let resp = actix_web::test::TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/projects")
+            .insert_header((ORIGIN, "https://console.example.com"))
+            .insert_header((ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            resp.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://console.example.com"
+        );
+        assert_eq!(resp.headers().get(ACCESS_CONTROL_MAX_AGE).unwrap(), "3600");
+
+        let resp = actix_web::test::TestRequest::get()
+            .uri("/projects")
+            .insert_header((ORIGIN, "https://console.example.com"))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://console.example.com"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_other_origins_get_no_cors_headers() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(app_data(&["https://console.example.com"]))
+                .wrap(from_fn(cors))
+                .route("/projects", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let resp = actix_web::test::TestRequest::get()
+            .uri("/projects")
+            .insert_header((ORIGIN, "https://evil.example.com"))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+
+    #[test]
+    fn test_wildcard_origin() {
+        let cors = CorsConfig {
+            allowed_origins: vec!["*".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(cors.allow_origin("https://any.example.com"), Some("*"));
+        assert_eq!(
+            CorsConfig::default().allow_origin("https://any.example.com"),
+            None
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use actix_web::error::{JsonPayloadError, PathError, QueryPayloadError, UrlencodedError};
+use actix_web::http::StatusCode;
+use actix_web::http::header::{CONTENT_TYPE, HeaderValue, RETRY_AFTER};
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use mongodb::error::{ErrorKind, WriteFailure};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+/// Media type of RFC 7807 problem details responses.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// MongoDB server error code for unique index violations.
+const DUPLICATE_KEY: i32 = 11000;
+
+/// Errors raised by repositories, services and route handlers.
+///
+/// Each variant maps to an HTTP status code and is rendered as an
+/// `application/problem+json` body, so clients can branch on `type`.
+#[derive(Debug, Error)]
+pub enum AppError {
+    /// The requested resource does not exist.
+    #[error("{0}")]
+    NotFound(String),
+    /// The request conflicts with existing state, e.g. a unique field is taken.
+    #[error("{0}")]
+    Conflict(String),
+    /// The request is malformed or fails validation.
+    #[error("{0}")]
+    Validation(String),
+    /// The caller could not be authenticated.
+    #[error("{0}")]
+    Unauthorized(String),
+    /// The caller is authenticated but not allowed to perform the request.
+    #[error("{0}")]
+    Forbidden(String),
+    /// The caller exceeded a rate limit or is locked out, and may retry after
+    /// `retry_after` seconds.
+    #[error("{message}")]
+    TooManyRequests { message: String, retry_after: u64 },
+    /// A backing service, such as the database, cannot be reached.
+    #[error("{0}")]
+    Unavailable(String),
+    /// Any other failure. The details are logged but not returned to the client.
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+impl AppError {
+    /// Short, stable identifier of the problem type.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not-found",
+            AppError::Conflict(_) => "conflict",
+            AppError::Validation(_) => "validation",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::TooManyRequests { .. } => "too-many-requests",
+            AppError::Unavailable(_) => "unavailable",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    /// Builds the problem details document describing this error.
+    pub fn problem(&self) -> Problem {
+        let status = self.status_code();
+        let detail = match self {
+            AppError::Internal(_) => "An unexpected error occurred".to_string(),
+            other => other.to_string(),
+        };
+        let problem = Problem {
+            problem_type: format!("urn:buraq:problem:{}", self.kind()),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail,
+            extensions: Map::new(),
+        };
+        match self {
+            AppError::TooManyRequests { retry_after, .. } => {
+                problem.with_extension("retry_after", retry_after)
+            }
+            _ => problem,
+        }
+    }
+}
+
+/// RFC 7807 problem details body.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    /// Problem-specific members added alongside the standard ones.
+    #[schema(ignore)]
+    #[serde(flatten, default, skip_serializing_if = "Map::is_empty")]
+    pub extensions: Map<String, Value>,
+}
+
+impl Problem {
+    /// Adds a problem-specific member to the body.
+    pub fn with_extension(mut self, name: &str, value: impl Serialize) -> Self {
+        self.extensions.insert(
+            name.to_string(),
+            serde_json::to_value(value).unwrap_or(Value::Null),
+        );
+        self
+    }
+
+    /// Renders the problem as an `application/problem+json` response.
+    pub fn to_response(&self) -> HttpResponse {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        HttpResponse::build(status)
+            .insert_header((CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON)))
+            .json(self)
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let AppError::Internal(e) = self {
+            tracing::error!(error = ?e, "Internal error");
+        }
+        let mut response = self.problem().to_response();
+        if let AppError::TooManyRequests { retry_after, .. } = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(*retry_after));
+        }
+        response
+    }
+}
+
+impl From<mongodb::error::Error> for AppError {
+    fn from(error: mongodb::error::Error) -> Self {
+        if is_duplicate_key(&error) {
+            return AppError::Conflict(
+                "A record with the same unique fields already exists".to_string(),
+            );
+        }
+        match *error.kind {
+            ErrorKind::ServerSelection { .. }
+            | ErrorKind::Io(_)
+            | ErrorKind::ConnectionPoolCleared { .. }
+            | ErrorKind::DnsResolve { .. } => {
+                AppError::Unavailable("Database is unavailable".to_string())
+            }
+            _ => AppError::Internal(error.into()),
+        }
+    }
+}
+
+impl From<mongodb::bson::ser::Error> for AppError {
+    fn from(error: mongodb::bson::ser::Error) -> Self {
+        AppError::Internal(error.into())
+    }
+}
+
+impl From<mongodb::bson::de::Error> for AppError {
+    fn from(error: mongodb::bson::de::Error) -> Self {
+        AppError::Internal(error.into())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for AppError {
+    fn from(error: rusqlite::Error) -> Self {
+        use rusqlite::ffi;
+
+        match error.sqlite_error() {
+            Some(failure)
+                if failure.extended_code == ffi::SQLITE_CONSTRAINT_UNIQUE
+                    || failure.extended_code == ffi::SQLITE_CONSTRAINT_PRIMARYKEY =>
+            {
+                AppError::Conflict(
+                    "A record with the same unique fields already exists".to_string(),
+                )
+            }
+            Some(failure)
+                if matches!(
+                    failure.code,
+                    rusqlite::ErrorCode::DatabaseBusy
+                        | rusqlite::ErrorCode::DatabaseLocked
+                        | rusqlite::ErrorCode::CannotOpen
+                ) =>
+            {
+                AppError::Unavailable("Database is unavailable".to_string())
+            }
+            _ => AppError::Internal(error.into()),
+        }
+    }
+}
+
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    match error.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => {
+            write_error.code == DUPLICATE_KEY
+        }
+        ErrorKind::Command(command_error) => command_error.code == DUPLICATE_KEY,
+        ErrorKind::InsertMany(insert_many_error) => insert_many_error
+            .write_errors
+            .iter()
+            .flatten()
+            .any(|write_error| write_error.code == DUPLICATE_KEY),
+        _ => false,
+    }
+}
+
+/// Renders malformed JSON bodies as validation problems.
+pub fn json_error_handler(error: JsonPayloadError, _request: &HttpRequest) -> actix_web::Error {
+    AppError::Validation(error.to_string()).into()
+}
+
+/// Renders malformed query strings as validation problems.
+pub fn query_error_handler(error: QueryPayloadError, _request: &HttpRequest) -> actix_web::Error {
+    AppError::Validation(error.to_string()).into()
+}
+
+/// Renders malformed form bodies as validation problems.
+pub fn form_error_handler(error: UrlencodedError, _request: &HttpRequest) -> actix_web::Error {
+    AppError::Validation(error.to_string()).into()
+}
+
+/// Renders malformed path parameters as validation problems.
+pub fn path_error_handler(error: PathError, _request: &HttpRequest) -> actix_web::Error {
+    AppError::Validation(error.to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[actix_web::test]
+    async fn test_error_response_is_problem_json() {
+        let error = AppError::NotFound("Environment not found".to_string());
+        let response = error.error_response();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            HeaderValue::from_static(PROBLEM_JSON)
+        );
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let problem: Problem = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            problem,
+            Problem {
+                problem_type: "urn:buraq:problem:not-found".to_string(),
+                title: "Not Found".to_string(),
+                status: 404,
+                detail: "Environment not found".to_string(),
+                extensions: Map::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_status_codes() {
+        let cases = [
+            (AppError::NotFound(String::new()), 404),
+            (AppError::Conflict(String::new()), 409),
+            (AppError::Validation(String::new()), 400),
+            (AppError::Unauthorized(String::new()), 401),
+            (AppError::Forbidden(String::new()), 403),
+            (
+                AppError::TooManyRequests {
+                    message: String::new(),
+                    retry_after: 1,
+                },
+                429,
+            ),
+            (AppError::Unavailable(String::new()), 503),
+            (AppError::Internal(anyhow::anyhow!("boom")), 500),
+        ];
+        for (error, status) in cases {
+            assert_eq!(error.status_code().as_u16(), status);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_too_many_requests_sets_retry_after() {
+        let error = AppError::TooManyRequests {
+            message: "Too many authentication attempts".to_string(),
+            retry_after: 42,
+        };
+        let response = error.error_response();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "42");
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let problem: Problem = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem.problem_type, "urn:buraq:problem:too-many-requests");
+        assert_eq!(problem.extensions["retry_after"], 42);
+    }
+
+    #[test]
+    fn test_internal_error_detail_is_hidden() {
+        let problem = AppError::Internal(anyhow::anyhow!("connection string leaked")).problem();
+        assert_eq!(problem.detail, "An unexpected error occurred");
+    }
+
+    #[test]
+    fn test_duplicate_key_maps_to_conflict() {
+        let write_error: mongodb::error::WriteError = mongodb::bson::from_document(
+            mongodb::bson::doc! { "code": DUPLICATE_KEY, "errmsg": "E11000 duplicate key error" },
+        )
+        .unwrap();
+        let error =
+            mongodb::error::Error::from(ErrorKind::Write(WriteFailure::WriteError(write_error)));
+
+        assert!(matches!(AppError::from(error), AppError::Conflict(_)));
+    }
+}
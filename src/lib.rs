@@ -1,8 +1,14 @@
 pub mod config;
+pub mod cors;
+pub mod errors;
+pub mod metrics;
+pub mod migrations;
 pub mod models;
+pub mod openapi;
 pub mod repositories;
 pub mod routes;
 pub mod serializers;
 pub mod services;
+pub mod telemetry;
 pub mod test_utils;
 pub mod utils;
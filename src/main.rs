@@ -1,15 +1,45 @@
+use actix_web::middleware::from_fn;
 use actix_web::{App, HttpServer, web};
-use buraq::config::{AppConfig, AppData};
+use buraq::config::{AppConfig, AppData, Storage};
+use buraq::errors;
+use buraq::models::migration::MigrationStatus;
+#[cfg(feature = "sqlite")]
+use buraq::repositories::registry::Repositories;
+#[cfg(feature = "sqlite")]
+use buraq::repositories::sqlite::SqliteDatabase;
+use buraq::services::migration_service::MigrationService;
+use buraq::services::purge_service::PurgeService;
 use buraq::utils::database::create_database_client;
+use buraq::utils::security::SecretsManager;
+use chrono::{SecondsFormat, Utc};
+use clap::{ArgMatches, Command};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::signal;
 
+/// The server command tree: serves the API without a subcommand.
+fn cli() -> Command<'static> {
+    Command::new("buraq")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Buraq authorization server")
+        .subcommand(
+            Command::new("migrate")
+                .about("Manage database migrations")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(Command::new("up").about("Apply pending migrations"))
+                .subcommand(
+                    Command::new("status").about("List migrations and whether they were applied"),
+                ),
+        )
+}
+
 /// The main entry point for the application.
 ///
 /// This function initializes the environment, sets up the application configuration,
-/// and starts the Actix web server. It also handles graceful shutdown on receiving
-/// a Ctrl+C signal.
+/// applies pending database migrations and starts the Actix web server. It also
+/// handles graceful shutdown on receiving a Ctrl+C signal. With the `migrate`
+/// subcommand, it manages migrations instead of serving.
 ///
 /// # Returns
 ///
@@ -22,45 +52,90 @@ use tokio::signal;
 /// the application configuration cannot be created, or the server fails to start.
 #[actix_web::main]
 async fn main() -> Result<(), anyhow::Error> {
+    let matches = cli().get_matches();
     // Load environment variables from a .env file
     dotenvy::dotenv()?;
-    // Initialize the logger
-    env_logger::init();
-
-    // Create application configuration from environment variables
+    // Create application configuration from the config file and environment variables
     let app_config = AppConfig::from_env(Some(true))?;
+
+    // Initialize structured logging and, when configured, trace export
+    let _telemetry = buraq::telemetry::init(app_config.application.otlp_endpoint.as_deref())?;
     let host = app_config.application.host.clone();
     let port = app_config.application.port;
+    let server_config = app_config.application.server.clone();
+    SecretsManager::set_master_key_source(app_config.application.master_key_source.clone())?;
 
-    // Create application data including the MongoDB client
-    let mongo_client = create_database_client(&app_config.application.database_uri).await?;
-    let database = mongo_client.database(&app_config.application.database_name);
-    buraq::utils::database::setup_database(database.clone()).await?;
+    if let Some(("migrate", migrate)) = matches.subcommand() {
+        return run_migrations(&app_config, migrate).await;
+    }
+
+    let mut app_data = open_storage(&app_config).await?;
+    app_data.config = Some(app_config.clone());
+    app_data.started_at = Some(Utc::now());
+    let repositories = app_data.repositories()?;
 
-    let app_data = web::Data::new(AppData {
-        config: Some(app_config.clone()),
-        mongo_client: Some(mongo_client),
-        database: Some(Arc::new(database)),
+    // Periodically hard-delete records whose soft-delete retention has expired
+    let purge_service = PurgeService::with_repositories(&repositories)?;
+    let retention_days = app_config.application.soft_delete_retention_days;
+    let purge_interval = Duration::from_secs(app_config.application.purge_interval_seconds);
+    actix_web::rt::spawn(async move {
+        let mut interval = tokio::time::interval(purge_interval);
+        loop {
+            interval.tick().await;
+            match purge_service.purge(retention_days).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!(purged, "Purged soft-deleted records"),
+                Err(e) => tracing::error!(error = ?e, "Error purging soft-deleted records"),
+            }
+        }
     });
 
-    println!("Starting the server on {}:{}", &host, &port);
+    let app_data = web::Data::new(app_data);
+
+    let tls = app_config
+        .application
+        .tls
+        .as_ref()
+        .map(buraq::utils::tls::server_config)
+        .transpose()?;
+    tracing::info!(%host, port, tls = tls.is_some(), "Starting the server");
 
     // Configure and start the Actix web server
+    let json_limit = server_config.json_limit_bytes;
+    let payload_limit = server_config.payload_limit_bytes;
     let server = HttpServer::new(move || {
         App::new()
+            .wrap(from_fn(buraq::cors::cors))
+            .wrap(from_fn(buraq::metrics::track_requests))
+            .wrap(from_fn(buraq::telemetry::trace_requests))
             .app_data(app_data.clone())
-            .configure(buraq::routes::project::configure_routes)
-            .configure(buraq::routes::access_token::configure_routes)
-            .configure(buraq::routes::service_account::configure_routes)
-            .configure(buraq::routes::environment::configure_routes)
-            .configure(buraq::routes::project_access::configure_routes)
-            .configure(buraq::routes::project_scope::configure_routes)
-            .configure(buraq::routes::service_account_key::configure_routes)
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(json_limit)
+                    .error_handler(errors::json_error_handler),
+            )
+            .app_data(
+                web::FormConfig::default()
+                    .limit(payload_limit)
+                    .error_handler(errors::form_error_handler),
+            )
+            .app_data(web::PayloadConfig::default().limit(payload_limit))
+            .app_data(web::QueryConfig::default().error_handler(errors::query_error_handler))
+            .app_data(web::PathConfig::default().error_handler(errors::path_error_handler))
+            .configure(buraq::routes::configure)
+            .configure(buraq::openapi::configure_routes)
     })
-    .bind((host, port))?
-    .shutdown_timeout(30) // 30 seconds graceful shutdown timeout
-    .workers(4) // Set number of workers
-    .keep_alive(Duration::from_secs(75)) // Keep-alive timeout
+    .on_connect(buraq::utils::tls::capture_client_certificate)
+    .shutdown_timeout(server_config.shutdown_timeout_seconds)
+    .workers(server_config.workers)
+    .keep_alive(Duration::from_secs(server_config.keep_alive_seconds))
+    .client_request_timeout(Duration::from_secs(
+        server_config.client_request_timeout_seconds,
+    ));
+    let server = match tls {
+        Some(tls) => server.bind_rustls_0_23((host, port), tls)?,
+        None => server.bind((host, port))?,
+    }
     .run();
 
     // Create a handle to the server for managing shutdown
@@ -69,19 +144,108 @@ async fn main() -> Result<(), anyhow::Error> {
     // Wait for the server to finish or for a Ctrl+C signal
     tokio::select! {
         _ = server => {
-            println!("Server finished");
+            tracing::info!("Server finished");
         }
         _ = signal::ctrl_c() => {
-            println!("Received ctrl+c signal, shutting down gracefully");
+            tracing::info!("Received ctrl+c signal, shutting down gracefully");
 
             // Stop accepting new connections
             server_handle.stop(true).await;
 
             // Give some time for cleanup
             tokio::time::sleep(Duration::from_secs(1)).await;
-            println!("Cleanup completed, server shutting down");
+            tracing::info!("Cleanup completed, server shutting down");
         }
     }
 
     Ok(())
 }
+
+/// Connects to the configured storage, creating its indexes or schema and
+/// applying pending migrations.
+async fn open_storage(app_config: &AppConfig) -> Result<AppData, anyhow::Error> {
+    match &app_config.application.storage {
+        Storage::Mongo => {
+            let mongo_client = create_database_client(&app_config.application.database_uri).await?;
+            let database = mongo_client.database(&app_config.application.database_name);
+            buraq::utils::database::setup_database(database.clone()).await?;
+            let database = Arc::new(database);
+
+            // Bring stored data up to date; with several instances starting together,
+            // one migrates while the others wait for it
+            let applied = MigrationService::new(database.clone())?.up().await?;
+            if !applied.is_empty() {
+                tracing::info!(?applied, "Applied database migrations");
+            }
+            Ok(AppData {
+                mongo_client: Some(mongo_client),
+                database: Some(database),
+                ..Default::default()
+            })
+        }
+        #[cfg(feature = "sqlite")]
+        Storage::Sqlite(path) => {
+            let database = SqliteDatabase::open(path)?;
+            let applied = database.migrate().await?;
+            if !applied.is_empty() {
+                tracing::info!(?applied, "Applied database migrations");
+            }
+            Ok(AppData {
+                repositories: Some(Repositories::sqlite(&database)),
+                sqlite: Some(database),
+                ..Default::default()
+            })
+        }
+        #[cfg(not(feature = "sqlite"))]
+        Storage::Sqlite(_) => anyhow::bail!("Buraq was built without the sqlite feature"),
+    }
+}
+
+/// Runs the `migrate` subcommand against the configured storage.
+async fn run_migrations(app_config: &AppConfig, command: &ArgMatches) -> Result<(), anyhow::Error> {
+    let status = matches!(command.subcommand(), Some(("status", _)));
+    match &app_config.application.storage {
+        Storage::Mongo => {
+            let mongo_client = create_database_client(&app_config.application.database_uri).await?;
+            let database = mongo_client.database(&app_config.application.database_name);
+            let migration_service = MigrationService::new(Arc::new(database.clone()))?;
+            if status {
+                print_migration_status(migration_service.status().await?);
+            } else {
+                buraq::utils::database::setup_database(database).await?;
+                print_applied_migrations(migration_service.up().await?);
+            }
+        }
+        #[cfg(feature = "sqlite")]
+        Storage::Sqlite(path) => {
+            let database = SqliteDatabase::open(path)?;
+            if status {
+                print_migration_status(database.migration_status().await?);
+            } else {
+                print_applied_migrations(database.migrate().await?);
+            }
+        }
+        #[cfg(not(feature = "sqlite"))]
+        Storage::Sqlite(_) => anyhow::bail!("Buraq was built without the sqlite feature"),
+    }
+    Ok(())
+}
+
+fn print_migration_status(migrations: Vec<MigrationStatus>) {
+    for migration in migrations {
+        let applied_at = migration.applied_at.map_or("pending".to_string(), |at| {
+            at.to_rfc3339_opts(SecondsFormat::Secs, true)
+        });
+        println!(
+            "{:<28} {:<22} {}",
+            migration.name, applied_at, migration.description
+        );
+    }
+}
+
+fn print_applied_migrations(applied: Vec<String>) {
+    println!("Applied {} migration(s)", applied.len());
+    for name in applied {
+        println!("  {}", name);
+    }
+}
@@ -0,0 +1,306 @@
+use crate::errors::AppError;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use mongodb::event::EventHandler;
+use mongodb::event::command::CommandEvent;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
+
+/// Environment label used when a token's environment cannot be resolved.
+pub const UNKNOWN_ENVIRONMENT: &str = "unknown";
+
+/// Route label used for requests that did not match any registered route, so
+/// arbitrary paths do not create new series.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Outcome label of token operations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Success,
+    Refused,
+    NotFound,
+    Error,
+}
+
+impl Outcome {
+    /// Classifies the result of a service call.
+    pub fn of<T>(result: &Result<T, AppError>) -> Self {
+        match result {
+            Ok(_) => Outcome::Success,
+            Err(AppError::Forbidden(_) | AppError::TooManyRequests { .. }) => Outcome::Refused,
+            Err(AppError::NotFound(_)) => Outcome::NotFound,
+            Err(_) => Outcome::Error,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Refused => "refused",
+            Outcome::NotFound => "not_found",
+            Outcome::Error => "error",
+        }
+    }
+}
+
+/// Token lifecycle operations counted by [`Metrics::record_token`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenOperation {
+    Issuance,
+    Introspection,
+    Revocation,
+}
+
+impl TokenOperation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TokenOperation::Issuance => "issuance",
+            TokenOperation::Introspection => "introspection",
+            TokenOperation::Revocation => "revocation",
+        }
+    }
+}
+
+/// Process-wide Prometheus metrics, exposed at `/metrics`.
+pub struct Metrics {
+    registry: Registry,
+    /// HTTP requests by method, route pattern and status code.
+    pub http_requests: IntCounterVec,
+    /// HTTP request latency by method, route pattern and status code.
+    pub http_request_duration: HistogramVec,
+    /// MongoDB command latency by collection (one per repository) and command.
+    pub mongo_operation_duration: HistogramVec,
+    /// Token operations by environment and outcome.
+    pub token_operations: IntCounterVec,
+    /// Key generation latency by algorithm.
+    pub key_generation_duration: HistogramVec,
+    /// Active keys expiring within the warning window, by kind.
+    pub keys_expiring: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("buraq".to_string()), None).unwrap();
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time spent handling HTTP requests",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let mongo_operation_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "mongo_operation_duration_seconds",
+                "Time spent on MongoDB commands",
+            )
+            .buckets(vec![
+                0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+            ]),
+            &["collection", "command", "outcome"],
+        )
+        .unwrap();
+        let token_operations = IntCounterVec::new(
+            Opts::new("token_operations_total", "Access token operations"),
+            &["operation", "environment", "outcome"],
+        )
+        .unwrap();
+        let key_generation_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "key_generation_duration_seconds",
+                "Time spent generating key pairs",
+            )
+            .buckets(vec![0.001, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+            &["algorithm"],
+        )
+        .unwrap();
+        let keys_expiring = IntGaugeVec::new(
+            Opts::new(
+                "keys_expiring",
+                "Enabled keys and tokens expiring within the warning window",
+            ),
+            &["kind"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(mongo_operation_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(token_operations.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(key_generation_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(keys_expiring.clone())).unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            mongo_operation_duration,
+            token_operations,
+            key_generation_duration,
+            keys_expiring,
+        }
+    }
+
+    /// Counts a token operation in the environment the token belongs to.
+    pub fn record_token(&self, operation: TokenOperation, environment: &str, outcome: Outcome) {
+        self.token_operations
+            .with_label_values(&[operation.as_str(), environment, outcome.as_str()])
+            .inc();
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// The process-wide metrics.
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Middleware recording the count and latency of every request, labelled by
+/// the matched route pattern rather than the raw path.
+pub async fn track_requests(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let response = next.call(request).await?;
+
+    let route = response
+        .request()
+        .match_pattern()
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+    let status = response.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    metrics().http_requests.with_label_values(&labels).inc();
+    metrics()
+        .http_request_duration
+        .with_label_values(&labels)
+        .observe(started.elapsed().as_secs_f64());
+    Ok(response)
+}
+
+/// MongoDB command monitor feeding [`Metrics::mongo_operation_duration`].
+///
+/// Started events carry the collection name and completion events carry the
+/// duration, so collections are remembered per in-flight request until the
+/// command completes.
+pub fn mongo_event_handler() -> EventHandler<CommandEvent> {
+    let in_flight: Mutex<HashMap<(u32, i32), String>> = Mutex::new(HashMap::new());
+    EventHandler::callback(move |event: CommandEvent| {
+        let mut in_flight = in_flight.lock().unwrap();
+        let (key, command, duration, outcome) = match event {
+            CommandEvent::Started(event) => {
+                // Commands such as `ping` do not target a collection
+                let collection = event
+                    .command
+                    .get_str(&event.command_name)
+                    .or_else(|_| event.command.get_str("collection"));
+                if let Ok(collection) = collection {
+                    in_flight.insert(
+                        (event.connection.id, event.request_id),
+                        collection.to_string(),
+                    );
+                }
+                return;
+            }
+            CommandEvent::Succeeded(event) => (
+                (event.connection.id, event.request_id),
+                event.command_name,
+                event.duration,
+                Outcome::Success,
+            ),
+            CommandEvent::Failed(event) => (
+                (event.connection.id, event.request_id),
+                event.command_name,
+                event.duration,
+                Outcome::Error,
+            ),
+            _ => return,
+        };
+        if let Some(collection) = in_flight.remove(&key) {
+            metrics()
+                .mongo_operation_duration
+                .with_label_values(&[collection.as_str(), command.as_str(), outcome.as_str()])
+                .observe(duration.as_secs_f64());
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{App, HttpResponse, web};
+
+    #[actix_web::test]
+    async fn test_track_requests_uses_route_pattern() {
+        let app = actix_web::test::init_service(App::new().wrap(from_fn(track_requests)).route(
+            "/metrics-test/{id}",
+            web::get().to(|| async { HttpResponse::Ok().finish() }),
+        ))
+        .await;
+        for id in ["a", "b"] {
+            actix_web::test::TestRequest::get()
+                .uri(&format!("/metrics-test/{}", id))
+                .send_request(&app)
+                .await;
+        }
+        actix_web::test::TestRequest::get()
+            .uri("/no-such-route")
+            .send_request(&app)
+            .await;
+
+        let requests = &metrics().http_requests;
+        assert_eq!(
+            requests
+                .with_label_values(&["GET", "/metrics-test/{id}", "200"])
+                .get(),
+            2
+        );
+        assert!(
+            requests
+                .with_label_values(&["GET", UNMATCHED_ROUTE, "404"])
+                .get()
+                >= 1
+        );
+    }
+
+    #[test]
+    fn test_render_text_format() {
+        metrics().record_token(TokenOperation::Issuance, "env-render", Outcome::Refused);
+
+        let text = metrics().render();
+        assert!(text.contains("# TYPE buraq_token_operations_total counter"));
+        assert!(text.contains(
+            "buraq_token_operations_total{environment=\"env-render\",operation=\"issuance\",outcome=\"refused\"} 1"
+        ));
+    }
+}
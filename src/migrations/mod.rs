@@ -0,0 +1,45 @@
+//! Changes to stored data that [`setup_database`](crate::utils::database::setup_database)
+//! cannot express as indexes.
+//!
+//! Migrations run in the order of their names and each runs once per
+//! database; [`MigrationService`](crate::services::migration_service::MigrationService)
+//! records the applied ones in the `_migrations` collection. A migration that
+//! fails part way is retried in full on the next run, so `up` must be safe to
+//! run again on data it already changed.
+
+use crate::errors::AppError;
+use async_trait::async_trait;
+use mongodb::Database;
+
+pub mod normalize_dates;
+
+#[async_trait]
+pub trait Migration: Send + Sync {
+    /// Unique name, prefixed with a sequence number so names sort in the order
+    /// migrations must run, e.g. `0001_normalize_dates`.
+    fn name(&self) -> &'static str;
+
+    /// One line describing what the migration changes.
+    fn description(&self) -> &'static str;
+
+    async fn up(&self, database: &Database) -> Result<(), AppError>;
+}
+
+/// Every migration, in the order they run.
+pub fn all() -> Vec<Box<dyn Migration>> {
+    vec![Box::new(normalize_dates::NormalizeDates)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_ordered_by_unique_names() {
+        let names: Vec<&str> = all().iter().map(|migration| migration.name()).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(names, sorted);
+    }
+}
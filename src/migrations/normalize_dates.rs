@@ -0,0 +1,174 @@
+use crate::errors::AppError;
+use crate::migrations::Migration;
+use crate::serializers::timestamp;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use mongodb::Database;
+use mongodb::bson::{Bson, Document, doc};
+
+/// Collections holding records with timestamps.
+const COLLECTIONS: [&str; 9] = [
+    "projects",
+    "project_templates",
+    "environments",
+    "project_scopes",
+    "project_access",
+    "service_accounts",
+    "service_account_keys",
+    "server_keys",
+    "access_tokens",
+];
+
+/// Timestamp fields of those records.
+const FIELDS: [&str; 5] = [
+    "created_at",
+    "updated_at",
+    "deleted_at",
+    "expires_at",
+    "locked_until",
+];
+
+/// Rewrites timestamps in the fixed-width form of [`timestamp::format`].
+///
+/// Timestamps are stored as RFC 3339 strings and compared lexically, but
+/// they used to be written as BSON dates, with a `+00:00` offset, or with as
+/// many fractional digits as needed. `.` sorts before `Z`, so
+/// `12:00:00.5Z` sorted before `12:00:00Z`.
+pub struct NormalizeDates;
+
+#[async_trait]
+impl Migration for NormalizeDates {
+    fn name(&self) -> &'static str {
+        "0001_normalize_dates"
+    }
+
+    fn description(&self) -> &'static str {
+        "Store every timestamp in the same fixed-width RFC 3339 form"
+    }
+
+    #[tracing::instrument(skip_all, name = "NormalizeDates::up")]
+    async fn up(&self, database: &Database) -> Result<(), AppError> {
+        let filter = doc! {
+            "$or": FIELDS
+                .iter()
+                .map(|field| doc! { *field: { "$type": ["string", "date"] } })
+                .collect::<Vec<_>>(),
+        };
+        let projection: Document = FIELDS
+            .iter()
+            .map(|field| (field.to_string(), Bson::Int32(1)))
+            .collect();
+
+        for name in COLLECTIONS {
+            let collection = database.collection::<Document>(name);
+            let documents: Vec<Document> = collection
+                .find(filter.clone())
+                .projection(projection.clone())
+                .await?
+                .try_collect()
+                .await?;
+
+            let mut updated = 0;
+            for document in documents {
+                let changes = normalize(&document);
+                if changes.is_empty() {
+                    continue;
+                }
+                if let Some(id) = document.get("_id") {
+                    collection
+                        .update_one(doc! { "_id": id }, doc! { "$set": changes })
+                        .await?;
+                    updated += 1;
+                }
+            }
+            tracing::info!(collection = name, updated, "Normalized timestamps");
+        }
+        Ok(())
+    }
+}
+
+/// The timestamp fields of `document` that are not in canonical form, with
+/// their canonical values.
+fn normalize(document: &Document) -> Document {
+    FIELDS
+        .iter()
+        .filter_map(|field| {
+            let value = document.get(*field)?;
+            let at = match value {
+                Bson::String(value) => DateTime::parse_from_rfc3339(value).ok()?.to_utc(),
+                Bson::DateTime(value) => {
+                    DateTime::<Utc>::from_timestamp_millis(value.timestamp_millis())?
+                }
+                _ => return None,
+            };
+            let canonical = timestamp::to_bson(&at);
+            (canonical != *value).then(|| (field.to_string(), canonical))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Result;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_normalize() {
+        let at = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+        let document = doc! {
+            "_id": 1,
+            "name": "2025-03-01T12:00:00+00:00",
+            "created_at": "2025-03-01T12:00:00Z",
+            "updated_at": "2025-03-01T13:00:00+01:00",
+            "deleted_at": Bson::Null,
+            "expires_at": mongodb::bson::DateTime::from_millis(at.timestamp_millis()),
+            "locked_until": "not a date",
+        };
+        assert_eq!(
+            normalize(&document),
+            doc! {
+                "created_at": "2025-03-01T12:00:00.000000000Z",
+                "updated_at": "2025-03-01T12:00:00.000000000Z",
+                "expires_at": "2025-03-01T12:00:00.000000000Z",
+            }
+        );
+        assert!(normalize(&doc! { "created_at": "2025-03-01T12:00:00.250000000Z" }).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_up() -> Result<()> {
+        let db = setup_test_db("normalize_dates").await?;
+        let projects = db.collection::<Document>("projects");
+        projects
+            .insert_many([
+                doc! { "_id": 1, "created_at": "2025-03-01T12:00:00Z", "updated_at": "2025-03-02T12:00:00.5+00:00" },
+                doc! { "_id": 2, "created_at": "2025-03-01T12:00:00.000000000Z", "updated_at": "2025-03-01T12:00:00.000000000Z" },
+            ])
+            .await?;
+
+        NormalizeDates.up(&db).await?;
+        // Running it again changes nothing
+        NormalizeDates.up(&db).await?;
+
+        let first = projects.find_one(doc! { "_id": 1 }).await?.unwrap();
+        assert_eq!(
+            first.get_str("created_at")?,
+            "2025-03-01T12:00:00.000000000Z"
+        );
+        assert_eq!(
+            first.get_str("updated_at")?,
+            "2025-03-02T12:00:00.500000000Z"
+        );
+        let second = projects.find_one(doc! { "_id": 2 }).await?.unwrap();
+        assert_eq!(
+            second.get_str("updated_at")?,
+            "2025-03-01T12:00:00.000000000Z"
+        );
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+}
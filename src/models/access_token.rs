@@ -1,9 +1,13 @@
+use crate::models::filter::DateRange;
 use crate::serializers::algorithm;
+use crate::utils::security::REDACTED;
 use chrono::{DateTime, Utc};
 use jsonwebtoken::Algorithm;
 use mongodb::bson::uuid::Uuid;
 use mongodb::bson::{Document, doc, from_document, to_document};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::{IntoParams, ToSchema};
 
 /// Represents an access token for API authentication
 ///
@@ -15,17 +19,48 @@ use serde::{Deserialize, Serialize};
 /// - `created_at`: Token creation timestamp
 /// - `enabled`: Whether the token is currently active
 /// - `project_access_id`: Identifier for the project access
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// - `deleted_at`: Timestamp when the token was soft-deleted, if it was
+/// - `deleted_by`: Who soft-deleted the token
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct AccessToken {
+    #[schema(value_type = Option<String>, format = Uuid)]
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<Uuid>,
+    #[schema(value_type = String, format = Uuid)]
     pub project_access_id: Uuid,
     pub key: String,
+    #[schema(value_type = crate::serializers::algorithm::AlgorithmSchema)]
     #[serde(with = "algorithm")]
     pub algorithm: Algorithm,
+    #[serde(with = "crate::serializers::timestamp")]
     pub expires_at: DateTime<Utc>,
+    #[serde(with = "crate::serializers::timestamp")]
     pub created_at: DateTime<Utc>,
     pub enabled: bool,
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+impl fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessToken")
+            .field("id", &self.id)
+            .field("project_access_id", &self.project_access_id)
+            .field("key", &REDACTED)
+            .field("algorithm", &self.algorithm)
+            .field("expires_at", &self.expires_at)
+            .field("created_at", &self.created_at)
+            .field("enabled", &self.enabled)
+            .field("deleted_at", &self.deleted_at)
+            .field("deleted_by", &self.deleted_by)
+            .finish()
+    }
 }
 
 impl From<AccessToken> for Document {
@@ -44,14 +79,19 @@ impl From<Document> for AccessToken {
 ///
 /// This is a read-only version of AccessToken that doesn't include the sensitive key field.
 /// Use this when returning token information to clients where the key shouldn't be exposed.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct AccessTokenRead {
+    #[schema(value_type = Option<String>, format = Uuid)]
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<Uuid>,
+    #[schema(value_type = String, format = Uuid)]
     pub project_access_id: Uuid,
+    #[schema(value_type = crate::serializers::algorithm::AlgorithmSchema)]
     #[serde(with = "algorithm")]
     pub algorithm: Algorithm,
+    #[serde(with = "crate::serializers::timestamp")]
     pub expires_at: DateTime<Utc>,
+    #[serde(with = "crate::serializers::timestamp")]
     pub created_at: DateTime<Utc>,
     pub enabled: bool,
 }
@@ -69,38 +109,87 @@ impl From<AccessToken> for AccessTokenRead {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct AccessTokenUpdatePayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub expires_at: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enabled: Option<bool>,
+    #[schema(value_type = Option<String>, format = Uuid)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_access_id: Option<Uuid>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl fmt::Debug for AccessTokenUpdatePayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessTokenUpdatePayload")
+            .field("key", &self.key.as_ref().map(|_| REDACTED))
+            .field("expires_at", &self.expires_at)
+            .field("enabled", &self.enabled)
+            .field("project_access_id", &self.project_access_id)
+            .finish()
+    }
+}
+
+/// Payload for creating an access token
+///
+/// # Fields
+/// - `algorithm`: Signing algorithm, `tokens.default_algorithm` when omitted
+/// - `expires_at`: Expiry, `tokens.default_ttl_seconds` from now when omitted
+/// - `project_access_id`: Project access the token is issued for
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct AccessTokenCreatePayload {
-    #[serde(with = "algorithm")]
-    pub algorithm: Algorithm,
-    pub expires_at: DateTime<Utc>,
+    #[schema(value_type = Option<crate::serializers::algorithm::AlgorithmSchema>)]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serializers::option_algorithm"
+    )]
+    pub algorithm: Option<Algorithm>,
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[schema(value_type = String, format = Uuid)]
     pub project_access_id: Uuid,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct AccessTokenFilter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
+    #[param(value_type = Option<crate::serializers::algorithm::AlgorithmSchema>)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub algorithm: Option<Algorithm>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_enabled: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_active: Option<bool>,
+    #[param(value_type = Option<String>, format = Uuid)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_access_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_before: Option<DateTime<Utc>>,
+    #[param(value_type = Option<Vec<String>>, style = Form, explode = false)]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serializers::option_uuid_list"
+    )]
+    pub id_in: Option<Vec<Uuid>>,
 }
 
 impl From<AccessTokenFilter> for Document {
@@ -115,19 +204,26 @@ impl From<AccessTokenFilter> for Document {
         if let Some(is_enabled) = value.is_enabled {
             doc.insert("enabled", is_enabled);
         }
-        if let Some(is_active) = value.is_active {
-            if is_active {
-                doc.insert("expires_at", doc! { "$gt": mongodb::bson::DateTime::now() });
-            }
-        }
         if let Some(project_access_id) = value.project_access_id {
             doc.insert("project_access_id", project_access_id);
         }
+        DateRange::new()
+            .after(value.created_after)
+            .before(value.created_before)
+            .apply(&mut doc, "created_at");
+        DateRange::new()
+            .after(value.is_active.filter(|active| *active).map(|_| Utc::now()))
+            .before(value.expires_before)
+            .apply(&mut doc, "expires_at");
+        if let Some(ids) = value.id_in {
+            doc.insert("_id", doc! { "$in": ids });
+        }
         doc
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
 pub enum AccessTokenSortableFields {
     Id,
     Key,
@@ -140,7 +236,7 @@ pub enum AccessTokenSortableFields {
 impl From<AccessTokenSortableFields> for String {
     fn from(value: AccessTokenSortableFields) -> Self {
         match value {
-            AccessTokenSortableFields::Id => "id".to_string(),
+            AccessTokenSortableFields::Id => "_id".to_string(),
             AccessTokenSortableFields::Key => "key".to_string(),
             AccessTokenSortableFields::Algorithm => "algorithm".to_string(),
             AccessTokenSortableFields::ExpiresAt => "expires_at".to_string(),
@@ -169,6 +265,8 @@ mod tests {
             created_at: now,
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         assert_eq!(token.key, "test-key");
@@ -186,6 +284,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let doc: Document = token.clone().into();
@@ -237,6 +337,7 @@ mod tests {
             is_enabled: Some(true),
             is_active: Some(true),
             project_access_id: Some(Uuid::new()),
+            ..Default::default()
         };
 
         let doc: Document = filter.into();
@@ -255,13 +356,13 @@ mod tests {
         let expires_at = Utc::now() + Duration::hours(1);
 
         let payload = AccessTokenCreatePayload {
-            algorithm: Algorithm::HS256,
-            expires_at,
+            algorithm: Some(Algorithm::HS256),
+            expires_at: Some(expires_at),
             project_access_id: project_id,
         };
 
-        assert_eq!(payload.algorithm, Algorithm::HS256);
-        assert_eq!(payload.expires_at, expires_at);
+        assert_eq!(payload.algorithm, Some(Algorithm::HS256));
+        assert_eq!(payload.expires_at, Some(expires_at));
         assert_eq!(payload.project_access_id, project_id);
     }
 
@@ -272,8 +373,8 @@ mod tests {
         let expires_at = Utc::now() + Duration::hours(1);
 
         let payload = AccessTokenCreatePayload {
-            algorithm: Algorithm::RS256,
-            expires_at,
+            algorithm: Some(Algorithm::RS256),
+            expires_at: Some(expires_at),
             project_access_id: project_id,
         };
 
@@ -301,9 +402,15 @@ mod tests {
 
         let payload: AccessTokenCreatePayload = serde_json::from_str(&json).unwrap();
 
-        assert_eq!(payload.algorithm, Algorithm::HS512);
+        assert_eq!(payload.algorithm, Some(Algorithm::HS512));
         assert_eq!(payload.project_access_id, project_id);
-        assert_eq!(payload.expires_at.to_rfc3339(), expires_at_str);
+        assert_eq!(payload.expires_at.unwrap().to_rfc3339(), expires_at_str);
+
+        // Both are filled in from the token defaults when omitted
+        let json = format!(r#"{{"project_access_id": "{project_id}"}}"#);
+        let payload: AccessTokenCreatePayload = serde_json::from_str(&json).unwrap();
+        assert_eq!(payload.algorithm, None);
+        assert_eq!(payload.expires_at, None);
     }
 
     #[test]
@@ -338,6 +445,8 @@ mod tests {
             created_at: now,
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let read_token = AccessTokenRead::from(token.clone());
@@ -375,7 +484,7 @@ mod tests {
 
     #[test]
     fn test_access_token_sortable_fields() {
-        assert_eq!(String::from(AccessTokenSortableFields::Id), "id");
+        assert_eq!(String::from(AccessTokenSortableFields::Id), "_id");
         assert_eq!(String::from(AccessTokenSortableFields::Key), "key");
         assert_eq!(
             String::from(AccessTokenSortableFields::Algorithm),
@@ -0,0 +1,168 @@
+use crate::errors::AppError;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use utoipa::ToSchema;
+
+/// Identifies the client certificate a service account may authenticate with
+/// over mutual TLS (RFC 8705 `tls_client_auth`).
+///
+/// The subject and subject alternative name variants follow the RFC 8705
+/// client metadata of the same names; `sha256_thumbprint` pins one exact
+/// certificate instead. A certificate is accepted when it was issued by a
+/// trusted CA and satisfies any of the account's bindings.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum CertificateBinding {
+    /// Subject distinguished name in RFC 4514 form, e.g. `CN=billing,O=Acme`.
+    SubjectDn(String),
+    /// A `dNSName` subject alternative name.
+    SanDns(String),
+    /// A `uniformResourceIdentifier` subject alternative name.
+    SanUri(String),
+    /// An `iPAddress` subject alternative name, IPv4 or IPv6.
+    SanIp(String),
+    /// An `rfc822Name` subject alternative name.
+    SanEmail(String),
+    /// SHA-256 digest of the DER certificate, as unpadded base64url (the
+    /// `x5t#S256` form) or hex, optionally colon separated.
+    Sha256Thumbprint(String),
+}
+
+/// Checks certificate bindings before they are stored.
+pub fn validate_certificate_bindings(bindings: &[CertificateBinding]) -> Result<(), AppError> {
+    for binding in bindings {
+        let valid = match binding {
+            CertificateBinding::SubjectDn(dn) => parse_dn(dn).is_some(),
+            CertificateBinding::SanDns(value)
+            | CertificateBinding::SanUri(value)
+            | CertificateBinding::SanEmail(value) => !value.trim().is_empty(),
+            CertificateBinding::SanIp(ip) => ip.parse::<IpAddr>().is_ok(),
+            CertificateBinding::Sha256Thumbprint(thumbprint) => {
+                decode_thumbprint(thumbprint).is_some()
+            }
+        };
+        if !valid {
+            return Err(AppError::Validation(format!(
+                "Invalid certificate binding {:?}",
+                binding
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Splits an RFC 4514 distinguished name into `(attribute, value)` pairs, with
+/// attribute types upper-cased and values lower-cased so names compare the way
+/// X.500 case-ignoring matching does. Returns `None` for a malformed name.
+pub fn parse_dn(dn: &str) -> Option<Vec<(String, String)>> {
+    let mut rdns = Vec::new();
+    let mut current = String::new();
+    let mut chars = dn.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => current.push(chars.next()?),
+            ',' => rdns.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    rdns.push(current);
+
+    rdns.into_iter()
+        .map(|rdn| {
+            let (attribute, value) = rdn.split_once('=')?;
+            let (attribute, value) = (attribute.trim(), value.trim());
+            if attribute.is_empty() || value.is_empty() {
+                return None;
+            }
+            Some((attribute.to_uppercase(), value.to_lowercase()))
+        })
+        .collect()
+}
+
+/// Decodes a SHA-256 thumbprint given as unpadded base64url or as hex.
+pub fn decode_thumbprint(thumbprint: &str) -> Option<Vec<u8>> {
+    let hex: String = thumbprint.chars().filter(|c| *c != ':').collect();
+    let digest = if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        (0..64)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?
+    } else {
+        URL_SAFE_NO_PAD.decode(thumbprint.trim()).ok()?
+    };
+    (digest.len() == 32).then_some(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binding_serialization() {
+        let binding = CertificateBinding::SanDns("billing.internal".to_string());
+        let json = serde_json::to_value(&binding).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "type": "san_dns", "value": "billing.internal" })
+        );
+        assert_eq!(
+            serde_json::from_value::<CertificateBinding>(json).unwrap(),
+            binding
+        );
+    }
+
+    #[test]
+    fn test_parse_dn() {
+        assert_eq!(
+            parse_dn("CN=Billing, O=Acme\\, Inc.").unwrap(),
+            [
+                ("CN".to_string(), "billing".to_string()),
+                ("O".to_string(), "acme, inc.".to_string()),
+            ]
+        );
+        assert!(parse_dn("billing").is_none());
+        assert!(parse_dn("CN=").is_none());
+        assert!(parse_dn("CN=billing,").is_none());
+    }
+
+    #[test]
+    fn test_decode_thumbprint() {
+        let digest: Vec<u8> = (0..32).collect();
+        let hex = digest
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":");
+        assert_eq!(decode_thumbprint(&hex).unwrap(), digest);
+        assert_eq!(
+            decode_thumbprint(&URL_SAFE_NO_PAD.encode(&digest)).unwrap(),
+            digest
+        );
+        assert!(decode_thumbprint("abcd").is_none());
+    }
+
+    #[test]
+    fn test_validate_certificate_bindings() {
+        assert!(
+            validate_certificate_bindings(&[
+                CertificateBinding::SubjectDn("CN=billing".to_string()),
+                CertificateBinding::SanIp("10.0.0.1".to_string()),
+                CertificateBinding::SanIp("::1".to_string()),
+            ])
+            .is_ok()
+        );
+        for binding in [
+            CertificateBinding::SubjectDn("billing".to_string()),
+            CertificateBinding::SanIp("10.0.0".to_string()),
+            CertificateBinding::SanDns(" ".to_string()),
+            CertificateBinding::Sha256Thumbprint("not-a-digest".to_string()),
+        ] {
+            assert!(matches!(
+                validate_certificate_bindings(&[binding]),
+                Err(AppError::Validation(_))
+            ));
+        }
+    }
+}
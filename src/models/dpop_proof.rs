@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A DPoP proof that has been accepted, remembered while it is fresh so it
+/// cannot be replayed (RFC 9449 section 11.1)
+///
+/// The id combines the proof key and the proof's `jti`, so a proof accepted
+/// by one instance is refused by every other instance sharing the database.
+/// A TTL index removes proofs once they are too old to be accepted anyway.
+///
+/// # Fields
+/// - `id`: `<jkt>:<jti>`
+/// - `expires_at`: When the proof stops being fresh. A BSON date rather than
+///   the usual RFC 3339 string, because TTL indexes only expire dates
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UsedDpopProof {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub expires_at: mongodb::bson::DateTime,
+}
+
+impl UsedDpopProof {
+    pub fn new(jkt: &str, jti: &str, expires_at: DateTime<Utc>) -> Self {
+        Self {
+            id: format!("{}:{}", jkt, jti),
+            expires_at: mongodb::bson::DateTime::from_millis(expires_at.timestamp_millis()),
+        }
+    }
+}
@@ -1,6 +1,9 @@
+use crate::models::filter::{DateRange, and, contains, starts_with};
+use crate::models::label::{LabelSelector, Labels};
 use chrono::{DateTime, Utc};
 use mongodb::bson::{Document, Uuid, doc, from_document, to_document};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
 /// Represents an environment associated with a project.
 ///
@@ -10,18 +13,35 @@ use serde::{Deserialize, Serialize};
 /// - `name`: Name of the environment
 /// - `description`: Description of the environment
 /// - `enabled`: Whether the environment is active/enabled
+/// - `labels`: Key-value pairs used to organize and select resources
 /// - `created_at`: Timestamp when environment was created
 /// - `updated_at`: Timestamp when environment was last updated
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// - `deleted_at`: Timestamp when the environment was soft-deleted, if it was
+/// - `deleted_by`: Who soft-deleted the environment
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct Environment {
+    #[schema(value_type = Option<String>, format = Uuid)]
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<Uuid>,
+    #[schema(value_type = String, format = Uuid)]
     pub project_id: Uuid,
     pub name: String,
     pub description: String,
     pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
     pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
     pub updated_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
 }
 
 impl From<Environment> for Document {
@@ -36,7 +56,7 @@ impl From<Document> for Environment {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct EnvironmentUpdatePayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
@@ -44,16 +64,40 @@ pub struct EnvironmentUpdatePayload {
     pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Labels>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct EnvironmentFilter {
+    #[param(value_type = Option<String>, format = Uuid)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_id: Option<Uuid>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_since: Option<DateTime<Utc>>,
+    #[param(value_type = Option<Vec<String>>, style = Form, explode = false)]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serializers::option_uuid_list"
+    )]
+    pub id_in: Option<Vec<Uuid>>,
+    #[param(value_type = Option<String>)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label_selector: Option<LabelSelector>,
 }
 
 impl From<EnvironmentFilter> for Document {
@@ -68,11 +112,31 @@ impl From<EnvironmentFilter> for Document {
         if let Some(is_enabled) = value.is_enabled {
             doc.insert("enabled", is_enabled);
         }
+        if let Some(prefix) = value.name_prefix {
+            and(&mut doc, doc! { "name": starts_with(&prefix) });
+        }
+        if let Some(search) = value.search {
+            and(&mut doc, doc! { "name": contains(&search) });
+        }
+        DateRange::new()
+            .after(value.created_after)
+            .before(value.created_before)
+            .apply(&mut doc, "created_at");
+        DateRange::new()
+            .since(value.updated_since)
+            .apply(&mut doc, "updated_at");
+        if let Some(ids) = value.id_in {
+            doc.insert("_id", doc! { "$in": ids });
+        }
+        if let Some(selector) = value.label_selector {
+            selector.apply(&mut doc);
+        }
         doc
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
 pub enum EnvironmentSortableFields {
     Id,
     Name,
@@ -83,7 +147,7 @@ pub enum EnvironmentSortableFields {
 impl From<EnvironmentSortableFields> for String {
     fn from(value: EnvironmentSortableFields) -> Self {
         match value {
-            EnvironmentSortableFields::Id => "id".to_string(),
+            EnvironmentSortableFields::Id => "_id".to_string(),
             EnvironmentSortableFields::Name => "name".to_string(),
             EnvironmentSortableFields::UpdatedAt => "updated_at".to_string(),
             EnvironmentSortableFields::CreatedAt => "created_at".to_string(),
@@ -109,6 +173,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         assert!(env.id.is_none());
@@ -135,6 +202,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         // Test serialization
@@ -169,6 +239,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let id = Uuid::new();
         environment.id = Some(id);
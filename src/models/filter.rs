@@ -0,0 +1,134 @@
+use crate::serializers::timestamp;
+use chrono::{DateTime, Utc};
+use mongodb::bson::{Bson, Document, doc};
+
+/// Matches strings starting with `prefix`, case-sensitively so an index on the
+/// field can still be used.
+pub fn starts_with(prefix: &str) -> Document {
+    doc! { "$regex": format!("^{}", escape_regex(prefix)) }
+}
+
+/// Matches strings containing `text`, ignoring case.
+pub fn contains(text: &str) -> Document {
+    doc! { "$regex": escape_regex(text), "$options": "i" }
+}
+
+/// Adds `condition` to the `$and` clause of `filter`, so several conditions on
+/// the same field can be combined.
+pub fn and(filter: &mut Document, condition: Document) {
+    match filter.get_array_mut("$and") {
+        Ok(conditions) => conditions.push(Bson::Document(condition)),
+        Err(_) => {
+            filter.insert("$and", vec![condition]);
+        }
+    }
+}
+
+/// Escapes regular expression metacharacters so `text` is matched literally.
+fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii_punctuation() || c.is_ascii_whitespace() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Comparison bounds on a single timestamp field, e.g. `{ "$gt": a, "$lt": b }`.
+///
+/// Timestamps are stored as fixed-width RFC 3339 strings, so bounds are
+/// rendered the same way and compared lexically.
+#[derive(Debug, Default)]
+pub struct DateRange(Document);
+
+impl DateRange {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Strictly later than `value`.
+    pub fn after(self, value: Option<DateTime<Utc>>) -> Self {
+        self.bound("$gt", value)
+    }
+
+    /// Later than or equal to `value`.
+    pub fn since(self, value: Option<DateTime<Utc>>) -> Self {
+        self.bound("$gte", value)
+    }
+
+    /// Strictly earlier than `value`.
+    pub fn before(self, value: Option<DateTime<Utc>>) -> Self {
+        self.bound("$lt", value)
+    }
+
+    /// Adds the bounds to `filter` under `field`, unless there are none.
+    pub fn apply(self, filter: &mut Document, field: &str) {
+        if !self.0.is_empty() {
+            filter.insert(field, self.0);
+        }
+    }
+
+    fn bound(mut self, operator: &str, value: Option<DateTime<Utc>>) -> Self {
+        if let Some(value) = value {
+            self.0.insert(operator, timestamp::to_bson(&value));
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_text_matchers_escape_input() {
+        assert_eq!(starts_with("pay"), doc! { "$regex": "^pay" });
+        assert_eq!(
+            contains("a.b (c)"),
+            doc! { "$regex": "a\\.b\\ \\(c\\)", "$options": "i" }
+        );
+    }
+
+    #[test]
+    fn test_and_combines_conditions() {
+        let mut filter = doc! { "enabled": true };
+        and(&mut filter, doc! { "name": starts_with("pay") });
+        and(&mut filter, doc! { "name": contains("ments") });
+        assert_eq!(
+            filter,
+            doc! {
+                "enabled": true,
+                "$and": [
+                    { "name": { "$regex": "^pay" } },
+                    { "name": { "$regex": "ments", "$options": "i" } },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_date_range() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2025, 2, 1, 12, 30, 0).unwrap();
+
+        let mut filter = Document::new();
+        DateRange::new()
+            .after(Some(start))
+            .before(Some(end))
+            .apply(&mut filter, "created_at");
+        DateRange::new()
+            .since(None)
+            .apply(&mut filter, "updated_at");
+
+        assert_eq!(
+            filter,
+            doc! { "created_at": {
+                "$gt": "2025-01-01T00:00:00.000000000Z",
+                "$lt": "2025-02-01T12:30:00.000000000Z",
+            } }
+        );
+    }
+}
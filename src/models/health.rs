@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Whether a check passed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+/// Outcome of checking one dependency
+///
+/// # Fields
+/// - `name`: Dependency that was checked, e.g. `mongo`
+/// - `status`: Whether the dependency is usable
+/// - `latency_ms`: Time the check took, in milliseconds
+/// - `error`: Set when the check failed; the cause itself is only logged
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct DependencyCheck {
+    pub name: String,
+    pub status: HealthStatus,
+    pub latency_ms: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Response of the liveness and readiness probes
+///
+/// # Fields
+/// - `status`: `up` when every check passed
+/// - `checks`: Individual dependency checks, empty for the liveness probe
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct Health {
+    pub status: HealthStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<DependencyCheck>,
+}
+
+impl Health {
+    /// Combines dependency checks, which are all required to pass.
+    pub fn from_checks(checks: Vec<DependencyCheck>) -> Self {
+        let status = if checks.iter().all(|check| check.status == HealthStatus::Up) {
+            HealthStatus::Up
+        } else {
+            HealthStatus::Down
+        };
+        Self { status, checks }
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.status == HealthStatus::Up
+    }
+}
+
+/// Build and runtime details reported by the status endpoint
+///
+/// # Fields
+/// - `version`: Version of the running build
+/// - `started_at`: When the server started
+/// - `uptime_seconds`: Seconds since the server started
+/// - `health`: Readiness of the server and its dependencies
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct ServiceStatus {
+    pub version: String,
+    pub started_at: Option<DateTime<Utc>>,
+    pub uptime_seconds: u64,
+    pub health: Health,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &str, status: HealthStatus) -> DependencyCheck {
+        DependencyCheck {
+            name: name.to_string(),
+            status,
+            latency_ms: 1.5,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_health_from_checks() {
+        let health = Health::from_checks(vec![
+            check("mongo", HealthStatus::Up),
+            check("indexes", HealthStatus::Up),
+        ]);
+        assert!(health.is_up());
+
+        let health = Health::from_checks(vec![
+            check("mongo", HealthStatus::Up),
+            check("secrets", HealthStatus::Down),
+        ]);
+        assert_eq!(health.status, HealthStatus::Down);
+        assert!(Health::from_checks(vec![]).is_up());
+    }
+
+    #[test]
+    fn test_health_serialization() {
+        let health = Health::from_checks(vec![]);
+        assert_eq!(
+            serde_json::to_value(&health).unwrap(),
+            serde_json::json!({ "status": "up" })
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use crate::errors::AppError;
+use crate::models::filter::and;
+use mongodb::bson::{Document, doc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Free-form key/value pairs used to organize resources, e.g. `team=payments`.
+pub type Labels = HashMap<String, String>;
+
+/// Longest label key or value accepted.
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// Checks label keys and values before they are stored.
+///
+/// Keys are 1 to 63 characters of letters, digits, `-`, `_` and `/`; values are
+/// up to 63 characters of letters, digits, `-`, `_` and `.`. Both must start and
+/// end with a letter or digit. Dots are not allowed in keys because labels are
+/// queried by field path.
+pub fn validate_labels(labels: &Labels) -> Result<(), AppError> {
+    for (key, value) in labels {
+        validate_key(key)?;
+        validate_value(value)?;
+    }
+    Ok(())
+}
+
+fn validate_key(key: &str) -> Result<(), AppError> {
+    if key.is_empty() || !is_label_text(key, &['-', '_', '/']) {
+        return Err(AppError::Validation(format!("Invalid label key `{}`", key)));
+    }
+    Ok(())
+}
+
+fn validate_value(value: &str) -> Result<(), AppError> {
+    if !value.is_empty() && !is_label_text(value, &['-', '_', '.']) {
+        return Err(AppError::Validation(format!(
+            "Invalid label value `{}`",
+            value
+        )));
+    }
+    Ok(())
+}
+
+fn is_label_text(text: &str, separators: &[char]) -> bool {
+    text.len() <= MAX_LABEL_LENGTH
+        && text.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && text.ends_with(|c: char| c.is_ascii_alphanumeric())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || separators.contains(&c))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operator {
+    Equals,
+    NotEquals,
+    In,
+    NotIn,
+    Exists,
+    DoesNotExist,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Requirement {
+    key: String,
+    operator: Operator,
+    values: Vec<String>,
+}
+
+impl Requirement {
+    fn parse(text: &str) -> Result<Self, AppError> {
+        let invalid = || AppError::Validation(format!("Invalid label selector `{}`", text));
+        let text = text.trim();
+
+        let (key, operator, values) = if let Some(key) = text.strip_prefix('!') {
+            (key, Operator::DoesNotExist, vec![])
+        } else if let Some((key, values)) = text.split_once(" notin ") {
+            (key, Operator::NotIn, parse_set(values).ok_or_else(invalid)?)
+        } else if let Some((key, values)) = text.split_once(" in ") {
+            (key, Operator::In, parse_set(values).ok_or_else(invalid)?)
+        } else if let Some((key, value)) = text.split_once("!=") {
+            (key, Operator::NotEquals, vec![value.trim().to_string()])
+        } else if let Some((key, value)) = text.split_once("==").or_else(|| text.split_once('=')) {
+            (key, Operator::Equals, vec![value.trim().to_string()])
+        } else {
+            (text, Operator::Exists, vec![])
+        };
+
+        let key = key.trim();
+        validate_key(key).map_err(|_| invalid())?;
+        for value in &values {
+            validate_value(value).map_err(|_| invalid())?;
+        }
+        Ok(Self {
+            key: key.to_string(),
+            operator,
+            values,
+        })
+    }
+
+    fn to_document(&self) -> Document {
+        let field = format!("labels.{}", self.key);
+        match self.operator {
+            Operator::Equals => doc! { field: &self.values[0] },
+            Operator::NotEquals => doc! { field: { "$ne": &self.values[0] } },
+            Operator::In => doc! { field: { "$in": &self.values } },
+            Operator::NotIn => doc! { field: { "$nin": &self.values } },
+            Operator::Exists => doc! { field: { "$exists": true } },
+            Operator::DoesNotExist => doc! { field: { "$exists": false } },
+        }
+    }
+
+    /// Matches the way the document from `to_document` does: `!=` and
+    /// `notin` also match when the label is absent.
+    fn matches(&self, labels: &Labels) -> bool {
+        let value = labels.get(&self.key);
+        match self.operator {
+            Operator::Equals => value == Some(&self.values[0]),
+            Operator::NotEquals => value != Some(&self.values[0]),
+            Operator::In => value.is_some_and(|value| self.values.contains(value)),
+            Operator::NotIn => !value.is_some_and(|value| self.values.contains(value)),
+            Operator::Exists => value.is_some(),
+            Operator::DoesNotExist => value.is_none(),
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.operator {
+            Operator::Equals => write!(f, "{}={}", self.key, self.values[0]),
+            Operator::NotEquals => write!(f, "{}!={}", self.key, self.values[0]),
+            Operator::In => write!(f, "{} in ({})", self.key, self.values.join(",")),
+            Operator::NotIn => write!(f, "{} notin ({})", self.key, self.values.join(",")),
+            Operator::Exists => write!(f, "{}", self.key),
+            Operator::DoesNotExist => write!(f, "!{}", self.key),
+        }
+    }
+}
+
+/// Parses the `(a, b)` value set of an `in`/`notin` requirement.
+fn parse_set(text: &str) -> Option<Vec<String>> {
+    let inner = text.trim().strip_prefix('(')?.strip_suffix(')')?;
+    Some(
+        inner
+            .split(',')
+            .map(|value| value.trim().to_string())
+            .collect(),
+    )
+}
+
+/// A Kubernetes-style label selector, e.g. `team=payments,tier!=critical`.
+///
+/// Requirements are comma-separated and must all match. Supported forms are
+/// `key=value` (or `==`), `key!=value`, `key in (a,b)`, `key notin (a,b)`,
+/// `key` (label present) and `!key` (label absent).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LabelSelector {
+    requirements: Vec<Requirement>,
+}
+
+impl LabelSelector {
+    /// Adds the selector's requirements to `filter`.
+    pub fn apply(&self, filter: &mut Document) {
+        for requirement in &self.requirements {
+            and(filter, requirement.to_document());
+        }
+    }
+
+    /// Whether `labels` satisfy every requirement, for records already loaded.
+    pub fn matches(&self, labels: &Labels) -> bool {
+        self.requirements
+            .iter()
+            .all(|requirement| requirement.matches(labels))
+    }
+}
+
+impl FromStr for LabelSelector {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        // Split on commas outside of `in (...)` value sets
+        let mut requirements = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for (index, c) in value.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    requirements.push(Requirement::parse(&value[start..index])?);
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        if !value[start..].trim().is_empty() || !requirements.is_empty() {
+            requirements.push(Requirement::parse(&value[start..])?);
+        }
+        Ok(Self { requirements })
+    }
+}
+
+impl fmt::Display for LabelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let requirements: Vec<String> = self.requirements.iter().map(|r| r.to_string()).collect();
+        write!(f, "{}", requirements.join(","))
+    }
+}
+
+impl Serialize for LabelSelector {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for LabelSelector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_labels() {
+        let labels = Labels::from([
+            ("team".to_string(), "payments".to_string()),
+            ("cost-center/id".to_string(), "cc_1.2".to_string()),
+            ("empty".to_string(), String::new()),
+        ]);
+        assert!(validate_labels(&labels).is_ok());
+
+        for (key, value) in [("", "a"), ("a.b", "c"), ("-team", "a"), ("team", "a b")] {
+            let labels = Labels::from([(key.to_string(), value.to_string())]);
+            assert!(validate_labels(&labels).is_err(), "{}={}", key, value);
+        }
+        let labels = Labels::from([("team".to_string(), "a".repeat(64))]);
+        assert!(validate_labels(&labels).is_err());
+    }
+
+    #[test]
+    fn test_label_selector_to_document() {
+        let selector: LabelSelector =
+            "team=payments, tier!=critical,env in (dev, qa),region notin (eu),owner,!legacy"
+                .parse()
+                .unwrap();
+
+        let mut filter = doc! { "enabled": true };
+        selector.apply(&mut filter);
+        assert_eq!(
+            filter,
+            doc! {
+                "enabled": true,
+                "$and": [
+                    { "labels.team": "payments" },
+                    { "labels.tier": { "$ne": "critical" } },
+                    { "labels.env": { "$in": ["dev", "qa"] } },
+                    { "labels.region": { "$nin": ["eu"] } },
+                    { "labels.owner": { "$exists": true } },
+                    { "labels.legacy": { "$exists": false } },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_label_selector_matches() {
+        let selector: LabelSelector = "team=payments,tier!=critical,env in (dev,qa),!legacy"
+            .parse()
+            .unwrap();
+        let labels = |pairs: &[(&str, &str)]| -> Labels {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+
+        assert!(selector.matches(&labels(&[("team", "payments"), ("env", "qa")])));
+        assert!(!selector.matches(&labels(&[("team", "payments"), ("env", "prod")])));
+        assert!(!selector.matches(&labels(&[
+            ("team", "payments"),
+            ("env", "qa"),
+            ("tier", "critical")
+        ])));
+        assert!(!selector.matches(&labels(&[
+            ("team", "payments"),
+            ("env", "qa"),
+            ("legacy", "")
+        ])));
+        assert!(LabelSelector::default().matches(&Labels::new()));
+    }
+
+    #[test]
+    fn test_label_selector_round_trip() {
+        let selector: LabelSelector = "team==payments,env in (dev,qa),!legacy".parse().unwrap();
+        assert_eq!(
+            selector.to_string(),
+            "team=payments,env in (dev,qa),!legacy"
+        );
+        assert_eq!(
+            selector.to_string().parse::<LabelSelector>().unwrap(),
+            selector
+        );
+
+        let empty: LabelSelector = "".parse().unwrap();
+        assert_eq!(empty, LabelSelector::default());
+    }
+
+    #[test]
+    fn test_label_selector_rejects_invalid_input() {
+        for value in ["=payments", "env in dev", "a.b=c", "team=pay ments", "a,,b"] {
+            assert!(value.parse::<LabelSelector>().is_err(), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::models::certificate_binding::CertificateBinding;
+use crate::models::label::{LabelSelector, Labels};
+use crate::utils::security::REDACTED;
+use mongodb::bson::uuid::Uuid;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::{IntoParams, ToSchema};
+
+fn enabled() -> bool {
+    true
+}
+
+/// Desired configuration, declared by name instead of by id.
+///
+/// # Fields
+/// - `projects`: Projects, each with its environments and scopes
+/// - `service_accounts`: Service accounts, identified by `user`
+/// - `access`: Grants of project scopes in an environment to a service account
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub projects: Vec<ProjectManifest>,
+    #[serde(default)]
+    pub service_accounts: Vec<ServiceAccountManifest>,
+    #[serde(default)]
+    pub access: Vec<AccessManifest>,
+}
+
+/// A project as declared in a manifest, identified by its name.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectManifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default)]
+    pub environments: Vec<EnvironmentManifest>,
+    #[serde(default)]
+    pub scopes: Vec<ScopeManifest>,
+}
+
+/// An environment as declared in a manifest, identified by its name within
+/// the project.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentManifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+}
+
+/// A project scope as declared in a manifest, identified by its name within
+/// the project.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ScopeManifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+}
+
+/// A service account as declared in a manifest, identified by its `user`.
+///
+/// Without a `secret`, a new account gets a generated one, returned once in
+/// the apply response, and an existing account keeps its own.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceAccountManifest {
+    pub user: String,
+    pub email: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default)]
+    pub certificate_bindings: Vec<CertificateBinding>,
+}
+
+impl fmt::Debug for ServiceAccountManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceAccountManifest")
+            .field("user", &self.user)
+            .field("email", &self.email)
+            .field("secret", &self.secret.as_ref().map(|_| REDACTED))
+            .field("enabled", &self.enabled)
+            .field("labels", &self.labels)
+            .field("certificate_bindings", &self.certificate_bindings)
+            .finish()
+    }
+}
+
+/// Access of a service account to an environment, as declared in a manifest.
+///
+/// Identified by project, environment and service account, since an account
+/// has at most one access per environment.
+///
+/// # Fields
+/// - `project`, `environment`: Names of the environment and its project
+/// - `service_account`: `user` of the service account
+/// - `name`: Name of the access, `<service account>-<environment>` by default
+/// - `scopes`: Names of the project scopes granted
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AccessManifest {
+    pub project: String,
+    pub environment: String,
+    pub service_account: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+}
+
+impl AccessManifest {
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{}-{}", self.service_account, self.environment))
+    }
+}
+
+/// Query parameters of an apply.
+///
+/// # Fields
+/// - `dry_run`: Compute and return the plan without changing anything
+/// - `prune`: Also delete resources the manifest does not declare
+/// - `selector`: Only prune resources whose labels match this selector
+/// - `all`: Prune every undeclared resource; required to prune without a selector
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ApplyQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub prune: bool,
+    #[param(value_type = Option<String>)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selector: Option<LabelSelector>,
+    #[serde(default)]
+    pub all: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeAction {
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    Project,
+    Environment,
+    Scope,
+    ServiceAccount,
+    Access,
+}
+
+/// A field whose value changes. Absent values are `null`; secrets are redacted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: serde_json::Value,
+    pub to: serde_json::Value,
+}
+
+/// One step of a plan.
+///
+/// # Fields
+/// - `action`: Whether the resource is created, updated or deleted
+/// - `kind`: Kind of resource
+/// - `name`: Path of names identifying it, e.g. `billing/production`
+/// - `id`: Id of the existing resource, for updates and deletes
+/// - `diff`: Fields that change; every declared field for a creation
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct Change {
+    pub action: ChangeAction,
+    pub kind: ResourceKind,
+    pub name: String,
+    #[schema(value_type = Option<String>, format = Uuid)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diff: Vec<FieldChange>,
+}
+
+/// Secret generated for a service account the apply created.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct GeneratedSecret {
+    pub service_account: String,
+    pub secret: String,
+}
+
+impl fmt::Debug for GeneratedSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeneratedSecret")
+            .field("service_account", &self.service_account)
+            .field("secret", &REDACTED)
+            .finish()
+    }
+}
+
+/// Outcome of an apply.
+///
+/// # Fields
+/// - `applied`: `false` for a dry run
+/// - `changes`: The plan, in the order it is carried out; empty when the
+///   current state already matches the manifest
+/// - `generated_secrets`: Secrets of the service accounts created without one
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ApplyResponse {
+    pub applied: bool,
+    pub changes: Vec<Change>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub generated_secrets: Vec<GeneratedSecret>,
+}
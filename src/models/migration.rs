@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A migration that has been applied, as recorded in the `_migrations` collection.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AppliedMigration {
+    #[serde(rename = "_id")]
+    pub name: String,
+    pub applied_at: DateTime<Utc>,
+}
+
+/// Whether a known migration has been applied.
+///
+/// # Fields
+/// - `name`: Name of the migration, which also orders it
+/// - `applied_at`: When it was applied, absent while it is pending
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MigrationStatus {
+    pub name: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+/// Lock held by the instance running migrations, so only one migrates at a time.
+///
+/// `expires_at` lets another instance take over the lock when its holder died
+/// without releasing it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MigrationLock {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub owner: String,
+    pub expires_at: mongodb::bson::DateTime,
+}
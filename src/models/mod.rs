@@ -1,10 +1,21 @@
 pub mod access_token;
+pub mod certificate_binding;
+pub mod dpop_proof;
 pub mod environment;
+pub mod filter;
+pub mod health;
+pub mod label;
+pub mod manifest;
+pub mod migration;
 pub mod pagination;
 pub mod project;
 pub mod project_access;
+pub mod project_bundle;
 pub mod project_scope;
+pub mod project_template;
+pub mod rate_limit;
 pub mod server_key;
 pub mod service_account;
 pub mod service_account_key;
 pub mod sort;
+pub mod token;
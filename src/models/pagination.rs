@@ -1,38 +1,196 @@
+use crate::errors::AppError;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use mongodb::bson::{Bson, Document, doc, to_document};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+/// Number of items returned when the client does not ask for a page size.
+pub const DEFAULT_LIMIT: u32 = 10;
+/// Largest page size a client may request.
+pub const MAX_LIMIT: u32 = 100;
+
+/// Query parameters selecting a slice of a list.
+///
+/// Either `page` (offset pagination) or `cursor` (keyset pagination) is used.
+/// When a cursor is given the page number is ignored.
+///
+/// # Fields
+/// - `page`: 1-based page number
+/// - `limit`: Page size, capped at [`MAX_LIMIT`]
+/// - `cursor`: Opaque cursor returned as `next_cursor` by a previous request
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Pagination {
     pub page: Option<u32>,
     pub limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
 }
 
 impl Pagination {
+    /// The page number in use, or `None` when paginating with a cursor.
+    pub fn page_number(&self) -> Option<u32> {
+        match self.cursor {
+            Some(_) => None,
+            None => Some(self.page.unwrap_or(1).max(1)),
+        }
+    }
+
     pub fn skip(&self) -> u64 {
-        if self.page.is_none() || self.limit.is_none() {
-            0
-        } else {
-            ((self.page.unwrap() - 1) * self.limit.unwrap()) as u64
+        match self.page_number() {
+            Some(page) => (page as u64 - 1) * self.limit() as u64,
+            None => 0,
         }
     }
 
     pub fn limit(&self) -> i64 {
-        if self.limit.is_none() {
-            10
-        } else {
-            self.limit.unwrap() as i64
+        self.limit
+            .map_or(DEFAULT_LIMIT, |limit| limit.clamp(1, MAX_LIMIT)) as i64
+    }
+}
+
+/// A page of results together with what a client needs to fetch the rest.
+///
+/// # Fields
+/// - `items`: Records on this page
+/// - `total`: Number of records matching the filter across all pages
+/// - `page`: Current page number, `null` when paginating with a cursor
+/// - `limit`: Page size that was applied
+/// - `next_cursor`: Cursor for the following page, `null` on the last page
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub page: Option<u32>,
+    pub limit: u32,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Converts the items, keeping the pagination details.
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            page: self.page,
+            limit: self.limit,
+            next_cursor: self.next_cursor,
         }
     }
+
+    /// Number of pages needed to list every matching record.
+    pub fn page_count(&self) -> u64 {
+        self.total.div_ceil(self.limit.max(1) as u64).max(1)
+    }
+}
+
+/// Position in a sorted list, encoded into the opaque `next_cursor` string.
+///
+/// The cursor records the sort it was produced for and the sort-key values of
+/// the last item returned, so the next page starts strictly after that item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    sort: Document,
+    after: Document,
+}
+
+impl Cursor {
+    /// Builds the cursor pointing after `item` for the given sort document.
+    pub fn after<T: Serialize>(sort: &Document, item: &T) -> Result<Self, AppError> {
+        let item = to_document(item)?;
+        let mut after = Document::new();
+        for field in sort.keys() {
+            after.insert(field, item.get(field).cloned().unwrap_or(Bson::Null));
+        }
+        Ok(Self {
+            sort: sort.clone(),
+            after,
+        })
+    }
+
+    pub fn encode(&self) -> Result<String, AppError> {
+        let mut bytes = Vec::new();
+        doc! { "s": &self.sort, "a": &self.after }
+            .to_writer(&mut bytes)
+            .map_err(anyhow::Error::from)?;
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    pub fn decode(value: &str) -> Result<Self, AppError> {
+        let invalid = || AppError::Validation("Invalid pagination cursor".to_string());
+        let bytes = URL_SAFE_NO_PAD.decode(value).map_err(|_| invalid())?;
+        let document = Document::from_reader(bytes.as_slice()).map_err(|_| invalid())?;
+        let sort = document.get_document("s").map_err(|_| invalid())?.clone();
+        let after = document.get_document("a").map_err(|_| invalid())?.clone();
+        // Values end up in equality and comparison positions of the filter,
+        // where a document could smuggle in operators and a regex would match
+        // by pattern, so only plain values are accepted.
+        if !after.values().all(is_plain_value) {
+            return Err(invalid());
+        }
+        Ok(Self { sort, after })
+    }
+
+    /// Filter matching the records that sort strictly after this cursor.
+    ///
+    /// Fails when the cursor was produced for a different sort order.
+    pub fn filter(&self, sort: &Document) -> Result<Document, AppError> {
+        if &self.sort != sort {
+            return Err(AppError::Validation(
+                "Pagination cursor does not match the requested sort".to_string(),
+            ));
+        }
+
+        let fields: Vec<(&String, &Bson)> = sort.iter().collect();
+        let mut branches = Vec::new();
+        for (position, (field, direction)) in fields.iter().enumerate() {
+            let mut branch = Document::new();
+            for (previous, _) in &fields[..position] {
+                branch.insert(*previous, self.value(previous));
+            }
+            let operator = match direction.as_i32() {
+                Some(-1) => "$lt",
+                _ => "$gt",
+            };
+            branch.insert(*field, doc! { operator: self.value(field) });
+            branches.push(branch);
+        }
+        Ok(doc! { "$or": branches })
+    }
+
+    fn value(&self, field: &str) -> Bson {
+        self.after.get(field).cloned().unwrap_or(Bson::Null)
+    }
+}
+
+/// Whether `value` compares as itself in a filter.
+fn is_plain_value(value: &Bson) -> bool {
+    !matches!(
+        value,
+        Bson::Document(_)
+            | Bson::Array(_)
+            | Bson::RegularExpression(_)
+            | Bson::JavaScriptCode(_)
+            | Bson::JavaScriptCodeWithScope(_)
+            | Bson::DbPointer(_)
+            | Bson::MinKey
+            | Bson::MaxKey
+            | Bson::Undefined
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mongodb::bson::uuid::Uuid;
 
     #[test]
     fn test_pagination_creation() {
         let pagination = Pagination {
             page: Some(1),
             limit: Some(10),
+            cursor: None,
         };
         assert_eq!(pagination.page, Some(1));
         assert_eq!(pagination.limit, Some(10));
@@ -43,6 +201,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(0),
             limit: Some(0),
+            cursor: None,
         };
         assert_eq!(pagination.page, Some(0));
         assert_eq!(pagination.limit, Some(0));
@@ -53,8 +212,131 @@ mod tests {
         let pagination = Pagination {
             page: Some(999999),
             limit: Some(999999),
+            cursor: None,
         };
         assert_eq!(pagination.page, Some(999999));
         assert_eq!(pagination.limit, Some(999999));
     }
+
+    #[test]
+    fn test_pagination_skip_and_limit() {
+        let pagination = Pagination {
+            page: Some(3),
+            limit: Some(20),
+            cursor: None,
+        };
+        assert_eq!(pagination.skip(), 40);
+        assert_eq!(pagination.limit(), 20);
+
+        // Page 0 is treated as the first page instead of underflowing
+        let pagination = Pagination {
+            page: Some(0),
+            limit: Some(0),
+            cursor: None,
+        };
+        assert_eq!(pagination.skip(), 0);
+        assert_eq!(pagination.limit(), 1);
+
+        let pagination = Pagination {
+            page: Some(2),
+            limit: Some(999999),
+            cursor: None,
+        };
+        assert_eq!(pagination.limit(), MAX_LIMIT as i64);
+        assert_eq!(pagination.skip(), MAX_LIMIT as u64);
+
+        let pagination = Pagination::default();
+        assert_eq!(pagination.page_number(), Some(1));
+        assert_eq!(pagination.limit(), DEFAULT_LIMIT as i64);
+    }
+
+    #[test]
+    fn test_cursor_ignores_page() {
+        let pagination = Pagination {
+            page: Some(5),
+            limit: Some(10),
+            cursor: Some("cursor".to_string()),
+        };
+        assert_eq!(pagination.page_number(), None);
+        assert_eq!(pagination.skip(), 0);
+    }
+
+    #[test]
+    fn test_page_map_and_count() {
+        let page = Page {
+            items: vec![1, 2, 3],
+            total: 25,
+            page: Some(1),
+            limit: 10,
+            next_cursor: None,
+        };
+        assert_eq!(page.page_count(), 3);
+
+        let page = page.map(|item| item.to_string());
+        assert_eq!(page.items, vec!["1", "2", "3"]);
+        assert_eq!(page.total, 25);
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        #[derive(Serialize)]
+        struct Item {
+            #[serde(rename = "_id")]
+            id: Uuid,
+            name: String,
+        }
+        let item = Item {
+            id: Uuid::new(),
+            name: "beta".to_string(),
+        };
+        let sort = doc! { "name": -1, "_id": 1 };
+
+        let cursor = Cursor::after(&sort, &item).unwrap();
+        let decoded = Cursor::decode(&cursor.encode().unwrap()).unwrap();
+        assert_eq!(decoded, cursor);
+
+        let filter = decoded.filter(&sort).unwrap();
+        assert_eq!(
+            filter,
+            doc! { "$or": [
+                { "name": { "$lt": "beta" } },
+                { "name": "beta", "_id": { "$gt": item.id } },
+            ] }
+        );
+    }
+
+    #[test]
+    fn test_cursor_rejects_other_sort_and_garbage() {
+        let cursor = Cursor::after(&doc! { "_id": 1 }, &doc! { "_id": 1 }).unwrap();
+        assert!(matches!(
+            cursor.filter(&doc! { "name": 1, "_id": 1 }),
+            Err(AppError::Validation(_))
+        ));
+        assert!(matches!(
+            Cursor::decode("not a cursor"),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_cursor_rejects_operator_values() {
+        let sort = doc! { "name": 1 };
+        for value in [
+            Bson::Document(doc! { "$ne": null }),
+            Bson::Array(vec![Bson::Int32(1)]),
+            Bson::RegularExpression(mongodb::bson::Regex {
+                pattern: ".*".to_string(),
+                options: String::new(),
+            }),
+        ] {
+            let cursor = Cursor {
+                sort: sort.clone(),
+                after: doc! { "name": value },
+            };
+            assert!(matches!(
+                Cursor::decode(&cursor.encode().unwrap()),
+                Err(AppError::Validation(_))
+            ));
+        }
+    }
 }
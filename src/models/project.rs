@@ -1,7 +1,10 @@
+use crate::models::filter::{DateRange, and, contains, starts_with};
+use crate::models::label::{LabelSelector, Labels};
 use chrono::{DateTime, Utc};
 use mongodb::bson::uuid::Uuid;
 use mongodb::bson::{Document, doc, from_document, to_document};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
 /// Represents a project with metadata and timestamps.
 ///
@@ -9,17 +12,33 @@ use serde::{Deserialize, Serialize};
 /// - `id`: Unique identifier for the project (MongoDB Uuid)
 /// - `name`: Name of the project
 /// - `description`: Description of the project
+/// - `labels`: Key-value pairs used to organize and select resources
 /// - `created_at`: Timestamp when project was created
 /// - `updated_at`: Timestamp when project was last updated
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// - `deleted_at`: Timestamp when the project was soft-deleted, if it was
+/// - `deleted_by`: Who soft-deleted the project
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct Project {
+    #[schema(value_type = Option<String>, format = Uuid)]
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<Uuid>,
     pub name: String,
     pub description: String,
     pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
     pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
     pub updated_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
 }
 
 impl From<Project> for Document {
@@ -34,7 +53,7 @@ impl From<Document> for Project {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct ProjectUpdatePayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
@@ -42,14 +61,37 @@ pub struct ProjectUpdatePayload {
     pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Labels>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ProjectFilter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_since: Option<DateTime<Utc>>,
+    #[param(value_type = Option<Vec<String>>, style = Form, explode = false)]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serializers::option_uuid_list"
+    )]
+    pub id_in: Option<Vec<Uuid>>,
+    #[param(value_type = Option<String>)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label_selector: Option<LabelSelector>,
 }
 
 impl From<ProjectFilter> for Document {
@@ -61,11 +103,93 @@ impl From<ProjectFilter> for Document {
         if let Some(is_enabled) = value.is_enabled {
             doc.insert("enabled", is_enabled);
         }
+        if let Some(prefix) = value.name_prefix {
+            and(&mut doc, doc! { "name": starts_with(&prefix) });
+        }
+        if let Some(search) = value.search {
+            and(&mut doc, doc! { "name": contains(&search) });
+        }
+        DateRange::new()
+            .after(value.created_after)
+            .before(value.created_before)
+            .apply(&mut doc, "created_at");
+        DateRange::new()
+            .since(value.updated_since)
+            .apply(&mut doc, "updated_at");
+        if let Some(ids) = value.id_in {
+            doc.insert("_id", doc! { "$in": ids });
+        }
+        if let Some(selector) = value.label_selector {
+            selector.apply(&mut doc);
+        }
         doc
     }
 }
 
+/// Query parameters accepted when creating a project.
+///
+/// # Fields
+/// - `template`: Name of the [template](crate::models::project_template::ProjectTemplate)
+///   to scaffold the project's environments, scopes and server keys from
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProjectCreateQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+/// Query parameters accepted when deleting a project.
+///
+/// # Fields
+/// - `cascade`: When `true`, the whole resource tree below the project is deleted with it
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProjectDeleteQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cascade: Option<bool>,
+}
+
+/// Identifiers of the resources that hang off a project.
+///
+/// Used both to report the dependents that block a plain delete and to
+/// summarise what a cascading delete removed.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, ToSchema)]
+pub struct ProjectDependents {
+    #[schema(value_type = Vec<String>)]
+    pub environments: Vec<Uuid>,
+    #[schema(value_type = Vec<String>)]
+    pub project_scopes: Vec<Uuid>,
+    #[schema(value_type = Vec<String>)]
+    pub project_accesses: Vec<Uuid>,
+    #[schema(value_type = Vec<String>)]
+    pub server_keys: Vec<Uuid>,
+    #[schema(value_type = Vec<String>)]
+    pub access_tokens: Vec<Uuid>,
+}
+
+impl ProjectDependents {
+    pub fn is_empty(&self) -> bool {
+        self.environments.is_empty()
+            && self.project_scopes.is_empty()
+            && self.project_accesses.is_empty()
+            && self.server_keys.is_empty()
+            && self.access_tokens.is_empty()
+    }
+}
+
+/// Result of a project delete request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectDeleteOutcome {
+    /// The project was deleted along with the listed dependents.
+    Deleted(ProjectDependents),
+    /// The project was not deleted because other resources still reference it.
+    Blocked(ProjectDependents),
+    /// No project exists with the given id.
+    NotFound,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
 pub enum ProjectSortableFields {
     Id,
     Name,
@@ -76,7 +200,7 @@ pub enum ProjectSortableFields {
 impl From<ProjectSortableFields> for String {
     fn from(value: ProjectSortableFields) -> Self {
         match value {
-            ProjectSortableFields::Id => "id".to_string(),
+            ProjectSortableFields::Id => "_id".to_string(),
             ProjectSortableFields::Name => "name".to_string(),
             ProjectSortableFields::UpdatedAt => "updated_at".to_string(),
             ProjectSortableFields::CreatedAt => "created_at".to_string(),
@@ -100,6 +224,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         // Verify fields are set correctly
@@ -124,6 +251,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         // Test serialization
@@ -155,6 +285,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let id = Uuid::new();
         project.id = Some(id);
@@ -169,4 +302,36 @@ mod tests {
         assert_eq!(converted.created_at, project.created_at);
         assert_eq!(converted.updated_at, project.updated_at);
     }
+
+    #[test]
+    fn test_project_dependents_is_empty() {
+        let mut dependents = ProjectDependents::default();
+        assert!(dependents.is_empty());
+
+        dependents.access_tokens.push(Uuid::new());
+        assert!(!dependents.is_empty());
+    }
+
+    #[test]
+    fn test_project_filter_from_query() {
+        let id = Uuid::new();
+        let query = format!(
+            "search=payments&name_prefix=Pay&created_after=2025-01-01T00:00:00Z&id_in={}",
+            id
+        );
+        let filter: ProjectFilter = serde_urlencoded::from_str(&query).unwrap();
+
+        let doc: Document = filter.into();
+        assert_eq!(
+            doc,
+            doc! {
+                "$and": [
+                    { "name": { "$regex": "^Pay" } },
+                    { "name": { "$regex": "payments", "$options": "i" } },
+                ],
+                "created_at": { "$gt": "2025-01-01T00:00:00.000000000Z" },
+                "_id": { "$in": [id] },
+            }
+        );
+    }
 }
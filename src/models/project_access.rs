@@ -1,7 +1,10 @@
+use crate::models::filter::{DateRange, and, contains, starts_with};
+use crate::models::label::{LabelSelector, Labels};
 use chrono::{DateTime, Utc};
 use mongodb::bson::uuid::Uuid;
-use mongodb::bson::{Document, from_document, to_document};
+use mongodb::bson::{Document, doc, from_document, to_document};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
 /// Represents access control configuration for a project environment.
 ///
@@ -12,20 +15,39 @@ use serde::{Deserialize, Serialize};
 /// - `service_account_id`: Foreign key reference to the associated service account
 /// - `project_scopes`: Array of project scope IDs this access is granted
 /// - `enabled`: Whether this access configuration is enabled
+/// - `labels`: Key-value pairs used to organize and select resources
 /// - `created_at`: Timestamp when access was created
 /// - `updated_at`: Timestamp when access was last updated
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// - `deleted_at`: Timestamp when the access was soft-deleted, if it was
+/// - `deleted_by`: Who soft-deleted the access
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct ProjectAccess {
+    #[schema(value_type = Option<String>, format = Uuid)]
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<Uuid>,
     pub name: String,
+    #[schema(value_type = String, format = Uuid)]
     pub environment_id: Uuid,
+    #[schema(value_type = Option<String>, format = Uuid)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub service_account_id: Option<Uuid>,
+    #[schema(value_type = Vec<String>)]
     pub project_scopes: Vec<Uuid>,
     pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
     pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
     pub updated_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
 }
 
 impl From<ProjectAccess> for Document {
@@ -40,26 +62,53 @@ impl From<Document> for ProjectAccess {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct ProjectAccessUpdatePayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[schema(value_type = Option<Vec<String>>)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_scopes: Option<Vec<Uuid>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Labels>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ProjectAccessFilter {
+    #[param(value_type = Option<String>, format = Uuid)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub environment_id: Option<Uuid>,
+    #[param(value_type = Option<String>, format = Uuid)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub service_account_id: Option<Uuid>,
+    #[param(value_type = Option<Vec<String>>)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_scopes: Option<Vec<Uuid>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_since: Option<DateTime<Utc>>,
+    #[param(value_type = Option<Vec<String>>, style = Form, explode = false)]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serializers::option_uuid_list"
+    )]
+    pub id_in: Option<Vec<Uuid>>,
+    #[param(value_type = Option<String>)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label_selector: Option<LabelSelector>,
 }
 
 impl From<ProjectAccessFilter> for Document {
@@ -77,11 +126,31 @@ impl From<ProjectAccessFilter> for Document {
         if let Some(is_enabled) = value.is_enabled {
             doc.insert("enabled", is_enabled);
         }
+        if let Some(prefix) = value.name_prefix {
+            and(&mut doc, doc! { "name": starts_with(&prefix) });
+        }
+        if let Some(search) = value.search {
+            and(&mut doc, doc! { "name": contains(&search) });
+        }
+        DateRange::new()
+            .after(value.created_after)
+            .before(value.created_before)
+            .apply(&mut doc, "created_at");
+        DateRange::new()
+            .since(value.updated_since)
+            .apply(&mut doc, "updated_at");
+        if let Some(ids) = value.id_in {
+            doc.insert("_id", doc! { "$in": ids });
+        }
+        if let Some(selector) = value.label_selector {
+            selector.apply(&mut doc);
+        }
         doc
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
 pub enum ProjectAccessSortableFields {
     Id,
     Name,
@@ -92,7 +161,7 @@ pub enum ProjectAccessSortableFields {
 impl From<ProjectAccessSortableFields> for String {
     fn from(value: ProjectAccessSortableFields) -> Self {
         match value {
-            ProjectAccessSortableFields::Id => "id".to_string(),
+            ProjectAccessSortableFields::Id => "_id".to_string(),
             ProjectAccessSortableFields::Name => "name".to_string(),
             ProjectAccessSortableFields::UpdatedAt => "updated_at".to_string(),
             ProjectAccessSortableFields::CreatedAt => "created_at".to_string(),
@@ -120,6 +189,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         assert!(access.id.is_none());
@@ -144,6 +216,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let doc: Document = access.clone().into();
@@ -165,6 +240,7 @@ mod tests {
             name: Some("New Name".to_string()),
             project_scopes: Some(vec![Uuid::new()]),
             enabled: Some(false),
+            labels: None,
         };
 
         assert_eq!(update.name.unwrap(), "New Name");
@@ -179,6 +255,7 @@ mod tests {
             service_account_id: Some(Uuid::new()),
             project_scopes: Some(vec![Uuid::new()]),
             is_enabled: Some(true),
+            ..Default::default()
         };
 
         let doc: Document = filter.into();
@@ -0,0 +1,229 @@
+use crate::models::certificate_binding::CertificateBinding;
+use crate::models::label::Labels;
+use crate::models::manifest::GeneratedSecret;
+use crate::utils::security::REDACTED;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::Algorithm;
+use mongodb::bson::uuid::Uuid;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::{IntoParams, ToSchema};
+
+/// Value of [`ProjectBundle::format`].
+pub const BUNDLE_FORMAT: &str = "buraq.project-bundle";
+/// Version of the bundles this server exports, and the only one it imports.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Header carrying the passphrase that protects the secrets of a bundle, kept
+/// out of the query string so it does not end up in access logs.
+pub const PASSPHRASE_HEADER: &str = "X-Bundle-Passphrase";
+
+/// A project with everything needed to recreate it on another installation.
+///
+/// Records keep the ids they have on the exporting installation, which the
+/// import replaces with new ones. Service account secrets and server keys are
+/// only included when the bundle was exported with a passphrase, encrypted
+/// under it as described by `encryption`.
+///
+/// # Fields
+/// - `format`, `version`: Identify the bundle layout
+/// - `service_accounts`: The service accounts with access to the project
+/// - `server_keys`: Signing keys of the environments, empty without a passphrase
+/// - `encryption`: How secrets were encrypted, absent without a passphrase
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ProjectBundle {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub project: BundledRecord,
+    #[serde(default)]
+    pub environments: Vec<BundledRecord>,
+    #[serde(default)]
+    pub scopes: Vec<BundledRecord>,
+    #[serde(default)]
+    pub service_accounts: Vec<BundledServiceAccount>,
+    #[serde(default)]
+    pub access: Vec<BundledAccess>,
+    #[serde(default)]
+    pub server_keys: Vec<BundledServerKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<BundleEncryption>,
+}
+
+/// A project, environment or scope in a bundle.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct BundledRecord {
+    #[schema(value_type = String, format = Uuid)]
+    pub id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+}
+
+/// A service account in a bundle; `secret` is encrypted under the passphrase.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct BundledServiceAccount {
+    #[schema(value_type = String, format = Uuid)]
+    pub id: Uuid,
+    pub user: String,
+    pub email: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default)]
+    pub certificate_bindings: Vec<CertificateBinding>,
+}
+
+impl fmt::Debug for BundledServiceAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BundledServiceAccount")
+            .field("id", &self.id)
+            .field("user", &self.user)
+            .field("email", &self.email)
+            .field("secret", &self.secret.as_ref().map(|_| REDACTED))
+            .field("enabled", &self.enabled)
+            .field("labels", &self.labels)
+            .field("certificate_bindings", &self.certificate_bindings)
+            .finish()
+    }
+}
+
+/// An access in a bundle, referring to records of the bundle by their ids.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct BundledAccess {
+    #[schema(value_type = String, format = Uuid)]
+    pub id: Uuid,
+    pub name: String,
+    #[schema(value_type = String, format = Uuid)]
+    pub environment_id: Uuid,
+    #[schema(value_type = String, format = Uuid)]
+    pub service_account_id: Uuid,
+    #[schema(value_type = Vec<String>)]
+    #[serde(default)]
+    pub project_scopes: Vec<Uuid>,
+    pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+}
+
+/// A server key in a bundle; `key` is encrypted under the passphrase.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct BundledServerKey {
+    #[schema(value_type = String, format = Uuid)]
+    pub id: Uuid,
+    #[schema(value_type = String, format = Uuid)]
+    pub environment_id: Uuid,
+    #[schema(value_type = crate::serializers::algorithm::AlgorithmSchema)]
+    #[serde(with = "crate::serializers::algorithm")]
+    pub algorithm: Algorithm,
+    pub key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl fmt::Debug for BundledServerKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BundledServerKey")
+            .field("id", &self.id)
+            .field("environment_id", &self.environment_id)
+            .field("algorithm", &self.algorithm)
+            .field("key", &REDACTED)
+            .field("created_at", &self.created_at)
+            .finish()
+    }
+}
+
+/// How the secrets of a bundle were encrypted: AES-256-GCM under a key
+/// derived from the passphrase with PBKDF2-HMAC-SHA256.
+///
+/// # Fields
+/// - `salt`: Base64 PBKDF2 salt
+/// - `iterations`: PBKDF2 iterations, between 100000 and 10000000
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct BundleEncryption {
+    pub algorithm: String,
+    pub kdf: String,
+    pub salt: String,
+    pub iterations: u32,
+}
+
+/// Query parameters of a bundle import.
+///
+/// # Fields
+/// - `dry_run`: Check for conflicts without importing anything
+/// - `name`: Name to give the imported project instead of the bundled one
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProjectImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// Kinds of records a bundle holds.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BundledKind {
+    Project,
+    Environment,
+    Scope,
+    ServiceAccount,
+    Access,
+    ServerKey,
+}
+
+/// A bundled record that cannot be imported as it is.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct ImportConflict {
+    pub kind: BundledKind,
+    pub name: String,
+    pub reason: String,
+}
+
+/// Id a bundled record got on import.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct IdMapping {
+    pub kind: BundledKind,
+    #[schema(value_type = String, format = Uuid)]
+    pub from: Uuid,
+    #[schema(value_type = String, format = Uuid)]
+    pub to: Uuid,
+}
+
+/// Outcome of a bundle import.
+///
+/// # Fields
+/// - `imported`: `false` for a dry run
+/// - `project_id`: Id of the imported project
+/// - `ids`: New id of every imported record, and of the existing service
+///   accounts reused in place of bundled ones
+/// - `reused_service_accounts`: Bundled service accounts whose `user` already
+///   existed, and which were linked to instead of created
+/// - `generated_secrets`: Secrets of the service accounts created without the
+///   bundled secret, because the bundle had none
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct ProjectImportReport {
+    pub imported: bool,
+    #[schema(value_type = Option<String>, format = Uuid)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ids: Vec<IdMapping>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reused_service_accounts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub generated_secrets: Vec<GeneratedSecret>,
+}
+
+/// Result of a bundle import.
+#[derive(Debug, Clone)]
+pub enum ProjectImportOutcome {
+    Imported(ProjectImportReport),
+    /// Nothing was imported because of the listed conflicts.
+    Conflicts(Vec<ImportConflict>),
+}
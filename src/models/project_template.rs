@@ -0,0 +1,282 @@
+use crate::models::environment::Environment;
+use crate::models::filter::{DateRange, and, contains, starts_with};
+use crate::models::label::{LabelSelector, Labels};
+use crate::models::project::Project;
+use crate::models::project_scope::ProjectScope;
+use crate::models::server_key::ServerKeyRead;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::Algorithm;
+use mongodb::bson::uuid::Uuid;
+use mongodb::bson::{Document, doc, from_document, to_document};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Name of the template that is built in, used when no stored template has
+/// this name.
+pub const STANDARD_TEMPLATE: &str = "standard";
+
+/// Represents a reusable layout that new projects are scaffolded from.
+///
+/// # Fields
+/// - `id`: Unique identifier for the template (UUID)
+/// - `name`: Unique name the template is selected by
+/// - `description`: Description of the template
+/// - `environments`: Environments created in each new project
+/// - `project_scopes`: Scopes created in each new project
+/// - `server_key_algorithm`: Algorithm of the server key generated for each environment
+/// - `labels`: Key-value pairs used to organize and select resources
+/// - `created_at`: Timestamp when template was created
+/// - `updated_at`: Timestamp when template was last updated
+/// - `deleted_at`: Timestamp when the template was soft-deleted, if it was
+/// - `deleted_by`: Who soft-deleted the template
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ProjectTemplate {
+    #[schema(value_type = Option<String>, format = Uuid)]
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub environments: Vec<TemplateEntry>,
+    #[serde(default)]
+    pub project_scopes: Vec<TemplateEntry>,
+    #[schema(value_type = crate::serializers::algorithm::AlgorithmSchema)]
+    #[serde(with = "crate::serializers::algorithm")]
+    pub server_key_algorithm: Algorithm,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+/// An environment or project scope a template creates.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct TemplateEntry {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl TemplateEntry {
+    fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+        }
+    }
+}
+
+impl ProjectTemplate {
+    /// The [`STANDARD_TEMPLATE`]: `dev`, `staging` and `prod` environments,
+    /// `read`, `write` and `admin` scopes, and an HS256 server key per
+    /// environment.
+    pub fn standard() -> Self {
+        Self {
+            id: None,
+            name: STANDARD_TEMPLATE.to_string(),
+            description: "Development, staging and production environments".to_string(),
+            environments: vec![
+                TemplateEntry::new("dev", "Development"),
+                TemplateEntry::new("staging", "Staging"),
+                TemplateEntry::new("prod", "Production"),
+            ],
+            project_scopes: vec![
+                TemplateEntry::new("read", "Read access"),
+                TemplateEntry::new("write", "Write access"),
+                TemplateEntry::new("admin", "Administrative access"),
+            ],
+            server_key_algorithm: Algorithm::HS256,
+            labels: Labels::default(),
+            created_at: None,
+            updated_at: None,
+            deleted_at: None,
+            deleted_by: None,
+        }
+    }
+}
+
+/// The records created when a project is scaffolded from a template.
+///
+/// # Fields
+/// - `project`: The new project
+/// - `environments`: Its environments, in template order
+/// - `project_scopes`: Its scopes, in template order
+/// - `server_keys`: The server key of each environment, in the same order
+#[derive(Debug, Clone)]
+pub struct ProjectScaffold {
+    pub project: Project,
+    pub environments: Vec<Environment>,
+    pub project_scopes: Vec<ProjectScope>,
+    pub server_keys: Vec<ServerKeyRead>,
+}
+
+impl From<ProjectTemplate> for Document {
+    fn from(value: ProjectTemplate) -> Self {
+        to_document(&value).unwrap()
+    }
+}
+
+impl From<Document> for ProjectTemplate {
+    fn from(value: Document) -> Self {
+        from_document(value.clone()).unwrap()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ProjectTemplateUpdatePayload {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environments: Option<Vec<TemplateEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_scopes: Option<Vec<TemplateEntry>>,
+    #[schema(value_type = Option<crate::serializers::algorithm::AlgorithmSchema>)]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serializers::option_algorithm"
+    )]
+    pub server_key_algorithm: Option<Algorithm>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Labels>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProjectTemplateFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_since: Option<DateTime<Utc>>,
+    #[param(value_type = Option<Vec<String>>, style = Form, explode = false)]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serializers::option_uuid_list"
+    )]
+    pub id_in: Option<Vec<Uuid>>,
+    #[param(value_type = Option<String>)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label_selector: Option<LabelSelector>,
+}
+
+impl From<ProjectTemplateFilter> for Document {
+    fn from(value: ProjectTemplateFilter) -> Self {
+        let mut doc = Document::new();
+        if let Some(name) = value.name {
+            doc.insert("name", name);
+        }
+        if let Some(prefix) = value.name_prefix {
+            and(&mut doc, doc! { "name": starts_with(&prefix) });
+        }
+        if let Some(search) = value.search {
+            and(&mut doc, doc! { "name": contains(&search) });
+        }
+        DateRange::new()
+            .after(value.created_after)
+            .before(value.created_before)
+            .apply(&mut doc, "created_at");
+        DateRange::new()
+            .since(value.updated_since)
+            .apply(&mut doc, "updated_at");
+        if let Some(ids) = value.id_in {
+            doc.insert("_id", doc! { "$in": ids });
+        }
+        if let Some(selector) = value.label_selector {
+            selector.apply(&mut doc);
+        }
+        doc
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectTemplateSortableFields {
+    Id,
+    Name,
+    UpdatedAt,
+    CreatedAt,
+}
+
+impl From<ProjectTemplateSortableFields> for String {
+    fn from(value: ProjectTemplateSortableFields) -> Self {
+        match value {
+            ProjectTemplateSortableFields::Id => "_id".to_string(),
+            ProjectTemplateSortableFields::Name => "name".to_string(),
+            ProjectTemplateSortableFields::UpdatedAt => "updated_at".to_string(),
+            ProjectTemplateSortableFields::CreatedAt => "created_at".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standard_template() {
+        let template = ProjectTemplate::standard();
+        assert_eq!(template.name, STANDARD_TEMPLATE);
+        let environments: Vec<&str> = template
+            .environments
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(environments, vec!["dev", "staging", "prod"]);
+        let scopes: Vec<&str> = template
+            .project_scopes
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(scopes, vec!["read", "write", "admin"]);
+    }
+
+    #[test]
+    fn test_document_conversion() {
+        let mut template = ProjectTemplate::standard();
+        template.id = Some(Uuid::new());
+        template.server_key_algorithm = Algorithm::HS512;
+
+        let doc: Document = template.clone().into();
+        assert_eq!(doc.get_str("server_key_algorithm").unwrap(), "HS512");
+        let converted: ProjectTemplate = doc.into();
+
+        assert_eq!(converted.id, template.id);
+        assert_eq!(converted.name, template.name);
+        assert_eq!(converted.environments, template.environments);
+        assert_eq!(converted.project_scopes, template.project_scopes);
+        assert_eq!(converted.server_key_algorithm, Algorithm::HS512);
+    }
+
+    #[test]
+    fn test_project_template_filter() {
+        let filter = ProjectTemplateFilter {
+            name: Some(STANDARD_TEMPLATE.to_string()),
+            ..Default::default()
+        };
+
+        let doc: Document = filter.into();
+
+        assert_eq!(doc.get_str("name").unwrap(), STANDARD_TEMPLATE);
+    }
+}
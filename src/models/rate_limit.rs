@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What an authentication attempt is counted against.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateLimitKey {
+    /// The client id the caller authenticates as.
+    Client(String),
+    /// The address the request came from.
+    Ip(String),
+}
+
+impl fmt::Display for RateLimitKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimitKey::Client(id) => write!(f, "client:{}", id),
+            RateLimitKey::Ip(address) => write!(f, "ip:{}", address),
+        }
+    }
+}
+
+/// Attempts counted against one key during one fixed window
+///
+/// The id combines the key and the window start, so every instance sharing
+/// the database increments the same document. A TTL index removes windows
+/// once they have expired.
+///
+/// # Fields
+/// - `id`: `<key>@<window start>`, the start being a Unix timestamp
+/// - `count`: Attempts made so far in the window
+/// - `expires_at`: End of the window. A BSON date rather than the usual
+///   RFC 3339 string, because TTL indexes only expire dates
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RateLimitWindow {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub count: u32,
+    pub expires_at: mongodb::bson::DateTime,
+}
+
+impl RateLimitWindow {
+    /// Start, as a Unix timestamp, of the `window_seconds` long window `now` falls in.
+    pub fn start(now: DateTime<Utc>, window_seconds: u64) -> i64 {
+        let window_seconds = window_seconds.max(1) as i64;
+        let timestamp = now.timestamp();
+        timestamp - timestamp.rem_euclid(window_seconds)
+    }
+
+    /// Id of the window starting at `start` for `key`.
+    pub fn id_for(key: &RateLimitKey, start: i64) -> String {
+        format!("{}@{}", key, start)
+    }
+
+    /// When the window ends and its attempts stop counting.
+    pub fn reset_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(self.expires_at.timestamp_millis()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_start_is_aligned() {
+        let now = DateTime::from_timestamp(1_000_075, 0).unwrap();
+        assert_eq!(RateLimitWindow::start(now, 60), 1_000_020);
+        assert_eq!(RateLimitWindow::start(now, 1), 1_000_075);
+    }
+
+    #[test]
+    fn test_window_id_and_reset() {
+        let key = RateLimitKey::Ip("10.0.0.1".to_string());
+        assert_eq!(RateLimitWindow::id_for(&key, 60), "ip:10.0.0.1@60");
+
+        let window = RateLimitWindow {
+            id: RateLimitWindow::id_for(&RateLimitKey::Client("abc".to_string()), 60),
+            count: 1,
+            expires_at: mongodb::bson::DateTime::from_millis(120_000),
+        };
+        assert_eq!(window.id, "client:abc@60");
+        assert_eq!(window.reset_at(), DateTime::from_timestamp(120, 0).unwrap());
+    }
+}
@@ -1,8 +1,12 @@
+use crate::models::filter::DateRange;
+use crate::utils::security::REDACTED;
 use chrono::{DateTime, Utc};
 use jsonwebtoken::Algorithm;
 use mongodb::bson::uuid::Uuid;
 use mongodb::bson::{Document, doc, from_document, to_document};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::{IntoParams, ToSchema};
 
 /// Represents a server key for API authentication
 ///
@@ -13,16 +17,46 @@ use serde::{Deserialize, Serialize};
 /// - `algorithm`: The algorithm used for the key
 /// - `created_at`: Key creation timestamp
 /// - `updated_at`: Timestamp when key was last updated
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// - `deleted_at`: Timestamp when the key was soft-deleted, if it was
+/// - `deleted_by`: Who soft-deleted the key
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct ServerKey {
+    #[schema(value_type = Option<String>, format = Uuid)]
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<Uuid>,
     pub key: String,
+    #[schema(value_type = String, format = Uuid)]
     pub environment_id: Uuid,
+    #[schema(value_type = crate::serializers::algorithm::AlgorithmSchema)]
     #[serde(with = "crate::serializers::algorithm")]
     pub algorithm: Algorithm,
+    #[serde(with = "crate::serializers::timestamp")]
     pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::serializers::timestamp")]
     pub updated_at: DateTime<Utc>,
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+impl fmt::Debug for ServerKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerKey")
+            .field("id", &self.id)
+            .field("key", &REDACTED)
+            .field("environment_id", &self.environment_id)
+            .field("algorithm", &self.algorithm)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("deleted_at", &self.deleted_at)
+            .field("deleted_by", &self.deleted_by)
+            .finish()
+    }
 }
 
 impl From<ServerKey> for Document {
@@ -37,13 +71,18 @@ impl From<Document> for ServerKey {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct ServerKeyRead {
+    #[schema(value_type = String, format = Uuid)]
     pub id: Uuid,
+    #[schema(value_type = String, format = Uuid)]
     pub environment_id: Uuid,
+    #[schema(value_type = crate::serializers::algorithm::AlgorithmSchema)]
     #[serde(with = "crate::serializers::algorithm")]
     pub algorithm: Algorithm,
+    #[serde(with = "crate::serializers::timestamp")]
     pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::serializers::timestamp")]
     pub updated_at: DateTime<Utc>,
 }
 
@@ -59,12 +98,14 @@ impl From<ServerKey> for ServerKeyRead {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct ServerKeyUpdatePayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
+    #[schema(value_type = Option<String>, format = Uuid)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub environment_id: Option<Uuid>,
+    #[schema(value_type = Option<crate::serializers::algorithm::AlgorithmSchema>)]
     #[serde(
         skip_serializing_if = "Option::is_none",
         with = "crate::serializers::option_algorithm"
@@ -72,24 +113,52 @@ pub struct ServerKeyUpdatePayload {
     pub algorithm: Option<Algorithm>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl fmt::Debug for ServerKeyUpdatePayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerKeyUpdatePayload")
+            .field("key", &self.key.as_ref().map(|_| REDACTED))
+            .field("environment_id", &self.environment_id)
+            .field("algorithm", &self.algorithm)
+            .finish()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct ServerKeyCreatePayload {
+    #[schema(value_type = String, format = Uuid)]
     pub environment_id: Uuid,
+    #[schema(value_type = crate::serializers::algorithm::AlgorithmSchema)]
     #[serde(with = "crate::serializers::algorithm")]
     pub algorithm: Algorithm,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ServerKeyFilter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
+    #[param(value_type = Option<crate::serializers::algorithm::AlgorithmSchema>)]
     #[serde(
         skip_serializing_if = "Option::is_none",
         with = "crate::serializers::option_algorithm"
     )]
     pub algorithm: Option<Algorithm>,
+    #[param(value_type = Option<String>, format = Uuid)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub environment_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_since: Option<DateTime<Utc>>,
+    #[param(value_type = Option<Vec<String>>, style = Form, explode = false)]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serializers::option_uuid_list"
+    )]
+    pub id_in: Option<Vec<Uuid>>,
 }
 
 impl From<ServerKeyFilter> for Document {
@@ -104,11 +173,22 @@ impl From<ServerKeyFilter> for Document {
         if let Some(environment_id) = value.environment_id {
             doc.insert("environment_id", environment_id);
         }
+        DateRange::new()
+            .after(value.created_after)
+            .before(value.created_before)
+            .apply(&mut doc, "created_at");
+        DateRange::new()
+            .since(value.updated_since)
+            .apply(&mut doc, "updated_at");
+        if let Some(ids) = value.id_in {
+            doc.insert("_id", doc! { "$in": ids });
+        }
         doc
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
 pub enum ServerKeySortableFields {
     Id,
     Algorithm,
@@ -118,7 +198,7 @@ pub enum ServerKeySortableFields {
 impl From<ServerKeySortableFields> for String {
     fn from(value: ServerKeySortableFields) -> Self {
         match value {
-            ServerKeySortableFields::Id => "id".to_string(),
+            ServerKeySortableFields::Id => "_id".to_string(),
             ServerKeySortableFields::Algorithm => "algorithm".to_string(),
             ServerKeySortableFields::EnvironmentId => "environment_id".to_string(),
         }
@@ -141,6 +221,8 @@ mod tests {
             algorithm: Algorithm::RS256,
             created_at: now,
             updated_at: now,
+            deleted_at: None,
+            deleted_by: None,
         };
 
         assert_eq!(server_key.key, "test-key");
@@ -156,6 +238,8 @@ mod tests {
             algorithm: Algorithm::HS256,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let doc: Document = server_key.clone().into();
@@ -185,6 +269,7 @@ mod tests {
             key: Some("test-key".to_string()),
             algorithm: Some(Algorithm::RS256),
             environment_id: Some(Uuid::new()),
+            ..Default::default()
         };
 
         let doc: Document = filter.into();
@@ -196,7 +281,7 @@ mod tests {
 
     #[test]
     fn test_server_key_sortable_fields() {
-        assert_eq!(String::from(ServerKeySortableFields::Id), "id");
+        assert_eq!(String::from(ServerKeySortableFields::Id), "_id");
         assert_eq!(
             String::from(ServerKeySortableFields::Algorithm),
             "algorithm"
@@ -283,6 +368,7 @@ mod tests {
             key: Some("test-key".to_string()),
             algorithm: Some(Algorithm::RS256),
             environment_id: Some(environment_id),
+            ..Default::default()
         };
 
         let json = to_value(&filter).unwrap();
@@ -303,6 +389,7 @@ mod tests {
             key: None,
             algorithm: Some(Algorithm::RS256),
             environment_id: None,
+            ..Default::default()
         };
 
         let json = to_value(&filter).unwrap();
@@ -346,6 +433,8 @@ mod tests {
             algorithm: Algorithm::RS256,
             created_at: now,
             updated_at: now,
+            deleted_at: None,
+            deleted_by: None,
         };
 
         // Act
@@ -382,8 +471,8 @@ mod tests {
         assert_eq!(json["id"], id.to_string());
         assert_eq!(json["environment_id"], environment_id.to_string());
         assert_eq!(json["algorithm"], "RS256");
-        assert_eq!(json["created_at"], "2023-01-01T00:00:00Z");
-        assert_eq!(json["updated_at"], "2023-01-02T00:00:00Z");
+        assert_eq!(json["created_at"], "2023-01-01T00:00:00.000000000Z");
+        assert_eq!(json["updated_at"], "2023-01-02T00:00:00.000000000Z");
 
         // Act - Deserialization
         let deserialized: ServerKeyRead = from_value(json).unwrap();
@@ -406,6 +495,8 @@ mod tests {
             algorithm: Algorithm::RS256,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         // Act & Assert
@@ -1,7 +1,13 @@
+use crate::models::certificate_binding::CertificateBinding;
+use crate::models::filter::{DateRange, and, contains};
+use crate::models::label::{LabelSelector, Labels};
+use crate::utils::security::REDACTED;
 use chrono::{DateTime, Utc};
 use mongodb::bson::uuid::Uuid;
 use mongodb::bson::{Document, doc, from_document, to_document};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::{IntoParams, ToSchema};
 
 /// Represents a service account for API authentication
 ///
@@ -11,18 +17,67 @@ use serde::{Deserialize, Serialize};
 /// - `user`: Username for the account
 /// - `secret`: Secret key for authentication
 /// - `enabled`: Whether the account is currently active
+/// - `labels`: Key-value pairs used to organize and select resources
+/// - `certificate_bindings`: Client certificates the account may authenticate with over mTLS
+/// - `failed_authentications`: Consecutive failed authentications since the last success
+/// - `locked_until`: When the lockout following repeated failures ends, if locked
 /// - `created_at`: Account creation timestamp
 /// - `updated_at`: Last update timestamp
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// - `deleted_at`: Timestamp when the account was soft-deleted, if it was
+/// - `deleted_by`: Who soft-deleted the account
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct ServiceAccount {
+    #[schema(value_type = Option<String>, format = Uuid)]
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<Uuid>,
     pub email: String,
     pub user: String,
     pub secret: String,
     pub enabled: bool,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default)]
+    pub certificate_bindings: Vec<CertificateBinding>,
+    #[serde(default)]
+    pub failed_authentications: u32,
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub locked_until: Option<DateTime<Utc>>,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
     pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
     pub updated_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+impl fmt::Debug for ServiceAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceAccount")
+            .field("id", &self.id)
+            .field("email", &self.email)
+            .field("user", &self.user)
+            .field("secret", &REDACTED)
+            .field("enabled", &self.enabled)
+            .field("labels", &self.labels)
+            .field("certificate_bindings", &self.certificate_bindings)
+            .field("failed_authentications", &self.failed_authentications)
+            .field("locked_until", &self.locked_until)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("deleted_at", &self.deleted_at)
+            .field("deleted_by", &self.deleted_by)
+            .finish()
+    }
 }
 
 impl ServiceAccount {
@@ -33,10 +88,23 @@ impl ServiceAccount {
             user,
             secret,
             enabled: true,
+            labels: Labels::new(),
+            certificate_bindings: Vec::new(),
+            failed_authentications: 0,
+            locked_until: None,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         }
     }
+
+    /// Time left on the account's lockout, or `None` when it is not locked.
+    pub fn lockout_remaining(&self, now: DateTime<Utc>) -> Option<chrono::Duration> {
+        self.locked_until
+            .map(|locked_until| locked_until - now)
+            .filter(|remaining| *remaining > chrono::Duration::zero())
+    }
 }
 
 impl From<ServiceAccount> for Document {
@@ -51,7 +119,7 @@ impl From<Document> for ServiceAccount {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct ServiceAccountUpdatePayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
@@ -61,9 +129,27 @@ pub struct ServiceAccountUpdatePayload {
     pub secret: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Labels>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certificate_bindings: Option<Vec<CertificateBinding>>,
+}
+
+impl fmt::Debug for ServiceAccountUpdatePayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceAccountUpdatePayload")
+            .field("email", &self.email)
+            .field("user", &self.user)
+            .field("secret", &self.secret.as_ref().map(|_| REDACTED))
+            .field("enabled", &self.enabled)
+            .field("labels", &self.labels)
+            .field("certificate_bindings", &self.certificate_bindings)
+            .finish()
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ServiceAccountFilter {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
@@ -71,6 +157,24 @@ pub struct ServiceAccountFilter {
     pub user: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_since: Option<DateTime<Utc>>,
+    #[param(value_type = Option<Vec<String>>, style = Form, explode = false)]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serializers::option_uuid_list"
+    )]
+    pub id_in: Option<Vec<Uuid>>,
+    #[param(value_type = Option<String>)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label_selector: Option<LabelSelector>,
 }
 
 impl From<ServiceAccountFilter> for Document {
@@ -85,11 +189,34 @@ impl From<ServiceAccountFilter> for Document {
         if let Some(is_enabled) = value.is_enabled {
             doc.insert("enabled", is_enabled);
         }
+        if let Some(search) = value.search {
+            and(
+                &mut doc,
+                doc! { "$or": [
+                    { "email": contains(&search) },
+                    { "user": contains(&search) },
+                ] },
+            );
+        }
+        DateRange::new()
+            .after(value.created_after)
+            .before(value.created_before)
+            .apply(&mut doc, "created_at");
+        DateRange::new()
+            .since(value.updated_since)
+            .apply(&mut doc, "updated_at");
+        if let Some(ids) = value.id_in {
+            doc.insert("_id", doc! { "$in": ids });
+        }
+        if let Some(selector) = value.label_selector {
+            selector.apply(&mut doc);
+        }
         doc
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
 pub enum ServiceAccountSortableFields {
     Id,
     Email,
@@ -101,7 +228,7 @@ pub enum ServiceAccountSortableFields {
 impl From<ServiceAccountSortableFields> for String {
     fn from(value: ServiceAccountSortableFields) -> Self {
         match value {
-            ServiceAccountSortableFields::Id => "id".to_string(),
+            ServiceAccountSortableFields::Id => "_id".to_string(),
             ServiceAccountSortableFields::Email => "email".to_string(),
             ServiceAccountSortableFields::User => "user".to_string(),
             ServiceAccountSortableFields::UpdatedAt => "updated_at".to_string(),
@@ -114,6 +241,48 @@ impl From<ServiceAccountSortableFields> for String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_service_account_debug_redacts_secret() {
+        let account = ServiceAccount::new(
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "secret123".to_string(),
+        );
+        let debug = format!("{:?}", account);
+        assert!(!debug.contains("secret123"));
+        assert!(debug.contains("test@example.com"));
+
+        let payload = ServiceAccountUpdatePayload {
+            email: None,
+            user: None,
+            secret: Some("secret456".to_string()),
+            enabled: None,
+            labels: None,
+            certificate_bindings: None,
+        };
+        assert!(!format!("{:?}", payload).contains("secret456"));
+    }
+
+    #[test]
+    fn test_lockout_remaining() {
+        let now = Utc::now();
+        let mut account = ServiceAccount::new(
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "secret123".to_string(),
+        );
+        assert_eq!(account.lockout_remaining(now), None);
+
+        account.locked_until = Some(now + chrono::Duration::seconds(30));
+        assert_eq!(
+            account.lockout_remaining(now),
+            Some(chrono::Duration::seconds(30))
+        );
+
+        account.locked_until = Some(now - chrono::Duration::seconds(1));
+        assert_eq!(account.lockout_remaining(now), None);
+    }
+
     #[test]
     fn test_service_account_creation() {
         let account = ServiceAccount::new(
@@ -152,6 +321,7 @@ mod tests {
             email: Some("test@example.com".to_string()),
             user: Some("testuser".to_string()),
             is_enabled: Some(true),
+            ..Default::default()
         };
 
         let doc: Document = filter.into();
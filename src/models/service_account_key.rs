@@ -1,9 +1,13 @@
+use crate::models::filter::DateRange;
 use crate::serializers::algorithm;
+use crate::utils::security::REDACTED;
 use chrono::{DateTime, Utc};
 use jsonwebtoken::Algorithm;
 use mongodb::bson::uuid::Uuid;
 use mongodb::bson::{Document, doc, from_document, to_document};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::{IntoParams, ToSchema};
 
 /// Represents a service account key for API authentication
 ///
@@ -15,18 +19,51 @@ use serde::{Deserialize, Serialize};
 /// - `expires_at`: Key expiration timestamp
 /// - `created_at`: Timestamp when key was created
 /// - `updated_at`: Timestamp when key was last updated
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// - `deleted_at`: Timestamp when the key was soft-deleted, if it was
+/// - `deleted_by`: Who soft-deleted the key
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct ServiceAccountKey {
+    #[schema(value_type = Option<String>, format = Uuid)]
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<Uuid>,
+    #[schema(value_type = String, format = Uuid)]
     pub service_account_id: Uuid,
+    #[schema(value_type = crate::serializers::algorithm::AlgorithmSchema)]
     #[serde(with = "algorithm")]
     pub algorithm: Algorithm,
     pub key: String,
+    #[serde(with = "crate::serializers::timestamp")]
     pub expires_at: DateTime<Utc>,
     pub enabled: bool,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
     pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "crate::serializers::option_timestamp")]
     pub updated_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+impl fmt::Debug for ServiceAccountKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceAccountKey")
+            .field("id", &self.id)
+            .field("service_account_id", &self.service_account_id)
+            .field("algorithm", &self.algorithm)
+            .field("key", &REDACTED)
+            .field("expires_at", &self.expires_at)
+            .field("enabled", &self.enabled)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("deleted_at", &self.deleted_at)
+            .field("deleted_by", &self.deleted_by)
+            .finish()
+    }
 }
 
 impl From<ServiceAccountKey> for Document {
@@ -41,26 +78,58 @@ impl From<Document> for ServiceAccountKey {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct ServiceAccountKeyUpdatePayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        with = "crate::serializers::option_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub expires_at: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enabled: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+impl fmt::Debug for ServiceAccountKeyUpdatePayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceAccountKeyUpdatePayload")
+            .field("key", &self.key.as_ref().map(|_| REDACTED))
+            .field("expires_at", &self.expires_at)
+            .field("enabled", &self.enabled)
+            .finish()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ServiceAccountKeyFilter {
+    #[param(value_type = Option<String>, format = Uuid)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub service_account_id: Option<Uuid>,
+    #[param(value_type = Option<crate::serializers::algorithm::AlgorithmSchema>)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub algorithm: Option<Algorithm>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_enabled: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_active: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_since: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_before: Option<DateTime<Utc>>,
+    #[param(value_type = Option<Vec<String>>, style = Form, explode = false)]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serializers::option_uuid_list"
+    )]
+    pub id_in: Option<Vec<Uuid>>,
 }
 
 impl From<ServiceAccountKeyFilter> for Document {
@@ -75,16 +144,26 @@ impl From<ServiceAccountKeyFilter> for Document {
         if let Some(is_enabled) = value.is_enabled {
             doc.insert("enabled", is_enabled);
         }
-        if let Some(is_active) = value.is_active {
-            if is_active {
-                doc.insert("expires_at", doc! { "$gt": mongodb::bson::DateTime::now() });
-            }
+        DateRange::new()
+            .after(value.created_after)
+            .before(value.created_before)
+            .apply(&mut doc, "created_at");
+        DateRange::new()
+            .since(value.updated_since)
+            .apply(&mut doc, "updated_at");
+        DateRange::new()
+            .after(value.is_active.filter(|active| *active).map(|_| Utc::now()))
+            .before(value.expires_before)
+            .apply(&mut doc, "expires_at");
+        if let Some(ids) = value.id_in {
+            doc.insert("_id", doc! { "$in": ids });
         }
         doc
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
 pub enum ServiceAccountKeySortableFields {
     Id,
     ServiceAccountId,
@@ -97,7 +176,7 @@ pub enum ServiceAccountKeySortableFields {
 impl From<ServiceAccountKeySortableFields> for String {
     fn from(value: ServiceAccountKeySortableFields) -> Self {
         match value {
-            ServiceAccountKeySortableFields::Id => "id".to_string(),
+            ServiceAccountKeySortableFields::Id => "_id".to_string(),
             ServiceAccountKeySortableFields::ServiceAccountId => "service_account_id".to_string(),
             ServiceAccountKeySortableFields::Algorithm => "algorithm".to_string(),
             ServiceAccountKeySortableFields::ExpiresAt => "expires_at".to_string(),
@@ -127,6 +206,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         assert!(service_account_key.id.is_none());
@@ -156,6 +237,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         // Test serialization
@@ -196,6 +279,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
         let id = Uuid::new();
         service_account_key.id = Some(id);
@@ -215,4 +300,25 @@ mod tests {
         assert_eq!(converted.created_at, service_account_key.created_at);
         assert_eq!(converted.updated_at, service_account_key.updated_at);
     }
+
+    #[test]
+    fn test_service_account_key_filter_expiring() {
+        // Keys expiring in the next 14 days
+        let now = Utc::now();
+        let filter = ServiceAccountKeyFilter {
+            is_active: Some(true),
+            expires_before: Some(now + chrono::Duration::days(14)),
+            ..Default::default()
+        };
+
+        let doc: Document = filter.into();
+        let expires_at = doc.get_document("expires_at").unwrap();
+        let bound = |operator| {
+            DateTime::parse_from_rfc3339(expires_at.get_str(operator).unwrap())
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        assert!(bound("$gt") >= now);
+        assert_eq!(bound("$lt"), now + chrono::Duration::days(14));
+    }
 }
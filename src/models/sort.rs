@@ -1,6 +1,10 @@
+use crate::errors::AppError;
 use mongodb::bson::{Document, doc};
+use serde::de::DeserializeOwned;
+use serde::de::value::{Error as ValueError, StrDeserializer};
 use serde::{Deserialize, Serialize};
 use std::vec::Vec;
+use utoipa::IntoParams;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SortDirection {
@@ -9,6 +13,18 @@ pub enum SortDirection {
 }
 
 impl SortDirection {
+    /// Parses the `asc`/`desc` suffix of a `sort` query parameter entry.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value {
+            "asc" => Ok(SortDirection::Ascending),
+            "desc" => Ok(SortDirection::Descending),
+            other => Err(AppError::Validation(format!(
+                "Invalid sort direction `{}`, expected `asc` or `desc`",
+                other
+            ))),
+        }
+    }
+
     fn as_i32(&self) -> i32 {
         match self {
             SortDirection::Ascending => 1,
@@ -74,6 +90,58 @@ where
     }
 }
 
+impl<T> SortBuilder<T>
+where
+    T: Into<String> + Clone + DeserializeOwned,
+{
+    /// Parses a `sort` query parameter such as `name:asc,created_at:desc`.
+    ///
+    /// Field names are the snake_case names of the `*SortableFields` variants.
+    /// The direction is optional and defaults to ascending.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        let mut builder = Self::new();
+        let mut seen = Vec::new();
+        for entry in value.split(',') {
+            let (name, direction) = match entry.split_once(':') {
+                Some((name, direction)) => (name.trim(), SortDirection::parse(direction.trim())?),
+                None => (entry.trim(), SortDirection::Ascending),
+            };
+            let field = T::deserialize(StrDeserializer::<ValueError>::new(name))
+                .map_err(|e| AppError::Validation(format!("Invalid sort field: {}", e)))?;
+            if seen.contains(&name) {
+                return Err(AppError::Validation(format!(
+                    "Sort field `{}` given more than once",
+                    name
+                )));
+            }
+            seen.push(name);
+            builder = builder.add_sort(field, direction);
+        }
+        Ok(builder)
+    }
+}
+
+/// The `sort` query parameter accepted by list endpoints.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SortQuery {
+    /// Comma-separated `field:direction` pairs, e.g. `name:asc,created_at:desc`
+    pub sort: Option<String>,
+}
+
+impl SortQuery {
+    /// The sort requested by the client, or `default` when none was given.
+    pub fn or<T>(self, default: SortBuilder<T>) -> Result<SortBuilder<T>, AppError>
+    where
+        T: Into<String> + Clone + DeserializeOwned,
+    {
+        match self.sort.as_deref().map(str::trim) {
+            Some(value) if !value.is_empty() => SortBuilder::parse(value),
+            _ => Ok(default),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,4 +196,59 @@ mod tests {
         assert_eq!(doc.get_i32("name").unwrap(), 1);
         assert_eq!(doc.get_i32("age").unwrap(), -1);
     }
+
+    #[derive(Debug, Clone, Deserialize, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Field {
+        Id,
+        Name,
+        CreatedAt,
+    }
+
+    impl From<Field> for String {
+        fn from(value: Field) -> Self {
+            match value {
+                Field::Id => "_id".to_string(),
+                Field::Name => "name".to_string(),
+                Field::CreatedAt => "created_at".to_string(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_sort_builder_parse() {
+        let doc = SortBuilder::<Field>::parse("created_at:desc, name ,id:asc")
+            .unwrap()
+            .to_document();
+        assert_eq!(doc, doc! { "created_at": -1, "name": 1, "_id": 1 });
+    }
+
+    #[test]
+    fn test_sort_builder_parse_rejects_invalid_input() {
+        for value in ["unknown:asc", "name:up", "name:asc,name:desc", "", "_id"] {
+            assert!(
+                matches!(
+                    SortBuilder::<Field>::parse(value),
+                    Err(AppError::Validation(_))
+                ),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_sort_query_or_default() {
+        let default = SortBuilder::new().ascending(Field::Id);
+
+        let sort = SortQuery { sort: None }.or(default.clone()).unwrap();
+        assert_eq!(sort.to_document(), doc! { "_id": 1 });
+
+        let sort = SortQuery {
+            sort: Some("name:desc".to_string()),
+        }
+        .or(default)
+        .unwrap();
+        assert_eq!(sort.to_document(), doc! { "name": -1 });
+    }
 }
@@ -0,0 +1,225 @@
+use crate::utils::security::REDACTED;
+use crate::utils::tokens::dpop::DPOP_TOKEN_TYPE;
+use crate::utils::tokens::key_builder::Claims;
+use mongodb::bson::uuid::Uuid;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::ToSchema;
+
+/// Grant type of service accounts exchanging their credentials for a token.
+pub const CLIENT_CREDENTIALS: &str = "client_credentials";
+
+/// Form parameters of an OAuth 2.0 client credentials token request
+/// (RFC 6749 section 4.4).
+///
+/// # Fields
+/// - `grant_type`: Must be `client_credentials`
+/// - `client_id`: Id of the service account requesting the token
+/// - `client_secret`: The account's secret; omitted when the client
+///   authenticates with its TLS certificate (RFC 8705 `tls_client_auth`)
+/// - `resource`: Environment the token is for (RFC 8707); required when the
+///   account has access to more than one
+/// - `scope`: Space separated project scope names; defaults to every scope granted
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    #[schema(value_type = String, format = Uuid)]
+    pub client_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    #[schema(value_type = Option<String>, format = Uuid)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl fmt::Debug for TokenRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenRequest")
+            .field("grant_type", &self.grant_type)
+            .field("client_id", &self.client_id)
+            .field(
+                "client_secret",
+                &self.client_secret.as_ref().map(|_| REDACTED),
+            )
+            .field("resource", &self.resource)
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+/// Successful token response (RFC 6749 section 5.1).
+///
+/// # Fields
+/// - `access_token`: The signed JWT
+/// - `token_type`: How the token is presented, `Bearer`, or `DPoP` for a
+///   token bound to a DPoP key
+/// - `expires_in`: Lifetime of the token in seconds
+/// - `scope`: Space separated project scope names the token grants
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+/// Form parameters of a token introspection request (RFC 7662 section 2.1).
+///
+/// # Fields
+/// - `token`: The access token to introspect
+/// - `token_type_hint`: Accepted and ignored; only access tokens are issued
+/// - `certificate_thumbprint`: `x5t#S256` thumbprint of the client certificate
+///   the token was presented with, for a resource server checking a
+///   certificate-bound token (RFC 8705 section 3)
+/// - `dpop_proof`: `DPoP` header the token was presented with, for a resource
+///   server checking a DPoP-bound token (RFC 9449 section 7)
+/// - `htm`, `htu`: Method and URI of the request the token and proof were
+///   presented in; required with `dpop_proof`
+#[derive(Deserialize, Serialize, Clone, ToSchema)]
+pub struct IntrospectionRequest {
+    pub token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_type_hint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate_thumbprint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dpop_proof: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub htm: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub htu: Option<String>,
+}
+
+impl fmt::Debug for IntrospectionRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntrospectionRequest")
+            .field("token", &REDACTED)
+            .field("token_type_hint", &self.token_type_hint)
+            .field("certificate_thumbprint", &self.certificate_thumbprint)
+            .field("dpop_proof", &self.dpop_proof.as_ref().map(|_| REDACTED))
+            .field("htm", &self.htm)
+            .field("htu", &self.htu)
+            .finish()
+    }
+}
+
+/// Token introspection response (RFC 7662 section 2.2).
+///
+/// Only `active` is set for a token that is invalid, expired, revoked or
+/// presented without proof of the key it is bound to.
+///
+/// # Fields
+/// - `active`: Whether the token is active for its presenter
+/// - `scope`: Space separated project scope names the token grants
+/// - `client_id`: Service account the token was issued to
+/// - `token_type`: `Bearer`, or `DPoP` for a token bound to a DPoP key
+/// - `exp`, `iat`: Expiry and issue time as UTC timestamps
+/// - `sub`: Service account the token was issued to
+/// - `aud`: Environment the token is for
+/// - `jti`: Unique identifier of the token
+/// - `cnf`: Confirmation of the key the token is bound to (RFC 8705, RFC 9449)
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<serde_json::Value>,
+}
+
+impl IntrospectionResponse {
+    /// Response for a token that is not active.
+    pub fn inactive() -> Self {
+        Self::default()
+    }
+}
+
+impl From<Claims> for IntrospectionResponse {
+    fn from(claims: Claims) -> Self {
+        let dpop_bound = claims
+            .cnf
+            .as_ref()
+            .is_some_and(|confirmation| confirmation.jkt.is_some());
+        let token_type = if dpop_bound {
+            DPOP_TOKEN_TYPE
+        } else {
+            "Bearer"
+        };
+        Self {
+            active: true,
+            scope: claims.scopes.map(|scopes| scopes.join(" ")),
+            client_id: Some(claims.sub.clone()),
+            token_type: Some(token_type.to_string()),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            sub: Some(claims.sub),
+            aud: claims.aud,
+            jti: claims.jti,
+            cnf: claims
+                .cnf
+                .and_then(|confirmation| serde_json::to_value(confirmation).ok()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_request_from_form() {
+        let client_id = Uuid::new();
+        let request: TokenRequest = serde_urlencoded::from_str(&format!(
+            "grant_type=client_credentials&client_id={}&client_secret=secret123&scope=read+write",
+            client_id
+        ))
+        .unwrap();
+
+        assert_eq!(request.grant_type, CLIENT_CREDENTIALS);
+        assert_eq!(request.client_id, client_id);
+        assert_eq!(request.scope.as_deref(), Some("read write"));
+        assert_eq!(request.resource, None);
+        assert!(!format!("{:?}", request).contains("secret123"));
+    }
+
+    #[test]
+    fn test_introspection_response() {
+        assert_eq!(
+            serde_json::to_value(IntrospectionResponse::inactive()).unwrap(),
+            serde_json::json!({ "active": false })
+        );
+
+        let claims = Claims::new("subject", 60)
+            .with_scopes(vec!["read".to_string(), "write".to_string()])
+            .with_certificate_thumbprint("thumbprint");
+        let json = serde_json::to_value(IntrospectionResponse::from(claims)).unwrap();
+        assert_eq!(json["active"], true);
+        assert_eq!(json["scope"], "read write");
+        assert_eq!(json["sub"], "subject");
+        assert_eq!(json["token_type"], "Bearer");
+        assert_eq!(json["cnf"], serde_json::json!({ "x5t#S256": "thumbprint" }));
+
+        let claims = Claims::new("subject", 60).with_key_thumbprint("jkt");
+        let json = serde_json::to_value(IntrospectionResponse::from(claims)).unwrap();
+        assert_eq!(json["token_type"], DPOP_TOKEN_TYPE);
+        assert_eq!(json["cnf"], serde_json::json!({ "jkt": "jkt" }));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::routes;
+use actix_web::web;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+/// Path the OpenAPI document is served at.
+pub const SPEC_PATH: &str = "/openapi.json";
+/// Path the Swagger UI is served under.
+pub const UI_PATH: &str = "/docs";
+
+/// OpenAPI 3.1 description of the HTTP API, generated from the route handlers
+/// and the serde models they accept and return.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Buraq",
+        description = "Manage projects, environments, service accounts, keys and access tokens."
+    ),
+    paths(
+        routes::health::healthz,
+        routes::health::readyz,
+        routes::health::status,
+        routes::metrics::export,
+        routes::project::create,
+        routes::project::list,
+        routes::project::read,
+        routes::project::update,
+        routes::project::delete,
+        routes::project::restore,
+        routes::project::export,
+        routes::project::import,
+        routes::environment::create,
+        routes::environment::list,
+        routes::environment::read,
+        routes::environment::update,
+        routes::environment::delete,
+        routes::environment::restore,
+        routes::access_token::create,
+        routes::access_token::list,
+        routes::access_token::read,
+        routes::access_token::update,
+        routes::access_token::delete,
+        routes::access_token::restore,
+        routes::service_account::create,
+        routes::service_account::list,
+        routes::service_account::read,
+        routes::service_account::update_service_account,
+        routes::service_account::delete,
+        routes::service_account::restore,
+        routes::project_access::create,
+        routes::project_access::list,
+        routes::project_access::read,
+        routes::project_access::update,
+        routes::project_access::delete,
+        routes::project_access::restore,
+        routes::project_scope::create,
+        routes::project_scope::list,
+        routes::project_scope::read,
+        routes::project_scope::update,
+        routes::project_scope::delete,
+        routes::project_scope::restore,
+        routes::project_template::create,
+        routes::project_template::list,
+        routes::project_template::read,
+        routes::project_template::update,
+        routes::project_template::delete,
+        routes::project_template::restore,
+        routes::server_key::create,
+        routes::server_key::list,
+        routes::server_key::read,
+        routes::server_key::update,
+        routes::server_key::delete,
+        routes::server_key::restore,
+        routes::server_key::rotate,
+        routes::service_account_key::create,
+        routes::service_account_key::list,
+        routes::service_account_key::read,
+        routes::service_account_key::update,
+        routes::service_account_key::delete,
+        routes::service_account_key::restore,
+        routes::token::token,
+        routes::token::introspect,
+        routes::token::verify,
+        routes::manifest::apply,
+    ),
+    tags(
+        (name = "health", description = "Liveness, readiness, status and metrics"),
+        (name = "projects", description = "Projects group environments and scopes"),
+        (name = "environments", description = "Deployment environments of a project"),
+        (name = "access-tokens", description = "Tokens issued for a project access"),
+        (name = "service-accounts", description = "Non-human identities"),
+        (name = "project-access", description = "Grants of project scopes to service accounts"),
+        (name = "project-scopes", description = "Permissions defined by a project"),
+        (name = "project-templates", description = "Layouts new projects are scaffolded from"),
+        (name = "server-keys", description = "Signing keys of an environment"),
+        (name = "service-account-keys", description = "Keys a service account signs with"),
+        (name = "oauth", description = "OAuth 2.0 token issuance, introspection and verification for service accounts"),
+        (name = "manifests", description = "Declarative configuration applied by name"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Serves the OpenAPI document at [`SPEC_PATH`] and the Swagger UI rendering
+/// it under [`UI_PATH`].
+pub fn configure_routes(config: &mut web::ServiceConfig) {
+    config
+        .service(SwaggerUi::new(format!("{}/{{_:.*}}", UI_PATH)).url(SPEC_PATH, ApiDoc::openapi()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+    fn documented_routes() -> BTreeSet<(String, String)> {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let mut routes = BTreeSet::new();
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            for method in item.as_object().unwrap().keys() {
+                if METHODS.contains(&method.as_str()) {
+                    routes.insert((method.clone(), path.clone()));
+                }
+            }
+        }
+        routes
+    }
+
+    /// `path` with each `{placeholder}` replaced by an id.
+    fn concrete(path: &str) -> String {
+        let mut concrete = String::new();
+        let mut rest = path;
+        while let Some(start) = rest.find('{') {
+            let end = start + rest[start..].find('}').unwrap();
+            concrete.push_str(&rest[..start]);
+            concrete.push_str("00000000-0000-4000-8000-000000000000");
+            rest = &rest[end + 1..];
+        }
+        concrete.push_str(rest);
+        concrete
+    }
+
+    /// Sends each method to every documented path of the app built by
+    /// `routes::configure`, and checks that actix routes exactly the
+    /// documented methods to a resource registered under that path.
+    #[actix_web::test]
+    async fn test_every_route_is_documented() {
+        use actix_web::http::{Method, StatusCode};
+        use actix_web::{App, HttpResponse, test};
+
+        let app = test::init_service(
+            App::new()
+                .configure(routes::configure)
+                .default_service(web::to(HttpResponse::NotImplemented)),
+        )
+        .await;
+        let documented = documented_routes();
+        let paths: BTreeSet<&String> = documented.iter().map(|(_, path)| path).collect();
+
+        for path in paths {
+            for method in METHODS {
+                let request = test::TestRequest::default()
+                    .method(Method::from_bytes(method.to_uppercase().as_bytes()).unwrap())
+                    .uri(&concrete(path))
+                    .to_request();
+                let response = test::call_service(&app, request).await;
+                let pattern = response.request().match_pattern();
+                assert_eq!(
+                    pattern.as_deref(),
+                    Some(path.as_str()),
+                    "{} is served by another resource",
+                    path
+                );
+                let registered = !matches!(
+                    response.status(),
+                    StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+                );
+                let is_documented = documented.contains(&(method.to_string(), path.clone()));
+                assert_eq!(
+                    registered,
+                    is_documented,
+                    "{} {} is {} but {}",
+                    method,
+                    path,
+                    if registered {
+                        "registered"
+                    } else {
+                        "not registered"
+                    },
+                    if is_documented {
+                        "documented"
+                    } else {
+                        "not documented"
+                    },
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_spec_describes_models() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3.1"));
+
+        let schemas = &spec["components"]["schemas"];
+        assert_eq!(schemas["Algorithm"]["enum"][5], "RS256");
+        assert_eq!(
+            schemas["ServerKeyCreatePayload"]["properties"]["algorithm"]["$ref"],
+            "#/components/schemas/Algorithm"
+        );
+        assert_eq!(schemas["Project"]["properties"]["_id"]["format"], "uuid");
+
+        let parameters = spec["paths"]["/projects"]["get"]["parameters"]
+            .as_array()
+            .unwrap();
+        let names: Vec<&str> = parameters
+            .iter()
+            .map(|parameter| parameter["name"].as_str().unwrap())
+            .collect();
+        for name in ["label_selector", "id_in", "page", "cursor", "sort"] {
+            assert!(names.contains(&name), "{} missing from {:?}", name, names);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_serves_spec() {
+        use actix_web::{App, test};
+
+        let app = test::init_service(App::new().configure(configure_routes)).await;
+        let response = test::TestRequest::get()
+            .uri(SPEC_PATH)
+            .send_request(&app)
+            .await;
+        assert!(response.status().is_success());
+        let spec: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(spec["info"]["title"], "Buraq");
+
+        let response = test::TestRequest::get()
+            .uri(&format!("{}/", UI_PATH))
+            .send_request(&app)
+            .await;
+        assert!(response.status().is_success());
+    }
+}
@@ -1,12 +1,14 @@
+use crate::errors::AppError;
 use crate::models::access_token::{
     AccessToken, AccessTokenFilter, AccessTokenSortableFields, AccessTokenUpdatePayload,
 };
-use crate::models::pagination::Pagination;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use anyhow::Error;
-use anyhow::Result;
+use crate::repositories::base::{IdFilter, Repository, exclude_deleted};
+use crate::repositories::mongo;
+use crate::repositories::unit_of_work::Transaction;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use mongodb::IndexModel;
 use mongodb::bson::to_document;
@@ -21,17 +23,18 @@ pub struct AccessTokenRepository {
 }
 
 impl AccessTokenRepository {
-    pub fn new(database: Database) -> Result<Self, anyhow::Error> {
+    pub fn new(database: Database) -> Result<Self, AppError> {
         let collection = database.collection::<AccessToken>("access_tokens");
         Ok(Self { collection })
     }
 
-    pub async fn ensure_indexes(&self) -> Result<(), Error> {
+    #[tracing::instrument(skip_all, name = "AccessTokenRepository::ensure_indexes")]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
         let _ = &self.collection.create_index(
             IndexModel::builder()
                 .keys(mongodb::bson::doc! { "project_access_id": 1, "algorithm": 1, "expires_at": 1 })
                 .build()
-        ).await.expect("Failed to create index on project_access_id, algorithm, expires_at");
+        ).await?;
 
         let _ = &self
             .collection
@@ -42,8 +45,7 @@ impl AccessTokenRepository {
                     )
                     .build(),
             )
-            .await
-            .expect("Failed to create index on project_access_id, algorithm, active");
+            .await?;
 
         let _ = &self
             .collection
@@ -52,57 +54,79 @@ impl AccessTokenRepository {
                     .keys(mongodb::bson::doc! { "project_access_id": 1, "enabled": 1 })
                     .build(),
             )
-            .await
-            .expect("Failed to create index on project_access_id, enabled");
+            .await?;
 
         Ok(())
     }
+
+    pub fn collection(&self) -> Result<Collection<AccessToken>, AppError> {
+        Ok(self.collection.clone())
+    }
 }
 
+/// A access token repository of any storage.
+pub type DynAccessTokenRepository = dyn Repository<
+        AccessToken,
+        UpdatePayload = AccessTokenUpdatePayload,
+        Filter = AccessTokenFilter,
+        Sort = AccessTokenSortableFields,
+    > + Send
+    + Sync;
+
 #[async_trait]
 impl Repository<AccessToken> for AccessTokenRepository {
     type UpdatePayload = AccessTokenUpdatePayload;
     type Filter = AccessTokenFilter;
     type Sort = AccessTokenSortableFields;
 
-    async fn create(&self, mut item: AccessToken) -> Result<AccessToken, Error> {
+    #[tracing::instrument(skip_all, name = "AccessTokenRepository::create")]
+    async fn create_in(
+        &self,
+        mut item: AccessToken,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<AccessToken, AppError> {
         if item.id.is_none() {
             item.id = Some(Uuid::new());
         }
+        item.deleted_at = None;
+        item.deleted_by = None;
 
-        self.collection
-            .insert_one(&item)
-            .await
-            .expect("Failed to create access token");
+        let action = self.collection.insert_one(&item);
+        match transaction {
+            Some(transaction) => action.session(transaction.session()).await?,
+            None => action.await?,
+        };
 
         Ok(item)
     }
 
-    async fn read(&self, id: Uuid) -> Result<Option<AccessToken>, Error> {
+    #[tracing::instrument(skip_all, name = "AccessTokenRepository::read")]
+    async fn read(&self, id: Uuid) -> Result<Option<AccessToken>, AppError> {
         let result = self
             .collection
-            .find_one(mongodb::bson::doc! { "_id": id })
+            .find_one(exclude_deleted(mongodb::bson::doc! { "_id": id }))
             .await?;
         Ok(result)
     }
 
-    async fn update(&self, id: Uuid, item: Self::UpdatePayload) -> Result<AccessToken, Error> {
+    #[tracing::instrument(skip_all, name = "AccessTokenRepository::update")]
+    async fn update(&self, id: Uuid, item: Self::UpdatePayload) -> Result<AccessToken, AppError> {
         let document = to_document(&item)?;
         self.collection
             .update_one(
-                mongodb::bson::doc! { "_id": id },
+                exclude_deleted(mongodb::bson::doc! { "_id": id }),
                 mongodb::bson::doc! { "$set": document },
             )
             .await?;
         let updated = self
-            .collection
-            .find_one(mongodb::bson::doc! { "_id": id })
+            .read(id)
             .await?
-            .unwrap();
+            .ok_or_else(|| AppError::NotFound("AccessToken not found".to_string()))?;
         Ok(updated)
     }
 
-    async fn delete(&self, id: Uuid) -> Result<bool, Error> {
+    #[tracing::instrument(skip_all, name = "AccessTokenRepository::delete")]
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
         let result = self
             .collection
             .delete_one(mongodb::bson::doc! { "_id": id })
@@ -110,13 +134,14 @@ impl Repository<AccessToken> for AccessTokenRepository {
         Ok(result.deleted_count > 0)
     }
 
+    #[tracing::instrument(skip_all, name = "AccessTokenRepository::find")]
     async fn find(
         &self,
         filter: Self::Filter,
         sort: Option<SortBuilder<Self::Sort>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<AccessToken>, Error> {
-        let filter_doc = filter.into();
+    ) -> Result<Vec<AccessToken>, AppError> {
+        let filter_doc = exclude_deleted(filter.into());
 
         // Create FindOptions
         let mut options = mongodb::options::FindOptions::default();
@@ -139,8 +164,62 @@ impl Repository<AccessToken> for AccessTokenRepository {
         Ok(items)
     }
 
-    fn collection(&self) -> Result<Collection<AccessToken>, Error> {
-        Ok(self.collection.clone())
+    async fn count(&self, filter: Self::Filter) -> Result<u64, AppError> {
+        mongo::count(&self.collection, filter.into()).await
+    }
+
+    async fn find_page(
+        &self,
+        filter: Self::Filter,
+        sort: Option<SortBuilder<Self::Sort>>,
+        pagination: Pagination,
+    ) -> Result<Page<AccessToken>, AppError> {
+        let sort = sort.map(SortBuilder::to_document);
+        mongo::find_page(&self.collection, filter.into(), sort, pagination).await
+    }
+
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        mongo::soft_delete(&self.collection, id, deleted_by).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<Option<AccessToken>, AppError> {
+        if !mongo::restore(&self.collection, id).await? {
+            return Ok(None);
+        }
+        self.read(id).await
+    }
+
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError> {
+        mongo::is_deleted(&self.collection, id).await
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        mongo::purge(&self.collection, deleted_before).await
+    }
+
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        mongo::distinct_ids(&self.collection, filter, transaction).await
+    }
+
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError> {
+        mongo::soft_delete_many(
+            &self.collection,
+            filter,
+            deleted_at,
+            deleted_by,
+            transaction,
+        )
+        .await
     }
 }
 
@@ -172,6 +251,8 @@ mod tests {
             created_at: now,
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = repo.create(token.clone()).await.unwrap();
@@ -193,6 +274,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = repo.create(token.clone()).await.unwrap();
@@ -214,6 +297,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = repo.create(token).await.unwrap();
@@ -242,6 +327,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = repo.create(token).await.unwrap();
@@ -265,6 +352,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         repo.create(token).await.unwrap();
@@ -275,6 +364,7 @@ mod tests {
             is_enabled: None,
             is_active: None,
             project_access_id: None,
+            ..Default::default()
         };
 
         let found = repo.find(filter, None, None).await.unwrap();
@@ -294,6 +384,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         repo.create(token).await.unwrap();
@@ -304,6 +396,7 @@ mod tests {
             is_enabled: None,
             is_active: None,
             project_access_id: None,
+            ..Default::default()
         };
 
         let found = repo.find(filter, None, None).await.unwrap();
@@ -323,6 +416,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         repo.create(token).await.unwrap();
@@ -333,6 +428,7 @@ mod tests {
             is_enabled: Some(true),
             is_active: None,
             project_access_id: None,
+            ..Default::default()
         };
 
         let found = repo.find(filter, None, None).await.unwrap();
@@ -353,6 +449,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id,
+            deleted_at: None,
+            deleted_by: None,
         };
 
         repo.create(token).await.unwrap();
@@ -363,6 +461,7 @@ mod tests {
             is_enabled: None,
             is_active: None,
             project_access_id: Some(project_access_id),
+            ..Default::default()
         };
 
         let found = repo.find(filter, None, None).await.unwrap();
@@ -1,31 +1,211 @@
-use crate::models::{pagination::Pagination, sort::SortBuilder};
-use anyhow::Error;
+use crate::errors::AppError;
+use crate::models::pagination::{Page, Pagination};
+use crate::models::sort::SortBuilder;
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use mongodb::Collection;
 use mongodb::bson::uuid::Uuid;
+use mongodb::bson::{Bson, Document, doc};
+use mongodb::error::ErrorKind;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 
+/// Filter matching documents that have not been soft-deleted.
+pub fn not_deleted() -> Document {
+    doc! { "deleted_at": Bson::Null }
+}
+
+/// Adds the soft-delete exclusion to a filter document.
+pub fn exclude_deleted(mut filter: Document) -> Document {
+    filter.extend(not_deleted());
+    filter
+}
+
+/// Filter matching documents that were soft-deleted before `deleted_before`.
+///
+/// `deleted_at` is compared in the canonical string form timestamps are
+/// stored in, and as a BSON date for records written in that form.
+pub fn deleted_before(deleted_before: DateTime<Utc>) -> Result<Document, AppError> {
+    Ok(doc! {
+        "$or": [
+            { "deleted_at": { "$lt": timestamp::to_bson(&deleted_before) } },
+            { "deleted_at": { "$lt": mongodb::bson::DateTime::from_millis(deleted_before.timestamp_millis()) } },
+        ]
+    })
+}
+
+/// Field an [`IdFilter`] matches ids against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdField {
+    /// The record's own id
+    Id,
+    ProjectId,
+    EnvironmentId,
+    ServiceAccountId,
+    ProjectAccessId,
+}
+
+impl IdField {
+    /// Name of the field in stored records.
+    pub fn name(self) -> &'static str {
+        match self {
+            IdField::Id => "_id",
+            IdField::ProjectId => "project_id",
+            IdField::EnvironmentId => "environment_id",
+            IdField::ServiceAccountId => "service_account_id",
+            IdField::ProjectAccessId => "project_access_id",
+        }
+    }
+}
+
+/// Selects records by their id, or by the id of the record they belong to,
+/// for [`Repository::distinct_ids`] and [`Repository::soft_delete_many`].
+///
+/// Only live records are selected unless [`IdFilter::with_deleted`] is set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IdFilter {
+    pub field: IdField,
+    pub ids: Vec<Uuid>,
+    pub with_deleted: bool,
+}
+
+impl IdFilter {
+    /// Live records whose id is one of `ids`.
+    pub fn ids(ids: impl Into<Vec<Uuid>>) -> Self {
+        Self::by(IdField::Id, ids)
+    }
+
+    /// Live records whose `field` is one of `ids`.
+    pub fn by(field: IdField, ids: impl Into<Vec<Uuid>>) -> Self {
+        Self {
+            field,
+            ids: ids.into(),
+            with_deleted: false,
+        }
+    }
+
+    /// Selects soft-deleted records as well.
+    pub fn with_deleted(mut self) -> Self {
+        self.with_deleted = true;
+        self
+    }
+}
+
 #[async_trait]
 pub trait Repository<T: Send + Sync + Serialize + DeserializeOwned + 'static> {
     type UpdatePayload: Send + Sync + Serialize + DeserializeOwned + 'static;
     type Filter: Send + Sync + Serialize + DeserializeOwned + 'static;
-    type Sort: Send + Sync + Serialize + DeserializeOwned + 'static;
+    type Sort: Send + Sync + Serialize + DeserializeOwned + Into<String> + Clone + 'static;
 
-    async fn create(&self, mut item: T) -> Result<T, Error>;
+    async fn create(&self, item: T) -> Result<T, AppError> {
+        self.create_in(item, None).await
+    }
 
-    async fn read(&self, id: Uuid) -> Result<Option<T>, Error>;
+    /// Creates a record, assigning its id when missing.
+    ///
+    /// Runs in `transaction` when given, so it commits or rolls back with it.
+    async fn create_in(
+        &self,
+        item: T,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<T, AppError>;
+
+    /// Reads a record by id. Soft-deleted records are not returned.
+    async fn read(&self, id: Uuid) -> Result<Option<T>, AppError>;
 
-    async fn update(&self, id: Uuid, update: Self::UpdatePayload) -> Result<T, Error>;
+    async fn update(&self, id: Uuid, update: Self::UpdatePayload) -> Result<T, AppError>;
 
-    async fn delete(&self, id: Uuid) -> Result<bool, Error>;
+    /// Permanently removes a record, whether or not it was soft-deleted.
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError>;
 
+    /// Finds records matching the filter. Soft-deleted records are not returned.
     async fn find(
         &self,
         filter: Self::Filter,
         sort: Option<SortBuilder<Self::Sort>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<T>, Error>;
+    ) -> Result<Vec<T>, AppError>;
+
+    /// Counts records matching the filter. Soft-deleted records are not counted.
+    async fn count(&self, filter: Self::Filter) -> Result<u64, AppError>;
+
+    /// Finds one page of records matching the filter, with the total count.
+    ///
+    /// Uses the cursor in `pagination` when present and the page number
+    /// otherwise. `_id` is appended to the sort so the order, and therefore
+    /// every cursor, is stable.
+    async fn find_page(
+        &self,
+        filter: Self::Filter,
+        sort: Option<SortBuilder<Self::Sort>>,
+        pagination: Pagination,
+    ) -> Result<Page<T>, AppError>;
+
+    /// Marks a record as deleted without removing it.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a live record was soft-deleted, `false` if none was found.
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError>;
+
+    /// Brings a soft-deleted record back.
+    ///
+    /// # Returns
+    ///
+    /// The restored record, or `None` if there was no soft-deleted record with this id.
+    async fn restore(&self, id: Uuid) -> Result<Option<T>, AppError>;
+
+    /// Returns `true` if a record with this id exists and has been soft-deleted.
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError>;
+
+    /// Permanently removes records that were soft-deleted before `deleted_before`.
+    ///
+    /// # Returns
+    ///
+    /// The number of records removed.
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError>;
+
+    /// Returns the ids of the records matching `filter`.
+    ///
+    /// Runs in `transaction` when given, so it sees the writes made in it.
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError>;
+
+    /// Marks every record matching `filter` as deleted at `deleted_at`.
+    ///
+    /// Runs in `transaction` when given, so it commits or rolls back with it.
+    ///
+    /// # Returns
+    ///
+    /// The number of records marked.
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError>;
+}
 
-    fn collection(&self) -> Result<Collection<T>, Error>;
+/// Drops an index by name, ignoring the error raised when it does not exist.
+///
+/// Used to retire indexes whose definition has changed, since `create_index`
+/// will not replace an existing index with the same keys prefix.
+pub async fn drop_index_if_exists<T: Send + Sync>(
+    collection: &Collection<T>,
+    name: &str,
+) -> Result<(), AppError> {
+    match collection.drop_index(name).await {
+        Ok(()) => Ok(()),
+        Err(e) => match *e.kind {
+            // IndexNotFound / NamespaceNotFound
+            ErrorKind::Command(ref command) if command.code == 27 || command.code == 26 => Ok(()),
+            _ => Err(e.into()),
+        },
+    }
 }
@@ -0,0 +1,107 @@
+use crate::errors::AppError;
+use crate::models::dpop_proof::UsedDpopProof;
+use async_trait::async_trait;
+use mongodb::bson::doc;
+use mongodb::options::IndexOptions;
+use mongodb::{Collection, Database, IndexModel};
+use std::time::Duration;
+
+/// Repository for the DPoP proofs accepted by every instance.
+///
+/// Used proofs are not soft-deleted or listed, so this does not implement
+/// [`Repository`](crate::repositories::base::Repository).
+pub struct DpopProofRepository {
+    collection: Collection<UsedDpopProof>,
+}
+
+impl DpopProofRepository {
+    pub fn new(database: Database) -> Result<Self, AppError> {
+        let collection = database.collection::<UsedDpopProof>("dpop_proofs");
+        Ok(Self { collection })
+    }
+
+    #[tracing::instrument(skip_all, name = "DpopProofRepository::ensure_indexes")]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        // Proofs are removed by MongoDB once they are no longer fresh
+        let _ = &self
+            .collection
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "expires_at": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .expire_after(Duration::from_secs(0))
+                            .build(),
+                    )
+                    .build(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub fn collection(&self) -> Result<Collection<UsedDpopProof>, AppError> {
+        Ok(self.collection.clone())
+    }
+}
+
+/// The DPoP proofs accepted by every instance.
+#[async_trait]
+pub trait DpopProofStore: Send + Sync {
+    /// Records a proof as used.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the proof had already been used.
+    async fn record(&self, proof: &UsedDpopProof) -> Result<bool, AppError>;
+}
+
+#[async_trait]
+impl DpopProofStore for DpopProofRepository {
+    #[tracing::instrument(skip_all, name = "DpopProofRepository::record")]
+    async fn record(&self, proof: &UsedDpopProof) -> Result<bool, AppError> {
+        match self.collection.insert_one(proof).await {
+            Ok(_) => Ok(true),
+            Err(e) => match AppError::from(e) {
+                AppError::Conflict(_) => Ok(false),
+                error => Err(error),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Result;
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn test_record_refuses_replays() -> Result<()> {
+        let db = setup_test_db("dpop_proof").await?;
+        let repo = DpopProofRepository::new(db.clone())?;
+        let expires_at = Utc::now() + chrono::Duration::seconds(60);
+
+        assert!(
+            repo.record(&UsedDpopProof::new("key", "1", expires_at))
+                .await?
+        );
+        assert!(
+            !repo
+                .record(&UsedDpopProof::new("key", "1", expires_at))
+                .await?
+        );
+        assert!(
+            repo.record(&UsedDpopProof::new("key", "2", expires_at))
+                .await?
+        );
+        assert!(
+            repo.record(&UsedDpopProof::new("other", "1", expires_at))
+                .await?
+        );
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+}
@@ -1,15 +1,18 @@
+use crate::errors::AppError;
 use crate::models::environment::{
     Environment, EnvironmentFilter, EnvironmentSortableFields, EnvironmentUpdatePayload,
 };
-use crate::models::pagination::Pagination;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use anyhow::{Error, Result};
+use crate::repositories::base::{IdFilter, Repository, drop_index_if_exists, exclude_deleted};
+use crate::repositories::mongo;
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use mongodb::bson::Uuid;
-use mongodb::bson::{Bson, doc, to_document};
+use mongodb::bson::{doc, to_document};
 use mongodb::options::IndexOptions;
 use mongodb::{Collection, Database, IndexModel};
 
@@ -30,22 +33,25 @@ impl EnvironmentRepository {
     /// # Returns
     ///
     /// Returns a Result containing the EnvironmentRepository or an error if initialization fails.
-    pub fn new(database: Database) -> Result<Self, Error> {
+    pub fn new(database: Database) -> Result<Self, AppError> {
         let collection = database.collection::<Environment>("environments");
         Ok(Self { collection })
     }
 
-    pub async fn ensure_indexes(&self) -> Result<(), Error> {
+    #[tracing::instrument(skip_all, name = "EnvironmentRepository::ensure_indexes")]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        // Unique constraints only apply to records that have not been soft-deleted
+        drop_index_if_exists(&self.collection, "project_id_1_name_1").await?;
+
         let _ = &self
             .collection
             .create_index(
                 IndexModel::builder()
-                    .keys(doc! { "project_id": 1, "name": 1 })
+                    .keys(doc! { "project_id": 1, "name": 1, "deleted_at": 1 })
                     .options(IndexOptions::builder().unique(true).build())
                     .build(),
             )
-            .await
-            .expect("Failed to create index on project_id, name");
+            .await?;
 
         let _ = &self
             .collection
@@ -54,60 +60,103 @@ impl EnvironmentRepository {
                     .keys(doc! { "project_id": 1, "enabled": 1 })
                     .build(),
             )
-            .await
-            .expect("Failed to create index on project_id, enabled");
+            .await?;
+
+        // Wildcard index so label selectors can match on any label key
+        let _ = &self
+            .collection
+            .create_index(IndexModel::builder().keys(doc! { "labels.$**": 1 }).build())
+            .await?;
 
         Ok(())
     }
+
+    pub fn collection(&self) -> Result<Collection<Environment>, AppError> {
+        Ok(self.collection.clone())
+    }
 }
 
+/// A environment repository of any storage.
+pub type DynEnvironmentRepository = dyn Repository<
+        Environment,
+        UpdatePayload = EnvironmentUpdatePayload,
+        Filter = EnvironmentFilter,
+        Sort = EnvironmentSortableFields,
+    > + Send
+    + Sync;
+
 #[async_trait]
 impl Repository<Environment> for EnvironmentRepository {
     type UpdatePayload = EnvironmentUpdatePayload;
     type Filter = EnvironmentFilter;
     type Sort = EnvironmentSortableFields;
 
-    async fn create(&self, mut item: Environment) -> Result<Environment, Error> {
+    #[tracing::instrument(skip_all, name = "EnvironmentRepository::create")]
+    async fn create_in(
+        &self,
+        mut item: Environment,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Environment, AppError> {
         if item.id.is_none() {
             item.id = Some(Uuid::new());
         }
+        item.deleted_at = None;
+        item.deleted_by = None;
         item.created_at = Some(Utc::now());
         item.updated_at = Some(Utc::now());
-        self.collection.insert_one(&item).await?;
+        let action = self.collection.insert_one(&item);
+        match transaction {
+            Some(transaction) => action.session(transaction.session()).await?,
+            None => action.await?,
+        };
         Ok(item)
     }
 
-    async fn read(&self, id: Uuid) -> Result<Option<Environment>, Error> {
-        let result = self.collection.find_one(doc! { "_id": id }).await?;
+    #[tracing::instrument(skip_all, name = "EnvironmentRepository::read")]
+    async fn read(&self, id: Uuid) -> Result<Option<Environment>, AppError> {
+        let result = self
+            .collection
+            .find_one(exclude_deleted(doc! { "_id": id }))
+            .await?;
         Ok(result)
     }
 
-    async fn update(&self, id: Uuid, payload: Self::UpdatePayload) -> Result<Environment, Error> {
+    #[tracing::instrument(skip_all, name = "EnvironmentRepository::update")]
+    async fn update(
+        &self,
+        id: Uuid,
+        payload: Self::UpdatePayload,
+    ) -> Result<Environment, AppError> {
         let mut document = to_document(&payload)?;
-        document.insert("updated_at", Bson::String(Utc::now().to_rfc3339()));
+        document.insert("updated_at", timestamp::to_bson(&Utc::now()));
 
         self.collection
-            .update_one(doc! { "_id": id }, doc! { "$set": document })
+            .update_one(
+                exclude_deleted(doc! { "_id": id }),
+                doc! { "$set": document },
+            )
             .await?;
         let updated = self
             .read(id)
             .await?
-            .ok_or_else(|| Error::msg("Environment not found"))?;
+            .ok_or_else(|| AppError::NotFound("Environment not found".to_string()))?;
         Ok(updated)
     }
 
-    async fn delete(&self, id: Uuid) -> Result<bool, Error> {
+    #[tracing::instrument(skip_all, name = "EnvironmentRepository::delete")]
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
         let result = self.collection.delete_one(doc! { "_id": id }).await?;
         Ok(result.deleted_count > 0)
     }
 
+    #[tracing::instrument(skip_all, name = "EnvironmentRepository::find")]
     async fn find(
         &self,
         filter: Self::Filter,
         sort: Option<SortBuilder<Self::Sort>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<Environment>, Error> {
-        let filter_doc = filter.into();
+    ) -> Result<Vec<Environment>, AppError> {
+        let filter_doc = exclude_deleted(filter.into());
 
         // Create FindOptions
         let mut options = mongodb::options::FindOptions::default();
@@ -130,8 +179,62 @@ impl Repository<Environment> for EnvironmentRepository {
         Ok(items)
     }
 
-    fn collection(&self) -> Result<Collection<Environment>, Error> {
-        Ok(self.collection.clone())
+    async fn count(&self, filter: Self::Filter) -> Result<u64, AppError> {
+        mongo::count(&self.collection, filter.into()).await
+    }
+
+    async fn find_page(
+        &self,
+        filter: Self::Filter,
+        sort: Option<SortBuilder<Self::Sort>>,
+        pagination: Pagination,
+    ) -> Result<Page<Environment>, AppError> {
+        let sort = sort.map(SortBuilder::to_document);
+        mongo::find_page(&self.collection, filter.into(), sort, pagination).await
+    }
+
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        mongo::soft_delete(&self.collection, id, deleted_by).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<Option<Environment>, AppError> {
+        if !mongo::restore(&self.collection, id).await? {
+            return Ok(None);
+        }
+        self.read(id).await
+    }
+
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError> {
+        mongo::is_deleted(&self.collection, id).await
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        mongo::purge(&self.collection, deleted_before).await
+    }
+
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        mongo::distinct_ids(&self.collection, filter, transaction).await
+    }
+
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError> {
+        mongo::soft_delete_many(
+            &self.collection,
+            filter,
+            deleted_at,
+            deleted_by,
+            transaction,
+        )
+        .await
     }
 }
 
@@ -139,6 +242,7 @@ impl Repository<Environment> for EnvironmentRepository {
 mod tests {
     use super::*;
     use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Result;
 
     async fn setup() -> (EnvironmentRepository, Database) {
         let db = setup_test_db("environment").await.unwrap();
@@ -162,6 +266,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = repo.create(environment).await?;
@@ -187,6 +294,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         repo.create(environment1).await?;
 
@@ -198,6 +308,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let result = repo.create(environment2).await;
 
@@ -220,6 +333,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let created = repo.create(environment).await?;
 
@@ -247,6 +363,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let created = repo.create(environment).await?;
 
@@ -254,6 +373,7 @@ mod tests {
             name: Some("Updated Environment".to_string()),
             description: Some("Updated Description".to_string()),
             enabled: Some(false),
+            labels: None,
         };
 
         let updated = repo.update(created.id.unwrap(), update).await?;
@@ -278,6 +398,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let created = repo.create(environment).await?;
 
@@ -291,6 +414,50 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_soft_delete_and_restore_environment() -> Result<()> {
+        let (repo, db) = setup().await;
+
+        let project_id = Uuid::new();
+        let environment = Environment {
+            id: None,
+            project_id,
+            name: "Test Environment".to_string(),
+            description: "Test Description".to_string(),
+            enabled: true,
+            created_at: Some(Utc::now()),
+            updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
+        };
+        let created = repo.create(environment.clone()).await?;
+        let id = created.id.unwrap();
+
+        assert!(repo.soft_delete(id, Some("alice".to_string())).await?);
+        assert!(!repo.soft_delete(id, None).await?);
+        assert!(repo.is_deleted(id).await?);
+        assert!(repo.read(id).await?.is_none());
+        let filter = EnvironmentFilter {
+            project_id: Some(project_id),
+            ..Default::default()
+        };
+        assert!(repo.find(filter, None, None).await?.is_empty());
+
+        // The name is free again while the original is soft-deleted
+        let replacement = repo.create(environment).await?;
+        repo.delete(replacement.id.unwrap()).await?;
+
+        let restored = repo.restore(id).await?.unwrap();
+        assert_eq!(restored.id, Some(id));
+        assert!(restored.deleted_at.is_none());
+        assert!(restored.deleted_by.is_none());
+        assert!(repo.restore(id).await?.is_none());
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_find_environments() -> Result<()> {
         let (repo, db) = setup().await;
@@ -304,6 +471,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let environment2 = Environment {
             id: None,
@@ -313,6 +483,9 @@ mod tests {
             enabled: false,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         repo.create(environment1).await?;
@@ -323,6 +496,7 @@ mod tests {
             project_id: None,
             name: None,
             is_enabled: None,
+            ..Default::default()
         };
         let all_environments = repo.find(filter, None, None).await?;
         assert_eq!(all_environments.len(), 2);
@@ -332,6 +506,7 @@ mod tests {
             project_id: None,
             name: Some("Environment 1".to_string()),
             is_enabled: None,
+            ..Default::default()
         };
         let environments = repo.find(name_filter, None, None).await?;
         assert_eq!(environments.len(), 1);
@@ -342,6 +517,7 @@ mod tests {
             project_id: None,
             name: None,
             is_enabled: Some(true),
+            ..Default::default()
         };
         let enabled_environments = repo.find(enabled_filter, None, None).await?;
         assert_eq!(enabled_environments.len(), 1);
@@ -351,6 +527,7 @@ mod tests {
             project_id: None,
             name: None,
             is_enabled: Some(false),
+            ..Default::default()
         };
         let disabled_environments = repo.find(disabled_filter, None, None).await?;
         assert_eq!(disabled_environments.len(), 1);
@@ -361,6 +538,7 @@ mod tests {
             project_id: None,
             name: Some("Non-existent".to_string()),
             is_enabled: None,
+            ..Default::default()
         };
         let non_matching = repo.find(non_matching_filter, None, None).await?;
         assert_eq!(non_matching.len(), 0);
@@ -382,6 +560,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         repo.create(environment).await?;
 
@@ -389,6 +570,7 @@ mod tests {
             project_id: Some(project_id),
             name: None,
             is_enabled: None,
+            ..Default::default()
         };
         let environments = repo.find(filter, None, None).await?;
         assert_eq!(environments.len(), 1);
@@ -410,6 +592,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         repo.create(environment).await?;
 
@@ -417,6 +602,7 @@ mod tests {
             project_id: None,
             name: Some("Test Environment".to_string()),
             is_enabled: None,
+            ..Default::default()
         };
         let environments = repo.find(filter, None, None).await?;
         assert_eq!(environments.len(), 1);
@@ -438,6 +624,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         repo.create(environment).await?;
 
@@ -445,6 +634,7 @@ mod tests {
             project_id: None,
             name: None,
             is_enabled: Some(true),
+            ..Default::default()
         };
         let environments = repo.find(filter, None, None).await?;
         assert_eq!(environments.len(), 1);
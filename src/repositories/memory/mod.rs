@@ -0,0 +1,933 @@
+//! Repositories that keep their records in memory instead of MongoDB, for
+//! tests and for embedding Buraq without a database.
+//!
+//! Records are held as the BSON documents MongoDB would store, so the filters,
+//! sorts and pagination cursors the models build are evaluated the same way
+//! (see [`query`]) and the unique indexes of `ensure_indexes` are enforced.
+//! Nothing is persisted, and there are no transactions: the `transaction`
+//! given to the `*_in` and `*_many` methods of [`Repository`] is ignored.
+
+pub mod query;
+
+use crate::errors::AppError;
+use crate::models::access_token::{
+    AccessToken, AccessTokenFilter, AccessTokenSortableFields, AccessTokenUpdatePayload,
+};
+use crate::models::dpop_proof::UsedDpopProof;
+use crate::models::environment::{
+    Environment, EnvironmentFilter, EnvironmentSortableFields, EnvironmentUpdatePayload,
+};
+use crate::models::pagination::{Cursor, Page, Pagination};
+use crate::models::project::{Project, ProjectFilter, ProjectSortableFields, ProjectUpdatePayload};
+use crate::models::project_access::{
+    ProjectAccess, ProjectAccessFilter, ProjectAccessSortableFields, ProjectAccessUpdatePayload,
+};
+use crate::models::project_scope::{
+    ProjectScope, ProjectScopeFilter, ProjectScopeSortableFields, ProjectScopeUpdatePayload,
+};
+use crate::models::project_template::{
+    ProjectTemplate, ProjectTemplateFilter, ProjectTemplateSortableFields,
+    ProjectTemplateUpdatePayload,
+};
+use crate::models::rate_limit::{RateLimitKey, RateLimitWindow};
+use crate::models::server_key::{
+    ServerKey, ServerKeyFilter, ServerKeySortableFields, ServerKeyUpdatePayload,
+};
+use crate::models::service_account::{
+    ServiceAccount, ServiceAccountFilter, ServiceAccountSortableFields, ServiceAccountUpdatePayload,
+};
+use crate::models::service_account_key::{
+    ServiceAccountKey, ServiceAccountKeyFilter, ServiceAccountKeySortableFields,
+    ServiceAccountKeyUpdatePayload,
+};
+use crate::models::sort::SortBuilder;
+use crate::repositories::base::{self, IdFilter, Repository, exclude_deleted};
+use crate::repositories::dpop_proof_repository::DpopProofStore;
+use crate::repositories::rate_limit_repository::RateLimitStore;
+use crate::repositories::service_account_repository::ServiceAccountStore;
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::bson::uuid::Uuid;
+use mongodb::bson::{Bson, Document, doc, from_bson, from_document, to_bson, to_document};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A [`Repository`] over records held in memory.
+///
+/// Clones share their records, like two repositories over the same collection.
+///
+/// # Type parameters
+/// - `T`: The model
+/// - `U`, `F`, `S`: Its update payload, filter and sortable fields
+pub struct MemoryRepository<T, U, F, S> {
+    /// Name of the model in "not found" errors
+    entity: &'static str,
+    /// Fields of each unique index
+    unique: &'static [&'static [&'static str]],
+    /// Whether `created_at` and `updated_at` are maintained
+    timestamps: bool,
+    records: Arc<Mutex<Vec<Document>>>,
+    #[allow(clippy::type_complexity)]
+    marker: PhantomData<fn() -> (T, U, F, S)>,
+}
+
+impl<T, U, F, S> Clone for MemoryRepository<T, U, F, S> {
+    fn clone(&self) -> Self {
+        Self {
+            records: self.records.clone(),
+            ..*self
+        }
+    }
+}
+
+impl<T, U, F, S> MemoryRepository<T, U, F, S> {
+    fn new(
+        entity: &'static str,
+        unique: &'static [&'static [&'static str]],
+        timestamps: bool,
+    ) -> Self {
+        Self {
+            entity,
+            unique,
+            timestamps,
+            records: Arc::new(Mutex::new(Vec::new())),
+            marker: PhantomData,
+        }
+    }
+
+    fn records(&self) -> Result<MutexGuard<'_, Vec<Document>>, AppError> {
+        self.records
+            .lock()
+            .map_err(|_| AppError::Internal(anyhow::anyhow!("In-memory records are poisoned")))
+    }
+
+    /// Fails like a unique index would if `record` would duplicate another
+    /// record than the one at `replacing`.
+    fn check_unique(
+        &self,
+        records: &[Document],
+        record: &Document,
+        replacing: Option<usize>,
+    ) -> Result<(), AppError> {
+        let key = |document: &Document, fields: &[&str]| -> Vec<Bson> {
+            fields
+                .iter()
+                .map(|field| {
+                    query::lookup(document, field)
+                        .cloned()
+                        .unwrap_or(Bson::Null)
+                })
+                .collect()
+        };
+        for fields in self.unique {
+            let wanted = key(record, fields);
+            let duplicate = records.iter().enumerate().any(|(position, other)| {
+                Some(position) != replacing && key(other, fields) == wanted
+            });
+            if duplicate {
+                return Err(AppError::Conflict(
+                    "A record with the same unique fields already exists".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The records matching `filter`, sorted by `sort`, after skipping `skip`
+    /// and keeping at most `limit`.
+    fn select(
+        &self,
+        filter: &Document,
+        sort: &Document,
+        skip: u64,
+        limit: Option<i64>,
+    ) -> Result<Vec<Document>, AppError> {
+        let mut selected = Vec::new();
+        for record in self.records()?.iter() {
+            if query::matches(record, filter)? {
+                selected.push(record.clone());
+            }
+        }
+        Ok(slice(selected, sort, skip, limit))
+    }
+
+    /// Applies `change` to the first record matching `filter`.
+    ///
+    /// # Returns
+    ///
+    /// The changed record, or `None` if none matched.
+    fn modify(
+        &self,
+        filter: &Document,
+        change: impl FnOnce(&mut Document) -> Result<(), AppError>,
+    ) -> Result<Option<Document>, AppError> {
+        let mut records = self.records()?;
+        let mut position = None;
+        for (index, record) in records.iter().enumerate() {
+            if query::matches(record, filter)? {
+                position = Some(index);
+                break;
+            }
+        }
+        let Some(position) = position else {
+            return Ok(None);
+        };
+        let mut record = records[position].clone();
+        change(&mut record)?;
+        self.check_unique(&records, &record, Some(position))?;
+        records[position] = record.clone();
+        Ok(Some(record))
+    }
+}
+
+/// Whether `record` is one of the records `filter` selects.
+pub(crate) fn selects(filter: &IdFilter, record: &Document) -> bool {
+    let live = matches!(record.get("deleted_at"), None | Some(Bson::Null));
+    let id = record
+        .get(filter.field.name())
+        .and_then(|value| from_bson::<Uuid>(value.clone()).ok());
+    (filter.with_deleted || live) && id.is_some_and(|id| filter.ids.contains(&id))
+}
+
+pub(crate) fn decode<T: DeserializeOwned>(documents: Vec<Document>) -> Result<Vec<T>, AppError> {
+    documents
+        .into_iter()
+        .map(|document| from_document(document).map_err(AppError::from))
+        .collect()
+}
+
+/// Sorts `records` by `sort` and keeps at most `limit` of them after skipping `skip`.
+pub(crate) fn slice(
+    mut records: Vec<Document>,
+    sort: &Document,
+    skip: u64,
+    limit: Option<i64>,
+) -> Vec<Document> {
+    records.sort_by(|a, b| query::compare(a, b, sort));
+    records
+        .into_iter()
+        .skip(skip as usize)
+        .take(limit.map_or(usize::MAX, |limit| limit.max(0) as usize))
+        .collect()
+}
+
+/// Cuts the page `pagination` asks for out of every record matching a filter,
+/// the way [`Repository::find_page`] pages a MongoDB collection.
+pub(crate) fn page<T: Serialize + DeserializeOwned>(
+    records: Vec<Document>,
+    sort: Option<Document>,
+    pagination: Pagination,
+) -> Result<Page<T>, AppError> {
+    let total = records.len() as u64;
+
+    let mut sort = sort.unwrap_or_default();
+    if !sort.contains_key("_id") {
+        sort.insert("_id", 1);
+    }
+
+    let records = match &pagination.cursor {
+        Some(cursor) => {
+            let after = Cursor::decode(cursor)?.filter(&sort)?;
+            let mut remaining = Vec::new();
+            for record in records {
+                if query::matches(&record, &after)? {
+                    remaining.push(record);
+                }
+            }
+            remaining
+        }
+        None => records,
+    };
+
+    // Keep one extra record to find out whether another page follows
+    let limit = pagination.limit();
+    let mut items: Vec<T> = decode(slice(records, &sort, pagination.skip(), Some(limit + 1)))?;
+
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        match items.last() {
+            Some(last) => Some(Cursor::after(&sort, last)?.encode()?),
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    Ok(Page {
+        items,
+        total,
+        page: pagination.page_number(),
+        limit: limit as u32,
+        next_cursor,
+    })
+}
+
+#[async_trait]
+impl<T, U, F, S> Repository<T> for MemoryRepository<T, U, F, S>
+where
+    T: Send + Sync + Serialize + DeserializeOwned + 'static,
+    U: Send + Sync + Serialize + DeserializeOwned + 'static,
+    F: Send + Sync + Serialize + DeserializeOwned + Into<Document> + 'static,
+    S: Send + Sync + Serialize + DeserializeOwned + Into<String> + Clone + 'static,
+{
+    type UpdatePayload = U;
+    type Filter = F;
+    type Sort = S;
+
+    async fn create_in(
+        &self,
+        item: T,
+        _transaction: Option<&mut Transaction>,
+    ) -> Result<T, AppError> {
+        let mut record = to_document(&item)?;
+        if matches!(record.get("_id"), None | Some(Bson::Null)) {
+            record.insert("_id", to_bson(&Uuid::new())?);
+        }
+        record.remove("deleted_at");
+        record.remove("deleted_by");
+        if self.timestamps {
+            record.insert("created_at", timestamp::to_bson(&Utc::now()));
+            record.insert("updated_at", timestamp::to_bson(&Utc::now()));
+        }
+        let mut records = self.records()?;
+        self.check_unique(&records, &record, None)?;
+        records.push(record.clone());
+        Ok(from_document(record)?)
+    }
+
+    async fn read(&self, id: Uuid) -> Result<Option<T>, AppError> {
+        let found = self.select(&exclude_deleted(doc! { "_id": id }), &doc! {}, 0, Some(1))?;
+        Ok(decode(found)?.pop())
+    }
+
+    async fn update(&self, id: Uuid, payload: U) -> Result<T, AppError> {
+        let mut changes = to_document(&payload)?;
+        if self.timestamps {
+            changes.insert("updated_at", timestamp::to_bson(&Utc::now()));
+        }
+        let updated = self.modify(&exclude_deleted(doc! { "_id": id }), |record| {
+            for (path, value) in changes {
+                query::set(record, &path, value);
+            }
+            Ok(())
+        })?;
+        match updated {
+            Some(record) => Ok(from_document(record)?),
+            None => Err(AppError::NotFound(format!("{} not found", self.entity))),
+        }
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
+        let id = to_bson(&id)?;
+        let mut records = self.records()?;
+        let before = records.len();
+        records.retain(|record| record.get("_id") != Some(&id));
+        Ok(records.len() < before)
+    }
+
+    async fn find(
+        &self,
+        filter: F,
+        sort: Option<SortBuilder<S>>,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<T>, AppError> {
+        let sort = sort.map(SortBuilder::to_document).unwrap_or_default();
+        let (skip, limit) = match pagination {
+            Some(pagination) => (pagination.skip(), Some(pagination.limit())),
+            None => (0, None),
+        };
+        decode(self.select(&exclude_deleted(filter.into()), &sort, skip, limit)?)
+    }
+
+    async fn count(&self, filter: F) -> Result<u64, AppError> {
+        let matching = self.select(&exclude_deleted(filter.into()), &doc! {}, 0, None)?;
+        Ok(matching.len() as u64)
+    }
+
+    async fn find_page(
+        &self,
+        filter: F,
+        sort: Option<SortBuilder<S>>,
+        pagination: Pagination,
+    ) -> Result<Page<T>, AppError> {
+        let matching = self.select(&exclude_deleted(filter.into()), &doc! {}, 0, None)?;
+        page(matching, sort.map(SortBuilder::to_document), pagination)
+    }
+
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        let filter = IdFilter::ids([id]);
+        let deleted = self.soft_delete_many(filter, Utc::now(), deleted_by, None);
+        Ok(deleted.await? > 0)
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<Option<T>, AppError> {
+        let restored = self.modify(
+            &doc! { "_id": id, "deleted_at": { "$ne": Bson::Null } },
+            |record| {
+                record.remove("deleted_at");
+                record.remove("deleted_by");
+                Ok(())
+            },
+        )?;
+        match restored {
+            Some(_) => self.read(id).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError> {
+        let filter = doc! { "_id": id, "deleted_at": { "$ne": Bson::Null } };
+        Ok(!self.select(&filter, &doc! {}, 0, Some(1))?.is_empty())
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        let filter = base::deleted_before(deleted_before)?;
+        let mut records = self.records()?;
+        let mut kept = Vec::with_capacity(records.len());
+        for record in records.iter() {
+            if !query::matches(record, &filter)? {
+                kept.push(record.clone());
+            }
+        }
+        let purged = records.len() - kept.len();
+        *records = kept;
+        Ok(purged as u64)
+    }
+
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        _transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        self.records()?
+            .iter()
+            .filter(|record| selects(&filter, record))
+            .filter_map(|record| record.get("_id").cloned())
+            .map(|id| from_bson::<Uuid>(id).map_err(AppError::from))
+            .collect()
+    }
+
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        _transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError> {
+        let changes =
+            doc! { "deleted_at": timestamp::to_bson(&deleted_at), "deleted_by": deleted_by };
+        let mut records = self.records()?;
+        let mut modified = 0;
+        for record in records.iter_mut() {
+            if !selects(&filter, record) {
+                continue;
+            }
+            let before = record.clone();
+            record.extend(changes.clone());
+            if *record != before {
+                modified += 1;
+            }
+        }
+        Ok(modified)
+    }
+}
+
+pub type MemoryProjectRepository =
+    MemoryRepository<Project, ProjectUpdatePayload, ProjectFilter, ProjectSortableFields>;
+pub type MemoryEnvironmentRepository = MemoryRepository<
+    Environment,
+    EnvironmentUpdatePayload,
+    EnvironmentFilter,
+    EnvironmentSortableFields,
+>;
+pub type MemoryProjectScopeRepository = MemoryRepository<
+    ProjectScope,
+    ProjectScopeUpdatePayload,
+    ProjectScopeFilter,
+    ProjectScopeSortableFields,
+>;
+pub type MemoryProjectTemplateRepository = MemoryRepository<
+    ProjectTemplate,
+    ProjectTemplateUpdatePayload,
+    ProjectTemplateFilter,
+    ProjectTemplateSortableFields,
+>;
+pub type MemoryProjectAccessRepository = MemoryRepository<
+    ProjectAccess,
+    ProjectAccessUpdatePayload,
+    ProjectAccessFilter,
+    ProjectAccessSortableFields,
+>;
+pub type MemoryServerKeyRepository =
+    MemoryRepository<ServerKey, ServerKeyUpdatePayload, ServerKeyFilter, ServerKeySortableFields>;
+pub type MemoryAccessTokenRepository = MemoryRepository<
+    AccessToken,
+    AccessTokenUpdatePayload,
+    AccessTokenFilter,
+    AccessTokenSortableFields,
+>;
+pub type MemoryServiceAccountRepository = MemoryRepository<
+    ServiceAccount,
+    ServiceAccountUpdatePayload,
+    ServiceAccountFilter,
+    ServiceAccountSortableFields,
+>;
+pub type MemoryServiceAccountKeyRepository = MemoryRepository<
+    ServiceAccountKey,
+    ServiceAccountKeyUpdatePayload,
+    ServiceAccountKeyFilter,
+    ServiceAccountKeySortableFields,
+>;
+
+// The unique indexes below mirror those of the `ensure_indexes` of each
+// MongoDB repository.
+
+impl MemoryProjectRepository {
+    pub fn projects() -> Self {
+        Self::new("Project", &[&["name", "deleted_at"]], true)
+    }
+}
+
+impl MemoryEnvironmentRepository {
+    pub fn environments() -> Self {
+        Self::new(
+            "Environment",
+            &[&["project_id", "name", "deleted_at"]],
+            true,
+        )
+    }
+}
+
+impl MemoryProjectScopeRepository {
+    pub fn project_scopes() -> Self {
+        Self::new(
+            "Project scope",
+            &[&["project_id", "name", "deleted_at"]],
+            true,
+        )
+    }
+}
+
+impl MemoryProjectTemplateRepository {
+    pub fn project_templates() -> Self {
+        Self::new("Project template", &[&["name", "deleted_at"]], true)
+    }
+}
+
+impl MemoryProjectAccessRepository {
+    pub fn project_access() -> Self {
+        Self::new(
+            "ProjectAccess",
+            &[&["service_account_id", "environment_id", "deleted_at"]],
+            true,
+        )
+    }
+}
+
+impl MemoryServerKeyRepository {
+    pub fn server_keys() -> Self {
+        Self::new(
+            "ServerKey",
+            &[&["environment_id", "key", "deleted_at"]],
+            true,
+        )
+    }
+}
+
+impl MemoryAccessTokenRepository {
+    pub fn access_tokens() -> Self {
+        Self::new("AccessToken", &[], false)
+    }
+}
+
+impl MemoryServiceAccountRepository {
+    pub fn service_accounts() -> Self {
+        Self::new(
+            "ServiceAccount",
+            &[&["email", "deleted_at"], &["user", "deleted_at"]],
+            true,
+        )
+    }
+}
+
+impl MemoryServiceAccountKeyRepository {
+    pub fn service_account_keys() -> Self {
+        Self::new(
+            "ServiceAccountKey",
+            &[&["service_account_id", "algorithm", "deleted_at"]],
+            true,
+        )
+    }
+}
+
+#[async_trait]
+impl ServiceAccountStore for MemoryServiceAccountRepository {
+    async fn record_failed_authentication(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<ServiceAccount>, AppError> {
+        let account = self.modify(&exclude_deleted(doc! { "_id": id }), |record| {
+            let failed = match record.get("failed_authentications") {
+                Some(Bson::Int32(count)) => *count as i64,
+                Some(Bson::Int64(count)) => *count,
+                _ => 0,
+            };
+            record.insert("failed_authentications", failed + 1);
+            Ok(())
+        })?;
+        account
+            .map(|account| from_document(account).map_err(AppError::from))
+            .transpose()
+    }
+
+    async fn lock(&self, id: Uuid, until: DateTime<Utc>) -> Result<(), AppError> {
+        let locked_until = timestamp::to_bson(&until);
+        self.modify(&exclude_deleted(doc! { "_id": id }), |record| {
+            record.insert("locked_until", locked_until);
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    async fn reset_failed_authentications(&self, id: Uuid) -> Result<(), AppError> {
+        self.modify(&exclude_deleted(doc! { "_id": id }), |record| {
+            record.insert("failed_authentications", 0);
+            record.remove("locked_until");
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
+/// Rate limit windows held in memory, so they only count the attempts made
+/// to this process.
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    windows: Mutex<HashMap<String, RateLimitWindow>>,
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn hit(
+        &self,
+        key: &RateLimitKey,
+        window_seconds: u64,
+        now: DateTime<Utc>,
+    ) -> Result<RateLimitWindow, AppError> {
+        let start = RateLimitWindow::start(now, window_seconds);
+        let id = RateLimitWindow::id_for(key, start);
+        let expires_at =
+            mongodb::bson::DateTime::from_millis((start + window_seconds.max(1) as i64) * 1000);
+
+        let mut windows = self
+            .windows
+            .lock()
+            .map_err(|_| AppError::Internal(anyhow::anyhow!("In-memory windows are poisoned")))?;
+        // Expired windows are dropped, as the TTL index does
+        let now = mongodb::bson::DateTime::from_millis(now.timestamp_millis());
+        windows.retain(|_, window| window.expires_at > now);
+        let window = windows.entry(id.clone()).or_insert(RateLimitWindow {
+            id,
+            count: 0,
+            expires_at,
+        });
+        window.count += 1;
+        Ok(window.clone())
+    }
+}
+
+/// Used DPoP proofs held in memory, so replays are only detected within this
+/// process.
+#[derive(Default)]
+pub struct MemoryDpopProofStore {
+    proofs: Mutex<HashMap<String, mongodb::bson::DateTime>>,
+}
+
+#[async_trait]
+impl DpopProofStore for MemoryDpopProofStore {
+    async fn record(&self, proof: &UsedDpopProof) -> Result<bool, AppError> {
+        let mut proofs = self
+            .proofs
+            .lock()
+            .map_err(|_| AppError::Internal(anyhow::anyhow!("In-memory proofs are poisoned")))?;
+        // Stale proofs are dropped, as the TTL index does
+        let now = mongodb::bson::DateTime::now();
+        proofs.retain(|_, expires_at| *expires_at > now);
+        Ok(proofs.insert(proof.id.clone(), proof.expires_at).is_none())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::label::Labels;
+    use crate::models::sort::SortDirection;
+    use crate::repositories::base::IdField;
+    use chrono::Duration;
+
+    fn project(name: &str) -> Project {
+        Project {
+            id: None,
+            name: name.to_string(),
+            description: String::new(),
+            enabled: true,
+            labels: Labels::default(),
+            created_at: None,
+            updated_at: None,
+            deleted_at: None,
+            deleted_by: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_crud_and_unique_index() -> Result<(), AppError> {
+        let repo = MemoryProjectRepository::projects();
+        let created = repo.create(project("billing")).await?;
+        let id = created.id.unwrap();
+        assert!(created.created_at.is_some());
+        assert_eq!(repo.read(id).await?.unwrap().name, "billing");
+
+        assert!(matches!(
+            repo.create(project("billing")).await,
+            Err(AppError::Conflict(_))
+        ));
+
+        let updated = repo
+            .update(
+                id,
+                ProjectUpdatePayload {
+                    name: None,
+                    description: Some("Invoices".to_string()),
+                    enabled: None,
+                    labels: None,
+                },
+            )
+            .await?;
+        assert_eq!(updated.description, "Invoices");
+        assert_eq!(updated.name, "billing");
+        assert!(matches!(
+            repo.update(
+                Uuid::new(),
+                ProjectUpdatePayload {
+                    name: None,
+                    description: None,
+                    enabled: Some(false),
+                    labels: None,
+                }
+            )
+            .await,
+            Err(AppError::NotFound(message)) if message == "Project not found"
+        ));
+
+        assert!(repo.delete(id).await?);
+        assert!(!repo.delete(id).await?);
+        assert!(repo.read(id).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_soft_delete_restore_and_purge() -> Result<(), AppError> {
+        let repo = MemoryProjectRepository::projects();
+        let id = repo.create(project("billing")).await?.id.unwrap();
+
+        assert!(repo.soft_delete(id, Some("alice".to_string())).await?);
+        assert!(!repo.soft_delete(id, None).await?);
+        assert!(repo.read(id).await?.is_none());
+        assert!(repo.is_deleted(id).await?);
+        // The name is free again while the record is deleted
+        let replacement = repo.create(project("billing")).await?.id.unwrap();
+
+        assert!(matches!(repo.restore(id).await, Err(AppError::Conflict(_))));
+        repo.delete(replacement).await?;
+        assert_eq!(repo.restore(id).await?.unwrap().name, "billing");
+        assert!(repo.restore(id).await?.is_none());
+
+        repo.soft_delete(id, None).await?;
+        assert_eq!(repo.purge(Utc::now() - Duration::days(1)).await?, 0);
+        assert_eq!(repo.purge(Utc::now() + Duration::seconds(1)).await?, 1);
+        assert!(!repo.is_deleted(id).await?);
+
+        // Records deleted with a BSON date are purged as well
+        let id = repo.create(project("archive")).await?.id.unwrap();
+        let deleted_at = mongodb::bson::DateTime::from_millis(
+            (Utc::now() - Duration::days(2)).timestamp_millis(),
+        );
+        for record in repo.records()?.iter_mut() {
+            if record.get("_id") == Some(&Bson::from(id)) {
+                record.insert("deleted_at", deleted_at);
+            }
+        }
+        assert_eq!(repo.purge(Utc::now() - Duration::days(3)).await?, 0);
+        assert_eq!(repo.purge(Utc::now() - Duration::days(1)).await?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_id_filter() -> Result<(), AppError> {
+        let repo = MemoryEnvironmentRepository::environments();
+        let project_id = Uuid::new();
+        let mut ids = Vec::new();
+        for (project_id, name) in [
+            (project_id, "dev"),
+            (project_id, "prod"),
+            (Uuid::new(), "dev"),
+        ] {
+            let environment = repo
+                .create(Environment {
+                    id: None,
+                    project_id,
+                    name: name.to_string(),
+                    description: String::new(),
+                    enabled: true,
+                    created_at: None,
+                    updated_at: None,
+                    deleted_at: None,
+                    deleted_by: None,
+                    labels: Labels::default(),
+                })
+                .await?;
+            ids.push(environment.id.unwrap());
+        }
+        let by_project = || IdFilter::by(IdField::ProjectId, [project_id]);
+
+        let mut found = repo.distinct_ids(by_project(), None).await?;
+        found.sort_by_key(|id| id.to_string());
+        let mut expected = ids[..2].to_vec();
+        expected.sort_by_key(|id| id.to_string());
+        assert_eq!(found, expected);
+
+        let deleted_at = Utc::now();
+        let marked = repo
+            .soft_delete_many(IdFilter::ids([ids[0]]), deleted_at, None, None)
+            .await?;
+        assert_eq!(marked, 1);
+        // Records already deleted are left alone
+        let marked = repo
+            .soft_delete_many(by_project(), Utc::now(), None, None)
+            .await?;
+        assert_eq!(marked, 1);
+        assert!(repo.read(ids[2]).await?.is_some());
+
+        assert!(repo.distinct_ids(by_project(), None).await?.is_empty());
+        let all = repo.distinct_ids(by_project().with_deleted(), None).await?;
+        assert_eq!(all.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_and_pages() -> Result<(), AppError> {
+        let repo = MemoryProjectRepository::projects();
+        for name in ["delta", "alpha", "charlie", "bravo", "echo"] {
+            repo.create(project(name)).await?;
+        }
+        let filter = ProjectFilter::default;
+        let by_name = || {
+            Some(
+                SortBuilder::new().add_sort(ProjectSortableFields::Name, SortDirection::Descending),
+            )
+        };
+
+        let found = repo.find(filter(), by_name(), None).await?;
+        let names: Vec<&str> = found.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["echo", "delta", "charlie", "bravo", "alpha"]);
+        assert_eq!(repo.count(filter()).await?, 5);
+
+        let first = repo
+            .find_page(
+                filter(),
+                by_name(),
+                Pagination {
+                    page: None,
+                    limit: Some(2),
+                    cursor: None,
+                },
+            )
+            .await?;
+        assert_eq!(first.total, 5);
+        let names: Vec<&str> = first.items.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["echo", "delta"]);
+
+        let second = repo
+            .find_page(
+                filter(),
+                by_name(),
+                Pagination {
+                    page: None,
+                    limit: Some(2),
+                    cursor: first.next_cursor,
+                },
+            )
+            .await?;
+        let names: Vec<&str> = second.items.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["charlie", "bravo"]);
+        assert!(second.next_cursor.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_service_account_lockout() -> Result<(), AppError> {
+        let repo = MemoryServiceAccountRepository::service_accounts();
+        let account = repo
+            .create(ServiceAccount {
+                id: None,
+                email: "ci@example.com".to_string(),
+                user: "ci".to_string(),
+                secret: "secret".to_string(),
+                enabled: true,
+                labels: Labels::default(),
+                certificate_bindings: vec![],
+                failed_authentications: 0,
+                locked_until: None,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+            })
+            .await?;
+        let id = account.id.unwrap();
+
+        repo.record_failed_authentication(id).await?;
+        let account = repo.record_failed_authentication(id).await?.unwrap();
+        assert_eq!(account.failed_authentications, 2);
+        repo.lock(id, Utc::now()).await?;
+        assert!(repo.read(id).await?.unwrap().locked_until.is_some());
+
+        repo.reset_failed_authentications(id).await?;
+        let account = repo.read(id).await?.unwrap();
+        assert_eq!(account.failed_authentications, 0);
+        assert!(account.locked_until.is_none());
+        assert!(
+            repo.record_failed_authentication(Uuid::new())
+                .await?
+                .is_none()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_and_dpop_proofs() -> Result<(), AppError> {
+        let limits = MemoryRateLimitStore::default();
+        let key = RateLimitKey::Client("ci".to_string());
+        let now = Utc::now();
+        limits.hit(&key, 60, now).await?;
+        assert_eq!(limits.hit(&key, 60, now).await?.count, 2);
+        assert_eq!(
+            limits
+                .hit(&key, 60, now + Duration::seconds(120))
+                .await?
+                .count,
+            1
+        );
+
+        let proofs = MemoryDpopProofStore::default();
+        let proof = UsedDpopProof::new("jkt", "jti", now + Duration::minutes(1));
+        assert!(proofs.record(&proof).await?);
+        assert!(!proofs.record(&proof).await?);
+        Ok(())
+    }
+}
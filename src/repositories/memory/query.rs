@@ -0,0 +1,385 @@
+//! Evaluation of MongoDB filters and sorts against documents held in memory.
+//!
+//! Only the operators the models and repositories build are supported:
+//! `$and`, `$or`, `$nor`, `$eq`, `$ne`, `$gt`, `$gte`, `$lt`, `$lte`, `$in`,
+//! `$nin`, `$exists` and `$regex` with `$options`. Comparisons follow MongoDB:
+//! a missing field equals `null`, an array field matches when any element
+//! does, and ranges only match values of the same type.
+
+use crate::errors::AppError;
+use mongodb::bson::{Bson, Document};
+use regex::RegexBuilder;
+use std::cmp::Ordering;
+
+/// Whether `document` matches `filter`.
+pub fn matches(document: &Document, filter: &Document) -> Result<bool, AppError> {
+    for (key, condition) in filter {
+        let matched = match key.as_str() {
+            "$and" => all(document, condition)?,
+            "$or" => any(document, condition)?,
+            "$nor" => !any(document, condition)?,
+            operator if operator.starts_with('$') => return Err(unsupported(operator)),
+            path => matches_field(lookup(document, path), condition)?,
+        };
+        if !matched {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// The value at a dotted `path` of `document`, if there is one.
+pub fn lookup<'a>(document: &'a Document, path: &str) -> Option<&'a Bson> {
+    let mut segments = path.split('.');
+    let mut value = document.get(segments.next()?)?;
+    for segment in segments {
+        value = value.as_document()?.get(segment)?;
+    }
+    Some(value)
+}
+
+/// Sets the value at a dotted `path` of `document`, creating the documents
+/// along the way.
+pub fn set(document: &mut Document, path: &str, value: Bson) {
+    match path.split_once('.') {
+        Some((head, rest)) => {
+            if !matches!(document.get(head), Some(Bson::Document(_))) {
+                document.insert(head, Document::new());
+            }
+            if let Some(Bson::Document(inner)) = document.get_mut(head) {
+                set(inner, rest, value);
+            }
+        }
+        None => {
+            document.insert(path, value);
+        }
+    }
+}
+
+/// Orders two documents by `sort`, a document of field paths and directions.
+pub fn compare(a: &Document, b: &Document, sort: &Document) -> Ordering {
+    for (path, direction) in sort {
+        let ordering = order(
+            lookup(a, path).unwrap_or(&Bson::Null),
+            lookup(b, path).unwrap_or(&Bson::Null),
+        );
+        let ordering = match direction.as_i32().or(direction.as_i64().map(|d| d as i32)) {
+            Some(-1) => ordering.reverse(),
+            _ => ordering,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+fn all(document: &Document, conditions: &Bson) -> Result<bool, AppError> {
+    for condition in clauses(conditions)? {
+        if !matches(document, condition)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn any(document: &Document, conditions: &Bson) -> Result<bool, AppError> {
+    for condition in clauses(conditions)? {
+        if matches(document, condition)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn clauses(conditions: &Bson) -> Result<Vec<&Document>, AppError> {
+    conditions
+        .as_array()
+        .map(|items| items.iter().filter_map(Bson::as_document).collect())
+        .ok_or_else(|| invalid("$and, $or and $nor take an array of filters"))
+}
+
+fn matches_field(value: Option<&Bson>, condition: &Bson) -> Result<bool, AppError> {
+    let operators = match condition {
+        Bson::Document(operators) if operators.keys().any(|key| key.starts_with('$')) => operators,
+        _ => return Ok(equals(value, condition)),
+    };
+    for (operator, operand) in operators {
+        let matched = match operator.as_str() {
+            "$eq" => equals(value, operand),
+            "$ne" => !equals(value, operand),
+            "$gt" => in_range(value, operand, |o| o == Ordering::Greater),
+            "$gte" => in_range(value, operand, |o| o != Ordering::Less),
+            "$lt" => in_range(value, operand, |o| o == Ordering::Less),
+            "$lte" => in_range(value, operand, |o| o != Ordering::Greater),
+            "$in" => one_of(value, operand)?,
+            "$nin" => !one_of(value, operand)?,
+            "$exists" => value.is_some() == operand.as_bool().unwrap_or(true),
+            "$regex" => {
+                let options = operators.get_str("$options").unwrap_or_default();
+                regex(value, operand, options)?
+            }
+            "$options" => true,
+            operator => return Err(unsupported(operator)),
+        };
+        if !matched {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// The values a condition is tested against: a missing field is `null`, and
+/// an array is tested both whole and element by element.
+fn candidates(value: Option<&Bson>) -> Vec<&Bson> {
+    match value {
+        None => vec![&Bson::Null],
+        Some(Bson::Array(items)) => items.iter().chain(value).collect(),
+        Some(value) => vec![value],
+    }
+}
+
+fn equals(value: Option<&Bson>, expected: &Bson) -> bool {
+    candidates(value)
+        .into_iter()
+        .any(|candidate| same(candidate, expected))
+}
+
+fn one_of(value: Option<&Bson>, operand: &Bson) -> Result<bool, AppError> {
+    let expected = operand
+        .as_array()
+        .ok_or_else(|| invalid("$in and $nin take an array"))?;
+    Ok(expected.iter().any(|expected| equals(value, expected)))
+}
+
+fn in_range(value: Option<&Bson>, bound: &Bson, accept: impl Fn(Ordering) -> bool) -> bool {
+    candidates(value)
+        .into_iter()
+        .any(|candidate| rank(candidate) == rank(bound) && accept(order(candidate, bound)))
+}
+
+fn regex(value: Option<&Bson>, pattern: &Bson, options: &str) -> Result<bool, AppError> {
+    let pattern = match pattern {
+        Bson::String(pattern) => pattern.as_str(),
+        Bson::RegularExpression(regex) => regex.pattern.as_str(),
+        _ => return Err(invalid("$regex takes a string")),
+    };
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(options.contains('i'))
+        .multi_line(options.contains('m'))
+        .build()
+        .map_err(|e| invalid(&format!("Invalid regular expression: {}", e)))?;
+    Ok(candidates(value)
+        .into_iter()
+        .any(|candidate| candidate.as_str().is_some_and(|text| regex.is_match(text))))
+}
+
+fn same(a: &Bson, b: &Bson) -> bool {
+    match (a, b) {
+        (Bson::Array(_), _) | (Bson::Document(_), _) => a == b,
+        _ => rank(a) == rank(b) && order(a, b) == Ordering::Equal,
+    }
+}
+
+/// Position of the type of `value` in MongoDB's comparison order.
+fn rank(value: &Bson) -> u8 {
+    match value {
+        Bson::MinKey => 0,
+        Bson::Null | Bson::Undefined => 1,
+        Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_) | Bson::Decimal128(_) => 2,
+        Bson::String(_) | Bson::Symbol(_) => 3,
+        Bson::Document(_) => 4,
+        Bson::Array(_) => 5,
+        Bson::Binary(_) => 6,
+        Bson::ObjectId(_) => 7,
+        Bson::Boolean(_) => 8,
+        Bson::DateTime(_) => 9,
+        Bson::Timestamp(_) => 10,
+        Bson::RegularExpression(_) => 11,
+        Bson::MaxKey => 13,
+        _ => 12,
+    }
+}
+
+/// Orders two values the way MongoDB sorts them.
+fn order(a: &Bson, b: &Bson) -> Ordering {
+    let by_rank = rank(a).cmp(&rank(b));
+    if by_rank != Ordering::Equal {
+        return by_rank;
+    }
+    match (a, b) {
+        (Bson::String(a), Bson::String(b)) => a.cmp(b),
+        (Bson::Boolean(a), Bson::Boolean(b)) => a.cmp(b),
+        (Bson::DateTime(a), Bson::DateTime(b)) => a.cmp(b),
+        (Bson::Binary(a), Bson::Binary(b)) => a.bytes.cmp(&b.bytes),
+        (Bson::ObjectId(a), Bson::ObjectId(b)) => a.cmp(b),
+        (Bson::Array(a), Bson::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| order(a, b))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        _ => match (number(a), number(b)) {
+            (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            _ => Ordering::Equal,
+        },
+    }
+}
+
+fn number(value: &Bson) -> Option<f64> {
+    match value {
+        Bson::Int32(value) => Some(*value as f64),
+        Bson::Int64(value) => Some(*value as f64),
+        Bson::Double(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn unsupported(operator: &str) -> AppError {
+    AppError::Internal(anyhow::anyhow!(
+        "Query operator {} is not supported in memory",
+        operator
+    ))
+}
+
+fn invalid(message: &str) -> AppError {
+    AppError::Internal(anyhow::anyhow!("Invalid filter: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::filter::{contains, starts_with};
+    use crate::models::label::LabelSelector;
+    use crate::models::pagination::Cursor;
+    use crate::repositories::base::exclude_deleted;
+    use mongodb::bson::doc;
+    use mongodb::bson::uuid::Uuid;
+
+    fn record() -> Document {
+        doc! {
+            "_id": Uuid::parse_str("6fa1f5c8-8a4e-4b3f-9bb8-0c9f3c2a7d11").unwrap(),
+            "name": "Payments",
+            "enabled": true,
+            "count": 3,
+            "scopes": ["read", "write"],
+            "labels": { "team": "billing", "tier": "1" },
+            "created_at": "2025-03-01T12:00:00Z",
+        }
+    }
+
+    #[test]
+    fn test_equality_and_missing_fields() -> Result<(), AppError> {
+        let record = record();
+        assert!(matches(
+            &record,
+            &doc! { "name": "Payments", "enabled": true }
+        )?);
+        assert!(!matches(&record, &doc! { "name": "payments" })?);
+        assert!(matches(&record, &doc! { "count": 3_i64 })?);
+        // A missing field equals null, which is how live records are found
+        assert!(matches(&record, &exclude_deleted(doc! {}))?);
+        assert!(!matches(
+            &record,
+            &doc! { "deleted_at": { "$ne": Bson::Null } }
+        )?);
+        assert!(matches(&record, &doc! { "labels.team": "billing" })?);
+        assert!(matches(
+            &record,
+            &doc! { "labels.owner": { "$exists": false } }
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrays_match_by_element() -> Result<(), AppError> {
+        let record = record();
+        assert!(matches(&record, &doc! { "scopes": "write" })?);
+        assert!(matches(
+            &record,
+            &doc! { "scopes": { "$in": ["admin", "read"] } }
+        )?);
+        assert!(!matches(&record, &doc! { "scopes": { "$nin": ["read"] } })?);
+        assert!(matches(&record, &doc! { "scopes": ["read", "write"] })?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ranges_compare_values_of_the_same_type() -> Result<(), AppError> {
+        let record = record();
+        assert!(matches(
+            &record,
+            &doc! { "created_at": { "$gt": "2025-02-01T00:00:00Z" } }
+        )?);
+        assert!(!matches(
+            &record,
+            &doc! { "created_at": { "$lt": "2025-02-01T00:00:00Z" } }
+        )?);
+        assert!(matches(
+            &record,
+            &doc! { "count": { "$gte": 3, "$lt": 4.5 } }
+        )?);
+        assert!(!matches(&record, &doc! { "count": { "$gt": "2" } })?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_filters() -> Result<(), AppError> {
+        let record = record();
+        assert!(matches(&record, &doc! { "name": starts_with("Pay") })?);
+        assert!(!matches(&record, &doc! { "name": starts_with("pay") })?);
+        assert!(matches(&record, &doc! { "name": contains("MEN") })?);
+
+        let mut filter = doc! {};
+        "team=billing,tier in (1,2),!owner"
+            .parse::<LabelSelector>()?
+            .apply(&mut filter);
+        assert!(matches(&record, &filter)?);
+        let mut filter = doc! {};
+        "team!=billing".parse::<LabelSelector>()?.apply(&mut filter);
+        assert!(!matches(&record, &filter)?);
+
+        assert!(matches(
+            &record,
+            &doc! { "$or": [{ "name": "Other" }, { "enabled": true }] }
+        )?);
+        assert!(!matches(&record, &doc! { "$nor": [{ "enabled": true }] })?);
+        assert!(matches(&record, &doc! { "$where": "true" }).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_and_cursor() -> Result<(), AppError> {
+        let mut records = [
+            doc! { "_id": 1, "name": "b", "rank": 2 },
+            doc! { "_id": 2, "name": "a" },
+            doc! { "_id": 3, "name": "b", "rank": 1 },
+            doc! { "_id": 4, "name": "c", "rank": 1 },
+        ];
+        let sort = doc! { "rank": -1, "_id": 1 };
+        records.sort_by(|a, b| compare(a, b, &sort));
+        let ids: Vec<i32> = records.iter().map(|r| r.get_i32("_id").unwrap()).collect();
+        // Missing values sort as null, below numbers
+        assert_eq!(ids, vec![1, 3, 4, 2]);
+
+        let after = Cursor::after(&sort, &records[1])?.filter(&sort)?;
+        let rest: Vec<i32> = records
+            .iter()
+            .filter(|record| matches(record, &after).unwrap())
+            .map(|r| r.get_i32("_id").unwrap())
+            .collect();
+        assert_eq!(rest, vec![4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_set() {
+        let mut document = doc! { "name": "a", "labels": "none" };
+        set(&mut document, "name", Bson::from("b"));
+        set(&mut document, "labels.team", Bson::from("billing"));
+        assert_eq!(
+            document,
+            doc! { "name": "b", "labels": { "team": "billing" } }
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use crate::errors::AppError;
+use crate::models::migration::{AppliedMigration, MigrationLock};
+use chrono::{DateTime, Duration, Utc};
+use futures::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::{Collection, Database};
+
+/// Id of the single lock document.
+const LOCK_ID: &str = "migrations";
+
+/// Repository for the record of applied migrations and the lock guarding them.
+///
+/// Migrations are not soft-deleted or listed, so this does not implement
+/// [`Repository`](crate::repositories::base::Repository).
+pub struct MigrationRepository {
+    collection: Collection<AppliedMigration>,
+    locks: Collection<MigrationLock>,
+}
+
+impl MigrationRepository {
+    pub fn new(database: Database) -> Result<Self, AppError> {
+        let collection = database.collection::<AppliedMigration>("_migrations");
+        let locks = database.collection::<MigrationLock>("_migrations_lock");
+        Ok(Self { collection, locks })
+    }
+
+    /// The migrations applied so far, by name.
+    #[tracing::instrument(skip_all, name = "MigrationRepository::applied")]
+    pub async fn applied(&self) -> Result<Vec<AppliedMigration>, AppError> {
+        let applied = self
+            .collection
+            .find(doc! {})
+            .sort(doc! { "_id": 1 })
+            .await?
+            .try_collect()
+            .await?;
+        Ok(applied)
+    }
+
+    /// Records that the migration `name` was applied at `applied_at`.
+    #[tracing::instrument(skip_all, name = "MigrationRepository::record")]
+    pub async fn record(&self, name: &str, applied_at: DateTime<Utc>) -> Result<(), AppError> {
+        self.collection
+            .insert_one(AppliedMigration {
+                name: name.to_string(),
+                applied_at,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Takes the migration lock for `owner` until `ttl` after `now`, or extends
+    /// it if `owner` already holds it.
+    ///
+    /// # Returns
+    ///
+    /// `false` if another owner holds a lock that has not expired.
+    #[tracing::instrument(skip_all, name = "MigrationRepository::lock")]
+    pub async fn lock(
+        &self,
+        owner: &str,
+        ttl: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let expires_at = mongodb::bson::DateTime::from_millis((now + ttl).timestamp_millis());
+        let result = self
+            .locks
+            .update_one(
+                doc! {
+                    "_id": LOCK_ID,
+                    "$or": [
+                        { "owner": owner },
+                        { "expires_at": { "$lte": mongodb::bson::DateTime::from_millis(now.timestamp_millis()) } },
+                    ],
+                },
+                doc! { "$set": { "owner": owner, "expires_at": expires_at } },
+            )
+            .upsert(true)
+            .await;
+        match result {
+            Ok(_) => Ok(true),
+            // The lock exists but the filter did not match it, so the upsert
+            // tried to insert a second one
+            Err(e) => match AppError::from(e) {
+                AppError::Conflict(_) => Ok(false),
+                error => Err(error),
+            },
+        }
+    }
+
+    /// Releases the migration lock if `owner` holds it.
+    #[tracing::instrument(skip_all, name = "MigrationRepository::unlock")]
+    pub async fn unlock(&self, owner: &str) -> Result<(), AppError> {
+        self.locks
+            .delete_one(doc! { "_id": LOCK_ID, "owner": owner })
+            .await?;
+        Ok(())
+    }
+
+    pub fn collection(&self) -> Result<Collection<AppliedMigration>, AppError> {
+        Ok(self.collection.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_lock_is_exclusive_until_released_or_expired() -> Result<()> {
+        let db = setup_test_db("migration_lock").await?;
+        let repo = MigrationRepository::new(db.clone())?;
+        let now = Utc::now();
+        let ttl = Duration::minutes(5);
+
+        assert!(repo.lock("a", ttl, now).await?);
+        assert!(repo.lock("a", ttl, now).await?);
+        assert!(!repo.lock("b", ttl, now).await?);
+
+        // An expired lock can be taken over
+        assert!(repo.lock("b", ttl, now + ttl).await?);
+        assert!(!repo.lock("a", ttl, now + ttl).await?);
+
+        // Releasing someone else's lock does nothing
+        repo.unlock("a").await?;
+        assert!(!repo.lock("a", ttl, now + ttl).await?);
+        repo.unlock("b").await?;
+        assert!(repo.lock("a", ttl, now + ttl).await?);
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_record_and_list_applied() -> Result<()> {
+        let db = setup_test_db("migration_record").await?;
+        let repo = MigrationRepository::new(db.clone())?;
+
+        repo.record("0002_second", Utc::now()).await?;
+        repo.record("0001_first", Utc::now()).await?;
+        let names: Vec<String> = repo.applied().await?.into_iter().map(|m| m.name).collect();
+        assert_eq!(names, vec!["0001_first", "0002_second"]);
+
+        // A migration is only recorded once
+        assert!(matches!(
+            repo.record("0001_first", Utc::now()).await,
+            Err(AppError::Conflict(_))
+        ));
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+}
@@ -1,9 +1,19 @@
 pub mod access_token_repository;
 pub mod base;
+pub mod dpop_proof_repository;
 pub mod environment_repository;
+pub mod memory;
+pub mod migration_repository;
+pub mod mongo;
 pub mod project_access_repository;
 pub mod project_repository;
 pub mod project_scope_repository;
+pub mod project_template_repository;
+pub mod rate_limit_repository;
+pub mod registry;
 pub mod server_key_repository;
 pub mod service_account_key_repository;
 pub mod service_account_repository;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod unit_of_work;
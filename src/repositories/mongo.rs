@@ -0,0 +1,209 @@
+//! MongoDB implementations of the [`Repository`] methods that work the same
+//! on every collection.
+//!
+//! The MongoDB repositories delegate to these from their `Repository` impls;
+//! the in-memory and SQLite repositories have their own.
+//!
+//! [`Repository`]: crate::repositories::base::Repository
+
+use crate::errors::AppError;
+use crate::models::pagination::{Cursor, Page, Pagination};
+use crate::repositories::base::{self, IdFilter, exclude_deleted};
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use mongodb::Collection;
+use mongodb::bson::uuid::Uuid;
+use mongodb::bson::{Bson, Document, doc, from_bson};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Counts records matching the filter. Soft-deleted records are not counted.
+#[tracing::instrument(skip_all, name = "Repository::count")]
+pub async fn count<T: Send + Sync>(
+    collection: &Collection<T>,
+    filter: Document,
+) -> Result<u64, AppError> {
+    Ok(collection.count_documents(exclude_deleted(filter)).await?)
+}
+
+/// Finds one page of records matching the filter, with the total count.
+///
+/// Uses the cursor in `pagination` when present and the page number
+/// otherwise. `_id` is appended to the sort so the order, and therefore
+/// every cursor, is stable.
+#[tracing::instrument(skip_all, name = "Repository::find_page")]
+pub async fn find_page<T>(
+    collection: &Collection<T>,
+    filter: Document,
+    sort: Option<Document>,
+    pagination: Pagination,
+) -> Result<Page<T>, AppError>
+where
+    T: Send + Sync + Serialize + DeserializeOwned,
+{
+    let filter = exclude_deleted(filter);
+    let total = collection.count_documents(filter.clone()).await?;
+
+    let mut sort = sort.unwrap_or_default();
+    if !sort.contains_key("_id") {
+        sort.insert("_id", 1);
+    }
+
+    let query = match &pagination.cursor {
+        Some(cursor) => doc! { "$and": [filter, Cursor::decode(cursor)?.filter(&sort)?] },
+        None => filter,
+    };
+
+    // Fetch one extra record to find out whether another page follows
+    let limit = pagination.limit();
+    let mut items: Vec<T> = collection
+        .find(query)
+        .sort(sort.clone())
+        .skip(pagination.skip())
+        .limit(limit + 1)
+        .await?
+        .try_collect()
+        .await?;
+
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        match items.last() {
+            Some(last) => Some(Cursor::after(&sort, last)?.encode()?),
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    Ok(Page {
+        items,
+        total,
+        page: pagination.page_number(),
+        limit: limit as u32,
+        next_cursor,
+    })
+}
+
+/// Marks a record as deleted without removing it.
+///
+/// # Returns
+///
+/// `true` if a live record was soft-deleted, `false` if none was found.
+#[tracing::instrument(skip_all, name = "Repository::soft_delete")]
+pub async fn soft_delete<T: Send + Sync>(
+    collection: &Collection<T>,
+    id: Uuid,
+    deleted_by: Option<String>,
+) -> Result<bool, AppError> {
+    let result = collection
+        .update_one(
+            exclude_deleted(doc! { "_id": id }),
+            doc! { "$set": { "deleted_at": timestamp::to_bson(&Utc::now()), "deleted_by": deleted_by } },
+        )
+        .await?;
+    Ok(result.modified_count > 0)
+}
+
+/// Brings a soft-deleted record back.
+///
+/// # Returns
+///
+/// `true` if a soft-deleted record with this id was restored.
+#[tracing::instrument(skip_all, name = "Repository::restore")]
+pub async fn restore<T: Send + Sync>(
+    collection: &Collection<T>,
+    id: Uuid,
+) -> Result<bool, AppError> {
+    let result = collection
+        .update_one(
+            doc! { "_id": id, "deleted_at": { "$ne": Bson::Null } },
+            doc! { "$unset": { "deleted_at": "", "deleted_by": "" } },
+        )
+        .await?;
+    Ok(result.modified_count > 0)
+}
+
+/// Returns `true` if a record with this id exists and has been soft-deleted.
+#[tracing::instrument(skip_all, name = "Repository::is_deleted")]
+pub async fn is_deleted<T: Send + Sync>(
+    collection: &Collection<T>,
+    id: Uuid,
+) -> Result<bool, AppError> {
+    let count = collection
+        .count_documents(doc! { "_id": id, "deleted_at": { "$ne": Bson::Null } })
+        .await?;
+    Ok(count > 0)
+}
+
+/// Permanently removes records that were soft-deleted before `deleted_before`.
+///
+/// # Returns
+///
+/// The number of records removed.
+#[tracing::instrument(skip_all, name = "Repository::purge")]
+pub async fn purge<T: Send + Sync>(
+    collection: &Collection<T>,
+    deleted_before: DateTime<Utc>,
+) -> Result<u64, AppError> {
+    let result = collection
+        .delete_many(base::deleted_before(deleted_before)?)
+        .await?;
+    Ok(result.deleted_count)
+}
+
+/// The query document selecting the records `filter` selects.
+fn id_query(filter: IdFilter) -> Document {
+    let query = doc! { filter.field.name(): { "$in": filter.ids } };
+    match filter.with_deleted {
+        true => query,
+        false => exclude_deleted(query),
+    }
+}
+
+/// Returns the ids of the records matching `filter`.
+///
+/// Runs in `transaction` when given, so it sees the writes made in it.
+#[tracing::instrument(skip_all, name = "Repository::distinct_ids")]
+pub async fn distinct_ids<T: Send + Sync>(
+    collection: &Collection<T>,
+    filter: IdFilter,
+    transaction: Option<&mut Transaction>,
+) -> Result<Vec<Uuid>, AppError> {
+    let action = collection.distinct("_id", id_query(filter));
+    let values = match transaction {
+        Some(transaction) => action.session(transaction.session()).await?,
+        None => action.await?,
+    };
+    values
+        .into_iter()
+        .map(|value| from_bson::<Uuid>(value).map_err(AppError::from))
+        .collect()
+}
+
+/// Marks every record matching `filter` as deleted at `deleted_at`.
+///
+/// Runs in `transaction` when given, so it commits or rolls back with it.
+///
+/// # Returns
+///
+/// The number of records marked.
+#[tracing::instrument(skip_all, name = "Repository::soft_delete_many")]
+pub async fn soft_delete_many<T: Send + Sync>(
+    collection: &Collection<T>,
+    filter: IdFilter,
+    deleted_at: DateTime<Utc>,
+    deleted_by: Option<String>,
+    transaction: Option<&mut Transaction>,
+) -> Result<u64, AppError> {
+    let action = collection.update_many(
+        id_query(filter),
+        doc! { "$set": { "deleted_at": timestamp::to_bson(&deleted_at), "deleted_by": deleted_by } },
+    );
+    let result = match transaction {
+        Some(transaction) => action.session(transaction.session()).await?,
+        None => action.await?,
+    };
+    Ok(result.modified_count)
+}
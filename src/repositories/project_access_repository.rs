@@ -1,16 +1,18 @@
-use crate::models::pagination::Pagination;
+use crate::errors::AppError;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::project_access::{
     ProjectAccess, ProjectAccessFilter, ProjectAccessSortableFields, ProjectAccessUpdatePayload,
 };
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use anyhow::Error;
-use anyhow::Result;
+use crate::repositories::base::{IdFilter, Repository, drop_index_if_exists, exclude_deleted};
+use crate::repositories::mongo;
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use mongodb::bson::uuid::Uuid;
-use mongodb::bson::{Bson, doc, to_document};
+use mongodb::bson::{doc, to_document};
 use mongodb::options::IndexOptions;
 use mongodb::{Collection, Database, IndexModel};
 
@@ -31,12 +33,16 @@ impl ProjectAccessRepository {
     /// # Returns
     ///
     /// Returns a Result containing the ProjectAccessRepository or an error if initialization fails.
-    pub fn new(database: Database) -> Result<Self, Error> {
+    pub fn new(database: Database) -> Result<Self, AppError> {
         let collection = database.collection::<ProjectAccess>("project_access");
         Ok(Self { collection })
     }
 
-    pub async fn ensure_indexes(&self) -> Result<(), Error> {
+    #[tracing::instrument(skip_all, name = "ProjectAccessRepository::ensure_indexes")]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        // Unique constraints only apply to records that have not been soft-deleted
+        drop_index_if_exists(&self.collection, "service_account_id_1_environment_id_1").await?;
+
         // Unique index on (project_id, name)
 
         // Compound unique index on (service_account_id, environment_id)
@@ -44,12 +50,11 @@ impl ProjectAccessRepository {
             .collection
             .create_index(
                 IndexModel::builder()
-                    .keys(doc! { "service_account_id": 1, "environment_id": 1 })
+                    .keys(doc! { "service_account_id": 1, "environment_id": 1, "deleted_at": 1 })
                     .options(IndexOptions::builder().unique(true).build())
                     .build(),
             )
-            .await
-            .expect("Failed to create unique index on service_account_id, environment_id");
+            .await?;
 
         // Index on project_scopes
         let _ = &self
@@ -59,67 +64,109 @@ impl ProjectAccessRepository {
                     .keys(doc! { "project_scopes": 1 })
                     .build(),
             )
-            .await
-            .expect("Failed to create index on project_scopes");
+            .await?;
 
         // Index on enabled
         let _ = &self
             .collection
             .create_index(IndexModel::builder().keys(doc! { "enabled": 1 }).build())
-            .await
-            .expect("Failed to create index on enabled");
+            .await?;
+
+        // Wildcard index so label selectors can match on any label key
+        let _ = &self
+            .collection
+            .create_index(IndexModel::builder().keys(doc! { "labels.$**": 1 }).build())
+            .await?;
 
         Ok(())
     }
+
+    pub fn collection(&self) -> Result<Collection<ProjectAccess>, AppError> {
+        Ok(self.collection.clone())
+    }
 }
 
+/// A project access repository of any storage.
+pub type DynProjectAccessRepository = dyn Repository<
+        ProjectAccess,
+        UpdatePayload = ProjectAccessUpdatePayload,
+        Filter = ProjectAccessFilter,
+        Sort = ProjectAccessSortableFields,
+    > + Send
+    + Sync;
+
 #[async_trait]
 impl Repository<ProjectAccess> for ProjectAccessRepository {
     type UpdatePayload = ProjectAccessUpdatePayload;
     type Filter = ProjectAccessFilter;
     type Sort = ProjectAccessSortableFields;
 
-    async fn create(&self, mut item: ProjectAccess) -> Result<ProjectAccess, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectAccessRepository::create")]
+    async fn create_in(
+        &self,
+        mut item: ProjectAccess,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<ProjectAccess, AppError> {
         if item.id.is_none() {
             item.id = Some(Uuid::new());
         }
+        item.deleted_at = None;
+        item.deleted_by = None;
         item.created_at = Some(Utc::now());
         item.updated_at = Some(Utc::now());
-        self.collection.insert_one(&item).await?;
+        let action = self.collection.insert_one(&item);
+        match transaction {
+            Some(transaction) => action.session(transaction.session()).await?,
+            None => action.await?,
+        };
         Ok(item)
     }
 
-    async fn read(&self, id: Uuid) -> Result<Option<ProjectAccess>, Error> {
-        let result = self.collection.find_one(doc! { "_id": id }).await?;
+    #[tracing::instrument(skip_all, name = "ProjectAccessRepository::read")]
+    async fn read(&self, id: Uuid) -> Result<Option<ProjectAccess>, AppError> {
+        let result = self
+            .collection
+            .find_one(exclude_deleted(doc! { "_id": id }))
+            .await?;
         Ok(result)
     }
 
-    async fn update(&self, id: Uuid, payload: Self::UpdatePayload) -> Result<ProjectAccess, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectAccessRepository::update")]
+    async fn update(
+        &self,
+        id: Uuid,
+        payload: Self::UpdatePayload,
+    ) -> Result<ProjectAccess, AppError> {
         let mut document = to_document(&payload)?;
-        document.insert("updated_at", Bson::String(Utc::now().to_rfc3339()));
+        document.insert("updated_at", timestamp::to_bson(&Utc::now()));
 
         self.collection
-            .update_one(doc! { "_id": id }, doc! { "$set": document })
+            .update_one(
+                exclude_deleted(doc! { "_id": id }),
+                doc! { "$set": document },
+            )
             .await?;
         let updated = self
             .read(id)
             .await?
-            .ok_or_else(|| Error::msg("ProjectAccess not found"))?;
+            .ok_or_else(|| AppError::NotFound("ProjectAccess not found".to_string()))?;
         Ok(updated)
     }
 
-    async fn delete(&self, id: Uuid) -> Result<bool, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectAccessRepository::delete")]
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
         let result = self.collection.delete_one(doc! { "_id": id }).await?;
         Ok(result.deleted_count > 0)
     }
 
+    #[tracing::instrument(skip_all, name = "ProjectAccessRepository::find")]
     async fn find(
         &self,
         filter: Self::Filter,
         sort: Option<SortBuilder<Self::Sort>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<ProjectAccess>, Error> {
-        let filter_doc = filter.into();
+    ) -> Result<Vec<ProjectAccess>, AppError> {
+        let filter_doc = exclude_deleted(filter.into());
 
         // Create FindOptions
         let mut options = mongodb::options::FindOptions::default();
@@ -142,8 +189,62 @@ impl Repository<ProjectAccess> for ProjectAccessRepository {
         Ok(items)
     }
 
-    fn collection(&self) -> Result<Collection<ProjectAccess>, Error> {
-        Ok(self.collection.clone())
+    async fn count(&self, filter: Self::Filter) -> Result<u64, AppError> {
+        mongo::count(&self.collection, filter.into()).await
+    }
+
+    async fn find_page(
+        &self,
+        filter: Self::Filter,
+        sort: Option<SortBuilder<Self::Sort>>,
+        pagination: Pagination,
+    ) -> Result<Page<ProjectAccess>, AppError> {
+        let sort = sort.map(SortBuilder::to_document);
+        mongo::find_page(&self.collection, filter.into(), sort, pagination).await
+    }
+
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        mongo::soft_delete(&self.collection, id, deleted_by).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<Option<ProjectAccess>, AppError> {
+        if !mongo::restore(&self.collection, id).await? {
+            return Ok(None);
+        }
+        self.read(id).await
+    }
+
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError> {
+        mongo::is_deleted(&self.collection, id).await
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        mongo::purge(&self.collection, deleted_before).await
+    }
+
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        mongo::distinct_ids(&self.collection, filter, transaction).await
+    }
+
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError> {
+        mongo::soft_delete_many(
+            &self.collection,
+            filter,
+            deleted_at,
+            deleted_by,
+            transaction,
+        )
+        .await
     }
 }
 
@@ -151,6 +252,7 @@ impl Repository<ProjectAccess> for ProjectAccessRepository {
 mod tests {
     use super::*;
     use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Result;
     use chrono::Utc;
 
     async fn setup() -> (ProjectAccessRepository, Database) {
@@ -174,6 +276,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = repo.create(project_access.clone()).await.unwrap();
@@ -199,6 +304,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = repo.create(project_access.clone()).await.unwrap();
@@ -229,6 +337,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = repo.create(project_access).await.unwrap();
@@ -236,6 +347,7 @@ mod tests {
             name: Some("Updated Access".to_string()),
             project_scopes: Some(vec![Uuid::new()]),
             enabled: Some(false),
+            labels: None,
         };
 
         let updated = repo.update(created.id.unwrap(), update).await.unwrap();
@@ -251,6 +363,7 @@ mod tests {
                     name: Some("Test".to_string()),
                     project_scopes: None,
                     enabled: None,
+                    labels: None,
                 },
             )
             .await;
@@ -272,6 +385,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = repo.create(project_access).await.unwrap();
@@ -305,6 +421,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let access2 = ProjectAccess {
             id: None,
@@ -315,6 +434,9 @@ mod tests {
             enabled: false,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         repo.create(access1).await.unwrap();
@@ -326,6 +448,7 @@ mod tests {
             service_account_id: None,
             project_scopes: None,
             is_enabled: None,
+            ..Default::default()
         };
         let all_access = repo.find(filter, None, None).await.unwrap();
         assert_eq!(all_access.len(), 2);
@@ -336,6 +459,7 @@ mod tests {
             service_account_id: None,
             project_scopes: None,
             is_enabled: None,
+            ..Default::default()
         };
         let env_access = repo.find(env_filter, None, None).await.unwrap();
         assert_eq!(env_access.len(), 2);
@@ -346,6 +470,7 @@ mod tests {
             service_account_id: None,
             project_scopes: None,
             is_enabled: Some(true),
+            ..Default::default()
         };
         let enabled_access = repo.find(enabled_filter, None, None).await.unwrap();
         assert_eq!(enabled_access.len(), 1);
@@ -1,13 +1,16 @@
-use crate::models::pagination::Pagination;
+use crate::errors::AppError;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::project::{Project, ProjectFilter, ProjectSortableFields, ProjectUpdatePayload};
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use anyhow::{Error, Result};
+use crate::repositories::base::{IdFilter, Repository, drop_index_if_exists, exclude_deleted};
+use crate::repositories::mongo;
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use mongodb::bson::uuid::Uuid;
-use mongodb::bson::{Bson, doc, to_document};
+use mongodb::bson::{doc, to_document};
 use mongodb::options::IndexOptions;
 use mongodb::{Collection, Database, IndexModel};
 
@@ -28,80 +31,122 @@ impl ProjectRepository {
     /// # Returns
     ///
     /// Returns a Result containing the ProjectRepository or an error if initialization fails.
-    pub fn new(database: Database) -> Result<Self, Error> {
+    pub fn new(database: Database) -> Result<Self, AppError> {
         let collection = database.collection::<Project>("projects");
         Ok(Self { collection })
     }
 
-    pub async fn ensure_indexes(&self) -> Result<(), Error> {
+    #[tracing::instrument(skip_all, name = "ProjectRepository::ensure_indexes")]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        // Unique constraints only apply to records that have not been soft-deleted
+        drop_index_if_exists(&self.collection, "name_1").await?;
+
         let _ = &self
             .collection
             .create_index(
                 IndexModel::builder()
-                    .keys(doc! { "name": 1 })
+                    .keys(doc! { "name": 1, "deleted_at": 1 })
                     .options(IndexOptions::builder().unique(true).build())
                     .build(),
             )
-            .await
-            .expect("Failed to create unique index on name");
+            .await?;
 
         let _ = &self
             .collection
             .create_index(IndexModel::builder().keys(doc! { "enabled": 1 }).build())
-            .await
-            .expect("Failed to create index on enabled");
+            .await?;
+
+        // Wildcard index so label selectors can match on any label key
+        let _ = &self
+            .collection
+            .create_index(IndexModel::builder().keys(doc! { "labels.$**": 1 }).build())
+            .await?;
 
         Ok(())
     }
+
+    pub fn collection(&self) -> Result<Collection<Project>, AppError> {
+        Ok(self.collection.clone())
+    }
 }
 
+/// A project repository of any storage.
+pub type DynProjectRepository = dyn Repository<
+        Project,
+        UpdatePayload = ProjectUpdatePayload,
+        Filter = ProjectFilter,
+        Sort = ProjectSortableFields,
+    > + Send
+    + Sync;
+
 #[async_trait]
 impl Repository<Project> for ProjectRepository {
     type UpdatePayload = ProjectUpdatePayload;
     type Filter = ProjectFilter;
     type Sort = ProjectSortableFields;
 
-    async fn create(&self, mut item: Project) -> Result<Project, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectRepository::create")]
+    async fn create_in(
+        &self,
+        mut item: Project,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Project, AppError> {
         if item.id.is_none() {
             item.id = Some(Uuid::new());
         }
+        item.deleted_at = None;
+        item.deleted_by = None;
         item.created_at = Some(Utc::now());
         item.updated_at = Some(Utc::now());
-        self.collection.insert_one(&item).await?;
+        let action = self.collection.insert_one(&item);
+        match transaction {
+            Some(transaction) => action.session(transaction.session()).await?,
+            None => action.await?,
+        };
         Ok(item)
     }
 
-    async fn read(&self, id: Uuid) -> Result<Option<Project>, Error> {
-        let result = self.collection.find_one(doc! { "_id": id }).await?;
+    #[tracing::instrument(skip_all, name = "ProjectRepository::read")]
+    async fn read(&self, id: Uuid) -> Result<Option<Project>, AppError> {
+        let result = self
+            .collection
+            .find_one(exclude_deleted(doc! { "_id": id }))
+            .await?;
         Ok(result)
     }
 
-    async fn update(&self, id: Uuid, payload: Self::UpdatePayload) -> Result<Project, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectRepository::update")]
+    async fn update(&self, id: Uuid, payload: Self::UpdatePayload) -> Result<Project, AppError> {
         let mut document = to_document(&payload)?;
-        document.insert("updated_at", Bson::String(Utc::now().to_rfc3339()));
+        document.insert("updated_at", timestamp::to_bson(&Utc::now()));
 
         self.collection
-            .update_one(doc! { "_id": id }, doc! { "$set": document })
+            .update_one(
+                exclude_deleted(doc! { "_id": id }),
+                doc! { "$set": document },
+            )
             .await?;
         let updated = self
             .read(id)
             .await?
-            .ok_or_else(|| Error::msg("Project not found"))?;
+            .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
         Ok(updated)
     }
 
-    async fn delete(&self, id: Uuid) -> Result<bool, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectRepository::delete")]
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
         let result = self.collection.delete_one(doc! { "_id": id }).await?;
         Ok(result.deleted_count > 0)
     }
 
+    #[tracing::instrument(skip_all, name = "ProjectRepository::find")]
     async fn find(
         &self,
         filter: Self::Filter,
         sort: Option<SortBuilder<Self::Sort>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<Project>, Error> {
-        let filter_doc = filter.into();
+    ) -> Result<Vec<Project>, AppError> {
+        let filter_doc = exclude_deleted(filter.into());
 
         // Create FindOptions
         let mut options = mongodb::options::FindOptions::default();
@@ -124,15 +169,71 @@ impl Repository<Project> for ProjectRepository {
         Ok(items)
     }
 
-    fn collection(&self) -> Result<Collection<Project>, Error> {
-        Ok(self.collection.clone())
+    async fn count(&self, filter: Self::Filter) -> Result<u64, AppError> {
+        mongo::count(&self.collection, filter.into()).await
+    }
+
+    async fn find_page(
+        &self,
+        filter: Self::Filter,
+        sort: Option<SortBuilder<Self::Sort>>,
+        pagination: Pagination,
+    ) -> Result<Page<Project>, AppError> {
+        let sort = sort.map(SortBuilder::to_document);
+        mongo::find_page(&self.collection, filter.into(), sort, pagination).await
+    }
+
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        mongo::soft_delete(&self.collection, id, deleted_by).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<Option<Project>, AppError> {
+        if !mongo::restore(&self.collection, id).await? {
+            return Ok(None);
+        }
+        self.read(id).await
+    }
+
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError> {
+        mongo::is_deleted(&self.collection, id).await
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        mongo::purge(&self.collection, deleted_before).await
+    }
+
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        mongo::distinct_ids(&self.collection, filter, transaction).await
+    }
+
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError> {
+        mongo::soft_delete_many(
+            &self.collection,
+            filter,
+            deleted_at,
+            deleted_by,
+            transaction,
+        )
+        .await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::label::Labels;
     use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Result;
     use chrono::Utc;
 
     async fn setup() -> (ProjectRepository, Database) {
@@ -152,8 +253,11 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
-        repo.create(project).await
+        Ok(repo.create(project).await?)
     }
 
     #[tokio::test]
@@ -209,6 +313,7 @@ mod tests {
             name: Some("Updated Project".to_string()),
             description: None,
             enabled: Some(false),
+            labels: None,
         };
 
         let updated = repo.update(project_id, update_payload).await?;
@@ -227,6 +332,7 @@ mod tests {
                     name: Some("Test".to_string()),
                     description: None,
                     enabled: None,
+                    labels: None,
                 },
             )
             .await;
@@ -271,6 +377,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let project2 = Project {
             id: None,
@@ -279,6 +388,9 @@ mod tests {
             enabled: false,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         repo.create(project1).await?;
@@ -288,6 +400,7 @@ mod tests {
         let filter = ProjectFilter {
             name: None,
             is_enabled: None,
+            ..Default::default()
         };
         let all_projects = repo.find(filter, None, None).await?;
         assert_eq!(all_projects.len(), 2);
@@ -296,6 +409,7 @@ mod tests {
         let name_filter = ProjectFilter {
             name: Some("Project 1".to_string()),
             is_enabled: None,
+            ..Default::default()
         };
         let projects = repo.find(name_filter, None, None).await?;
         assert_eq!(projects.len(), 1);
@@ -305,6 +419,7 @@ mod tests {
         let enabled_filter = ProjectFilter {
             name: None,
             is_enabled: Some(true),
+            ..Default::default()
         };
         let enabled_projects = repo.find(enabled_filter, None, None).await?;
         assert_eq!(enabled_projects.len(), 1);
@@ -313,6 +428,7 @@ mod tests {
         let disabled_filter = ProjectFilter {
             name: None,
             is_enabled: Some(false),
+            ..Default::default()
         };
         let disabled_projects = repo.find(disabled_filter, None, None).await?;
         assert_eq!(disabled_projects.len(), 1);
@@ -322,6 +438,7 @@ mod tests {
         let non_matching_filter = ProjectFilter {
             name: Some("Non-existent".to_string()),
             is_enabled: None,
+            ..Default::default()
         };
         let non_matching = repo.find(non_matching_filter, None, None).await?;
         assert_eq!(non_matching.len(), 0);
@@ -329,4 +446,106 @@ mod tests {
         cleanup_test_db(db).await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_find_projects_with_rich_filters() -> Result<()> {
+        let (repo, db) = setup().await;
+
+        let mut ids = Vec::new();
+        for name in ["Payments API", "payments-worker", "Billing"] {
+            let project = repo
+                .create(Project {
+                    id: None,
+                    name: name.to_string(),
+                    description: "Test Description".to_string(),
+                    enabled: true,
+                    created_at: None,
+                    updated_at: None,
+                    deleted_at: None,
+                    deleted_by: None,
+                    labels: Default::default(),
+                })
+                .await?;
+            ids.push(project.id.unwrap());
+        }
+
+        let search = ProjectFilter {
+            search: Some("PAYMENTS".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(repo.find(search, None, None).await?.len(), 2);
+
+        let prefix = ProjectFilter {
+            name_prefix: Some("Pay".to_string()),
+            ..Default::default()
+        };
+        let projects = repo.find(prefix, None, None).await?;
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].name, "Payments API");
+
+        let created_later = ProjectFilter {
+            created_after: Some(Utc::now() + chrono::Duration::hours(1)),
+            ..Default::default()
+        };
+        assert!(repo.find(created_later, None, None).await?.is_empty());
+
+        let created_earlier = ProjectFilter {
+            created_before: Some(Utc::now() + chrono::Duration::hours(1)),
+            ..Default::default()
+        };
+        assert_eq!(repo.find(created_earlier, None, None).await?.len(), 3);
+
+        let by_id = ProjectFilter {
+            id_in: Some(vec![ids[0], ids[2]]),
+            ..Default::default()
+        };
+        assert_eq!(repo.find(by_id, None, None).await?.len(), 2);
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_projects_by_label_selector() -> Result<()> {
+        let (repo, db) = setup().await;
+
+        for (name, team, tier) in [
+            ("Checkout", "payments", "critical"),
+            ("Invoices", "payments", "standard"),
+            ("Search", "discovery", "standard"),
+        ] {
+            repo.create(Project {
+                id: None,
+                name: name.to_string(),
+                description: "Test Description".to_string(),
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Labels::from([
+                    ("team".to_string(), team.to_string()),
+                    ("tier".to_string(), tier.to_string()),
+                ]),
+            })
+            .await?;
+        }
+
+        let filter = ProjectFilter {
+            label_selector: Some("team=payments,tier!=critical".parse()?),
+            ..Default::default()
+        };
+        let projects = repo.find(filter, None, None).await?;
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].name, "Invoices");
+
+        let filter = ProjectFilter {
+            label_selector: Some("team in (payments,discovery),!owner".parse()?),
+            ..Default::default()
+        };
+        assert_eq!(repo.find(filter, None, None).await?.len(), 3);
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
 }
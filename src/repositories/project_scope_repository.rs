@@ -1,16 +1,18 @@
-use crate::models::pagination::Pagination;
+use crate::errors::AppError;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::project_scope::{
     ProjectScope, ProjectScopeFilter, ProjectScopeSortableFields, ProjectScopeUpdatePayload,
 };
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use anyhow::Error;
-use anyhow::Result;
+use crate::repositories::base::{IdFilter, Repository, drop_index_if_exists, exclude_deleted};
+use crate::repositories::mongo;
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use mongodb::bson::uuid::Uuid;
-use mongodb::bson::{Bson, doc, to_document};
+use mongodb::bson::{doc, to_document};
 use mongodb::options::IndexOptions;
 use mongodb::{Collection, Database, IndexModel};
 
@@ -31,22 +33,25 @@ impl ProjectScopeRepository {
     /// # Returns
     ///
     /// Returns a Result containing the ProjectScopeRepository or an error if initialization fails.
-    pub fn new(database: Database) -> Result<Self, Error> {
+    pub fn new(database: Database) -> Result<Self, AppError> {
         let collection = database.collection::<ProjectScope>("project_scopes");
         Ok(Self { collection })
     }
 
-    pub async fn ensure_indexes(&self) -> Result<(), Error> {
+    #[tracing::instrument(skip_all, name = "ProjectScopeRepository::ensure_indexes")]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        // Unique constraints only apply to records that have not been soft-deleted
+        drop_index_if_exists(&self.collection, "project_id_1_name_1").await?;
+
         let _ = &self
             .collection
             .create_index(
                 IndexModel::builder()
-                    .keys(doc! { "project_id": 1, "name": 1 })
+                    .keys(doc! { "project_id": 1, "name": 1, "deleted_at": 1 })
                     .options(IndexOptions::builder().unique(true).build())
                     .build(),
             )
-            .await
-            .expect("Failed to create index on project_id, name");
+            .await?;
 
         let _ = &self
             .collection
@@ -55,60 +60,103 @@ impl ProjectScopeRepository {
                     .keys(doc! { "project_id": 1, "enabled": 1 })
                     .build(),
             )
-            .await
-            .expect("Failed to create index on project_id, enabled");
+            .await?;
+
+        // Wildcard index so label selectors can match on any label key
+        let _ = &self
+            .collection
+            .create_index(IndexModel::builder().keys(doc! { "labels.$**": 1 }).build())
+            .await?;
 
         Ok(())
     }
+
+    pub fn collection(&self) -> Result<Collection<ProjectScope>, AppError> {
+        Ok(self.collection.clone())
+    }
 }
 
+/// A project scope repository of any storage.
+pub type DynProjectScopeRepository = dyn Repository<
+        ProjectScope,
+        UpdatePayload = ProjectScopeUpdatePayload,
+        Filter = ProjectScopeFilter,
+        Sort = ProjectScopeSortableFields,
+    > + Send
+    + Sync;
+
 #[async_trait]
 impl Repository<ProjectScope> for ProjectScopeRepository {
     type UpdatePayload = ProjectScopeUpdatePayload;
     type Filter = ProjectScopeFilter;
     type Sort = ProjectScopeSortableFields;
 
-    async fn create(&self, mut item: ProjectScope) -> Result<ProjectScope, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectScopeRepository::create")]
+    async fn create_in(
+        &self,
+        mut item: ProjectScope,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<ProjectScope, AppError> {
         if item.id.is_none() {
             item.id = Some(Uuid::new());
         }
+        item.deleted_at = None;
+        item.deleted_by = None;
         item.created_at = Some(Utc::now());
         item.updated_at = Some(Utc::now());
-        self.collection.insert_one(&item).await?;
+        let action = self.collection.insert_one(&item);
+        match transaction {
+            Some(transaction) => action.session(transaction.session()).await?,
+            None => action.await?,
+        };
         Ok(item)
     }
 
-    async fn read(&self, id: Uuid) -> Result<Option<ProjectScope>, Error> {
-        let result = self.collection.find_one(doc! { "_id": id }).await?;
+    #[tracing::instrument(skip_all, name = "ProjectScopeRepository::read")]
+    async fn read(&self, id: Uuid) -> Result<Option<ProjectScope>, AppError> {
+        let result = self
+            .collection
+            .find_one(exclude_deleted(doc! { "_id": id }))
+            .await?;
         Ok(result)
     }
 
-    async fn update(&self, id: Uuid, payload: Self::UpdatePayload) -> Result<ProjectScope, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectScopeRepository::update")]
+    async fn update(
+        &self,
+        id: Uuid,
+        payload: Self::UpdatePayload,
+    ) -> Result<ProjectScope, AppError> {
         let mut document = to_document(&payload)?;
-        document.insert("updated_at", Bson::String(Utc::now().to_rfc3339()));
+        document.insert("updated_at", timestamp::to_bson(&Utc::now()));
 
         self.collection
-            .update_one(doc! { "_id": id }, doc! { "$set": document })
+            .update_one(
+                exclude_deleted(doc! { "_id": id }),
+                doc! { "$set": document },
+            )
             .await?;
         let updated = self
             .read(id)
             .await?
-            .ok_or_else(|| Error::msg("Project scope not found"))?;
+            .ok_or_else(|| AppError::NotFound("Project scope not found".to_string()))?;
         Ok(updated)
     }
 
-    async fn delete(&self, id: Uuid) -> Result<bool, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectScopeRepository::delete")]
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
         let result = self.collection.delete_one(doc! { "_id": id }).await?;
         Ok(result.deleted_count > 0)
     }
 
+    #[tracing::instrument(skip_all, name = "ProjectScopeRepository::find")]
     async fn find(
         &self,
         filter: Self::Filter,
         sort: Option<SortBuilder<Self::Sort>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<ProjectScope>, Error> {
-        let filter_doc = filter.into();
+    ) -> Result<Vec<ProjectScope>, AppError> {
+        let filter_doc = exclude_deleted(filter.into());
 
         // Create FindOptions
         let mut options = mongodb::options::FindOptions::default();
@@ -131,8 +179,62 @@ impl Repository<ProjectScope> for ProjectScopeRepository {
         Ok(items)
     }
 
-    fn collection(&self) -> Result<Collection<ProjectScope>, Error> {
-        Ok(self.collection.clone())
+    async fn count(&self, filter: Self::Filter) -> Result<u64, AppError> {
+        mongo::count(&self.collection, filter.into()).await
+    }
+
+    async fn find_page(
+        &self,
+        filter: Self::Filter,
+        sort: Option<SortBuilder<Self::Sort>>,
+        pagination: Pagination,
+    ) -> Result<Page<ProjectScope>, AppError> {
+        let sort = sort.map(SortBuilder::to_document);
+        mongo::find_page(&self.collection, filter.into(), sort, pagination).await
+    }
+
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        mongo::soft_delete(&self.collection, id, deleted_by).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<Option<ProjectScope>, AppError> {
+        if !mongo::restore(&self.collection, id).await? {
+            return Ok(None);
+        }
+        self.read(id).await
+    }
+
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError> {
+        mongo::is_deleted(&self.collection, id).await
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        mongo::purge(&self.collection, deleted_before).await
+    }
+
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        mongo::distinct_ids(&self.collection, filter, transaction).await
+    }
+
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError> {
+        mongo::soft_delete_many(
+            &self.collection,
+            filter,
+            deleted_at,
+            deleted_by,
+            transaction,
+        )
+        .await
     }
 }
 
@@ -140,6 +242,7 @@ impl Repository<ProjectScope> for ProjectScopeRepository {
 mod tests {
     use super::*;
     use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Error;
     use chrono::Utc;
 
     async fn setup() -> (ProjectScopeRepository, Database) {
@@ -163,6 +266,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = repo.create(scope.clone()).await?;
@@ -188,6 +294,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = repo.create(scope.clone()).await?;
@@ -217,6 +326,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = repo.create(scope).await?;
@@ -224,6 +336,7 @@ mod tests {
             name: Some("write:users".to_string()),
             description: Some("Allows writing user data".to_string()),
             enabled: Some(false),
+            labels: None,
         };
 
         let updated = repo.update(created.id.unwrap(), update).await?;
@@ -240,6 +353,7 @@ mod tests {
                     name: Some("Test".to_string()),
                     description: None,
                     enabled: None,
+                    labels: None,
                 },
             )
             .await;
@@ -260,6 +374,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = repo.create(scope).await?;
@@ -289,6 +406,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let scope2 = ProjectScope {
             id: Some(Uuid::new()),
@@ -298,6 +418,9 @@ mod tests {
             enabled: false,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         repo.create(scope1).await?;
@@ -308,6 +431,7 @@ mod tests {
             project_id: Some(project_id),
             name: None,
             is_enabled: None,
+            ..Default::default()
         };
         let found = repo.find(filter, None, None).await?;
         assert_eq!(found.len(), 1);
@@ -318,6 +442,7 @@ mod tests {
             project_id: None,
             name: None,
             is_enabled: Some(true),
+            ..Default::default()
         };
         let enabled_scopes = repo.find(enabled_filter, None, None).await?;
         assert_eq!(enabled_scopes.len(), 1);
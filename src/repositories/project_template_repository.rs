@@ -0,0 +1,291 @@
+use crate::errors::AppError;
+use crate::models::pagination::{Page, Pagination};
+use crate::models::project_template::{
+    ProjectTemplate, ProjectTemplateFilter, ProjectTemplateSortableFields,
+    ProjectTemplateUpdatePayload,
+};
+use crate::models::sort::SortBuilder;
+use crate::repositories::base::{IdFilter, Repository, exclude_deleted};
+use crate::repositories::mongo;
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use mongodb::bson::uuid::Uuid;
+use mongodb::bson::{doc, to_document};
+use mongodb::options::IndexOptions;
+use mongodb::{Collection, Database, IndexModel};
+
+/// Repository for managing ProjectTemplate documents in MongoDB.
+///
+/// Provides CRUD operations for ProjectTemplate entities.
+pub struct ProjectTemplateRepository {
+    collection: Collection<ProjectTemplate>,
+}
+
+impl ProjectTemplateRepository {
+    /// Creates a new ProjectTemplateRepository instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `database` - MongoDB Database instance
+    ///
+    /// # Returns
+    ///
+    /// Returns a Result containing the ProjectTemplateRepository or an error if initialization fails.
+    pub fn new(database: Database) -> Result<Self, AppError> {
+        let collection = database.collection::<ProjectTemplate>("project_templates");
+        Ok(Self { collection })
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateRepository::ensure_indexes")]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        // Unique constraints only apply to records that have not been soft-deleted
+        let _ = &self
+            .collection
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "name": 1, "deleted_at": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+
+        // Wildcard index so label selectors can match on any label key
+        let _ = &self
+            .collection
+            .create_index(IndexModel::builder().keys(doc! { "labels.$**": 1 }).build())
+            .await?;
+
+        Ok(())
+    }
+
+    pub fn collection(&self) -> Result<Collection<ProjectTemplate>, AppError> {
+        Ok(self.collection.clone())
+    }
+}
+
+/// A project template repository of any storage.
+pub type DynProjectTemplateRepository = dyn Repository<
+        ProjectTemplate,
+        UpdatePayload = ProjectTemplateUpdatePayload,
+        Filter = ProjectTemplateFilter,
+        Sort = ProjectTemplateSortableFields,
+    > + Send
+    + Sync;
+
+#[async_trait]
+impl Repository<ProjectTemplate> for ProjectTemplateRepository {
+    type UpdatePayload = ProjectTemplateUpdatePayload;
+    type Filter = ProjectTemplateFilter;
+    type Sort = ProjectTemplateSortableFields;
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateRepository::create")]
+    async fn create_in(
+        &self,
+        mut item: ProjectTemplate,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<ProjectTemplate, AppError> {
+        if item.id.is_none() {
+            item.id = Some(Uuid::new());
+        }
+        item.deleted_at = None;
+        item.deleted_by = None;
+        item.created_at = Some(Utc::now());
+        item.updated_at = Some(Utc::now());
+        let action = self.collection.insert_one(&item);
+        match transaction {
+            Some(transaction) => action.session(transaction.session()).await?,
+            None => action.await?,
+        };
+        Ok(item)
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateRepository::read")]
+    async fn read(&self, id: Uuid) -> Result<Option<ProjectTemplate>, AppError> {
+        let result = self
+            .collection
+            .find_one(exclude_deleted(doc! { "_id": id }))
+            .await?;
+        Ok(result)
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateRepository::update")]
+    async fn update(
+        &self,
+        id: Uuid,
+        payload: Self::UpdatePayload,
+    ) -> Result<ProjectTemplate, AppError> {
+        let mut document = to_document(&payload)?;
+        document.insert("updated_at", timestamp::to_bson(&Utc::now()));
+
+        self.collection
+            .update_one(
+                exclude_deleted(doc! { "_id": id }),
+                doc! { "$set": document },
+            )
+            .await?;
+        let updated = self
+            .read(id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Project template not found".to_string()))?;
+        Ok(updated)
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateRepository::delete")]
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
+        let result = self.collection.delete_one(doc! { "_id": id }).await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateRepository::find")]
+    async fn find(
+        &self,
+        filter: Self::Filter,
+        sort: Option<SortBuilder<Self::Sort>>,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<ProjectTemplate>, AppError> {
+        let filter_doc = exclude_deleted(filter.into());
+
+        // Create FindOptions
+        let mut options = mongodb::options::FindOptions::default();
+
+        if let Some(s) = sort {
+            options.sort = Some(s.to_document());
+        }
+
+        if let Some(p) = pagination {
+            options.skip = Some(p.skip());
+            options.limit = Some(p.limit());
+        }
+
+        let result = self
+            .collection
+            .find(filter_doc)
+            .with_options(options)
+            .await?;
+        let items: Vec<ProjectTemplate> = result.try_collect().await?;
+        Ok(items)
+    }
+
+    async fn count(&self, filter: Self::Filter) -> Result<u64, AppError> {
+        mongo::count(&self.collection, filter.into()).await
+    }
+
+    async fn find_page(
+        &self,
+        filter: Self::Filter,
+        sort: Option<SortBuilder<Self::Sort>>,
+        pagination: Pagination,
+    ) -> Result<Page<ProjectTemplate>, AppError> {
+        let sort = sort.map(SortBuilder::to_document);
+        mongo::find_page(&self.collection, filter.into(), sort, pagination).await
+    }
+
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        mongo::soft_delete(&self.collection, id, deleted_by).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<Option<ProjectTemplate>, AppError> {
+        if !mongo::restore(&self.collection, id).await? {
+            return Ok(None);
+        }
+        self.read(id).await
+    }
+
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError> {
+        mongo::is_deleted(&self.collection, id).await
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        mongo::purge(&self.collection, deleted_before).await
+    }
+
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        mongo::distinct_ids(&self.collection, filter, transaction).await
+    }
+
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError> {
+        mongo::soft_delete_many(
+            &self.collection,
+            filter,
+            deleted_at,
+            deleted_by,
+            transaction,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::project_template::TemplateEntry;
+    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Error;
+
+    async fn setup() -> (ProjectTemplateRepository, Database) {
+        let db = setup_test_db("project_template").await.unwrap();
+        let repo = ProjectTemplateRepository::new(db.clone()).expect("Failed to create repository");
+        repo.ensure_indexes()
+            .await
+            .expect("Failed to create indexes");
+        (repo, db)
+    }
+
+    #[tokio::test]
+    async fn test_create_and_read_project_template() -> Result<(), Error> {
+        let (repo, db) = setup().await;
+
+        let created = repo.create(ProjectTemplate::standard()).await?;
+        assert!(created.id.is_some());
+        assert!(created.created_at.is_some());
+
+        let read = repo.read(created.id.unwrap()).await?.unwrap();
+        assert_eq!(read.name, "standard");
+        assert_eq!(read.environments, created.environments);
+        assert_eq!(read.project_scopes, created.project_scopes);
+
+        // Names are unique
+        let duplicate = repo.create(ProjectTemplate::standard()).await;
+        assert!(matches!(duplicate, Err(AppError::Conflict(_))));
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_project_template() -> Result<(), Error> {
+        let (repo, db) = setup().await;
+        let created = repo.create(ProjectTemplate::standard()).await?;
+
+        let update = ProjectTemplateUpdatePayload {
+            name: None,
+            description: None,
+            environments: Some(vec![TemplateEntry {
+                name: "prod".to_string(),
+                description: String::new(),
+            }]),
+            project_scopes: None,
+            server_key_algorithm: None,
+            labels: None,
+        };
+        let updated = repo.update(created.id.unwrap(), update).await?;
+        assert_eq!(updated.environments.len(), 1);
+        assert_eq!(updated.project_scopes, created.project_scopes);
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+}
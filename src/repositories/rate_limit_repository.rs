@@ -0,0 +1,142 @@
+use crate::errors::AppError;
+use crate::models::rate_limit::{RateLimitKey, RateLimitWindow};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::bson::doc;
+use mongodb::options::{IndexOptions, ReturnDocument};
+use mongodb::{Collection, Database, IndexModel};
+use std::time::Duration;
+
+/// Repository for the rate limit counters shared by every instance.
+///
+/// Windows are not soft-deleted or listed, so this does not implement
+/// [`Repository`](crate::repositories::base::Repository).
+pub struct RateLimitRepository {
+    collection: Collection<RateLimitWindow>,
+}
+
+impl RateLimitRepository {
+    pub fn new(database: Database) -> Result<Self, AppError> {
+        let collection = database.collection::<RateLimitWindow>("rate_limits");
+        Ok(Self { collection })
+    }
+
+    #[tracing::instrument(skip_all, name = "RateLimitRepository::ensure_indexes")]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        // Expired windows are removed by MongoDB
+        let _ = &self
+            .collection
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "expires_at": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .expire_after(Duration::from_secs(0))
+                            .build(),
+                    )
+                    .build(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub fn collection(&self) -> Result<Collection<RateLimitWindow>, AppError> {
+        Ok(self.collection.clone())
+    }
+}
+
+/// Counters of authentication attempts, shared by every instance.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Counts one attempt against `key` in the window `now` falls in.
+    ///
+    /// # Returns
+    ///
+    /// The window, including this attempt.
+    async fn hit(
+        &self,
+        key: &RateLimitKey,
+        window_seconds: u64,
+        now: DateTime<Utc>,
+    ) -> Result<RateLimitWindow, AppError>;
+}
+
+#[async_trait]
+impl RateLimitStore for RateLimitRepository {
+    #[tracing::instrument(skip_all, name = "RateLimitRepository::hit")]
+    async fn hit(
+        &self,
+        key: &RateLimitKey,
+        window_seconds: u64,
+        now: DateTime<Utc>,
+    ) -> Result<RateLimitWindow, AppError> {
+        let start = RateLimitWindow::start(now, window_seconds);
+        let id = RateLimitWindow::id_for(key, start);
+        let expires_at =
+            mongodb::bson::DateTime::from_millis((start + window_seconds.max(1) as i64) * 1000);
+
+        let mut retried = false;
+        loop {
+            let result = self
+                .collection
+                .find_one_and_update(
+                    doc! { "_id": &id },
+                    doc! {
+                        "$inc": { "count": 1 },
+                        "$setOnInsert": { "expires_at": expires_at },
+                    },
+                )
+                .upsert(true)
+                .return_document(ReturnDocument::After)
+                .await;
+            match result {
+                Ok(Some(window)) => return Ok(window),
+                Ok(None) => {
+                    return Err(AppError::Internal(anyhow::anyhow!(
+                        "Rate limit upsert returned no document"
+                    )));
+                }
+                Err(e) => match AppError::from(e) {
+                    // Two first attempts raced to insert the window; the loser
+                    // increments the one the winner created
+                    AppError::Conflict(_) if !retried => retried = true,
+                    error => return Err(error),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Result;
+
+    async fn setup() -> (RateLimitRepository, Database) {
+        let db = setup_test_db("rate_limit").await.unwrap();
+        let repo = RateLimitRepository::new(db.clone()).expect("Failed to create repository");
+        (repo, db)
+    }
+
+    #[tokio::test]
+    async fn test_hit_counts_attempts_per_key_and_window() -> Result<()> {
+        let (repo, db) = setup().await;
+        let now = Utc::now();
+        let client = RateLimitKey::Client("client-1".to_string());
+        let ip = RateLimitKey::Ip("10.0.0.1".to_string());
+
+        assert_eq!(repo.hit(&client, 60, now).await?.count, 1);
+        assert_eq!(repo.hit(&client, 60, now).await?.count, 2);
+        assert_eq!(repo.hit(&ip, 60, now).await?.count, 1);
+
+        let next_window = now + chrono::Duration::seconds(60);
+        let window = repo.hit(&client, 60, next_window).await?;
+        assert_eq!(window.count, 1);
+        assert!(window.reset_at() > next_window);
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+use crate::errors::AppError;
+use crate::repositories::access_token_repository::{
+    AccessTokenRepository, DynAccessTokenRepository,
+};
+use crate::repositories::dpop_proof_repository::{DpopProofRepository, DpopProofStore};
+use crate::repositories::environment_repository::{
+    DynEnvironmentRepository, EnvironmentRepository,
+};
+use crate::repositories::memory::{
+    MemoryAccessTokenRepository, MemoryDpopProofStore, MemoryEnvironmentRepository,
+    MemoryProjectAccessRepository, MemoryProjectRepository, MemoryProjectScopeRepository,
+    MemoryProjectTemplateRepository, MemoryRateLimitStore, MemoryServerKeyRepository,
+    MemoryServiceAccountKeyRepository, MemoryServiceAccountRepository,
+};
+use crate::repositories::project_access_repository::{
+    DynProjectAccessRepository, ProjectAccessRepository,
+};
+use crate::repositories::project_repository::{DynProjectRepository, ProjectRepository};
+use crate::repositories::project_scope_repository::{
+    DynProjectScopeRepository, ProjectScopeRepository,
+};
+use crate::repositories::project_template_repository::{
+    DynProjectTemplateRepository, ProjectTemplateRepository,
+};
+use crate::repositories::rate_limit_repository::{RateLimitRepository, RateLimitStore};
+use crate::repositories::server_key_repository::{DynServerKeyRepository, ServerKeyRepository};
+use crate::repositories::service_account_key_repository::{
+    DynServiceAccountKeyRepository, ServiceAccountKeyRepository,
+};
+use crate::repositories::service_account_repository::{
+    DynServiceAccountRepository, ServiceAccountRepository,
+};
+#[cfg(feature = "sqlite")]
+use crate::repositories::sqlite::{
+    SqliteAccessTokenRepository, SqliteDatabase, SqliteDpopProofStore, SqliteEnvironmentRepository,
+    SqliteProjectAccessRepository, SqliteProjectRepository, SqliteProjectScopeRepository,
+    SqliteProjectTemplateRepository, SqliteRateLimitStore, SqliteServerKeyRepository,
+    SqliteServiceAccountKeyRepository, SqliteServiceAccountRepository,
+};
+use mongodb::Database;
+use std::fmt;
+use std::sync::Arc;
+
+/// The repositories services are built on, whatever their storage.
+///
+/// Services take their repositories from here through their
+/// `with_repositories` constructor, so the same service code runs over
+/// MongoDB, SQLite or [in-memory](crate::repositories::memory) records.
+///
+/// # Fields
+/// - `database`: The database behind the repositories, used for
+///   transactions; `None` when they are held in memory
+#[derive(Clone)]
+pub struct Repositories {
+    pub projects: Arc<DynProjectRepository>,
+    pub environments: Arc<DynEnvironmentRepository>,
+    pub project_scopes: Arc<DynProjectScopeRepository>,
+    pub project_templates: Arc<DynProjectTemplateRepository>,
+    pub project_access: Arc<DynProjectAccessRepository>,
+    pub server_keys: Arc<DynServerKeyRepository>,
+    pub access_tokens: Arc<DynAccessTokenRepository>,
+    pub service_accounts: Arc<DynServiceAccountRepository>,
+    pub service_account_keys: Arc<DynServiceAccountKeyRepository>,
+    pub rate_limits: Arc<dyn RateLimitStore>,
+    pub dpop_proofs: Arc<dyn DpopProofStore>,
+    pub database: Option<Arc<Database>>,
+}
+
+impl fmt::Debug for Repositories {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Repositories")
+            .field("database", &self.database)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Repositories {
+    /// Repositories over the collections of `database`.
+    pub fn mongo(database: Arc<Database>) -> Result<Self, AppError> {
+        let db = database.as_ref();
+        Ok(Self {
+            projects: Arc::new(ProjectRepository::new(db.clone())?),
+            environments: Arc::new(EnvironmentRepository::new(db.clone())?),
+            project_scopes: Arc::new(ProjectScopeRepository::new(db.clone())?),
+            project_templates: Arc::new(ProjectTemplateRepository::new(db.clone())?),
+            project_access: Arc::new(ProjectAccessRepository::new(db.clone())?),
+            server_keys: Arc::new(ServerKeyRepository::new(db.clone())?),
+            access_tokens: Arc::new(AccessTokenRepository::new(db.clone())?),
+            service_accounts: Arc::new(ServiceAccountRepository::new(db.clone())?),
+            service_account_keys: Arc::new(ServiceAccountKeyRepository::new(db.clone())?),
+            rate_limits: Arc::new(RateLimitRepository::new(db.clone())?),
+            dpop_proofs: Arc::new(DpopProofRepository::new(db.clone())?),
+            database: Some(database),
+        })
+    }
+
+    /// Empty repositories held in memory, shared by every clone.
+    pub fn in_memory() -> Self {
+        Self {
+            projects: Arc::new(MemoryProjectRepository::projects()),
+            environments: Arc::new(MemoryEnvironmentRepository::environments()),
+            project_scopes: Arc::new(MemoryProjectScopeRepository::project_scopes()),
+            project_templates: Arc::new(MemoryProjectTemplateRepository::project_templates()),
+            project_access: Arc::new(MemoryProjectAccessRepository::project_access()),
+            server_keys: Arc::new(MemoryServerKeyRepository::server_keys()),
+            access_tokens: Arc::new(MemoryAccessTokenRepository::access_tokens()),
+            service_accounts: Arc::new(MemoryServiceAccountRepository::service_accounts()),
+            service_account_keys: Arc::new(
+                MemoryServiceAccountKeyRepository::service_account_keys(),
+            ),
+            rate_limits: Arc::new(MemoryRateLimitStore::default()),
+            dpop_proofs: Arc::new(MemoryDpopProofStore::default()),
+            database: None,
+        }
+    }
+
+    /// Repositories over the tables of a SQLite `database`, which must have
+    /// been migrated.
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite(database: &SqliteDatabase) -> Self {
+        Self {
+            projects: Arc::new(SqliteProjectRepository::projects(database)),
+            environments: Arc::new(SqliteEnvironmentRepository::environments(database)),
+            project_scopes: Arc::new(SqliteProjectScopeRepository::project_scopes(database)),
+            project_templates: Arc::new(SqliteProjectTemplateRepository::project_templates(
+                database,
+            )),
+            project_access: Arc::new(SqliteProjectAccessRepository::project_access(database)),
+            server_keys: Arc::new(SqliteServerKeyRepository::server_keys(database)),
+            access_tokens: Arc::new(SqliteAccessTokenRepository::access_tokens(database)),
+            service_accounts: Arc::new(SqliteServiceAccountRepository::service_accounts(database)),
+            service_account_keys: Arc::new(
+                SqliteServiceAccountKeyRepository::service_account_keys(database),
+            ),
+            rate_limits: Arc::new(SqliteRateLimitStore::new(database)),
+            dpop_proofs: Arc::new(SqliteDpopProofStore::new(database)),
+            database: None,
+        }
+    }
+}
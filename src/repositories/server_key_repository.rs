@@ -1,15 +1,18 @@
-use crate::models::pagination::Pagination;
+use crate::errors::AppError;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::server_key::{
     ServerKey, ServerKeyFilter, ServerKeySortableFields, ServerKeyUpdatePayload,
 };
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use anyhow::{Error, Result};
+use crate::repositories::base::{IdFilter, Repository, drop_index_if_exists, exclude_deleted};
+use crate::repositories::mongo;
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use mongodb::bson::Uuid;
-use mongodb::bson::{Bson, doc, to_document};
+use mongodb::bson::{doc, to_document};
 use mongodb::options::{FindOptions, IndexOptions};
 use mongodb::{Collection, Database, IndexModel};
 
@@ -31,22 +34,25 @@ impl ServerKeyRepository {
     /// # Returns
     ///
     /// Returns a Result containing the ServerKeyRepository or an error if initialization fails.
-    pub fn new(database: Database) -> Result<Self, Error> {
+    pub fn new(database: Database) -> Result<Self, AppError> {
         let collection = database.collection::<ServerKey>("server_keys");
         Ok(Self { collection })
     }
 
-    pub async fn ensure_indexes(&self) -> Result<(), Error> {
+    #[tracing::instrument(skip_all, name = "ServerKeyRepository::ensure_indexes")]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        // Unique constraints only apply to records that have not been soft-deleted
+        drop_index_if_exists(&self.collection, "environment_id_1_key_1").await?;
+
         let _ = &self
             .collection
             .create_index(
                 IndexModel::builder()
-                    .keys(doc! { "environment_id": 1, "key": 1 })
+                    .keys(doc! { "environment_id": 1, "key": 1, "deleted_at": 1 })
                     .options(IndexOptions::builder().unique(true).build())
                     .build(),
             )
-            .await
-            .expect("Failed to create index on environment_id, key");
+            .await?;
 
         let _ = &self
             .collection
@@ -55,60 +61,93 @@ impl ServerKeyRepository {
                     .keys(doc! { "environment_id": 1, "algorithm": 1 })
                     .build(),
             )
-            .await
-            .expect("Failed to create index on environment_id, algorithm");
+            .await?;
 
         Ok(())
     }
+
+    pub fn collection(&self) -> Result<Collection<ServerKey>, AppError> {
+        Ok(self.collection.clone())
+    }
 }
 
+/// A server key repository of any storage.
+pub type DynServerKeyRepository = dyn Repository<
+        ServerKey,
+        UpdatePayload = ServerKeyUpdatePayload,
+        Filter = ServerKeyFilter,
+        Sort = ServerKeySortableFields,
+    > + Send
+    + Sync;
+
 #[async_trait]
 impl Repository<ServerKey> for ServerKeyRepository {
     type UpdatePayload = ServerKeyUpdatePayload;
     type Filter = ServerKeyFilter;
     type Sort = ServerKeySortableFields;
 
-    async fn create(&self, mut item: ServerKey) -> Result<ServerKey, Error> {
+    #[tracing::instrument(skip_all, name = "ServerKeyRepository::create")]
+    async fn create_in(
+        &self,
+        mut item: ServerKey,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<ServerKey, AppError> {
         if item.id.is_none() {
             item.id = Some(Uuid::new());
         }
+        item.deleted_at = None;
+        item.deleted_by = None;
         item.created_at = Utc::now();
         item.updated_at = Utc::now();
-        self.collection.insert_one(&item).await?;
+        let action = self.collection.insert_one(&item);
+        match transaction {
+            Some(transaction) => action.session(transaction.session()).await?,
+            None => action.await?,
+        };
         Ok(item)
     }
 
-    async fn read(&self, id: Uuid) -> Result<Option<ServerKey>, Error> {
-        let result = self.collection.find_one(doc! { "_id": id }).await?;
+    #[tracing::instrument(skip_all, name = "ServerKeyRepository::read")]
+    async fn read(&self, id: Uuid) -> Result<Option<ServerKey>, AppError> {
+        let result = self
+            .collection
+            .find_one(exclude_deleted(doc! { "_id": id }))
+            .await?;
         Ok(result)
     }
 
-    async fn update(&self, id: Uuid, payload: Self::UpdatePayload) -> Result<ServerKey, Error> {
+    #[tracing::instrument(skip_all, name = "ServerKeyRepository::update")]
+    async fn update(&self, id: Uuid, payload: Self::UpdatePayload) -> Result<ServerKey, AppError> {
         let mut document = to_document(&payload)?;
-        document.insert("updated_at", Bson::String(Utc::now().to_rfc3339()));
+        document.insert("updated_at", timestamp::to_bson(&Utc::now()));
 
         self.collection
-            .update_one(doc! { "_id": id }, doc! { "$set": document })
+            .update_one(
+                exclude_deleted(doc! { "_id": id }),
+                doc! { "$set": document },
+            )
             .await?;
         let updated = self
             .read(id)
             .await?
-            .ok_or_else(|| Error::msg("ServerKey not found"))?;
+            .ok_or_else(|| AppError::NotFound("ServerKey not found".to_string()))?;
         Ok(updated)
     }
 
-    async fn delete(&self, id: Uuid) -> Result<bool, Error> {
+    #[tracing::instrument(skip_all, name = "ServerKeyRepository::delete")]
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
         let result = self.collection.delete_one(doc! { "_id": id }).await?;
         Ok(result.deleted_count > 0)
     }
 
+    #[tracing::instrument(skip_all, name = "ServerKeyRepository::find")]
     async fn find(
         &self,
         filter: Self::Filter,
         sort: Option<SortBuilder<Self::Sort>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<ServerKey>, Error> {
-        let filter_doc = filter.into();
+    ) -> Result<Vec<ServerKey>, AppError> {
+        let filter_doc = exclude_deleted(filter.into());
 
         // Create FindOptions
         let mut options = FindOptions::default();
@@ -131,8 +170,62 @@ impl Repository<ServerKey> for ServerKeyRepository {
         Ok(items)
     }
 
-    fn collection(&self) -> Result<Collection<ServerKey>, Error> {
-        Ok(self.collection.clone())
+    async fn count(&self, filter: Self::Filter) -> Result<u64, AppError> {
+        mongo::count(&self.collection, filter.into()).await
+    }
+
+    async fn find_page(
+        &self,
+        filter: Self::Filter,
+        sort: Option<SortBuilder<Self::Sort>>,
+        pagination: Pagination,
+    ) -> Result<Page<ServerKey>, AppError> {
+        let sort = sort.map(SortBuilder::to_document);
+        mongo::find_page(&self.collection, filter.into(), sort, pagination).await
+    }
+
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        mongo::soft_delete(&self.collection, id, deleted_by).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<Option<ServerKey>, AppError> {
+        if !mongo::restore(&self.collection, id).await? {
+            return Ok(None);
+        }
+        self.read(id).await
+    }
+
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError> {
+        mongo::is_deleted(&self.collection, id).await
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        mongo::purge(&self.collection, deleted_before).await
+    }
+
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        mongo::distinct_ids(&self.collection, filter, transaction).await
+    }
+
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError> {
+        mongo::soft_delete_many(
+            &self.collection,
+            filter,
+            deleted_at,
+            deleted_by,
+            transaction,
+        )
+        .await
     }
 }
 
@@ -141,6 +234,7 @@ mod tests {
     use super::*;
     use crate::test_utils::cleanup_test_db;
     use crate::test_utils::setup_test_db;
+    use anyhow::Result;
     use jsonwebtoken::Algorithm;
     use mongodb::Database;
 
@@ -163,6 +257,8 @@ mod tests {
             algorithm: Algorithm::HS256,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let result = repository.create(server_key).await?;
@@ -186,6 +282,8 @@ mod tests {
             algorithm: Algorithm::HS256,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = repository.create(server_key).await?;
@@ -209,6 +307,8 @@ mod tests {
             algorithm: Algorithm::HS256,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = repository.create(server_key).await?;
@@ -240,6 +340,8 @@ mod tests {
             algorithm: Algorithm::HS256,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = repository.create(server_key).await?;
@@ -1,18 +1,20 @@
-use crate::models::pagination::Pagination;
+use crate::errors::AppError;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::service_account_key::{
     ServiceAccountKey, ServiceAccountKeyFilter, ServiceAccountKeySortableFields,
     ServiceAccountKeyUpdatePayload,
 };
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use anyhow::Error;
-use anyhow::Result;
+use crate::repositories::base::{IdFilter, Repository, drop_index_if_exists, exclude_deleted};
+use crate::repositories::mongo;
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use mongodb::IndexModel;
 use mongodb::bson::uuid::Uuid;
-use mongodb::bson::{Bson, doc, to_document};
+use mongodb::bson::{doc, to_document};
 use mongodb::options::IndexOptions;
 use mongodb::{Collection, Database};
 
@@ -24,17 +26,21 @@ pub struct ServiceAccountKeyRepository {
 }
 
 impl ServiceAccountKeyRepository {
-    pub fn new(database: Database) -> Result<Self, anyhow::Error> {
+    pub fn new(database: Database) -> Result<Self, AppError> {
         let collection = database.collection::<ServiceAccountKey>("service_account_keys");
         Ok(Self { collection })
     }
 
-    pub async fn ensure_indexes(&self) -> Result<()> {
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyRepository::ensure_indexes")]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        // Unique constraints only apply to records that have not been soft-deleted
+        drop_index_if_exists(&self.collection, "service_account_id_1_algorithm_1").await?;
+
         let unique_options = IndexOptions::builder().unique(true).build();
         let non_unique_options = IndexOptions::builder().unique(false).build();
 
         let unique_index = IndexModel::builder()
-            .keys(doc! { "service_account_id": 1, "algorithm": 1 })
+            .keys(doc! { "service_account_id": 1, "algorithm": 1, "deleted_at": 1 })
             .options(unique_options)
             .build();
 
@@ -47,59 +53,93 @@ impl ServiceAccountKeyRepository {
         self.collection.create_index(non_unique_index).await?;
         Ok(())
     }
+
+    pub fn collection(&self) -> Result<Collection<ServiceAccountKey>, AppError> {
+        Ok(self.collection.clone())
+    }
 }
 
+/// A service account key repository of any storage.
+pub type DynServiceAccountKeyRepository = dyn Repository<
+        ServiceAccountKey,
+        UpdatePayload = ServiceAccountKeyUpdatePayload,
+        Filter = ServiceAccountKeyFilter,
+        Sort = ServiceAccountKeySortableFields,
+    > + Send
+    + Sync;
+
 #[async_trait]
 impl Repository<ServiceAccountKey> for ServiceAccountKeyRepository {
     type UpdatePayload = ServiceAccountKeyUpdatePayload;
     type Filter = ServiceAccountKeyFilter;
     type Sort = ServiceAccountKeySortableFields;
 
-    async fn create(&self, mut item: ServiceAccountKey) -> Result<ServiceAccountKey, Error> {
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyRepository::create")]
+    async fn create_in(
+        &self,
+        mut item: ServiceAccountKey,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<ServiceAccountKey, AppError> {
         if item.id.is_none() {
             item.id = Some(Uuid::new());
         }
+        item.deleted_at = None;
+        item.deleted_by = None;
         item.created_at = Some(Utc::now());
         item.updated_at = Some(Utc::now());
-        self.collection.insert_one(&item).await?;
+        let action = self.collection.insert_one(&item);
+        match transaction {
+            Some(transaction) => action.session(transaction.session()).await?,
+            None => action.await?,
+        };
         Ok(item)
     }
 
-    async fn read(&self, id: Uuid) -> Result<Option<ServiceAccountKey>, Error> {
-        let result = self.collection.find_one(doc! { "_id": id }).await?;
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyRepository::read")]
+    async fn read(&self, id: Uuid) -> Result<Option<ServiceAccountKey>, AppError> {
+        let result = self
+            .collection
+            .find_one(exclude_deleted(doc! { "_id": id }))
+            .await?;
         Ok(result)
     }
 
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyRepository::update")]
     async fn update(
         &self,
         id: Uuid,
         payload: Self::UpdatePayload,
-    ) -> Result<ServiceAccountKey, Error> {
+    ) -> Result<ServiceAccountKey, AppError> {
         let mut document = to_document(&payload)?;
-        document.insert("updated_at", Bson::String(Utc::now().to_rfc3339()));
+        document.insert("updated_at", timestamp::to_bson(&Utc::now()));
 
         self.collection
-            .update_one(doc! { "_id": id }, doc! { "$set": document })
+            .update_one(
+                exclude_deleted(doc! { "_id": id }),
+                doc! { "$set": document },
+            )
             .await?;
         let updated = self
             .read(id)
             .await?
-            .ok_or_else(|| Error::msg("ServiceAccountKey not found"))?;
+            .ok_or_else(|| AppError::NotFound("ServiceAccountKey not found".to_string()))?;
         Ok(updated)
     }
 
-    async fn delete(&self, id: Uuid) -> Result<bool, Error> {
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyRepository::delete")]
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
         let result = self.collection.delete_one(doc! { "_id": id }).await?;
         Ok(result.deleted_count > 0)
     }
 
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyRepository::find")]
     async fn find(
         &self,
         filter: Self::Filter,
         sort: Option<SortBuilder<Self::Sort>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<ServiceAccountKey>, Error> {
-        let filter_doc = filter.into();
+    ) -> Result<Vec<ServiceAccountKey>, AppError> {
+        let filter_doc = exclude_deleted(filter.into());
 
         // Create FindOptions
         let mut options = mongodb::options::FindOptions::default();
@@ -122,8 +162,62 @@ impl Repository<ServiceAccountKey> for ServiceAccountKeyRepository {
         Ok(items)
     }
 
-    fn collection(&self) -> Result<Collection<ServiceAccountKey>, Error> {
-        Ok(self.collection.clone())
+    async fn count(&self, filter: Self::Filter) -> Result<u64, AppError> {
+        mongo::count(&self.collection, filter.into()).await
+    }
+
+    async fn find_page(
+        &self,
+        filter: Self::Filter,
+        sort: Option<SortBuilder<Self::Sort>>,
+        pagination: Pagination,
+    ) -> Result<Page<ServiceAccountKey>, AppError> {
+        let sort = sort.map(SortBuilder::to_document);
+        mongo::find_page(&self.collection, filter.into(), sort, pagination).await
+    }
+
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        mongo::soft_delete(&self.collection, id, deleted_by).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<Option<ServiceAccountKey>, AppError> {
+        if !mongo::restore(&self.collection, id).await? {
+            return Ok(None);
+        }
+        self.read(id).await
+    }
+
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError> {
+        mongo::is_deleted(&self.collection, id).await
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        mongo::purge(&self.collection, deleted_before).await
+    }
+
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        mongo::distinct_ids(&self.collection, filter, transaction).await
+    }
+
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError> {
+        mongo::soft_delete_many(
+            &self.collection,
+            filter,
+            deleted_at,
+            deleted_by,
+            transaction,
+        )
+        .await
     }
 }
 
@@ -158,6 +252,8 @@ mod tests {
             enabled: true,
             created_at: Some(now),
             updated_at: Some(now),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = repo.create(key.clone()).await.unwrap();
@@ -179,6 +275,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = repo.create(key.clone()).await.unwrap();
@@ -201,6 +299,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = repo.create(key).await.unwrap();
@@ -229,6 +329,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = repo.create(key).await.unwrap();
@@ -253,6 +355,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
         let key2 = ServiceAccountKey {
             id: Some(Uuid::new()),
@@ -263,6 +367,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         repo.create(key1).await.unwrap();
@@ -274,6 +380,7 @@ mod tests {
             algorithm: None,
             is_enabled: None,
             is_active: None,
+            ..Default::default()
         };
         let all_keys = repo.find(filter, None, None).await.unwrap();
         assert_eq!(all_keys.len(), 2);
@@ -284,6 +391,7 @@ mod tests {
             algorithm: None,
             is_enabled: Some(true),
             is_active: None,
+            ..Default::default()
         };
         let enabled_keys = repo.find(enabled_filter, None, None).await.unwrap();
         assert_eq!(enabled_keys.len(), 2);
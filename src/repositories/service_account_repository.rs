@@ -1,17 +1,19 @@
-use crate::models::pagination::Pagination;
+use crate::errors::AppError;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::service_account::{
     ServiceAccount, ServiceAccountFilter, ServiceAccountSortableFields, ServiceAccountUpdatePayload,
 };
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use anyhow::Error;
-use anyhow::Result;
+use crate::repositories::base::{IdFilter, Repository, drop_index_if_exists, exclude_deleted};
+use crate::repositories::mongo;
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use mongodb::bson::uuid::Uuid;
-use mongodb::bson::{Bson, doc, to_document};
-use mongodb::options::IndexOptions;
+use mongodb::bson::{doc, to_document};
+use mongodb::options::{IndexOptions, ReturnDocument};
 use mongodb::{Collection, Database, IndexModel};
 
 /// Repository for managing ServiceAccount documents in MongoDB.
@@ -22,34 +24,129 @@ pub struct ServiceAccountRepository {
 }
 
 impl ServiceAccountRepository {
-    pub fn new(database: Database) -> Result<Self, anyhow::Error> {
+    pub fn new(database: Database) -> Result<Self, AppError> {
         let collection = database.collection::<ServiceAccount>("service_accounts");
         Ok(Self { collection })
     }
 
-    pub async fn ensure_indexes(&self) -> Result<(), Error> {
+    #[tracing::instrument(skip_all, name = "ServiceAccountRepository::ensure_indexes")]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        // Unique constraints only apply to records that have not been soft-deleted
+        drop_index_if_exists(&self.collection, "email_1").await?;
+        drop_index_if_exists(&self.collection, "user_1").await?;
+
         self.collection
             .create_index(
                 IndexModel::builder()
-                    .keys(doc! { "email": 1 })
+                    .keys(doc! { "email": 1, "deleted_at": 1 })
                     .options(IndexOptions::builder().unique(true).build())
                     .build(),
             )
-            .await
-            .expect("Failed to create unique index on email");
+            .await?;
 
         self.collection
             .create_index(
                 IndexModel::builder()
-                    .keys(doc! { "user": 1 })
+                    .keys(doc! { "user": 1, "deleted_at": 1 })
                     .options(IndexOptions::builder().unique(true).build())
                     .build(),
             )
-            .await
-            .expect("Failed to create unique index on user");
+            .await?;
+
+        // Wildcard index so label selectors can match on any label key
+        let _ = &self
+            .collection
+            .create_index(IndexModel::builder().keys(doc! { "labels.$**": 1 }).build())
+            .await?;
 
         Ok(())
     }
+
+    pub fn collection(&self) -> Result<Collection<ServiceAccount>, AppError> {
+        Ok(self.collection.clone())
+    }
+}
+
+/// Bookkeeping of failed authentications, on top of the operations of
+/// [`Repository`].
+#[async_trait]
+pub trait ServiceAccountStore:
+    Repository<
+        ServiceAccount,
+        UpdatePayload = ServiceAccountUpdatePayload,
+        Filter = ServiceAccountFilter,
+        Sort = ServiceAccountSortableFields,
+    > + Send
+    + Sync
+{
+    /// Counts a failed authentication against the account.
+    ///
+    /// # Returns
+    ///
+    /// The account with its updated failure count, or `None` if it does not exist.
+    async fn record_failed_authentication(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<ServiceAccount>, AppError>;
+
+    /// Refuses authentication for the account until `until`.
+    async fn lock(&self, id: Uuid, until: DateTime<Utc>) -> Result<(), AppError>;
+
+    /// Clears the failure count and any lockout after a successful authentication.
+    async fn reset_failed_authentications(&self, id: Uuid) -> Result<(), AppError>;
+}
+
+/// A service account repository of any storage.
+pub type DynServiceAccountRepository = dyn ServiceAccountStore;
+
+#[async_trait]
+impl ServiceAccountStore for ServiceAccountRepository {
+    #[tracing::instrument(
+        skip_all,
+        name = "ServiceAccountRepository::record_failed_authentication"
+    )]
+    async fn record_failed_authentication(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<ServiceAccount>, AppError> {
+        let account = self
+            .collection
+            .find_one_and_update(
+                exclude_deleted(doc! { "_id": id }),
+                doc! { "$inc": { "failed_authentications": 1 } },
+            )
+            .return_document(ReturnDocument::After)
+            .await?;
+        Ok(account)
+    }
+
+    #[tracing::instrument(skip_all, name = "ServiceAccountRepository::lock")]
+    async fn lock(&self, id: Uuid, until: DateTime<Utc>) -> Result<(), AppError> {
+        self.collection
+            .update_one(
+                exclude_deleted(doc! { "_id": id }),
+                doc! { "$set": { "locked_until": timestamp::to_bson(&until) } },
+            )
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        name = "ServiceAccountRepository::reset_failed_authentications"
+    )]
+    async fn reset_failed_authentications(&self, id: Uuid) -> Result<(), AppError> {
+        self.collection
+            .update_one(
+                exclude_deleted(doc! { "_id": id }),
+                doc! {
+                    "$set": { "failed_authentications": 0 },
+                    "$unset": { "locked_until": "" },
+                },
+            )
+            .await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -58,51 +155,72 @@ impl Repository<ServiceAccount> for ServiceAccountRepository {
     type Filter = ServiceAccountFilter;
     type Sort = ServiceAccountSortableFields;
 
-    async fn create(&self, mut item: ServiceAccount) -> Result<ServiceAccount, Error> {
+    #[tracing::instrument(skip_all, name = "ServiceAccountRepository::create")]
+    async fn create_in(
+        &self,
+        mut item: ServiceAccount,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<ServiceAccount, AppError> {
         if item.id.is_none() {
             item.id = Some(Uuid::new());
         }
+        item.deleted_at = None;
+        item.deleted_by = None;
         item.created_at = Some(Utc::now());
         item.updated_at = Some(Utc::now());
-        self.collection.insert_one(&item).await?;
+        let action = self.collection.insert_one(&item);
+        match transaction {
+            Some(transaction) => action.session(transaction.session()).await?,
+            None => action.await?,
+        };
         Ok(item)
     }
 
-    async fn read(&self, id: Uuid) -> Result<Option<ServiceAccount>, Error> {
-        let result = self.collection.find_one(doc! { "_id": id }).await?;
+    #[tracing::instrument(skip_all, name = "ServiceAccountRepository::read")]
+    async fn read(&self, id: Uuid) -> Result<Option<ServiceAccount>, AppError> {
+        let result = self
+            .collection
+            .find_one(exclude_deleted(doc! { "_id": id }))
+            .await?;
         Ok(result)
     }
 
+    #[tracing::instrument(skip_all, name = "ServiceAccountRepository::update")]
     async fn update(
         &self,
         id: Uuid,
         payload: Self::UpdatePayload,
-    ) -> Result<ServiceAccount, Error> {
+    ) -> Result<ServiceAccount, AppError> {
         let mut document = to_document(&payload)?;
-        document.insert("updated_at", Bson::String(Utc::now().to_rfc3339()));
+        document.insert("updated_at", timestamp::to_bson(&Utc::now()));
 
         self.collection
-            .update_one(doc! { "_id": id }, doc! { "$set": document })
+            .update_one(
+                exclude_deleted(doc! { "_id": id }),
+                doc! { "$set": document },
+            )
             .await?;
         let updated = self
             .read(id)
             .await?
-            .ok_or_else(|| Error::msg("ServiceAccount not found"))?;
+            .ok_or_else(|| AppError::NotFound("ServiceAccount not found".to_string()))?;
         Ok(updated)
     }
 
-    async fn delete(&self, id: Uuid) -> Result<bool, Error> {
+    #[tracing::instrument(skip_all, name = "ServiceAccountRepository::delete")]
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
         let result = self.collection.delete_one(doc! { "_id": id }).await?;
         Ok(result.deleted_count > 0)
     }
 
+    #[tracing::instrument(skip_all, name = "ServiceAccountRepository::find")]
     async fn find(
         &self,
         filter: Self::Filter,
         sort: Option<SortBuilder<Self::Sort>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<ServiceAccount>, Error> {
-        let filter_doc = filter.into();
+    ) -> Result<Vec<ServiceAccount>, AppError> {
+        let filter_doc = exclude_deleted(filter.into());
 
         // Create FindOptions
         let mut options = mongodb::options::FindOptions::default();
@@ -125,8 +243,62 @@ impl Repository<ServiceAccount> for ServiceAccountRepository {
         Ok(items)
     }
 
-    fn collection(&self) -> Result<Collection<ServiceAccount>, Error> {
-        Ok(self.collection.clone())
+    async fn count(&self, filter: Self::Filter) -> Result<u64, AppError> {
+        mongo::count(&self.collection, filter.into()).await
+    }
+
+    async fn find_page(
+        &self,
+        filter: Self::Filter,
+        sort: Option<SortBuilder<Self::Sort>>,
+        pagination: Pagination,
+    ) -> Result<Page<ServiceAccount>, AppError> {
+        let sort = sort.map(SortBuilder::to_document);
+        mongo::find_page(&self.collection, filter.into(), sort, pagination).await
+    }
+
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        mongo::soft_delete(&self.collection, id, deleted_by).await
+    }
+
+    async fn restore(&self, id: Uuid) -> Result<Option<ServiceAccount>, AppError> {
+        if !mongo::restore(&self.collection, id).await? {
+            return Ok(None);
+        }
+        self.read(id).await
+    }
+
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError> {
+        mongo::is_deleted(&self.collection, id).await
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        mongo::purge(&self.collection, deleted_before).await
+    }
+
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        mongo::distinct_ids(&self.collection, filter, transaction).await
+    }
+
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError> {
+        mongo::soft_delete_many(
+            &self.collection,
+            filter,
+            deleted_at,
+            deleted_by,
+            transaction,
+        )
+        .await
     }
 }
 
@@ -134,6 +306,7 @@ impl Repository<ServiceAccount> for ServiceAccountRepository {
 mod tests {
     use super::*;
     use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Result;
 
     async fn setup() -> (ServiceAccountRepository, Database) {
         let db = setup_test_db("service_account").await.unwrap();
@@ -198,6 +371,8 @@ mod tests {
             user: Some("newuser".to_string()),
             secret: Some("newsecret".to_string()),
             enabled: Some(false),
+            labels: None,
+            certificate_bindings: None,
         };
 
         let updated = repo.update(created.id.unwrap(), update).await?;
@@ -252,6 +427,7 @@ mod tests {
             email: None,
             user: None,
             is_enabled: None,
+            ..Default::default()
         };
         let all_accounts = repo.find(filter, None, None).await?;
         assert_eq!(all_accounts.len(), 2);
@@ -261,6 +437,7 @@ mod tests {
             email: Some("test1@example.com".to_string()),
             user: None,
             is_enabled: None,
+            ..Default::default()
         };
         let accounts = repo.find(email_filter, None, None).await?;
         assert_eq!(accounts.len(), 1);
@@ -271,6 +448,7 @@ mod tests {
             email: None,
             user: None,
             is_enabled: Some(true),
+            ..Default::default()
         };
         let enabled_accounts = repo.find(enabled_filter, None, None).await?;
         assert_eq!(enabled_accounts.len(), 2);
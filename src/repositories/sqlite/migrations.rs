@@ -0,0 +1,214 @@
+//! Schema of the SQLite database, applied in order and recorded in
+//! `_migrations` like the MongoDB [migrations](crate::migrations).
+
+use crate::errors::AppError;
+use crate::models::migration::MigrationStatus;
+use crate::repositories::sqlite::SqliteDatabase;
+use crate::serializers::timestamp;
+use chrono::{DateTime, Utc};
+use rusqlite::{OptionalExtension, TransactionBehavior, params};
+
+/// A change to the SQLite schema.
+pub struct SchemaMigration {
+    /// Unique name, which also orders the migration
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Statements run in one transaction
+    pub sql: &'static str,
+}
+
+/// Every schema migration, oldest first.
+///
+/// Records are stored as canonical extended JSON documents. The unique
+/// indexes mirror those of the `ensure_indexes` of each MongoDB repository;
+/// a missing or null field is indexed as JSON `null`, so like in MongoDB two
+/// records that both lack it collide.
+pub const MIGRATIONS: &[SchemaMigration] = &[
+    SchemaMigration {
+        name: "0001_create_tables",
+        description: "Create the record tables and their unique indexes",
+        sql: "
+        CREATE TABLE projects (id TEXT PRIMARY KEY, document TEXT NOT NULL);
+        CREATE UNIQUE INDEX projects_name ON projects (
+            ifnull(document -> '$.name', 'null'),
+            ifnull(document -> '$.deleted_at', 'null')
+        );
+
+        CREATE TABLE environments (id TEXT PRIMARY KEY, document TEXT NOT NULL);
+        CREATE UNIQUE INDEX environments_project_id_name ON environments (
+            ifnull(document -> '$.project_id', 'null'),
+            ifnull(document -> '$.name', 'null'),
+            ifnull(document -> '$.deleted_at', 'null')
+        );
+
+        CREATE TABLE project_scopes (id TEXT PRIMARY KEY, document TEXT NOT NULL);
+        CREATE UNIQUE INDEX project_scopes_project_id_name ON project_scopes (
+            ifnull(document -> '$.project_id', 'null'),
+            ifnull(document -> '$.name', 'null'),
+            ifnull(document -> '$.deleted_at', 'null')
+        );
+
+        CREATE TABLE project_access (id TEXT PRIMARY KEY, document TEXT NOT NULL);
+        CREATE UNIQUE INDEX project_access_service_account_id_environment_id ON project_access (
+            ifnull(document -> '$.service_account_id', 'null'),
+            ifnull(document -> '$.environment_id', 'null'),
+            ifnull(document -> '$.deleted_at', 'null')
+        );
+
+        CREATE TABLE server_keys (id TEXT PRIMARY KEY, document TEXT NOT NULL);
+        CREATE UNIQUE INDEX server_keys_environment_id_key ON server_keys (
+            ifnull(document -> '$.environment_id', 'null'),
+            ifnull(document -> '$.key', 'null'),
+            ifnull(document -> '$.deleted_at', 'null')
+        );
+
+        CREATE TABLE access_tokens (id TEXT PRIMARY KEY, document TEXT NOT NULL);
+
+        CREATE TABLE service_accounts (id TEXT PRIMARY KEY, document TEXT NOT NULL);
+        CREATE UNIQUE INDEX service_accounts_email ON service_accounts (
+            ifnull(document -> '$.email', 'null'),
+            ifnull(document -> '$.deleted_at', 'null')
+        );
+        CREATE UNIQUE INDEX service_accounts_user ON service_accounts (
+            ifnull(document -> '$.user', 'null'),
+            ifnull(document -> '$.deleted_at', 'null')
+        );
+
+        CREATE TABLE service_account_keys (id TEXT PRIMARY KEY, document TEXT NOT NULL);
+        CREATE UNIQUE INDEX service_account_keys_service_account_id_algorithm
+            ON service_account_keys (
+                ifnull(document -> '$.service_account_id', 'null'),
+                ifnull(document -> '$.algorithm', 'null'),
+                ifnull(document -> '$.deleted_at', 'null')
+            );
+
+        CREATE TABLE rate_limits (
+            id TEXT PRIMARY KEY,
+            count INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL
+        );
+
+        CREATE TABLE dpop_proofs (id TEXT PRIMARY KEY, expires_at INTEGER NOT NULL);
+    ",
+    },
+    SchemaMigration {
+        name: "0002_create_project_templates",
+        description: "Create the project templates table",
+        sql: "
+            CREATE TABLE project_templates (id TEXT PRIMARY KEY, document TEXT NOT NULL);
+            CREATE UNIQUE INDEX project_templates_name ON project_templates (
+                ifnull(document -> '$.name', 'null'),
+                ifnull(document -> '$.deleted_at', 'null')
+            );
+        ",
+    },
+];
+
+const CREATE_MIGRATIONS_TABLE: &str =
+    "CREATE TABLE IF NOT EXISTS _migrations (name TEXT PRIMARY KEY, applied_at TEXT NOT NULL)";
+
+impl SqliteDatabase {
+    /// Applies the schema migrations that have not been applied yet.
+    ///
+    /// Each migration runs in an exclusive transaction, so several processes
+    /// opening the same file apply it only once.
+    ///
+    /// # Returns
+    ///
+    /// The names of the migrations applied, in order.
+    #[tracing::instrument(skip_all, name = "SqliteDatabase::migrate")]
+    pub async fn migrate(&self) -> Result<Vec<String>, AppError> {
+        self.call(|connection| {
+            connection.execute(CREATE_MIGRATIONS_TABLE, [])?;
+            let mut applied = Vec::new();
+            for migration in MIGRATIONS {
+                let transaction =
+                    connection.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+                let done = transaction
+                    .query_row(
+                        "SELECT 1 FROM _migrations WHERE name = ?1",
+                        [migration.name],
+                        |_| Ok(()),
+                    )
+                    .optional()?
+                    .is_some();
+                if done {
+                    continue;
+                }
+                transaction.execute_batch(migration.sql)?;
+                transaction.execute(
+                    "INSERT INTO _migrations (name, applied_at) VALUES (?1, ?2)",
+                    params![migration.name, timestamp::format(&Utc::now())],
+                )?;
+                transaction.commit()?;
+                applied.push(migration.name.to_string());
+            }
+            Ok(applied)
+        })
+        .await
+    }
+
+    /// Lists every schema migration and when it was applied.
+    #[tracing::instrument(skip_all, name = "SqliteDatabase::migration_status")]
+    pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>, AppError> {
+        self.call(|connection| {
+            connection.execute(CREATE_MIGRATIONS_TABLE, [])?;
+            let mut statuses = Vec::new();
+            for migration in MIGRATIONS {
+                let applied_at: Option<String> = connection
+                    .query_row(
+                        "SELECT applied_at FROM _migrations WHERE name = ?1",
+                        [migration.name],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                statuses.push(MigrationStatus {
+                    name: migration.name.to_string(),
+                    description: migration.description.to_string(),
+                    applied_at: applied_at
+                        .and_then(|at| DateTime::parse_from_rfc3339(&at).ok())
+                        .map(|at| at.with_timezone(&Utc)),
+                });
+            }
+            Ok(statuses)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        let names: Vec<&str> = MIGRATIONS.iter().map(|migration| migration.name).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(names, sorted);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_once() -> Result<(), AppError> {
+        let database = SqliteDatabase::open_in_memory()?;
+        assert!(
+            database
+                .migration_status()
+                .await?
+                .iter()
+                .all(|status| status.applied_at.is_none())
+        );
+
+        assert_eq!(database.migrate().await?.len(), MIGRATIONS.len());
+        assert!(database.migrate().await?.is_empty());
+        assert!(
+            database
+                .migration_status()
+                .await?
+                .iter()
+                .all(|status| status.applied_at.is_some())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,994 @@
+//! Repositories that keep their records in a SQLite file instead of MongoDB,
+//! so Buraq can run as a single binary. Built with the `sqlite` feature.
+//!
+//! Each record is stored as the BSON document MongoDB would hold, in canonical
+//! extended JSON, and filters, sorts and pagination cursors are evaluated over
+//! those documents like the [in-memory](crate::repositories::memory)
+//! repositories do. Lookups by id use the primary key; every other query reads
+//! the whole table, which suits the few thousand records a small deployment
+//! holds. The unique indexes of `ensure_indexes` are expression indexes
+//! created by the schema [`migrations`].
+//!
+//! There are no multi-statement transactions across repositories: the
+//! `transaction` given to the `*_in` and `*_many` methods of [`Repository`]
+//! is ignored.
+
+pub mod migrations;
+
+use crate::errors::AppError;
+use crate::models::access_token::{
+    AccessToken, AccessTokenFilter, AccessTokenSortableFields, AccessTokenUpdatePayload,
+};
+use crate::models::dpop_proof::UsedDpopProof;
+use crate::models::environment::{
+    Environment, EnvironmentFilter, EnvironmentSortableFields, EnvironmentUpdatePayload,
+};
+use crate::models::pagination::{Page, Pagination};
+use crate::models::project::{Project, ProjectFilter, ProjectSortableFields, ProjectUpdatePayload};
+use crate::models::project_access::{
+    ProjectAccess, ProjectAccessFilter, ProjectAccessSortableFields, ProjectAccessUpdatePayload,
+};
+use crate::models::project_scope::{
+    ProjectScope, ProjectScopeFilter, ProjectScopeSortableFields, ProjectScopeUpdatePayload,
+};
+use crate::models::project_template::{
+    ProjectTemplate, ProjectTemplateFilter, ProjectTemplateSortableFields,
+    ProjectTemplateUpdatePayload,
+};
+use crate::models::rate_limit::{RateLimitKey, RateLimitWindow};
+use crate::models::server_key::{
+    ServerKey, ServerKeyFilter, ServerKeySortableFields, ServerKeyUpdatePayload,
+};
+use crate::models::service_account::{
+    ServiceAccount, ServiceAccountFilter, ServiceAccountSortableFields, ServiceAccountUpdatePayload,
+};
+use crate::models::service_account_key::{
+    ServiceAccountKey, ServiceAccountKeyFilter, ServiceAccountKeySortableFields,
+    ServiceAccountKeyUpdatePayload,
+};
+use crate::models::sort::SortBuilder;
+use crate::repositories::base::{self, IdFilter, Repository, exclude_deleted};
+use crate::repositories::dpop_proof_repository::DpopProofStore;
+use crate::repositories::memory::{decode, page, query, selects, slice};
+use crate::repositories::rate_limit_repository::RateLimitStore;
+use crate::repositories::service_account_repository::ServiceAccountStore;
+use crate::repositories::unit_of_work::Transaction;
+use crate::serializers::timestamp;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::bson::uuid::Uuid;
+use mongodb::bson::{Bson, Document, doc, from_bson, from_document, to_bson, to_document};
+use rusqlite::{Connection, TransactionBehavior, params, params_from_iter};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A SQLite database, shared by the repositories opened over it.
+///
+/// Clones share the connection. Statements run on Tokio's blocking thread
+/// pool, one at a time.
+#[derive(Clone, Debug)]
+pub struct SqliteDatabase {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteDatabase {
+    /// Opens the database file at `path`, creating it if needed.
+    ///
+    /// The schema is not touched; call [`migrate`](Self::migrate) before use.
+    pub fn open(path: &Path) -> Result<Self, AppError> {
+        let connection = Connection::open(path)?;
+        // Let readers proceed while another process writes, and wait for its
+        // lock rather than failing straight away
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.busy_timeout(Duration::from_secs(5))?;
+        Ok(Self::from_connection(connection))
+    }
+
+    /// Opens a database that lives in memory, for tests.
+    pub fn open_in_memory() -> Result<Self, AppError> {
+        Ok(Self::from_connection(Connection::open_in_memory()?))
+    }
+
+    fn from_connection(connection: Connection) -> Self {
+        Self {
+            connection: Arc::new(Mutex::new(connection)),
+        }
+    }
+
+    /// Runs `f` with the connection on the blocking thread pool.
+    pub async fn call<R, F>(&self, f: F) -> Result<R, AppError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<R, AppError> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().map_err(|_| {
+                AppError::Internal(anyhow::anyhow!("SQLite connection is poisoned"))
+            })?;
+            f(&mut connection)
+        })
+        .await
+        .map_err(|e| AppError::Internal(e.into()))?
+    }
+
+    /// Checks the database answers a query.
+    #[tracing::instrument(skip_all, name = "SqliteDatabase::ping")]
+    pub async fn ping(&self) -> Result<(), AppError> {
+        self.call(|connection| {
+            connection.query_row("SELECT 1", [], |_| Ok(()))?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// A [`Repository`] over one table of a [`SqliteDatabase`].
+///
+/// # Type parameters
+/// - `T`: The model
+/// - `U`, `F`, `S`: Its update payload, filter and sortable fields
+pub struct SqliteRepository<T, U, F, S> {
+    database: SqliteDatabase,
+    /// Table holding the records, created by the schema migrations
+    table: &'static str,
+    /// Name of the model in "not found" errors
+    entity: &'static str,
+    /// Whether `created_at` and `updated_at` are maintained
+    timestamps: bool,
+    #[allow(clippy::type_complexity)]
+    marker: PhantomData<fn() -> (T, U, F, S)>,
+}
+
+fn encode(document: Document) -> String {
+    Bson::Document(document)
+        .into_canonical_extjson()
+        .to_string()
+}
+
+fn decode_row(text: &str) -> Result<Document, AppError> {
+    let value: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| AppError::Internal(e.into()))?;
+    match Bson::try_from(value) {
+        Ok(Bson::Document(document)) => Ok(document),
+        Ok(other) => Err(AppError::Internal(anyhow::anyhow!(
+            "Expected a stored document, found {}",
+            other
+        ))),
+        Err(e) => Err(AppError::Internal(e.into())),
+    }
+}
+
+/// Primary key of a record: its `_id`, hyphenated.
+fn record_id(record: &Document) -> Result<String, AppError> {
+    let id = record
+        .get("_id")
+        .cloned()
+        .ok_or_else(|| AppError::Internal(anyhow::anyhow!("Record has no _id")))?;
+    Ok(from_bson::<Uuid>(id)?.to_string())
+}
+
+/// The records of `table` matching `filter`, only looking at the record with
+/// primary key `id` when given.
+fn matching(
+    connection: &Connection,
+    table: &str,
+    id: Option<&str>,
+    filter: &Document,
+) -> Result<Vec<Document>, AppError> {
+    let sql = match id {
+        Some(_) => format!("SELECT document FROM {} WHERE id = ?1", table),
+        None => format!("SELECT document FROM {}", table),
+    };
+    let mut statement = connection.prepare_cached(&sql)?;
+    let rows = statement.query_map(params_from_iter(id), |row| row.get::<_, String>(0))?;
+    let mut selected = Vec::new();
+    for row in rows {
+        let record = decode_row(&row?)?;
+        if query::matches(&record, filter)? {
+            selected.push(record);
+        }
+    }
+    Ok(selected)
+}
+
+/// Applies `change` to the records of `table` matching `filter`, or only to
+/// the first of them when `first` is set, in one transaction.
+///
+/// # Returns
+///
+/// Every record matched, after the change, and how many were modified.
+fn rewrite(
+    connection: &mut Connection,
+    table: &str,
+    id: Option<&str>,
+    filter: &Document,
+    first: bool,
+    change: impl Fn(&mut Document) -> Result<(), AppError>,
+) -> Result<(Vec<Document>, u64), AppError> {
+    let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut records = matching(&transaction, table, id, filter)?;
+    if first {
+        records.truncate(1);
+    }
+    let mut modified = 0;
+    for record in records.iter_mut() {
+        let before = record.clone();
+        change(record)?;
+        if *record == before {
+            continue;
+        }
+        transaction.execute(
+            &format!("UPDATE {} SET document = ?1 WHERE id = ?2", table),
+            params![encode(record.clone()), record_id(record)?],
+        )?;
+        modified += 1;
+    }
+    transaction.commit()?;
+    Ok((records, modified))
+}
+
+impl<T, U, F, S> SqliteRepository<T, U, F, S> {
+    fn new(
+        database: &SqliteDatabase,
+        table: &'static str,
+        entity: &'static str,
+        timestamps: bool,
+    ) -> Self {
+        Self {
+            database: database.clone(),
+            table,
+            entity,
+            timestamps,
+            marker: PhantomData,
+        }
+    }
+
+    /// The records matching `filter`, only looking at the record with id `id`
+    /// when given.
+    async fn select(&self, id: Option<Uuid>, filter: Document) -> Result<Vec<Document>, AppError> {
+        let table = self.table;
+        let id = id.map(|id| id.to_string());
+        self.database
+            .call(move |connection| matching(connection, table, id.as_deref(), &filter))
+            .await
+    }
+
+    /// Applies `change` to the live record with id `id`.
+    ///
+    /// # Returns
+    ///
+    /// The changed record, or `None` if there is no such record.
+    async fn modify(
+        &self,
+        id: Uuid,
+        filter: Document,
+        change: impl Fn(&mut Document) -> Result<(), AppError> + Send + 'static,
+    ) -> Result<Option<Document>, AppError> {
+        let table = self.table;
+        let key = id.to_string();
+        let (mut records, _) = self
+            .database
+            .call(move |connection| rewrite(connection, table, Some(&key), &filter, true, change))
+            .await?;
+        Ok(records.pop())
+    }
+}
+
+#[async_trait]
+impl<T, U, F, S> Repository<T> for SqliteRepository<T, U, F, S>
+where
+    T: Send + Sync + Serialize + DeserializeOwned + 'static,
+    U: Send + Sync + Serialize + DeserializeOwned + 'static,
+    F: Send + Sync + Serialize + DeserializeOwned + Into<Document> + 'static,
+    S: Send + Sync + Serialize + DeserializeOwned + Into<String> + Clone + 'static,
+{
+    type UpdatePayload = U;
+    type Filter = F;
+    type Sort = S;
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::create")]
+    async fn create_in(
+        &self,
+        item: T,
+        _transaction: Option<&mut Transaction>,
+    ) -> Result<T, AppError> {
+        let mut record = to_document(&item)?;
+        if matches!(record.get("_id"), None | Some(Bson::Null)) {
+            record.insert("_id", to_bson(&Uuid::new())?);
+        }
+        record.remove("deleted_at");
+        record.remove("deleted_by");
+        if self.timestamps {
+            record.insert("created_at", timestamp::to_bson(&Utc::now()));
+            record.insert("updated_at", timestamp::to_bson(&Utc::now()));
+        }
+        let table = self.table;
+        let id = record_id(&record)?;
+        let stored = encode(record.clone());
+        self.database
+            .call(move |connection| {
+                connection.execute(
+                    &format!("INSERT INTO {} (id, document) VALUES (?1, ?2)", table),
+                    params![id, stored],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(from_document(record)?)
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::read")]
+    async fn read(&self, id: Uuid) -> Result<Option<T>, AppError> {
+        let found = self
+            .select(Some(id), exclude_deleted(doc! { "_id": id }))
+            .await?;
+        Ok(decode(found)?.pop())
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::update")]
+    async fn update(&self, id: Uuid, payload: U) -> Result<T, AppError> {
+        let mut changes = to_document(&payload)?;
+        if self.timestamps {
+            changes.insert("updated_at", timestamp::to_bson(&Utc::now()));
+        }
+        let updated = self
+            .modify(id, exclude_deleted(doc! { "_id": id }), move |record| {
+                for (path, value) in changes.clone() {
+                    query::set(record, &path, value);
+                }
+                Ok(())
+            })
+            .await?;
+        match updated {
+            Some(record) => Ok(from_document(record)?),
+            None => Err(AppError::NotFound(format!("{} not found", self.entity))),
+        }
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::delete")]
+    async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
+        let table = self.table;
+        let deleted = self
+            .database
+            .call(move |connection| {
+                let deleted = connection.execute(
+                    &format!("DELETE FROM {} WHERE id = ?1", table),
+                    [id.to_string()],
+                )?;
+                Ok(deleted)
+            })
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::find")]
+    async fn find(
+        &self,
+        filter: F,
+        sort: Option<SortBuilder<S>>,
+        pagination: Option<Pagination>,
+    ) -> Result<Vec<T>, AppError> {
+        let sort = sort.map(SortBuilder::to_document).unwrap_or_default();
+        let (skip, limit) = match pagination {
+            Some(pagination) => (pagination.skip(), Some(pagination.limit())),
+            None => (0, None),
+        };
+        let matching = self.select(None, exclude_deleted(filter.into())).await?;
+        decode(slice(matching, &sort, skip, limit))
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::count")]
+    async fn count(&self, filter: F) -> Result<u64, AppError> {
+        let matching = self.select(None, exclude_deleted(filter.into())).await?;
+        Ok(matching.len() as u64)
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::find_page")]
+    async fn find_page(
+        &self,
+        filter: F,
+        sort: Option<SortBuilder<S>>,
+        pagination: Pagination,
+    ) -> Result<Page<T>, AppError> {
+        let matching = self.select(None, exclude_deleted(filter.into())).await?;
+        page(matching, sort.map(SortBuilder::to_document), pagination)
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::soft_delete")]
+    async fn soft_delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        let changes =
+            doc! { "deleted_at": timestamp::to_bson(&Utc::now()), "deleted_by": deleted_by };
+        let deleted = self
+            .modify(id, exclude_deleted(doc! { "_id": id }), move |record| {
+                record.extend(changes.clone());
+                Ok(())
+            })
+            .await?;
+        Ok(deleted.is_some())
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::restore")]
+    async fn restore(&self, id: Uuid) -> Result<Option<T>, AppError> {
+        let restored = self
+            .modify(
+                id,
+                doc! { "_id": id, "deleted_at": { "$ne": Bson::Null } },
+                |record| {
+                    record.remove("deleted_at");
+                    record.remove("deleted_by");
+                    Ok(())
+                },
+            )
+            .await?;
+        match restored {
+            Some(_) => self.read(id).await,
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::is_deleted")]
+    async fn is_deleted(&self, id: Uuid) -> Result<bool, AppError> {
+        let filter = doc! { "_id": id, "deleted_at": { "$ne": Bson::Null } };
+        Ok(!self.select(Some(id), filter).await?.is_empty())
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::purge")]
+    async fn purge(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        let table = self.table;
+        let filter = base::deleted_before(deleted_before)?;
+        self.database
+            .call(move |connection| {
+                let transaction =
+                    connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let expired = matching(&transaction, table, None, &filter)?;
+                let mut purged = 0;
+                for record in expired {
+                    purged += transaction.execute(
+                        &format!("DELETE FROM {} WHERE id = ?1", table),
+                        [record_id(&record)?],
+                    )? as u64;
+                }
+                transaction.commit()?;
+                Ok(purged)
+            })
+            .await
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::distinct_ids")]
+    async fn distinct_ids(
+        &self,
+        filter: IdFilter,
+        _transaction: Option<&mut Transaction>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        self.select(None, doc! {})
+            .await?
+            .into_iter()
+            .filter(|record| selects(&filter, record))
+            .filter_map(|record| record.get("_id").cloned())
+            .map(|id| from_bson::<Uuid>(id).map_err(AppError::from))
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteRepository::soft_delete_many")]
+    async fn soft_delete_many(
+        &self,
+        filter: IdFilter,
+        deleted_at: DateTime<Utc>,
+        deleted_by: Option<String>,
+        _transaction: Option<&mut Transaction>,
+    ) -> Result<u64, AppError> {
+        let table = self.table;
+        let changes =
+            doc! { "deleted_at": timestamp::to_bson(&deleted_at), "deleted_by": deleted_by };
+        let (_, modified) = self
+            .database
+            .call(move |connection| {
+                rewrite(connection, table, None, &doc! {}, false, |record| {
+                    if selects(&filter, record) {
+                        record.extend(changes.clone());
+                    }
+                    Ok(())
+                })
+            })
+            .await?;
+        Ok(modified)
+    }
+}
+
+pub type SqliteProjectRepository =
+    SqliteRepository<Project, ProjectUpdatePayload, ProjectFilter, ProjectSortableFields>;
+pub type SqliteEnvironmentRepository = SqliteRepository<
+    Environment,
+    EnvironmentUpdatePayload,
+    EnvironmentFilter,
+    EnvironmentSortableFields,
+>;
+pub type SqliteProjectScopeRepository = SqliteRepository<
+    ProjectScope,
+    ProjectScopeUpdatePayload,
+    ProjectScopeFilter,
+    ProjectScopeSortableFields,
+>;
+pub type SqliteProjectTemplateRepository = SqliteRepository<
+    ProjectTemplate,
+    ProjectTemplateUpdatePayload,
+    ProjectTemplateFilter,
+    ProjectTemplateSortableFields,
+>;
+pub type SqliteProjectAccessRepository = SqliteRepository<
+    ProjectAccess,
+    ProjectAccessUpdatePayload,
+    ProjectAccessFilter,
+    ProjectAccessSortableFields,
+>;
+pub type SqliteServerKeyRepository =
+    SqliteRepository<ServerKey, ServerKeyUpdatePayload, ServerKeyFilter, ServerKeySortableFields>;
+pub type SqliteAccessTokenRepository = SqliteRepository<
+    AccessToken,
+    AccessTokenUpdatePayload,
+    AccessTokenFilter,
+    AccessTokenSortableFields,
+>;
+pub type SqliteServiceAccountRepository = SqliteRepository<
+    ServiceAccount,
+    ServiceAccountUpdatePayload,
+    ServiceAccountFilter,
+    ServiceAccountSortableFields,
+>;
+pub type SqliteServiceAccountKeyRepository = SqliteRepository<
+    ServiceAccountKey,
+    ServiceAccountKeyUpdatePayload,
+    ServiceAccountKeyFilter,
+    ServiceAccountKeySortableFields,
+>;
+
+impl SqliteProjectRepository {
+    pub fn projects(database: &SqliteDatabase) -> Self {
+        Self::new(database, "projects", "Project", true)
+    }
+}
+
+impl SqliteEnvironmentRepository {
+    pub fn environments(database: &SqliteDatabase) -> Self {
+        Self::new(database, "environments", "Environment", true)
+    }
+}
+
+impl SqliteProjectScopeRepository {
+    pub fn project_scopes(database: &SqliteDatabase) -> Self {
+        Self::new(database, "project_scopes", "Project scope", true)
+    }
+}
+
+impl SqliteProjectTemplateRepository {
+    pub fn project_templates(database: &SqliteDatabase) -> Self {
+        Self::new(database, "project_templates", "Project template", true)
+    }
+}
+
+impl SqliteProjectAccessRepository {
+    pub fn project_access(database: &SqliteDatabase) -> Self {
+        Self::new(database, "project_access", "ProjectAccess", true)
+    }
+}
+
+impl SqliteServerKeyRepository {
+    pub fn server_keys(database: &SqliteDatabase) -> Self {
+        Self::new(database, "server_keys", "ServerKey", true)
+    }
+}
+
+impl SqliteAccessTokenRepository {
+    pub fn access_tokens(database: &SqliteDatabase) -> Self {
+        Self::new(database, "access_tokens", "AccessToken", false)
+    }
+}
+
+impl SqliteServiceAccountRepository {
+    pub fn service_accounts(database: &SqliteDatabase) -> Self {
+        Self::new(database, "service_accounts", "ServiceAccount", true)
+    }
+}
+
+impl SqliteServiceAccountKeyRepository {
+    pub fn service_account_keys(database: &SqliteDatabase) -> Self {
+        Self::new(database, "service_account_keys", "ServiceAccountKey", true)
+    }
+}
+
+#[async_trait]
+impl ServiceAccountStore for SqliteServiceAccountRepository {
+    #[tracing::instrument(
+        skip_all,
+        name = "SqliteServiceAccountRepository::record_failed_authentication"
+    )]
+    async fn record_failed_authentication(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<ServiceAccount>, AppError> {
+        let account = self
+            .modify(id, exclude_deleted(doc! { "_id": id }), |record| {
+                let failed = match record.get("failed_authentications") {
+                    Some(Bson::Int32(count)) => *count as i64,
+                    Some(Bson::Int64(count)) => *count,
+                    _ => 0,
+                };
+                record.insert("failed_authentications", failed + 1);
+                Ok(())
+            })
+            .await?;
+        account
+            .map(|account| from_document(account).map_err(AppError::from))
+            .transpose()
+    }
+
+    #[tracing::instrument(skip_all, name = "SqliteServiceAccountRepository::lock")]
+    async fn lock(&self, id: Uuid, until: DateTime<Utc>) -> Result<(), AppError> {
+        let locked_until = timestamp::to_bson(&until);
+        self.modify(id, exclude_deleted(doc! { "_id": id }), move |record| {
+            record.insert("locked_until", locked_until.clone());
+            Ok(())
+        })
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        name = "SqliteServiceAccountRepository::reset_failed_authentications"
+    )]
+    async fn reset_failed_authentications(&self, id: Uuid) -> Result<(), AppError> {
+        self.modify(id, exclude_deleted(doc! { "_id": id }), |record| {
+            record.insert("failed_authentications", 0);
+            record.remove("locked_until");
+            Ok(())
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+/// Rate limit windows stored in SQLite, shared by every process using the file.
+pub struct SqliteRateLimitStore {
+    database: SqliteDatabase,
+}
+
+impl SqliteRateLimitStore {
+    pub fn new(database: &SqliteDatabase) -> Self {
+        Self {
+            database: database.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for SqliteRateLimitStore {
+    #[tracing::instrument(skip_all, name = "SqliteRateLimitStore::hit")]
+    async fn hit(
+        &self,
+        key: &RateLimitKey,
+        window_seconds: u64,
+        now: DateTime<Utc>,
+    ) -> Result<RateLimitWindow, AppError> {
+        let start = RateLimitWindow::start(now, window_seconds);
+        let id = RateLimitWindow::id_for(key, start);
+        let expires_at = (start + window_seconds.max(1) as i64) * 1000;
+        let now = now.timestamp_millis();
+        self.database
+            .call(move |connection| {
+                // Expired windows are dropped, as the TTL index does
+                connection.execute("DELETE FROM rate_limits WHERE expires_at <= ?1", [now])?;
+                let (count, expires_at) = connection.query_row(
+                    "INSERT INTO rate_limits (id, count, expires_at) VALUES (?1, 1, ?2)
+                     ON CONFLICT (id) DO UPDATE SET count = count + 1
+                     RETURNING count, expires_at",
+                    params![id, expires_at],
+                    |row| Ok((row.get::<_, u32>(0)?, row.get::<_, i64>(1)?)),
+                )?;
+                Ok(RateLimitWindow {
+                    id,
+                    count,
+                    expires_at: mongodb::bson::DateTime::from_millis(expires_at),
+                })
+            })
+            .await
+    }
+}
+
+/// Used DPoP proofs stored in SQLite, so replays are detected across every
+/// process using the file.
+pub struct SqliteDpopProofStore {
+    database: SqliteDatabase,
+}
+
+impl SqliteDpopProofStore {
+    pub fn new(database: &SqliteDatabase) -> Self {
+        Self {
+            database: database.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl DpopProofStore for SqliteDpopProofStore {
+    #[tracing::instrument(skip_all, name = "SqliteDpopProofStore::record")]
+    async fn record(&self, proof: &UsedDpopProof) -> Result<bool, AppError> {
+        let id = proof.id.clone();
+        let expires_at = proof.expires_at.timestamp_millis();
+        self.database
+            .call(move |connection| {
+                // Stale proofs are dropped, as the TTL index does
+                connection.execute(
+                    "DELETE FROM dpop_proofs WHERE expires_at <= ?1",
+                    [Utc::now().timestamp_millis()],
+                )?;
+                let inserted = connection.execute(
+                    "INSERT OR IGNORE INTO dpop_proofs (id, expires_at) VALUES (?1, ?2)",
+                    params![id, expires_at],
+                )?;
+                Ok(inserted == 1)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::label::Labels;
+    use crate::models::sort::SortDirection;
+    use chrono::Duration;
+    use rusqlite::OptionalExtension;
+
+    /// Returns whether the record with id `id` is stored in `table`, deleted or not.
+    async fn stored(
+        database: &SqliteDatabase,
+        table: &'static str,
+        id: Uuid,
+    ) -> Result<bool, AppError> {
+        database
+            .call(move |connection| {
+                let found = connection
+                    .query_row(
+                        &format!("SELECT 1 FROM {} WHERE id = ?1", table),
+                        [id.to_string()],
+                        |_| Ok(()),
+                    )
+                    .optional()?;
+                Ok(found.is_some())
+            })
+            .await
+    }
+
+    async fn database() -> SqliteDatabase {
+        let database = SqliteDatabase::open_in_memory().unwrap();
+        database.migrate().await.unwrap();
+        database
+    }
+
+    fn project(name: &str) -> Project {
+        Project {
+            id: None,
+            name: name.to_string(),
+            description: String::new(),
+            enabled: true,
+            labels: Labels::default(),
+            created_at: None,
+            updated_at: None,
+            deleted_at: None,
+            deleted_by: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_crud_and_unique_index() -> Result<(), AppError> {
+        let repo = SqliteProjectRepository::projects(&database().await);
+        let created = repo.create(project("billing")).await?;
+        let id = created.id.unwrap();
+        assert!(created.created_at.is_some());
+        assert_eq!(repo.read(id).await?.unwrap().name, "billing");
+
+        assert!(matches!(
+            repo.create(project("billing")).await,
+            Err(AppError::Conflict(_))
+        ));
+
+        let other = repo.create(project("payments")).await?.id.unwrap();
+        let renamed = ProjectUpdatePayload {
+            name: Some("billing".to_string()),
+            description: None,
+            enabled: None,
+            labels: None,
+        };
+        assert!(matches!(
+            repo.update(other, renamed).await,
+            Err(AppError::Conflict(_))
+        ));
+
+        let disabled = ProjectUpdatePayload {
+            name: None,
+            description: Some("Invoices".to_string()),
+            enabled: Some(false),
+            labels: None,
+        };
+        let updated = repo.update(id, disabled).await?;
+        assert_eq!(updated.name, "billing");
+        assert_eq!(updated.description, "Invoices");
+        assert!(!updated.enabled);
+
+        assert!(repo.delete(id).await?);
+        assert!(!repo.delete(id).await?);
+        assert!(repo.read(id).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_soft_delete_restore_and_purge() -> Result<(), AppError> {
+        let database = database().await;
+        let repo = SqliteProjectRepository::projects(&database);
+        let id = repo.create(project("billing")).await?.id.unwrap();
+
+        assert!(repo.soft_delete(id, Some("alice".to_string())).await?);
+        assert!(!repo.soft_delete(id, None).await?);
+        assert!(repo.read(id).await?.is_none());
+        assert!(repo.is_deleted(id).await?);
+        // The name is free again while the record is deleted
+        let replacement = repo.create(project("billing")).await?.id.unwrap();
+
+        assert!(matches!(repo.restore(id).await, Err(AppError::Conflict(_))));
+        repo.delete(replacement).await?;
+        assert_eq!(repo.restore(id).await?.unwrap().name, "billing");
+        assert!(repo.restore(id).await?.is_none());
+
+        repo.soft_delete(id, None).await?;
+        assert_eq!(repo.purge(Utc::now() - Duration::days(1)).await?, 0);
+        assert_eq!(repo.purge(Utc::now() + Duration::seconds(1)).await?, 1);
+        assert!(!stored(&database, "projects", id).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_and_pages() -> Result<(), AppError> {
+        let repo = SqliteProjectRepository::projects(&database().await);
+        for name in ["c", "a", "b"] {
+            repo.create(project(name)).await?;
+        }
+        let disabled = repo.create(project("d")).await?.id.unwrap();
+        let update = ProjectUpdatePayload {
+            name: None,
+            description: None,
+            enabled: Some(false),
+            labels: None,
+        };
+        repo.update(disabled, update).await?;
+
+        let enabled = ProjectFilter {
+            is_enabled: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(repo.count(enabled).await?, 3);
+
+        let by_name =
+            || SortBuilder::new().add_sort(ProjectSortableFields::Name, SortDirection::Ascending);
+        let first = repo
+            .find_page(
+                ProjectFilter::default(),
+                Some(by_name()),
+                Pagination {
+                    page: None,
+                    limit: Some(3),
+                    cursor: None,
+                },
+            )
+            .await?;
+        let names: Vec<&str> = first.items.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(first.total, 4);
+
+        let second = repo
+            .find_page(
+                ProjectFilter::default(),
+                Some(by_name()),
+                Pagination {
+                    page: None,
+                    limit: Some(3),
+                    cursor: first.next_cursor,
+                },
+            )
+            .await?;
+        let names: Vec<&str> = second.items.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["d"]);
+        assert!(second.next_cursor.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_service_account_lockout() -> Result<(), AppError> {
+        let repo = SqliteServiceAccountRepository::service_accounts(&database().await);
+        let account = repo
+            .create(ServiceAccount::new(
+                "billing@example.com".to_string(),
+                "billing".to_string(),
+                "secret".to_string(),
+            ))
+            .await?;
+        let id = account.id.unwrap();
+
+        repo.record_failed_authentication(id).await?;
+        let failed = repo.record_failed_authentication(id).await?.unwrap();
+        assert_eq!(failed.failed_authentications, 2);
+
+        repo.lock(id, Utc::now() + Duration::minutes(5)).await?;
+        assert!(repo.read(id).await?.unwrap().locked_until.is_some());
+
+        repo.reset_failed_authentications(id).await?;
+        let reset = repo.read(id).await?.unwrap();
+        assert_eq!(reset.failed_authentications, 0);
+        assert!(reset.locked_until.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_and_dpop_proofs() -> Result<(), AppError> {
+        let database = database().await;
+        let rate_limits = SqliteRateLimitStore::new(&database);
+        let key = RateLimitKey::Client("ci".to_string());
+        let now = Utc::now();
+        assert_eq!(rate_limits.hit(&key, 60, now).await?.count, 1);
+        assert_eq!(rate_limits.hit(&key, 60, now).await?.count, 2);
+        assert_eq!(
+            rate_limits
+                .hit(&key, 60, now + Duration::seconds(60))
+                .await?
+                .count,
+            1
+        );
+
+        let proofs = SqliteDpopProofStore::new(&database);
+        let expires_at = Utc::now() + Duration::seconds(60);
+        assert!(
+            proofs
+                .record(&UsedDpopProof::new("key", "1", expires_at))
+                .await?
+        );
+        assert!(
+            !proofs
+                .record(&UsedDpopProof::new("key", "1", expires_at))
+                .await?
+        );
+        assert!(
+            proofs
+                .record(&UsedDpopProof::new("key", "2", expires_at))
+                .await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_records_survive_reopening() -> Result<(), AppError> {
+        let path = std::env::temp_dir().join(format!("buraq-{}.sqlite", Uuid::new()));
+        let id = {
+            let database = SqliteDatabase::open(&path)?;
+            database.migrate().await?;
+            let repo = SqliteProjectRepository::projects(&database);
+            repo.create(project("billing")).await?.id.unwrap()
+        };
+
+        let database = SqliteDatabase::open(&path)?;
+        assert!(database.migrate().await?.is_empty());
+        let repo = SqliteProjectRepository::projects(&database);
+        assert_eq!(repo.read(id).await?.unwrap().name, "billing");
+
+        drop(repo);
+        drop(database);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+        Ok(())
+    }
+}
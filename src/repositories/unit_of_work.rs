@@ -0,0 +1,180 @@
+use crate::errors::AppError;
+use crate::repositories::registry::Repositories;
+use mongodb::error::{TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT};
+use mongodb::{ClientSession, Database};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long a unit of work keeps retrying, the limit the MongoDB drivers use
+/// for their own transaction helpers.
+const RETRY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Writes to several collections that commit or roll back together.
+///
+/// [`UnitOfWork::run`] hands its work the [`Transaction`] every write must run
+/// in; repositories take it through their `*_in` and `*_many` methods. Over
+/// MongoDB the work runs in a transaction. Repositories of other storage have
+/// no transaction to give: the work then runs once, without one.
+///
+/// # Fields
+/// - `database`: The database to start sessions on; `None` when the
+///   repositories are not backed by MongoDB
+#[derive(Clone, Debug)]
+pub struct UnitOfWork {
+    database: Option<Arc<Database>>,
+}
+
+/// The transaction a [`UnitOfWork`] runs its work in.
+///
+/// Only the MongoDB repositories look inside; other storage ignores it.
+pub struct Transaction {
+    session: ClientSession,
+}
+
+impl Transaction {
+    /// The MongoDB session the transaction runs in.
+    pub(crate) fn session(&mut self) -> &mut ClientSession {
+        &mut self.session
+    }
+}
+
+impl UnitOfWork {
+    pub fn new(repositories: &Repositories) -> Self {
+        Self {
+            database: repositories.database.clone(),
+        }
+    }
+
+    /// Runs `work` in a transaction and commits it, or aborts it when `work` fails.
+    ///
+    /// On a transient error, such as a write conflict with another
+    /// transaction or a lost connection, the transaction is aborted and `work`
+    /// run again from the start, so it must not have effects outside the
+    /// transaction. A commit whose outcome is unknown is retried on its own.
+    ///
+    /// # Returns
+    ///
+    /// What `work` returned once committed, or its error.
+    #[tracing::instrument(skip_all, name = "UnitOfWork::run")]
+    pub async fn run<T, F>(&self, mut work: F) -> Result<T, AppError>
+    where
+        F: AsyncFnMut(Option<&mut Transaction>) -> Result<T, AppError>,
+    {
+        let Some(database) = &self.database else {
+            return work(None).await;
+        };
+        let mut transaction = Transaction {
+            session: database.client().start_session().await?,
+        };
+        let started = Instant::now();
+
+        'transaction: loop {
+            transaction.session.start_transaction().await?;
+            let value = match work(Some(&mut transaction)).await {
+                Ok(value) => value,
+                Err(e) => {
+                    if let Err(abort) = transaction.session.abort_transaction().await {
+                        tracing::warn!(error = ?abort, "Error aborting transaction");
+                    }
+                    if is_transient(&e) && started.elapsed() < RETRY_TIMEOUT {
+                        tracing::warn!(error = %e, "Retrying transaction after a transient error");
+                        continue 'transaction;
+                    }
+                    return Err(e);
+                }
+            };
+
+            loop {
+                match transaction.session.commit_transaction().await {
+                    Ok(()) => return Ok(value),
+                    Err(e) if started.elapsed() >= RETRY_TIMEOUT => return Err(e.into()),
+                    Err(e) if e.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT) => continue,
+                    Err(e) if e.contains_label(TRANSIENT_TRANSACTION_ERROR) => {
+                        tracing::warn!(error = %e, "Retrying transaction after a transient error");
+                        continue 'transaction;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+    }
+}
+
+/// Whether a failed transaction may succeed when run again.
+///
+/// Connection failures reach services as [`AppError::Unavailable`] without
+/// their labels, but inside a transaction the driver always labels them
+/// transient.
+fn is_transient(error: &AppError) -> bool {
+    match error {
+        AppError::Unavailable(_) => true,
+        AppError::Internal(e) => e
+            .downcast_ref::<mongodb::error::Error>()
+            .is_some_and(|e| e.contains_label(TRANSIENT_TRANSACTION_ERROR)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::project::Project;
+
+    fn project(name: &str) -> Project {
+        Project {
+            id: None,
+            name: name.to_string(),
+            description: String::new(),
+            enabled: true,
+            created_at: None,
+            updated_at: None,
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let unlabelled = mongodb::error::Error::custom("write conflict");
+        assert!(!is_transient(&AppError::from(unlabelled)));
+        assert!(is_transient(&AppError::Unavailable(
+            "Database is unavailable".to_string()
+        )));
+        assert!(!is_transient(&AppError::Conflict(
+            "A record with the same unique fields already exists".to_string()
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_run_without_database() -> Result<(), AppError> {
+        let repositories = Repositories::in_memory();
+        let unit_of_work = UnitOfWork::new(&repositories);
+
+        let mut runs = 0;
+        let created = unit_of_work
+            .run(async |transaction| {
+                runs += 1;
+                assert!(transaction.is_none());
+                repositories
+                    .projects
+                    .create_in(project("billing"), transaction)
+                    .await
+            })
+            .await?;
+        assert_eq!(runs, 1);
+        assert!(
+            repositories
+                .projects
+                .read(created.id.unwrap())
+                .await?
+                .is_some()
+        );
+
+        let result: Result<(), AppError> = unit_of_work
+            .run(async |_| Err(AppError::Validation("invalid".to_string())))
+            .await;
+        assert!(matches!(result, Err(AppError::Validation(_))));
+        Ok(())
+    }
+}
@@ -1,162 +1,211 @@
 use crate::config::AppData;
+use crate::errors::{AppError, Problem};
 use crate::models::access_token::{
     AccessToken, AccessTokenCreatePayload, AccessTokenFilter, AccessTokenRead,
     AccessTokenSortableFields, AccessTokenUpdatePayload,
 };
-use crate::models::pagination::Pagination;
-use crate::models::sort::{SortBuilder, SortDirection};
+use crate::models::pagination::{Page, Pagination};
+use crate::models::sort::{SortBuilder, SortDirection, SortQuery};
+use crate::routes::actor;
+use crate::routes::page_response;
+use crate::routes::parse_id;
 use crate::services::access_token_service::AccessTokenService;
 use crate::utils::tokens::key_builder::KeyBuilder;
 use chrono::Utc;
-use mongodb::bson::uuid::Uuid;
-
-use actix_web::{Error, HttpResponse, web};
 
+use actix_web::{HttpRequest, HttpResponse, web};
+
+#[utoipa::path(
+    post,
+    path = "/access-tokens",
+    tag = "access-tokens",
+    request_body = AccessTokenCreatePayload,
+    responses(
+        (status = 200, description = "Access token created", body = AccessTokenRead),
+        (status = 400, description = "Invalid access token", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "access_token::create")]
 pub async fn create(
     data: web::Data<AppData>,
     payload: web::Json<AccessTokenCreatePayload>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
+) -> Result<HttpResponse, AppError> {
+    let service = AccessTokenService::with_repositories(&data.repositories()?)?;
+    let defaults = data
+        .config
         .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = AccessTokenService::new(database.clone()).unwrap();
-    let private_key = KeyBuilder::new()
-        .generate_key(payload.algorithm)
-        .unwrap()
-        .private_key;
+        .map(|config| config.application.tokens.clone())
+        .unwrap_or_default();
+    let now = Utc::now();
+    let algorithm = payload.algorithm.unwrap_or(defaults.default_algorithm);
+    let expires_at = defaults.expires_at(payload.expires_at, now)?;
+    let private_key = KeyBuilder::new().generate_key(algorithm)?.private_key;
     let access_token = AccessToken {
         id: None,
         project_access_id: payload.project_access_id,
-        key: String::from_utf8(private_key).unwrap(),
-        algorithm: payload.algorithm,
-        expires_at: payload.expires_at,
-        created_at: Utc::now(),
+        key: String::from_utf8(private_key).map_err(anyhow::Error::from)?,
+        algorithm,
+        expires_at,
+        created_at: now,
         enabled: true,
+        deleted_at: None,
+        deleted_by: None,
     };
-    let access_token = service.create(access_token).await;
-
-    match access_token {
-        Ok(access_token) => Ok(HttpResponse::Ok().json(AccessTokenRead::from(access_token))),
-        Err(e) => {
-            println!("Error creating Access Token: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+    let access_token = service.create(access_token).await?;
+
+    Ok(HttpResponse::Ok().json(AccessTokenRead::from(access_token)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/access-tokens/{id}",
+    tag = "access-tokens",
+    params(("id" = String, Path, description = "Access token id")),
+    responses(
+        (status = 200, description = "Access token found", body = AccessTokenRead),
+        (status = 404, description = "Access token not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "access_token::read")]
 pub async fn read(
     data: web::Data<AppData>,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = AccessTokenService::new(database.clone()).unwrap();
-    let access_token_id = Uuid::parse_str(path.into_inner()).unwrap();
+) -> Result<HttpResponse, AppError> {
+    let service = AccessTokenService::with_repositories(&data.repositories()?)?;
+    let access_token_id = parse_id(&path.into_inner())?;
     let access_token = service.get_access_token(access_token_id).await;
 
-    match access_token {
-        Ok(Some(access_token)) => Ok(HttpResponse::Ok().json(AccessTokenRead::from(access_token))),
-        Ok(None) => Ok(HttpResponse::NotFound().finish()),
-        Err(e) => {
-            println!("Error getting Access Token: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+    match access_token? {
+        Some(access_token) => Ok(HttpResponse::Ok().json(AccessTokenRead::from(access_token))),
+        None => Err(AppError::NotFound("Access token not found".to_string())),
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/access-tokens/{id}",
+    tag = "access-tokens",
+    params(("id" = String, Path, description = "Access token id")),
+    request_body = AccessTokenUpdatePayload,
+    responses(
+        (status = 200, description = "Access token updated", body = AccessTokenRead),
+        (status = 400, description = "Invalid access token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Access token not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "access_token::update")]
 pub async fn update(
     data: web::Data<AppData>,
     path: web::Path<String>,
     payload: web::Json<AccessTokenUpdatePayload>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = AccessTokenService::new(database.clone()).unwrap();
-    let access_token_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let access_token = service.update(access_token_id, payload.into_inner()).await;
-
-    match access_token {
-        Ok(access_token) => Ok(HttpResponse::Ok().json(AccessTokenRead::from(access_token))),
-        Err(e) => {
-            println!("Error updating AccessToken: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = AccessTokenService::with_repositories(&data.repositories()?)?;
+    let access_token_id = parse_id(&path.into_inner())?;
+
+    let access_token = service
+        .update(access_token_id, payload.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(AccessTokenRead::from(access_token)))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/access-tokens/{id}",
+    tag = "access-tokens",
+    params(("id" = String, Path, description = "Access token id")),
+    responses(
+        (status = 204, description = "Access token deleted"),
+        (status = 404, description = "Access token not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "access_token::delete")]
 pub async fn delete(
     data: web::Data<AppData>,
+    req: HttpRequest,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = AccessTokenService::new(database.clone()).unwrap();
-    let access_token_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let result = service.delete(access_token_id).await;
-
-    match result {
-        Ok(deleted) => {
-            if deleted {
-                Ok(HttpResponse::NoContent().finish())
-            } else {
-                Ok(HttpResponse::NotFound().finish())
-            }
-        }
-        Err(e) => {
-            println!("Error deleting Acess Token: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+) -> Result<HttpResponse, AppError> {
+    let service = AccessTokenService::with_repositories(&data.repositories()?)?;
+    let access_token_id = parse_id(&path.into_inner())?;
+
+    let deleted = service.delete(access_token_id, actor(&req)).await?;
+
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound("Access token not found".to_string()))
+    }
+}
+
+/// Handler to restore a soft-deleted access token.
+#[utoipa::path(
+    post,
+    path = "/access-tokens/{id}:restore",
+    tag = "access-tokens",
+    params(("id" = String, Path, description = "Access token id")),
+    responses(
+        (status = 200, description = "Access token restored", body = AccessToken),
+        (status = 404, description = "Access token not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "access_token::restore")]
+pub async fn restore(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = AccessTokenService::with_repositories(&data.repositories()?)?;
+    let access_token_id = parse_id(&path.into_inner())?;
+
+    match service.restore(access_token_id).await? {
+        Some(restored) => Ok(HttpResponse::Ok().json(restored)),
+        None => Err(AppError::NotFound("Access token not found".to_string())),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/access-tokens",
+    tag = "access-tokens",
+    params(AccessTokenFilter, Pagination, SortQuery),
+    responses(
+        (status = 200, description = "A page of access tokens", body = Page<AccessTokenRead>,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages"))),
+        (status = 400, description = "Invalid filter, sort or cursor", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "access_token::list")]
 pub async fn list(
+    req: HttpRequest,
     data: web::Data<AppData>,
     filter: Option<web::Query<AccessTokenFilter>>,
     pagination: web::Query<Pagination>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-
-    let service = AccessTokenService::new(database.clone()).unwrap();
-    let sort = SortBuilder::new().add_sort(AccessTokenSortableFields::Id, SortDirection::Ascending);
+    sort: web::Query<SortQuery>,
+) -> Result<HttpResponse, AppError> {
+    let service = AccessTokenService::with_repositories(&data.repositories()?)?;
+    let sort = sort
+        .into_inner()
+        .or(SortBuilder::new().add_sort(AccessTokenSortableFields::Id, SortDirection::Ascending))?;
 
     let filter = filter.map_or_else(AccessTokenFilter::default, |q| q.into_inner());
 
-    let results = service
-        .find(filter, Some(sort), Some(pagination.into_inner()))
-        .await;
+    let access_tokens = service
+        .list(filter, Some(sort), pagination.into_inner())
+        .await?
+        .map(AccessTokenRead::from);
 
-    let access_tokens = results.map(|access_tokens| {
-        access_tokens
-            .into_iter()
-            .map(AccessTokenRead::from)
-            .collect::<Vec<AccessTokenRead>>()
-    });
-
-    match access_tokens {
-        Ok(access_tokens) => Ok(HttpResponse::Ok().json(access_tokens)),
-        Err(e) => {
-            println!("Error listing accessToken: {:?}", e);
-            Err(actix_web::error::ErrorInternalServerError(e))
-        }
-    }
+    Ok(page_response(&req, &access_tokens))
 }
 
 pub fn configure_routes(config: &mut web::ServiceConfig) {
     config.service(
         web::scope("/access-tokens")
-            .service(web::resource("").route(web::post().to(create)))
+            .service(
+                web::resource("")
+                    .route(web::post().to(create))
+                    .route(web::get().to(list)),
+            )
+            // Registered before "/{id}", which would otherwise match "<id>:restore"
+            .service(web::resource("/{id}:restore").route(web::post().to(restore)))
             .service(
                 web::resource("/{id}")
                     .route(web::get().to(read))
@@ -169,17 +218,18 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::repositories::registry::Repositories;
     use actix_web::{App, test};
     use chrono::{Duration, Utc};
     use jsonwebtoken::Algorithm;
+    use mongodb::bson::uuid::Uuid;
 
     #[actix_web::test]
     async fn test_create_access_token_success() {
         // Setup
-        let db = setup_test_db("access_token_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
@@ -192,8 +242,8 @@ mod tests {
         // Test
         let payload = AccessTokenCreatePayload {
             project_access_id: Uuid::new(),
-            algorithm: Algorithm::RS256,
-            expires_at: Utc::now() + Duration::hours(1),
+            algorithm: Some(Algorithm::RS256),
+            expires_at: Some(Utc::now() + Duration::hours(1)),
         };
 
         let resp = test::TestRequest::post()
@@ -205,20 +255,62 @@ mod tests {
         assert!(resp.status().is_success());
         let created_token: AccessTokenRead = test::read_body_json(resp).await;
 
-        assert_eq!(created_token.algorithm, payload.algorithm);
-        assert_eq!(created_token.expires_at, payload.expires_at);
+        assert_eq!(Some(created_token.algorithm), payload.algorithm);
+        assert_eq!(Some(created_token.expires_at), payload.expires_at);
         assert!(created_token.enabled);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_create_access_token_uses_defaults() {
+        let repositories = Repositories::in_memory();
+        let app_data = web::Data::new(AppData {
+            repositories: Some(repositories.clone()),
+            ..Default::default()
+        });
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data.clone())
+                .configure(configure_routes),
+        )
+        .await;
+        let defaults = crate::config::TokenConfig::default();
+
+        let before = Utc::now();
+        let resp = test::TestRequest::post()
+            .uri("/access-tokens")
+            .set_json(serde_json::json!({ "project_access_id": Uuid::new().to_string() }))
+            .send_request(&app)
+            .await;
+
+        assert!(resp.status().is_success());
+        let created_token: AccessTokenRead = test::read_body_json(resp).await;
+        assert_eq!(created_token.algorithm, defaults.default_algorithm);
+        assert!(
+            created_token.expires_at
+                >= before + Duration::seconds(defaults.default_ttl_seconds as i64)
+        );
+
+        // Expiries beyond the longest lifetime are refused
+        let resp = test::TestRequest::post()
+            .uri("/access-tokens")
+            .set_json(AccessTokenCreatePayload {
+                project_access_id: Uuid::new(),
+                algorithm: None,
+                expires_at: Some(Utc::now() + Duration::days(3650)),
+            })
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
     }
 
     #[actix_web::test]
     async fn test_get_access_token_success() {
         // Setup
-        let db = setup_test_db("access_token_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
@@ -231,8 +323,8 @@ mod tests {
         // First create an access token
         let payload = AccessTokenCreatePayload {
             project_access_id: Uuid::new(),
-            algorithm: Algorithm::RS256,
-            expires_at: Utc::now() + Duration::hours(1),
+            algorithm: Some(Algorithm::RS256),
+            expires_at: Some(Utc::now() + Duration::hours(1)),
         };
 
         let resp = test::TestRequest::post()
@@ -254,19 +346,18 @@ mod tests {
         let retrieved_token: AccessTokenRead = test::read_body_json(resp).await;
 
         assert_eq!(retrieved_token.id, created_token.id);
-        assert_eq!(retrieved_token.algorithm, payload.algorithm);
-        assert_eq!(retrieved_token.expires_at, payload.expires_at);
+        assert_eq!(Some(retrieved_token.algorithm), payload.algorithm);
+        assert_eq!(Some(retrieved_token.expires_at), payload.expires_at);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_update_access_token_success() {
         // Setup
-        let db = setup_test_db("access_token_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
@@ -280,8 +371,8 @@ mod tests {
         let now = Utc::now();
         let payload = AccessTokenCreatePayload {
             project_access_id: Uuid::new(),
-            algorithm: Algorithm::RS256,
-            expires_at: now + Duration::hours(1),
+            algorithm: Some(Algorithm::RS256),
+            expires_at: Some(now + Duration::hours(1)),
         };
 
         let resp = test::TestRequest::post()
@@ -317,15 +408,14 @@ mod tests {
         assert_eq!(updated_token.project_access_id, new_project_id);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_delete_access_token_success() {
         // Setup
-        let db = setup_test_db("access_token_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
@@ -338,8 +428,8 @@ mod tests {
         // First create an access token
         let payload = AccessTokenCreatePayload {
             project_access_id: Uuid::new(),
-            algorithm: Algorithm::RS256,
-            expires_at: Utc::now() + Duration::hours(1),
+            algorithm: Some(Algorithm::RS256),
+            expires_at: Some(Utc::now() + Duration::hours(1)),
         };
 
         let resp = test::TestRequest::post()
@@ -368,15 +458,14 @@ mod tests {
         assert_eq!(resp.status(), 404); // Not Found
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_get_nonexistent_access_token() {
         // Setup
-        let db = setup_test_db("access_token_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
@@ -396,6 +485,5 @@ mod tests {
         assert_eq!(resp.status(), 404);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 }
@@ -1,143 +1,185 @@
 use crate::config::AppData;
+use crate::errors::{AppError, Problem};
 use crate::models::environment::{
     Environment, EnvironmentFilter, EnvironmentSortableFields, EnvironmentUpdatePayload,
 };
-use crate::models::pagination::Pagination;
-use crate::models::sort::{SortBuilder, SortDirection};
+use crate::models::pagination::{Page, Pagination};
+use crate::models::sort::{SortBuilder, SortDirection, SortQuery};
+use crate::routes::actor;
+use crate::routes::page_response;
+use crate::routes::parse_id;
 use crate::services::environment_service::EnvironmentService;
-use actix_web::{Error, HttpResponse, web};
-use mongodb::bson::uuid::Uuid;
+use actix_web::{HttpRequest, HttpResponse, web};
 
 /// Handler to create a new environment.
+#[utoipa::path(
+    post,
+    path = "/environments",
+    tag = "environments",
+    request_body = Environment,
+    responses(
+        (status = 200, description = "Environment created", body = Environment),
+        (status = 400, description = "Invalid environment", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "environment::create")]
 pub async fn create(
     data: web::Data<AppData>,
     environment: web::Json<Environment>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = EnvironmentService::new(database.clone())
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-    let environment = service.create(environment.into_inner()).await;
-    match environment {
-        Ok(environment) => Ok(HttpResponse::Ok().json(environment)),
-        Err(e) => {
-            println!("Error creating environment: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = EnvironmentService::with_repositories(&data.repositories()?)?;
+    let environment = service.create(environment.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(environment))
 }
 
 /// Handler to retrieve an environment by its ID.
+#[utoipa::path(
+    get,
+    path = "/environments/{id}",
+    tag = "environments",
+    params(("id" = String, Path, description = "Environment id")),
+    responses(
+        (status = 200, description = "Environment found", body = Environment),
+        (status = 404, description = "Environment not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "environment::read")]
 pub async fn read(
     data: web::Data<AppData>,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = EnvironmentService::new(database.clone())
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-    let environment_id = Uuid::parse_str(path.into_inner())
-        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid UUID format"))?;
+) -> Result<HttpResponse, AppError> {
+    let service = EnvironmentService::with_repositories(&data.repositories()?)?;
+    let environment_id = parse_id(&path.into_inner())?;
     let environment = service.get_environment(environment_id).await;
-    match environment {
-        Ok(Some(environment)) => Ok(HttpResponse::Ok().json(environment)),
-        Ok(None) => Ok(HttpResponse::NotFound().finish()),
-        Err(e) => {
-            println!("Error getting environment: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+    match environment? {
+        Some(environment) => Ok(HttpResponse::Ok().json(environment)),
+        None => Err(AppError::NotFound("Environment not found".to_string())),
     }
 }
 
 /// Handler to update an existing environment.
+#[utoipa::path(
+    patch,
+    path = "/environments/{id}",
+    tag = "environments",
+    params(("id" = String, Path, description = "Environment id")),
+    request_body = EnvironmentUpdatePayload,
+    responses(
+        (status = 200, description = "Environment updated", body = Environment),
+        (status = 400, description = "Invalid environment", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Environment not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "environment::update")]
 pub async fn update(
     data: web::Data<AppData>,
     path: web::Path<String>,
     payload: web::Json<EnvironmentUpdatePayload>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = EnvironmentService::new(database.clone()).unwrap();
-    let environment_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let environment = service.update(environment_id, payload.into_inner()).await;
-
-    match environment {
-        Ok(environment) => Ok(HttpResponse::Ok().json(environment)),
-        Err(e) => {
-            println!("Error updating project: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = EnvironmentService::with_repositories(&data.repositories()?)?;
+    let environment_id = parse_id(&path.into_inner())?;
+
+    let environment = service.update(environment_id, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(environment))
 }
 
 /// Handler to delete an environment by its ID.
+#[utoipa::path(
+    delete,
+    path = "/environments/{id}",
+    tag = "environments",
+    params(("id" = String, Path, description = "Environment id")),
+    responses(
+        (status = 204, description = "Environment deleted"),
+        (status = 404, description = "Environment not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "environment::delete")]
 pub async fn delete(
     data: web::Data<AppData>,
+    req: HttpRequest,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = EnvironmentService::new(database.clone()).unwrap();
-    let environment_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let result = service.delete(environment_id).await;
-
-    match result {
-        Ok(deleted) => {
-            if deleted {
-                Ok(HttpResponse::NoContent().finish())
-            } else {
-                Ok(HttpResponse::NotFound().finish())
-            }
-        }
-        Err(e) => {
-            println!("Error deleting project: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+) -> Result<HttpResponse, AppError> {
+    let service = EnvironmentService::with_repositories(&data.repositories()?)?;
+    let environment_id = parse_id(&path.into_inner())?;
+
+    let deleted = service.delete(environment_id, actor(&req)).await?;
+
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound("Environment not found".to_string()))
+    }
+}
+
+/// Handler to restore a soft-deleted environment.
+#[utoipa::path(
+    post,
+    path = "/environments/{id}:restore",
+    tag = "environments",
+    params(("id" = String, Path, description = "Environment id")),
+    responses(
+        (status = 200, description = "Environment restored", body = Environment),
+        (status = 404, description = "Environment not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "environment::restore")]
+pub async fn restore(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = EnvironmentService::with_repositories(&data.repositories()?)?;
+    let environment_id = parse_id(&path.into_inner())?;
+
+    match service.restore(environment_id).await? {
+        Some(restored) => Ok(HttpResponse::Ok().json(restored)),
+        None => Err(AppError::NotFound("Environment not found".to_string())),
     }
 }
+
+#[utoipa::path(
+    get,
+    path = "/environments",
+    tag = "environments",
+    params(EnvironmentFilter, Pagination, SortQuery),
+    responses(
+        (status = 200, description = "A page of environments", body = Page<Environment>,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages"))),
+        (status = 400, description = "Invalid filter, sort or cursor", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "environment::list")]
 pub async fn list(
+    req: HttpRequest,
     data: web::Data<AppData>,
     filter: Option<web::Query<EnvironmentFilter>>,
     pagination: web::Query<Pagination>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-
-    let service = EnvironmentService::new(database.clone()).unwrap();
-    let sort = SortBuilder::new().add_sort(EnvironmentSortableFields::Id, SortDirection::Ascending);
+    sort: web::Query<SortQuery>,
+) -> Result<HttpResponse, AppError> {
+    let service = EnvironmentService::with_repositories(&data.repositories()?)?;
+    let sort = sort
+        .into_inner()
+        .or(SortBuilder::new().add_sort(EnvironmentSortableFields::Id, SortDirection::Ascending))?;
 
     let filter = filter.map_or_else(EnvironmentFilter::default, |q| q.into_inner());
 
     let environments = service
-        .find(filter, Some(sort), Some(pagination.into_inner()))
-        .await;
-
-    match environments {
-        Ok(environments) => Ok(HttpResponse::Ok().json(environments)),
-        Err(e) => {
-            println!("Error listing projects: {:?}", e);
-            Err(actix_web::error::ErrorInternalServerError(e))
-        }
-    }
+        .list(filter, Some(sort), pagination.into_inner())
+        .await?;
+    Ok(page_response(&req, &environments))
 }
 
 /// Configures the routes for environments.
 pub fn configure_routes(config: &mut web::ServiceConfig) {
     config.service(
         web::scope("/environments")
-            .service(web::resource("").route(web::post().to(create)))
+            .service(
+                web::resource("")
+                    .route(web::post().to(create))
+                    .route(web::get().to(list)),
+            )
+            // Registered before "/{id}", which would otherwise match "<id>:restore"
+            .service(web::resource("/{id}:restore").route(web::post().to(restore)))
             .service(
                 web::resource("/{id}")
                     .route(web::get().to(read))
@@ -151,17 +193,19 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
 mod tests {
 
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::models::pagination::Page;
+    use crate::repositories::registry::Repositories;
     use actix_web::{App, test};
+    use mongodb::bson::uuid::Uuid;
 
     use chrono::Utc;
 
     #[actix_web::test]
     async fn test_list_environments_no_filter() {
         // Setup
-        let db = setup_test_db("environemt_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -193,6 +237,9 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
 
             let _ = test::TestRequest::post()
@@ -209,19 +256,19 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let environments: Vec<Environment> = test::read_body_json(resp).await;
+        let page: Page<Environment> = test::read_body_json(resp).await;
+        let environments = page.items;
         assert_eq!(environments.len(), 5);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_environments_with_filter() {
         // Setup
-        let db = setup_test_db("environment_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
@@ -252,6 +299,9 @@ mod tests {
                 enabled: i % 2 == 0,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
             let _ = test::TestRequest::post()
                 .uri("/environments")
@@ -267,19 +317,19 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let environments: Vec<Environment> = test::read_body_json(resp).await;
+        let page: Page<Environment> = test::read_body_json(resp).await;
+        let environments = page.items;
         assert_eq!(environments.len(), 3);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_environments_with_filter_and_pagination() {
         // Setup
-        let db = setup_test_db("environment_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
@@ -312,6 +362,9 @@ mod tests {
                 enabled: i % 2 == 0,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
             let _ = test::TestRequest::post()
                 .uri("/environments")
@@ -327,25 +380,138 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let environments: Vec<Environment> = test::read_body_json(resp).await;
+        let page: Page<Environment> = test::read_body_json(resp).await;
+        assert_eq!(page.total, 5);
+        assert_eq!(page.page, Some(1));
+        let environments = page.items;
         assert_eq!(environments.len(), 2);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_list_environments_sorted() {
+        // Setup
+        let repositories = Repositories::in_memory();
+        let app_data = web::Data::new(AppData {
+            repositories: Some(repositories.clone()),
+            ..Default::default()
+        });
+        let app =
+            test::init_service(App::new().app_data(app_data).configure(configure_routes)).await;
+
+        for name in ["beta", "alpha", "gamma"] {
+            let environment = Environment {
+                id: None,
+                project_id: Uuid::new(),
+                name: name.to_string(),
+                description: "Test Description".to_string(),
+                enabled: true,
+                created_at: Some(Utc::now()),
+                updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            };
+            let _ = test::TestRequest::post()
+                .uri("/environments")
+                .set_json(&environment)
+                .send_request(&app)
+                .await;
+        }
+
+        let resp = test::TestRequest::get()
+            .uri("/environments?sort=name:desc")
+            .send_request(&app)
+            .await;
+        assert!(resp.status().is_success());
+        let page: Page<Environment> = test::read_body_json(resp).await;
+        let names: Vec<String> = page.items.into_iter().map(|e| e.name).collect();
+        assert_eq!(names, vec!["gamma", "beta", "alpha"]);
+
+        let resp = test::TestRequest::get()
+            .uri("/environments?sort=description:asc")
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), 400);
+
+        // Cleanup
+    }
+
+    #[actix_web::test]
+    async fn test_list_environments_with_cursor() {
+        // Setup
+        let repositories = Repositories::in_memory();
+        let app_data = web::Data::new(AppData {
+            repositories: Some(repositories.clone()),
+            ..Default::default()
+        });
+        let app =
+            test::init_service(App::new().app_data(app_data).configure(configure_routes)).await;
+
+        for i in 0..5 {
+            let environment = Environment {
+                id: None,
+                project_id: Uuid::new(),
+                name: format!("Test Environment {}", i),
+                description: "Test Description".to_string(),
+                enabled: true,
+                created_at: Some(Utc::now()),
+                updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            };
+            let _ = test::TestRequest::post()
+                .uri("/environments")
+                .set_json(&environment)
+                .send_request(&app)
+                .await;
+        }
+
+        // Follow next_cursor until the last page
+        let mut names = Vec::new();
+        let resp = test::TestRequest::get()
+            .uri("/environments?limit=2")
+            .send_request(&app)
+            .await;
+        assert!(resp.headers().contains_key(actix_web::http::header::LINK));
+        let mut page: Page<Environment> = test::read_body_json(resp).await;
+        loop {
+            names.extend(page.items.iter().map(|e| e.name.clone()));
+            let Some(cursor) = page.next_cursor else {
+                break;
+            };
+            let uri = format!("/environments?limit=2&cursor={}", cursor);
+            let resp = test::TestRequest::get().uri(&uri).send_request(&app).await;
+            assert!(resp.status().is_success());
+            page = test::read_body_json(resp).await;
+            assert_eq!(page.page, None);
+            assert_eq!(page.total, 5);
+        }
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), 5);
+
+        // Cleanup
     }
 
     #[actix_web::test]
     async fn test_create_environment_success() {
         // Setup
-        let db = setup_test_db("environment_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
             App::new().app_data(app_data.clone()).service(
                 web::scope("/environments")
-                    .service(web::resource("").route(web::post().to(create)))
+                    .service(
+                        web::resource("")
+                            .route(web::post().to(create))
+                            .route(web::get().to(list)),
+                    )
                     .service(
                         web::resource("/{id}")
                             .route(web::get().to(read))
@@ -366,6 +532,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -384,21 +553,24 @@ mod tests {
         assert!(created_environment.id.is_some());
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_get_environment_success() {
         // Setup
-        let db = setup_test_db("environment_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
             App::new().app_data(app_data.clone()).service(
                 web::scope("/environments")
-                    .service(web::resource("").route(web::post().to(create)))
+                    .service(
+                        web::resource("")
+                            .route(web::post().to(create))
+                            .route(web::get().to(list)),
+                    )
                     .service(
                         web::resource("/{id}")
                             .route(web::get().to(read))
@@ -419,6 +591,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -443,21 +618,24 @@ mod tests {
         assert_eq!(retrieved_environment.name, created_environment.name);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_get_nonexistent_environment() {
         // Setup
-        let db = setup_test_db("environment_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
             App::new().app_data(app_data.clone()).service(
                 web::scope("/environments")
-                    .service(web::resource("").route(web::post().to(create)))
+                    .service(
+                        web::resource("")
+                            .route(web::post().to(create))
+                            .route(web::get().to(list)),
+                    )
                     .service(
                         web::resource("/{id}")
                             .route(web::get().to(read))
@@ -477,21 +655,24 @@ mod tests {
         assert!(resp.status().is_client_error());
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_update_environment_success() {
         // Setup
-        let db = setup_test_db("environment_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
             App::new().app_data(app_data.clone()).service(
                 web::scope("/environments")
-                    .service(web::resource("").route(web::post().to(create)))
+                    .service(
+                        web::resource("")
+                            .route(web::post().to(create))
+                            .route(web::get().to(list)),
+                    )
                     .service(
                         web::resource("/{id}")
                             .route(web::get().to(read))
@@ -512,6 +693,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -528,6 +712,7 @@ mod tests {
             name: Some("Updated Environment".to_string()),
             description: Some("Updated Description".to_string()),
             enabled: Some(false),
+            labels: None,
         };
 
         let resp = test::TestRequest::patch()
@@ -544,21 +729,24 @@ mod tests {
         assert!(!updated_environment.enabled);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_delete_environment_success() {
         // Setup
-        let db = setup_test_db("environment_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
             App::new().app_data(app_data.clone()).service(
                 web::scope("/environments")
-                    .service(web::resource("").route(web::post().to(create)))
+                    .service(
+                        web::resource("")
+                            .route(web::post().to(create))
+                            .route(web::get().to(list)),
+                    )
                     .service(
                         web::resource("/{id}")
                             .route(web::get().to(read))
@@ -579,6 +767,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -607,21 +798,24 @@ mod tests {
         assert!(resp.status().is_client_error());
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_delete_nonexistent_environment() {
         // Setup
-        let db = setup_test_db("environment_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
         let app = test::init_service(
             App::new().app_data(app_data.clone()).service(
                 web::scope("/environments")
-                    .service(web::resource("").route(web::post().to(create)))
+                    .service(
+                        web::resource("")
+                            .route(web::post().to(create))
+                            .route(web::get().to(list)),
+                    )
                     .service(
                         web::resource("/{id}")
                             .route(web::get().to(read))
@@ -641,6 +835,97 @@ mod tests {
         assert!(resp.status().is_client_error());
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_restore_environment() {
+        // Setup
+        let repositories = Repositories::in_memory();
+        let app_data = web::Data::new(AppData {
+            repositories: Some(repositories.clone()),
+            ..Default::default()
+        });
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data.clone())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let environment = Environment {
+            id: None,
+            project_id: Uuid::new(),
+            name: "Test Environment".to_string(),
+            description: "Test Description".to_string(),
+            enabled: true,
+            created_at: Some(Utc::now()),
+            updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
+        };
+        let resp = test::TestRequest::post()
+            .uri("/environments")
+            .set_json(&environment)
+            .send_request(&app)
+            .await;
+        let created_environment: Environment = test::read_body_json(resp).await;
+        let environment_id = created_environment.id.unwrap();
+
+        let resp = test::TestRequest::delete()
+            .uri(&format!("/environments/{}", environment_id))
+            .insert_header((crate::routes::ACTOR_HEADER, "alice"))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), 204);
+
+        let resp = test::TestRequest::post()
+            .uri(&format!("/environments/{}:restore", environment_id))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), 200);
+        let restored: Environment = test::read_body_json(resp).await;
+        assert_eq!(restored.id, Some(environment_id));
+        assert!(restored.deleted_at.is_none());
+
+        let resp = test::TestRequest::get()
+            .uri(&format!("/environments/{}", environment_id))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), 200);
+
+        // Restoring a live environment finds nothing to restore
+        let resp = test::TestRequest::post()
+            .uri(&format!("/environments/{}:restore", environment_id))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), 404);
+
+        // Cleanup
+    }
+
+    #[actix_web::test]
+    async fn test_restore_route_is_not_shadowed_by_id_route() {
+        // Without a database the handler fails, which proves the request reached it
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::default()))
+                .configure(configure_routes),
+        )
+        .await;
+
+        let resp = test::TestRequest::post()
+            .uri(&format!("/environments/{}:restore", Uuid::new()))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), 503);
+        assert_eq!(
+            resp.headers()
+                .get(actix_web::http::header::CONTENT_TYPE)
+                .unwrap(),
+            crate::errors::PROBLEM_JSON
+        );
+        let problem: crate::errors::Problem = test::read_body_json(resp).await;
+        assert_eq!(problem.problem_type, "urn:buraq:problem:unavailable");
     }
 }
@@ -0,0 +1,130 @@
+use crate::config::AppData;
+use crate::models::health::{Health, HealthStatus, ServiceStatus};
+use crate::services::health_service::HealthService;
+use actix_web::{HttpResponse, web};
+
+/// Liveness probe: answers as long as the process is serving requests.
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses(
+        (status = 200, description = "The process is up", body = Health),
+    )
+)]
+#[tracing::instrument(skip_all, name = "health::healthz")]
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(Health {
+        status: HealthStatus::Up,
+        checks: vec![],
+    })
+}
+
+/// Readiness probe: answers 200 only when every dependency check passes.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready to serve traffic", body = Health),
+        (status = 503, description = "A dependency check failed", body = Health),
+    )
+)]
+#[tracing::instrument(skip_all, name = "health::readyz")]
+pub async fn readyz(data: web::Data<AppData>) -> HttpResponse {
+    let health = HealthService::new(&data).readiness().await;
+    if health.is_up() {
+        HttpResponse::Ok().json(health)
+    } else {
+        HttpResponse::ServiceUnavailable().json(health)
+    }
+}
+
+/// Admin status: build version, uptime and dependency latencies.
+#[utoipa::path(
+    get,
+    path = "/status",
+    tag = "health",
+    responses(
+        (status = 200, description = "Server status", body = ServiceStatus),
+    )
+)]
+#[tracing::instrument(skip_all, name = "health::status")]
+pub async fn status(data: web::Data<AppData>) -> HttpResponse {
+    let status = HealthService::new(&data).status(&data).await;
+    HttpResponse::Ok().json(status)
+}
+
+/// Configures the health and status routes.
+pub fn configure_routes(config: &mut web::ServiceConfig) {
+    config
+        .service(web::resource("/healthz").route(web::get().to(healthz)))
+        .service(web::resource("/readyz").route(web::get().to(readyz)))
+        .service(web::resource("/status").route(web::get().to(status)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::{App, test};
+    use chrono::Utc;
+
+    #[actix_web::test]
+    async fn test_healthz() {
+        let app = test::init_service(App::new().configure(configure_routes)).await;
+        let resp = test::TestRequest::get()
+            .uri("/healthz")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let health: Health = test::read_body_json(resp).await;
+        assert!(health.is_up());
+    }
+
+    #[actix_web::test]
+    async fn test_readyz_without_database() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::default()))
+                .configure(configure_routes),
+        )
+        .await;
+        let resp = test::TestRequest::get()
+            .uri("/readyz")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let health: Health = test::read_body_json(resp).await;
+        assert_eq!(health.status, HealthStatus::Down);
+        assert_eq!(health.checks[0].name, "mongo");
+        assert_eq!(health.checks[0].status, HealthStatus::Down);
+        // The cause stays in the server logs
+        assert_eq!(
+            health.checks[0].error.as_deref(),
+            Some(crate::services::health_service::CHECK_FAILED)
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_status_reports_version_and_uptime() {
+        let app_data = web::Data::new(AppData {
+            started_at: Some(Utc::now() - chrono::Duration::seconds(90)),
+            ..Default::default()
+        });
+        let app =
+            test::init_service(App::new().app_data(app_data).configure(configure_routes)).await;
+        let resp = test::TestRequest::get()
+            .uri("/status")
+            .send_request(&app)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let status: ServiceStatus = test::read_body_json(resp).await;
+        assert_eq!(status.version, env!("CARGO_PKG_VERSION"));
+        assert!(status.uptime_seconds >= 90);
+        assert_eq!(status.health.checks.len(), 4);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::config::AppData;
+use crate::errors::{AppError, Problem};
+use crate::models::manifest::{ApplyQuery, ApplyResponse, Manifest};
+use crate::routes::actor;
+use crate::services::manifest_service::ManifestService;
+
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpMessage, HttpRequest, HttpResponse, web};
+use futures::future::LocalBoxFuture;
+
+/// Content types of YAML manifests; any other body is read as JSON.
+const YAML_CONTENT_TYPES: &[&str] = &["application/yaml", "application/x-yaml", "text/yaml"];
+
+/// A manifest sent as JSON, or as YAML with one of [`YAML_CONTENT_TYPES`].
+pub struct ManifestBody(pub Manifest);
+
+impl FromRequest for ManifestBody {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        if YAML_CONTENT_TYPES.contains(&req.content_type()) {
+            let body = web::Bytes::from_request(req, payload);
+            Box::pin(async move {
+                let manifest = serde_norway::from_slice(&body.await?)
+                    .map_err(|e| AppError::Validation(format!("Invalid YAML manifest: {}", e)))?;
+                Ok(Self(manifest))
+            })
+        } else {
+            let json = web::Json::<Manifest>::from_request(req, payload);
+            Box::pin(async move { Ok(Self(json.await?.into_inner())) })
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/apply",
+    tag = "manifests",
+    params(ApplyQuery),
+    request_body(content(
+        (Manifest = "application/json"),
+        (Manifest = "application/yaml"),
+    )),
+    responses(
+        (status = 200, description = "Plan computed and, unless a dry run, applied", body = ApplyResponse),
+        (status = 400, description = "Invalid manifest, or pruning without a selector", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "A change conflicts with existing state", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "manifest::apply")]
+pub async fn apply(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    query: web::Query<ApplyQuery>,
+    manifest: ManifestBody,
+) -> Result<HttpResponse, AppError> {
+    let service = ManifestService::with_repositories(&data.repositories()?)?;
+    let response = service.apply(&manifest.0, &query, actor(&req)).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub fn configure_routes(config: &mut web::ServiceConfig) {
+    config.service(web::resource("/apply").route(web::post().to(apply)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::registry::Repositories;
+    use actix_web::http::StatusCode;
+    use actix_web::http::header::CONTENT_TYPE;
+    use actix_web::{App, test};
+
+    const MANIFEST: &str = "\
+projects:
+- name: billing
+  labels:
+    team: payments
+  environments:
+  - name: production
+";
+
+    #[actix_web::test]
+    async fn test_apply_yaml_manifest() {
+        let app_data = web::Data::new(AppData {
+            repositories: Some(Repositories::in_memory()),
+            ..Default::default()
+        });
+        let app =
+            test::init_service(App::new().app_data(app_data).configure(configure_routes)).await;
+
+        let response: ApplyResponse = test::call_and_read_body_json(
+            &app,
+            test::TestRequest::post()
+                .uri("/apply")
+                .insert_header((CONTENT_TYPE, "application/yaml"))
+                .set_payload(MANIFEST)
+                .to_request(),
+        )
+        .await;
+        assert!(response.applied);
+        assert_eq!(response.changes.len(), 2);
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/apply")
+                .insert_header((CONTENT_TYPE, "application/yaml"))
+                .set_payload("projects: [\n")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Pruning everything undeclared has to be asked for explicitly
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/apply?prune=true")
+                .set_json(serde_json::json!({ "projects": [] }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
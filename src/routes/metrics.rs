@@ -0,0 +1,79 @@
+use crate::config::AppData;
+use crate::errors::AppError;
+use crate::metrics::metrics;
+use crate::services::access_token_service::AccessTokenService;
+use crate::services::service_account_key_service::ServiceAccountKeyService;
+use actix_web::{HttpResponse, web};
+use chrono::{Duration, Utc};
+
+/// Keys and tokens expiring within this many days are reported as nearing expiry.
+pub const EXPIRY_WARNING_DAYS: i64 = 7;
+
+/// Content type of the Prometheus text exposition format.
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Handler exposing the metrics in the Prometheus text format.
+///
+/// The expiry gauges are refreshed from the database on every scrape.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text format", body = String, content_type = "text/plain"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "metrics::export")]
+pub async fn export(data: web::Data<AppData>) -> Result<HttpResponse, AppError> {
+    if let Ok(repositories) = data.repositories() {
+        let deadline = Utc::now() + Duration::days(EXPIRY_WARNING_DAYS);
+        let access_tokens = AccessTokenService::with_repositories(&repositories)?
+            .count_expiring(deadline)
+            .await?;
+        let service_account_keys = ServiceAccountKeyService::with_repositories(&repositories)?
+            .count_expiring(deadline)
+            .await?;
+        let gauge = &metrics().keys_expiring;
+        gauge
+            .with_label_values(&["access_token"])
+            .set(access_tokens as i64);
+        gauge
+            .with_label_values(&["service_account_key"])
+            .set(service_account_keys as i64);
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(TEXT_FORMAT)
+        .body(metrics().render()))
+}
+
+/// Configures the metrics route.
+pub fn configure_routes(config: &mut web::ServiceConfig) {
+    config.service(web::resource("/metrics").route(web::get().to(export)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{App, test};
+
+    #[actix_web::test]
+    async fn test_export_without_database() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::default()))
+                .configure(configure_routes),
+        )
+        .await;
+        let resp = test::TestRequest::get()
+            .uri("/metrics")
+            .send_request(&app)
+            .await;
+
+        assert!(resp.status().is_success());
+        assert_eq!(resp.headers().get("content-type").unwrap(), TEXT_FORMAT);
+        let body = test::read_body(resp).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("# HELP buraq_"));
+    }
+}
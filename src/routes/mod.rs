@@ -1,8 +1,172 @@
 pub mod access_token;
 pub mod environment;
+pub mod health;
+pub mod manifest;
+pub mod metrics;
 pub mod project;
 pub mod project_access;
 pub mod project_scope;
+pub mod project_template;
 pub mod server_key;
 pub mod service_account;
 pub mod service_account_key;
+pub mod token;
+
+use crate::errors::AppError;
+use crate::models::pagination::Page;
+use actix_web::http::header::LINK;
+use actix_web::{HttpRequest, HttpResponse, web};
+use mongodb::bson::uuid::Uuid;
+use serde::Serialize;
+
+/// Registers the routes of every resource.
+pub fn configure(config: &mut web::ServiceConfig) {
+    health::configure_routes(config);
+    metrics::configure_routes(config);
+    project::configure_routes(config);
+    access_token::configure_routes(config);
+    service_account::configure_routes(config);
+    environment::configure_routes(config);
+    project_access::configure_routes(config);
+    project_scope::configure_routes(config);
+    project_template::configure_routes(config);
+    server_key::configure_routes(config);
+    service_account_key::configure_routes(config);
+    token::configure_routes(config);
+    manifest::configure_routes(config);
+}
+
+/// Header identifying who performed a mutating request, recorded on soft-deletes.
+pub const ACTOR_HEADER: &str = "X-Actor";
+
+/// Returns the caller identity sent in the [`ACTOR_HEADER`] header, if any.
+pub fn actor(request: &HttpRequest) -> Option<String> {
+    request
+        .headers()
+        .get(ACTOR_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Parses a resource id taken from the request path.
+pub fn parse_id(value: &str) -> Result<Uuid, AppError> {
+    Uuid::parse_str(value).map_err(|_| AppError::Validation(format!("Invalid id: {}", value)))
+}
+
+/// Renders a list response: the page envelope as the body, plus a `Link`
+/// header with the `first`, `prev`, `next` and `last` pages where they apply.
+pub fn page_response<T: Serialize>(request: &HttpRequest, page: &Page<T>) -> HttpResponse {
+    let links = page_links(request, page);
+    let mut response = HttpResponse::Ok();
+    if !links.is_empty() {
+        response.insert_header((LINK, links.join(", ")));
+    }
+    response.json(page)
+}
+
+fn page_links<T>(request: &HttpRequest, page: &Page<T>) -> Vec<String> {
+    // Keep the caller's filters and page size, replacing only the position
+    let query: Vec<(String, String)> =
+        serde_urlencoded::from_str::<Vec<(String, String)>>(request.query_string())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(name, _)| name != "page" && name != "cursor")
+            .collect();
+    let link = |name: &str, value: String, rel: &str| {
+        let mut query = query.clone();
+        query.push((name.to_string(), value));
+        format!(
+            "<{}?{}>; rel=\"{}\"",
+            request.path(),
+            serde_urlencoded::to_string(&query).unwrap_or_default(),
+            rel
+        )
+    };
+
+    let mut links = Vec::new();
+    match page.page {
+        Some(number) => {
+            let last = page.page_count();
+            links.push(link("page", "1".to_string(), "first"));
+            if number > 1 {
+                links.push(link("page", (number - 1).to_string(), "prev"));
+            }
+            if (number as u64) < last {
+                links.push(link("page", (number + 1).to_string(), "next"));
+            }
+            links.push(link("page", last.to_string(), "last"));
+        }
+        None => {
+            if let Some(cursor) = &page.next_cursor {
+                links.push(link("cursor", cursor.clone(), "next"));
+            }
+        }
+    }
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_actor_from_header() {
+        let request = TestRequest::default()
+            .insert_header((ACTOR_HEADER, "alice"))
+            .to_http_request();
+        assert_eq!(actor(&request), Some("alice".to_string()));
+
+        let request = TestRequest::default().to_http_request();
+        assert_eq!(actor(&request), None);
+    }
+
+    #[test]
+    fn test_page_response_links() {
+        let request = TestRequest::get()
+            .uri("/environments?name=dev&page=2&limit=2")
+            .to_http_request();
+        let page = Page {
+            items: vec![1, 2],
+            total: 5,
+            page: Some(2),
+            limit: 2,
+            next_cursor: Some("abc".to_string()),
+        };
+        let response = page_response(&request, &page);
+        assert_eq!(
+            response.headers().get(LINK).unwrap(),
+            "</environments?name=dev&limit=2&page=1>; rel=\"first\", \
+             </environments?name=dev&limit=2&page=1>; rel=\"prev\", \
+             </environments?name=dev&limit=2&page=3>; rel=\"next\", \
+             </environments?name=dev&limit=2&page=3>; rel=\"last\""
+        );
+
+        // Cursor pagination only knows the way forward
+        let request = TestRequest::get()
+            .uri("/environments?cursor=xyz")
+            .to_http_request();
+        let page = Page {
+            items: vec![1],
+            total: 5,
+            page: None,
+            limit: 10,
+            next_cursor: Some("abc".to_string()),
+        };
+        let response = page_response(&request, &page);
+        assert_eq!(
+            response.headers().get(LINK).unwrap(),
+            "</environments?cursor=abc>; rel=\"next\""
+        );
+    }
+
+    #[test]
+    fn test_parse_id() {
+        let id = Uuid::new();
+        assert_eq!(parse_id(&id.to_string()).unwrap(), id);
+        assert!(matches!(
+            parse_id("not-a-uuid"),
+            Err(AppError::Validation(_))
+        ));
+    }
+}
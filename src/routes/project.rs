@@ -1,131 +1,282 @@
 use crate::config::AppData;
-use crate::models::pagination::Pagination;
-use crate::models::project::{Project, ProjectFilter, ProjectSortableFields, ProjectUpdatePayload};
-use crate::models::sort::{SortBuilder, SortDirection};
+use crate::errors::{AppError, Problem};
+use crate::models::pagination::{Page, Pagination};
+use crate::models::project::{
+    Project, ProjectCreateQuery, ProjectDeleteOutcome, ProjectDeleteQuery, ProjectDependents,
+    ProjectFilter, ProjectSortableFields, ProjectUpdatePayload,
+};
+use crate::models::project_bundle::{
+    PASSPHRASE_HEADER, ProjectBundle, ProjectImportOutcome, ProjectImportQuery, ProjectImportReport,
+};
+use crate::models::sort::{SortBuilder, SortDirection, SortQuery};
+use crate::routes::actor;
+use crate::routes::page_response;
+use crate::routes::parse_id;
+use crate::services::project_bundle_service::ProjectBundleService;
 use crate::services::project_service::ProjectService;
-use mongodb::bson::uuid::Uuid;
-
-use actix_web::{Error, HttpResponse, web};
-
+use crate::services::project_template_service::ProjectTemplateService;
+
+use actix_web::{HttpRequest, HttpResponse, web};
+
+/// Handler to create a project, scaffolded from a template when one is named.
+#[utoipa::path(
+    post,
+    path = "/projects",
+    tag = "projects",
+    params(ProjectCreateQuery),
+    request_body = Project,
+    responses(
+        (status = 200, description = "Project created", body = Project),
+        (status = 400, description = "Invalid project", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Project template not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project::create")]
 pub async fn create(
     data: web::Data<AppData>,
+    query: web::Query<ProjectCreateQuery>,
     project: web::Json<Project>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectService::new(database.clone()).unwrap();
-    let project = service.create(project.into_inner()).await;
-
-    match project {
-        Ok(project) => Ok(HttpResponse::Ok().json(project)),
-        Err(e) => {
-            println!("Error creating project: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
+) -> Result<HttpResponse, AppError> {
+    let repositories = data.repositories()?;
+    let project = match query.into_inner().template {
+        Some(template) => {
+            ProjectTemplateService::with_repositories(&repositories)?
+                .create_project(project.into_inner(), &template)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Project template not found".to_string()))?
+                .project
         }
-    }
+        None => {
+            ProjectService::with_repositories(&repositories)?
+                .create(project.into_inner())
+                .await?
+        }
+    };
+    Ok(HttpResponse::Ok().json(project))
 }
 
+#[utoipa::path(
+    get,
+    path = "/projects/{id}",
+    tag = "projects",
+    params(("id" = String, Path, description = "Project id")),
+    responses(
+        (status = 200, description = "Project found", body = Project),
+        (status = 404, description = "Project not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project::read")]
 pub async fn read(
     data: web::Data<AppData>,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectService::new(database.clone()).unwrap();
-    let project_id = Uuid::parse_str(path.into_inner()).unwrap();
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectService::with_repositories(&data.repositories()?)?;
+    let project_id = parse_id(&path.into_inner())?;
     let project = service.get_project(project_id).await;
 
-    match project {
-        Ok(Some(project)) => Ok(HttpResponse::Ok().json(project)),
-        Ok(None) => Ok(HttpResponse::NotFound().finish()),
-        Err(e) => {
-            println!("Error getting project: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+    match project? {
+        Some(project) => Ok(HttpResponse::Ok().json(project)),
+        None => Err(AppError::NotFound("Project not found".to_string())),
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/projects/{id}",
+    tag = "projects",
+    params(("id" = String, Path, description = "Project id")),
+    request_body = ProjectUpdatePayload,
+    responses(
+        (status = 200, description = "Project updated", body = Project),
+        (status = 400, description = "Invalid project", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Project not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project::update")]
 pub async fn update(
     data: web::Data<AppData>,
     path: web::Path<String>,
     payload: web::Json<ProjectUpdatePayload>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectService::new(database.clone()).unwrap();
-    let project_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let project = service.update(project_id, payload.into_inner()).await;
-
-    match project {
-        Ok(project) => Ok(HttpResponse::Ok().json(project)),
-        Err(e) => {
-            println!("Error updating project: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectService::with_repositories(&data.repositories()?)?;
+    let project_id = parse_id(&path.into_inner())?;
+
+    let project = service.update(project_id, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(project))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/projects/{id}",
+    tag = "projects",
+    params(("id" = String, Path, description = "Project id"), ProjectDeleteQuery),
+    responses(
+        (status = 200, description = "Project and dependents deleted", body = ProjectDependents),
+        (status = 204, description = "Project deleted"),
+        (status = 404, description = "Project not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Project has dependents", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project::delete")]
 pub async fn delete(
     data: web::Data<AppData>,
+    req: HttpRequest,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectService::new(database.clone()).unwrap();
-    let project_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let result = service.delete(project_id).await;
-
-    match result {
-        Ok(deleted) => {
-            if deleted {
-                Ok(HttpResponse::NoContent().finish())
+    query: web::Query<ProjectDeleteQuery>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectService::with_repositories(&data.repositories()?)?;
+    let project_id = parse_id(&path.into_inner())?;
+    let cascade = query.cascade.unwrap_or(false);
+
+    let outcome = service.delete(project_id, cascade, actor(&req)).await?;
+
+    match outcome {
+        ProjectDeleteOutcome::Deleted(removed) => {
+            if cascade {
+                Ok(HttpResponse::Ok().json(removed))
             } else {
-                Ok(HttpResponse::NotFound().finish())
+                Ok(HttpResponse::NoContent().finish())
             }
         }
-        Err(e) => {
-            println!("Error deleting project: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+        ProjectDeleteOutcome::Blocked(dependents) => Ok(AppError::Conflict(
+            "Project has dependent resources; delete them first or pass cascade=true".to_string(),
+        )
+        .problem()
+        .with_extension("dependents", dependents)
+        .to_response()),
+        ProjectDeleteOutcome::NotFound => Err(AppError::NotFound("Project not found".to_string())),
+    }
+}
+
+/// Handler to restore a soft-deleted project.
+#[utoipa::path(
+    post,
+    path = "/projects/{id}:restore",
+    tag = "projects",
+    params(("id" = String, Path, description = "Project id")),
+    responses(
+        (status = 200, description = "Project restored", body = Project),
+        (status = 404, description = "Project not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project::restore")]
+pub async fn restore(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectService::with_repositories(&data.repositories()?)?;
+    let project_id = parse_id(&path.into_inner())?;
+
+    match service.restore(project_id).await? {
+        Some(restored) => Ok(HttpResponse::Ok().json(restored)),
+        None => Err(AppError::NotFound("Project not found".to_string())),
+    }
+}
+
+/// Handler to export a project as a bundle, to import on another installation.
+#[utoipa::path(
+    get,
+    path = "/projects/{id}/export",
+    tag = "projects",
+    params(
+        ("id" = String, Path, description = "Project id"),
+        ("X-Bundle-Passphrase" = Option<String>, Header,
+            description = "Include service account secrets and server keys, encrypted under this passphrase"),
+    ),
+    responses(
+        (status = 200, description = "Project bundle", body = ProjectBundle),
+        (status = 404, description = "Project not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project::export")]
+pub async fn export(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectBundleService::with_repositories(&data.repositories()?)?;
+    let project_id = parse_id(&path.into_inner())?;
+
+    match service.export(project_id, passphrase(&req)).await? {
+        Some(bundle) => Ok(HttpResponse::Ok().json(bundle)),
+        None => Err(AppError::NotFound("Project not found".to_string())),
+    }
+}
+
+/// Handler to recreate a project from a bundle, with new ids.
+#[utoipa::path(
+    post,
+    path = "/projects/import",
+    tag = "projects",
+    params(
+        ProjectImportQuery,
+        ("X-Bundle-Passphrase" = Option<String>, Header,
+            description = "Passphrase the bundle's secrets were encrypted under"),
+    ),
+    request_body = ProjectBundle,
+    responses(
+        (status = 200, description = "Project imported, or checked on a dry run", body = ProjectImportReport),
+        (status = 400, description = "Invalid bundle or passphrase", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "The bundle conflicts with existing records", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project::import")]
+pub async fn import(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    query: web::Query<ProjectImportQuery>,
+    bundle: web::Json<ProjectBundle>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectBundleService::with_repositories(&data.repositories()?)?;
+
+    match service.import(&bundle, &query, passphrase(&req)).await? {
+        ProjectImportOutcome::Imported(report) => Ok(HttpResponse::Ok().json(report)),
+        ProjectImportOutcome::Conflicts(conflicts) => Ok(AppError::Conflict(
+            "The bundle conflicts with existing records; nothing was imported".to_string(),
+        )
+        .problem()
+        .with_extension("conflicts", conflicts)
+        .to_response()),
     }
 }
 
+fn passphrase(request: &HttpRequest) -> Option<&str> {
+    request
+        .headers()
+        .get(PASSPHRASE_HEADER)
+        .and_then(|value| value.to_str().ok())
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects",
+    tag = "projects",
+    params(ProjectFilter, Pagination, SortQuery),
+    responses(
+        (status = 200, description = "A page of projects", body = Page<Project>,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages"))),
+        (status = 400, description = "Invalid filter, sort or cursor", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project::list")]
 pub async fn list(
+    req: HttpRequest,
     data: web::Data<AppData>,
     filter: Option<web::Query<ProjectFilter>>,
     pagination: web::Query<Pagination>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-
-    let service = ProjectService::new(database.clone()).unwrap();
-    let sort = SortBuilder::new().add_sort(ProjectSortableFields::Id, SortDirection::Ascending);
+    sort: web::Query<SortQuery>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectService::with_repositories(&data.repositories()?)?;
+    let sort = sort
+        .into_inner()
+        .or(SortBuilder::new().add_sort(ProjectSortableFields::Id, SortDirection::Ascending))?;
 
     let filter = filter.map_or_else(ProjectFilter::default, |q| q.into_inner());
 
     let projects = service
-        .find(filter, Some(sort), Some(pagination.into_inner()))
-        .await;
-
-    match projects {
-        Ok(projects) => Ok(HttpResponse::Ok().json(projects)),
-        Err(e) => {
-            println!("Error listing projects: {:?}", e);
-            Err(actix_web::error::ErrorInternalServerError(e))
-        }
-    }
+        .list(filter, Some(sort), pagination.into_inner())
+        .await?;
+    Ok(page_response(&req, &projects))
 }
 
 pub fn configure_routes(config: &mut web::ServiceConfig) {
@@ -136,6 +287,11 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
                     .route(web::post().to(create))
                     .route(web::get().to(list)),
             )
+            // Registered before "/{id}", which would otherwise match "import"
+            // and "<id>:restore"
+            .service(web::resource("/import").route(web::post().to(import)))
+            .service(web::resource("/{id}:restore").route(web::post().to(restore)))
+            .service(web::resource("/{id}/export").route(web::get().to(export)))
             .service(
                 web::resource("/{id}")
                     .route(web::get().to(read))
@@ -148,16 +304,20 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::models::environment::Environment;
+    use crate::models::pagination::Page;
+    use crate::models::project::ProjectDependents;
+    use crate::repositories::registry::Repositories;
     use actix_web::{App, test};
     use chrono::Utc;
+    use mongodb::bson::uuid::Uuid;
 
     #[actix_web::test]
     async fn test_create_project_success() {
         // Setup
-        let db = setup_test_db("project_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -183,6 +343,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -199,15 +362,63 @@ mod tests {
         assert!(created_project.id.is_some());
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_create_project_ignores_deleted_at() {
+        // Setup
+        let repositories = Repositories::in_memory();
+        let app_data = web::Data::new(AppData {
+            repositories: Some(repositories.clone()),
+            ..Default::default()
+        });
+
+        let app = test::init_service(
+            App::new().app_data(app_data.clone()).service(
+                web::scope("/projects")
+                    .service(web::resource("").route(web::post().to(create)))
+                    .service(web::resource("/{id}").route(web::get().to(read))),
+            ),
+        )
+        .await;
+
+        // Test
+        let project = Project {
+            id: None,
+            name: "Test Project".to_string(),
+            description: "Test Description".to_string(),
+            enabled: true,
+            created_at: None,
+            updated_at: None,
+            deleted_at: Some(Utc::now()),
+            deleted_by: Some("someone".to_string()),
+            labels: Default::default(),
+        };
+
+        let resp = test::TestRequest::post()
+            .uri("/projects")
+            .set_json(&project)
+            .send_request(&app)
+            .await;
+
+        assert!(resp.status().is_success());
+        let created_project: Project = test::read_body_json(resp).await;
+        assert!(created_project.deleted_at.is_none());
+        assert!(created_project.deleted_by.is_none());
+
+        let resp = test::TestRequest::get()
+            .uri(&format!("/projects/{}", created_project.id.unwrap()))
+            .send_request(&app)
+            .await;
+        assert!(resp.status().is_success());
     }
 
     #[actix_web::test]
     async fn test_get_project_success() {
         // Setup
-        let db = setup_test_db("project_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -233,6 +444,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -256,15 +470,14 @@ mod tests {
         assert_eq!(retrieved_project.name, created_project.name);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_get_nonexistent_project() {
         // Setup
-        let db = setup_test_db("project_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -291,15 +504,14 @@ mod tests {
         assert!(resp.status().is_client_error());
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_update_project_success() {
         // Setup
-        let db = setup_test_db("project_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -325,6 +537,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -341,6 +556,7 @@ mod tests {
             name: Some("Updated Project".to_string()),
             description: Some("Updated Description".to_string()),
             enabled: Some(false),
+            labels: None,
         };
 
         let resp = test::TestRequest::patch()
@@ -356,15 +572,14 @@ mod tests {
         assert!(!updated_project.enabled);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_update_nonexistent_project() {
         // Setup
-        let db = setup_test_db("project_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -387,6 +602,7 @@ mod tests {
             name: Some("Updated Project".to_string()),
             description: Some("Updated Description".to_string()),
             enabled: Some(false),
+            labels: None,
         };
 
         let resp = test::TestRequest::patch()
@@ -398,15 +614,14 @@ mod tests {
         assert!(resp.status().is_client_error());
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_delete_project_success() {
         // Setup
-        let db = setup_test_db("project_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -432,6 +647,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -460,15 +678,14 @@ mod tests {
         assert!(resp.status().is_client_error());
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_delete_nonexistent_project() {
         // Setup
-        let db = setup_test_db("project_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -495,15 +712,153 @@ mod tests {
         assert!(resp.status().is_client_error());
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_delete_project_with_dependents() {
+        // Setup
+        let repositories = Repositories::in_memory();
+        let app_data = web::Data::new(AppData {
+            repositories: Some(repositories.clone()),
+            ..Default::default()
+        });
+
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data.clone())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let project = Project {
+            id: None,
+            name: "Test Project".to_string(),
+            description: "Test Description".to_string(),
+            enabled: true,
+            created_at: Some(Utc::now()),
+            updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
+        };
+
+        let resp = test::TestRequest::post()
+            .uri("/projects")
+            .set_json(&project)
+            .send_request(&app)
+            .await;
+        let created_project: Project = test::read_body_json(resp).await;
+        let project_id = created_project.id.unwrap();
+
+        let environment = repositories
+            .environments
+            .create(Environment {
+                id: None,
+                project_id,
+                name: "production".to_string(),
+                description: "Production".to_string(),
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await
+            .unwrap();
+
+        // A plain delete is refused and lists the blocking environment
+        let resp = test::TestRequest::delete()
+            .uri(&format!("/projects/{}", project_id))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::CONFLICT);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        let blocking: ProjectDependents =
+            serde_json::from_value(body["dependents"].clone()).unwrap();
+        assert_eq!(blocking.environments, vec![environment.id.unwrap()]);
+
+        // A cascading delete removes the project and its environment
+        let resp = test::TestRequest::delete()
+            .uri(&format!("/projects/{}?cascade=true", project_id))
+            .send_request(&app)
+            .await;
+        assert!(resp.status().is_success());
+        let removed: ProjectDependents = test::read_body_json(resp).await;
+        assert_eq!(removed.environments, vec![environment.id.unwrap()]);
+
+        let resp = test::TestRequest::get()
+            .uri(&format!("/projects/{}", project_id))
+            .send_request(&app)
+            .await;
+        assert!(resp.status().is_client_error());
+
+        // Cleanup
+    }
+
+    #[actix_web::test]
+    async fn test_create_project_from_template() {
+        // Setup
+        let repositories = Repositories::in_memory();
+        let app_data = web::Data::new(AppData {
+            repositories: Some(repositories.clone()),
+            ..Default::default()
+        });
+
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data.clone())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let project = Project {
+            id: None,
+            name: "Test Project".to_string(),
+            description: "Test Description".to_string(),
+            enabled: true,
+            created_at: None,
+            updated_at: None,
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
+        };
+
+        let resp = test::TestRequest::post()
+            .uri("/projects?template=standard")
+            .set_json(&project)
+            .send_request(&app)
+            .await;
+        assert!(resp.status().is_success());
+        let created_project: Project = test::read_body_json(resp).await;
+        let project_id = created_project.id.unwrap();
+
+        // The cascading delete reports the scaffolded tree
+        let resp = test::TestRequest::delete()
+            .uri(&format!("/projects/{}?cascade=true", project_id))
+            .send_request(&app)
+            .await;
+        let removed: ProjectDependents = test::read_body_json(resp).await;
+        assert_eq!(removed.environments.len(), 3);
+        assert_eq!(removed.project_scopes.len(), 3);
+        assert_eq!(removed.server_keys.len(), 3);
+
+        let resp = test::TestRequest::post()
+            .uri("/projects?template=unknown")
+            .set_json(&project)
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+
+        // Cleanup
     }
 
     #[actix_web::test]
     async fn test_list_projects_no_filter() {
         // Setup
-        let db = setup_test_db("project_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -534,6 +889,9 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
 
             let _ = test::TestRequest::post()
@@ -550,19 +908,19 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let projects: Vec<Project> = test::read_body_json(resp).await;
+        let page: Page<Project> = test::read_body_json(resp).await;
+        let projects = page.items;
         assert_eq!(projects.len(), 5);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_projects_with_pagination() {
         // Setup
-        let db = setup_test_db("project_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -593,6 +951,9 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
 
             let _ = test::TestRequest::post()
@@ -609,19 +970,19 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let projects: Vec<Project> = test::read_body_json(resp).await;
+        let page: Page<Project> = test::read_body_json(resp).await;
+        let projects = page.items;
         assert_eq!(projects.len(), 5);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_projects_with_filter() {
         // Setup
-        let db = setup_test_db("project_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -652,6 +1013,9 @@ mod tests {
                 enabled: i % 2 == 0,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
 
             let _ = test::TestRequest::post()
@@ -668,19 +1032,19 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let projects: Vec<Project> = test::read_body_json(resp).await;
+        let page: Page<Project> = test::read_body_json(resp).await;
+        let projects = page.items;
         assert_eq!(projects.len(), 3);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_projects_with_filter_and_pagination() {
         // Setup
-        let db = setup_test_db("project_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -711,6 +1075,9 @@ mod tests {
                 enabled: i % 2 == 0,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
 
             let _ = test::TestRequest::post()
@@ -727,10 +1094,10 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let projects: Vec<Project> = test::read_body_json(resp).await;
+        let page: Page<Project> = test::read_body_json(resp).await;
+        let projects = page.items;
         assert_eq!(projects.len(), 2);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 }
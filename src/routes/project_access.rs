@@ -1,135 +1,169 @@
 use crate::config::AppData;
-use crate::models::pagination::Pagination;
+use crate::errors::{AppError, Problem};
+use crate::models::pagination::{Page, Pagination};
 use crate::models::project_access::{
     ProjectAccess, ProjectAccessFilter, ProjectAccessSortableFields, ProjectAccessUpdatePayload,
 };
-use crate::models::sort::{SortBuilder, SortDirection};
+use crate::models::sort::{SortBuilder, SortDirection, SortQuery};
+use crate::routes::actor;
+use crate::routes::page_response;
+use crate::routes::parse_id;
 use crate::services::project_access_service::ProjectAccessService;
-use actix_web::{Error, HttpResponse, web};
-use mongodb::bson::uuid::Uuid;
-
+use actix_web::{HttpRequest, HttpResponse, web};
+
+#[utoipa::path(
+    post,
+    path = "/project-access",
+    tag = "project-access",
+    request_body = ProjectAccess,
+    responses(
+        (status = 200, description = "Project access created", body = ProjectAccess),
+        (status = 400, description = "Invalid project access", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_access::create")]
 pub async fn create(
     data: web::Data<AppData>,
     project_access: web::Json<ProjectAccess>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectAccessService::new(database.clone()).unwrap();
-    let project_access = service.create(project_access.into_inner()).await;
-
-    match project_access {
-        Ok(project_access) => Ok(HttpResponse::Ok().json(project_access)),
-        Err(e) => {
-            println!("Error creating project access: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectAccessService::with_repositories(&data.repositories()?)?;
+    let project_access = service.create(project_access.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(project_access))
 }
 
+#[utoipa::path(
+    get,
+    path = "/project-access/{id}",
+    tag = "project-access",
+    params(("id" = String, Path, description = "Project access id")),
+    responses(
+        (status = 200, description = "Project access found", body = ProjectAccess),
+        (status = 404, description = "Project access not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_access::read")]
 pub async fn read(
     data: web::Data<AppData>,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectAccessService::new(database.clone()).unwrap();
-    let project_access_id = Uuid::parse_str(path.into_inner()).unwrap();
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectAccessService::with_repositories(&data.repositories()?)?;
+    let project_access_id = parse_id(&path.into_inner())?;
     let project_access = service.get_project_access(project_access_id).await;
 
-    match project_access {
-        Ok(Some(project_access)) => Ok(HttpResponse::Ok().json(project_access)),
-        Ok(None) => Ok(HttpResponse::NotFound().finish()),
-        Err(e) => {
-            println!("Error getting project access: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+    match project_access? {
+        Some(project_access) => Ok(HttpResponse::Ok().json(project_access)),
+        None => Err(AppError::NotFound("Project access not found".to_string())),
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/project-access/{id}",
+    tag = "project-access",
+    params(("id" = String, Path, description = "Project access id")),
+    request_body = ProjectAccessUpdatePayload,
+    responses(
+        (status = 200, description = "Project access updated", body = ProjectAccess),
+        (status = 400, description = "Invalid project access", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Project access not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_access::update")]
 pub async fn update(
     data: web::Data<AppData>,
     path: web::Path<String>,
     payload: web::Json<ProjectAccessUpdatePayload>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectAccessService::new(database.clone()).unwrap();
-    let project_access_id = Uuid::parse_str(path.into_inner()).unwrap();
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectAccessService::with_repositories(&data.repositories()?)?;
+    let project_access_id = parse_id(&path.into_inner())?;
 
     let project_access = service
         .update(project_access_id, payload.into_inner())
-        .await;
+        .await?;
+    Ok(HttpResponse::Ok().json(project_access))
+}
 
-    match project_access {
-        Ok(project_access) => Ok(HttpResponse::Ok().json(project_access)),
-        Err(e) => {
-            println!("Error updating project access: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+#[utoipa::path(
+    delete,
+    path = "/project-access/{id}",
+    tag = "project-access",
+    params(("id" = String, Path, description = "Project access id")),
+    responses(
+        (status = 204, description = "Project access deleted"),
+        (status = 404, description = "Project access not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_access::delete")]
+pub async fn delete(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectAccessService::with_repositories(&data.repositories()?)?;
+    let project_access_id = parse_id(&path.into_inner())?;
+
+    let deleted = service.delete(project_access_id, actor(&req)).await?;
+
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound("Project access not found".to_string()))
     }
 }
 
-pub async fn delete(
+/// Handler to restore a soft-deleted project access.
+#[utoipa::path(
+    post,
+    path = "/project-access/{id}:restore",
+    tag = "project-access",
+    params(("id" = String, Path, description = "Project access id")),
+    responses(
+        (status = 200, description = "Project access restored", body = ProjectAccess),
+        (status = 404, description = "Project access not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_access::restore")]
+pub async fn restore(
     data: web::Data<AppData>,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectAccessService::new(database.clone()).unwrap();
-    let project_access_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let result = service.delete(project_access_id).await;
-
-    match result {
-        Ok(deleted) => {
-            if deleted {
-                Ok(HttpResponse::NoContent().finish())
-            } else {
-                Ok(HttpResponse::NotFound().finish())
-            }
-        }
-        Err(e) => {
-            println!("Error deleting project access: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectAccessService::with_repositories(&data.repositories()?)?;
+    let project_access_id = parse_id(&path.into_inner())?;
+
+    match service.restore(project_access_id).await? {
+        Some(restored) => Ok(HttpResponse::Ok().json(restored)),
+        None => Err(AppError::NotFound("Project access not found".to_string())),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/project-access",
+    tag = "project-access",
+    params(ProjectAccessFilter, Pagination, SortQuery),
+    responses(
+        (status = 200, description = "A page of project accesss", body = Page<ProjectAccess>,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages"))),
+        (status = 400, description = "Invalid filter, sort or cursor", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_access::list")]
 pub async fn list(
+    req: HttpRequest,
     data: web::Data<AppData>,
     query: web::Query<ProjectAccessFilter>,
     pagination: web::Query<Pagination>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectAccessService::new(database.clone()).unwrap();
+    sort: web::Query<SortQuery>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectAccessService::with_repositories(&data.repositories()?)?;
     let sort =
-        SortBuilder::new().add_sort(ProjectAccessSortableFields::Id, SortDirection::Ascending);
+        sort.into_inner()
+            .or(SortBuilder::new()
+                .add_sort(ProjectAccessSortableFields::Id, SortDirection::Ascending))?;
     let project_accesses = service
-        .find(
-            query.into_inner(),
-            Some(sort),
-            Some(pagination.into_inner()),
-        )
-        .await;
-
-    match project_accesses {
-        Ok(project_accesses) => Ok(HttpResponse::Ok().json(project_accesses)),
-        Err(e) => {
-            println!("Error listing project accesses: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+        .list(query.into_inner(), Some(sort), pagination.into_inner())
+        .await?;
+    Ok(page_response(&req, &project_accesses))
 }
 
 pub fn configure_routes(config: &mut web::ServiceConfig) {
@@ -140,6 +174,8 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
                     .route(web::post().to(create))
                     .route(web::get().to(list)),
             )
+            // Registered before "/{id}", which would otherwise match "<id>:restore"
+            .service(web::resource("/{id}:restore").route(web::post().to(restore)))
             .service(
                 web::resource("/{id}")
                     .route(web::get().to(read))
@@ -152,16 +188,18 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::models::pagination::Page;
+    use crate::repositories::registry::Repositories;
     use actix_web::{App, test};
     use chrono::Utc;
+    use mongodb::bson::uuid::Uuid;
 
     #[actix_web::test]
     async fn test_create_project_access_success() {
         // Setup
-        let db = setup_test_db("project_access_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -193,6 +231,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -209,15 +250,14 @@ mod tests {
         assert!(created_access.id.is_some());
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_project_access_success() {
         // Setup
-        let db = setup_test_db("project_access_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -250,6 +290,9 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
 
             let resp = test::TestRequest::post()
@@ -268,19 +311,19 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let project_accesses: Vec<ProjectAccess> = test::read_body_json(resp).await;
+        let page: Page<ProjectAccess> = test::read_body_json(resp).await;
+        let project_accesses = page.items;
         assert_eq!(project_accesses.len(), 3);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_project_access_with_pagination() {
         // Setup
-        let db = setup_test_db("project_access_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -313,6 +356,9 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
 
             let resp = test::TestRequest::post()
@@ -331,19 +377,19 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let project_accesses: Vec<ProjectAccess> = test::read_body_json(resp).await;
+        let page: Page<ProjectAccess> = test::read_body_json(resp).await;
+        let project_accesses = page.items;
         assert_eq!(project_accesses.len(), 2);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_project_access_with_filters() {
         // Setup
-        let db = setup_test_db("project_access_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -377,6 +423,9 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
 
             let resp = test::TestRequest::post()
@@ -395,11 +444,11 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let project_accesses: Vec<ProjectAccess> = test::read_body_json(resp).await;
+        let page: Page<ProjectAccess> = test::read_body_json(resp).await;
+        let project_accesses = page.items;
         assert_eq!(project_accesses.len(), 1);
         assert_eq!(project_accesses[0].environment_id, env_id);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 }
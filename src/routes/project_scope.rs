@@ -1,133 +1,166 @@
 use crate::config::AppData;
-use crate::models::pagination::Pagination;
+use crate::errors::{AppError, Problem};
+use crate::models::pagination::{Page, Pagination};
 use crate::models::project_scope::{
     ProjectScope, ProjectScopeFilter, ProjectScopeSortableFields, ProjectScopeUpdatePayload,
 };
-use crate::models::sort::{SortBuilder, SortDirection};
+use crate::models::sort::{SortBuilder, SortDirection, SortQuery};
+use crate::routes::actor;
+use crate::routes::page_response;
+use crate::routes::parse_id;
 use crate::services::project_scope_service::ProjectScopeService;
-use actix_web::{Error, HttpResponse, web};
-use mongodb::bson::uuid::Uuid;
-
+use actix_web::{HttpRequest, HttpResponse, web};
+
+#[utoipa::path(
+    post,
+    path = "/project-scopes",
+    tag = "project-scopes",
+    request_body = ProjectScope,
+    responses(
+        (status = 200, description = "Project scope created", body = ProjectScope),
+        (status = 400, description = "Invalid project scope", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_scope::create")]
 pub async fn create(
     data: web::Data<AppData>,
     project_scope: web::Json<ProjectScope>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectScopeService::new(database.clone()).unwrap();
-    let project_scope = service.create(project_scope.into_inner()).await;
-
-    match project_scope {
-        Ok(project_scope) => Ok(HttpResponse::Ok().json(project_scope)),
-        Err(e) => {
-            println!("Error creating project scope: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectScopeService::with_repositories(&data.repositories()?)?;
+    let project_scope = service.create(project_scope.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(project_scope))
 }
 
+#[utoipa::path(
+    get,
+    path = "/project-scopes/{id}",
+    tag = "project-scopes",
+    params(("id" = String, Path, description = "Project scope id")),
+    responses(
+        (status = 200, description = "Project scope found", body = ProjectScope),
+        (status = 404, description = "Project scope not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_scope::read")]
 pub async fn read(
     data: web::Data<AppData>,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectScopeService::new(database.clone()).unwrap();
-    let scope_id = Uuid::parse_str(path.into_inner()).unwrap();
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectScopeService::with_repositories(&data.repositories()?)?;
+    let scope_id = parse_id(&path.into_inner())?;
     let project_scope = service.get_project_scope(scope_id).await;
 
-    match project_scope {
-        Ok(Some(project_scope)) => Ok(HttpResponse::Ok().json(project_scope)),
-        Ok(None) => Ok(HttpResponse::NotFound().finish()),
-        Err(e) => {
-            println!("Error getting project scope: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+    match project_scope? {
+        Some(project_scope) => Ok(HttpResponse::Ok().json(project_scope)),
+        None => Err(AppError::NotFound("Project scope not found".to_string())),
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/project-scopes/{id}",
+    tag = "project-scopes",
+    params(("id" = String, Path, description = "Project scope id")),
+    request_body = ProjectScopeUpdatePayload,
+    responses(
+        (status = 200, description = "Project scope updated", body = ProjectScope),
+        (status = 400, description = "Invalid project scope", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Project scope not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_scope::update")]
 pub async fn update(
     data: web::Data<AppData>,
     path: web::Path<String>,
     update: web::Json<ProjectScopeUpdatePayload>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectScopeService::new(database.clone()).unwrap();
-    let scope_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let result = service.update(scope_id, update.into_inner()).await;
-
-    match result {
-        Ok(updated) => Ok(HttpResponse::Ok().json(updated)),
-        Err(e) => {
-            println!("Error updating project scope: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectScopeService::with_repositories(&data.repositories()?)?;
+    let scope_id = parse_id(&path.into_inner())?;
+
+    let result = service.update(scope_id, update.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(result))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/project-scopes/{id}",
+    tag = "project-scopes",
+    params(("id" = String, Path, description = "Project scope id")),
+    responses(
+        (status = 204, description = "Project scope deleted"),
+        (status = 404, description = "Project scope not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_scope::delete")]
 pub async fn delete(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectScopeService::with_repositories(&data.repositories()?)?;
+    let scope_id = parse_id(&path.into_inner())?;
+
+    let deleted = service.delete(scope_id, actor(&req)).await?;
+
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound("Project scope not found".to_string()))
+    }
+}
+
+/// Handler to restore a soft-deleted project scope.
+#[utoipa::path(
+    post,
+    path = "/project-scopes/{id}:restore",
+    tag = "project-scopes",
+    params(("id" = String, Path, description = "Project scope id")),
+    responses(
+        (status = 200, description = "Project scope restored", body = ProjectScope),
+        (status = 404, description = "Project scope not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_scope::restore")]
+pub async fn restore(
     data: web::Data<AppData>,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectScopeService::new(database.clone()).unwrap();
-    let scope_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let result = service.delete(scope_id).await;
-
-    match result {
-        Ok(deleted) => {
-            if deleted {
-                Ok(HttpResponse::NoContent().finish())
-            } else {
-                Ok(HttpResponse::NotFound().finish())
-            }
-        }
-        Err(e) => {
-            println!("Error deleting project scope: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectScopeService::with_repositories(&data.repositories()?)?;
+    let scope_id = parse_id(&path.into_inner())?;
+
+    match service.restore(scope_id).await? {
+        Some(restored) => Ok(HttpResponse::Ok().json(restored)),
+        None => Err(AppError::NotFound("Project scope not found".to_string())),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/project-scopes",
+    tag = "project-scopes",
+    params(ProjectScopeFilter, Pagination, SortQuery),
+    responses(
+        (status = 200, description = "A page of project scopes", body = Page<ProjectScope>,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages"))),
+        (status = 400, description = "Invalid filter, sort or cursor", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_scope::list")]
 pub async fn list(
+    req: HttpRequest,
     data: web::Data<AppData>,
     query: web::Query<ProjectScopeFilter>,
     pagination: web::Query<Pagination>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ProjectScopeService::new(database.clone()).unwrap();
-    let sort =
-        SortBuilder::new().add_sort(ProjectScopeSortableFields::Id, SortDirection::Ascending);
+    sort: web::Query<SortQuery>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectScopeService::with_repositories(&data.repositories()?)?;
+    let sort = sort
+        .into_inner()
+        .or(SortBuilder::new().add_sort(ProjectScopeSortableFields::Id, SortDirection::Ascending))?;
     let project_scopes = service
-        .find(
-            query.into_inner(),
-            Some(sort),
-            Some(pagination.into_inner()),
-        )
-        .await;
-
-    match project_scopes {
-        Ok(scopes) => Ok(HttpResponse::Ok().json(scopes)),
-        Err(e) => {
-            println!("Error listing project scopes: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+        .list(query.into_inner(), Some(sort), pagination.into_inner())
+        .await?;
+    Ok(page_response(&req, &project_scopes))
 }
 
 pub fn configure_routes(config: &mut web::ServiceConfig) {
@@ -138,6 +171,8 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
                     .route(web::post().to(create))
                     .route(web::get().to(list)),
             )
+            // Registered before "/{id}", which would otherwise match "<id>:restore"
+            .service(web::resource("/{id}:restore").route(web::post().to(restore)))
             .service(
                 web::resource("/{id}")
                     .route(web::get().to(read))
@@ -150,15 +185,17 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::models::pagination::Page;
+    use crate::repositories::registry::Repositories;
     use actix_web::{App, test};
     use chrono::Utc;
+    use mongodb::bson::uuid::Uuid;
 
     #[actix_web::test]
     async fn test_create_project_scope_success() {
-        let db = setup_test_db("project_scope_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -188,6 +225,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -202,15 +242,13 @@ mod tests {
         assert_eq!(created_scope.description, project_scope.description);
         assert!(created_scope.enabled);
         assert!(created_scope.id.is_some());
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_get_project_scope_success() {
-        let db = setup_test_db("project_scope_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -240,6 +278,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -260,15 +301,13 @@ mod tests {
         let retrieved_scope: ProjectScope = test::read_body_json(resp).await;
         assert_eq!(retrieved_scope.id, created_scope.id);
         assert_eq!(retrieved_scope.name, created_scope.name);
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_update_project_scope_success() {
-        let db = setup_test_db("project_scope_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -298,6 +337,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -313,6 +355,7 @@ mod tests {
             name: Some("write:users".to_string()),
             description: Some("Updated Description".to_string()),
             enabled: Some(false),
+            labels: None,
         };
 
         let resp = test::TestRequest::patch()
@@ -326,15 +369,13 @@ mod tests {
         assert_eq!(updated_scope.name, "write:users");
         assert_eq!(updated_scope.description, "Updated Description");
         assert!(!updated_scope.enabled);
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_delete_project_scope_success() {
-        let db = setup_test_db("project_scope_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -364,6 +405,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let resp = test::TestRequest::post()
@@ -388,15 +432,13 @@ mod tests {
             .await;
 
         assert!(resp.status().is_client_error());
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_project_scopes_pagination() {
-        let db = setup_test_db("project_scope_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -426,6 +468,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let project_scope2 = ProjectScope {
@@ -436,6 +481,9 @@ mod tests {
             enabled: false,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         test::TestRequest::post()
@@ -456,17 +504,16 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let scopes: Vec<ProjectScope> = test::read_body_json(resp).await;
+        let page: Page<ProjectScope> = test::read_body_json(resp).await;
+        let scopes = page.items;
         assert_eq!(scopes.len(), 1);
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_project_scopes_filter_by_name() {
-        let db = setup_test_db("project_scope_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -496,6 +543,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let project_scope2 = ProjectScope {
@@ -506,6 +556,9 @@ mod tests {
             enabled: false,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         test::TestRequest::post()
@@ -526,18 +579,17 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let scopes: Vec<ProjectScope> = test::read_body_json(resp).await;
+        let page: Page<ProjectScope> = test::read_body_json(resp).await;
+        let scopes = page.items;
         assert_eq!(scopes.len(), 1);
         assert_eq!(scopes[0].name, "read:users");
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_project_scopes_filter_by_enabled() {
-        let db = setup_test_db("project_scope_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -567,6 +619,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let project_scope2 = ProjectScope {
@@ -577,6 +632,9 @@ mod tests {
             enabled: false,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         test::TestRequest::post()
@@ -597,10 +655,9 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let scopes: Vec<ProjectScope> = test::read_body_json(resp).await;
+        let page: Page<ProjectScope> = test::read_body_json(resp).await;
+        let scopes = page.items;
         assert_eq!(scopes.len(), 1);
         assert!(scopes[0].enabled);
-
-        cleanup_test_db(db).await.unwrap();
     }
 }
@@ -0,0 +1,257 @@
+use crate::config::AppData;
+use crate::errors::{AppError, Problem};
+use crate::models::pagination::{Page, Pagination};
+use crate::models::project_template::{
+    ProjectTemplate, ProjectTemplateFilter, ProjectTemplateSortableFields,
+    ProjectTemplateUpdatePayload,
+};
+use crate::models::sort::{SortBuilder, SortDirection, SortQuery};
+use crate::routes::actor;
+use crate::routes::page_response;
+use crate::routes::parse_id;
+use crate::services::project_template_service::ProjectTemplateService;
+use actix_web::{HttpRequest, HttpResponse, web};
+
+#[utoipa::path(
+    post,
+    path = "/project-templates",
+    tag = "project-templates",
+    request_body = ProjectTemplate,
+    responses(
+        (status = 200, description = "Project template created", body = ProjectTemplate),
+        (status = 400, description = "Invalid project template", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_template::create")]
+pub async fn create(
+    data: web::Data<AppData>,
+    project_template: web::Json<ProjectTemplate>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectTemplateService::with_repositories(&data.repositories()?)?;
+    let project_template = service.create(project_template.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(project_template))
+}
+
+#[utoipa::path(
+    get,
+    path = "/project-templates/{id}",
+    tag = "project-templates",
+    params(("id" = String, Path, description = "Project template id")),
+    responses(
+        (status = 200, description = "Project template found", body = ProjectTemplate),
+        (status = 404, description = "Project template not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_template::read")]
+pub async fn read(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectTemplateService::with_repositories(&data.repositories()?)?;
+    let template_id = parse_id(&path.into_inner())?;
+    let project_template = service.get_project_template(template_id).await;
+
+    match project_template? {
+        Some(project_template) => Ok(HttpResponse::Ok().json(project_template)),
+        None => Err(AppError::NotFound("Project template not found".to_string())),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/project-templates/{id}",
+    tag = "project-templates",
+    params(("id" = String, Path, description = "Project template id")),
+    request_body = ProjectTemplateUpdatePayload,
+    responses(
+        (status = 200, description = "Project template updated", body = ProjectTemplate),
+        (status = 400, description = "Invalid project template", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Project template not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_template::update")]
+pub async fn update(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+    update: web::Json<ProjectTemplateUpdatePayload>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectTemplateService::with_repositories(&data.repositories()?)?;
+    let template_id = parse_id(&path.into_inner())?;
+
+    let result = service.update(template_id, update.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/project-templates/{id}",
+    tag = "project-templates",
+    params(("id" = String, Path, description = "Project template id")),
+    responses(
+        (status = 204, description = "Project template deleted"),
+        (status = 404, description = "Project template not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_template::delete")]
+pub async fn delete(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectTemplateService::with_repositories(&data.repositories()?)?;
+    let template_id = parse_id(&path.into_inner())?;
+
+    let deleted = service.delete(template_id, actor(&req)).await?;
+
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound("Project template not found".to_string()))
+    }
+}
+
+/// Handler to restore a soft-deleted project template.
+#[utoipa::path(
+    post,
+    path = "/project-templates/{id}:restore",
+    tag = "project-templates",
+    params(("id" = String, Path, description = "Project template id")),
+    responses(
+        (status = 200, description = "Project template restored", body = ProjectTemplate),
+        (status = 404, description = "Project template not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_template::restore")]
+pub async fn restore(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectTemplateService::with_repositories(&data.repositories()?)?;
+    let template_id = parse_id(&path.into_inner())?;
+
+    match service.restore(template_id).await? {
+        Some(restored) => Ok(HttpResponse::Ok().json(restored)),
+        None => Err(AppError::NotFound("Project template not found".to_string())),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/project-templates",
+    tag = "project-templates",
+    params(ProjectTemplateFilter, Pagination, SortQuery),
+    responses(
+        (status = 200, description = "A page of project templates", body = Page<ProjectTemplate>,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages"))),
+        (status = 400, description = "Invalid filter, sort or cursor", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "project_template::list")]
+pub async fn list(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    query: web::Query<ProjectTemplateFilter>,
+    pagination: web::Query<Pagination>,
+    sort: web::Query<SortQuery>,
+) -> Result<HttpResponse, AppError> {
+    let service = ProjectTemplateService::with_repositories(&data.repositories()?)?;
+    let sort =
+        sort.into_inner().or(SortBuilder::new()
+            .add_sort(ProjectTemplateSortableFields::Id, SortDirection::Ascending))?;
+    let project_templates = service
+        .list(query.into_inner(), Some(sort), pagination.into_inner())
+        .await?;
+    Ok(page_response(&req, &project_templates))
+}
+
+pub fn configure_routes(config: &mut web::ServiceConfig) {
+    config.service(
+        web::scope("/project-templates")
+            .service(
+                web::resource("")
+                    .route(web::post().to(create))
+                    .route(web::get().to(list)),
+            )
+            // Registered before "/{id}", which would otherwise match "<id>:restore"
+            .service(web::resource("/{id}:restore").route(web::post().to(restore)))
+            .service(
+                web::resource("/{id}")
+                    .route(web::get().to(read))
+                    .route(web::patch().to(update))
+                    .route(web::delete().to(delete)),
+            ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::pagination::Page;
+    use crate::repositories::registry::Repositories;
+    use actix_web::{App, test};
+
+    #[actix_web::test]
+    async fn test_create_and_list_project_templates() {
+        let repositories = Repositories::in_memory();
+        let app_data = web::Data::new(AppData {
+            repositories: Some(repositories.clone()),
+            ..Default::default()
+        });
+
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data.clone())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let mut template = ProjectTemplate::standard();
+        template.name = "minimal".to_string();
+        template.project_scopes.clear();
+
+        let resp = test::TestRequest::post()
+            .uri("/project-templates")
+            .set_json(&template)
+            .send_request(&app)
+            .await;
+        assert!(resp.status().is_success());
+        let created: ProjectTemplate = test::read_body_json(resp).await;
+        assert!(created.id.is_some());
+        assert_eq!(created.environments, template.environments);
+
+        let resp = test::TestRequest::get()
+            .uri("/project-templates?name=minimal")
+            .send_request(&app)
+            .await;
+        assert!(resp.status().is_success());
+        let page: Page<ProjectTemplate> = test::read_body_json(resp).await;
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, created.id);
+    }
+
+    #[actix_web::test]
+    async fn test_create_project_template_with_duplicate_environments() {
+        let repositories = Repositories::in_memory();
+        let app_data = web::Data::new(AppData {
+            repositories: Some(repositories.clone()),
+            ..Default::default()
+        });
+
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data.clone())
+                .configure(configure_routes),
+        )
+        .await;
+
+        let mut template = ProjectTemplate::standard();
+        template.environments.push(template.environments[0].clone());
+
+        let resp = test::TestRequest::post()
+            .uri("/project-templates")
+            .set_json(&template)
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), 400);
+    }
+}
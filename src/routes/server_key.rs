@@ -1,141 +1,199 @@
 use crate::config::AppData;
-use crate::models::pagination::Pagination;
-use crate::models::server_key::{ServerKeyCreatePayload, ServerKeyFilter, ServerKeyUpdatePayload};
+use crate::errors::{AppError, Problem};
+use crate::models::pagination::{Page, Pagination};
+use crate::models::server_key::{
+    ServerKeyCreatePayload, ServerKeyFilter, ServerKeyRead, ServerKeySortableFields,
+    ServerKeyUpdatePayload,
+};
+use crate::models::sort::{SortBuilder, SortDirection, SortQuery};
+use crate::routes::actor;
+use crate::routes::page_response;
+use crate::routes::parse_id;
 use crate::services::server_key_service::ServerKeyService;
-use actix_web::{Error, HttpResponse, web};
-use mongodb::bson::uuid::Uuid;
+use actix_web::{HttpRequest, HttpResponse, web};
 
 /// Handler to create a new server key.
+#[utoipa::path(
+    post,
+    path = "/server-keys",
+    tag = "server-keys",
+    request_body = ServerKeyCreatePayload,
+    responses(
+        (status = 200, description = "Server key created", body = ServerKeyRead),
+        (status = 400, description = "Invalid server key", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "server_key::create")]
 pub async fn create(
     data: web::Data<AppData>,
     payload: web::Json<ServerKeyCreatePayload>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServerKeyService::new(database.clone())
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-    let server_key = service.create(payload.into_inner()).await;
-    match server_key {
-        Ok(server_key) => Ok(HttpResponse::Ok().json(server_key)),
-        Err(e) => {
-            println!("Error creating server key: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = ServerKeyService::with_repositories(&data.repositories()?)?;
+    let server_key = service.create(payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(server_key))
 }
 
 /// Handler to retrieve a server key by its ID.
+#[utoipa::path(
+    get,
+    path = "/server-keys/{id}",
+    tag = "server-keys",
+    params(("id" = String, Path, description = "Server key id")),
+    responses(
+        (status = 200, description = "Server key found", body = ServerKeyRead),
+        (status = 404, description = "Server key not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "server_key::read")]
 pub async fn read(
     data: web::Data<AppData>,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServerKeyService::new(database.clone())
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-    let server_key_id = Uuid::parse_str(path.into_inner())
-        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid UUID format"))?;
+) -> Result<HttpResponse, AppError> {
+    let service = ServerKeyService::with_repositories(&data.repositories()?)?;
+    let server_key_id = parse_id(&path.into_inner())?;
     let server_key = service.get(server_key_id).await;
-    match server_key {
-        Ok(Some(server_key_read)) => Ok(HttpResponse::Ok().json(server_key_read)),
-        Ok(None) => Ok(HttpResponse::NotFound().finish()),
-        Err(e) => {
-            println!("Error getting server key: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+    match server_key? {
+        Some(server_key_read) => Ok(HttpResponse::Ok().json(server_key_read)),
+        None => Err(AppError::NotFound("Server key not found".to_string())),
     }
 }
 
 /// Handler to update an existing server key.
+#[utoipa::path(
+    patch,
+    path = "/server-keys/{id}",
+    tag = "server-keys",
+    params(("id" = String, Path, description = "Server key id")),
+    request_body = ServerKeyUpdatePayload,
+    responses(
+        (status = 200, description = "Server key updated", body = ServerKeyRead),
+        (status = 400, description = "Invalid server key", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Server key not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "server_key::update")]
 pub async fn update(
     data: web::Data<AppData>,
     path: web::Path<String>,
     payload: web::Json<ServerKeyUpdatePayload>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServerKeyService::new(database.clone())
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-    let server_key_id = Uuid::parse_str(path.into_inner())
-        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid UUID format"))?;
-
-    let server_key = service.update(server_key_id, payload.into_inner()).await;
-
-    match server_key {
-        Ok(server_key) => Ok(HttpResponse::Ok().json(server_key)),
-        Err(e) => {
-            println!("Error updating server key: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = ServerKeyService::with_repositories(&data.repositories()?)?;
+    let server_key_id = parse_id(&path.into_inner())?;
+
+    let server_key = service.update(server_key_id, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(server_key))
 }
 
 /// Handler to delete a server key by its ID.
+#[utoipa::path(
+    delete,
+    path = "/server-keys/{id}",
+    tag = "server-keys",
+    params(("id" = String, Path, description = "Server key id")),
+    responses(
+        (status = 204, description = "Server key deleted"),
+        (status = 404, description = "Server key not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "server_key::delete")]
 pub async fn delete(
     data: web::Data<AppData>,
+    req: HttpRequest,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServerKeyService::new(database.clone())
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-    let server_key_id = Uuid::parse_str(path.into_inner())
-        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid UUID format"))?;
-
-    let result = service.delete(server_key_id).await;
-
-    match result {
-        Ok(deleted) => {
-            if deleted {
-                Ok(HttpResponse::NoContent().finish())
-            } else {
-                Ok(HttpResponse::NotFound().finish())
-            }
-        }
-        Err(e) => {
-            println!("Error deleting server key: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+) -> Result<HttpResponse, AppError> {
+    let service = ServerKeyService::with_repositories(&data.repositories()?)?;
+    let server_key_id = parse_id(&path.into_inner())?;
+
+    let deleted = service.delete(server_key_id, actor(&req)).await?;
+
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound("Server key not found".to_string()))
+    }
+}
+
+/// Handler to restore a soft-deleted server key.
+#[utoipa::path(
+    post,
+    path = "/server-keys/{id}:restore",
+    tag = "server-keys",
+    params(("id" = String, Path, description = "Server key id")),
+    responses(
+        (status = 200, description = "Server key restored", body = ServerKeyRead),
+        (status = 404, description = "Server key not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "server_key::restore")]
+pub async fn restore(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = ServerKeyService::with_repositories(&data.repositories()?)?;
+    let server_key_id = parse_id(&path.into_inner())?;
+
+    match service.restore(server_key_id).await? {
+        Some(restored) => Ok(HttpResponse::Ok().json(restored)),
+        None => Err(AppError::NotFound("Server key not found".to_string())),
+    }
+}
+
+/// Handler to rotate a server key, replacing it with a new one.
+#[utoipa::path(
+    post,
+    path = "/server-keys/{id}:rotate",
+    tag = "server-keys",
+    params(("id" = String, Path, description = "Server key id")),
+    responses(
+        (status = 200, description = "Server key rotated; the new key", body = ServerKeyRead),
+        (status = 404, description = "Server key not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "server_key::rotate")]
+pub async fn rotate(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = ServerKeyService::with_repositories(&data.repositories()?)?;
+    let server_key_id = parse_id(&path.into_inner())?;
+
+    match service.rotate(server_key_id, actor(&req)).await? {
+        Some(rotated) => Ok(HttpResponse::Ok().json(rotated)),
+        None => Err(AppError::NotFound("Server key not found".to_string())),
     }
 }
 
 /// Handler to list server keys with filtering and pagination.
+#[utoipa::path(
+    get,
+    path = "/server-keys",
+    tag = "server-keys",
+    params(ServerKeyFilter, Pagination, SortQuery),
+    responses(
+        (status = 200, description = "A page of server keys", body = Page<ServerKeyRead>,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages"))),
+        (status = 400, description = "Invalid filter, sort or cursor", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "server_key::list")]
 pub async fn list(
+    req: HttpRequest,
     data: web::Data<AppData>,
     filter: Option<web::Query<ServerKeyFilter>>,
     pagination: web::Query<Pagination>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServerKeyService::new(database.clone())
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-
-    dbg!(filter.clone());
-    dbg!(pagination.clone());
-    dbg!(&service);
+    sort: web::Query<SortQuery>,
+) -> Result<HttpResponse, AppError> {
+    let service = ServerKeyService::with_repositories(&data.repositories()?)?;
 
     let pagination = pagination.into_inner();
     let filter = filter.map(|f| f.into_inner()).unwrap_or_default();
+    let sort = sort
+        .into_inner()
+        .or(SortBuilder::new().add_sort(ServerKeySortableFields::Id, SortDirection::Ascending))?;
 
-    let result = service.find(filter, None, Some(pagination)).await;
-
-    match result {
-        Ok(server_key_reads) => Ok(HttpResponse::Ok().json(server_key_reads)),
-        Err(e) => {
-            println!("Error listing server keys: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+    let result = service.list(filter, Some(sort), pagination).await?;
+    Ok(page_response(&req, &result))
 }
 
 /// Configures the routes for server keys.
@@ -147,6 +205,9 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
                     .route(web::post().to(create))
                     .route(web::get().to(list)),
             )
+            // Registered before "/{id}", which would otherwise match "<id>:restore"
+            .service(web::resource("/{id}:restore").route(web::post().to(restore)))
+            .service(web::resource("/{id}:rotate").route(web::post().to(rotate)))
             .service(
                 web::resource("/{id}")
                     .route(web::get().to(read))
@@ -159,17 +220,19 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::pagination::Page;
     use crate::models::server_key::ServerKeyRead;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::repositories::registry::Repositories;
     use actix_web::{App, test};
     use jsonwebtoken::Algorithm;
+    use mongodb::bson::uuid::Uuid;
 
     #[actix_web::test]
     async fn test_list_server_keys_no_filter() {
         // Setup
-        let db = setup_test_db("server_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -200,20 +263,20 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let server_keys: Vec<ServerKeyRead> = test::read_body_json(resp).await;
+        let page: Page<ServerKeyRead> = test::read_body_json(resp).await;
+        let server_keys = page.items;
         assert_eq!(server_keys.len(), 5);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_server_keys_with_filter() {
         // Set the master encryption key for testing
         // Setup
-        let db = setup_test_db("server_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -251,7 +314,8 @@ mod tests {
             .await;
 
         assert_eq!(resp.status(), 200);
-        let server_keys: Vec<ServerKeyRead> = test::read_body_json(resp).await;
+        let page: Page<ServerKeyRead> = test::read_body_json(resp).await;
+        let server_keys = page.items;
         assert_eq!(server_keys.len(), 5);
         assert!(
             server_keys
@@ -260,15 +324,14 @@ mod tests {
         );
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_create_server_key_success() {
         // Setup
-        let db = setup_test_db("server_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -300,15 +363,14 @@ mod tests {
         assert_eq!(created_key.algorithm, Algorithm::HS256);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_get_server_key_success() {
         // Set the master encryption key for testing
-        let db = setup_test_db("server_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -349,15 +411,14 @@ mod tests {
         assert_eq!(retrieved_key.algorithm, Algorithm::HS256);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_update_server_key_success() {
         // Setup
-        let db = setup_test_db("server_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -404,15 +465,14 @@ mod tests {
         assert_eq!(updated_key.algorithm, Algorithm::HS256);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_delete_server_key_success() {
         // Setup
-        let db = setup_test_db("server_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -456,6 +516,5 @@ mod tests {
         assert_eq!(resp.status(), 404);
 
         // Cleanup
-        cleanup_test_db(db).await.unwrap();
     }
 }
@@ -1,137 +1,170 @@
 use crate::config::AppData;
-use crate::models::pagination::Pagination;
+use crate::errors::{AppError, Problem};
+use crate::models::pagination::{Page, Pagination};
 use crate::models::service_account::{
     ServiceAccount, ServiceAccountFilter, ServiceAccountSortableFields, ServiceAccountUpdatePayload,
 };
-use crate::models::sort::{SortBuilder, SortDirection};
+use crate::models::sort::{SortBuilder, SortDirection, SortQuery};
+use crate::routes::actor;
+use crate::routes::page_response;
+use crate::routes::parse_id;
 use crate::services::service_account_service::ServiceAccountService;
-use actix_web::{Error, HttpResponse, web};
-use mongodb::bson::uuid::Uuid;
-
+use actix_web::{HttpRequest, HttpResponse, web};
+
+#[utoipa::path(
+    post,
+    path = "/service-accounts",
+    tag = "service-accounts",
+    request_body = ServiceAccount,
+    responses(
+        (status = 200, description = "Service account created", body = ServiceAccount),
+        (status = 400, description = "Invalid service account", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account::create")]
 pub async fn create(
     data: web::Data<AppData>,
     service_account: web::Json<ServiceAccount>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServiceAccountService::new(database.clone()).unwrap();
-    let service_account = service.create(service_account.into_inner()).await;
-
-    match service_account {
-        Ok(service_account) => Ok(HttpResponse::Ok().json(service_account)),
-        Err(e) => {
-            println!("Error creating service account: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountService::with_repositories(&data.repositories()?)?;
+    let service_account = service.create(service_account.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(service_account))
 }
 
+#[utoipa::path(
+    get,
+    path = "/service-accounts/{id}",
+    tag = "service-accounts",
+    params(("id" = String, Path, description = "Service account id")),
+    responses(
+        (status = 200, description = "Service account found", body = ServiceAccount),
+        (status = 404, description = "Service account not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account::read")]
 pub async fn read(
     data: web::Data<AppData>,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServiceAccountService::new(database.clone()).unwrap();
-    let service_account_id = Uuid::parse_str(path.into_inner()).unwrap();
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountService::with_repositories(&data.repositories()?)?;
+    let service_account_id = parse_id(&path.into_inner())?;
     let service_account = service.get_service_account(service_account_id).await;
 
-    match service_account {
-        Ok(Some(service_account)) => Ok(HttpResponse::Ok().json(service_account)),
-        Ok(None) => Ok(HttpResponse::NotFound().finish()),
-        Err(e) => {
-            println!("Error getting service account: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+    match service_account? {
+        Some(service_account) => Ok(HttpResponse::Ok().json(service_account)),
+        None => Err(AppError::NotFound("Service account not found".to_string())),
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/service-accounts/{id}",
+    tag = "service-accounts",
+    params(("id" = String, Path, description = "Service account id")),
+    request_body = ServiceAccountUpdatePayload,
+    responses(
+        (status = 200, description = "Service account updated", body = ServiceAccount),
+        (status = 400, description = "Invalid service account", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Service account not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account::update_service_account")]
 pub async fn update_service_account(
     data: web::Data<AppData>,
     path: web::Path<String>,
     payload: web::Json<ServiceAccountUpdatePayload>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServiceAccountService::new(database.clone()).unwrap();
-    let service_account_id = Uuid::parse_str(path.into_inner()).unwrap();
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountService::with_repositories(&data.repositories()?)?;
+    let service_account_id = parse_id(&path.into_inner())?;
 
     let service_account = service
         .update(service_account_id, payload.into_inner())
-        .await;
+        .await?;
+    Ok(HttpResponse::Ok().json(service_account))
+}
 
-    match service_account {
-        Ok(service_account) => Ok(HttpResponse::Ok().json(service_account)),
-        Err(e) => {
-            println!("Error updating service account: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+#[utoipa::path(
+    delete,
+    path = "/service-accounts/{id}",
+    tag = "service-accounts",
+    params(("id" = String, Path, description = "Service account id")),
+    responses(
+        (status = 204, description = "Service account deleted"),
+        (status = 404, description = "Service account not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account::delete")]
+pub async fn delete(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountService::with_repositories(&data.repositories()?)?;
+    let service_account_id = parse_id(&path.into_inner())?;
+
+    let deleted = service.delete(service_account_id, actor(&req)).await?;
+
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound("Service account not found".to_string()))
     }
 }
 
-pub async fn delete(
+/// Handler to restore a soft-deleted service account.
+#[utoipa::path(
+    post,
+    path = "/service-accounts/{id}:restore",
+    tag = "service-accounts",
+    params(("id" = String, Path, description = "Service account id")),
+    responses(
+        (status = 200, description = "Service account restored", body = ServiceAccount),
+        (status = 404, description = "Service account not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account::restore")]
+pub async fn restore(
     data: web::Data<AppData>,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServiceAccountService::new(database.clone()).unwrap();
-    let service_account_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let result = service.delete(service_account_id).await;
-
-    match result {
-        Ok(deleted) => {
-            if deleted {
-                Ok(HttpResponse::NoContent().finish())
-            } else {
-                Ok(HttpResponse::NotFound().finish())
-            }
-        }
-        Err(e) => {
-            println!("Error deleting service account: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountService::with_repositories(&data.repositories()?)?;
+    let service_account_id = parse_id(&path.into_inner())?;
+
+    match service.restore(service_account_id).await? {
+        Some(restored) => Ok(HttpResponse::Ok().json(restored)),
+        None => Err(AppError::NotFound("Service account not found".to_string())),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/service-accounts",
+    tag = "service-accounts",
+    params(ServiceAccountFilter, Pagination, SortQuery),
+    responses(
+        (status = 200, description = "A page of service accounts", body = Page<ServiceAccount>,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages"))),
+        (status = 400, description = "Invalid filter, sort or cursor", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account::list")]
 pub async fn list(
+    req: HttpRequest,
     data: web::Data<AppData>,
     query: web::Query<ServiceAccountFilter>,
     pagination: web::Query<Pagination>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServiceAccountService::new(database.clone()).unwrap();
+    sort: web::Query<SortQuery>,
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountService::with_repositories(&data.repositories()?)?;
     let sort =
-        SortBuilder::new().add_sort(ServiceAccountSortableFields::Id, SortDirection::Ascending);
+        sort.into_inner()
+            .or(SortBuilder::new()
+                .add_sort(ServiceAccountSortableFields::Id, SortDirection::Ascending))?;
     let service_accounts = service
-        .find(
-            query.into_inner(),
-            Some(sort),
-            Some(pagination.into_inner()),
-        )
-        .await;
-
-    dbg!(&service_accounts);
+        .list(query.into_inner(), Some(sort), pagination.into_inner())
+        .await?;
 
-    match service_accounts {
-        Ok(service_accounts) => Ok(HttpResponse::Ok().json(service_accounts)),
-        Err(e) => {
-            println!("Error listing service accounts: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+    Ok(page_response(&req, &service_accounts))
 }
 
 pub fn configure_routes(config: &mut web::ServiceConfig) {
@@ -142,6 +175,8 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
                     .route(web::post().to(create))
                     .route(web::get().to(list)),
             )
+            // Registered before "/{id}", which would otherwise match "<id>:restore"
+            .service(web::resource("/{id}:restore").route(web::post().to(restore)))
             .service(
                 web::resource("/{id}")
                     .route(web::get().to(read))
@@ -153,18 +188,17 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
-
     use super::*;
+    use crate::models::pagination::Page;
     use crate::models::service_account::ServiceAccount;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::repositories::registry::Repositories;
     use actix_web::{App, test};
 
     #[actix_web::test]
     async fn test_create_service_account_success() {
-        let db = setup_test_db("service_account_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -193,15 +227,13 @@ mod tests {
         assert_eq!(created_service_account.email, service_account.email);
         assert_eq!(created_service_account.user, service_account.user);
         assert!(created_service_account.id.is_some());
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_get_service_account_success() {
-        let db = setup_test_db("service_account_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -219,7 +251,7 @@ mod tests {
             "secret123".to_string(),
         );
 
-        let created_service_account = ServiceAccountService::new(Arc::new(db.clone()))
+        let created_service_account = ServiceAccountService::with_repositories(&repositories)
             .unwrap()
             .create(service_account.clone())
             .await
@@ -237,15 +269,13 @@ mod tests {
         let retrieved_service_account: ServiceAccount = test::read_body_json(resp).await;
         assert_eq!(retrieved_service_account.email, service_account.email);
         assert_eq!(retrieved_service_account.user, service_account.user);
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_update_service_account_success() {
-        let db = setup_test_db("service_account_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -264,7 +294,7 @@ mod tests {
             "secret123".to_string(),
         );
 
-        let created_service_account = ServiceAccountService::new(Arc::new(db.clone()))
+        let created_service_account = ServiceAccountService::with_repositories(&repositories)
             .unwrap()
             .create(service_account.clone())
             .await
@@ -275,6 +305,8 @@ mod tests {
             user: Some("newuser".to_string()),
             secret: Some("newsecret".to_string()),
             enabled: Some(false),
+            labels: None,
+            certificate_bindings: None,
         };
 
         let resp = test::TestRequest::patch()
@@ -292,15 +324,13 @@ mod tests {
         assert_eq!(updated_service_account.user, "newuser");
         assert_eq!(updated_service_account.secret, "newsecret");
         assert!(!updated_service_account.enabled);
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_delete_service_account_success() {
-        let db = setup_test_db("service_account_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -318,7 +348,7 @@ mod tests {
             "secret123".to_string(),
         );
 
-        let created_service_account = ServiceAccountService::new(Arc::new(db.clone()))
+        let created_service_account = ServiceAccountService::with_repositories(&repositories)
             .unwrap()
             .create(service_account.clone())
             .await
@@ -343,15 +373,13 @@ mod tests {
             .await;
 
         assert!(resp.status().is_client_error());
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_service_accounts_with_pagination() {
-        let db = setup_test_db("service_account_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -367,7 +395,7 @@ mod tests {
                 "secret123".to_string(),
             );
 
-            let _ = ServiceAccountService::new(Arc::new(db.clone()))
+            let _ = ServiceAccountService::with_repositories(&repositories)
                 .unwrap()
                 .create(service_account)
                 .await;
@@ -380,17 +408,16 @@ mod tests {
 
         let status = resp.status();
         assert!(status.is_success());
-        let service_accounts: Vec<ServiceAccount> = test::read_body_json(resp).await;
+        let page: Page<ServiceAccount> = test::read_body_json(resp).await;
+        let service_accounts = page.items;
         assert_eq!(service_accounts.len(), 5);
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_service_accounts_with_filter() {
-        let db = setup_test_db("service_account_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -406,7 +433,7 @@ mod tests {
                 "secret123".to_string(),
             );
 
-            let _ = ServiceAccountService::new(Arc::new(db.clone()))
+            let _ = ServiceAccountService::with_repositories(&repositories)
                 .unwrap()
                 .create(service_account)
                 .await;
@@ -419,17 +446,16 @@ mod tests {
 
         let status = resp.status();
         assert!(status.is_success());
-        let service_accounts: Vec<ServiceAccount> = test::read_body_json(resp).await;
+        let page: Page<ServiceAccount> = test::read_body_json(resp).await;
+        let service_accounts = page.items;
         assert_eq!(service_accounts.len(), 1);
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_service_accounts_with_filter_and_pagination() {
-        let db = setup_test_db("service_account_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -445,7 +471,7 @@ mod tests {
                 "secret123".to_string(),
             );
 
-            let _ = ServiceAccountService::new(Arc::new(db.clone()))
+            let _ = ServiceAccountService::with_repositories(&repositories)
                 .unwrap()
                 .create(service_account)
                 .await;
@@ -458,9 +484,8 @@ mod tests {
 
         let status = resp.status();
         assert!(status.is_success());
-        let service_accounts: Vec<ServiceAccount> = test::read_body_json(resp).await;
+        let page: Page<ServiceAccount> = test::read_body_json(resp).await;
+        let service_accounts = page.items;
         assert_eq!(service_accounts.len(), 1);
-
-        cleanup_test_db(db).await.unwrap();
     }
 }
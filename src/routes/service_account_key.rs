@@ -1,133 +1,175 @@
 use crate::config::AppData;
-use crate::models::pagination::Pagination;
+use crate::errors::{AppError, Problem};
+use crate::models::pagination::{Page, Pagination};
 use crate::models::service_account_key::{
     ServiceAccountKey, ServiceAccountKeyFilter, ServiceAccountKeySortableFields,
     ServiceAccountKeyUpdatePayload,
 };
-use crate::models::sort::{SortBuilder, SortDirection};
+use crate::models::sort::{SortBuilder, SortDirection, SortQuery};
+use crate::routes::actor;
+use crate::routes::page_response;
+use crate::routes::parse_id;
 use crate::services::service_account_key_service::ServiceAccountKeyService;
-use actix_web::{Error, HttpResponse, web};
-use mongodb::bson::uuid::Uuid;
-
+use actix_web::{HttpRequest, HttpResponse, web};
+
+#[utoipa::path(
+    post,
+    path = "/service_account_keys",
+    tag = "service-account-keys",
+    request_body = ServiceAccountKey,
+    responses(
+        (status = 200, description = "Service account key created", body = ServiceAccountKey),
+        (status = 400, description = "Invalid service account key", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account_key::create")]
 pub async fn create(
     data: web::Data<AppData>,
     service_account_key: web::Json<ServiceAccountKey>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServiceAccountKeyService::new(database.clone()).unwrap();
-    let service_account_key = service.create(service_account_key.into_inner()).await;
-
-    match service_account_key {
-        Ok(key) => Ok(HttpResponse::Ok().json(key)),
-        Err(e) => {
-            println!("Error creating service account key: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountKeyService::with_repositories(&data.repositories()?)?;
+    let service_account_key = service.create(service_account_key.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(service_account_key))
 }
 
+#[utoipa::path(
+    get,
+    path = "/service_account_keys/{id}",
+    tag = "service-account-keys",
+    params(("id" = String, Path, description = "Service account key id")),
+    responses(
+        (status = 200, description = "Service account key found", body = ServiceAccountKey),
+        (status = 404, description = "Service account key not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account_key::read")]
 pub async fn read(
     data: web::Data<AppData>,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServiceAccountKeyService::new(database.clone()).unwrap();
-    let key_id = Uuid::parse_str(path.into_inner()).unwrap();
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountKeyService::with_repositories(&data.repositories()?)?;
+    let key_id = parse_id(&path.into_inner())?;
     let service_account_key = service.get_service_account_key(key_id).await;
 
-    match service_account_key {
-        Ok(Some(key)) => Ok(HttpResponse::Ok().json(key)),
-        Ok(None) => Ok(HttpResponse::NotFound().finish()),
-        Err(e) => {
-            println!("Error getting service account key: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+    match service_account_key? {
+        Some(key) => Ok(HttpResponse::Ok().json(key)),
+        None => Err(AppError::NotFound(
+            "Service account key not found".to_string(),
+        )),
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/service_account_keys/{id}",
+    tag = "service-account-keys",
+    params(("id" = String, Path, description = "Service account key id")),
+    request_body = ServiceAccountKeyUpdatePayload,
+    responses(
+        (status = 200, description = "Service account key updated", body = ServiceAccountKey),
+        (status = 400, description = "Invalid service account key", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Service account key not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account_key::update")]
 pub async fn update(
     data: web::Data<AppData>,
     path: web::Path<String>,
     payload: web::Json<ServiceAccountKeyUpdatePayload>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServiceAccountKeyService::new(database.clone()).unwrap();
-    let key_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let service_account_key = service.update(key_id, payload.into_inner()).await;
-
-    match service_account_key {
-        Ok(key) => Ok(HttpResponse::Ok().json(key)),
-        Err(e) => {
-            println!("Error updating service account key: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountKeyService::with_repositories(&data.repositories()?)?;
+    let key_id = parse_id(&path.into_inner())?;
+
+    let service_account_key = service.update(key_id, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(service_account_key))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/service_account_keys/{id}",
+    tag = "service-account-keys",
+    params(("id" = String, Path, description = "Service account key id")),
+    responses(
+        (status = 204, description = "Service account key deleted"),
+        (status = 404, description = "Service account key not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account_key::delete")]
 pub async fn delete(
     data: web::Data<AppData>,
+    req: HttpRequest,
     path: web::Path<String>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServiceAccountKeyService::new(database.clone()).unwrap();
-    let key_id = Uuid::parse_str(path.into_inner()).unwrap();
-
-    let result = service.delete(key_id).await;
-
-    match result {
-        Ok(deleted) => {
-            if deleted {
-                Ok(HttpResponse::NoContent().finish())
-            } else {
-                Ok(HttpResponse::NotFound().finish())
-            }
-        }
-        Err(e) => {
-            println!("Error deleting service account key: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountKeyService::with_repositories(&data.repositories()?)?;
+    let key_id = parse_id(&path.into_inner())?;
+
+    let deleted = service.delete(key_id, actor(&req)).await?;
+
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound(
+            "Service account key not found".to_string(),
+        ))
+    }
+}
+
+/// Handler to restore a soft-deleted service account key.
+#[utoipa::path(
+    post,
+    path = "/service_account_keys/{id}:restore",
+    tag = "service-account-keys",
+    params(("id" = String, Path, description = "Service account key id")),
+    responses(
+        (status = 200, description = "Service account key restored", body = ServiceAccountKey),
+        (status = 404, description = "Service account key not found", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account_key::restore")]
+pub async fn restore(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountKeyService::with_repositories(&data.repositories()?)?;
+    let key_id = parse_id(&path.into_inner())?;
+
+    match service.restore(key_id).await? {
+        Some(restored) => Ok(HttpResponse::Ok().json(restored)),
+        None => Err(AppError::NotFound(
+            "Service account key not found".to_string(),
+        )),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/service_account_keys",
+    tag = "service-account-keys",
+    params(ServiceAccountKeyFilter, Pagination, SortQuery),
+    responses(
+        (status = 200, description = "A page of service account keys", body = Page<ServiceAccountKey>,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages"))),
+        (status = 400, description = "Invalid filter, sort or cursor", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "service_account_key::list")]
 pub async fn list(
+    req: HttpRequest,
     data: web::Data<AppData>,
     filter: Option<web::Query<ServiceAccountKeyFilter>>,
     pagination: web::Query<Pagination>,
-) -> Result<HttpResponse, Error> {
-    let database = data
-        .database
-        .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not initialized"))?;
-    let service = ServiceAccountKeyService::new(database.clone()).unwrap();
+    sort: web::Query<SortQuery>,
+) -> Result<HttpResponse, AppError> {
+    let service = ServiceAccountKeyService::with_repositories(&data.repositories()?)?;
     let filter = filter.map_or_else(ServiceAccountKeyFilter::default, |q| q.into_inner());
-    let sort = SortBuilder::new().add_sort(
+    let sort = sort.into_inner().or(SortBuilder::new().add_sort(
         ServiceAccountKeySortableFields::Id,
         SortDirection::Ascending,
-    );
+    ))?;
     let service_account_keys = service
-        .find(filter, Some(sort), Some(pagination.into_inner()))
-        .await;
-
-    match service_account_keys {
-        Ok(keys) => Ok(HttpResponse::Ok().json(keys)),
-        Err(e) => {
-            println!("Error listing service account keys: {:?}", e);
-            Err(actix_web::error::ErrorBadRequest(e))
-        }
-    }
+        .list(filter, Some(sort), pagination.into_inner())
+        .await?;
+    Ok(page_response(&req, &service_account_keys))
 }
 
 pub fn configure_routes(config: &mut web::ServiceConfig) {
@@ -138,6 +180,8 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
                     .route(web::post().to(create))
                     .route(web::get().to(list)),
             )
+            // Registered before "/{id}", which would otherwise match "<id>:restore"
+            .service(web::resource("/{id}:restore").route(web::post().to(restore)))
             .service(
                 web::resource("/{id}")
                     .route(web::get().to(read))
@@ -150,16 +194,18 @@ pub fn configure_routes(config: &mut web::ServiceConfig) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::models::pagination::Page;
+    use crate::repositories::registry::Repositories;
     use actix_web::{App, test};
     use chrono::{Duration, Utc};
     use jsonwebtoken::Algorithm;
+    use mongodb::bson::uuid::Uuid;
 
     #[actix_web::test]
     async fn test_create_service_account_key_success() {
-        let db = setup_test_db("service_account_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -181,6 +227,8 @@ mod tests {
             enabled: true,
             created_at: Some(now),
             updated_at: Some(now),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let resp = test::TestRequest::post()
@@ -193,15 +241,13 @@ mod tests {
         let created_key: ServiceAccountKey = test::read_body_json(resp).await;
         assert_eq!(created_key.key, key.key);
         assert!(created_key.id.is_some());
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_get_service_account_key_success() {
-        let db = setup_test_db("service_account_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -223,9 +269,11 @@ mod tests {
             enabled: true,
             created_at: Some(now),
             updated_at: Some(now),
+            deleted_at: None,
+            deleted_by: None,
         };
 
-        let created_key = ServiceAccountKeyService::new(app_data.database.clone().unwrap())
+        let created_key = ServiceAccountKeyService::with_repositories(&repositories)
             .unwrap()
             .create(key.clone())
             .await
@@ -242,15 +290,13 @@ mod tests {
         assert!(resp.status().is_success());
         let retrieved_key: ServiceAccountKey = test::read_body_json(resp).await;
         assert_eq!(retrieved_key.id, created_key.id);
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_update_service_account_key_success() {
-        let db = setup_test_db("service_account_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -272,9 +318,11 @@ mod tests {
             enabled: true,
             created_at: Some(now),
             updated_at: Some(now),
+            deleted_at: None,
+            deleted_by: None,
         };
 
-        let created_key = ServiceAccountKeyService::new(app_data.database.clone().unwrap())
+        let created_key = ServiceAccountKeyService::with_repositories(&repositories)
             .unwrap()
             .create(key.clone())
             .await
@@ -299,15 +347,13 @@ mod tests {
         let updated_key: ServiceAccountKey = test::read_body_json(resp).await;
         assert_eq!(updated_key.key, "new-key");
         assert!(!updated_key.enabled);
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_delete_service_account_key_success() {
-        let db = setup_test_db("service_account_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -329,9 +375,11 @@ mod tests {
             enabled: true,
             created_at: Some(now),
             updated_at: Some(now),
+            deleted_at: None,
+            deleted_by: None,
         };
 
-        let created_key = ServiceAccountKeyService::new(app_data.database.clone().unwrap())
+        let created_key = ServiceAccountKeyService::with_repositories(&repositories)
             .unwrap()
             .create(key.clone())
             .await
@@ -356,15 +404,13 @@ mod tests {
             .await;
 
         assert!(resp.status().is_client_error());
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_service_account_keys_success() {
-        let db = setup_test_db("service_account_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -387,8 +433,10 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
             };
-            ServiceAccountKeyService::new(app_data.database.clone().unwrap())
+            ServiceAccountKeyService::with_repositories(&repositories)
                 .unwrap()
                 .create(key)
                 .await
@@ -401,17 +449,16 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let keys: Vec<ServiceAccountKey> = test::read_body_json(resp).await;
+        let page: Page<ServiceAccountKey> = test::read_body_json(resp).await;
+        let keys = page.items;
         assert_eq!(keys.len(), 3);
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_service_account_keys_with_pagination() {
-        let db = setup_test_db("service_account_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -434,8 +481,10 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
             };
-            ServiceAccountKeyService::new(app_data.database.clone().unwrap())
+            ServiceAccountKeyService::with_repositories(&repositories)
                 .unwrap()
                 .create(key)
                 .await
@@ -448,17 +497,16 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let keys: Vec<ServiceAccountKey> = test::read_body_json(resp).await;
+        let page: Page<ServiceAccountKey> = test::read_body_json(resp).await;
+        let keys = page.items;
         assert_eq!(keys.len(), 2);
-
-        cleanup_test_db(db).await.unwrap();
     }
 
     #[actix_web::test]
     async fn test_list_service_account_keys_with_enabled_filter() {
-        let db = setup_test_db("service_account_key_routes").await.unwrap();
+        let repositories = Repositories::in_memory();
         let app_data = web::Data::new(AppData {
-            database: Some(std::sync::Arc::new(db.clone())),
+            repositories: Some(repositories.clone()),
             ..Default::default()
         });
 
@@ -481,8 +529,10 @@ mod tests {
                 enabled: i % 2 == 0,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
             };
-            ServiceAccountKeyService::new(app_data.database.clone().unwrap())
+            ServiceAccountKeyService::with_repositories(&repositories)
                 .unwrap()
                 .create(key)
                 .await
@@ -495,9 +545,8 @@ mod tests {
             .await;
 
         assert!(resp.status().is_success());
-        let keys: Vec<ServiceAccountKey> = test::read_body_json(resp).await;
+        let page: Page<ServiceAccountKey> = test::read_body_json(resp).await;
+        let keys = page.items;
         assert_eq!(keys.len(), 2);
-
-        cleanup_test_db(db).await.unwrap();
     }
 }
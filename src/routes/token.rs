@@ -0,0 +1,545 @@
+use crate::config::AppData;
+use crate::errors::{AppError, Problem};
+use crate::models::token::{
+    CLIENT_CREDENTIALS, IntrospectionRequest, IntrospectionResponse, TokenRequest, TokenResponse,
+};
+use crate::services::authentication_service::AuthenticationService;
+use crate::services::token_service::{DpopPresentation, ProofOfPossession, TokenService};
+use crate::utils::certificate::ClientCertificate;
+use crate::utils::tokens::dpop::{DPOP_TOKEN_TYPE, DpopRequest};
+use crate::utils::tokens::key_builder::Confirmation;
+use actix_web::http::header::{AUTHORIZATION, CacheControl, CacheDirective, PRAGMA};
+use actix_web::{HttpRequest, HttpResponse, web};
+
+/// OAuth 2.0 token endpoint for the client credentials grant.
+///
+/// The service account authenticates with `client_secret`, or, over the mTLS
+/// listener, with a client certificate satisfying one of its certificate
+/// bindings (RFC 8705 `tls_client_auth`). A secret takes precedence when both
+/// are presented. A token requested over mTLS is bound to the presented
+/// certificate (RFC 8705 section 3) and is only accepted from its holder.
+///
+/// Clients that cannot use mTLS may send a `DPoP` proof header instead; the
+/// token is then bound to the proof's key (RFC 9449) and issued with the
+/// `DPoP` token type.
+#[utoipa::path(
+    post,
+    path = "/oauth/token",
+    tag = "oauth",
+    request_body(content = TokenRequest, content_type = "application/x-www-form-urlencoded"),
+    params(
+        ("DPoP" = Option<String>, Header, description = "DPoP proof to bind the token to (RFC 9449)"),
+    ),
+    responses(
+        (status = 200, description = "Access token issued", body = TokenResponse),
+        (status = 400, description = "Invalid token request", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Client authentication failed, or the DPoP proof is invalid", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Access to the environment or scope is not granted", body = Problem, content_type = "application/problem+json"),
+        (status = 429, description = "Too many attempts or the account is locked", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "token::token")]
+pub async fn token(
+    request: HttpRequest,
+    data: web::Data<AppData>,
+    form: web::Form<TokenRequest>,
+) -> Result<HttpResponse, AppError> {
+    let form = form.into_inner();
+    if form.grant_type != CLIENT_CREDENTIALS {
+        return Err(AppError::Validation(format!(
+            "Unsupported grant_type `{}`",
+            form.grant_type
+        )));
+    }
+    let certificate = request.conn_data::<ClientCertificate>();
+    if form.client_secret.is_none() && certificate.is_none() {
+        return Err(AppError::Unauthorized(
+            "Client authentication required".to_string(),
+        ));
+    }
+
+    let repositories = data.repositories()?;
+    let config = data
+        .config
+        .as_ref()
+        .map(|config| config.application.clone());
+    let authentication = AuthenticationService::with_repositories(
+        &repositories,
+        config
+            .as_ref()
+            .map(|config| config.rate_limit.clone())
+            .unwrap_or_default(),
+    )?;
+    let source_ip = request.peer_addr().map(|address| address.ip().to_string());
+    let account = match (&form.client_secret, certificate) {
+        (Some(secret), _) => {
+            authentication
+                .authenticate(form.client_id, secret, source_ip.as_deref())
+                .await?
+        }
+        (None, Some(certificate)) => {
+            authentication
+                .authenticate_certificate(form.client_id, certificate, source_ip.as_deref())
+                .await?
+        }
+        (None, None) => unreachable!("checked above"),
+    };
+
+    let service = TokenService::with_repositories(
+        &repositories,
+        config.map(|config| config.tokens).unwrap_or_default(),
+    )?;
+    let key_thumbprint = match dpop_header(&request)? {
+        Some(proof) => {
+            let uri = request_uri(&request);
+            let dpop_request = DpopRequest {
+                method: request.method().as_str(),
+                uri: &uri,
+                access_token: None,
+            };
+            Some(service.check_dpop_proof(proof, &dpop_request).await?)
+        }
+        None => None,
+    };
+    let confirmation = (certificate.is_some() || key_thumbprint.is_some()).then(|| Confirmation {
+        x5t_s256: certificate.map(ClientCertificate::thumbprint),
+        jkt: key_thumbprint,
+    });
+    let response = service
+        .issue(&account, form.resource, form.scope.as_deref(), confirmation)
+        .await?;
+
+    // Token responses must not be cached (RFC 6749 section 5.1)
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .insert_header((PRAGMA, "no-cache"))
+        .json(response))
+}
+
+/// Token introspection endpoint (RFC 7662).
+///
+/// A certificate-bound token is only reported active when the thumbprint of
+/// the certificate it was presented with is passed as
+/// `certificate_thumbprint`, and a DPoP-bound one when the `DPoP` proof it
+/// was presented with is passed as `dpop_proof`, with the `htm` and `htu` of
+/// the request.
+#[utoipa::path(
+    post,
+    path = "/oauth/introspect",
+    tag = "oauth",
+    request_body(content = IntrospectionRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Token introspected", body = IntrospectionResponse),
+        (status = 400, description = "Invalid introspection request", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "token::introspect")]
+pub async fn introspect(
+    data: web::Data<AppData>,
+    form: web::Form<IntrospectionRequest>,
+) -> Result<HttpResponse, AppError> {
+    let form = form.into_inner();
+    let dpop = match (form.dpop_proof, form.htm, form.htu) {
+        (Some(proof), Some(method), Some(uri)) => Some(DpopPresentation { proof, method, uri }),
+        (Some(_), _, _) => {
+            return Err(AppError::Validation(
+                "`htm` and `htu` are required with `dpop_proof`".to_string(),
+            ));
+        }
+        (None, _, _) => None,
+    };
+    let proof = ProofOfPossession {
+        certificate_thumbprint: form.certificate_thumbprint,
+        dpop,
+    };
+    let response = token_service(&data)?
+        .introspect(&form.token, &proof)
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .json(response))
+}
+
+/// Verifies the access token the request is authorized with.
+///
+/// A certificate-bound token must be presented over the mTLS listener with
+/// the certificate it was issued to. A DPoP-bound token must be presented
+/// with the `DPoP` authorization scheme and a fresh `DPoP` proof for this
+/// request, signed by the key it was issued to.
+#[utoipa::path(
+    get,
+    path = "/oauth/verify",
+    tag = "oauth",
+    params(
+        ("DPoP" = Option<String>, Header, description = "DPoP proof for a token presented with the DPoP scheme (RFC 9449)"),
+    ),
+    responses(
+        (status = 200, description = "Token is valid", body = IntrospectionResponse),
+        (status = 401, description = "Token is missing, invalid, or presented without the certificate it is bound to", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(skip_all, name = "token::verify")]
+pub async fn verify(
+    request: HttpRequest,
+    data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let (scheme, token) = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| {
+            scheme.eq_ignore_ascii_case("Bearer") || scheme.eq_ignore_ascii_case(DPOP_TOKEN_TYPE)
+        })
+        .ok_or_else(|| AppError::Unauthorized("Bearer or DPoP token required".to_string()))?;
+    // A proof only counts with the DPoP scheme (RFC 9449 section 7.1)
+    let dpop = if scheme.eq_ignore_ascii_case(DPOP_TOKEN_TYPE) {
+        let proof = dpop_header(&request)?
+            .ok_or_else(|| AppError::Unauthorized("DPoP proof required".to_string()))?;
+        Some(DpopPresentation {
+            proof: proof.to_string(),
+            method: request.method().to_string(),
+            uri: request_uri(&request),
+        })
+    } else {
+        None
+    };
+    let proof = ProofOfPossession {
+        certificate_thumbprint: request
+            .conn_data::<ClientCertificate>()
+            .map(ClientCertificate::thumbprint),
+        dpop,
+    };
+    let claims = token_service(&data)?.verify(token.trim(), &proof).await?;
+    Ok(HttpResponse::Ok().json(IntrospectionResponse::from(claims)))
+}
+
+/// The request's single `DPoP` header, if any.
+fn dpop_header(request: &HttpRequest) -> Result<Option<&str>, AppError> {
+    let mut headers = request.headers().get_all(DPOP_TOKEN_TYPE);
+    match (headers.next(), headers.next()) {
+        (None, _) => Ok(None),
+        (Some(proof), None) => proof
+            .to_str()
+            .map(Some)
+            .map_err(|_| AppError::Unauthorized("Invalid DPoP proof".to_string())),
+        (Some(_), Some(_)) => Err(AppError::Unauthorized(
+            "Only one DPoP proof may be sent".to_string(),
+        )),
+    }
+}
+
+/// Absolute URI of the request without its query, as a DPoP proof's `htu`
+/// names it. Honours `Forwarded` and `X-Forwarded-*` headers.
+fn request_uri(request: &HttpRequest) -> String {
+    let connection = request.connection_info();
+    format!(
+        "{}://{}{}",
+        connection.scheme(),
+        connection.host(),
+        request.path()
+    )
+}
+
+fn token_service(data: &AppData) -> Result<TokenService, AppError> {
+    TokenService::with_repositories(
+        &data.repositories()?,
+        data.config
+            .as_ref()
+            .map(|config| config.application.tokens.clone())
+            .unwrap_or_default(),
+    )
+}
+
+/// Configures the OAuth 2.0 routes.
+pub fn configure_routes(config: &mut web::ServiceConfig) {
+    config
+        .service(web::resource("/oauth/token").route(web::post().to(token)))
+        .service(web::resource("/oauth/introspect").route(web::post().to(introspect)))
+        .service(web::resource("/oauth/verify").route(web::get().to(verify)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::project_access::ProjectAccess;
+    use crate::models::server_key::ServerKeyCreatePayload;
+    use crate::models::service_account::ServiceAccount;
+    use crate::repositories::registry::Repositories;
+    use crate::services::server_key_service::ServerKeyService;
+    use crate::test_utils::{dpop_key, dpop_proof};
+    use actix_web::http::StatusCode;
+    use actix_web::http::header::CACHE_CONTROL;
+    use actix_web::{App, test};
+    use jsonwebtoken::Algorithm;
+    use mongodb::bson::uuid::Uuid;
+
+    #[actix_web::test]
+    async fn test_unsupported_grant_type() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::default()))
+                .configure(configure_routes),
+        )
+        .await;
+        let resp = test::TestRequest::post()
+            .uri("/oauth/token")
+            .set_form([
+                ("grant_type", "password"),
+                ("client_id", &Uuid::new().to_string()),
+                ("client_secret", "secret123"),
+            ])
+            .send_request(&app)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_client_authentication_required() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::default()))
+                .configure(configure_routes),
+        )
+        .await;
+        let resp = test::TestRequest::post()
+            .uri("/oauth/token")
+            .set_form([
+                ("grant_type", CLIENT_CREDENTIALS),
+                ("client_id", &Uuid::new().to_string()),
+            ])
+            .send_request(&app)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_verify_requires_bearer_token() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::default()))
+                .configure(configure_routes),
+        )
+        .await;
+        let resp = test::TestRequest::get()
+            .uri("/oauth/verify")
+            .insert_header((AUTHORIZATION, "Basic dXNlcjpwYXNz"))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_verify_requires_dpop_proof_with_dpop_scheme() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::default()))
+                .configure(configure_routes),
+        )
+        .await;
+        let resp = test::TestRequest::get()
+            .uri("/oauth/verify")
+            .insert_header((AUTHORIZATION, "DPoP token"))
+            .send_request(&app)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_introspect_requires_dpop_request() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData::default()))
+                .configure(configure_routes),
+        )
+        .await;
+        let resp = test::TestRequest::post()
+            .uri("/oauth/introspect")
+            .set_form([("token", "token"), ("dpop_proof", "proof")])
+            .send_request(&app)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// A service account with access to an environment that has a server key.
+    async fn setup(repositories: &Repositories) -> ServiceAccount {
+        let environment_id = Uuid::new();
+        let account = repositories
+            .service_accounts
+            .create(ServiceAccount::new(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "secret123".to_string(),
+            ))
+            .await
+            .unwrap();
+        repositories
+            .project_access
+            .create(ProjectAccess {
+                id: None,
+                name: "reader".to_string(),
+                environment_id,
+                service_account_id: account.id,
+                project_scopes: vec![],
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await
+            .unwrap();
+        ServerKeyService::with_repositories(repositories)
+            .unwrap()
+            .create(ServerKeyCreatePayload {
+                environment_id,
+                algorithm: Algorithm::HS256,
+            })
+            .await
+            .unwrap();
+        account
+    }
+
+    #[actix_web::test]
+    async fn test_token_with_client_secret() {
+        let repositories = Repositories::in_memory();
+        let account = setup(&repositories).await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData {
+                    repositories: Some(repositories.clone()),
+                    ..Default::default()
+                }))
+                .configure(configure_routes),
+        )
+        .await;
+        let client_id = account.id.unwrap().to_string();
+        let resp = test::TestRequest::post()
+            .uri("/oauth/token")
+            .set_form([
+                ("grant_type", CLIENT_CREDENTIALS),
+                ("client_id", &client_id),
+                ("client_secret", "secret123"),
+            ])
+            .send_request(&app)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+        let response: TokenResponse = test::read_body_json(resp).await;
+        assert_eq!(response.token_type, "Bearer");
+        assert!(!response.access_token.is_empty());
+
+        let resp = test::TestRequest::get()
+            .uri("/oauth/verify")
+            .insert_header((AUTHORIZATION, format!("Bearer {}", response.access_token)))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = test::TestRequest::post()
+            .uri("/oauth/introspect")
+            .set_form([("token", response.access_token.as_str())])
+            .send_request(&app)
+            .await;
+        let introspection: IntrospectionResponse = test::read_body_json(resp).await;
+        assert!(introspection.active);
+        assert_eq!(introspection.sub.as_deref(), Some(client_id.as_str()));
+
+        let resp = test::TestRequest::post()
+            .uri("/oauth/token")
+            .set_form([
+                ("grant_type", CLIENT_CREDENTIALS),
+                ("client_id", &client_id),
+                ("client_secret", "wrong"),
+            ])
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_token_with_dpop_proof() {
+        let repositories = Repositories::in_memory();
+        let account = setup(&repositories).await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppData {
+                    repositories: Some(repositories.clone()),
+                    ..Default::default()
+                }))
+                .configure(configure_routes),
+        )
+        .await;
+        let (key, jwk) = dpop_key();
+        let client_id = account.id.unwrap().to_string();
+        let resp = test::TestRequest::post()
+            .uri("/oauth/token")
+            .insert_header((
+                DPOP_TOKEN_TYPE,
+                dpop_proof(
+                    &key,
+                    &jwk,
+                    "POST",
+                    "http://localhost:8080/oauth/token",
+                    None,
+                ),
+            ))
+            .set_form([
+                ("grant_type", CLIENT_CREDENTIALS),
+                ("client_id", &client_id),
+                ("client_secret", "secret123"),
+            ])
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let response: TokenResponse = test::read_body_json(resp).await;
+        assert_eq!(response.token_type, DPOP_TOKEN_TYPE);
+        let token = response.access_token;
+
+        let verify_uri = "http://localhost:8080/oauth/verify";
+        let resp = test::TestRequest::get()
+            .uri("/oauth/verify")
+            .insert_header((AUTHORIZATION, format!("DPoP {}", token)))
+            .insert_header((
+                DPOP_TOKEN_TYPE,
+                dpop_proof(&key, &jwk, "GET", verify_uri, Some(&token)),
+            ))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Presented as a bearer token, without proof of the key
+        let resp = test::TestRequest::get()
+            .uri("/oauth/verify")
+            .insert_header((AUTHORIZATION, format!("Bearer {}", token)))
+            .insert_header((
+                DPOP_TOKEN_TYPE,
+                dpop_proof(&key, &jwk, "GET", verify_uri, Some(&token)),
+            ))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        // A resource server forwarding the proof it received
+        let proof = dpop_proof(&key, &jwk, "GET", "https://api.test/orders", Some(&token));
+        let resp = test::TestRequest::post()
+            .uri("/oauth/introspect")
+            .set_form([
+                ("token", token.as_str()),
+                ("dpop_proof", proof.as_str()),
+                ("htm", "GET"),
+                ("htu", "https://api.test/orders"),
+            ])
+            .send_request(&app)
+            .await;
+        let introspection: IntrospectionResponse = test::read_body_json(resp).await;
+        assert!(introspection.active);
+        assert_eq!(introspection.token_type.as_deref(), Some(DPOP_TOKEN_TYPE));
+    }
+}
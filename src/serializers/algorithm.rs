@@ -1,11 +1,29 @@
 use jsonwebtoken::Algorithm;
 use serde::{Deserialize, Deserializer, Serializer};
-
-pub fn serialize<S>(algorithm: &Algorithm, serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    let algorithm_str = match algorithm {
+use std::borrow::Cow;
+use utoipa::openapi::RefOr;
+use utoipa::openapi::schema::{ObjectBuilder, Schema, Type};
+use utoipa::{PartialSchema, ToSchema};
+
+/// Every JWT signing algorithm, in the order the API documents them.
+pub const ALGORITHMS: [Algorithm; 12] = [
+    Algorithm::HS256,
+    Algorithm::HS384,
+    Algorithm::HS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::EdDSA,
+];
+
+/// Name an algorithm is written as in the API and in stored records.
+pub fn name(algorithm: Algorithm) -> &'static str {
+    match algorithm {
         Algorithm::HS256 => "HS256",
         Algorithm::HS384 => "HS384",
         Algorithm::HS512 => "HS512",
@@ -18,8 +36,41 @@ where
         Algorithm::PS384 => "PS384",
         Algorithm::PS512 => "PS512",
         Algorithm::EdDSA => "EdDSA",
-    };
-    serializer.serialize_str(algorithm_str)
+    }
+}
+
+/// The algorithm written as `value`, if any.
+pub fn from_name(value: &str) -> Option<Algorithm> {
+    ALGORITHMS
+        .into_iter()
+        .find(|algorithm| name(*algorithm) == value)
+}
+
+/// OpenAPI schema of `jsonwebtoken::Algorithm`, named `Algorithm`, for use as
+/// `value_type`; the orphan rule keeps `ToSchema` off the type itself.
+pub struct AlgorithmSchema;
+
+impl PartialSchema for AlgorithmSchema {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .description(Some("JWT signing algorithm"))
+            .enum_values(Some(ALGORITHMS.map(name)))
+            .into()
+    }
+}
+
+impl ToSchema for AlgorithmSchema {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("Algorithm")
+    }
+}
+
+pub fn serialize<S>(algorithm: &Algorithm, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(name(*algorithm))
 }
 
 pub fn deserialize<'de, D>(deserializer: D) -> Result<Algorithm, D::Error>
@@ -27,21 +78,7 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    match s.as_str() {
-        "HS256" => Ok(Algorithm::HS256),
-        "HS384" => Ok(Algorithm::HS384),
-        "HS512" => Ok(Algorithm::HS512),
-        "ES256" => Ok(Algorithm::ES256),
-        "ES384" => Ok(Algorithm::ES384),
-        "RS256" => Ok(Algorithm::RS256),
-        "RS384" => Ok(Algorithm::RS384),
-        "RS512" => Ok(Algorithm::RS512),
-        "PS256" => Ok(Algorithm::PS256),
-        "PS384" => Ok(Algorithm::PS384),
-        "PS512" => Ok(Algorithm::PS512),
-        "EdDSA" => Ok(Algorithm::EdDSA),
-        _ => Err(serde::de::Error::custom("Invalid algorithm type")),
-    }
+    from_name(&s).ok_or_else(|| serde::de::Error::custom("Invalid algorithm type"))
 }
 
 pub fn serialize_option<S>(algorithm: &Option<Algorithm>, serializer: S) -> Result<S::Ok, S::Error>
@@ -159,6 +196,14 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_names_round_trip() {
+        for algorithm in ALGORITHMS {
+            assert_eq!(from_name(name(algorithm)), Some(algorithm));
+            assert_eq!(name(algorithm).parse::<Algorithm>().unwrap(), algorithm);
+        }
+    }
+
     #[test]
     fn test_deserialize_option_invalid() {
         let json = r#"{"algorithm":"INVALID"}"#;
@@ -1,2 +1,5 @@
 pub mod algorithm;
 pub mod option_algorithm;
+pub mod option_timestamp;
+pub mod option_uuid_list;
+pub mod timestamp;
@@ -0,0 +1,59 @@
+use crate::serializers::timestamp;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        Some(value) => timestamp::serialize(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<DateTime<Utc>>::deserialize(deserializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::{Deserialize, Serialize};
+    use serde_json::{from_value, json, to_value};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestStruct {
+        #[serde(default, with = "crate::serializers::option_timestamp")]
+        at: Option<DateTime<Utc>>,
+    }
+
+    #[test]
+    fn test_serialize() {
+        let at = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+        assert_eq!(
+            to_value(TestStruct { at: Some(at) }).unwrap(),
+            json!({ "at": "2025-03-01T12:00:00.000000000Z" })
+        );
+        assert_eq!(
+            to_value(TestStruct { at: None }).unwrap(),
+            json!({ "at": null })
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let test: TestStruct = from_value(json!({ "at": "2025-03-01T12:00:00Z" })).unwrap();
+        assert_eq!(
+            test.at,
+            Some(Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap())
+        );
+        let test: TestStruct = from_value(json!({ "at": null })).unwrap();
+        assert_eq!(test.at, None);
+        let test: TestStruct = from_value(json!({})).unwrap();
+        assert_eq!(test.at, None);
+    }
+}
@@ -0,0 +1,69 @@
+use mongodb::bson::uuid::Uuid;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<S>(ids: &Option<Vec<Uuid>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    ids.serialize(serializer)
+}
+
+/// Accepts either a list of ids or, as sent in query strings, a single
+/// comma-separated string.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<Uuid>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Ids {
+        List(Vec<Uuid>),
+        Joined(String),
+    }
+
+    match Option::<Ids>::deserialize(deserializer)? {
+        Some(Ids::List(ids)) => Ok(Some(ids)),
+        Some(Ids::Joined(value)) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| Uuid::parse_str(id).map_err(serde::de::Error::custom))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestStruct {
+        #[serde(default, with = "crate::serializers::option_uuid_list")]
+        ids: Option<Vec<Uuid>>,
+    }
+
+    #[test]
+    fn test_deserialize_comma_separated_query() {
+        let first = Uuid::new();
+        let second = Uuid::new();
+        let query = format!("ids={},{}", first, second);
+        let test: TestStruct = serde_urlencoded::from_str(&query).unwrap();
+        assert_eq!(test.ids, Some(vec![first, second]));
+
+        let test: TestStruct = serde_urlencoded::from_str("").unwrap();
+        assert_eq!(test.ids, None);
+
+        let result: Result<TestStruct, _> = serde_urlencoded::from_str("ids=not-a-uuid");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_list() {
+        let id = Uuid::new();
+        let test: TestStruct = serde_json::from_value(json!({ "ids": [id.to_string()] })).unwrap();
+        assert_eq!(test.ids, Some(vec![id]));
+    }
+}
@@ -0,0 +1,77 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use mongodb::bson::Bson;
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// Renders a timestamp the way it is stored: RFC 3339 in UTC with a `Z`
+/// suffix and always nine fractional digits.
+///
+/// Every stored timestamp has the same width, so comparing them as strings
+/// orders them by time, and nothing is lost on the way back.
+pub fn format(value: &DateTime<Utc>) -> String {
+    value.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+/// [`format`] as a BSON value, for filters and updates.
+pub fn to_bson(value: &DateTime<Utc>) -> Bson {
+    Bson::String(format(value))
+}
+
+pub fn serialize<S>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format(value))
+}
+
+/// Accepts any RFC 3339 timestamp, whatever its offset and precision.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    DateTime::<Utc>::deserialize(deserializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde::Serialize;
+    use serde_json::{from_value, json, to_value};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestStruct {
+        #[serde(with = "crate::serializers::timestamp")]
+        at: DateTime<Utc>,
+    }
+
+    #[test]
+    fn test_serialize_fixed_width() {
+        let second = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+        let half = second + chrono::Duration::milliseconds(500);
+        assert_eq!(
+            to_value(TestStruct { at: second }).unwrap(),
+            json!({ "at": "2025-03-01T12:00:00.000000000Z" })
+        );
+        assert_eq!(format(&half), "2025-03-01T12:00:00.500000000Z");
+        // A later instant sorts later
+        assert!(format(&second) < format(&half));
+    }
+
+    #[test]
+    fn test_deserialize_any_offset() {
+        let test: TestStruct = from_value(json!({ "at": "2025-03-01T13:00:00.5+01:00" })).unwrap();
+        assert_eq!(
+            test.at,
+            Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap()
+                + chrono::Duration::milliseconds(500)
+        );
+    }
+
+    #[test]
+    fn test_serialize_deserialize_roundtrip() {
+        let test = TestStruct { at: Utc::now() };
+        let serialized = to_value(&test).unwrap();
+        let deserialized: TestStruct = from_value(serialized).unwrap();
+        assert_eq!(test, deserialized);
+    }
+}
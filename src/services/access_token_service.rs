@@ -1,75 +1,191 @@
+use crate::errors::AppError;
+use crate::metrics::{Outcome, TokenOperation, UNKNOWN_ENVIRONMENT, metrics};
 use crate::models::access_token::{
     AccessToken, AccessTokenFilter, AccessTokenSortableFields, AccessTokenUpdatePayload,
 };
-use crate::models::pagination::Pagination;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::sort::SortBuilder;
-use crate::repositories::access_token_repository::AccessTokenRepository;
-use crate::repositories::base::Repository;
-use anyhow::Error;
+use crate::repositories::access_token_repository::DynAccessTokenRepository;
+use crate::repositories::project_access_repository::DynProjectAccessRepository;
+use crate::repositories::registry::Repositories;
+use crate::repositories::service_account_repository::DynServiceAccountRepository;
+use chrono::{DateTime, Utc};
 use mongodb::Database;
 use mongodb::bson::uuid::Uuid;
 use std::sync::Arc;
 
 pub struct AccessTokenService {
-    access_token_repository: AccessTokenRepository,
+    access_token_repository: Arc<DynAccessTokenRepository>,
+    project_access_repository: Arc<DynProjectAccessRepository>,
+    service_account_repository: Arc<DynServiceAccountRepository>,
 }
 
 impl AccessTokenService {
-    pub fn new(database: Arc<Database>) -> Result<Self, Error> {
-        let access_token_repository = AccessTokenRepository::new(database.as_ref().clone())?;
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
+    }
+
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
         Ok(Self {
-            access_token_repository,
+            access_token_repository: repositories.access_tokens.clone(),
+            project_access_repository: repositories.project_access.clone(),
+            service_account_repository: repositories.service_accounts.clone(),
         })
     }
 
-    pub async fn create(&self, access_token: AccessToken) -> Result<AccessToken, Error> {
+    /// Issues an access token.
+    ///
+    /// Tokens are refused when the project access, or the service account it grants
+    /// access to, has been soft-deleted.
+    #[tracing::instrument(skip_all, name = "AccessTokenService::create")]
+    pub async fn create(&self, access_token: AccessToken) -> Result<AccessToken, AppError> {
+        let environment = self.environment_of(access_token.project_access_id).await;
+        let result = self.issue(access_token).await;
+        metrics().record_token(TokenOperation::Issuance, &environment, Outcome::of(&result));
+        result
+    }
+
+    #[tracing::instrument(skip_all, name = "AccessTokenService::issue")]
+    async fn issue(&self, access_token: AccessToken) -> Result<AccessToken, AppError> {
+        let project_access_id = access_token.project_access_id;
+        if self
+            .project_access_repository
+            .is_deleted(project_access_id)
+            .await?
+        {
+            return Err(AppError::Forbidden(
+                "Project access has been deleted".to_string(),
+            ));
+        }
+        let service_account_id = self
+            .project_access_repository
+            .read(project_access_id)
+            .await?
+            .and_then(|project_access| project_access.service_account_id);
+        if let Some(service_account_id) = service_account_id
+            && self
+                .service_account_repository
+                .is_deleted(service_account_id)
+                .await?
+        {
+            return Err(AppError::Forbidden(
+                "Service account has been deleted".to_string(),
+            ));
+        }
         self.access_token_repository.create(access_token).await
     }
 
-    pub async fn get_access_token(&self, id: Uuid) -> Result<Option<AccessToken>, Error> {
+    #[tracing::instrument(skip_all, name = "AccessTokenService::get_access_token")]
+    pub async fn get_access_token(&self, id: Uuid) -> Result<Option<AccessToken>, AppError> {
         self.access_token_repository.read(id).await
     }
 
+    #[tracing::instrument(skip_all, name = "AccessTokenService::update")]
     pub async fn update(
         &self,
         id: Uuid,
         access_token: AccessTokenUpdatePayload,
-    ) -> Result<AccessToken, Error> {
+    ) -> Result<AccessToken, AppError> {
         self.access_token_repository.update(id, access_token).await
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<bool, Error> {
-        self.access_token_repository.delete(id).await
+    /// Revokes an access token by soft-deleting it.
+    #[tracing::instrument(skip_all, name = "AccessTokenService::delete")]
+    pub async fn delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        let environment = match self.access_token_repository.read(id).await {
+            Ok(Some(access_token)) => self.environment_of(access_token.project_access_id).await,
+            _ => UNKNOWN_ENVIRONMENT.to_string(),
+        };
+        let result = self
+            .access_token_repository
+            .soft_delete(id, deleted_by)
+            .await;
+        let outcome = match result {
+            Ok(false) => Outcome::NotFound,
+            _ => Outcome::of(&result),
+        };
+        metrics().record_token(TokenOperation::Revocation, &environment, outcome);
+        result
+    }
+
+    /// Number of enabled, unexpired tokens expiring before `deadline`.
+    #[tracing::instrument(skip_all, name = "AccessTokenService::count_expiring")]
+    pub async fn count_expiring(&self, deadline: DateTime<Utc>) -> Result<u64, AppError> {
+        self.access_token_repository
+            .count(AccessTokenFilter {
+                is_enabled: Some(true),
+                is_active: Some(true),
+                expires_before: Some(deadline),
+                ..Default::default()
+            })
+            .await
+    }
+
+    /// Id of the environment a project access belongs to, used to label metrics.
+    #[tracing::instrument(skip_all, name = "AccessTokenService::environment_of")]
+    async fn environment_of(&self, project_access_id: Uuid) -> String {
+        match self.project_access_repository.read(project_access_id).await {
+            Ok(Some(project_access)) => project_access.environment_id.to_string(),
+            _ => UNKNOWN_ENVIRONMENT.to_string(),
+        }
+    }
+
+    #[tracing::instrument(skip_all, name = "AccessTokenService::restore")]
+    pub async fn restore(&self, id: Uuid) -> Result<Option<AccessToken>, AppError> {
+        self.access_token_repository.restore(id).await
     }
 
+    #[tracing::instrument(skip_all, name = "AccessTokenService::find")]
     pub async fn find(
         &self,
         filter: AccessTokenFilter,
         sort: Option<SortBuilder<AccessTokenSortableFields>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<AccessToken>, Error> {
+    ) -> Result<Vec<AccessToken>, AppError> {
         self.access_token_repository
             .find(filter, sort, pagination)
             .await
     }
+
+    #[tracing::instrument(skip_all, name = "AccessTokenService::list")]
+    pub async fn list(
+        &self,
+        filter: AccessTokenFilter,
+        sort: Option<SortBuilder<AccessTokenSortableFields>>,
+        pagination: Pagination,
+    ) -> Result<Page<AccessToken>, AppError> {
+        self.access_token_repository
+            .find_page(filter, sort, pagination)
+            .await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::models::project_access::ProjectAccess;
+    use crate::models::service_account::ServiceAccount;
+    use crate::test_utils::{Backend, TestStore};
+    use anyhow::Error;
     use chrono::{Duration, Utc};
     use jsonwebtoken::Algorithm;
+    use rstest::rstest;
 
-    async fn setup() -> (AccessTokenService, Database) {
-        let db = setup_test_db("access_token_service").await.unwrap();
-        let service = AccessTokenService::new(Arc::new(db.clone())).unwrap();
-        (service, db)
+    async fn setup(backend: Backend) -> (AccessTokenService, TestStore) {
+        let store = TestStore::setup("access_token_service", backend)
+            .await
+            .unwrap();
+        let service = AccessTokenService::with_repositories(&store.repositories).unwrap();
+        (service, store)
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_create_access_token() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_create_access_token(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let now = Utc::now();
         let token = AccessToken {
             id: None,
@@ -79,6 +195,8 @@ mod tests {
             created_at: now,
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = service.create(token.clone()).await?;
@@ -86,13 +204,77 @@ mod tests {
         assert_eq!(created.key, token.key);
         assert_eq!(created.algorithm, token.algorithm);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_create_access_token_for_deleted_service_account(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
+        let service_account = store
+            .repositories
+            .service_accounts
+            .create(ServiceAccount::new(
+                "deleted@example.com".to_string(),
+                "deleted".to_string(),
+                "secret".to_string(),
+            ))
+            .await?;
+        let project_access = store
+            .repositories
+            .project_access
+            .create(ProjectAccess {
+                id: None,
+                name: "reader".to_string(),
+                environment_id: Uuid::new(),
+                service_account_id: service_account.id,
+                project_scopes: vec![],
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await?;
+        store
+            .repositories
+            .service_accounts
+            .soft_delete(service_account.id.unwrap(), None)
+            .await?;
+
+        let now = Utc::now();
+        let token = AccessToken {
+            id: None,
+            key: "test-key".to_string(),
+            algorithm: Algorithm::RS256,
+            expires_at: now + Duration::hours(1),
+            created_at: now,
+            enabled: true,
+            project_access_id: project_access.id.unwrap(),
+            deleted_at: None,
+            deleted_by: None,
+        };
+        let result = service.create(token).await;
+        assert!(result.is_err());
+
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_get_access_token() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_get_access_token(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let token = AccessToken {
             id: Some(Uuid::new()),
             key: "test-key".to_string(),
@@ -101,6 +283,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = service.create(token.clone()).await?;
@@ -111,13 +295,17 @@ mod tests {
         assert_eq!(retrieved.id, created.id);
         assert_eq!(retrieved.key, created.key);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_update_access_token() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_update_access_token(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let token = AccessToken {
             id: Some(Uuid::new()),
             key: "test-key".to_string(),
@@ -126,6 +314,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = service.create(token).await?;
@@ -140,13 +330,17 @@ mod tests {
         assert_eq!(updated.key, "new-key");
         assert!(!updated.enabled);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_delete_access_token() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_delete_access_token(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let token = AccessToken {
             id: Some(Uuid::new()),
             key: "test-key".to_string(),
@@ -155,22 +349,28 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = service.create(token).await?;
-        let deleted = service.delete(created.id.unwrap()).await?;
+        let deleted = service.delete(created.id.unwrap(), None).await?;
         assert!(deleted);
 
         let read = service.get_access_token(created.id.unwrap()).await?;
         assert!(read.is_none());
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_access_tokens() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_access_tokens(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let token1 = AccessToken {
             id: Some(Uuid::new()),
             key: "test-key-1".to_string(),
@@ -179,6 +379,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
         let token2 = AccessToken {
             id: Some(Uuid::new()),
@@ -188,6 +390,8 @@ mod tests {
             created_at: Utc::now(),
             enabled: true,
             project_access_id: Uuid::new(),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         service.create(token1).await?;
@@ -199,18 +403,25 @@ mod tests {
             is_enabled: Some(true),
             is_active: None,
             project_access_id: None,
+            ..Default::default()
         };
 
         let found = service.find(filter, None, None).await?;
         assert_eq!(found.len(), 2);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_access_tokens_with_pagination() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_access_tokens_with_pagination(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
 
         // Create 5 test tokens
         for i in 1..=5 {
@@ -222,6 +433,8 @@ mod tests {
                 created_at: Utc::now(),
                 enabled: true,
                 project_access_id: Uuid::new(),
+                deleted_at: None,
+                deleted_by: None,
             };
             service.create(token).await?;
         }
@@ -230,6 +443,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(1),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -239,6 +453,7 @@ mod tests {
                     is_enabled: None,
                     is_active: None,
                     project_access_id: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -252,6 +467,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(2),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -261,6 +477,7 @@ mod tests {
                     is_enabled: None,
                     is_active: None,
                     project_access_id: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -270,7 +487,7 @@ mod tests {
         assert_eq!(found[0].key, "test-key-3");
         assert_eq!(found[1].key, "test-key-4");
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 }
@@ -0,0 +1,471 @@
+use crate::config::RateLimitConfig;
+use crate::errors::AppError;
+use crate::models::rate_limit::RateLimitKey;
+use crate::models::service_account::ServiceAccount;
+use crate::repositories::rate_limit_repository::RateLimitStore;
+use crate::repositories::registry::Repositories;
+use crate::repositories::service_account_repository::DynServiceAccountRepository;
+use crate::utils::certificate::ClientCertificate;
+use chrono::{DateTime, Utc};
+use mongodb::Database;
+use mongodb::bson::uuid::Uuid;
+use std::sync::Arc;
+
+/// Verifies service account credentials while protecting them from brute force.
+///
+/// Attempts are rate limited per client id and per source IP, and an account
+/// is locked for an exponentially growing period after repeated failures.
+/// Counters and lockouts are stored in MongoDB so they hold across every
+/// instance sharing the database.
+pub struct AuthenticationService {
+    service_account_repository: Arc<DynServiceAccountRepository>,
+    rate_limit_repository: Arc<dyn RateLimitStore>,
+    config: RateLimitConfig,
+}
+
+impl AuthenticationService {
+    pub fn new(database: Arc<Database>, config: RateLimitConfig) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?, config)
+    }
+
+    pub fn with_repositories(
+        repositories: &Repositories,
+        config: RateLimitConfig,
+    ) -> Result<Self, AppError> {
+        Ok(Self {
+            service_account_repository: repositories.service_accounts.clone(),
+            rate_limit_repository: repositories.rate_limits.clone(),
+            config,
+        })
+    }
+
+    /// Authenticates a service account by its id and secret.
+    ///
+    /// # Errors
+    ///
+    /// * `AppError::TooManyRequests` - A rate limit was exceeded or the account is locked
+    /// * `AppError::Unauthorized` - The account does not exist, is disabled, or the secret is wrong
+    #[tracing::instrument(skip_all, name = "AuthenticationService::authenticate")]
+    pub async fn authenticate(
+        &self,
+        client_id: Uuid,
+        secret: &str,
+        source_ip: Option<&str>,
+    ) -> Result<ServiceAccount, AppError> {
+        self.verify(client_id, source_ip, |account| {
+            secrets_match(&account.secret, secret)
+        })
+        .await
+    }
+
+    /// Authenticates a service account by the certificate it presented over
+    /// mutual TLS (RFC 8705 `tls_client_auth`).
+    ///
+    /// The TLS listener has already checked the certificate was issued by a
+    /// trusted CA; here it must satisfy one of the account's certificate
+    /// bindings. Attempts are rate limited and count towards lockouts like
+    /// secret-based ones.
+    ///
+    /// # Errors
+    ///
+    /// * `AppError::TooManyRequests` - A rate limit was exceeded or the account is locked
+    /// * `AppError::Unauthorized` - The account does not exist, is disabled, or the
+    ///   certificate does not satisfy any of its bindings
+    #[tracing::instrument(skip_all, name = "AuthenticationService::authenticate_certificate")]
+    pub async fn authenticate_certificate(
+        &self,
+        client_id: Uuid,
+        certificate: &ClientCertificate,
+        source_ip: Option<&str>,
+    ) -> Result<ServiceAccount, AppError> {
+        self.verify(client_id, source_ip, |account| {
+            account
+                .certificate_bindings
+                .iter()
+                .any(|binding| certificate.satisfies(binding))
+        })
+        .await
+    }
+
+    /// Applies rate limits and lockouts around checking a credential.
+    async fn verify(
+        &self,
+        client_id: Uuid,
+        source_ip: Option<&str>,
+        credential_matches: impl FnOnce(&ServiceAccount) -> bool,
+    ) -> Result<ServiceAccount, AppError> {
+        let now = Utc::now();
+        self.check_rate_limit(
+            RateLimitKey::Client(client_id.to_string()),
+            self.config.per_client,
+            now,
+        )
+        .await?;
+        if let Some(source_ip) = source_ip {
+            self.check_rate_limit(
+                RateLimitKey::Ip(source_ip.to_string()),
+                self.config.per_ip,
+                now,
+            )
+            .await?;
+        }
+
+        let account = match self.service_account_repository.read(client_id).await? {
+            Some(account) if account.enabled => account,
+            _ => return Err(invalid_credentials()),
+        };
+        if let Some(remaining) = account.lockout_remaining(now) {
+            return Err(AppError::TooManyRequests {
+                message: "Service account is temporarily locked".to_string(),
+                retry_after: retry_after_seconds(remaining),
+            });
+        }
+
+        if !credential_matches(&account) {
+            self.record_failure(client_id, now).await?;
+            return Err(invalid_credentials());
+        }
+        if account.failed_authentications > 0 || account.locked_until.is_some() {
+            self.service_account_repository
+                .reset_failed_authentications(client_id)
+                .await?;
+        }
+        Ok(account)
+    }
+
+    /// Counts the attempt against `key`, refusing it once `limit` is exceeded.
+    async fn check_rate_limit(
+        &self,
+        key: RateLimitKey,
+        limit: u32,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        if limit == 0 {
+            return Ok(());
+        }
+        let window = self
+            .rate_limit_repository
+            .hit(&key, self.config.window_seconds, now)
+            .await?;
+        if window.count > limit {
+            tracing::warn!(key = %key, attempts = window.count, "Authentication rate limit exceeded");
+            return Err(AppError::TooManyRequests {
+                message: "Too many authentication attempts".to_string(),
+                retry_after: retry_after_seconds(window.reset_at() - now),
+            });
+        }
+        Ok(())
+    }
+
+    async fn record_failure(&self, client_id: Uuid, now: DateTime<Utc>) -> Result<(), AppError> {
+        let Some(account) = self
+            .service_account_repository
+            .record_failed_authentication(client_id)
+            .await?
+        else {
+            return Ok(());
+        };
+        if let Some(lockout) = self.config.lockout_for(account.failed_authentications) {
+            let until = chrono::Duration::from_std(lockout)
+                .ok()
+                .and_then(|lockout| now.checked_add_signed(lockout))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC);
+            self.service_account_repository
+                .lock(client_id, until)
+                .await?;
+            tracing::warn!(
+                %client_id,
+                failures = account.failed_authentications,
+                locked_until = %until,
+                "Service account locked after repeated failed authentications"
+            );
+        }
+        Ok(())
+    }
+}
+
+fn invalid_credentials() -> AppError {
+    AppError::Unauthorized("Invalid client credentials".to_string())
+}
+
+/// Compares secrets in constant time, so response times do not reveal how
+/// much of a guess was right.
+fn secrets_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len() && openssl::memcmp::eq(expected.as_bytes(), given.as_bytes())
+}
+
+/// Whole seconds to wait, rounded up so a client retrying on time is not refused.
+fn retry_after_seconds(remaining: chrono::Duration) -> u64 {
+    let millis = remaining.num_milliseconds().max(0) as u64;
+    millis.div_ceil(1000).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{Backend, TestStore};
+    use anyhow::Error;
+    use rstest::rstest;
+
+    async fn setup(
+        backend: Backend,
+        config: RateLimitConfig,
+    ) -> (AuthenticationService, ServiceAccount, TestStore) {
+        let store = TestStore::setup("authentication_service", backend)
+            .await
+            .unwrap();
+        let account = store
+            .repositories
+            .service_accounts
+            .create(ServiceAccount::new(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "secret123".to_string(),
+            ))
+            .await
+            .unwrap();
+        let service =
+            AuthenticationService::with_repositories(&store.repositories, config).unwrap();
+        (service, account, store)
+    }
+
+    #[test]
+    fn test_secrets_match() {
+        assert!(secrets_match("secret123", "secret123"));
+        assert!(!secrets_match("secret123", "secret124"));
+        assert!(!secrets_match("secret123", "secret"));
+    }
+
+    #[test]
+    fn test_retry_after_rounds_up() {
+        assert_eq!(retry_after_seconds(chrono::Duration::milliseconds(1001)), 2);
+        assert_eq!(retry_after_seconds(chrono::Duration::seconds(30)), 30);
+        assert_eq!(retry_after_seconds(chrono::Duration::seconds(-5)), 1);
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_authenticate(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, account, store) = setup(backend, RateLimitConfig::default()).await;
+        let id = account.id.unwrap();
+
+        let authenticated = service
+            .authenticate(id, "secret123", Some("10.0.0.1"))
+            .await?;
+        assert_eq!(authenticated.id, account.id);
+
+        let result = service.authenticate(id, "wrong", Some("10.0.0.1")).await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        let result = service
+            .authenticate(Uuid::new(), "secret123", Some("10.0.0.1"))
+            .await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_authenticate_certificate(#[case] backend: Backend) -> Result<(), Error> {
+        use crate::models::certificate_binding::CertificateBinding;
+        use crate::models::service_account::ServiceAccountUpdatePayload;
+        use crate::test_utils::{certificate_authority, client_certificate};
+
+        let (service, account, store) = setup(backend, RateLimitConfig::default()).await;
+        let id = account.id.unwrap();
+        let (ca, ca_key) = certificate_authority("Client CA");
+        let certificate = |common_name: &str| {
+            let (certificate, _) =
+                client_certificate(&ca, &ca_key, common_name, &["DNS:billing.internal"]);
+            ClientCertificate::from_der(&certificate.to_der().unwrap()).unwrap()
+        };
+
+        // Accounts without bindings cannot authenticate with a certificate
+        let result = service
+            .authenticate_certificate(id, &certificate("billing"), None)
+            .await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+
+        service
+            .service_account_repository
+            .update(
+                id,
+                ServiceAccountUpdatePayload {
+                    email: None,
+                    user: None,
+                    secret: None,
+                    enabled: None,
+                    labels: None,
+                    certificate_bindings: Some(vec![CertificateBinding::SubjectDn(
+                        "CN=billing,O=Buraq Tests".to_string(),
+                    )]),
+                },
+            )
+            .await?;
+        let authenticated = service
+            .authenticate_certificate(id, &certificate("billing"), None)
+            .await?;
+        assert_eq!(authenticated.id, account.id);
+        let result = service
+            .authenticate_certificate(id, &certificate("payments"), None)
+            .await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+
+        // The earlier failure was cleared by the successful authentication
+        let account = service.service_account_repository.read(id).await?.unwrap();
+        assert_eq!(account.failed_authentications, 1);
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_lockout_after_repeated_failures(#[case] backend: Backend) -> Result<(), Error> {
+        let config = RateLimitConfig {
+            lockout_threshold: 2,
+            lockout_base_seconds: 30,
+            ..Default::default()
+        };
+        let (service, account, store) = setup(backend, config).await;
+        let id = account.id.unwrap();
+
+        for _ in 0..2 {
+            let result = service.authenticate(id, "wrong", None).await;
+            assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        }
+
+        // Even the right secret is refused while the account is locked
+        match service.authenticate(id, "secret123", None).await {
+            Err(AppError::TooManyRequests { retry_after, .. }) => {
+                assert!((1..=30).contains(&retry_after))
+            }
+            other => panic!("Expected a lockout, got {:?}", other),
+        }
+
+        let locked = service.service_account_repository.read(id).await?.unwrap();
+        assert_eq!(locked.failed_authentications, 2);
+        assert!(locked.locked_until.is_some());
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lockout_in_memory() -> Result<(), Error> {
+        let repositories = Repositories::in_memory();
+        let account = repositories
+            .service_accounts
+            .create(ServiceAccount::new(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "secret123".to_string(),
+            ))
+            .await?;
+        let id = account.id.unwrap();
+        let config = RateLimitConfig {
+            lockout_threshold: 1,
+            ..Default::default()
+        };
+        let service = AuthenticationService::with_repositories(&repositories, config)?;
+
+        let result = service.authenticate(id, "wrong", None).await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        let result = service.authenticate(id, "secret123", None).await;
+        assert!(matches!(result, Err(AppError::TooManyRequests { .. })));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lockout_longer_than_dates_go() -> Result<(), Error> {
+        let repositories = Repositories::in_memory();
+        let account = repositories
+            .service_accounts
+            .create(ServiceAccount::new(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "secret123".to_string(),
+            ))
+            .await?;
+        let id = account.id.unwrap();
+        let config = RateLimitConfig {
+            lockout_threshold: 1,
+            lockout_base_seconds: u64::MAX,
+            lockout_max_seconds: u64::MAX,
+            ..Default::default()
+        };
+        let service = AuthenticationService::with_repositories(&repositories, config)?;
+
+        let result = service.authenticate(id, "wrong", None).await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        let locked = repositories.service_accounts.read(id).await?.unwrap();
+        assert_eq!(locked.locked_until, Some(DateTime::<Utc>::MAX_UTC));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_success_resets_failures(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, account, store) = setup(backend, RateLimitConfig::default()).await;
+        let id = account.id.unwrap();
+
+        let _ = service.authenticate(id, "wrong", None).await;
+        service.authenticate(id, "secret123", None).await?;
+
+        let account = service.service_account_repository.read(id).await?.unwrap();
+        assert_eq!(account.failed_authentications, 0);
+        assert!(account.locked_until.is_none());
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_rate_limit_per_client_and_ip(#[case] backend: Backend) -> Result<(), Error> {
+        let config = RateLimitConfig {
+            per_client: 2,
+            per_ip: 2,
+            ..Default::default()
+        };
+        let (service, account, store) = setup(backend, config).await;
+        let id = account.id.unwrap();
+
+        service
+            .authenticate(id, "secret123", Some("10.0.0.1"))
+            .await?;
+        service
+            .authenticate(id, "secret123", Some("10.0.0.1"))
+            .await?;
+        let result = service
+            .authenticate(id, "secret123", Some("10.0.0.1"))
+            .await;
+        assert!(matches!(result, Err(AppError::TooManyRequests { .. })));
+
+        // A refused client attempt is not counted against the address, but
+        // other clients from the same address share its limit
+        let result = service
+            .authenticate(Uuid::new(), "secret123", Some("10.0.0.1"))
+            .await;
+        assert!(matches!(result, Err(AppError::TooManyRequests { .. })));
+
+        store.cleanup().await?;
+        Ok(())
+    }
+}
@@ -1,74 +1,113 @@
+use crate::errors::AppError;
 use crate::models::environment::{
     Environment, EnvironmentFilter, EnvironmentSortableFields, EnvironmentUpdatePayload,
 };
-use crate::models::pagination::Pagination;
+use crate::models::label::validate_labels;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use crate::repositories::environment_repository::EnvironmentRepository;
-use anyhow::Error;
+use crate::repositories::environment_repository::DynEnvironmentRepository;
+use crate::repositories::registry::Repositories;
 use mongodb::Database;
 use mongodb::bson::uuid::Uuid;
 use std::sync::Arc;
 
 pub struct EnvironmentService {
-    environment_repository: EnvironmentRepository,
+    environment_repository: Arc<DynEnvironmentRepository>,
 }
 
 impl EnvironmentService {
-    pub fn new(database: Arc<Database>) -> Result<Self, Error> {
-        let environment_repository = EnvironmentRepository::new(database.as_ref().clone())?;
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
+    }
+
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
         Ok(Self {
-            environment_repository,
+            environment_repository: repositories.environments.clone(),
         })
     }
 
-    pub async fn create(&self, environment: Environment) -> Result<Environment, Error> {
+    #[tracing::instrument(skip_all, name = "EnvironmentService::create")]
+    pub async fn create(&self, environment: Environment) -> Result<Environment, AppError> {
+        validate_labels(&environment.labels)?;
         self.environment_repository.create(environment).await
     }
 
-    pub async fn get_environment(&self, id: Uuid) -> Result<Option<Environment>, Error> {
+    #[tracing::instrument(skip_all, name = "EnvironmentService::get_environment")]
+    pub async fn get_environment(&self, id: Uuid) -> Result<Option<Environment>, AppError> {
         self.environment_repository.read(id).await
     }
 
+    #[tracing::instrument(skip_all, name = "EnvironmentService::update")]
     pub async fn update(
         &self,
         id: Uuid,
         environment: EnvironmentUpdatePayload,
-    ) -> Result<Environment, Error> {
+    ) -> Result<Environment, AppError> {
+        if let Some(labels) = &environment.labels {
+            validate_labels(labels)?;
+        }
         self.environment_repository.update(id, environment).await
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<bool, Error> {
-        self.environment_repository.delete(id).await
+    #[tracing::instrument(skip_all, name = "EnvironmentService::delete")]
+    pub async fn delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        self.environment_repository
+            .soft_delete(id, deleted_by)
+            .await
+    }
+
+    #[tracing::instrument(skip_all, name = "EnvironmentService::restore")]
+    pub async fn restore(&self, id: Uuid) -> Result<Option<Environment>, AppError> {
+        self.environment_repository.restore(id).await
     }
 
+    #[tracing::instrument(skip_all, name = "EnvironmentService::find")]
     pub async fn find(
         &self,
         filter: EnvironmentFilter,
         sort: Option<SortBuilder<EnvironmentSortableFields>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<Environment>, Error> {
+    ) -> Result<Vec<Environment>, AppError> {
         self.environment_repository
             .find(filter, sort, pagination)
             .await
     }
+
+    #[tracing::instrument(skip_all, name = "EnvironmentService::list")]
+    pub async fn list(
+        &self,
+        filter: EnvironmentFilter,
+        sort: Option<SortBuilder<EnvironmentSortableFields>>,
+        pagination: Pagination,
+    ) -> Result<Page<Environment>, AppError> {
+        self.environment_repository
+            .find_page(filter, sort, pagination)
+            .await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::test_utils::{Backend, TestStore};
     use chrono::Utc;
+    use rstest::rstest;
 
-    async fn setup() -> (EnvironmentService, Database) {
-        let db = setup_test_db("environment_service").await.unwrap();
-        let service = EnvironmentService::new(Arc::new(db.clone())).unwrap();
-        (service, db)
+    async fn setup(backend: Backend) -> (EnvironmentService, TestStore) {
+        let store = TestStore::setup("environment_service", backend)
+            .await
+            .unwrap();
+        let service = EnvironmentService::with_repositories(&store.repositories).unwrap();
+        (service, store)
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_create_environment() {
-        let (service, db) = setup().await;
+    async fn test_create_environment(#[case] backend: Backend) {
+        let (service, store) = setup(backend).await;
         let project_id = Uuid::new();
         let environment = Environment {
             id: None,
@@ -78,6 +117,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(environment).await.unwrap();
@@ -86,12 +128,16 @@ mod tests {
         assert_eq!(created.description, "Test Description");
         assert!(created.enabled);
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_get_environment() {
-        let (service, db) = setup().await;
+    async fn test_get_environment(#[case] backend: Backend) {
+        let (service, store) = setup(backend).await;
         let project_id = Uuid::new();
         let environment = Environment {
             id: None,
@@ -101,6 +147,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(environment).await.unwrap();
@@ -113,12 +162,16 @@ mod tests {
         assert_eq!(retrieved.name, created.name);
         assert_eq!(retrieved.description, created.description);
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_update_environment() {
-        let (service, db) = setup().await;
+    async fn test_update_environment(#[case] backend: Backend) {
+        let (service, store) = setup(backend).await;
         let project_id = Uuid::new();
         let environment = Environment {
             id: None,
@@ -128,6 +181,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(environment).await.unwrap();
@@ -135,6 +191,7 @@ mod tests {
             name: Some("Updated Environment".to_string()),
             description: Some("Updated Description".to_string()),
             enabled: Some(false),
+            labels: None,
         };
 
         let updated = service.update(created.id.unwrap(), update).await.unwrap();
@@ -142,12 +199,16 @@ mod tests {
         assert_eq!(updated.description, "Updated Description");
         assert!(!updated.enabled);
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_delete_environment() {
-        let (service, db) = setup().await;
+    async fn test_delete_environment(#[case] backend: Backend) {
+        let (service, store) = setup(backend).await;
         let project_id = Uuid::new();
         let environment = Environment {
             id: None,
@@ -157,21 +218,28 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(environment).await.unwrap();
-        let deleted = service.delete(created.id.unwrap()).await.unwrap();
+        let deleted = service.delete(created.id.unwrap(), None).await.unwrap();
         assert!(deleted);
 
         let read = service.get_environment(created.id.unwrap()).await.unwrap();
         assert!(read.is_none());
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_environments() {
-        let (service, db) = setup().await;
+    async fn test_find_environments(#[case] backend: Backend) {
+        let (service, store) = setup(backend).await;
         let project_id = Uuid::new();
         let environment1 = Environment {
             id: None,
@@ -181,6 +249,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let environment2 = Environment {
@@ -191,6 +262,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         service.create(environment1).await.unwrap();
@@ -200,6 +274,7 @@ mod tests {
             project_id: Some(project_id),
             name: None,
             is_enabled: None,
+            ..Default::default()
         };
 
         let found = service.find(filter, None, None).await.unwrap();
@@ -209,18 +284,23 @@ mod tests {
             project_id: None,
             name: Some("Environment 1".to_string()),
             is_enabled: None,
+            ..Default::default()
         };
 
         let found = service.find(filter, None, None).await.unwrap();
         assert_eq!(found.len(), 1);
         assert_eq!(found[0].name, "Environment 1");
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_environments_with_pagination() {
-        let (service, db) = setup().await;
+    async fn test_find_environments_with_pagination(#[case] backend: Backend) {
+        let (service, store) = setup(backend).await;
         let project_id = Uuid::new();
 
         // Create 5 test environments
@@ -233,6 +313,9 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
             service.create(environment).await.unwrap();
         }
@@ -241,6 +324,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(1),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -248,6 +332,7 @@ mod tests {
                     project_id: Some(project_id),
                     name: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -262,6 +347,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(2),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -269,6 +355,7 @@ mod tests {
                     project_id: Some(project_id),
                     name: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -283,6 +370,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(3),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -290,6 +378,7 @@ mod tests {
                     project_id: Some(project_id),
                     name: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -299,6 +388,6 @@ mod tests {
         assert_eq!(found.len(), 1);
         assert_eq!(found[0].name, "Environment 5");
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
     }
 }
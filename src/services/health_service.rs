@@ -0,0 +1,186 @@
+use crate::config::AppData;
+use crate::models::health::{DependencyCheck, Health, HealthStatus, ServiceStatus};
+#[cfg(feature = "sqlite")]
+use crate::repositories::sqlite::SqliteDatabase;
+use crate::utils::database::collections_missing_indexes;
+use crate::utils::security::SecretsManager;
+use chrono::Utc;
+use mongodb::bson::doc;
+use mongodb::bson::uuid::Uuid;
+use mongodb::{Client, Database};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Checks the dependencies the server needs to handle requests.
+pub struct HealthService {
+    mongo_client: Option<Arc<Client>>,
+    database: Option<Arc<Database>>,
+    #[cfg(feature = "sqlite")]
+    sqlite: Option<SqliteDatabase>,
+}
+
+impl HealthService {
+    pub fn new(data: &AppData) -> Self {
+        Self {
+            mongo_client: data.mongo_client.clone(),
+            database: data.database.clone(),
+            #[cfg(feature = "sqlite")]
+            sqlite: data.sqlite.clone(),
+        }
+    }
+
+    /// Readiness: the storage answers (MongoDB a ping and every collection
+    /// has its indexes, or the SQLite file a query), and the secrets manager
+    /// has a master key it can encrypt and decrypt with.
+    #[tracing::instrument(skip_all, name = "HealthService::readiness")]
+    pub async fn readiness(&self) -> Health {
+        let mut checks = self.check_storage().await;
+        checks.extend([
+            check("master_key", async {
+                SecretsManager::new(false).map(|_| ())
+            })
+            .await,
+            check("secrets", check_secrets()).await,
+        ]);
+        Health::from_checks(checks)
+    }
+
+    async fn check_storage(&self) -> Vec<DependencyCheck> {
+        #[cfg(feature = "sqlite")]
+        if let Some(sqlite) = &self.sqlite {
+            return vec![check("sqlite", async { Ok(sqlite.ping().await?) }).await];
+        }
+        let mongo = self.check_mongo().await;
+        let indexes = self.check_indexes(mongo.status).await;
+        vec![mongo, indexes]
+    }
+
+    /// Build version and uptime, together with the readiness checks.
+    #[tracing::instrument(skip_all, name = "HealthService::status")]
+    pub async fn status(&self, data: &AppData) -> ServiceStatus {
+        let uptime = data
+            .started_at
+            .map(|started_at| (Utc::now() - started_at).num_seconds().max(0) as u64)
+            .unwrap_or(0);
+        ServiceStatus {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at: data.started_at,
+            uptime_seconds: uptime,
+            health: self.readiness().await,
+        }
+    }
+
+    #[tracing::instrument(skip_all, name = "HealthService::check_mongo")]
+    async fn check_mongo(&self) -> DependencyCheck {
+        check("mongo", async {
+            let client = self
+                .mongo_client
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("MongoDB client not initialized"))?;
+            client
+                .database("admin")
+                .run_command(doc! { "ping": 1 })
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Skipped when MongoDB is unreachable, so the probe does not wait on a
+    /// server selection timeout a second time.
+    #[tracing::instrument(skip_all, name = "HealthService::check_indexes")]
+    async fn check_indexes(&self, mongo: HealthStatus) -> DependencyCheck {
+        check("indexes", async {
+            if mongo == HealthStatus::Down {
+                anyhow::bail!("MongoDB is unreachable");
+            }
+            let database = self
+                .database
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("Database not initialized"))?;
+            let missing = collections_missing_indexes(database.as_ref().clone()).await?;
+            if !missing.is_empty() {
+                anyhow::bail!("Indexes missing on {}", missing.join(", "));
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// The secrets manager is unsealed when a value survives an encrypt/decrypt
+/// round trip with the loaded master key.
+async fn check_secrets() -> Result<(), anyhow::Error> {
+    let secrets_manager = SecretsManager::new(false)?;
+    let probe = Uuid::new();
+    let encrypted = secrets_manager.encrypt("readiness", &probe)?;
+    if secrets_manager.decrypt(&encrypted, &probe)? != "readiness" {
+        anyhow::bail!("Secrets manager returned a different value");
+    }
+    Ok(())
+}
+
+/// Reported for a failed check; the probes are unauthenticated, so the cause
+/// is only logged.
+pub const CHECK_FAILED: &str = "Check failed, see the server logs";
+
+/// Runs one check and times it.
+async fn check(
+    name: &str,
+    probe: impl Future<Output = Result<(), anyhow::Error>>,
+) -> DependencyCheck {
+    let started = Instant::now();
+    let result = probe.await;
+    let (status, error) = match result {
+        Ok(()) => (HealthStatus::Up, None),
+        Err(e) => {
+            tracing::warn!(check = name, error = %e, "Dependency check failed");
+            (HealthStatus::Down, Some(CHECK_FAILED.to_string()))
+        }
+    };
+    DependencyCheck {
+        name: name.to_string(),
+        status,
+        latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_readiness_without_dependencies() {
+        let service = HealthService::new(&AppData::default());
+        let health =
+            temp_env::async_with_vars([("BURAQ_MASTER_KEY", None::<&str>)], service.readiness())
+                .await;
+
+        assert_eq!(health.status, HealthStatus::Down);
+        let names: Vec<&str> = health.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["mongo", "indexes", "master_key", "secrets"]);
+        assert!(
+            health
+                .checks
+                .iter()
+                .all(|c| c.status == HealthStatus::Down
+                    && c.error.as_deref() == Some(CHECK_FAILED))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_secrets_checks_with_master_key() {
+        let service = HealthService::new(&AppData::default());
+        let health = temp_env::async_with_vars(
+            [("BURAQ_MASTER_KEY", Some("test-master-key"))],
+            service.readiness(),
+        )
+        .await;
+
+        for check in &health.checks[2..] {
+            assert_eq!(check.status, HealthStatus::Up, "{:?}", check);
+        }
+    }
+}
@@ -0,0 +1,1605 @@
+use crate::errors::AppError;
+use crate::models::certificate_binding::validate_certificate_bindings;
+use crate::models::environment::{Environment, EnvironmentFilter, EnvironmentUpdatePayload};
+use crate::models::label::{Labels, validate_labels};
+use crate::models::manifest::{
+    AccessManifest, ApplyQuery, ApplyResponse, Change, ChangeAction, FieldChange, GeneratedSecret,
+    Manifest, ResourceKind,
+};
+use crate::models::project::{Project, ProjectFilter, ProjectUpdatePayload};
+use crate::models::project_access::{
+    ProjectAccess, ProjectAccessFilter, ProjectAccessUpdatePayload,
+};
+use crate::models::project_scope::{ProjectScope, ProjectScopeFilter, ProjectScopeUpdatePayload};
+use crate::models::service_account::{
+    ServiceAccount, ServiceAccountFilter, ServiceAccountUpdatePayload,
+};
+use crate::repositories::registry::Repositories;
+use crate::services::environment_service::EnvironmentService;
+use crate::services::project_access_service::ProjectAccessService;
+use crate::services::project_scope_service::ProjectScopeService;
+use crate::services::project_service::ProjectService;
+use crate::services::service_account_service::ServiceAccountService;
+use crate::utils::security::{REDACTED, generate_secret};
+use mongodb::Database;
+use mongodb::bson::uuid::Uuid;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+
+/// Applies manifests: declarations of projects, environments, scopes,
+/// service accounts and accesses by name.
+///
+/// Applying compares the manifest with the current records, plans the
+/// creations, updates and (when pruning) deletions that make them match, and
+/// carries the plan out. Applying the same manifest again plans nothing.
+pub struct ManifestService {
+    project_service: ProjectService,
+    environment_service: EnvironmentService,
+    project_scope_service: ProjectScopeService,
+    service_account_service: ServiceAccountService,
+    project_access_service: ProjectAccessService,
+}
+
+/// Records a plan is computed against.
+#[derive(Debug, Clone, Default)]
+pub struct State {
+    pub projects: Vec<Project>,
+    pub environments: Vec<Environment>,
+    pub scopes: Vec<ProjectScope>,
+    pub service_accounts: Vec<ServiceAccount>,
+    pub accesses: Vec<ProjectAccess>,
+}
+
+impl State {
+    fn project(&self, name: &str) -> Option<&Project> {
+        self.projects.iter().find(|project| project.name == name)
+    }
+
+    fn environment(&self, project: &str, name: &str) -> Option<&Environment> {
+        let project_id = self.project(project)?.id?;
+        self.environments
+            .iter()
+            .find(|environment| environment.project_id == project_id && environment.name == name)
+    }
+
+    fn scope(&self, project: &str, name: &str) -> Option<&ProjectScope> {
+        let project_id = self.project(project)?.id?;
+        self.scopes
+            .iter()
+            .find(|scope| scope.project_id == project_id && scope.name == name)
+    }
+
+    fn service_account(&self, user: &str) -> Option<&ServiceAccount> {
+        self.service_accounts
+            .iter()
+            .find(|account| account.user == user)
+    }
+
+    fn access(&self, environment_id: Uuid, service_account_id: Uuid) -> Option<&ProjectAccess> {
+        self.accesses.iter().find(|access| {
+            access.environment_id == environment_id
+                && access.service_account_id == Some(service_account_id)
+        })
+    }
+
+    fn project_name(&self, id: Uuid) -> String {
+        self.projects
+            .iter()
+            .find(|project| project.id == Some(id))
+            .map_or_else(|| id.to_string(), |project| project.name.clone())
+    }
+
+    /// `project/environment` path of an environment.
+    fn environment_path(&self, id: Uuid) -> String {
+        self.environments
+            .iter()
+            .find(|environment| environment.id == Some(id))
+            .map_or_else(
+                || id.to_string(),
+                |environment| {
+                    format!(
+                        "{}/{}",
+                        self.project_name(environment.project_id),
+                        environment.name
+                    )
+                },
+            )
+    }
+
+    fn scope_name(&self, id: Uuid) -> String {
+        self.scopes
+            .iter()
+            .find(|scope| scope.id == Some(id))
+            .map_or_else(|| id.to_string(), |scope| scope.name.clone())
+    }
+
+    fn user(&self, id: Option<Uuid>) -> String {
+        id.and_then(|id| {
+            self.service_accounts
+                .iter()
+                .find(|account| account.id == Some(id))
+        })
+        .map_or_else(|| "-".to_string(), |account| account.user.clone())
+    }
+}
+
+/// A change of the plan, with the manifest entry it comes from.
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub change: Change,
+    target: Target,
+}
+
+/// Indexes into the manifest of the entry a step creates or updates.
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Project(usize),
+    Environment(usize, usize),
+    Scope(usize, usize),
+    ServiceAccount(usize),
+    Access(usize),
+    /// A record the manifest does not declare, to be deleted
+    Undeclared,
+}
+
+/// Ids of the records apply has created or found, by name.
+#[derive(Debug, Default)]
+struct Ids {
+    projects: HashMap<String, Uuid>,
+    environments: HashMap<(String, String), Uuid>,
+    scopes: HashMap<(String, String), Uuid>,
+    service_accounts: HashMap<String, Uuid>,
+}
+
+impl Ids {
+    fn new(state: &State) -> Self {
+        let mut ids = Self::default();
+        for project in &state.projects {
+            if let Some(id) = project.id {
+                ids.projects.insert(project.name.clone(), id);
+            }
+        }
+        for environment in &state.environments {
+            if let Some(id) = environment.id {
+                let project = state.project_name(environment.project_id);
+                ids.environments
+                    .insert((project, environment.name.clone()), id);
+            }
+        }
+        for scope in &state.scopes {
+            if let Some(id) = scope.id {
+                let project = state.project_name(scope.project_id);
+                ids.scopes.insert((project, scope.name.clone()), id);
+            }
+        }
+        for account in &state.service_accounts {
+            if let Some(id) = account.id {
+                ids.service_accounts.insert(account.user.clone(), id);
+            }
+        }
+        ids
+    }
+
+    fn get<K: std::hash::Hash + Eq>(
+        map: &HashMap<K, Uuid>,
+        key: &K,
+        what: &str,
+    ) -> Result<Uuid, AppError> {
+        map.get(key)
+            .copied()
+            .ok_or_else(|| AppError::Conflict(format!("{} disappeared while applying", what)))
+    }
+}
+
+impl ManifestService {
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
+    }
+
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
+        Ok(Self {
+            project_service: ProjectService::with_repositories(repositories)?,
+            environment_service: EnvironmentService::with_repositories(repositories)?,
+            project_scope_service: ProjectScopeService::with_repositories(repositories)?,
+            service_account_service: ServiceAccountService::with_repositories(repositories)?,
+            project_access_service: ProjectAccessService::with_repositories(repositories)?,
+        })
+    }
+
+    /// Plans the changes that make the records match `manifest` and, unless
+    /// `query.dry_run`, carries them out.
+    ///
+    /// The manifest is validated as a whole before anything changes. The
+    /// changes are not transactional: when one fails, those before it stay
+    /// applied, and applying the manifest again resumes from there.
+    ///
+    /// # Errors
+    /// - `AppError::Validation` if the manifest is invalid or refers to
+    ///   records that neither it nor the database declares
+    #[tracing::instrument(skip_all, name = "ManifestService::apply")]
+    pub async fn apply(
+        &self,
+        manifest: &Manifest,
+        query: &ApplyQuery,
+        actor: Option<String>,
+    ) -> Result<ApplyResponse, AppError> {
+        let state = self.state().await?;
+        let steps = plan(manifest, &state, query)?;
+        let mut generated_secrets = Vec::new();
+
+        if !query.dry_run {
+            let mut ids = Ids::new(&state);
+            for step in &steps {
+                if let Some(secret) = self
+                    .execute(manifest, step, &mut ids, actor.clone())
+                    .await?
+                {
+                    generated_secrets.push(secret);
+                }
+            }
+        }
+
+        Ok(ApplyResponse {
+            applied: !query.dry_run,
+            changes: steps.into_iter().map(|step| step.change).collect(),
+            generated_secrets,
+        })
+    }
+
+    async fn state(&self) -> Result<State, AppError> {
+        Ok(State {
+            projects: self
+                .project_service
+                .find(ProjectFilter::default(), None, None)
+                .await?,
+            environments: self
+                .environment_service
+                .find(EnvironmentFilter::default(), None, None)
+                .await?,
+            scopes: self
+                .project_scope_service
+                .find(ProjectScopeFilter::default(), None, None)
+                .await?,
+            service_accounts: self
+                .service_account_service
+                .find(ServiceAccountFilter::default(), None, None)
+                .await?,
+            accesses: self
+                .project_access_service
+                .find(ProjectAccessFilter::default(), None, None)
+                .await?,
+        })
+    }
+
+    async fn execute(
+        &self,
+        manifest: &Manifest,
+        step: &Step,
+        ids: &mut Ids,
+        actor: Option<String>,
+    ) -> Result<Option<GeneratedSecret>, AppError> {
+        let Change {
+            action, kind, id, ..
+        } = &step.change;
+        match (step.target, action, id) {
+            (Target::Undeclared, ChangeAction::Delete, Some(id)) => {
+                let id = *id;
+                match kind {
+                    // Everything in the project goes with it
+                    ResourceKind::Project => {
+                        self.project_service.delete(id, true, actor).await?;
+                    }
+                    ResourceKind::Environment => {
+                        self.environment_service.delete(id, actor).await?;
+                    }
+                    ResourceKind::Scope => {
+                        self.project_scope_service.delete(id, actor).await?;
+                    }
+                    ResourceKind::ServiceAccount => {
+                        self.service_account_service.delete(id, actor).await?;
+                    }
+                    ResourceKind::Access => {
+                        self.project_access_service.delete(id, actor).await?;
+                    }
+                }
+            }
+            (Target::Project(p), _, id) => {
+                let declared = &manifest.projects[p];
+                let project = match id {
+                    None => {
+                        self.project_service
+                            .create(Project {
+                                id: None,
+                                name: declared.name.clone(),
+                                description: declared.description.clone(),
+                                enabled: declared.enabled,
+                                labels: declared.labels.clone(),
+                                created_at: None,
+                                updated_at: None,
+                                deleted_at: None,
+                                deleted_by: None,
+                            })
+                            .await?
+                    }
+                    Some(id) => {
+                        self.project_service
+                            .update(
+                                *id,
+                                ProjectUpdatePayload {
+                                    name: None,
+                                    description: Some(declared.description.clone()),
+                                    enabled: Some(declared.enabled),
+                                    labels: Some(declared.labels.clone()),
+                                },
+                            )
+                            .await?
+                    }
+                };
+                if let Some(id) = project.id {
+                    ids.projects.insert(declared.name.clone(), id);
+                }
+            }
+            (Target::Environment(p, e), _, id) => {
+                let project = &manifest.projects[p].name;
+                let declared = &manifest.projects[p].environments[e];
+                let environment = match id {
+                    None => {
+                        let project_id = Ids::get(&ids.projects, project, "Project")?;
+                        self.environment_service
+                            .create(Environment {
+                                id: None,
+                                project_id,
+                                name: declared.name.clone(),
+                                description: declared.description.clone(),
+                                enabled: declared.enabled,
+                                labels: declared.labels.clone(),
+                                created_at: None,
+                                updated_at: None,
+                                deleted_at: None,
+                                deleted_by: None,
+                            })
+                            .await?
+                    }
+                    Some(id) => {
+                        self.environment_service
+                            .update(
+                                *id,
+                                EnvironmentUpdatePayload {
+                                    name: None,
+                                    description: Some(declared.description.clone()),
+                                    enabled: Some(declared.enabled),
+                                    labels: Some(declared.labels.clone()),
+                                },
+                            )
+                            .await?
+                    }
+                };
+                if let Some(id) = environment.id {
+                    ids.environments
+                        .insert((project.clone(), declared.name.clone()), id);
+                }
+            }
+            (Target::Scope(p, s), _, id) => {
+                let project = &manifest.projects[p].name;
+                let declared = &manifest.projects[p].scopes[s];
+                let scope = match id {
+                    None => {
+                        let project_id = Ids::get(&ids.projects, project, "Project")?;
+                        self.project_scope_service
+                            .create(ProjectScope {
+                                id: None,
+                                project_id,
+                                name: declared.name.clone(),
+                                description: declared.description.clone(),
+                                enabled: declared.enabled,
+                                labels: declared.labels.clone(),
+                                created_at: None,
+                                updated_at: None,
+                                deleted_at: None,
+                                deleted_by: None,
+                            })
+                            .await?
+                    }
+                    Some(id) => {
+                        self.project_scope_service
+                            .update(
+                                *id,
+                                ProjectScopeUpdatePayload {
+                                    name: None,
+                                    description: Some(declared.description.clone()),
+                                    enabled: Some(declared.enabled),
+                                    labels: Some(declared.labels.clone()),
+                                },
+                            )
+                            .await?
+                    }
+                };
+                if let Some(id) = scope.id {
+                    ids.scopes
+                        .insert((project.clone(), declared.name.clone()), id);
+                }
+            }
+            (Target::ServiceAccount(a), _, id) => {
+                let declared = &manifest.service_accounts[a];
+                let mut generated = None;
+                let account = match id {
+                    None => {
+                        let secret = match &declared.secret {
+                            Some(secret) => secret.clone(),
+                            None => {
+                                let secret = generate_secret();
+                                generated = Some(GeneratedSecret {
+                                    service_account: declared.user.clone(),
+                                    secret: secret.clone(),
+                                });
+                                secret
+                            }
+                        };
+                        let mut account = ServiceAccount::new(
+                            declared.email.clone(),
+                            declared.user.clone(),
+                            secret,
+                        );
+                        account.enabled = declared.enabled;
+                        account.labels = declared.labels.clone();
+                        account.certificate_bindings = declared.certificate_bindings.clone();
+                        self.service_account_service.create(account).await?
+                    }
+                    Some(id) => {
+                        self.service_account_service
+                            .update(
+                                *id,
+                                ServiceAccountUpdatePayload {
+                                    email: Some(declared.email.clone()),
+                                    user: None,
+                                    secret: declared.secret.clone(),
+                                    enabled: Some(declared.enabled),
+                                    labels: Some(declared.labels.clone()),
+                                    certificate_bindings: Some(
+                                        declared.certificate_bindings.clone(),
+                                    ),
+                                },
+                            )
+                            .await?
+                    }
+                };
+                if let Some(id) = account.id {
+                    ids.service_accounts.insert(declared.user.clone(), id);
+                }
+                return Ok(generated);
+            }
+            (Target::Access(a), _, id) => {
+                let declared = &manifest.access[a];
+                let project_scopes = declared
+                    .scopes
+                    .iter()
+                    .map(|scope| {
+                        Ids::get(
+                            &ids.scopes,
+                            &(declared.project.clone(), scope.clone()),
+                            "Scope",
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                match id {
+                    None => {
+                        let environment_id = Ids::get(
+                            &ids.environments,
+                            &(declared.project.clone(), declared.environment.clone()),
+                            "Environment",
+                        )?;
+                        let service_account_id = Ids::get(
+                            &ids.service_accounts,
+                            &declared.service_account,
+                            "Service account",
+                        )?;
+                        self.project_access_service
+                            .create(ProjectAccess {
+                                id: None,
+                                name: declared.name(),
+                                environment_id,
+                                service_account_id: Some(service_account_id),
+                                project_scopes,
+                                enabled: declared.enabled,
+                                labels: declared.labels.clone(),
+                                created_at: None,
+                                updated_at: None,
+                                deleted_at: None,
+                                deleted_by: None,
+                            })
+                            .await?;
+                    }
+                    Some(id) => {
+                        self.project_access_service
+                            .update(
+                                *id,
+                                ProjectAccessUpdatePayload {
+                                    name: Some(declared.name()),
+                                    project_scopes: Some(project_scopes),
+                                    enabled: Some(declared.enabled),
+                                    labels: Some(declared.labels.clone()),
+                                },
+                            )
+                            .await?;
+                    }
+                }
+            }
+            (Target::Undeclared, _, _) => {
+                return Err(AppError::Internal(anyhow::anyhow!(
+                    "Undeclared records can only be deleted"
+                )));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Computes the changes that make `state` match `manifest`, in the order they
+/// must be carried out: creations and updates parents first, then deletions
+/// children first.
+///
+/// With `query.prune`, records that the manifest neither declares nor refers
+/// to are deleted, when they match `query.selector`, or all of them with
+/// `query.all`. A pruned project takes everything in it along.
+pub fn plan(manifest: &Manifest, state: &State, query: &ApplyQuery) -> Result<Vec<Step>, AppError> {
+    if query.prune {
+        match (&query.selector, query.all) {
+            (None, false) => {
+                return Err(AppError::Validation(
+                    "Pruning needs a selector, or `all` to prune every undeclared record"
+                        .to_string(),
+                ));
+            }
+            (Some(_), true) => {
+                return Err(AppError::Validation(
+                    "`selector` and `all` cannot be combined".to_string(),
+                ));
+            }
+            _ => {}
+        }
+    }
+    validate(manifest, state)?;
+    let mut steps = Vec::new();
+    let mut push = |action, kind, name: String, id, diff: Vec<FieldChange>, target| {
+        if action != ChangeAction::Update || !diff.is_empty() {
+            steps.push(Step {
+                change: Change {
+                    action,
+                    kind,
+                    name,
+                    id,
+                    diff,
+                },
+                target,
+            });
+        }
+    };
+    let action = |id: Option<Uuid>| match id {
+        Some(_) => ChangeAction::Update,
+        None => ChangeAction::Create,
+    };
+
+    for (p, declared) in manifest.projects.iter().enumerate() {
+        let current = state.project(&declared.name);
+        let mut diff = Diff::default();
+        diff.common(
+            current.map(|c| (&c.name, &c.description, c.enabled, &c.labels)),
+            (
+                &declared.name,
+                &declared.description,
+                declared.enabled,
+                &declared.labels,
+            ),
+        );
+        let id = current.and_then(|c| c.id);
+        push(
+            action(id),
+            ResourceKind::Project,
+            declared.name.clone(),
+            id,
+            diff.0,
+            Target::Project(p),
+        );
+    }
+    for (p, project) in manifest.projects.iter().enumerate() {
+        for (e, declared) in project.environments.iter().enumerate() {
+            let current = state.environment(&project.name, &declared.name);
+            let mut diff = Diff::default();
+            diff.common(
+                current.map(|c| (&c.name, &c.description, c.enabled, &c.labels)),
+                (
+                    &declared.name,
+                    &declared.description,
+                    declared.enabled,
+                    &declared.labels,
+                ),
+            );
+            let id = current.and_then(|c| c.id);
+            let name = format!("{}/{}", project.name, declared.name);
+            push(
+                action(id),
+                ResourceKind::Environment,
+                name,
+                id,
+                diff.0,
+                Target::Environment(p, e),
+            );
+        }
+        for (s, declared) in project.scopes.iter().enumerate() {
+            let current = state.scope(&project.name, &declared.name);
+            let mut diff = Diff::default();
+            diff.common(
+                current.map(|c| (&c.name, &c.description, c.enabled, &c.labels)),
+                (
+                    &declared.name,
+                    &declared.description,
+                    declared.enabled,
+                    &declared.labels,
+                ),
+            );
+            let id = current.and_then(|c| c.id);
+            let name = format!("{}/{}", project.name, declared.name);
+            push(
+                action(id),
+                ResourceKind::Scope,
+                name,
+                id,
+                diff.0,
+                Target::Scope(p, s),
+            );
+        }
+    }
+    for (a, declared) in manifest.service_accounts.iter().enumerate() {
+        let current = state.service_account(&declared.user);
+        let mut diff = Diff::default();
+        diff.field("user", current.map(|c| json!(c.user)), json!(declared.user));
+        diff.field(
+            "email",
+            current.map(|c| json!(c.email)),
+            json!(declared.email),
+        );
+        diff.field(
+            "enabled",
+            current.map(|c| json!(c.enabled)),
+            json!(declared.enabled),
+        );
+        diff.field(
+            "labels",
+            current.map(|c| labels(&c.labels)),
+            labels(&declared.labels),
+        );
+        diff.field(
+            "certificate_bindings",
+            current.map(|c| json!(c.certificate_bindings)),
+            json!(declared.certificate_bindings),
+        );
+        // Secrets are compared but never shown
+        match (current, &declared.secret) {
+            (None, _) => diff.field("secret", None, json!(REDACTED)),
+            (Some(current), Some(secret)) if current.secret != *secret => {
+                diff.0.push(FieldChange {
+                    field: "secret".to_string(),
+                    from: json!(REDACTED),
+                    to: json!(REDACTED),
+                })
+            }
+            _ => {}
+        }
+        let id = current.and_then(|c| c.id);
+        push(
+            action(id),
+            ResourceKind::ServiceAccount,
+            declared.user.clone(),
+            id,
+            diff.0,
+            Target::ServiceAccount(a),
+        );
+    }
+    for (a, declared) in manifest.access.iter().enumerate() {
+        let current = current_access(state, declared);
+        let mut diff = Diff::default();
+        diff.field(
+            "name",
+            current.map(|c| json!(c.name)),
+            json!(declared.name()),
+        );
+        diff.field(
+            "scopes",
+            current.map(|c| {
+                json!(
+                    c.project_scopes
+                        .iter()
+                        .map(|id| state.scope_name(*id))
+                        .collect::<BTreeSet<_>>()
+                )
+            }),
+            json!(declared.scopes.iter().collect::<BTreeSet<_>>()),
+        );
+        diff.field(
+            "enabled",
+            current.map(|c| json!(c.enabled)),
+            json!(declared.enabled),
+        );
+        diff.field(
+            "labels",
+            current.map(|c| labels(&c.labels)),
+            labels(&declared.labels),
+        );
+        let id = current.and_then(|c| c.id);
+        push(
+            action(id),
+            ResourceKind::Access,
+            access_path(declared),
+            id,
+            diff.0,
+            Target::Access(a),
+        );
+    }
+
+    if query.prune {
+        let selected = |labels: &Labels| {
+            query
+                .selector
+                .as_ref()
+                .is_none_or(|selector| selector.matches(labels))
+        };
+        let kept = References::new(manifest, state);
+        let pruned_projects: HashSet<Uuid> = state
+            .projects
+            .iter()
+            .filter_map(|project| {
+                project
+                    .id
+                    .filter(|id| !kept.projects.contains(id) && selected(&project.labels))
+            })
+            .collect();
+        let in_pruned_project = |environment_id: Uuid| {
+            state
+                .environments
+                .iter()
+                .find(|environment| environment.id == Some(environment_id))
+                .is_some_and(|environment| pruned_projects.contains(&environment.project_id))
+        };
+        let mut delete = |kind, name: String, id: Uuid| {
+            push(
+                ChangeAction::Delete,
+                kind,
+                name,
+                Some(id),
+                Vec::new(),
+                Target::Undeclared,
+            )
+        };
+
+        for access in &state.accesses {
+            if let Some(id) = access.id
+                && !kept.accesses.contains(&id)
+                && !in_pruned_project(access.environment_id)
+                && selected(&access.labels)
+            {
+                let name = format!(
+                    "{}/{}",
+                    state.environment_path(access.environment_id),
+                    state.user(access.service_account_id)
+                );
+                delete(ResourceKind::Access, name, id);
+            }
+        }
+        for scope in &state.scopes {
+            if let Some(id) = scope.id
+                && !kept.scopes.contains(&id)
+                && !pruned_projects.contains(&scope.project_id)
+                && selected(&scope.labels)
+            {
+                let name = format!("{}/{}", state.project_name(scope.project_id), scope.name);
+                delete(ResourceKind::Scope, name, id);
+            }
+        }
+        for environment in &state.environments {
+            if let Some(id) = environment.id
+                && !kept.environments.contains(&id)
+                && !pruned_projects.contains(&environment.project_id)
+                && selected(&environment.labels)
+            {
+                delete(ResourceKind::Environment, state.environment_path(id), id);
+            }
+        }
+        for account in &state.service_accounts {
+            if let Some(id) = account.id
+                && !kept.service_accounts.contains(&id)
+                && selected(&account.labels)
+            {
+                delete(ResourceKind::ServiceAccount, account.user.clone(), id);
+            }
+        }
+        for project in &state.projects {
+            if let Some(id) = project.id
+                && pruned_projects.contains(&id)
+            {
+                delete(ResourceKind::Project, project.name.clone(), id);
+            }
+        }
+    }
+    Ok(steps)
+}
+
+/// The existing access an access declaration matches.
+fn current_access<'a>(state: &'a State, declared: &AccessManifest) -> Option<&'a ProjectAccess> {
+    let environment = state.environment(&declared.project, &declared.environment)?;
+    let account = state.service_account(&declared.service_account)?;
+    state.access(environment.id?, account.id?)
+}
+
+fn access_path(declared: &AccessManifest) -> String {
+    format!(
+        "{}/{}/{}",
+        declared.project, declared.environment, declared.service_account
+    )
+}
+
+/// Labels as a JSON object with sorted keys, for stable diffs.
+fn labels(labels: &Labels) -> Value {
+    json!(labels.iter().collect::<BTreeMap<_, _>>())
+}
+
+/// Field changes of one record. For a record to be created, every field is
+/// listed, changing from `null`.
+#[derive(Default)]
+struct Diff(Vec<FieldChange>);
+
+/// Name, description, enabled flag and labels, which projects, environments
+/// and scopes share.
+type Common<'a> = (&'a String, &'a String, bool, &'a Labels);
+
+impl Diff {
+    fn common(&mut self, current: Option<Common>, declared: Common) {
+        let (name, description, enabled, labels) = declared;
+        self.field("name", current.map(|c| json!(c.0)), json!(name));
+        self.field(
+            "description",
+            current.map(|c| json!(c.1)),
+            json!(description),
+        );
+        self.field("enabled", current.map(|c| json!(c.2)), json!(enabled));
+        self.field(
+            "labels",
+            current.map(|c| self::labels(c.3)),
+            self::labels(labels),
+        );
+    }
+
+    fn field(&mut self, field: &str, from: Option<Value>, to: Value) {
+        let from = from.unwrap_or(Value::Null);
+        if from != to {
+            self.0.push(FieldChange {
+                field: field.to_string(),
+                from,
+                to,
+            });
+        }
+    }
+}
+
+/// Ids of existing records that the manifest declares or refers to, which
+/// pruning keeps.
+#[derive(Default)]
+struct References {
+    projects: HashSet<Uuid>,
+    environments: HashSet<Uuid>,
+    scopes: HashSet<Uuid>,
+    service_accounts: HashSet<Uuid>,
+    accesses: HashSet<Uuid>,
+}
+
+impl References {
+    fn new(manifest: &Manifest, state: &State) -> Self {
+        let mut kept = Self::default();
+        for project in &manifest.projects {
+            kept.projects
+                .extend(state.project(&project.name).and_then(|p| p.id));
+            for environment in &project.environments {
+                kept.environments.extend(
+                    state
+                        .environment(&project.name, &environment.name)
+                        .and_then(|e| e.id),
+                );
+            }
+            for scope in &project.scopes {
+                kept.scopes
+                    .extend(state.scope(&project.name, &scope.name).and_then(|s| s.id));
+            }
+        }
+        for account in &manifest.service_accounts {
+            kept.service_accounts
+                .extend(state.service_account(&account.user).and_then(|a| a.id));
+        }
+        for access in &manifest.access {
+            kept.projects
+                .extend(state.project(&access.project).and_then(|p| p.id));
+            kept.environments.extend(
+                state
+                    .environment(&access.project, &access.environment)
+                    .and_then(|e| e.id),
+            );
+            for scope in &access.scopes {
+                kept.scopes
+                    .extend(state.scope(&access.project, scope).and_then(|s| s.id));
+            }
+            kept.service_accounts.extend(
+                state
+                    .service_account(&access.service_account)
+                    .and_then(|a| a.id),
+            );
+            kept.accesses
+                .extend(current_access(state, access).and_then(|a| a.id));
+        }
+        kept
+    }
+}
+
+/// Checks the manifest as a whole: required names, duplicates, labels,
+/// certificate bindings, and that accesses refer to projects, environments,
+/// scopes and service accounts that the manifest or the database declares.
+fn validate(manifest: &Manifest, state: &State) -> Result<(), AppError> {
+    let invalid = |message: String| Err(AppError::Validation(message));
+    let mut projects = HashSet::new();
+    for project in &manifest.projects {
+        if project.name.trim().is_empty() {
+            return invalid("Every project needs a name".to_string());
+        }
+        if !projects.insert(project.name.as_str()) {
+            return invalid(format!("Project `{}` is declared twice", project.name));
+        }
+        validate_labels(&project.labels)?;
+
+        let mut environments = HashSet::new();
+        for environment in &project.environments {
+            if environment.name.trim().is_empty() {
+                return invalid(format!(
+                    "Every environment of `{}` needs a name",
+                    project.name
+                ));
+            }
+            if !environments.insert(environment.name.as_str()) {
+                return invalid(format!(
+                    "Environment `{}/{}` is declared twice",
+                    project.name, environment.name
+                ));
+            }
+            validate_labels(&environment.labels)?;
+        }
+        let mut scopes = HashSet::new();
+        for scope in &project.scopes {
+            if scope.name.trim().is_empty() {
+                return invalid(format!("Every scope of `{}` needs a name", project.name));
+            }
+            if !scopes.insert(scope.name.as_str()) {
+                return invalid(format!(
+                    "Scope `{}/{}` is declared twice",
+                    project.name, scope.name
+                ));
+            }
+            validate_labels(&scope.labels)?;
+        }
+    }
+
+    let mut users = HashSet::new();
+    for account in &manifest.service_accounts {
+        if account.user.trim().is_empty() || account.email.trim().is_empty() {
+            return invalid("Every service account needs a user and an email".to_string());
+        }
+        if !users.insert(account.user.as_str()) {
+            return invalid(format!(
+                "Service account `{}` is declared twice",
+                account.user
+            ));
+        }
+        validate_labels(&account.labels)?;
+        validate_certificate_bindings(&account.certificate_bindings)?;
+    }
+
+    let mut accesses = HashSet::new();
+    for access in &manifest.access {
+        let path = access_path(access);
+        if !accesses.insert(path.clone()) {
+            return invalid(format!("Access `{}` is declared twice", path));
+        }
+        validate_labels(&access.labels)?;
+
+        let project = manifest
+            .projects
+            .iter()
+            .find(|project| project.name == access.project);
+        if project.is_none() && state.project(&access.project).is_none() {
+            return invalid(format!(
+                "Access `{}` refers to unknown project `{}`",
+                path, access.project
+            ));
+        }
+        let declares_environment = project.is_some_and(|project| {
+            project
+                .environments
+                .iter()
+                .any(|environment| environment.name == access.environment)
+        });
+        if !declares_environment
+            && state
+                .environment(&access.project, &access.environment)
+                .is_none()
+        {
+            return invalid(format!(
+                "Access `{}` refers to unknown environment `{}/{}`",
+                path, access.project, access.environment
+            ));
+        }
+        for scope in &access.scopes {
+            let declares_scope = project.is_some_and(|project| {
+                project
+                    .scopes
+                    .iter()
+                    .any(|declared| declared.name == *scope)
+            });
+            if !declares_scope && state.scope(&access.project, scope).is_none() {
+                return invalid(format!(
+                    "Access `{}` refers to unknown scope `{}/{}`",
+                    path, access.project, scope
+                ));
+            }
+        }
+        if !users.contains(access.service_account.as_str())
+            && state.service_account(&access.service_account).is_none()
+        {
+            return invalid(format!(
+                "Access `{}` refers to unknown service account `{}`",
+                path, access.service_account
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::manifest::{
+        EnvironmentManifest, ProjectManifest, ScopeManifest, ServiceAccountManifest,
+    };
+    use crate::test_utils::{Backend, TestStore};
+    use anyhow::Error;
+    use rstest::rstest;
+
+    fn manifest() -> Manifest {
+        serde_json::from_value(json!({
+            "projects": [{
+                "name": "billing",
+                "labels": {"team": "payments"},
+                "environments": [{"name": "production"}],
+                "scopes": [{"name": "invoices:read"}, {"name": "invoices:write"}]
+            }],
+            "service_accounts": [{"user": "billing-worker", "email": "worker@example.com"}],
+            "access": [{
+                "project": "billing",
+                "environment": "production",
+                "service_account": "billing-worker",
+                "scopes": ["invoices:read"]
+            }]
+        }))
+        .unwrap()
+    }
+
+    /// Records matching `manifest`, as applying it would leave them.
+    fn state(manifest: &Manifest) -> State {
+        let mut state = State::default();
+        for declared in &manifest.projects {
+            let ProjectManifest {
+                name,
+                description,
+                enabled,
+                labels,
+                ..
+            } = declared.clone();
+            let project_id = Uuid::new();
+            state.projects.push(Project {
+                id: Some(project_id),
+                name,
+                description,
+                enabled,
+                labels,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+            });
+            for EnvironmentManifest {
+                name,
+                description,
+                enabled,
+                labels,
+            } in declared.environments.clone()
+            {
+                state.environments.push(Environment {
+                    id: Some(Uuid::new()),
+                    project_id,
+                    name,
+                    description,
+                    enabled,
+                    labels,
+                    created_at: None,
+                    updated_at: None,
+                    deleted_at: None,
+                    deleted_by: None,
+                });
+            }
+            for ScopeManifest {
+                name,
+                description,
+                enabled,
+                labels,
+            } in declared.scopes.clone()
+            {
+                state.scopes.push(ProjectScope {
+                    id: Some(Uuid::new()),
+                    project_id,
+                    name,
+                    description,
+                    enabled,
+                    labels,
+                    created_at: None,
+                    updated_at: None,
+                    deleted_at: None,
+                    deleted_by: None,
+                });
+            }
+        }
+        for declared in &manifest.service_accounts {
+            let ServiceAccountManifest {
+                user,
+                email,
+                enabled,
+                labels,
+                certificate_bindings,
+                ..
+            } = declared.clone();
+            let mut account = ServiceAccount::new(email, user, "secret".to_string());
+            account.id = Some(Uuid::new());
+            account.enabled = enabled;
+            account.labels = labels;
+            account.certificate_bindings = certificate_bindings;
+            state.service_accounts.push(account);
+        }
+        for declared in &manifest.access {
+            let environment = state
+                .environment(&declared.project, &declared.environment)
+                .unwrap();
+            let account = state.service_account(&declared.service_account).unwrap();
+            let project_scopes = declared
+                .scopes
+                .iter()
+                .map(|scope| state.scope(&declared.project, scope).unwrap().id.unwrap())
+                .collect();
+            let access = ProjectAccess {
+                id: Some(Uuid::new()),
+                name: declared.name(),
+                environment_id: environment.id.unwrap(),
+                service_account_id: account.id,
+                project_scopes,
+                enabled: declared.enabled,
+                labels: declared.labels.clone(),
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+            };
+            state.accesses.push(access);
+        }
+        state
+    }
+
+    fn summary(steps: &[Step]) -> Vec<(ChangeAction, ResourceKind, &str)> {
+        steps
+            .iter()
+            .map(|step| {
+                (
+                    step.change.action,
+                    step.change.kind,
+                    step.change.name.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_creates_parents_first() {
+        let steps = plan(&manifest(), &State::default(), &ApplyQuery::default()).unwrap();
+        assert_eq!(
+            summary(&steps),
+            vec![
+                (ChangeAction::Create, ResourceKind::Project, "billing"),
+                (
+                    ChangeAction::Create,
+                    ResourceKind::Environment,
+                    "billing/production"
+                ),
+                (
+                    ChangeAction::Create,
+                    ResourceKind::Scope,
+                    "billing/invoices:read"
+                ),
+                (
+                    ChangeAction::Create,
+                    ResourceKind::Scope,
+                    "billing/invoices:write"
+                ),
+                (
+                    ChangeAction::Create,
+                    ResourceKind::ServiceAccount,
+                    "billing-worker"
+                ),
+                (
+                    ChangeAction::Create,
+                    ResourceKind::Access,
+                    "billing/production/billing-worker"
+                ),
+            ]
+        );
+        assert!(steps.iter().all(|step| step.change.id.is_none()));
+
+        let account = &steps[4].change;
+        let secret = account
+            .diff
+            .iter()
+            .find(|change| change.field == "secret")
+            .unwrap();
+        assert_eq!(secret.to, json!(REDACTED));
+        let access = &steps[5].change;
+        let name = access
+            .diff
+            .iter()
+            .find(|change| change.field == "name")
+            .unwrap();
+        assert_eq!(
+            (&name.from, &name.to),
+            (&Value::Null, &json!("billing-worker-production"))
+        );
+    }
+
+    #[test]
+    fn test_plan_is_empty_when_state_matches() {
+        let manifest = manifest();
+        let state = state(&manifest);
+        let query = ApplyQuery {
+            prune: true,
+            all: true,
+            ..Default::default()
+        };
+        assert!(plan(&manifest, &state, &query).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_plan_updates_changed_fields_only() {
+        let mut manifest = manifest();
+        let state = state(&manifest);
+        manifest.projects[0]
+            .labels
+            .insert("tier".to_string(), "gold".to_string());
+        manifest.projects[0].environments[0].enabled = false;
+        manifest.service_accounts[0].secret = Some("rotated".to_string());
+        manifest.access[0].scopes.push("invoices:write".to_string());
+
+        let steps = plan(&manifest, &state, &ApplyQuery::default()).unwrap();
+        assert_eq!(
+            summary(&steps),
+            vec![
+                (ChangeAction::Update, ResourceKind::Project, "billing"),
+                (
+                    ChangeAction::Update,
+                    ResourceKind::Environment,
+                    "billing/production"
+                ),
+                (
+                    ChangeAction::Update,
+                    ResourceKind::ServiceAccount,
+                    "billing-worker"
+                ),
+                (
+                    ChangeAction::Update,
+                    ResourceKind::Access,
+                    "billing/production/billing-worker"
+                ),
+            ]
+        );
+        assert_eq!(steps[0].change.id, state.projects[0].id);
+        assert_eq!(
+            steps[0].change.diff,
+            vec![FieldChange {
+                field: "labels".to_string(),
+                from: json!({"team": "payments"}),
+                to: json!({"team": "payments", "tier": "gold"}),
+            }]
+        );
+        assert_eq!(steps[1].change.diff[0].field, "enabled");
+        assert_eq!(
+            steps[2].change.diff,
+            vec![FieldChange {
+                field: "secret".to_string(),
+                from: json!(REDACTED),
+                to: json!(REDACTED),
+            }]
+        );
+        assert_eq!(
+            steps[3].change.diff[0].to,
+            json!(["invoices:read", "invoices:write"])
+        );
+    }
+
+    #[test]
+    fn test_plan_prunes_undeclared_records() {
+        let mut declared = manifest();
+        declared.projects.push(
+            serde_json::from_value(json!({"name": "legacy", "labels": {"team": "payments"}}))
+                .unwrap(),
+        );
+        declared.projects[0]
+            .scopes
+            .push(serde_json::from_value(json!({"name": "refunds"})).unwrap());
+        declared.service_accounts.push(serde_json::from_value(json!({"user": "intern", "email": "intern@example.com", "labels": {"team": "growth"}})).unwrap());
+        let state = state(&declared);
+
+        let manifest = manifest();
+        assert!(
+            plan(&manifest, &state, &ApplyQuery::default())
+                .unwrap()
+                .is_empty()
+        );
+
+        let query = ApplyQuery {
+            prune: true,
+            all: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            summary(&plan(&manifest, &state, &query).unwrap()),
+            vec![
+                (ChangeAction::Delete, ResourceKind::Scope, "billing/refunds"),
+                (ChangeAction::Delete, ResourceKind::ServiceAccount, "intern"),
+                (ChangeAction::Delete, ResourceKind::Project, "legacy"),
+            ]
+        );
+
+        let query = ApplyQuery {
+            prune: true,
+            selector: Some("team=payments".parse().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            summary(&plan(&manifest, &state, &query).unwrap()),
+            vec![(ChangeAction::Delete, ResourceKind::Project, "legacy")]
+        );
+
+        // Records referred to by an access are kept, even when not declared
+        let mut manifest = manifest;
+        manifest.projects.clear();
+        manifest.service_accounts.clear();
+        let query = ApplyQuery {
+            prune: true,
+            all: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            summary(&plan(&manifest, &state, &query).unwrap()),
+            vec![
+                (
+                    ChangeAction::Delete,
+                    ResourceKind::Scope,
+                    "billing/invoices:write"
+                ),
+                (ChangeAction::Delete, ResourceKind::Scope, "billing/refunds"),
+                (ChangeAction::Delete, ResourceKind::ServiceAccount, "intern"),
+                (ChangeAction::Delete, ResourceKind::Project, "legacy"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_needs_selector_to_prune() {
+        let manifest = manifest();
+        let state = state(&manifest);
+        let invalid = |query: &ApplyQuery| match plan(&manifest, &state, query) {
+            Err(AppError::Validation(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other.is_ok()),
+        };
+
+        let query = ApplyQuery {
+            prune: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            invalid(&query),
+            "Pruning needs a selector, or `all` to prune every undeclared record"
+        );
+        let query = ApplyQuery {
+            prune: true,
+            all: true,
+            selector: Some("team=payments".parse().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(invalid(&query), "`selector` and `all` cannot be combined");
+    }
+
+    #[test]
+    fn test_plan_validates_manifest() {
+        let empty = State::default();
+        let query = ApplyQuery::default();
+        let invalid = |declared: &Manifest| match plan(declared, &empty, &query) {
+            Err(AppError::Validation(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other.is_ok()),
+        };
+
+        let mut declared = manifest();
+        let environment = declared.projects[0].environments[0].clone();
+        declared.projects[0].environments.push(environment);
+        assert_eq!(
+            invalid(&declared),
+            "Environment `billing/production` is declared twice"
+        );
+
+        let mut declared = manifest();
+        declared.access[0].scopes.push("refunds".to_string());
+        assert_eq!(
+            invalid(&declared),
+            "Access `billing/production/billing-worker` refers to unknown scope `billing/refunds`"
+        );
+
+        let mut declared = manifest();
+        declared.service_accounts.clear();
+        assert_eq!(
+            invalid(&declared),
+            "Access `billing/production/billing-worker` refers to unknown service account `billing-worker`"
+        );
+        // References to existing records are fine
+        let existing = state(&manifest());
+        assert!(plan(&declared, &existing, &query).unwrap().is_empty());
+
+        let mut declared = manifest();
+        declared.projects[0]
+            .labels
+            .insert("Bad Key".to_string(), "x".to_string());
+        assert!(matches!(
+            plan(&declared, &empty, &query),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_apply(#[case] backend: Backend) -> Result<(), Error> {
+        let store = TestStore::setup("manifest_service", backend).await?;
+        let service = ManifestService::with_repositories(&store.repositories)?;
+        let manifest = manifest();
+
+        let dry_run = ApplyQuery {
+            dry_run: true,
+            ..Default::default()
+        };
+        let response = service.apply(&manifest, &dry_run, None).await?;
+        assert!(!response.applied);
+        assert_eq!(response.changes.len(), 6);
+
+        let response = service
+            .apply(&manifest, &ApplyQuery::default(), None)
+            .await?;
+        assert!(response.applied);
+        assert_eq!(response.changes.len(), 6);
+        assert_eq!(response.generated_secrets.len(), 1);
+        assert_eq!(
+            response.generated_secrets[0].service_account,
+            "billing-worker"
+        );
+
+        let accesses = service
+            .project_access_service
+            .find(ProjectAccessFilter::default(), None, None)
+            .await?;
+        assert_eq!(accesses.len(), 1);
+        assert_eq!(accesses[0].project_scopes.len(), 1);
+
+        // Applying again changes nothing
+        let response = service
+            .apply(&manifest, &ApplyQuery::default(), None)
+            .await?;
+        assert!(response.changes.is_empty());
+
+        let mut pruned = manifest.clone();
+        pruned.access.clear();
+        pruned.service_accounts.clear();
+        let prune = ApplyQuery {
+            prune: true,
+            all: true,
+            ..Default::default()
+        };
+        let response = service
+            .apply(&pruned, &prune, Some("admin".to_string()))
+            .await?;
+        assert_eq!(
+            response
+                .changes
+                .iter()
+                .map(|change| change.kind)
+                .collect::<Vec<_>>(),
+            vec![ResourceKind::Access, ResourceKind::ServiceAccount]
+        );
+        assert!(
+            service
+                .service_account_service
+                .find(ServiceAccountFilter::default(), None, None)
+                .await?
+                .is_empty()
+        );
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_apply_prune_keeps_unselected_records(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let store = TestStore::setup("manifest_service_prune", backend).await?;
+        let service = ManifestService::with_repositories(&store.repositories)?;
+
+        let mut declared = manifest();
+        declared.projects.push(
+            serde_json::from_value(json!({"name": "legacy", "labels": {"team": "payments"}}))
+                .unwrap(),
+        );
+        declared.projects.push(
+            serde_json::from_value(json!({"name": "ledger", "labels": {"team": "growth"}}))
+                .unwrap(),
+        );
+        declared.service_accounts.push(serde_json::from_value(json!({"user": "intern", "email": "intern@example.com", "labels": {"team": "growth"}})).unwrap());
+        service
+            .apply(&declared, &ApplyQuery::default(), None)
+            .await?;
+
+        let query = ApplyQuery {
+            prune: true,
+            selector: Some("team=payments".parse().unwrap()),
+            ..Default::default()
+        };
+        let response = service.apply(&manifest(), &query, None).await?;
+        assert_eq!(
+            response
+                .changes
+                .iter()
+                .map(|change| (change.action, change.kind, change.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(ChangeAction::Delete, ResourceKind::Project, "legacy")]
+        );
+
+        let mut projects: Vec<String> = service
+            .project_service
+            .find(ProjectFilter::default(), None, None)
+            .await?
+            .into_iter()
+            .map(|project| project.name)
+            .collect();
+        projects.sort();
+        assert_eq!(projects, vec!["billing", "ledger"]);
+        let accounts = service
+            .service_account_service
+            .find(ServiceAccountFilter::default(), None, None)
+            .await?;
+        assert!(accounts.iter().any(|account| account.user == "intern"));
+
+        store.cleanup().await?;
+        Ok(())
+    }
+}
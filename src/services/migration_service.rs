@@ -0,0 +1,167 @@
+use crate::errors::AppError;
+use crate::migrations::{self, Migration};
+use crate::models::migration::MigrationStatus;
+use crate::repositories::migration_repository::MigrationRepository;
+use chrono::{Duration, Utc};
+use mongodb::Database;
+use mongodb::bson::uuid::Uuid;
+use std::sync::Arc;
+
+/// How long the lock is held without being renewed. It is renewed before each
+/// migration, so this bounds the time a single migration may take.
+const LOCK_TTL: Duration = Duration::minutes(10);
+/// How often an instance waiting for the lock checks whether it was released.
+const LOCK_POLL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Applies pending [migrations](crate::migrations) to the database.
+pub struct MigrationService {
+    database: Arc<Database>,
+    migration_repository: MigrationRepository,
+    migrations: Vec<Box<dyn Migration>>,
+}
+
+impl MigrationService {
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Ok(Self {
+            migration_repository: MigrationRepository::new(database.as_ref().clone())?,
+            database,
+            migrations: migrations::all(),
+        })
+    }
+
+    /// Every known migration, in order, with when it was applied.
+    #[tracing::instrument(skip_all, name = "MigrationService::status")]
+    pub async fn status(&self) -> Result<Vec<MigrationStatus>, AppError> {
+        let applied = self.migration_repository.applied().await?;
+        Ok(self
+            .migrations
+            .iter()
+            .map(|migration| MigrationStatus {
+                name: migration.name().to_string(),
+                description: migration.description().to_string(),
+                applied_at: applied
+                    .iter()
+                    .find(|record| record.name == migration.name())
+                    .map(|record| record.applied_at),
+            })
+            .collect())
+    }
+
+    /// Applies the pending migrations in order.
+    ///
+    /// Only one instance migrates at a time: the others wait for the lock and
+    /// then find nothing left to apply.
+    ///
+    /// # Returns
+    ///
+    /// The names of the migrations applied, empty if none were pending.
+    ///
+    /// # Errors
+    ///
+    /// `AppError::Conflict` if another instance held the lock for longer than
+    /// its expiry; any error of a failed migration, whose successors are then
+    /// not run.
+    #[tracing::instrument(skip_all, name = "MigrationService::up")]
+    pub async fn up(&self) -> Result<Vec<String>, AppError> {
+        let owner = Uuid::new().to_string();
+        self.acquire(&owner).await?;
+        let result = self.apply(&owner).await;
+        self.migration_repository.unlock(&owner).await?;
+        result
+    }
+
+    async fn acquire(&self, owner: &str) -> Result<(), AppError> {
+        let deadline = Utc::now() + LOCK_TTL;
+        while !self
+            .migration_repository
+            .lock(owner, LOCK_TTL, Utc::now())
+            .await?
+        {
+            if Utc::now() > deadline {
+                return Err(AppError::Conflict(
+                    "Migrations are being applied by another instance".to_string(),
+                ));
+            }
+            tracing::info!("Waiting for another instance to finish migrating");
+            tokio::time::sleep(LOCK_POLL).await;
+        }
+        Ok(())
+    }
+
+    async fn apply(&self, owner: &str) -> Result<Vec<String>, AppError> {
+        let status = self.status().await?;
+        let mut applied = Vec::new();
+        for (migration, status) in self.migrations.iter().zip(status) {
+            if status.applied_at.is_some() {
+                continue;
+            }
+            if !self
+                .migration_repository
+                .lock(owner, LOCK_TTL, Utc::now())
+                .await?
+            {
+                return Err(AppError::Conflict(
+                    "Lost the migration lock to another instance".to_string(),
+                ));
+            }
+            tracing::info!(migration = migration.name(), "Applying migration");
+            migration.up(&self.database).await?;
+            self.migration_repository
+                .record(migration.name(), Utc::now())
+                .await?;
+            applied.push(migration.name().to_string());
+        }
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_up_applies_pending_migrations_once() -> Result<()> {
+        let db = setup_test_db("migration_service").await?;
+        let service = MigrationService::new(Arc::new(db.clone()))?;
+
+        let status = service.status().await?;
+        assert!(!status.is_empty());
+        assert!(
+            status
+                .iter()
+                .all(|migration| migration.applied_at.is_none())
+        );
+
+        let applied = service.up().await?;
+        assert_eq!(
+            applied,
+            status.iter().map(|m| m.name.clone()).collect::<Vec<_>>()
+        );
+        assert!(
+            service
+                .status()
+                .await?
+                .iter()
+                .all(|migration| migration.applied_at.is_some())
+        );
+        assert!(service.up().await?.is_empty());
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_up_runs_once_across_instances() -> Result<()> {
+        let db = setup_test_db("migration_service_concurrent").await?;
+        let first = MigrationService::new(Arc::new(db.clone()))?;
+        let second = MigrationService::new(Arc::new(db.clone()))?;
+
+        let (a, b) = tokio::join!(first.up(), second.up());
+        assert_eq!(a?.len() + b?.len(), migrations::all().len());
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+}
@@ -1,8 +1,16 @@
 pub mod access_token_service;
+pub mod authentication_service;
 pub mod environment_service;
+pub mod health_service;
+pub mod manifest_service;
+pub mod migration_service;
 pub mod project_access_service;
+pub mod project_bundle_service;
 pub mod project_scope_service;
 pub mod project_service;
+pub mod project_template_service;
+pub mod purge_service;
 pub mod server_key_service;
 pub mod service_account_key_service;
 pub mod service_account_service;
+pub mod token_service;
@@ -1,76 +1,116 @@
-use crate::models::pagination::Pagination;
+use crate::errors::AppError;
+use crate::models::label::validate_labels;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::project_access::{
     ProjectAccess, ProjectAccessFilter, ProjectAccessSortableFields, ProjectAccessUpdatePayload,
 };
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use crate::repositories::project_access_repository::ProjectAccessRepository;
-use anyhow::Error;
+use crate::repositories::project_access_repository::DynProjectAccessRepository;
+use crate::repositories::registry::Repositories;
 use mongodb::Database;
 use mongodb::bson::uuid::Uuid;
 use std::sync::Arc;
 
 pub struct ProjectAccessService {
-    project_access_repository: ProjectAccessRepository,
+    project_access_repository: Arc<DynProjectAccessRepository>,
 }
 
 impl ProjectAccessService {
-    pub fn new(database: Arc<Database>) -> Result<Self, Error> {
-        let project_access_repository = ProjectAccessRepository::new(database.as_ref().clone())?;
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
+    }
+
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
         Ok(Self {
-            project_access_repository,
+            project_access_repository: repositories.project_access.clone(),
         })
     }
 
-    pub async fn create(&self, project_access: ProjectAccess) -> Result<ProjectAccess, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectAccessService::create")]
+    pub async fn create(&self, project_access: ProjectAccess) -> Result<ProjectAccess, AppError> {
+        validate_labels(&project_access.labels)?;
         self.project_access_repository.create(project_access).await
     }
 
-    pub async fn get_project_access(&self, id: Uuid) -> Result<Option<ProjectAccess>, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectAccessService::get_project_access")]
+    pub async fn get_project_access(&self, id: Uuid) -> Result<Option<ProjectAccess>, AppError> {
         self.project_access_repository.read(id).await
     }
 
+    #[tracing::instrument(skip_all, name = "ProjectAccessService::update")]
     pub async fn update(
         &self,
         id: Uuid,
         project_access: ProjectAccessUpdatePayload,
-    ) -> Result<ProjectAccess, Error> {
+    ) -> Result<ProjectAccess, AppError> {
+        if let Some(labels) = &project_access.labels {
+            validate_labels(labels)?;
+        }
         self.project_access_repository
             .update(id, project_access)
             .await
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<bool, Error> {
-        self.project_access_repository.delete(id).await
+    #[tracing::instrument(skip_all, name = "ProjectAccessService::delete")]
+    pub async fn delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        self.project_access_repository
+            .soft_delete(id, deleted_by)
+            .await
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectAccessService::restore")]
+    pub async fn restore(&self, id: Uuid) -> Result<Option<ProjectAccess>, AppError> {
+        self.project_access_repository.restore(id).await
     }
 
+    #[tracing::instrument(skip_all, name = "ProjectAccessService::find")]
     pub async fn find(
         &self,
         filter: ProjectAccessFilter,
         sort: Option<SortBuilder<ProjectAccessSortableFields>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<ProjectAccess>, Error> {
+    ) -> Result<Vec<ProjectAccess>, AppError> {
         self.project_access_repository
             .find(filter, sort, pagination)
             .await
     }
+
+    #[tracing::instrument(skip_all, name = "ProjectAccessService::list")]
+    pub async fn list(
+        &self,
+        filter: ProjectAccessFilter,
+        sort: Option<SortBuilder<ProjectAccessSortableFields>>,
+        pagination: Pagination,
+    ) -> Result<Page<ProjectAccess>, AppError> {
+        self.project_access_repository
+            .find_page(filter, sort, pagination)
+            .await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::test_utils::{Backend, TestStore};
+    use anyhow::Error;
     use chrono::Utc;
+    use rstest::rstest;
 
-    async fn setup() -> (ProjectAccessService, Database) {
-        let db = setup_test_db("project_access_service").await.unwrap();
-        let service = ProjectAccessService::new(Arc::new(db.clone())).unwrap();
-        (service, db)
+    async fn setup(backend: Backend) -> (ProjectAccessService, TestStore) {
+        let store = TestStore::setup("project_access_service", backend)
+            .await
+            .unwrap();
+        let service = ProjectAccessService::with_repositories(&store.repositories).unwrap();
+        (service, store)
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_create_project_access() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_create_project_access(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project_access = ProjectAccess {
             id: None,
             name: "Test Access".to_string(),
@@ -80,6 +120,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(project_access.clone()).await?;
@@ -89,13 +132,17 @@ mod tests {
         assert!(created.created_at.is_some());
         assert!(created.updated_at.is_some());
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_get_project_access() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_get_project_access(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project_access = ProjectAccess {
             id: None,
             name: "Test Access".to_string(),
@@ -105,6 +152,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(project_access.clone()).await?;
@@ -118,13 +168,17 @@ mod tests {
         assert_eq!(retrieved.created_at, created.created_at);
         assert_eq!(retrieved.updated_at, created.updated_at);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_update_project_access() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_update_project_access(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project_access = ProjectAccess {
             id: None,
             name: "Test Access".to_string(),
@@ -134,6 +188,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(project_access).await?;
@@ -141,6 +198,7 @@ mod tests {
             name: Some("Updated Access".to_string()),
             project_scopes: Some(vec![Uuid::new()]),
             enabled: Some(false),
+            labels: None,
         };
 
         let updated = service.update(created.id.unwrap(), update).await?;
@@ -148,13 +206,17 @@ mod tests {
         assert!(!updated.enabled);
         assert!(updated.updated_at.unwrap() > created.updated_at.unwrap());
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_delete_project_access() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_delete_project_access(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project_access = ProjectAccess {
             id: None,
             name: "Test Access".to_string(),
@@ -164,22 +226,29 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(project_access).await?;
-        let deleted = service.delete(created.id.unwrap()).await?;
+        let deleted = service.delete(created.id.unwrap(), None).await?;
         assert!(deleted);
 
         let read = service.get_project_access(created.id.unwrap()).await?;
         assert!(read.is_none());
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_project_access_with_filter() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_project_access_with_filter(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let env_id = Uuid::new();
         let project_access1 = ProjectAccess {
             id: None,
@@ -190,6 +259,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let project_access2 = ProjectAccess {
             id: None,
@@ -200,6 +272,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         service.create(project_access1).await?;
@@ -210,19 +285,26 @@ mod tests {
             service_account_id: None,
             project_scopes: None,
             is_enabled: None,
+            ..Default::default()
         };
 
         let found = service.find(filter, None, None).await?;
         assert_eq!(found.len(), 1);
         assert_eq!(found[0].environment_id, env_id);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_project_access_with_pagination() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_project_access_with_pagination(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
 
         // Create 5 test project accesses
         for i in 1..=5 {
@@ -235,6 +317,9 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
             service.create(project_access).await?;
         }
@@ -243,6 +328,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(1),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -251,6 +337,7 @@ mod tests {
                     service_account_id: None,
                     project_scopes: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -264,6 +351,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(2),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -272,6 +360,7 @@ mod tests {
                     service_account_id: None,
                     project_scopes: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -281,7 +370,7 @@ mod tests {
         assert_eq!(found[0].name, "Access 3");
         assert_eq!(found[1].name, "Access 4");
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 }
@@ -0,0 +1,901 @@
+use crate::errors::AppError;
+use crate::models::environment::{Environment, EnvironmentFilter};
+use crate::models::manifest::GeneratedSecret;
+use crate::models::project::{Project, ProjectFilter};
+use crate::models::project_access::{ProjectAccess, ProjectAccessFilter};
+use crate::models::project_bundle::{
+    BUNDLE_FORMAT, BUNDLE_VERSION, BundleEncryption, BundledAccess, BundledKind, BundledRecord,
+    BundledServerKey, BundledServiceAccount, IdMapping, ImportConflict, ProjectBundle,
+    ProjectImportOutcome, ProjectImportQuery, ProjectImportReport,
+};
+use crate::models::project_scope::{ProjectScope, ProjectScopeFilter};
+use crate::models::service_account::{ServiceAccount, ServiceAccountFilter};
+use crate::repositories::registry::Repositories;
+use crate::services::environment_service::EnvironmentService;
+use crate::services::project_access_service::ProjectAccessService;
+use crate::services::project_scope_service::ProjectScopeService;
+use crate::services::project_service::ProjectService;
+use crate::services::server_key_service::ServerKeyService;
+use crate::services::service_account_service::ServiceAccountService;
+use crate::utils::security::{PassphraseCipher, generate_secret};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::Utc;
+use mongodb::Database;
+use mongodb::bson::uuid::Uuid;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+const ENCRYPTION_ALGORITHM: &str = "AES-256-GCM";
+const ENCRYPTION_KDF: &str = "PBKDF2-HMAC-SHA256";
+
+/// Exports projects as bundles and imports them, to move a project between
+/// installations.
+pub struct ProjectBundleService {
+    project_service: ProjectService,
+    environment_service: EnvironmentService,
+    project_scope_service: ProjectScopeService,
+    service_account_service: ServiceAccountService,
+    project_access_service: ProjectAccessService,
+    server_key_service: ServerKeyService,
+}
+
+/// Secrets of a bundle, decrypted.
+#[derive(Default)]
+struct Secrets {
+    /// Service account secrets by bundled service account id
+    service_accounts: HashMap<Uuid, String>,
+    /// Key material by bundled server key id
+    server_keys: HashMap<Uuid, String>,
+}
+
+impl ProjectBundleService {
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
+    }
+
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
+        Ok(Self {
+            project_service: ProjectService::with_repositories(repositories)?,
+            environment_service: EnvironmentService::with_repositories(repositories)?,
+            project_scope_service: ProjectScopeService::with_repositories(repositories)?,
+            service_account_service: ServiceAccountService::with_repositories(repositories)?,
+            project_access_service: ProjectAccessService::with_repositories(repositories)?,
+            server_key_service: ServerKeyService::with_repositories(repositories)?,
+        })
+    }
+
+    /// Bundles a project with its environments, scopes and accesses, and the
+    /// service accounts those accesses are for.
+    ///
+    /// Without a passphrase, service account secrets and server keys are left
+    /// out. With one, they are included, encrypted under it.
+    ///
+    /// # Returns
+    /// `None` if the project does not exist.
+    #[tracing::instrument(skip_all, name = "ProjectBundleService::export")]
+    pub async fn export(
+        &self,
+        id: Uuid,
+        passphrase: Option<&str>,
+    ) -> Result<Option<ProjectBundle>, AppError> {
+        let Some(project) = self.project_service.get_project(id).await? else {
+            return Ok(None);
+        };
+        let cipher = match passphrase {
+            Some(passphrase) => {
+                let passphrase = passphrase.to_string();
+                Some(derive_cipher(move || PassphraseCipher::new(&passphrase)).await?)
+            }
+            None => None,
+        };
+
+        let environments = self
+            .environment_service
+            .find(
+                EnvironmentFilter {
+                    project_id: Some(id),
+                    ..Default::default()
+                },
+                None,
+                None,
+            )
+            .await?;
+        let scopes = self
+            .project_scope_service
+            .find(
+                ProjectScopeFilter {
+                    project_id: Some(id),
+                    ..Default::default()
+                },
+                None,
+                None,
+            )
+            .await?;
+        let mut accesses = Vec::new();
+        let mut server_keys = Vec::new();
+        for environment in &environments {
+            let Some(environment_id) = environment.id else {
+                continue;
+            };
+            accesses.extend(
+                self.project_access_service
+                    .find(
+                        ProjectAccessFilter {
+                            environment_id: Some(environment_id),
+                            ..Default::default()
+                        },
+                        None,
+                        None,
+                    )
+                    .await?,
+            );
+            if let Some(cipher) = &cipher {
+                for (server_key, private_key) in
+                    self.server_key_service.export(environment_id).await?
+                {
+                    server_keys.push(BundledServerKey {
+                        id: server_key.id,
+                        environment_id,
+                        algorithm: server_key.algorithm,
+                        key: cipher.encrypt(&private_key)?,
+                        created_at: server_key.created_at,
+                    });
+                }
+            }
+        }
+
+        let service_account_ids: Vec<Uuid> = accesses
+            .iter()
+            .filter_map(|access| access.service_account_id)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let service_accounts = match service_account_ids.is_empty() {
+            true => Vec::new(),
+            false => {
+                self.service_account_service
+                    .find(
+                        ServiceAccountFilter {
+                            id_in: Some(service_account_ids),
+                            ..Default::default()
+                        },
+                        None,
+                        None,
+                    )
+                    .await?
+            }
+        };
+        let service_accounts = service_accounts
+            .into_iter()
+            .map(|account| {
+                Ok(BundledServiceAccount {
+                    id: account.id.unwrap_or_default(),
+                    secret: cipher
+                        .as_ref()
+                        .map(|cipher| cipher.encrypt(&account.secret))
+                        .transpose()?,
+                    user: account.user,
+                    email: account.email,
+                    enabled: account.enabled,
+                    labels: account.labels,
+                    certificate_bindings: account.certificate_bindings,
+                })
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        // Accesses are only kept with their service account: those without
+        // one, or whose account is deleted, are left out
+        let bundled_accounts: HashSet<Uuid> =
+            service_accounts.iter().map(|account| account.id).collect();
+
+        Ok(Some(ProjectBundle {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            exported_at: Utc::now(),
+            project: BundledRecord {
+                id,
+                name: project.name,
+                description: project.description,
+                enabled: project.enabled,
+                labels: project.labels,
+            },
+            environments: environments
+                .into_iter()
+                .map(|environment| BundledRecord {
+                    id: environment.id.unwrap_or_default(),
+                    name: environment.name,
+                    description: environment.description,
+                    enabled: environment.enabled,
+                    labels: environment.labels,
+                })
+                .collect(),
+            scopes: scopes
+                .into_iter()
+                .map(|scope| BundledRecord {
+                    id: scope.id.unwrap_or_default(),
+                    name: scope.name,
+                    description: scope.description,
+                    enabled: scope.enabled,
+                    labels: scope.labels,
+                })
+                .collect(),
+            service_accounts,
+            access: accesses
+                .into_iter()
+                .filter_map(|access| {
+                    let service_account_id = access.service_account_id?;
+                    bundled_accounts
+                        .contains(&service_account_id)
+                        .then(|| BundledAccess {
+                            id: access.id.unwrap_or_default(),
+                            name: access.name,
+                            environment_id: access.environment_id,
+                            service_account_id,
+                            project_scopes: access.project_scopes,
+                            enabled: access.enabled,
+                            labels: access.labels,
+                        })
+                })
+                .collect(),
+            server_keys,
+            encryption: cipher.map(|cipher| BundleEncryption {
+                algorithm: ENCRYPTION_ALGORITHM.to_string(),
+                kdf: ENCRYPTION_KDF.to_string(),
+                salt: STANDARD.encode(cipher.salt()),
+                iterations: cipher.iterations(),
+            }),
+        }))
+    }
+
+    /// Recreates a bundled project, with new ids for every record.
+    ///
+    /// Bundled service accounts whose `user` exists already are linked to
+    /// instead of created. Those created without a bundled secret get a
+    /// generated one, returned in the report. Nothing is imported when the
+    /// project name is taken or a service account's email belongs to another
+    /// account; those conflicts are returned instead.
+    ///
+    /// The import is not transactional: if a record fails to be created, those
+    /// before it remain, and can be removed by deleting the project with
+    /// `cascade`.
+    ///
+    /// # Errors
+    /// - `AppError::Validation` if the bundle is of another format or version,
+    ///   refers to records it does not hold, or holds secrets and the
+    ///   passphrase is missing or wrong
+    #[tracing::instrument(skip_all, name = "ProjectBundleService::import")]
+    pub async fn import(
+        &self,
+        bundle: &ProjectBundle,
+        query: &ProjectImportQuery,
+        passphrase: Option<&str>,
+    ) -> Result<ProjectImportOutcome, AppError> {
+        check_bundle(bundle)?;
+        let secrets = decrypt_secrets(bundle, passphrase).await?;
+        let name = query.name.as_deref().unwrap_or(&bundle.project.name);
+
+        let project_exists = self
+            .project_service
+            .find(
+                ProjectFilter {
+                    name: Some(name.to_string()),
+                    ..Default::default()
+                },
+                None,
+                None,
+            )
+            .await?
+            .into_iter()
+            .next()
+            .is_some();
+        let existing_accounts = self
+            .service_account_service
+            .find(ServiceAccountFilter::default(), None, None)
+            .await?;
+        let (reused, conflicts) = reconcile(bundle, name, project_exists, &existing_accounts);
+        if !conflicts.is_empty() {
+            return Ok(ProjectImportOutcome::Conflicts(conflicts));
+        }
+
+        let mut report = ProjectImportReport {
+            reused_service_accounts: bundle
+                .service_accounts
+                .iter()
+                .filter(|account| reused.contains_key(&account.id))
+                .map(|account| account.user.clone())
+                .collect(),
+            ..Default::default()
+        };
+        if query.dry_run {
+            return Ok(ProjectImportOutcome::Imported(report));
+        }
+
+        let mut ids = Remap::default();
+        let project = self
+            .project_service
+            .create(Project {
+                id: None,
+                name: name.to_string(),
+                description: bundle.project.description.clone(),
+                enabled: bundle.project.enabled,
+                labels: bundle.project.labels.clone(),
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+            })
+            .await?;
+        let project_id = ids.insert(BundledKind::Project, bundle.project.id, project.id)?;
+
+        for bundled in &bundle.environments {
+            let environment = self
+                .environment_service
+                .create(Environment {
+                    id: None,
+                    project_id,
+                    name: bundled.name.clone(),
+                    description: bundled.description.clone(),
+                    enabled: bundled.enabled,
+                    labels: bundled.labels.clone(),
+                    created_at: None,
+                    updated_at: None,
+                    deleted_at: None,
+                    deleted_by: None,
+                })
+                .await?;
+            ids.insert(BundledKind::Environment, bundled.id, environment.id)?;
+        }
+        for bundled in &bundle.scopes {
+            let scope = self
+                .project_scope_service
+                .create(ProjectScope {
+                    id: None,
+                    project_id,
+                    name: bundled.name.clone(),
+                    description: bundled.description.clone(),
+                    enabled: bundled.enabled,
+                    labels: bundled.labels.clone(),
+                    created_at: None,
+                    updated_at: None,
+                    deleted_at: None,
+                    deleted_by: None,
+                })
+                .await?;
+            ids.insert(BundledKind::Scope, bundled.id, scope.id)?;
+        }
+        let mut generated_secrets = Vec::new();
+        for bundled in &bundle.service_accounts {
+            if let Some(existing) = reused.get(&bundled.id) {
+                ids.insert(BundledKind::ServiceAccount, bundled.id, Some(*existing))?;
+                continue;
+            }
+            let secret = match secrets.service_accounts.get(&bundled.id) {
+                Some(secret) => secret.clone(),
+                None => {
+                    let secret = generate_secret();
+                    generated_secrets.push(GeneratedSecret {
+                        service_account: bundled.user.clone(),
+                        secret: secret.clone(),
+                    });
+                    secret
+                }
+            };
+            let mut account =
+                ServiceAccount::new(bundled.email.clone(), bundled.user.clone(), secret);
+            account.enabled = bundled.enabled;
+            account.labels = bundled.labels.clone();
+            account.certificate_bindings = bundled.certificate_bindings.clone();
+            let account = self.service_account_service.create(account).await?;
+            ids.insert(BundledKind::ServiceAccount, bundled.id, account.id)?;
+        }
+        for bundled in &bundle.access {
+            let access = self
+                .project_access_service
+                .create(ProjectAccess {
+                    id: None,
+                    name: bundled.name.clone(),
+                    environment_id: ids.get(bundled.environment_id),
+                    service_account_id: Some(ids.get(bundled.service_account_id)),
+                    project_scopes: bundled
+                        .project_scopes
+                        .iter()
+                        .map(|id| ids.get(*id))
+                        .collect(),
+                    enabled: bundled.enabled,
+                    labels: bundled.labels.clone(),
+                    created_at: None,
+                    updated_at: None,
+                    deleted_at: None,
+                    deleted_by: None,
+                })
+                .await?;
+            ids.insert(BundledKind::Access, bundled.id, access.id)?;
+        }
+        // Oldest first, so the newest bundled key stays the one tokens are signed with
+        let mut server_keys: Vec<&BundledServerKey> = bundle.server_keys.iter().collect();
+        server_keys.sort_by_key(|server_key| server_key.created_at);
+        for bundled in server_keys {
+            let server_key = self
+                .server_key_service
+                .import(
+                    ids.get(bundled.environment_id),
+                    bundled.algorithm,
+                    &secrets.server_keys[&bundled.id],
+                )
+                .await?;
+            ids.insert(BundledKind::ServerKey, bundled.id, Some(server_key.id))?;
+        }
+
+        report.imported = true;
+        report.ids = ids.mappings;
+        report.project_id = Some(project_id);
+        report.generated_secrets = generated_secrets;
+        Ok(ProjectImportOutcome::Imported(report))
+    }
+}
+
+/// New ids of the bundled records created so far.
+#[derive(Default)]
+struct Remap {
+    ids: HashMap<Uuid, Uuid>,
+    mappings: Vec<IdMapping>,
+}
+
+impl Remap {
+    fn insert(
+        &mut self,
+        kind: BundledKind,
+        from: Uuid,
+        to: Option<Uuid>,
+    ) -> Result<Uuid, AppError> {
+        let to = to.ok_or_else(|| anyhow::anyhow!("Created record has no id"))?;
+        self.ids.insert(from, to);
+        self.mappings.push(IdMapping { kind, from, to });
+        Ok(to)
+    }
+
+    /// New id of a bundled record; [`check_bundle`] ensures every reference
+    /// is to a record created before.
+    fn get(&self, from: Uuid) -> Uuid {
+        self.ids[&from]
+    }
+}
+
+/// Checks that a bundle is of the supported format and version, and that its
+/// records only refer to records it holds.
+fn check_bundle(bundle: &ProjectBundle) -> Result<(), AppError> {
+    let invalid = |message: String| Err(AppError::Validation(message));
+    if bundle.format != BUNDLE_FORMAT {
+        return invalid(format!("Not a project bundle: format `{}`", bundle.format));
+    }
+    if bundle.version != BUNDLE_VERSION {
+        return invalid(format!(
+            "Unsupported bundle version {}; this server reads version {}",
+            bundle.version, BUNDLE_VERSION
+        ));
+    }
+
+    let environments: HashSet<Uuid> = bundle.environments.iter().map(|e| e.id).collect();
+    let scopes: HashSet<Uuid> = bundle.scopes.iter().map(|s| s.id).collect();
+    let service_accounts: HashSet<Uuid> = bundle.service_accounts.iter().map(|a| a.id).collect();
+    let mut names = HashSet::new();
+    for environment in &bundle.environments {
+        if !names.insert(("environment", environment.name.as_str())) {
+            return invalid(format!(
+                "Environment `{}` is bundled twice",
+                environment.name
+            ));
+        }
+    }
+    for scope in &bundle.scopes {
+        if !names.insert(("scope", scope.name.as_str())) {
+            return invalid(format!("Scope `{}` is bundled twice", scope.name));
+        }
+    }
+    for account in &bundle.service_accounts {
+        if !names.insert(("service account", account.user.as_str())) {
+            return invalid(format!(
+                "Service account `{}` is bundled twice",
+                account.user
+            ));
+        }
+    }
+    for access in &bundle.access {
+        if !environments.contains(&access.environment_id)
+            || !service_accounts.contains(&access.service_account_id)
+            || !access.project_scopes.iter().all(|id| scopes.contains(id))
+        {
+            return invalid(format!(
+                "Access `{}` refers to records missing from the bundle",
+                access.name
+            ));
+        }
+    }
+    for server_key in &bundle.server_keys {
+        if !environments.contains(&server_key.environment_id) {
+            return invalid(format!(
+                "Server key {} refers to an environment missing from the bundle",
+                server_key.id
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Derives a passphrase cipher on the blocking thread pool: PBKDF2 is slow by
+/// design and would otherwise stall the worker serving other requests.
+async fn derive_cipher<F>(derive: F) -> Result<PassphraseCipher, AppError>
+where
+    F: FnOnce() -> anyhow::Result<PassphraseCipher> + Send + 'static,
+{
+    tokio::task::spawn_blocking(derive)
+        .await
+        .map_err(|e| AppError::Internal(e.into()))?
+        .map_err(|e| AppError::Validation(e.to_string()))
+}
+
+/// Decrypts the secrets of a bundle, all of them before anything is imported
+/// so a wrong passphrase changes nothing.
+async fn decrypt_secrets(
+    bundle: &ProjectBundle,
+    passphrase: Option<&str>,
+) -> Result<Secrets, AppError> {
+    let encrypted_accounts: Vec<&BundledServiceAccount> = bundle
+        .service_accounts
+        .iter()
+        .filter(|account| account.secret.is_some())
+        .collect();
+    let Some(encryption) = &bundle.encryption else {
+        if !encrypted_accounts.is_empty() || !bundle.server_keys.is_empty() {
+            return Err(AppError::Validation(
+                "The bundle holds secrets but does not say how they are encrypted".to_string(),
+            ));
+        }
+        return Ok(Secrets::default());
+    };
+    if encryption.algorithm != ENCRYPTION_ALGORITHM || encryption.kdf != ENCRYPTION_KDF {
+        return Err(AppError::Validation(format!(
+            "Unsupported bundle encryption {} with {}",
+            encryption.algorithm, encryption.kdf
+        )));
+    }
+    let passphrase = passphrase.ok_or_else(|| {
+        AppError::Validation(
+            "The bundle holds encrypted secrets; a passphrase is required".to_string(),
+        )
+    })?;
+    let salt = STANDARD
+        .decode(&encryption.salt)
+        .map_err(|_| AppError::Validation("The bundle's salt is not valid Base64".to_string()))?;
+    let iterations = encryption.iterations;
+    if !(PassphraseCipher::MIN_ITERATIONS..=PassphraseCipher::MAX_ITERATIONS).contains(&iterations)
+    {
+        return Err(AppError::Validation(format!(
+            "The bundle's key derivation iterations must be between {} and {}",
+            PassphraseCipher::MIN_ITERATIONS,
+            PassphraseCipher::MAX_ITERATIONS
+        )));
+    }
+    let passphrase = passphrase.to_string();
+    let cipher =
+        derive_cipher(move || PassphraseCipher::with_salt(&passphrase, salt, iterations)).await?;
+    let decrypt = |text: &str| {
+        cipher
+            .decrypt(text)
+            .map_err(|e| AppError::Validation(format!("Cannot decrypt the bundle: {}", e)))
+    };
+
+    let mut secrets = Secrets::default();
+    for account in encrypted_accounts {
+        if let Some(secret) = &account.secret {
+            secrets
+                .service_accounts
+                .insert(account.id, decrypt(secret)?);
+        }
+    }
+    for server_key in &bundle.server_keys {
+        secrets
+            .server_keys
+            .insert(server_key.id, decrypt(&server_key.key)?);
+    }
+    Ok(secrets)
+}
+
+/// Matches bundled service accounts with existing ones by `user`, and lists
+/// what keeps the bundle from being imported under the project name `name`.
+///
+/// # Returns
+/// The ids of the existing accounts to reuse, by bundled account id, and the
+/// conflicts.
+fn reconcile(
+    bundle: &ProjectBundle,
+    name: &str,
+    project_exists: bool,
+    existing_accounts: &[ServiceAccount],
+) -> (HashMap<Uuid, Uuid>, Vec<ImportConflict>) {
+    let mut reused = HashMap::new();
+    let mut conflicts = Vec::new();
+    if project_exists {
+        conflicts.push(ImportConflict {
+            kind: BundledKind::Project,
+            name: name.to_string(),
+            reason: "A project with this name exists; import it under another name".to_string(),
+        });
+    }
+    for bundled in &bundle.service_accounts {
+        if let Some(existing) = existing_accounts
+            .iter()
+            .find(|account| account.user == bundled.user)
+        {
+            if let Some(id) = existing.id {
+                reused.insert(bundled.id, id);
+            }
+        } else if let Some(existing) = existing_accounts
+            .iter()
+            .find(|account| account.email == bundled.email)
+        {
+            conflicts.push(ImportConflict {
+                kind: BundledKind::ServiceAccount,
+                name: bundled.user.clone(),
+                reason: format!(
+                    "Email `{}` belongs to service account `{}`",
+                    bundled.email, existing.user
+                ),
+            });
+        }
+    }
+    (reused, conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::server_key::ServerKeyCreatePayload;
+    use crate::test_utils::{Backend, TestStore};
+    use anyhow::Error;
+    use jsonwebtoken::Algorithm;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn bundle() -> ProjectBundle {
+        let (project, production, read, worker) =
+            (Uuid::new(), Uuid::new(), Uuid::new(), Uuid::new());
+        serde_json::from_value(json!({
+            "format": BUNDLE_FORMAT,
+            "version": BUNDLE_VERSION,
+            "exported_at": "2026-01-01T00:00:00Z",
+            "project": {"id": project, "name": "billing", "enabled": true},
+            "environments": [{"id": production, "name": "production", "enabled": true}],
+            "scopes": [{"id": read, "name": "invoices:read", "enabled": true}],
+            "service_accounts": [{
+                "id": worker, "user": "billing-worker", "email": "worker@example.com", "enabled": true
+            }],
+            "access": [{
+                "id": Uuid::new(),
+                "name": "worker-production",
+                "environment_id": production,
+                "service_account_id": worker,
+                "project_scopes": [read],
+                "enabled": true
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn encrypt(bundle: &mut ProjectBundle, passphrase: &str) {
+        let cipher = PassphraseCipher::with_salt(
+            passphrase,
+            b"salt".to_vec(),
+            PassphraseCipher::MIN_ITERATIONS,
+        )
+        .unwrap();
+        bundle.service_accounts[0].secret = Some(cipher.encrypt("s3cr3t").unwrap());
+        bundle.server_keys.push(BundledServerKey {
+            id: Uuid::new(),
+            environment_id: bundle.environments[0].id,
+            algorithm: Algorithm::HS256,
+            key: cipher.encrypt("a2V5").unwrap(),
+            created_at: Utc::now(),
+        });
+        bundle.encryption = Some(BundleEncryption {
+            algorithm: ENCRYPTION_ALGORITHM.to_string(),
+            kdf: ENCRYPTION_KDF.to_string(),
+            salt: STANDARD.encode(b"salt"),
+            iterations: PassphraseCipher::MIN_ITERATIONS,
+        });
+    }
+
+    #[test]
+    fn test_check_bundle() {
+        assert!(check_bundle(&bundle()).is_ok());
+
+        let mut other = bundle();
+        other.version = BUNDLE_VERSION + 1;
+        assert!(matches!(check_bundle(&other), Err(AppError::Validation(_))));
+
+        let mut dangling = bundle();
+        dangling.access[0].project_scopes.push(Uuid::new());
+        assert!(matches!(
+            check_bundle(&dangling),
+            Err(AppError::Validation(_))
+        ));
+
+        let mut duplicated = bundle();
+        let environment = duplicated.environments[0].clone();
+        duplicated.environments.push(BundledRecord {
+            id: Uuid::new(),
+            ..environment
+        });
+        assert!(matches!(
+            check_bundle(&duplicated),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_secrets() {
+        assert!(
+            decrypt_secrets(&bundle(), None)
+                .await
+                .unwrap()
+                .service_accounts
+                .is_empty()
+        );
+
+        let mut encrypted = bundle();
+        encrypt(&mut encrypted, "correct horse");
+        let secrets = decrypt_secrets(&encrypted, Some("correct horse"))
+            .await
+            .unwrap();
+        assert_eq!(
+            secrets.service_accounts[&encrypted.service_accounts[0].id],
+            "s3cr3t"
+        );
+        assert_eq!(secrets.server_keys[&encrypted.server_keys[0].id], "a2V5");
+
+        for passphrase in [None, Some("battery staple")] {
+            assert!(matches!(
+                decrypt_secrets(&encrypted, passphrase).await,
+                Err(AppError::Validation(_))
+            ));
+        }
+
+        // Iteration counts are bounded before any key is derived
+        for iterations in [
+            0,
+            PassphraseCipher::MIN_ITERATIONS - 1,
+            PassphraseCipher::MAX_ITERATIONS + 1,
+            u32::MAX,
+        ] {
+            let mut tampered = encrypted.clone();
+            tampered.encryption.as_mut().unwrap().iterations = iterations;
+            match decrypt_secrets(&tampered, Some("correct horse")).await {
+                Err(AppError::Validation(message)) => assert_eq!(
+                    message,
+                    "The bundle's key derivation iterations must be between 100000 and 10000000"
+                ),
+                other => panic!("expected a validation error, got {:?}", other.is_ok()),
+            }
+        }
+
+        encrypted.encryption = None;
+        assert!(matches!(
+            decrypt_secrets(&encrypted, None).await,
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let bundle = bundle();
+        let (reused, conflicts) = reconcile(&bundle, "billing", false, &[]);
+        assert!(reused.is_empty() && conflicts.is_empty());
+
+        let mut same_user = ServiceAccount::new(
+            "other@example.com".to_string(),
+            "billing-worker".to_string(),
+            "secret".to_string(),
+        );
+        same_user.id = Some(Uuid::new());
+        let (reused, conflicts) = reconcile(&bundle, "billing", false, &[same_user.clone()]);
+        assert_eq!(
+            reused[&bundle.service_accounts[0].id],
+            same_user.id.unwrap()
+        );
+        assert!(conflicts.is_empty());
+
+        let mut same_email = same_user;
+        same_email.user = "someone-else".to_string();
+        same_email.email = "worker@example.com".to_string();
+        let (reused, conflicts) = reconcile(&bundle, "billing", true, &[same_email]);
+        assert!(reused.is_empty());
+        assert_eq!(
+            conflicts
+                .iter()
+                .map(|c| (c.kind, c.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (BundledKind::Project, "billing"),
+                (BundledKind::ServiceAccount, "billing-worker")
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_export_and_import(#[case] backend: Backend) -> Result<(), Error> {
+        let store = TestStore::setup("project_bundle_service", backend).await?;
+        let service = ProjectBundleService::with_repositories(&store.repositories)?;
+
+        let ProjectImportOutcome::Imported(report) = service
+            .import(&bundle(), &ProjectImportQuery::default(), None)
+            .await?
+        else {
+            panic!("expected the bundle to be imported");
+        };
+        assert!(report.imported);
+        assert_eq!(report.ids.len(), 5);
+        assert_eq!(report.generated_secrets.len(), 1);
+        let project_id = report.project_id.unwrap();
+        let environment_id = report.ids[1].to;
+        service
+            .server_key_service
+            .create(ServerKeyCreatePayload {
+                environment_id,
+                algorithm: Algorithm::HS256,
+            })
+            .await?;
+
+        let without_secrets = service.export(project_id, None).await?.unwrap();
+        assert!(without_secrets.encryption.is_none());
+        assert!(without_secrets.server_keys.is_empty());
+        assert!(without_secrets.service_accounts[0].secret.is_none());
+        assert_eq!(without_secrets.access[0].environment_id, environment_id);
+
+        // The same bundle cannot be imported twice under the same name
+        let bundle = service
+            .export(project_id, Some("correct horse"))
+            .await?
+            .unwrap();
+        assert_eq!(bundle.server_keys.len(), 1);
+        let ProjectImportOutcome::Conflicts(conflicts) = service
+            .import(
+                &bundle,
+                &ProjectImportQuery::default(),
+                Some("correct horse"),
+            )
+            .await?
+        else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(conflicts[0].kind, BundledKind::Project);
+
+        let query = ProjectImportQuery {
+            name: Some("billing-copy".to_string()),
+            ..Default::default()
+        };
+        let ProjectImportOutcome::Imported(copy) = service
+            .import(&bundle, &query, Some("correct horse"))
+            .await?
+        else {
+            panic!("expected the bundle to be imported");
+        };
+        assert_eq!(copy.reused_service_accounts, vec!["billing-worker"]);
+        assert!(copy.generated_secrets.is_empty());
+        let copied = service
+            .export(copy.project_id.unwrap(), Some("x"))
+            .await?
+            .unwrap();
+        assert_eq!(copied.project.name, "billing-copy");
+        assert_eq!(copied.server_keys.len(), 1);
+        assert_ne!(copied.environments[0].id, environment_id);
+        assert_eq!(copied.service_accounts[0].id, bundle.service_accounts[0].id);
+
+        assert!(service.export(Uuid::new(), None).await?.is_none());
+        store.cleanup().await?;
+        Ok(())
+    }
+}
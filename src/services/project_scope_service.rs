@@ -1,76 +1,116 @@
-use crate::models::pagination::Pagination;
+use crate::errors::AppError;
+use crate::models::label::validate_labels;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::project_scope::{
     ProjectScope, ProjectScopeFilter, ProjectScopeSortableFields, ProjectScopeUpdatePayload,
 };
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use crate::repositories::project_scope_repository::ProjectScopeRepository;
-use anyhow::Error;
+use crate::repositories::project_scope_repository::DynProjectScopeRepository;
+use crate::repositories::registry::Repositories;
 use mongodb::Database;
 use mongodb::bson::uuid::Uuid;
 use std::sync::Arc;
 
 pub struct ProjectScopeService {
-    project_scope_repository: ProjectScopeRepository,
+    project_scope_repository: Arc<DynProjectScopeRepository>,
 }
 
 impl ProjectScopeService {
-    pub fn new(database: Arc<Database>) -> Result<Self, Error> {
-        let project_scope_repository = ProjectScopeRepository::new(database.as_ref().clone())?;
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
+    }
+
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
         Ok(Self {
-            project_scope_repository,
+            project_scope_repository: repositories.project_scopes.clone(),
         })
     }
 
-    pub async fn create(&self, project_scope: ProjectScope) -> Result<ProjectScope, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectScopeService::create")]
+    pub async fn create(&self, project_scope: ProjectScope) -> Result<ProjectScope, AppError> {
+        validate_labels(&project_scope.labels)?;
         self.project_scope_repository.create(project_scope).await
     }
 
-    pub async fn get_project_scope(&self, id: Uuid) -> Result<Option<ProjectScope>, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectScopeService::get_project_scope")]
+    pub async fn get_project_scope(&self, id: Uuid) -> Result<Option<ProjectScope>, AppError> {
         self.project_scope_repository.read(id).await
     }
 
+    #[tracing::instrument(skip_all, name = "ProjectScopeService::update")]
     pub async fn update(
         &self,
         id: Uuid,
         project_scope: ProjectScopeUpdatePayload,
-    ) -> Result<ProjectScope, Error> {
+    ) -> Result<ProjectScope, AppError> {
+        if let Some(labels) = &project_scope.labels {
+            validate_labels(labels)?;
+        }
         self.project_scope_repository
             .update(id, project_scope)
             .await
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<bool, Error> {
-        self.project_scope_repository.delete(id).await
+    #[tracing::instrument(skip_all, name = "ProjectScopeService::delete")]
+    pub async fn delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        self.project_scope_repository
+            .soft_delete(id, deleted_by)
+            .await
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectScopeService::restore")]
+    pub async fn restore(&self, id: Uuid) -> Result<Option<ProjectScope>, AppError> {
+        self.project_scope_repository.restore(id).await
     }
 
+    #[tracing::instrument(skip_all, name = "ProjectScopeService::find")]
     pub async fn find(
         &self,
         filter: ProjectScopeFilter,
         sort: Option<SortBuilder<ProjectScopeSortableFields>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<ProjectScope>, Error> {
+    ) -> Result<Vec<ProjectScope>, AppError> {
         self.project_scope_repository
             .find(filter, sort, pagination)
             .await
     }
+
+    #[tracing::instrument(skip_all, name = "ProjectScopeService::list")]
+    pub async fn list(
+        &self,
+        filter: ProjectScopeFilter,
+        sort: Option<SortBuilder<ProjectScopeSortableFields>>,
+        pagination: Pagination,
+    ) -> Result<Page<ProjectScope>, AppError> {
+        self.project_scope_repository
+            .find_page(filter, sort, pagination)
+            .await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::test_utils::{Backend, TestStore};
+    use anyhow::Error;
     use chrono::Utc;
+    use rstest::rstest;
 
-    async fn setup() -> (ProjectScopeService, Database) {
-        let db = setup_test_db("project_scope_service").await.unwrap();
-        let service = ProjectScopeService::new(Arc::new(db.clone())).unwrap();
-        (service, db)
+    async fn setup(backend: Backend) -> (ProjectScopeService, TestStore) {
+        let store = TestStore::setup("project_scope_service", backend)
+            .await
+            .unwrap();
+        let service = ProjectScopeService::with_repositories(&store.repositories).unwrap();
+        (service, store)
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_create_project_scope() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_create_project_scope(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project_id = Uuid::new();
         let scope = ProjectScope {
             id: None,
@@ -80,6 +120,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(scope.clone()).await?;
@@ -90,13 +133,17 @@ mod tests {
         assert!(created.created_at.is_some());
         assert!(created.updated_at.is_some());
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_get_project_scope() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_get_project_scope(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let scope = ProjectScope {
             id: Some(Uuid::new()),
             project_id: Uuid::new(),
@@ -105,6 +152,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(scope.clone()).await?;
@@ -118,13 +168,17 @@ mod tests {
         assert_eq!(retrieved.created_at, created.created_at);
         assert_eq!(retrieved.updated_at, created.updated_at);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_update_project_scope() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_update_project_scope(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let scope = ProjectScope {
             id: Some(Uuid::new()),
             project_id: Uuid::new(),
@@ -133,6 +187,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(scope).await?;
@@ -140,6 +197,7 @@ mod tests {
             name: Some("write:users".to_string()),
             description: Some("Allows writing user data".to_string()),
             enabled: Some(false),
+            labels: None,
         };
 
         let updated = service.update(created.id.unwrap(), update).await?;
@@ -148,13 +206,17 @@ mod tests {
         assert!(!updated.enabled);
         assert!(updated.updated_at.unwrap() > created.updated_at.unwrap());
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_delete_project_scope() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_delete_project_scope(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let scope = ProjectScope {
             id: Some(Uuid::new()),
             project_id: Uuid::new(),
@@ -163,22 +225,29 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(scope).await?;
-        let deleted = service.delete(created.id.unwrap()).await?;
+        let deleted = service.delete(created.id.unwrap(), None).await?;
         assert!(deleted);
 
         let read = service.get_project_scope(created.id.unwrap()).await?;
         assert!(read.is_none());
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_project_scopes() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_project_scopes(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project_id = Uuid::new();
         let scope1 = ProjectScope {
             id: Some(Uuid::new()),
@@ -188,6 +257,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let scope2 = ProjectScope {
             id: Some(Uuid::new()),
@@ -197,6 +269,9 @@ mod tests {
             enabled: false,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         service.create(scope1).await?;
@@ -206,6 +281,7 @@ mod tests {
             project_id: Some(project_id),
             name: Some("read:users".to_string()),
             is_enabled: Some(true),
+            ..Default::default()
         };
 
         let found = service.find(filter, None, None).await?;
@@ -213,13 +289,19 @@ mod tests {
         assert_eq!(found[0].name, "read:users");
         assert!(found[0].enabled);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_project_scopes_with_pagination() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_project_scopes_with_pagination(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project_id = Uuid::new();
 
         // Create 5 test scopes
@@ -232,6 +314,9 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
             service.create(scope).await?;
         }
@@ -240,6 +325,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(1),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -247,6 +333,7 @@ mod tests {
                     project_id: Some(project_id),
                     name: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -258,6 +345,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(2),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -265,6 +353,7 @@ mod tests {
                     project_id: Some(project_id),
                     name: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -276,6 +365,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(3),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -283,6 +373,7 @@ mod tests {
                     project_id: Some(project_id),
                     name: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -290,7 +381,7 @@ mod tests {
             .await?;
         assert_eq!(found.len(), 1);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 }
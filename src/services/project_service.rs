@@ -1,65 +1,337 @@
-use crate::models::pagination::Pagination;
-use crate::models::project::{Project, ProjectFilter, ProjectSortableFields, ProjectUpdatePayload};
+use crate::errors::AppError;
+use crate::models::environment::Environment;
+use crate::models::label::validate_labels;
+use crate::models::pagination::{Page, Pagination};
+use crate::models::project::{
+    Project, ProjectDeleteOutcome, ProjectDependents, ProjectFilter, ProjectSortableFields,
+    ProjectUpdatePayload,
+};
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use crate::repositories::project_repository::ProjectRepository;
-use anyhow::Error;
+use crate::repositories::access_token_repository::DynAccessTokenRepository;
+use crate::repositories::base::{IdField, IdFilter};
+use crate::repositories::environment_repository::DynEnvironmentRepository;
+use crate::repositories::project_access_repository::DynProjectAccessRepository;
+use crate::repositories::project_repository::DynProjectRepository;
+use crate::repositories::project_scope_repository::DynProjectScopeRepository;
+use crate::repositories::registry::Repositories;
+use crate::repositories::server_key_repository::DynServerKeyRepository;
+use crate::repositories::unit_of_work::{Transaction, UnitOfWork};
+use chrono::Utc;
 use mongodb::Database;
+use mongodb::bson::doc;
 use mongodb::bson::uuid::Uuid;
 use std::sync::Arc;
 
 pub struct ProjectService {
-    project_repository: ProjectRepository,
+    unit_of_work: UnitOfWork,
+    project_repository: Arc<DynProjectRepository>,
+    environment_repository: Arc<DynEnvironmentRepository>,
+    project_scope_repository: Arc<DynProjectScopeRepository>,
+    project_access_repository: Arc<DynProjectAccessRepository>,
+    server_key_repository: Arc<DynServerKeyRepository>,
+    access_token_repository: Arc<DynAccessTokenRepository>,
 }
 
 impl ProjectService {
-    pub fn new(database: Arc<Database>) -> Result<Self, Error> {
-        let project_repository = ProjectRepository::new(database.as_ref().clone())?;
-        Ok(Self { project_repository })
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
     }
 
-    pub async fn create(&self, project: Project) -> Result<Project, Error> {
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
+        Ok(Self {
+            unit_of_work: UnitOfWork::new(repositories),
+            project_repository: repositories.projects.clone(),
+            environment_repository: repositories.environments.clone(),
+            project_scope_repository: repositories.project_scopes.clone(),
+            project_access_repository: repositories.project_access.clone(),
+            server_key_repository: repositories.server_keys.clone(),
+            access_token_repository: repositories.access_tokens.clone(),
+        })
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectService::create")]
+    pub async fn create(&self, project: Project) -> Result<Project, AppError> {
+        validate_labels(&project.labels)?;
         self.project_repository.create(project).await
     }
 
-    pub async fn get_project(&self, id: Uuid) -> Result<Option<Project>, Error> {
+    /// Creates a project together with its environments, which commit or roll
+    /// back together.
+    ///
+    /// The `project_id` of each environment is set to the new project's id.
+    ///
+    /// # Returns
+    ///
+    /// The created project and environments, in the order given.
+    #[tracing::instrument(skip_all, name = "ProjectService::create_with_environments")]
+    pub async fn create_with_environments(
+        &self,
+        project: Project,
+        environments: Vec<Environment>,
+    ) -> Result<(Project, Vec<Environment>), AppError> {
+        validate_labels(&project.labels)?;
+        for environment in &environments {
+            validate_labels(&environment.labels)?;
+        }
+
+        self.unit_of_work
+            .run(async |mut transaction| {
+                let project = self
+                    .project_repository
+                    .create_in(project.clone(), transaction.as_deref_mut())
+                    .await?;
+                let project_id = project
+                    .id
+                    .ok_or_else(|| anyhow::anyhow!("Created record has no id"))?;
+                let mut created = Vec::with_capacity(environments.len());
+                for environment in &environments {
+                    let environment = Environment {
+                        project_id,
+                        ..environment.clone()
+                    };
+                    created.push(
+                        self.environment_repository
+                            .create_in(environment, transaction.as_deref_mut())
+                            .await?,
+                    );
+                }
+                Ok((project, created))
+            })
+            .await
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectService::get_project")]
+    pub async fn get_project(&self, id: Uuid) -> Result<Option<Project>, AppError> {
         self.project_repository.read(id).await
     }
 
-    pub async fn update(&self, id: Uuid, project: ProjectUpdatePayload) -> Result<Project, Error> {
+    #[tracing::instrument(skip_all, name = "ProjectService::update")]
+    pub async fn update(
+        &self,
+        id: Uuid,
+        project: ProjectUpdatePayload,
+    ) -> Result<Project, AppError> {
+        if let Some(labels) = &project.labels {
+            validate_labels(labels)?;
+        }
         self.project_repository.update(id, project).await
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<bool, Error> {
-        self.project_repository.delete(id).await
+    /// Soft-deletes a project, optionally together with everything that depends on it.
+    ///
+    /// Without `cascade` the delete is refused while environments, scopes, accesses,
+    /// server keys or access tokens still reference the project, and the blocking
+    /// dependents are returned. With `cascade` the whole subtree is marked deleted in one
+    /// [unit of work](UnitOfWork), which revokes every access token issued under it.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the project to delete
+    /// * `cascade` - Whether dependent resources should be deleted as well
+    /// * `deleted_by` - Who requested the delete, recorded on every deleted record
+    #[tracing::instrument(skip_all, name = "ProjectService::delete")]
+    pub async fn delete(
+        &self,
+        id: Uuid,
+        cascade: bool,
+        deleted_by: Option<String>,
+    ) -> Result<ProjectDeleteOutcome, AppError> {
+        if !cascade {
+            if self.project_repository.read(id).await?.is_none() {
+                return Ok(ProjectDeleteOutcome::NotFound);
+            }
+            let dependents = self.dependents(id, None).await?;
+            if !dependents.is_empty() {
+                return Ok(ProjectDeleteOutcome::Blocked(dependents));
+            }
+            return match self.project_repository.soft_delete(id, deleted_by).await? {
+                true => Ok(ProjectDeleteOutcome::Deleted(dependents)),
+                false => Ok(ProjectDeleteOutcome::NotFound),
+            };
+        }
+
+        self.unit_of_work
+            .run(async |transaction| self.delete_tree(id, deleted_by.clone(), transaction).await)
+            .await
+    }
+
+    /// Restores a soft-deleted project. Dependents deleted with it are not restored.
+    #[tracing::instrument(skip_all, name = "ProjectService::restore")]
+    pub async fn restore(&self, id: Uuid) -> Result<Option<Project>, AppError> {
+        self.project_repository.restore(id).await
+    }
+
+    /// Lists the live resources that reference a project, directly or transitively.
+    #[tracing::instrument(skip_all, name = "ProjectService::dependents")]
+    pub async fn dependents(
+        &self,
+        id: Uuid,
+        mut transaction: Option<&mut Transaction>,
+    ) -> Result<ProjectDependents, AppError> {
+        let environments = self
+            .environment_repository
+            .distinct_ids(
+                IdFilter::by(IdField::ProjectId, [id]),
+                transaction.as_deref_mut(),
+            )
+            .await?;
+        let project_scopes = self
+            .project_scope_repository
+            .distinct_ids(
+                IdFilter::by(IdField::ProjectId, [id]),
+                transaction.as_deref_mut(),
+            )
+            .await?;
+        let project_accesses = self
+            .project_access_repository
+            .distinct_ids(
+                IdFilter::by(IdField::EnvironmentId, environments.clone()),
+                transaction.as_deref_mut(),
+            )
+            .await?;
+        let server_keys = self
+            .server_key_repository
+            .distinct_ids(
+                IdFilter::by(IdField::EnvironmentId, environments.clone()),
+                transaction.as_deref_mut(),
+            )
+            .await?;
+        let access_tokens = self
+            .access_token_repository
+            .distinct_ids(
+                IdFilter::by(IdField::ProjectAccessId, project_accesses.clone()),
+                transaction,
+            )
+            .await?;
+
+        Ok(ProjectDependents {
+            environments,
+            project_scopes,
+            project_accesses,
+            server_keys,
+            access_tokens,
+        })
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectService::delete_tree")]
+    async fn delete_tree(
+        &self,
+        id: Uuid,
+        deleted_by: Option<String>,
+        mut transaction: Option<&mut Transaction>,
+    ) -> Result<ProjectDeleteOutcome, AppError> {
+        let project = self
+            .project_repository
+            .distinct_ids(IdFilter::ids([id]), transaction.as_deref_mut())
+            .await?;
+        if project.is_empty() {
+            return Ok(ProjectDeleteOutcome::NotFound);
+        }
+
+        let dependents = self.dependents(id, transaction.as_deref_mut()).await?;
+        let deleted_at = Utc::now();
+
+        self.access_token_repository
+            .soft_delete_many(
+                IdFilter::ids(dependents.access_tokens.clone()),
+                deleted_at,
+                deleted_by.clone(),
+                transaction.as_deref_mut(),
+            )
+            .await?;
+        self.server_key_repository
+            .soft_delete_many(
+                IdFilter::ids(dependents.server_keys.clone()),
+                deleted_at,
+                deleted_by.clone(),
+                transaction.as_deref_mut(),
+            )
+            .await?;
+        self.project_access_repository
+            .soft_delete_many(
+                IdFilter::ids(dependents.project_accesses.clone()),
+                deleted_at,
+                deleted_by.clone(),
+                transaction.as_deref_mut(),
+            )
+            .await?;
+        self.project_scope_repository
+            .soft_delete_many(
+                IdFilter::ids(dependents.project_scopes.clone()),
+                deleted_at,
+                deleted_by.clone(),
+                transaction.as_deref_mut(),
+            )
+            .await?;
+        self.environment_repository
+            .soft_delete_many(
+                IdFilter::ids(dependents.environments.clone()),
+                deleted_at,
+                deleted_by.clone(),
+                transaction.as_deref_mut(),
+            )
+            .await?;
+        self.project_repository
+            .soft_delete_many(IdFilter::ids([id]), deleted_at, deleted_by, transaction)
+            .await?;
+
+        Ok(ProjectDeleteOutcome::Deleted(dependents))
     }
 
+    #[tracing::instrument(skip_all, name = "ProjectService::find")]
     pub async fn find(
         &self,
         filter: ProjectFilter,
         sort: Option<SortBuilder<ProjectSortableFields>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<Project>, Error> {
+    ) -> Result<Vec<Project>, AppError> {
         self.project_repository.find(filter, sort, pagination).await
     }
+
+    #[tracing::instrument(skip_all, name = "ProjectService::list")]
+    pub async fn list(
+        &self,
+        filter: ProjectFilter,
+        sort: Option<SortBuilder<ProjectSortableFields>>,
+        pagination: Pagination,
+    ) -> Result<Page<Project>, AppError> {
+        self.project_repository
+            .find_page(filter, sort, pagination)
+            .await
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::Utc;
+    use chrono::{Duration, Utc};
 
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::models::access_token::AccessToken;
+    use crate::models::environment::Environment;
+    use crate::models::label::Labels;
+    use crate::models::project_access::ProjectAccess;
+    use crate::models::project_scope::ProjectScope;
+    use crate::models::server_key::ServerKey;
+    use crate::repositories::environment_repository::EnvironmentRepository;
+    use crate::repositories::project_repository::ProjectRepository;
+    use crate::test_utils::{Backend, TestStore};
+    use anyhow::Error;
+    use jsonwebtoken::Algorithm;
+    use rstest::rstest;
 
-    async fn setup() -> (ProjectService, Database) {
-        let db = setup_test_db("project_service").await.unwrap();
-        let service = ProjectService::new(Arc::new(db.clone())).unwrap();
-        (service, db)
+    async fn setup(backend: Backend) -> (ProjectService, TestStore) {
+        let store = TestStore::setup("project_service", backend).await.unwrap();
+        let service = ProjectService::with_repositories(&store.repositories).unwrap();
+        (service, store)
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_create_project() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_create_project(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project = Project {
             id: None,
             name: "Test Project".to_string(),
@@ -67,6 +339,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
 
         let created = service.create(project).await?;
@@ -75,13 +350,128 @@ mod tests {
         assert_eq!(created.description, "Test Description");
         assert!(created.enabled);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_get_project() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_create_project_with_invalid_labels(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
+        let project = Project {
+            id: None,
+            name: "Test Project".to_string(),
+            description: "Test Description".to_string(),
+            enabled: true,
+            created_at: None,
+            updated_at: None,
+            deleted_at: None,
+            deleted_by: None,
+            labels: Labels::from([("cost.center".to_string(), "42".to_string())]),
+        };
+
+        let result = service.create(project).await;
+        assert!(matches!(result, Err(AppError::Validation(_))));
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    fn environment(name: &str) -> Environment {
+        Environment {
+            id: None,
+            project_id: Uuid::new(),
+            name: name.to_string(),
+            description: String::new(),
+            enabled: true,
+            created_at: None,
+            updated_at: None,
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
+        }
+    }
+
+    fn project(name: &str) -> Project {
+        Project {
+            id: None,
+            name: name.to_string(),
+            description: "Test Description".to_string(),
+            enabled: true,
+            created_at: None,
+            updated_at: None,
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
+        }
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_create_project_with_environments(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
+
+        let (project, environments) = service
+            .create_with_environments(
+                project("Test Project"),
+                vec![environment("dev"), environment("prod")],
+            )
+            .await?;
+        let project_id = project.id.unwrap();
+        assert_eq!(environments.len(), 2);
+        assert_eq!(environments[0].name, "dev");
+        assert_eq!(environments[1].name, "prod");
+        assert!(environments.iter().all(|e| e.project_id == project_id));
+
+        let dependents = service.dependents(project_id, None).await?;
+        assert_eq!(dependents.environments.len(), 2);
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    // Relies on the unique environment index and a MongoDB transaction
+    #[tokio::test]
+    async fn test_create_project_with_environments_rolls_back() -> Result<(), Error> {
+        let (service, store) = setup(Backend::Mongo).await;
+        let database = store.repositories.database.clone().unwrap();
+        EnvironmentRepository::new(database.as_ref().clone())?
+            .ensure_indexes()
+            .await?;
+
+        let result = service
+            .create_with_environments(
+                project("Test Project"),
+                vec![environment("dev"), environment("dev")],
+            )
+            .await;
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+
+        let projects = ProjectRepository::new(database.as_ref().clone())?
+            .collection()?
+            .count_documents(doc! {})
+            .await?;
+        assert_eq!(projects, 0);
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_get_project(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project = Project {
             id: None,
             name: "Test Project".to_string(),
@@ -89,6 +479,9 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let created = service.create(project).await?;
         let retrieved = service.get_project(created.id.unwrap()).await?.unwrap();
@@ -97,13 +490,17 @@ mod tests {
         assert_eq!(retrieved.description, "Test Description");
         assert!(retrieved.enabled);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_update_project() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_update_project(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project = Project {
             id: None,
             name: "Test Project".to_string(),
@@ -111,12 +508,16 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let created = service.create(project).await?;
         let update = ProjectUpdatePayload {
             name: Some("Updated Project".to_string()),
             description: Some("Updated Description".to_string()),
             enabled: Some(false),
+            labels: None,
         };
 
         let updated = service.update(created.id.unwrap(), update).await?;
@@ -124,13 +525,17 @@ mod tests {
         assert_eq!(updated.description, "Updated Description");
         assert!(!updated.enabled);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_delete_project() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_delete_project(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project = Project {
             id: None,
             name: "Test Project".to_string(),
@@ -138,21 +543,283 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         let created = service.create(project).await?;
-        let deleted = service.delete(created.id.unwrap()).await?;
-        assert!(deleted);
+        let deleted = service.delete(created.id.unwrap(), false, None).await?;
+        assert_eq!(
+            deleted,
+            ProjectDeleteOutcome::Deleted(ProjectDependents::default())
+        );
 
         let read = service.get_project(created.id.unwrap()).await?;
         assert!(read.is_none());
 
-        cleanup_test_db(db).await?;
+        let missing = service.delete(Uuid::new(), false, None).await?;
+        assert_eq!(missing, ProjectDeleteOutcome::NotFound);
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    // Reads the soft-deleted document through its MongoDB collection
+    #[tokio::test]
+    async fn test_restore_project() -> Result<(), Error> {
+        let (service, store) = setup(Backend::Mongo).await;
+        let project = Project {
+            id: None,
+            name: "Test Project".to_string(),
+            description: "Test Description".to_string(),
+            enabled: true,
+            created_at: Some(Utc::now()),
+            updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
+        };
+        let created = service.create(project).await?;
+        let id = created.id.unwrap();
+
+        service.delete(id, false, Some("alice".to_string())).await?;
+        let database = store.repositories.database.clone().unwrap();
+        let deleted = ProjectRepository::new(database.as_ref().clone())?
+            .collection()?
+            .find_one(doc! { "_id": id })
+            .await?
+            .unwrap();
+        assert!(deleted.deleted_at.is_some());
+        assert_eq!(deleted.deleted_by.as_deref(), Some("alice"));
+
+        let restored = service.restore(id).await?.unwrap();
+        assert_eq!(restored.name, "Test Project");
+        assert!(service.get_project(id).await?.is_some());
+        assert!(service.restore(id).await?.is_none());
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    async fn create_project_tree(
+        service: &ProjectService,
+        repositories: &Repositories,
+    ) -> Result<(Project, ProjectDependents), Error> {
+        let project = service
+            .create(Project {
+                id: None,
+                name: "Tree Project".to_string(),
+                description: "Project with dependents".to_string(),
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await?;
+        let project_id = project.id.unwrap();
+
+        let environment = repositories
+            .environments
+            .create(Environment {
+                id: None,
+                project_id,
+                name: "production".to_string(),
+                description: "Production".to_string(),
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await?;
+        let environment_id = environment.id.unwrap();
+
+        let scope = repositories
+            .project_scopes
+            .create(ProjectScope {
+                id: None,
+                project_id,
+                name: "read".to_string(),
+                description: "Read access".to_string(),
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await?;
+
+        let access = repositories
+            .project_access
+            .create(ProjectAccess {
+                id: None,
+                name: "reader".to_string(),
+                environment_id,
+                service_account_id: Some(Uuid::new()),
+                project_scopes: vec![scope.id.unwrap()],
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await?;
+
+        let server_key = repositories
+            .server_keys
+            .create(ServerKey {
+                id: None,
+                key: "encrypted-key".to_string(),
+                environment_id,
+                algorithm: Algorithm::HS256,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                deleted_at: None,
+                deleted_by: None,
+            })
+            .await?;
+
+        let token = repositories
+            .access_tokens
+            .create(AccessToken {
+                id: None,
+                project_access_id: access.id.unwrap(),
+                key: "token-key".to_string(),
+                algorithm: Algorithm::HS256,
+                expires_at: Utc::now() + Duration::hours(1),
+                created_at: Utc::now(),
+                enabled: true,
+                deleted_at: None,
+                deleted_by: None,
+            })
+            .await?;
+
+        let dependents = ProjectDependents {
+            environments: vec![environment_id],
+            project_scopes: vec![scope.id.unwrap()],
+            project_accesses: vec![access.id.unwrap()],
+            server_keys: vec![server_key.id.unwrap()],
+            access_tokens: vec![token.id.unwrap()],
+        };
+        Ok((project, dependents))
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_delete_project_blocked_by_dependents(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
+        let (project, dependents) = create_project_tree(&service, &store.repositories).await?;
+        let project_id = project.id.unwrap();
+
+        let outcome = service.delete(project_id, false, None).await?;
+        assert_eq!(outcome, ProjectDeleteOutcome::Blocked(dependents));
+
+        // Nothing was removed
+        assert!(service.get_project(project_id).await?.is_some());
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_delete_project_cascade(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
+        let (project, dependents) = create_project_tree(&service, &store.repositories).await?;
+        let project_id = project.id.unwrap();
+
+        let outcome = service.delete(project_id, true, None).await?;
+        assert_eq!(outcome, ProjectDeleteOutcome::Deleted(dependents.clone()));
+
+        assert!(service.get_project(project_id).await?.is_none());
+        assert!(service.dependents(project_id, None).await?.is_empty());
+        let token = store
+            .repositories
+            .access_tokens
+            .read(dependents.access_tokens[0])
+            .await?;
+        assert!(token.is_none());
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_project_cascade_in_memory() -> Result<(), Error> {
+        let repositories = Repositories::in_memory();
+        let service = ProjectService::with_repositories(&repositories)?;
+        let project = service
+            .create(Project {
+                id: None,
+                name: "Memory Project".to_string(),
+                description: "Project held in memory".to_string(),
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await?;
+        let project_id = project.id.unwrap();
+        let environment = repositories
+            .environments
+            .create(Environment {
+                id: None,
+                project_id,
+                name: "production".to_string(),
+                description: "Production".to_string(),
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await?;
+        let environment_id = environment.id.unwrap();
+
+        let outcome = service.delete(project_id, false, None).await?;
+        assert!(matches!(outcome, ProjectDeleteOutcome::Blocked(_)));
+
+        let outcome = service
+            .delete(project_id, true, Some("alice".to_string()))
+            .await?;
+        assert_eq!(
+            outcome,
+            ProjectDeleteOutcome::Deleted(ProjectDependents {
+                environments: vec![environment_id],
+                ..Default::default()
+            })
+        );
+        assert!(service.get_project(project_id).await?.is_none());
+        assert!(repositories.environments.is_deleted(environment_id).await?);
+
+        let restored = service.restore(project_id).await?.unwrap();
+        assert_eq!(restored.deleted_by, None);
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_projects_with_name_filter() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_projects_with_name_filter(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project1 = Project {
             id: None,
             name: "Project 1".to_string(),
@@ -160,25 +827,35 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         service.create(project1).await?;
 
         let filter = ProjectFilter {
             name: Some("Project 1".to_string()),
             is_enabled: None,
+            ..Default::default()
         };
 
         let found = service.find(filter, None, None).await?;
         assert_eq!(found.len(), 1);
         assert_eq!(found[0].name, "Project 1");
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_projects_with_no_matching_filter() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_projects_with_no_matching_filter(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let project1 = Project {
             id: None,
             name: "Project 1".to_string(),
@@ -186,24 +863,32 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
         };
         service.create(project1).await?;
 
         let filter = ProjectFilter {
             name: Some("Non-existent Project".to_string()),
             is_enabled: None,
+            ..Default::default()
         };
 
         let found = service.find(filter, None, None).await?;
         assert_eq!(found.len(), 0);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_projects_with_pagination() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_projects_with_pagination(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
 
         // Create 5 test projects
         for i in 1..=5 {
@@ -214,6 +899,9 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
             service.create(project).await?;
         }
@@ -222,12 +910,14 @@ mod tests {
         let pagination = Pagination {
             page: Some(1),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
                 ProjectFilter {
                     name: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -241,12 +931,14 @@ mod tests {
         let pagination = Pagination {
             page: Some(2),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
                 ProjectFilter {
                     name: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -260,12 +952,14 @@ mod tests {
         let pagination = Pagination {
             page: Some(3),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
                 ProjectFilter {
                     name: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -274,13 +968,19 @@ mod tests {
         assert_eq!(found.len(), 1);
         assert_eq!(found[0].name, "Project 5");
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_projects_with_filter_and_pagination() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_projects_with_filter_and_pagination(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
 
         // Create 5 enabled and 5 disabled projects
         for i in 1..=10 {
@@ -291,6 +991,9 @@ mod tests {
                 enabled: i <= 5, // First 5 enabled, last 5 disabled
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
             };
             service.create(project).await?;
         }
@@ -299,10 +1002,12 @@ mod tests {
         let filter = ProjectFilter {
             name: None,
             is_enabled: Some(true),
+            ..Default::default()
         };
         let pagination = Pagination {
             page: Some(1),
             limit: Some(3),
+            cursor: None,
         };
 
         let found = service.find(filter, None, Some(pagination)).await?;
@@ -313,17 +1018,19 @@ mod tests {
         let filter = ProjectFilter {
             name: None,
             is_enabled: Some(true),
+            ..Default::default()
         };
         let pagination = Pagination {
             page: Some(2),
             limit: Some(3),
+            cursor: None,
         };
 
         let found = service.find(filter, None, Some(pagination)).await?;
         assert_eq!(found.len(), 2); // Only 2 remaining enabled projects
         assert!(found.iter().all(|p| p.enabled));
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 }
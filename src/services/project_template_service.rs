@@ -0,0 +1,429 @@
+use crate::errors::AppError;
+use crate::models::environment::Environment;
+use crate::models::label::validate_labels;
+use crate::models::pagination::{Page, Pagination};
+use crate::models::project::Project;
+use crate::models::project_scope::ProjectScope;
+use crate::models::project_template::{
+    ProjectScaffold, ProjectTemplate, ProjectTemplateFilter, ProjectTemplateSortableFields,
+    ProjectTemplateUpdatePayload, STANDARD_TEMPLATE, TemplateEntry,
+};
+use crate::models::server_key::ServerKeyCreatePayload;
+use crate::models::sort::SortBuilder;
+use crate::repositories::environment_repository::DynEnvironmentRepository;
+use crate::repositories::project_repository::DynProjectRepository;
+use crate::repositories::project_scope_repository::DynProjectScopeRepository;
+use crate::repositories::project_template_repository::DynProjectTemplateRepository;
+use crate::repositories::registry::Repositories;
+use crate::repositories::unit_of_work::UnitOfWork;
+use crate::services::server_key_service::ServerKeyService;
+use mongodb::Database;
+use mongodb::bson::uuid::Uuid;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+pub struct ProjectTemplateService {
+    unit_of_work: UnitOfWork,
+    project_template_repository: Arc<DynProjectTemplateRepository>,
+    project_repository: Arc<DynProjectRepository>,
+    environment_repository: Arc<DynEnvironmentRepository>,
+    project_scope_repository: Arc<DynProjectScopeRepository>,
+    server_key_service: ServerKeyService,
+}
+
+impl ProjectTemplateService {
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
+    }
+
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
+        Ok(Self {
+            unit_of_work: UnitOfWork::new(repositories),
+            project_template_repository: repositories.project_templates.clone(),
+            project_repository: repositories.projects.clone(),
+            environment_repository: repositories.environments.clone(),
+            project_scope_repository: repositories.project_scopes.clone(),
+            server_key_service: ServerKeyService::with_repositories(repositories)?,
+        })
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateService::create")]
+    pub async fn create(&self, template: ProjectTemplate) -> Result<ProjectTemplate, AppError> {
+        validate_labels(&template.labels)?;
+        validate_entries("environment", &template.environments)?;
+        validate_entries("project scope", &template.project_scopes)?;
+        self.project_template_repository.create(template).await
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateService::get_project_template")]
+    pub async fn get_project_template(
+        &self,
+        id: Uuid,
+    ) -> Result<Option<ProjectTemplate>, AppError> {
+        self.project_template_repository.read(id).await
+    }
+
+    /// Finds the template a project is scaffolded from by its name.
+    ///
+    /// A stored template takes precedence, so storing one named
+    /// [`STANDARD_TEMPLATE`] replaces the built-in
+    /// [standard template](ProjectTemplate::standard).
+    #[tracing::instrument(skip_all, name = "ProjectTemplateService::find_by_name")]
+    pub async fn find_by_name(&self, name: &str) -> Result<Option<ProjectTemplate>, AppError> {
+        let filter = ProjectTemplateFilter {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+        let stored = self
+            .project_template_repository
+            .find(filter, None, None)
+            .await?;
+        match stored.into_iter().next() {
+            Some(template) => Ok(Some(template)),
+            None if name == STANDARD_TEMPLATE => Ok(Some(ProjectTemplate::standard())),
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateService::update")]
+    pub async fn update(
+        &self,
+        id: Uuid,
+        template: ProjectTemplateUpdatePayload,
+    ) -> Result<ProjectTemplate, AppError> {
+        if let Some(labels) = &template.labels {
+            validate_labels(labels)?;
+        }
+        if let Some(environments) = &template.environments {
+            validate_entries("environment", environments)?;
+        }
+        if let Some(project_scopes) = &template.project_scopes {
+            validate_entries("project scope", project_scopes)?;
+        }
+        self.project_template_repository.update(id, template).await
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateService::delete")]
+    pub async fn delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        self.project_template_repository
+            .soft_delete(id, deleted_by)
+            .await
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateService::restore")]
+    pub async fn restore(&self, id: Uuid) -> Result<Option<ProjectTemplate>, AppError> {
+        self.project_template_repository.restore(id).await
+    }
+
+    #[tracing::instrument(skip_all, name = "ProjectTemplateService::list")]
+    pub async fn list(
+        &self,
+        filter: ProjectTemplateFilter,
+        sort: Option<SortBuilder<ProjectTemplateSortableFields>>,
+        pagination: Pagination,
+    ) -> Result<Page<ProjectTemplate>, AppError> {
+        self.project_template_repository
+            .find_page(filter, sort, pagination)
+            .await
+    }
+
+    /// Creates a project with the environments and scopes of the template
+    /// named `template`, and a server key for each environment, in one
+    /// [unit of work](UnitOfWork).
+    ///
+    /// # Returns
+    ///
+    /// The created records, or `None` if there is no template with this name.
+    #[tracing::instrument(skip_all, name = "ProjectTemplateService::create_project")]
+    pub async fn create_project(
+        &self,
+        project: Project,
+        template: &str,
+    ) -> Result<Option<ProjectScaffold>, AppError> {
+        validate_labels(&project.labels)?;
+        let Some(template) = self.find_by_name(template).await? else {
+            return Ok(None);
+        };
+
+        let scaffold = self
+            .unit_of_work
+            .run(async |mut transaction| {
+                let project = self
+                    .project_repository
+                    .create_in(project.clone(), transaction.as_deref_mut())
+                    .await?;
+                let project_id = project
+                    .id
+                    .ok_or_else(|| anyhow::anyhow!("Created record has no id"))?;
+
+                let mut environments = Vec::with_capacity(template.environments.len());
+                let mut server_keys = Vec::with_capacity(template.environments.len());
+                for entry in &template.environments {
+                    let environment = self
+                        .environment_repository
+                        .create_in(
+                            Environment {
+                                id: None,
+                                project_id,
+                                name: entry.name.clone(),
+                                description: entry.description.clone(),
+                                enabled: true,
+                                created_at: None,
+                                updated_at: None,
+                                deleted_at: None,
+                                deleted_by: None,
+                                labels: Default::default(),
+                            },
+                            transaction.as_deref_mut(),
+                        )
+                        .await?;
+                    let payload = ServerKeyCreatePayload {
+                        environment_id: environment
+                            .id
+                            .ok_or_else(|| anyhow::anyhow!("Created record has no id"))?,
+                        algorithm: template.server_key_algorithm,
+                    };
+                    server_keys.push(
+                        self.server_key_service
+                            .create_in(payload, transaction.as_deref_mut())
+                            .await?,
+                    );
+                    environments.push(environment);
+                }
+
+                let mut project_scopes = Vec::with_capacity(template.project_scopes.len());
+                for entry in &template.project_scopes {
+                    project_scopes.push(
+                        self.project_scope_repository
+                            .create_in(
+                                ProjectScope {
+                                    id: None,
+                                    project_id,
+                                    name: entry.name.clone(),
+                                    description: entry.description.clone(),
+                                    enabled: true,
+                                    created_at: None,
+                                    updated_at: None,
+                                    deleted_at: None,
+                                    deleted_by: None,
+                                    labels: Default::default(),
+                                },
+                                transaction.as_deref_mut(),
+                            )
+                            .await?,
+                    );
+                }
+
+                Ok(ProjectScaffold {
+                    project,
+                    environments,
+                    project_scopes,
+                    server_keys,
+                })
+            })
+            .await?;
+        Ok(Some(scaffold))
+    }
+}
+
+/// Checks that every entry of a template has a name, used only once.
+///
+/// A project cannot hold two environments or scopes of the same name, so a
+/// template repeating one could never be applied.
+fn validate_entries(kind: &str, entries: &[TemplateEntry]) -> Result<(), AppError> {
+    let mut names = HashSet::new();
+    for entry in entries {
+        if entry.name.trim().is_empty() {
+            return Err(AppError::Validation(format!("Every {} needs a name", kind)));
+        }
+        if !names.insert(entry.name.as_str()) {
+            return Err(AppError::Validation(format!(
+                "Duplicate {} name \"{}\"",
+                kind, entry.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{Backend, TestStore};
+    use anyhow::Error;
+    use jsonwebtoken::Algorithm;
+    use rstest::rstest;
+
+    async fn setup(backend: Backend) -> (ProjectTemplateService, TestStore) {
+        let store = TestStore::setup("project_template_service", backend)
+            .await
+            .unwrap();
+        let service = ProjectTemplateService::with_repositories(&store.repositories).unwrap();
+        (service, store)
+    }
+
+    fn project(name: &str) -> Project {
+        Project {
+            id: None,
+            name: name.to_string(),
+            description: "Test Description".to_string(),
+            enabled: true,
+            created_at: None,
+            updated_at: None,
+            deleted_at: None,
+            deleted_by: None,
+            labels: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_validate_entries() {
+        let entry = |name: &str| TemplateEntry {
+            name: name.to_string(),
+            description: String::new(),
+        };
+        assert!(validate_entries("environment", &[entry("dev"), entry("prod")]).is_ok());
+        assert!(matches!(
+            validate_entries("environment", &[entry("dev"), entry("dev")]),
+            Err(AppError::Validation(_))
+        ));
+        assert!(matches!(
+            validate_entries("project scope", &[entry(" ")]),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_create_project_from_standard_template(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
+
+        let scaffold = service
+            .create_project(project("Billing"), STANDARD_TEMPLATE)
+            .await?
+            .unwrap();
+        let project_id = scaffold.project.id.unwrap();
+
+        let environments: Vec<&str> = scaffold
+            .environments
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(environments, vec!["dev", "staging", "prod"]);
+        assert!(
+            scaffold
+                .environments
+                .iter()
+                .all(|e| e.project_id == project_id)
+        );
+
+        let scopes: Vec<&str> = scaffold
+            .project_scopes
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(scopes, vec!["read", "write", "admin"]);
+
+        assert_eq!(scaffold.server_keys.len(), 3);
+        for (environment, server_key) in scaffold.environments.iter().zip(&scaffold.server_keys) {
+            assert_eq!(Some(server_key.environment_id), environment.id);
+            assert_eq!(server_key.algorithm, Algorithm::HS256);
+        }
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_stored_template_replaces_standard(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
+
+        let mut template = ProjectTemplate::standard();
+        template.environments.truncate(1);
+        template.project_scopes.clear();
+        template.server_key_algorithm = Algorithm::HS512;
+        service.create(template).await?;
+
+        let scaffold = service
+            .create_project(project("Billing"), STANDARD_TEMPLATE)
+            .await?
+            .unwrap();
+        assert_eq!(scaffold.environments.len(), 1);
+        assert!(scaffold.project_scopes.is_empty());
+        assert_eq!(scaffold.server_keys[0].algorithm, Algorithm::HS512);
+
+        // No template by that name
+        let missing = service.create_project(project("Other"), "unknown").await?;
+        assert!(missing.is_none());
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_create_project_template_with_duplicate_names(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
+
+        let mut template = ProjectTemplate::standard();
+        template.name = "duplicated".to_string();
+        template.environments.push(template.environments[0].clone());
+        let result = service.create(template).await;
+        assert!(matches!(result, Err(AppError::Validation(_))));
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    // Relies on the unique environment index and a MongoDB transaction
+    #[tokio::test]
+    async fn test_create_project_rolls_back() -> Result<(), Error> {
+        let (service, store) = setup(Backend::Mongo).await;
+        let database = store.repositories.database.clone().unwrap();
+        crate::utils::database::setup_database(database.as_ref().clone()).await?;
+
+        // Stored without the service's validation, so applying it fails on
+        // the second environment, after the project was created
+        let mut template = ProjectTemplate::standard();
+        template.name = "broken".to_string();
+        template.environments.push(template.environments[0].clone());
+        store
+            .repositories
+            .project_templates
+            .create(template)
+            .await?;
+
+        let result = service.create_project(project("Billing"), "broken").await;
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+
+        let projects = store
+            .repositories
+            .projects
+            .count(Default::default())
+            .await?;
+        assert_eq!(projects, 0);
+        let server_keys = store
+            .repositories
+            .server_keys
+            .count(Default::default())
+            .await?;
+        assert_eq!(server_keys, 0);
+
+        store.cleanup().await?;
+        Ok(())
+    }
+}
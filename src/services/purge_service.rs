@@ -0,0 +1,130 @@
+use crate::errors::AppError;
+use crate::repositories::access_token_repository::DynAccessTokenRepository;
+use crate::repositories::environment_repository::DynEnvironmentRepository;
+use crate::repositories::project_access_repository::DynProjectAccessRepository;
+use crate::repositories::project_repository::DynProjectRepository;
+use crate::repositories::project_scope_repository::DynProjectScopeRepository;
+use crate::repositories::project_template_repository::DynProjectTemplateRepository;
+use crate::repositories::registry::Repositories;
+use crate::repositories::server_key_repository::DynServerKeyRepository;
+use crate::repositories::service_account_key_repository::DynServiceAccountKeyRepository;
+use crate::repositories::service_account_repository::DynServiceAccountRepository;
+use chrono::{Duration, Utc};
+use mongodb::Database;
+use std::sync::Arc;
+
+/// Permanently removes soft-deleted records once their retention period has passed.
+pub struct PurgeService {
+    project_repository: Arc<DynProjectRepository>,
+    environment_repository: Arc<DynEnvironmentRepository>,
+    project_scope_repository: Arc<DynProjectScopeRepository>,
+    project_template_repository: Arc<DynProjectTemplateRepository>,
+    project_access_repository: Arc<DynProjectAccessRepository>,
+    server_key_repository: Arc<DynServerKeyRepository>,
+    access_token_repository: Arc<DynAccessTokenRepository>,
+    service_account_repository: Arc<DynServiceAccountRepository>,
+    service_account_key_repository: Arc<DynServiceAccountKeyRepository>,
+}
+
+impl PurgeService {
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
+    }
+
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
+        Ok(Self {
+            project_repository: repositories.projects.clone(),
+            environment_repository: repositories.environments.clone(),
+            project_scope_repository: repositories.project_scopes.clone(),
+            project_template_repository: repositories.project_templates.clone(),
+            project_access_repository: repositories.project_access.clone(),
+            server_key_repository: repositories.server_keys.clone(),
+            access_token_repository: repositories.access_tokens.clone(),
+            service_account_repository: repositories.service_accounts.clone(),
+            service_account_key_repository: repositories.service_account_keys.clone(),
+        })
+    }
+
+    /// Hard-deletes every record soft-deleted more than `retention_days` days ago.
+    ///
+    /// # Returns
+    ///
+    /// The total number of records removed across all collections.
+    #[tracing::instrument(skip_all, name = "PurgeService::purge")]
+    pub async fn purge(&self, retention_days: i64) -> Result<u64, AppError> {
+        let cutoff = Utc::now() - Duration::days(retention_days);
+
+        let mut purged = 0;
+        purged += self.access_token_repository.purge(cutoff).await?;
+        purged += self.server_key_repository.purge(cutoff).await?;
+        purged += self.project_access_repository.purge(cutoff).await?;
+        purged += self.project_scope_repository.purge(cutoff).await?;
+        purged += self.environment_repository.purge(cutoff).await?;
+        purged += self.project_repository.purge(cutoff).await?;
+        purged += self.project_template_repository.purge(cutoff).await?;
+        purged += self.service_account_key_repository.purge(cutoff).await?;
+        purged += self.service_account_repository.purge(cutoff).await?;
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::environment::Environment;
+    use crate::repositories::base::Repository;
+    use crate::repositories::environment_repository::EnvironmentRepository;
+    use crate::serializers::timestamp;
+    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use anyhow::Error;
+    use mongodb::bson::doc;
+
+    #[tokio::test]
+    async fn test_purge_respects_retention() -> Result<(), Error> {
+        let db = setup_test_db("purge_service").await?;
+        let service = PurgeService::new(Arc::new(db.clone()))?;
+        let repository = EnvironmentRepository::new(db.clone())?;
+
+        let mut ids = Vec::new();
+        for name in ["expired", "recent", "live"] {
+            let environment = repository
+                .create(Environment {
+                    id: None,
+                    project_id: mongodb::bson::uuid::Uuid::new(),
+                    name: name.to_string(),
+                    description: "Test Description".to_string(),
+                    enabled: true,
+                    created_at: None,
+                    updated_at: None,
+                    deleted_at: None,
+                    deleted_by: None,
+                    labels: Default::default(),
+                })
+                .await?;
+            ids.push(environment.id.unwrap());
+        }
+        repository.soft_delete(ids[0], None).await?;
+        repository.soft_delete(ids[1], None).await?;
+
+        // Backdate the first delete past the retention period
+        let backdated = timestamp::to_bson(&(Utc::now() - Duration::days(31)));
+        repository
+            .collection()?
+            .update_one(
+                doc! { "_id": ids[0] },
+                doc! { "$set": { "deleted_at": backdated } },
+            )
+            .await?;
+
+        let purged = service.purge(30).await?;
+        assert_eq!(purged, 1);
+
+        let remaining = repository.collection()?.count_documents(doc! {}).await?;
+        assert_eq!(remaining, 2);
+        assert!(repository.is_deleted(ids[1]).await?);
+        assert!(repository.read(ids[2]).await?.is_some());
+
+        cleanup_test_db(db).await?;
+        Ok(())
+    }
+}
@@ -1,40 +1,66 @@
-use crate::models::pagination::Pagination;
+use crate::errors::AppError;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::server_key::{
     ServerKey, ServerKeyCreatePayload, ServerKeyFilter, ServerKeyRead, ServerKeySortableFields,
     ServerKeyUpdatePayload,
 };
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use crate::repositories::server_key_repository::ServerKeyRepository;
+use crate::repositories::access_token_repository::DynAccessTokenRepository;
+use crate::repositories::base::{IdField, IdFilter};
+use crate::repositories::project_access_repository::DynProjectAccessRepository;
+use crate::repositories::registry::Repositories;
+use crate::repositories::server_key_repository::DynServerKeyRepository;
+use crate::repositories::unit_of_work::{Transaction, UnitOfWork};
 use crate::utils::security::SecretsManager;
 use crate::utils::tokens::key_builder::KeyBuilder;
-use anyhow::Error;
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
 use chrono::Utc;
+use jsonwebtoken::Algorithm;
 use mongodb::Database;
+use mongodb::bson::doc;
 use mongodb::bson::uuid::Uuid;
 use std::sync::Arc;
 
-#[derive(Debug)]
 pub struct ServerKeyService {
-    server_key_repository: ServerKeyRepository,
+    unit_of_work: UnitOfWork,
+    server_key_repository: Arc<DynServerKeyRepository>,
+    project_access_repository: Arc<DynProjectAccessRepository>,
+    access_token_repository: Arc<DynAccessTokenRepository>,
     secrets_manager: SecretsManager,
 }
 
 impl ServerKeyService {
-    pub fn new(database: Arc<Database>) -> Result<Self, Error> {
-        let server_key_repository = ServerKeyRepository::new(database.as_ref().clone())?;
-        let secrets_manager = SecretsManager::new(true)?;
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
+    }
+
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
         Ok(Self {
-            server_key_repository,
-            secrets_manager,
+            unit_of_work: UnitOfWork::new(repositories),
+            server_key_repository: repositories.server_keys.clone(),
+            project_access_repository: repositories.project_access.clone(),
+            access_token_repository: repositories.access_tokens.clone(),
+            secrets_manager: SecretsManager::new(true)?,
         })
     }
 
-    pub async fn create(&self, payload: ServerKeyCreatePayload) -> Result<ServerKeyRead, Error> {
+    #[tracing::instrument(skip_all, name = "ServerKeyService::create")]
+    pub async fn create(&self, payload: ServerKeyCreatePayload) -> Result<ServerKeyRead, AppError> {
+        self.create_in(payload, None).await
+    }
+
+    /// Generates a server key and stores it encrypted.
+    ///
+    /// Runs in `transaction` when given, so it commits or rolls back with it.
+    #[tracing::instrument(skip_all, name = "ServerKeyService::create_in")]
+    pub async fn create_in(
+        &self,
+        payload: ServerKeyCreatePayload,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<ServerKeyRead, AppError> {
         let key_builder = KeyBuilder::new();
-        let key_pair = key_builder.generate_key(payload.algorithm).unwrap();
+        let key_pair = key_builder.generate_key(payload.algorithm)?;
         let private_key = STANDARD.encode(key_pair.private_key);
 
         let encrypted_key = self
@@ -48,8 +74,13 @@ impl ServerKeyService {
             algorithm: payload.algorithm,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            deleted_at: None,
+            deleted_by: None,
         };
-        let servery_key = self.server_key_repository.create(server_key).await;
+        let servery_key = self
+            .server_key_repository
+            .create_in(server_key, transaction)
+            .await;
 
         match servery_key {
             Ok(server_key) => Ok(ServerKeyRead::from(server_key)),
@@ -57,7 +88,62 @@ impl ServerKeyService {
         }
     }
 
-    pub async fn get(&self, id: Uuid) -> Result<Option<ServerKeyRead>, Error> {
+    /// Stores a server key with existing key material, Base64 encoded as
+    /// returned by [`ServerKeyService::export`], e.g. when importing a project.
+    #[tracing::instrument(skip_all, name = "ServerKeyService::import")]
+    pub async fn import(
+        &self,
+        environment_id: Uuid,
+        algorithm: Algorithm,
+        private_key: &str,
+    ) -> Result<ServerKeyRead, AppError> {
+        let encrypted_key = self.secrets_manager.encrypt(private_key, &environment_id)?;
+        let server_key = ServerKey {
+            id: None,
+            key: encrypted_key,
+            environment_id,
+            algorithm,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+            deleted_by: None,
+        };
+        let server_key = self.server_key_repository.create(server_key).await?;
+        Ok(ServerKeyRead::from(server_key))
+    }
+
+    /// The server keys of an environment with their decrypted key material,
+    /// Base64 encoded, oldest first.
+    #[tracing::instrument(skip_all, name = "ServerKeyService::export")]
+    pub async fn export(
+        &self,
+        environment_id: Uuid,
+    ) -> Result<Vec<(ServerKeyRead, String)>, AppError> {
+        let mut server_keys = self
+            .server_key_repository
+            .find(
+                ServerKeyFilter {
+                    environment_id: Some(environment_id),
+                    ..Default::default()
+                },
+                None,
+                None,
+            )
+            .await?;
+        server_keys.sort_by_key(|server_key| server_key.created_at);
+        server_keys
+            .into_iter()
+            .map(|server_key| {
+                let private_key = self
+                    .secrets_manager
+                    .decrypt(&server_key.key, &environment_id)?;
+                Ok((ServerKeyRead::from(server_key), private_key))
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all, name = "ServerKeyService::get")]
+    pub async fn get(&self, id: Uuid) -> Result<Option<ServerKeyRead>, AppError> {
         let server_key = self.server_key_repository.read(id).await?;
 
         match server_key {
@@ -66,11 +152,12 @@ impl ServerKeyService {
         }
     }
 
+    #[tracing::instrument(skip_all, name = "ServerKeyService::update")]
     pub async fn update(
         &self,
         id: Uuid,
         server_key: ServerKeyUpdatePayload,
-    ) -> Result<ServerKeyRead, Error> {
+    ) -> Result<ServerKeyRead, AppError> {
         let server_key = self.server_key_repository.update(id, server_key).await;
 
         match server_key {
@@ -79,43 +166,135 @@ impl ServerKeyService {
         }
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<bool, Error> {
-        self.server_key_repository.delete(id).await
+    #[tracing::instrument(skip_all, name = "ServerKeyService::delete")]
+    pub async fn delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        self.server_key_repository.soft_delete(id, deleted_by).await
     }
 
+    /// Replaces a server key with a new one for the same environment and
+    /// algorithm, in one [unit of work](UnitOfWork).
+    ///
+    /// The old key is soft-deleted, so tokens signed with it no longer verify,
+    /// and the access tokens issued under its environment are revoked.
+    ///
+    /// # Returns
+    ///
+    /// The new key, or `None` if there is no live server key with this id.
+    #[tracing::instrument(skip_all, name = "ServerKeyService::rotate")]
+    pub async fn rotate(
+        &self,
+        id: Uuid,
+        deleted_by: Option<String>,
+    ) -> Result<Option<ServerKeyRead>, AppError> {
+        let Some(current) = self.server_key_repository.read(id).await? else {
+            return Ok(None);
+        };
+
+        self.unit_of_work
+            .run(async |mut transaction| {
+                let deleted_at = Utc::now();
+                let retired = self
+                    .server_key_repository
+                    .soft_delete_many(
+                        IdFilter::ids([id]),
+                        deleted_at,
+                        deleted_by.clone(),
+                        transaction.as_deref_mut(),
+                    )
+                    .await?;
+                // Deleted since it was read
+                if retired == 0 {
+                    return Ok(None);
+                }
+
+                let accesses = self
+                    .project_access_repository
+                    .distinct_ids(
+                        IdFilter::by(IdField::EnvironmentId, [current.environment_id])
+                            .with_deleted(),
+                        transaction.as_deref_mut(),
+                    )
+                    .await?;
+                self.access_token_repository
+                    .soft_delete_many(
+                        IdFilter::by(IdField::ProjectAccessId, accesses),
+                        deleted_at,
+                        deleted_by.clone(),
+                        transaction.as_deref_mut(),
+                    )
+                    .await?;
+
+                let payload = ServerKeyCreatePayload {
+                    environment_id: current.environment_id,
+                    algorithm: current.algorithm,
+                };
+                Ok(Some(self.create_in(payload, transaction).await?))
+            })
+            .await
+    }
+
+    #[tracing::instrument(skip_all, name = "ServerKeyService::restore")]
+    pub async fn restore(&self, id: Uuid) -> Result<Option<ServerKeyRead>, AppError> {
+        let server_key = self.server_key_repository.restore(id).await?;
+        Ok(server_key.map(ServerKeyRead::from))
+    }
+
+    #[tracing::instrument(skip_all, name = "ServerKeyService::find")]
     pub async fn find(
         &self,
         filter: ServerKeyFilter,
         sort: Option<SortBuilder<ServerKeySortableFields>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<ServerKeyRead>, Error> {
+    ) -> Result<Vec<ServerKeyRead>, AppError> {
         let server_keys = self
             .server_key_repository
             .find(filter, sort, pagination)
             .await?;
         Ok(server_keys.into_iter().map(ServerKeyRead::from).collect())
     }
+
+    #[tracing::instrument(skip_all, name = "ServerKeyService::list")]
+    pub async fn list(
+        &self,
+        filter: ServerKeyFilter,
+        sort: Option<SortBuilder<ServerKeySortableFields>>,
+        pagination: Pagination,
+    ) -> Result<Page<ServerKeyRead>, AppError> {
+        let page = self
+            .server_key_repository
+            .find_page(filter, sort, pagination)
+            .await?;
+        Ok(page.map(ServerKeyRead::from))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        models::sort::SortDirection,
-        test_utils::{cleanup_test_db, setup_test_db},
+        models::{access_token::AccessToken, project_access::ProjectAccess, sort::SortDirection},
+        test_utils::{Backend, TestStore},
     };
     use anyhow::Result;
+    use chrono::Duration;
     use jsonwebtoken::Algorithm;
-
-    async fn setup() -> (ServerKeyService, Database) {
-        let db = setup_test_db("server_key_service").await.unwrap();
-        let service = ServerKeyService::new(Arc::new(db.clone())).unwrap();
-        (service, db)
+    use rstest::rstest;
+
+    async fn setup(backend: Backend) -> (ServerKeyService, TestStore) {
+        let store = TestStore::setup("server_key_service", backend)
+            .await
+            .unwrap();
+        let service = ServerKeyService::with_repositories(&store.repositories).unwrap();
+        (service, store)
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_create_server_key() -> Result<()> {
-        let (service, db) = setup().await;
+    async fn test_create_server_key(#[case] backend: Backend) -> Result<()> {
+        let (service, store) = setup(backend).await;
         let environment_id = Uuid::new();
 
         let payload = ServerKeyCreatePayload {
@@ -129,13 +308,17 @@ mod tests {
         assert!(created.created_at <= Utc::now());
         assert!(created.updated_at <= Utc::now());
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_get_server_key() -> Result<()> {
-        let (service, db) = setup().await;
+    async fn test_get_server_key(#[case] backend: Backend) -> Result<()> {
+        let (service, store) = setup(backend).await;
         let environment_id = Uuid::new();
 
         // Create a server key first
@@ -158,13 +341,17 @@ mod tests {
         let non_existent = service.get(Uuid::new()).await?;
         assert!(non_existent.is_none());
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_update_server_key() -> Result<()> {
-        let (service, db) = setup().await;
+    async fn test_update_server_key(#[case] backend: Backend) -> Result<()> {
+        let (service, store) = setup(backend).await;
         let environment_id = Uuid::new();
         let new_environment_id = Uuid::new();
 
@@ -190,13 +377,17 @@ mod tests {
         assert_eq!(updated.created_at, created.created_at);
         assert!(updated.updated_at >= created.updated_at);
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_delete_server_key() -> Result<()> {
-        let (service, db) = setup().await;
+    async fn test_delete_server_key(#[case] backend: Backend) -> Result<()> {
+        let (service, store) = setup(backend).await;
         let environment_id = Uuid::new();
 
         // Create a server key first
@@ -212,7 +403,7 @@ mod tests {
         assert!(retrieved.is_some());
 
         // Delete it
-        let deleted = service.delete(created.id).await?;
+        let deleted = service.delete(created.id, None).await?;
         assert!(deleted);
 
         // Verify it's gone
@@ -220,16 +411,101 @@ mod tests {
         assert!(retrieved_after_delete.is_none());
 
         // Try to delete a non-existent key
-        let non_existent_delete = service.delete(Uuid::new()).await?;
+        let non_existent_delete = service.delete(Uuid::new(), None).await?;
         assert!(!non_existent_delete);
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_rotate_server_key(#[case] backend: Backend) -> Result<()> {
+        let (service, store) = setup(backend).await;
+        let repositories = &store.repositories;
+        let environment_id = Uuid::new();
+
+        let created = service
+            .create(ServerKeyCreatePayload {
+                environment_id,
+                algorithm: Algorithm::HS384,
+            })
+            .await?;
+        let access = repositories
+            .project_access
+            .create(ProjectAccess {
+                id: None,
+                name: "reader".to_string(),
+                environment_id,
+                service_account_id: Some(Uuid::new()),
+                project_scopes: vec![],
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await?;
+        let token = repositories
+            .access_tokens
+            .create(AccessToken {
+                id: None,
+                project_access_id: access.id.unwrap(),
+                key: "token-key".to_string(),
+                algorithm: Algorithm::HS384,
+                expires_at: Utc::now() + Duration::hours(1),
+                created_at: Utc::now(),
+                enabled: true,
+                deleted_at: None,
+                deleted_by: None,
+            })
+            .await?;
+
+        let rotated = service
+            .rotate(created.id, Some("alice".to_string()))
+            .await?
+            .unwrap();
+        assert_ne!(rotated.id, created.id);
+        assert_eq!(rotated.environment_id, environment_id);
+        assert_eq!(rotated.algorithm, Algorithm::HS384);
+
+        assert!(service.get(created.id).await?.is_none());
+        assert!(service.get(rotated.id).await?.is_some());
+        assert!(
+            repositories
+                .access_tokens
+                .read(token.id.unwrap())
+                .await?
+                .is_none()
+        );
+        // The access itself is kept; only its tokens are revoked
+        assert!(
+            repositories
+                .project_access
+                .read(access.id.unwrap())
+                .await?
+                .is_some()
+        );
+
+        // The old key is gone, so it cannot be rotated again
+        assert!(service.rotate(created.id, None).await?.is_none());
+        assert!(service.rotate(Uuid::new(), None).await?.is_none());
+
+        store.cleanup().await.unwrap();
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_server_keys() -> Result<()> {
-        let (service, db) = setup().await;
+    async fn test_find_server_keys(#[case] backend: Backend) -> Result<()> {
+        let (service, store) = setup(backend).await;
         let environment_id = Uuid::new();
         let second_environment_id = Uuid::new();
 
@@ -252,6 +528,7 @@ mod tests {
             environment_id: Some(environment_id),
             algorithm: None,
             key: None,
+            ..Default::default()
         };
 
         let found1 = service.find(filter1, None, None).await?;
@@ -264,6 +541,7 @@ mod tests {
             environment_id: None,
             algorithm: Some(Algorithm::HS256),
             key: None,
+            ..Default::default()
         };
 
         let found2 = service.find(filter2, None, None).await?;
@@ -276,13 +554,17 @@ mod tests {
         let found_all = service.find(filter_all, None, None).await?;
         assert_eq!(found_all.len(), 2);
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_server_keys_with_pagination() -> Result<()> {
-        let (service, db) = setup().await;
+    async fn test_find_server_keys_with_pagination(#[case] backend: Backend) -> Result<()> {
+        let (service, store) = setup(backend).await;
         let environment_id = Uuid::new();
 
         // Create 5 server keys
@@ -302,6 +584,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(1),
             limit: Some(2),
+            cursor: None,
         };
 
         let found = service
@@ -310,6 +593,7 @@ mod tests {
                     environment_id: Some(environment_id),
                     algorithm: None,
                     key: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -322,6 +606,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(2),
             limit: Some(2),
+            cursor: None,
         };
 
         let found = service
@@ -330,6 +615,7 @@ mod tests {
                     environment_id: Some(environment_id),
                     algorithm: None,
                     key: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -342,6 +628,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(3),
             limit: Some(2),
+            cursor: None,
         };
 
         let found = service
@@ -350,6 +637,7 @@ mod tests {
                     environment_id: Some(environment_id),
                     algorithm: None,
                     key: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -368,13 +656,17 @@ mod tests {
 
         assert_eq!(found.len(), 5);
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_encryption_decryption() -> Result<()> {
-        let (service, db) = setup().await;
+    async fn test_encryption_decryption(#[case] backend: Backend) -> Result<()> {
+        let (service, store) = setup(backend).await;
         let environment_id = Uuid::new();
 
         // Create a server key
@@ -387,13 +679,13 @@ mod tests {
         let created = service.create(payload).await?;
 
         // The key should be encrypted in the database
-        let server_key_repo = ServerKeyRepository::new(db.clone())?;
+        let server_key_repo = store.repositories.server_keys.clone();
         let stored_key = server_key_repo.read(created.id).await?.unwrap();
 
         // The stored key should be encrypted and not match the original
         assert!(!stored_key.key.is_empty());
 
-        cleanup_test_db(db).await.unwrap();
+        store.cleanup().await.unwrap();
         Ok(())
     }
 }
@@ -1,87 +1,135 @@
-use crate::models::pagination::Pagination;
+use crate::errors::AppError;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::service_account_key::{
     ServiceAccountKey, ServiceAccountKeyFilter, ServiceAccountKeySortableFields,
     ServiceAccountKeyUpdatePayload,
 };
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use crate::repositories::service_account_key_repository::ServiceAccountKeyRepository;
-use anyhow::Error;
+use crate::repositories::registry::Repositories;
+use crate::repositories::service_account_key_repository::DynServiceAccountKeyRepository;
+use chrono::{DateTime, Utc};
 use mongodb::Database;
 use mongodb::bson::uuid::Uuid;
 use std::sync::Arc;
 
 pub struct ServiceAccountKeyService {
-    service_account_key_repository: ServiceAccountKeyRepository,
+    service_account_key_repository: Arc<DynServiceAccountKeyRepository>,
 }
 
 impl ServiceAccountKeyService {
-    pub fn new(database: Arc<Database>) -> Result<Self, Error> {
-        let service_account_key_repository =
-            ServiceAccountKeyRepository::new(database.as_ref().clone())?;
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
+    }
+
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
         Ok(Self {
-            service_account_key_repository,
+            service_account_key_repository: repositories.service_account_keys.clone(),
         })
     }
 
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyService::create")]
     pub async fn create(
         &self,
         service_account_key: ServiceAccountKey,
-    ) -> Result<ServiceAccountKey, Error> {
+    ) -> Result<ServiceAccountKey, AppError> {
         self.service_account_key_repository
             .create(service_account_key)
             .await
     }
 
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyService::get_service_account_key")]
     pub async fn get_service_account_key(
         &self,
         id: Uuid,
-    ) -> Result<Option<ServiceAccountKey>, Error> {
+    ) -> Result<Option<ServiceAccountKey>, AppError> {
         self.service_account_key_repository.read(id).await
     }
 
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyService::update")]
     pub async fn update(
         &self,
         id: Uuid,
         service_account_key: ServiceAccountKeyUpdatePayload,
-    ) -> Result<ServiceAccountKey, Error> {
+    ) -> Result<ServiceAccountKey, AppError> {
         self.service_account_key_repository
             .update(id, service_account_key)
             .await
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<bool, Error> {
-        self.service_account_key_repository.delete(id).await
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyService::delete")]
+    pub async fn delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        self.service_account_key_repository
+            .soft_delete(id, deleted_by)
+            .await
+    }
+
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyService::restore")]
+    pub async fn restore(&self, id: Uuid) -> Result<Option<ServiceAccountKey>, AppError> {
+        self.service_account_key_repository.restore(id).await
+    }
+
+    /// Number of enabled, unexpired keys expiring before `deadline`.
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyService::count_expiring")]
+    pub async fn count_expiring(&self, deadline: DateTime<Utc>) -> Result<u64, AppError> {
+        self.service_account_key_repository
+            .count(ServiceAccountKeyFilter {
+                is_enabled: Some(true),
+                is_active: Some(true),
+                expires_before: Some(deadline),
+                ..Default::default()
+            })
+            .await
     }
 
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyService::find")]
     pub async fn find(
         &self,
         filter: ServiceAccountKeyFilter,
         sort: Option<SortBuilder<ServiceAccountKeySortableFields>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<ServiceAccountKey>, Error> {
+    ) -> Result<Vec<ServiceAccountKey>, AppError> {
         self.service_account_key_repository
             .find(filter, sort, pagination)
             .await
     }
+
+    #[tracing::instrument(skip_all, name = "ServiceAccountKeyService::list")]
+    pub async fn list(
+        &self,
+        filter: ServiceAccountKeyFilter,
+        sort: Option<SortBuilder<ServiceAccountKeySortableFields>>,
+        pagination: Pagination,
+    ) -> Result<Page<ServiceAccountKey>, AppError> {
+        self.service_account_key_repository
+            .find_page(filter, sort, pagination)
+            .await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
+    use crate::test_utils::{Backend, TestStore};
+    use anyhow::Error;
     use chrono::{Duration, Utc};
     use jsonwebtoken::Algorithm;
+    use rstest::rstest;
 
-    async fn setup() -> (ServiceAccountKeyService, Database) {
-        let db = setup_test_db("service_account_key_service").await.unwrap();
-        let service = ServiceAccountKeyService::new(Arc::new(db.clone())).unwrap();
-        (service, db)
+    async fn setup(backend: Backend) -> (ServiceAccountKeyService, TestStore) {
+        let store = TestStore::setup("service_account_key_service", backend)
+            .await
+            .unwrap();
+        let service = ServiceAccountKeyService::with_repositories(&store.repositories).unwrap();
+        (service, store)
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_create_service_account_key() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_create_service_account_key(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let now = Utc::now();
         let key = ServiceAccountKey {
             id: None,
@@ -92,6 +140,8 @@ mod tests {
             enabled: true,
             created_at: Some(now),
             updated_at: Some(now),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = service.create(key.clone()).await?;
@@ -99,13 +149,17 @@ mod tests {
         assert_eq!(created.key, key.key);
         assert_eq!(created.algorithm, key.algorithm);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_get_service_account_key() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_get_service_account_key(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let key = ServiceAccountKey {
             id: Some(Uuid::new()),
             service_account_id: Uuid::new(),
@@ -115,6 +169,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = service.create(key.clone()).await?;
@@ -125,13 +181,17 @@ mod tests {
         assert_eq!(retrieved.id, created.id);
         assert_eq!(retrieved.key, created.key);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_update_service_account_key() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_update_service_account_key(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let key = ServiceAccountKey {
             id: Some(Uuid::new()),
             service_account_id: Uuid::new(),
@@ -141,6 +201,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = service.create(key).await?;
@@ -154,13 +216,17 @@ mod tests {
         assert_eq!(updated.key, "new-key");
         assert!(!updated.enabled);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_delete_service_account_key() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_delete_service_account_key(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let key = ServiceAccountKey {
             id: Some(Uuid::new()),
             service_account_id: Uuid::new(),
@@ -170,22 +236,30 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         let created = service.create(key).await?;
-        let deleted = service.delete(created.id.unwrap()).await?;
+        let deleted = service.delete(created.id.unwrap(), None).await?;
         assert!(deleted);
 
         let read = service.get_service_account_key(created.id.unwrap()).await?;
         assert!(read.is_none());
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_service_account_keys_by_enabled_status() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_service_account_keys_by_enabled_status(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let key1 = ServiceAccountKey {
             id: Some(Uuid::new()),
             service_account_id: Uuid::new(),
@@ -195,6 +269,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
         let key2 = ServiceAccountKey {
             id: Some(Uuid::new()),
@@ -205,6 +281,8 @@ mod tests {
             enabled: false,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         service.create(key1.clone()).await?;
@@ -215,6 +293,7 @@ mod tests {
             algorithm: None,
             is_enabled: Some(true),
             is_active: None,
+            ..Default::default()
         };
 
         let found = service.find(filter, None, None).await?;
@@ -222,13 +301,19 @@ mod tests {
         assert_eq!(found[0].id, key1.id);
         assert!(found[0].enabled);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_service_account_keys_by_algorithm() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_service_account_keys_by_algorithm(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let key1 = ServiceAccountKey {
             id: Some(Uuid::new()),
             service_account_id: Uuid::new(),
@@ -238,6 +323,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
         let key2 = ServiceAccountKey {
             id: Some(Uuid::new()),
@@ -248,6 +335,8 @@ mod tests {
             enabled: true,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            deleted_at: None,
+            deleted_by: None,
         };
 
         service.create(key1).await?;
@@ -258,19 +347,26 @@ mod tests {
             algorithm: Some(Algorithm::RS256),
             is_enabled: None,
             is_active: None,
+            ..Default::default()
         };
 
         let found = service.find(filter, None, None).await?;
         assert_eq!(found.len(), 1);
         assert_eq!(found[0].algorithm, Algorithm::RS256);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_service_account_keys_with_pagination() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_service_account_keys_with_pagination(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
 
         // Create 5 test service account keys
         for i in 1..=5 {
@@ -283,6 +379,8 @@ mod tests {
                 enabled: true,
                 created_at: Some(Utc::now()),
                 updated_at: Some(Utc::now()),
+                deleted_at: None,
+                deleted_by: None,
             };
             service.create(key).await?;
         }
@@ -291,6 +389,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(1),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -299,6 +398,7 @@ mod tests {
                     algorithm: None,
                     is_enabled: None,
                     is_active: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -312,6 +412,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(2),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -320,6 +421,7 @@ mod tests {
                     algorithm: None,
                     is_enabled: None,
                     is_active: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -333,6 +435,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(3),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -341,6 +444,7 @@ mod tests {
                     algorithm: None,
                     is_enabled: None,
                     is_active: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -349,7 +453,7 @@ mod tests {
         assert_eq!(found.len(), 1);
         assert_eq!(found[0].key, "test-key-5");
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 }
@@ -1,78 +1,203 @@
-use crate::models::pagination::Pagination;
+use crate::errors::AppError;
+use crate::models::certificate_binding::validate_certificate_bindings;
+use crate::models::label::validate_labels;
+use crate::models::pagination::{Page, Pagination};
 use crate::models::service_account::{
     ServiceAccount, ServiceAccountFilter, ServiceAccountSortableFields, ServiceAccountUpdatePayload,
 };
 use crate::models::sort::SortBuilder;
-use crate::repositories::base::Repository;
-use crate::repositories::service_account_repository::ServiceAccountRepository;
-use anyhow::Error;
+use crate::repositories::access_token_repository::DynAccessTokenRepository;
+use crate::repositories::base::{IdField, IdFilter};
+use crate::repositories::project_access_repository::DynProjectAccessRepository;
+use crate::repositories::registry::Repositories;
+use crate::repositories::service_account_key_repository::DynServiceAccountKeyRepository;
+use crate::repositories::service_account_repository::DynServiceAccountRepository;
+use crate::repositories::unit_of_work::UnitOfWork;
+use chrono::Utc;
 use mongodb::Database;
+use mongodb::bson::doc;
 use mongodb::bson::uuid::Uuid;
 use std::sync::Arc;
 
 pub struct ServiceAccountService {
-    service_account_repository: ServiceAccountRepository,
+    unit_of_work: UnitOfWork,
+    service_account_repository: Arc<DynServiceAccountRepository>,
+    service_account_key_repository: Arc<DynServiceAccountKeyRepository>,
+    project_access_repository: Arc<DynProjectAccessRepository>,
+    access_token_repository: Arc<DynAccessTokenRepository>,
 }
 
 impl ServiceAccountService {
-    pub fn new(database: Arc<Database>) -> Result<Self, Error> {
-        let service_account_repository = ServiceAccountRepository::new(database.as_ref().clone())?;
+    pub fn new(database: Arc<Database>) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?)
+    }
+
+    pub fn with_repositories(repositories: &Repositories) -> Result<Self, AppError> {
         Ok(Self {
-            service_account_repository,
+            unit_of_work: UnitOfWork::new(repositories),
+            service_account_repository: repositories.service_accounts.clone(),
+            service_account_key_repository: repositories.service_account_keys.clone(),
+            project_access_repository: repositories.project_access.clone(),
+            access_token_repository: repositories.access_tokens.clone(),
         })
     }
 
-    pub async fn create(&self, service_account: ServiceAccount) -> Result<ServiceAccount, Error> {
+    #[tracing::instrument(skip_all, name = "ServiceAccountService::create")]
+    pub async fn create(
+        &self,
+        mut service_account: ServiceAccount,
+    ) -> Result<ServiceAccount, AppError> {
+        validate_labels(&service_account.labels)?;
+        validate_certificate_bindings(&service_account.certificate_bindings)?;
+        // A new account starts unlocked, whatever the caller sent
+        service_account.failed_authentications = 0;
+        service_account.locked_until = None;
         self.service_account_repository
             .create(service_account)
             .await
     }
 
-    pub async fn get_service_account(&self, id: Uuid) -> Result<Option<ServiceAccount>, Error> {
+    #[tracing::instrument(skip_all, name = "ServiceAccountService::get_service_account")]
+    pub async fn get_service_account(&self, id: Uuid) -> Result<Option<ServiceAccount>, AppError> {
         self.service_account_repository.read(id).await
     }
 
+    #[tracing::instrument(skip_all, name = "ServiceAccountService::update")]
     pub async fn update(
         &self,
         id: Uuid,
         service_account: ServiceAccountUpdatePayload,
-    ) -> Result<ServiceAccount, Error> {
+    ) -> Result<ServiceAccount, AppError> {
+        if let Some(labels) = &service_account.labels {
+            validate_labels(labels)?;
+        }
+        if let Some(bindings) = &service_account.certificate_bindings {
+            validate_certificate_bindings(bindings)?;
+        }
         self.service_account_repository
             .update(id, service_account)
             .await
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<bool, Error> {
-        self.service_account_repository.delete(id).await
+    /// Soft-deletes a service account with its keys and project accesses, and
+    /// revokes the access tokens issued through those accesses, in one
+    /// [unit of work](UnitOfWork). Restoring the account does not restore them.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a live service account was deleted, `false` if none was found.
+    #[tracing::instrument(skip_all, name = "ServiceAccountService::delete")]
+    pub async fn delete(&self, id: Uuid, deleted_by: Option<String>) -> Result<bool, AppError> {
+        self.unit_of_work
+            .run(async |mut transaction| {
+                let deleted_at = Utc::now();
+                let deleted = self
+                    .service_account_repository
+                    .soft_delete_many(
+                        IdFilter::ids([id]),
+                        deleted_at,
+                        deleted_by.clone(),
+                        transaction.as_deref_mut(),
+                    )
+                    .await?;
+                if deleted == 0 {
+                    return Ok(false);
+                }
+
+                self.service_account_key_repository
+                    .soft_delete_many(
+                        IdFilter::by(IdField::ServiceAccountId, [id]),
+                        deleted_at,
+                        deleted_by.clone(),
+                        transaction.as_deref_mut(),
+                    )
+                    .await?;
+                let accesses = self
+                    .project_access_repository
+                    .distinct_ids(
+                        IdFilter::by(IdField::ServiceAccountId, [id]),
+                        transaction.as_deref_mut(),
+                    )
+                    .await?;
+                self.access_token_repository
+                    .soft_delete_many(
+                        IdFilter::by(IdField::ProjectAccessId, accesses.clone()),
+                        deleted_at,
+                        deleted_by.clone(),
+                        transaction.as_deref_mut(),
+                    )
+                    .await?;
+                self.project_access_repository
+                    .soft_delete_many(
+                        IdFilter::ids(accesses),
+                        deleted_at,
+                        deleted_by.clone(),
+                        transaction,
+                    )
+                    .await?;
+                Ok(true)
+            })
+            .await
+    }
+
+    #[tracing::instrument(skip_all, name = "ServiceAccountService::restore")]
+    pub async fn restore(&self, id: Uuid) -> Result<Option<ServiceAccount>, AppError> {
+        self.service_account_repository.restore(id).await
     }
 
+    #[tracing::instrument(skip_all, name = "ServiceAccountService::find")]
     pub async fn find(
         &self,
         filter: ServiceAccountFilter,
         sort: Option<SortBuilder<ServiceAccountSortableFields>>,
         pagination: Option<Pagination>,
-    ) -> Result<Vec<ServiceAccount>, Error> {
+    ) -> Result<Vec<ServiceAccount>, AppError> {
         self.service_account_repository
             .find(filter, sort, pagination)
             .await
     }
+
+    #[tracing::instrument(skip_all, name = "ServiceAccountService::list")]
+    pub async fn list(
+        &self,
+        filter: ServiceAccountFilter,
+        sort: Option<SortBuilder<ServiceAccountSortableFields>>,
+        pagination: Pagination,
+    ) -> Result<Page<ServiceAccount>, AppError> {
+        self.service_account_repository
+            .find_page(filter, sort, pagination)
+            .await
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
-    use crate::test_utils::{cleanup_test_db, setup_test_db};
-
-    async fn setup() -> (ServiceAccountService, Database) {
-        let db = setup_test_db("service_account_service").await.unwrap();
-        let service = ServiceAccountService::new(Arc::new(db.clone())).unwrap();
-        (service, db)
+    use crate::models::access_token::AccessToken;
+    use crate::models::project_access::ProjectAccess;
+    use crate::models::service_account_key::ServiceAccountKey;
+    use crate::test_utils::{Backend, TestStore};
+    use anyhow::Error;
+    use chrono::Duration;
+    use jsonwebtoken::Algorithm;
+    use rstest::rstest;
+
+    async fn setup(backend: Backend) -> (ServiceAccountService, TestStore) {
+        let store = TestStore::setup("service_account_service", backend)
+            .await
+            .unwrap();
+        let service = ServiceAccountService::with_repositories(&store.repositories).unwrap();
+        (service, store)
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_create_service_account() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_create_service_account(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let account = ServiceAccount::new(
             "test@example.com".to_string(),
             "testuser".to_string(),
@@ -85,13 +210,49 @@ mod tests {
         assert_eq!(created.user, account.user);
         assert_eq!(created.secret, account.secret);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_create_service_account_starts_unlocked(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
+        let mut account = ServiceAccount::new(
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "secret123".to_string(),
+        );
+        account.failed_authentications = 50;
+        account.locked_until = Some(Utc::now() + Duration::days(365));
+
+        let created = service.create(account).await?;
+        assert_eq!(created.failed_authentications, 0);
+        assert!(created.locked_until.is_none());
+
+        let stored = service
+            .get_service_account(created.id.unwrap())
+            .await?
+            .unwrap();
+        assert_eq!(stored.failed_authentications, 0);
+        assert!(stored.locked_until.is_none());
+
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_get_service_account() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_get_service_account(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let account = ServiceAccount::new(
             "test@example.com".to_string(),
             "testuser".to_string(),
@@ -106,13 +267,17 @@ mod tests {
         assert_eq!(retrieved.id, created.id);
         assert_eq!(retrieved.email, created.email);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_update_service_account() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_update_service_account(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let account = ServiceAccount::new(
             "test@example.com".to_string(),
             "testuser".to_string(),
@@ -125,6 +290,8 @@ mod tests {
             user: Some("newuser".to_string()),
             secret: Some("newsecret".to_string()),
             enabled: Some(false),
+            labels: None,
+            certificate_bindings: None,
         };
 
         let updated = service.update(created.id.unwrap(), update).await?;
@@ -133,13 +300,17 @@ mod tests {
         assert_eq!(updated.secret, "newsecret");
         assert!(!updated.enabled);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_delete_service_account() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_delete_service_account(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let account = ServiceAccount::new(
             "test@example.com".to_string(),
             "testuser".to_string(),
@@ -147,19 +318,121 @@ mod tests {
         );
 
         let created = service.create(account).await?;
-        let deleted = service.delete(created.id.unwrap()).await?;
+        let deleted = service.delete(created.id.unwrap(), None).await?;
         assert!(deleted);
 
         let read = service.get_service_account(created.id.unwrap()).await?;
         assert!(read.is_none());
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_delete_service_account_cascade(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
+        let repositories = &store.repositories;
+        let created = service
+            .create(ServiceAccount::new(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "secret123".to_string(),
+            ))
+            .await?;
+        let account_id = created.id.unwrap();
+
+        let key = repositories
+            .service_account_keys
+            .create(ServiceAccountKey {
+                id: None,
+                service_account_id: account_id,
+                algorithm: Algorithm::HS256,
+                key: "account-key".to_string(),
+                expires_at: Utc::now() + Duration::days(1),
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+            })
+            .await?;
+        let access = repositories
+            .project_access
+            .create(ProjectAccess {
+                id: None,
+                name: "reader".to_string(),
+                environment_id: Uuid::new(),
+                service_account_id: Some(account_id),
+                project_scopes: vec![],
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await?;
+        let token = repositories
+            .access_tokens
+            .create(AccessToken {
+                id: None,
+                project_access_id: access.id.unwrap(),
+                key: "token-key".to_string(),
+                algorithm: Algorithm::HS256,
+                expires_at: Utc::now() + Duration::hours(1),
+                created_at: Utc::now(),
+                enabled: true,
+                deleted_at: None,
+                deleted_by: None,
+            })
+            .await?;
+
+        assert!(
+            service
+                .delete(account_id, Some("alice".to_string()))
+                .await?
+        );
+        assert!(service.get_service_account(account_id).await?.is_none());
+        assert!(
+            repositories
+                .service_account_keys
+                .read(key.id.unwrap())
+                .await?
+                .is_none()
+        );
+        assert!(
+            repositories
+                .project_access
+                .read(access.id.unwrap())
+                .await?
+                .is_none()
+        );
+        assert!(
+            repositories
+                .access_tokens
+                .read(token.id.unwrap())
+                .await?
+                .is_none()
+        );
+
+        // Already deleted
+        assert!(!service.delete(account_id, None).await?);
+
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_service_accounts() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_service_accounts(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
         let account1 = ServiceAccount::new(
             "test1@example.com".to_string(),
             "testuser1".to_string(),
@@ -178,19 +451,26 @@ mod tests {
             email: Some("test2@example.com".to_string()),
             user: None,
             is_enabled: None,
+            ..Default::default()
         };
 
         let found = service.find(filter, None, None).await?;
         assert_eq!(found.len(), 1);
         assert_eq!(found[0].id, created2.id);
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
     #[tokio::test]
-    async fn test_find_service_accounts_with_pagination() -> Result<(), Error> {
-        let (service, db) = setup().await;
+    async fn test_find_service_accounts_with_pagination(
+        #[case] backend: Backend,
+    ) -> Result<(), Error> {
+        let (service, store) = setup(backend).await;
 
         // Create 5 test accounts
         for i in 1..=5 {
@@ -206,6 +486,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(1),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -213,6 +494,7 @@ mod tests {
                     email: None,
                     user: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -226,6 +508,7 @@ mod tests {
         let pagination = Pagination {
             page: Some(2),
             limit: Some(2),
+            cursor: None,
         };
         let found = service
             .find(
@@ -233,6 +516,7 @@ mod tests {
                     email: None,
                     user: None,
                     is_enabled: None,
+                    ..Default::default()
                 },
                 None,
                 Some(pagination),
@@ -242,7 +526,7 @@ mod tests {
         assert_eq!(found[0].user, "testuser3");
         assert_eq!(found[1].user, "testuser4");
 
-        cleanup_test_db(db).await?;
+        store.cleanup().await?;
         Ok(())
     }
 }
@@ -0,0 +1,870 @@
+use crate::config::TokenConfig;
+use crate::errors::AppError;
+use crate::metrics::{Outcome, TokenOperation, UNKNOWN_ENVIRONMENT, metrics};
+use crate::models::dpop_proof::UsedDpopProof;
+use crate::models::project_access::{ProjectAccess, ProjectAccessFilter};
+use crate::models::server_key::ServerKeyFilter;
+use crate::models::service_account::ServiceAccount;
+use crate::models::token::{IntrospectionResponse, TokenResponse};
+use crate::repositories::dpop_proof_repository::DpopProofStore;
+use crate::repositories::environment_repository::DynEnvironmentRepository;
+use crate::repositories::project_access_repository::DynProjectAccessRepository;
+use crate::repositories::project_repository::DynProjectRepository;
+use crate::repositories::project_scope_repository::DynProjectScopeRepository;
+use crate::repositories::registry::Repositories;
+use crate::repositories::server_key_repository::DynServerKeyRepository;
+use crate::repositories::service_account_repository::DynServiceAccountRepository;
+use crate::utils::security::SecretsManager;
+use crate::utils::tokens::dpop::{DPOP_TOKEN_TYPE, DpopRequest, verify_proof};
+use crate::utils::tokens::key_builder::{Claims, Confirmation, KeyBuilder};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, Validation};
+use mongodb::Database;
+use mongodb::bson::uuid::Uuid;
+use std::sync::Arc;
+
+/// Issues signed access tokens to authenticated service accounts, and
+/// verifies them when they are presented.
+///
+/// Tokens are JWTs signed with the newest server key of the environment the
+/// account has access to, carrying the account as `sub`, the environment as
+/// `aud` and the granted project scope names as `scopes`.
+pub struct TokenService {
+    environment_repository: Arc<DynEnvironmentRepository>,
+    project_repository: Arc<DynProjectRepository>,
+    project_access_repository: Arc<DynProjectAccessRepository>,
+    project_scope_repository: Arc<DynProjectScopeRepository>,
+    server_key_repository: Arc<DynServerKeyRepository>,
+    service_account_repository: Arc<DynServiceAccountRepository>,
+    dpop_proof_repository: Arc<dyn DpopProofStore>,
+    config: TokenConfig,
+}
+
+/// What the presenter of a token proved possession of, checked against the
+/// token's `cnf` claim.
+#[derive(Debug, Clone, Default)]
+pub struct ProofOfPossession {
+    /// SHA-256 thumbprint of the client certificate the token was presented with.
+    pub certificate_thumbprint: Option<String>,
+    /// DPoP proof the token was presented with.
+    pub dpop: Option<DpopPresentation>,
+}
+
+/// A DPoP proof and the request it was presented in.
+#[derive(Debug, Clone)]
+pub struct DpopPresentation {
+    /// The `DPoP` header of the request
+    pub proof: String,
+    /// HTTP method of the request
+    pub method: String,
+    /// Absolute URI of the request
+    pub uri: String,
+}
+
+impl TokenService {
+    pub fn new(database: Arc<Database>, config: TokenConfig) -> Result<Self, AppError> {
+        Self::with_repositories(&Repositories::mongo(database)?, config)
+    }
+
+    pub fn with_repositories(
+        repositories: &Repositories,
+        config: TokenConfig,
+    ) -> Result<Self, AppError> {
+        Ok(Self {
+            environment_repository: repositories.environments.clone(),
+            project_repository: repositories.projects.clone(),
+            project_access_repository: repositories.project_access.clone(),
+            project_scope_repository: repositories.project_scopes.clone(),
+            server_key_repository: repositories.server_keys.clone(),
+            service_account_repository: repositories.service_accounts.clone(),
+            dpop_proof_repository: repositories.dpop_proofs.clone(),
+            config,
+        })
+    }
+
+    /// Issues a token for `account`, which the caller has already authenticated.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource` - Environment the token is for; may be omitted when the
+    ///   account has access to a single environment
+    /// * `scope` - Space separated project scope names to narrow the token to
+    /// * `confirmation` - Key the token is bound to, when sender-constrained
+    ///
+    /// # Errors
+    ///
+    /// * `AppError::Forbidden` - The account has no enabled access to the
+    ///   environment, a requested scope is not granted, or the environment has
+    ///   no server key
+    /// * `AppError::Validation` - `resource` is needed to pick the environment
+    #[tracing::instrument(skip_all, name = "TokenService::issue")]
+    pub async fn issue(
+        &self,
+        account: &ServiceAccount,
+        resource: Option<Uuid>,
+        scope: Option<&str>,
+        confirmation: Option<Confirmation>,
+    ) -> Result<TokenResponse, AppError> {
+        let access = self.project_access(account, resource).await;
+        let environment = access
+            .as_ref()
+            .map(|access| access.environment_id.to_string())
+            .unwrap_or_else(|_| UNKNOWN_ENVIRONMENT.to_string());
+        let result = match access {
+            Ok(access) => self.sign(account, &access, scope, confirmation).await,
+            Err(e) => Err(e),
+        };
+        metrics().record_token(TokenOperation::Issuance, &environment, Outcome::of(&result));
+        result
+    }
+
+    /// The enabled project access of `account` to `resource`, or to its only
+    /// environment when no resource is given.
+    #[tracing::instrument(skip_all, name = "TokenService::project_access")]
+    async fn project_access(
+        &self,
+        account: &ServiceAccount,
+        resource: Option<Uuid>,
+    ) -> Result<ProjectAccess, AppError> {
+        let mut accesses = self
+            .project_access_repository
+            .find(
+                ProjectAccessFilter {
+                    service_account_id: account.id,
+                    environment_id: resource,
+                    is_enabled: Some(true),
+                    ..Default::default()
+                },
+                None,
+                None,
+            )
+            .await?;
+        match accesses.len() {
+            0 => Err(AppError::Forbidden(
+                "Service account has no access to the requested environment".to_string(),
+            )),
+            1 => Ok(accesses.remove(0)),
+            _ if resource.is_none() => Err(AppError::Validation(
+                "`resource` must name the environment: the service account has access to several"
+                    .to_string(),
+            )),
+            _ => Err(AppError::Conflict(
+                "Service account has several accesses to the requested environment".to_string(),
+            )),
+        }
+    }
+
+    async fn sign(
+        &self,
+        account: &ServiceAccount,
+        access: &ProjectAccess,
+        scope: Option<&str>,
+        confirmation: Option<Confirmation>,
+    ) -> Result<TokenResponse, AppError> {
+        let scopes = self.scopes(access, scope).await?;
+        let (private_key, algorithm) = self.signing_key(access.environment_id).await?;
+        let subject = account
+            .id
+            .ok_or_else(|| anyhow::anyhow!("Service account has no id"))?;
+        let ttl = self.config.default_ttl_seconds;
+
+        let mut claims = Claims::new(subject.to_string(), ttl as i64)
+            .with_audience(vec![access.environment_id.to_string()])
+            .with_jti(Uuid::new().to_string())
+            .with_scopes(scopes.clone());
+        let token_type = match &confirmation {
+            Some(Confirmation { jkt: Some(_), .. }) => DPOP_TOKEN_TYPE,
+            _ => "Bearer",
+        };
+        if self.config.include_labels {
+            claims = self.with_labels(claims, account, access).await?;
+        }
+        claims.cnf = confirmation;
+        let access_token = KeyBuilder::new().create_jwt(&claims, &private_key, algorithm)?;
+
+        Ok(TokenResponse {
+            access_token,
+            token_type: token_type.to_string(),
+            expires_in: ttl,
+            scope: (!scopes.is_empty()).then(|| scopes.join(" ")),
+        })
+    }
+
+    /// Copies the labels of the account, the access's environment and its
+    /// project into the `meta` claim; on a shared key the account's label
+    /// wins over the environment's, which wins over the project's.
+    async fn with_labels(
+        &self,
+        claims: Claims,
+        account: &ServiceAccount,
+        access: &ProjectAccess,
+    ) -> Result<Claims, AppError> {
+        let mut claims = claims.with_labels(&account.labels);
+        if let Some(environment) = self
+            .environment_repository
+            .read(access.environment_id)
+            .await?
+        {
+            claims = claims.with_labels(&environment.labels);
+            if let Some(project) = self.project_repository.read(environment.project_id).await? {
+                claims = claims.with_labels(&project.labels);
+            }
+        }
+        Ok(claims)
+    }
+
+    /// Names of the enabled scopes the access grants, narrowed to `requested`.
+    async fn scopes(
+        &self,
+        access: &ProjectAccess,
+        requested: Option<&str>,
+    ) -> Result<Vec<String>, AppError> {
+        let mut granted = Vec::new();
+        for id in &access.project_scopes {
+            if let Some(scope) = self.project_scope_repository.read(*id).await?
+                && scope.enabled
+            {
+                granted.push(scope.name);
+            }
+        }
+        let Some(requested) = requested else {
+            return Ok(granted);
+        };
+
+        let mut scopes = Vec::new();
+        for name in requested.split_whitespace() {
+            if !granted.iter().any(|granted| granted == name) {
+                return Err(AppError::Forbidden(format!(
+                    "Scope `{}` is not granted to the service account",
+                    name
+                )));
+            }
+            if !scopes.iter().any(|scope| scope == name) {
+                scopes.push(name.to_string());
+            }
+        }
+        Ok(scopes)
+    }
+
+    /// Private key and algorithm of the environment's newest server key.
+    async fn signing_key(&self, environment_id: Uuid) -> Result<(Vec<u8>, Algorithm), AppError> {
+        self.server_keys(environment_id)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                AppError::Forbidden("Environment has no server key to sign tokens with".to_string())
+            })
+    }
+
+    /// Private keys and algorithms of the environment's server keys, newest first.
+    async fn server_keys(
+        &self,
+        environment_id: Uuid,
+    ) -> Result<Vec<(Vec<u8>, Algorithm)>, AppError> {
+        let mut server_keys = self
+            .server_key_repository
+            .find(
+                ServerKeyFilter {
+                    environment_id: Some(environment_id),
+                    ..Default::default()
+                },
+                None,
+                None,
+            )
+            .await?;
+        server_keys.sort_by_key(|server_key| std::cmp::Reverse(server_key.created_at));
+
+        let secrets_manager = SecretsManager::new(true)?;
+        server_keys
+            .into_iter()
+            .map(|server_key| {
+                let encoded = secrets_manager.decrypt(&server_key.key, &environment_id)?;
+                let private_key = STANDARD.decode(encoded).map_err(anyhow::Error::from)?;
+                Ok((private_key, server_key.algorithm))
+            })
+            .collect()
+    }
+
+    /// Checks a DPoP proof (RFC 9449) against the request it came with, and
+    /// records it so it cannot be used again.
+    ///
+    /// # Returns
+    ///
+    /// The JWK thumbprint of the key the proof was signed with.
+    ///
+    /// # Errors
+    ///
+    /// * `AppError::Unauthorized` - The proof is invalid, not fresh, was
+    ///   created for another request, or has already been used
+    #[tracing::instrument(skip_all, name = "TokenService::check_dpop_proof")]
+    pub async fn check_dpop_proof(
+        &self,
+        proof: &str,
+        request: &DpopRequest<'_>,
+    ) -> Result<String, AppError> {
+        let max_age = self.config.dpop_max_age_seconds;
+        let proof = verify_proof(proof, request, Utc::now().timestamp(), max_age)?;
+
+        // Proofs are only accepted until `max_age` after they were created
+        let fresh_until = DateTime::from_timestamp(
+            proof
+                .claims
+                .iat
+                .saturating_add(max_age.min(i64::MAX as u64) as i64),
+            0,
+        )
+        .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let used = UsedDpopProof::new(&proof.jkt, &proof.claims.jti, fresh_until);
+        if !self.dpop_proof_repository.record(&used).await? {
+            tracing::warn!(jkt = %proof.jkt, "DPoP proof replayed");
+            return Err(AppError::Unauthorized(
+                "DPoP proof has already been used".to_string(),
+            ));
+        }
+        Ok(proof.jkt)
+    }
+
+    /// Verifies a presented token and returns its claims.
+    ///
+    /// The token must be signed by one of the server keys of the environment
+    /// in its audience, unexpired, and issued to a service account that is
+    /// still enabled. A token bound to a client certificate (RFC 8705) is only
+    /// accepted from the presenter of that certificate, and one bound to a
+    /// DPoP key (RFC 9449) only with a fresh proof signed by that key.
+    ///
+    /// # Errors
+    ///
+    /// * `AppError::Unauthorized` - The token is invalid, expired, its account
+    ///   is disabled or deleted, or the presenter did not prove possession of
+    ///   the key the token is bound to
+    #[tracing::instrument(skip_all, name = "TokenService::verify")]
+    pub async fn verify(&self, token: &str, proof: &ProofOfPossession) -> Result<Claims, AppError> {
+        let environment_id = unverified_environment(token)?;
+        let algorithm = jsonwebtoken::decode_header(token)
+            .map_err(|_| invalid_token())?
+            .alg;
+        let mut validation = Validation::new(algorithm);
+        validation.set_audience(&[environment_id.to_string()]);
+
+        let key_builder = KeyBuilder::new();
+        let claims = self
+            .server_keys(environment_id)
+            .await?
+            .into_iter()
+            .filter(|(_, key_algorithm)| *key_algorithm == algorithm)
+            .find_map(|(key, _)| {
+                key_builder
+                    .verify_jwt::<Claims>(token, &key, &validation)
+                    .ok()
+            })
+            .ok_or_else(invalid_token)?;
+
+        let subject = Uuid::parse_str(&claims.sub).map_err(|_| invalid_token())?;
+        match self.service_account_repository.read(subject).await? {
+            Some(account) if account.enabled => {}
+            _ => return Err(invalid_token()),
+        }
+        let key_thumbprint = match &proof.dpop {
+            Some(dpop) => {
+                let request = DpopRequest {
+                    method: &dpop.method,
+                    uri: &dpop.uri,
+                    access_token: Some(token),
+                };
+                Some(self.check_dpop_proof(&dpop.proof, &request).await?)
+            }
+            None => None,
+        };
+        check_confirmation(
+            claims.cnf.as_ref(),
+            proof.certificate_thumbprint.as_deref(),
+            key_thumbprint.as_deref(),
+        )?;
+        Ok(claims)
+    }
+
+    /// Token introspection (RFC 7662): the token's claims when it is active
+    /// for the presenter, or just `active: false`.
+    #[tracing::instrument(skip_all, name = "TokenService::introspect")]
+    pub async fn introspect(
+        &self,
+        token: &str,
+        proof: &ProofOfPossession,
+    ) -> Result<IntrospectionResponse, AppError> {
+        let environment = unverified_environment(token)
+            .map(|environment_id| environment_id.to_string())
+            .unwrap_or_else(|_| UNKNOWN_ENVIRONMENT.to_string());
+        let result = match self.verify(token, proof).await {
+            Ok(claims) => Ok(IntrospectionResponse::from(claims)),
+            Err(AppError::Unauthorized(reason)) => {
+                tracing::info!(%reason, "Introspected token is not active");
+                Ok(IntrospectionResponse::inactive())
+            }
+            Err(e) => Err(e),
+        };
+        let outcome = match &result {
+            Ok(response) if !response.active => Outcome::Refused,
+            _ => Outcome::of(&result),
+        };
+        metrics().record_token(TokenOperation::Introspection, &environment, outcome);
+        result
+    }
+}
+
+fn invalid_token() -> AppError {
+    AppError::Unauthorized("Invalid access token".to_string())
+}
+
+/// Environment in the audience of a token, read before its signature is
+/// checked so the environment's server keys can be looked up.
+fn unverified_environment(token: &str) -> Result<Uuid, AppError> {
+    let mut validation = Validation::default();
+    validation.insecure_disable_signature_validation();
+    validation.validate_aud = false;
+    validation.validate_exp = false;
+    let claims = jsonwebtoken::decode::<Claims>(
+        token,
+        &jsonwebtoken::DecodingKey::from_secret(&[]),
+        &validation,
+    )
+    .map_err(|_| invalid_token())?
+    .claims;
+    claims
+        .aud
+        .and_then(|audience| audience.into_iter().next())
+        .and_then(|audience| Uuid::parse_str(&audience).ok())
+        .ok_or_else(invalid_token)
+}
+
+/// Refuses a sender-constrained token whose presenter did not prove
+/// possession of the key it is bound to.
+fn check_confirmation(
+    confirmation: Option<&Confirmation>,
+    certificate_thumbprint: Option<&str>,
+    key_thumbprint: Option<&str>,
+) -> Result<(), AppError> {
+    let Some(confirmation) = confirmation else {
+        return Ok(());
+    };
+    if !thumbprint_matches(confirmation.x5t_s256.as_deref(), certificate_thumbprint) {
+        return Err(AppError::Unauthorized(
+            "Access token is bound to a client certificate that was not presented".to_string(),
+        ));
+    }
+    if !thumbprint_matches(confirmation.jkt.as_deref(), key_thumbprint) {
+        return Err(AppError::Unauthorized(
+            "Access token is bound to a DPoP key it was not presented with a proof of".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the presented thumbprint is the expected one, when one is expected.
+fn thumbprint_matches(expected: Option<&str>, presented: Option<&str>) -> bool {
+    let Some(expected) = expected else {
+        return true;
+    };
+    let presented = presented.unwrap_or_default();
+    expected.len() == presented.len()
+        && openssl::memcmp::eq(expected.as_bytes(), presented.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::environment::Environment;
+    use crate::models::label::Labels;
+    use crate::models::project::Project;
+    use crate::models::project_scope::ProjectScope;
+    use crate::models::server_key::ServerKeyCreatePayload;
+    use crate::services::server_key_service::ServerKeyService;
+    use crate::test_utils::{Backend, TestStore, dpop_key, dpop_proof};
+    use crate::utils::tokens::dpop::jwk_thumbprint;
+    use anyhow::Error;
+    use jsonwebtoken::{DecodingKey, decode};
+    use rstest::rstest;
+
+    /// Creates an account with access to a new environment, granted the
+    /// `read` and `write` scopes, and a server key for that environment.
+    async fn setup(backend: Backend) -> (TokenService, ServiceAccount, Uuid, TestStore) {
+        let store = TestStore::setup("token_service", backend).await.unwrap();
+        let environment_id = Uuid::new();
+        let account = store
+            .repositories
+            .service_accounts
+            .create(ServiceAccount::new(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "secret123".to_string(),
+            ))
+            .await
+            .unwrap();
+
+        let scopes = &store.repositories.project_scopes;
+        let mut project_scopes = Vec::new();
+        for name in ["read", "write"] {
+            let scope = scopes
+                .create(ProjectScope {
+                    id: None,
+                    project_id: Uuid::new(),
+                    name: name.to_string(),
+                    description: String::new(),
+                    enabled: true,
+                    labels: Default::default(),
+                    created_at: None,
+                    updated_at: None,
+                    deleted_at: None,
+                    deleted_by: None,
+                })
+                .await
+                .unwrap();
+            project_scopes.push(scope.id.unwrap());
+        }
+        store
+            .repositories
+            .project_access
+            .create(ProjectAccess {
+                id: None,
+                name: "reader".to_string(),
+                environment_id,
+                service_account_id: account.id,
+                project_scopes,
+                enabled: true,
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+                labels: Default::default(),
+            })
+            .await
+            .unwrap();
+        ServerKeyService::with_repositories(&store.repositories)
+            .unwrap()
+            .create(ServerKeyCreatePayload {
+                environment_id,
+                algorithm: Algorithm::HS256,
+            })
+            .await
+            .unwrap();
+
+        let service =
+            TokenService::with_repositories(&store.repositories, TokenConfig::default()).unwrap();
+        (service, account, environment_id, store)
+    }
+
+    fn claims(token: &str) -> Claims {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.insecure_disable_signature_validation();
+        validation.validate_aud = false;
+        decode::<Claims>(token, &DecodingKey::from_secret(&[]), &validation)
+            .unwrap()
+            .claims
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_issue_token(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, account, environment_id, store) = setup(backend).await;
+
+        let response = service.issue(&account, None, None, None).await?;
+        assert_eq!(response.token_type, "Bearer");
+        assert_eq!(
+            response.expires_in,
+            TokenConfig::default().default_ttl_seconds
+        );
+        assert_eq!(response.scope.as_deref(), Some("read write"));
+
+        let claims = claims(&response.access_token);
+        assert_eq!(claims.sub, account.id.unwrap().to_string());
+        assert_eq!(claims.aud, Some(vec![environment_id.to_string()]));
+        assert_eq!(
+            claims.scopes,
+            Some(vec!["read".to_string(), "write".to_string()])
+        );
+        assert!(claims.jti.is_some());
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_issue_token_narrows_scopes(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, account, environment_id, store) = setup(backend).await;
+
+        let response = service
+            .issue(&account, Some(environment_id), Some("read"), None)
+            .await?;
+        assert_eq!(response.scope.as_deref(), Some("read"));
+
+        let result = service
+            .issue(&account, None, Some("read admin"), None)
+            .await;
+        assert!(matches!(result, Err(AppError::Forbidden(_))));
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_issue_token_without_access(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, account, _, store) = setup(backend).await;
+
+        let result = service.issue(&account, Some(Uuid::new()), None, None).await;
+        assert!(matches!(result, Err(AppError::Forbidden(_))));
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_issue_token_with_labels(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, mut account, environment_id, store) = setup(backend).await;
+        let labels = |pairs: &[(&str, &str)]| -> Labels {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+        let project = store
+            .repositories
+            .projects
+            .create(Project {
+                id: None,
+                name: "billing".to_string(),
+                description: String::new(),
+                enabled: true,
+                labels: labels(&[("team", "payments"), ("tier", "gold")]),
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+            })
+            .await?;
+        store
+            .repositories
+            .environments
+            .create(Environment {
+                id: Some(environment_id),
+                project_id: project.id.unwrap(),
+                name: "prod".to_string(),
+                description: String::new(),
+                enabled: true,
+                labels: labels(&[("stage", "prod"), ("tier", "platinum")]),
+                created_at: None,
+                updated_at: None,
+                deleted_at: None,
+                deleted_by: None,
+            })
+            .await?;
+        account.labels = labels(&[("owner", "ci"), ("stage", "any")]);
+
+        // Labels are left out unless enabled
+        let response = service.issue(&account, None, None, None).await?;
+        assert!(claims(&response.access_token).meta.is_none());
+
+        let service = TokenService::with_repositories(
+            &store.repositories,
+            TokenConfig {
+                include_labels: true,
+                ..Default::default()
+            },
+        )?;
+        let response = service.issue(&account, None, None, None).await?;
+        assert_eq!(
+            claims(&response.access_token).meta,
+            Some(labels(&[
+                ("owner", "ci"),
+                ("stage", "any"),
+                ("tier", "platinum"),
+                ("team", "payments"),
+            ]))
+        );
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_confirmation() {
+        let certificate_bound = Confirmation {
+            x5t_s256: Some("thumbprint".to_string()),
+            ..Default::default()
+        };
+        let key_bound = Confirmation {
+            jkt: Some("jkt".to_string()),
+            ..Default::default()
+        };
+
+        assert!(check_confirmation(None, None, None).is_ok());
+        assert!(check_confirmation(Some(&certificate_bound), Some("thumbprint"), None).is_ok());
+        assert!(check_confirmation(Some(&key_bound), None, Some("jkt")).is_ok());
+        for (confirmation, certificate, key) in [
+            (&certificate_bound, None, None),
+            (&certificate_bound, Some("other"), None),
+            (&certificate_bound, None, Some("thumbprint")),
+            (&key_bound, None, None),
+            (&key_bound, None, Some("other")),
+            (&key_bound, Some("jkt"), None),
+        ] {
+            assert!(matches!(
+                check_confirmation(Some(confirmation), certificate, key),
+                Err(AppError::Unauthorized(_))
+            ));
+        }
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_verify_certificate_bound_token(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, account, _, store) = setup(backend).await;
+        let confirmation = Confirmation {
+            x5t_s256: Some("thumbprint".to_string()),
+            ..Default::default()
+        };
+        let response = service
+            .issue(&account, None, None, Some(confirmation.clone()))
+            .await?;
+        assert_eq!(claims(&response.access_token).cnf, Some(confirmation));
+
+        let proof = ProofOfPossession {
+            certificate_thumbprint: Some("thumbprint".to_string()),
+            ..Default::default()
+        };
+        let verified = service.verify(&response.access_token, &proof).await?;
+        assert_eq!(verified.sub, account.id.unwrap().to_string());
+        let introspection = service.introspect(&response.access_token, &proof).await?;
+        assert!(introspection.active);
+
+        // Presented without the certificate, or with another one
+        let result = service
+            .verify(&response.access_token, &ProofOfPossession::default())
+            .await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        let other = ProofOfPossession {
+            certificate_thumbprint: Some("other".to_string()),
+            ..Default::default()
+        };
+        let introspection = service.introspect(&response.access_token, &other).await?;
+        assert!(!introspection.active);
+        assert!(introspection.sub.is_none());
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_verify_refuses_invalid_tokens(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, account, _, store) = setup(backend).await;
+        let response = service.issue(&account, None, None, None).await?;
+        let proof = ProofOfPossession::default();
+        service.verify(&response.access_token, &proof).await?;
+
+        let mut tampered = response.access_token.clone();
+        tampered.pop();
+        for token in ["not-a-token", tampered.as_str()] {
+            let result = service.verify(token, &proof).await;
+            assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        }
+
+        store
+            .repositories
+            .service_accounts
+            .soft_delete(account.id.unwrap(), None)
+            .await?;
+        let introspection = service.introspect(&response.access_token, &proof).await?;
+        assert!(!introspection.active);
+
+        store.cleanup().await?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::mongo(Backend::Mongo)]
+    #[case::memory(Backend::Memory)]
+    #[cfg_attr(feature = "sqlite", case::sqlite(Backend::Sqlite))]
+    #[tokio::test]
+    async fn test_verify_dpop_bound_token(#[case] backend: Backend) -> Result<(), Error> {
+        let (service, account, _, store) = setup(backend).await;
+        let (key, jwk) = dpop_key();
+        let token_uri = "https://buraq.test/oauth/token";
+        let proof = dpop_proof(&key, &jwk, "POST", token_uri, None);
+        let issuance = DpopRequest {
+            method: "POST",
+            uri: token_uri,
+            access_token: None,
+        };
+        let jkt = service.check_dpop_proof(&proof, &issuance).await?;
+        assert_eq!(jkt, jwk_thumbprint(&jwk)?);
+        // The same proof cannot be used twice
+        let replayed = service.check_dpop_proof(&proof, &issuance).await;
+        assert!(matches!(replayed, Err(AppError::Unauthorized(_))));
+
+        let confirmation = Confirmation {
+            jkt: Some(jkt),
+            ..Default::default()
+        };
+        let response = service
+            .issue(&account, None, None, Some(confirmation))
+            .await?;
+        assert_eq!(response.token_type, DPOP_TOKEN_TYPE);
+        let token = response.access_token;
+
+        let verify_uri = "https://buraq.test/oauth/verify";
+        let presented = |proof: String| ProofOfPossession {
+            dpop: Some(DpopPresentation {
+                proof,
+                method: "GET".to_string(),
+                uri: verify_uri.to_string(),
+            }),
+            ..Default::default()
+        };
+        let proof = presented(dpop_proof(&key, &jwk, "GET", verify_uri, Some(&token)));
+        service.verify(&token, &proof).await?;
+        let introspection = service.introspect(&token, &proof).await?;
+        assert!(!introspection.active, "replayed proofs are refused");
+
+        let (other_key, other_jwk) = dpop_key();
+        for proof in [
+            ProofOfPossession::default(),
+            presented(dpop_proof(
+                &other_key,
+                &other_jwk,
+                "GET",
+                verify_uri,
+                Some(&token),
+            )),
+            presented(dpop_proof(&key, &jwk, "GET", verify_uri, None)),
+        ] {
+            let result = service.verify(&token, &proof).await;
+            assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        }
+
+        store.cleanup().await?;
+        Ok(())
+    }
+}
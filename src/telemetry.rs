@@ -0,0 +1,290 @@
+use crate::routes::actor;
+use actix_web::HttpMessage;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use mongodb::bson::uuid::Uuid;
+use std::time::Instant;
+use tracing::Instrument;
+use tracing::field::Empty;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Header carrying the id that correlates a request with its log lines.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Longest request id accepted from a client; longer ones are replaced.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// Id of the request being handled, stored in the request extensions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+/// Keeps trace export running; pending spans are flushed when it is dropped.
+#[must_use = "traces stop being exported when the guard is dropped"]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush traces: {}", e);
+        }
+    }
+}
+
+/// Installs the global subscriber writing one JSON object per log line.
+///
+/// The level is read from `RUST_LOG` and defaults to `info`. Records emitted
+/// through the `log` crate by dependencies are forwarded as well. When built
+/// with the `otel` feature and given an endpoint, spans are also exported over
+/// OTLP/HTTP.
+pub fn init(otlp_endpoint: Option<&str>) -> Result<TelemetryGuard, anyhow::Error> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let fmt = tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(true)
+        .with_span_list(false);
+    let subscriber = tracing_subscriber::registry().with(filter).with(fmt);
+
+    #[cfg(feature = "otel")]
+    {
+        use opentelemetry::trace::TracerProvider;
+
+        let provider = otlp_endpoint.map(otel::tracer_provider).transpose()?;
+        let layer = provider
+            .as_ref()
+            .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("buraq")));
+        subscriber.with(layer).try_init()?;
+        Ok(TelemetryGuard { provider })
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        subscriber.try_init()?;
+        if otlp_endpoint.is_some() {
+            tracing::warn!("BURAQ_OTLP_ENDPOINT is ignored: built without the `otel` feature");
+        }
+        Ok(TelemetryGuard {})
+    }
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use actix_web::http::header::HeaderMap;
+    use opentelemetry::global;
+    use opentelemetry::propagation::Extractor;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::Resource;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    /// Builds the provider exporting to `endpoint` and makes it, and the W3C
+    /// trace context propagator, the process-wide defaults.
+    pub fn tracer_provider(endpoint: &str) -> Result<SdkTracerProvider, anyhow::Error> {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name("buraq").build())
+            .build();
+        global::set_tracer_provider(provider.clone());
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        Ok(provider)
+    }
+
+    /// Reads propagation headers such as `traceparent` from a request.
+    pub struct HeaderExtractor<'a>(pub &'a HeaderMap);
+
+    impl Extractor for HeaderExtractor<'_> {
+        fn get(&self, key: &str) -> Option<&str> {
+            self.0.get(key).and_then(|value| value.to_str().ok())
+        }
+
+        fn keys(&self) -> Vec<&str> {
+            self.0.keys().map(|name| name.as_str()).collect()
+        }
+    }
+
+    /// Continues the caller's trace, when the request carries one, in `span`.
+    pub fn set_remote_parent(span: &tracing::Span, headers: &HeaderMap) {
+        let context = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(headers))
+        });
+        // Fails only when the span is disabled, in which case there is nothing to link
+        let _ = span.set_parent(context);
+    }
+}
+
+/// The client's request id when it is usable, a new one otherwise.
+fn request_id(request: &ServiceRequest) -> String {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| {
+            !value.is_empty()
+                && value.len() <= MAX_REQUEST_ID_LENGTH
+                && value.chars().all(|c| c.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new().to_string())
+}
+
+/// Middleware assigning every request an id and handling it inside a span.
+///
+/// The span carries the request id, method, route pattern and caller, plus the
+/// resource id and status once the request has been routed and answered. The
+/// id is echoed back in the [`REQUEST_ID_HEADER`] response header. With the
+/// `otel` feature, a W3C `traceparent` header makes the span a child of the
+/// caller's trace.
+pub async fn trace_requests(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let started = Instant::now();
+    let request_id = request_id(&request);
+    let span = tracing::info_span!(
+        "http_request",
+        request_id = %request_id,
+        method = %request.method(),
+        route = request.match_pattern().as_deref().unwrap_or(crate::metrics::UNMATCHED_ROUTE),
+        principal = actor(request.request()).as_deref(),
+        resource_id = Empty,
+        status = Empty,
+    );
+    #[cfg(feature = "otel")]
+    otel::set_remote_parent(&span, request.headers());
+    request
+        .extensions_mut()
+        .insert(RequestId(request_id.clone()));
+
+    let mut response = next.call(request).instrument(span.clone()).await?;
+
+    if let Some(id) = response.request().match_info().get("id") {
+        span.record("resource_id", id);
+    }
+    let status = response.status();
+    span.record("status", status.as_u16());
+    span.in_scope(|| {
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+        if status.is_server_error() {
+            tracing::error!(latency_ms, "request failed");
+        } else {
+            tracing::info!(latency_ms, "request completed");
+        }
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static("x-request-id"), value);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{App, HttpRequest, HttpResponse, web};
+
+    async fn echo(request: HttpRequest) -> HttpResponse {
+        let id = request.extensions().get::<RequestId>().cloned().unwrap();
+        HttpResponse::Ok().body(id.0)
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_is_generated_and_echoed() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(from_fn(trace_requests))
+                .route("/items/{id}", web::get().to(echo)),
+        )
+        .await;
+
+        let resp = actix_web::test::TestRequest::get()
+            .uri("/items/42")
+            .send_request(&app)
+            .await;
+        let header = resp.headers().get(REQUEST_ID_HEADER).unwrap().clone();
+        assert!(Uuid::parse_str(header.to_str().unwrap()).is_ok());
+        let body = actix_web::test::read_body(resp).await;
+        assert_eq!(body, header.as_bytes());
+    }
+
+    #[actix_web::test]
+    async fn test_client_request_id_is_kept_when_valid() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(from_fn(trace_requests))
+                .route("/items/{id}", web::get().to(echo)),
+        )
+        .await;
+
+        let resp = actix_web::test::TestRequest::get()
+            .uri("/items/42")
+            .insert_header((REQUEST_ID_HEADER, "abc-123"))
+            .send_request(&app)
+            .await;
+        assert_eq!(resp.headers().get(REQUEST_ID_HEADER).unwrap(), "abc-123");
+
+        let resp = actix_web::test::TestRequest::get()
+            .uri("/items/42")
+            .insert_header((REQUEST_ID_HEADER, "x".repeat(MAX_REQUEST_ID_LENGTH + 1)))
+            .send_request(&app)
+            .await;
+        let header = resp.headers().get(REQUEST_ID_HEADER).unwrap();
+        assert!(Uuid::parse_str(header.to_str().unwrap()).is_ok());
+    }
+
+    #[cfg(feature = "otel")]
+    #[actix_web::test]
+    async fn test_traceparent_becomes_span_parent() {
+        use opentelemetry::trace::{TraceContextExt, TraceId, TracerProvider};
+        use opentelemetry_sdk::propagation::TraceContextPropagator;
+        use opentelemetry_sdk::trace::SdkTracerProvider;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+        use tracing_subscriber::Registry;
+
+        async fn trace_id(_: HttpRequest) -> HttpResponse {
+            let context = tracing::Span::current().context();
+            HttpResponse::Ok().body(context.span().span_context().trace_id().to_string())
+        }
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(from_fn(trace_requests))
+                .route("/trace", web::get().to(trace_id)),
+        )
+        .await;
+        let resp = actix_web::test::TestRequest::get()
+            .uri("/trace")
+            .insert_header((
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ))
+            .send_request(&app)
+            .await;
+
+        let body = actix_web::test::read_body(resp).await;
+        let expected = TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap();
+        assert_eq!(body, expected.to_string().as_bytes());
+    }
+}
@@ -1,8 +1,12 @@
 use crate::config::AppConfig;
+use crate::repositories::registry::Repositories;
+#[cfg(feature = "sqlite")]
+use crate::repositories::sqlite::SqliteDatabase;
 use crate::utils::database::create_database_client;
 use anyhow::Result;
 use dotenvy::dotenv;
 use mongodb::Database;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 static DB_COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -54,6 +58,262 @@ pub async fn cleanup_test_db(db: Database) -> Result<()> {
     Ok(())
 }
 
+/// Storage backend a test runs against.
+///
+/// Service tests take one as an `rstest` case, so they run over every
+/// backend the crate was built with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Mongo,
+    Memory,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+/// Repositories over a fresh, empty database of one [`Backend`].
+pub struct TestStore {
+    pub repositories: Repositories,
+    database: Option<Database>,
+}
+
+impl TestStore {
+    /// Sets up the store: a uniquely named MongoDB database as with
+    /// [`setup_test_db`], in-memory repositories, or a migrated in-memory
+    /// SQLite database.
+    pub async fn setup(prefix: &str, backend: Backend) -> Result<Self> {
+        dotenv().ok();
+        match backend {
+            Backend::Mongo => {
+                let database = setup_test_db(prefix).await?;
+                Ok(Self {
+                    repositories: Repositories::mongo(Arc::new(database.clone()))?,
+                    database: Some(database),
+                })
+            }
+            Backend::Memory => Ok(Self {
+                repositories: Repositories::in_memory(),
+                database: None,
+            }),
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite => {
+                let database = SqliteDatabase::open_in_memory()?;
+                database.migrate().await?;
+                Ok(Self {
+                    repositories: Repositories::sqlite(&database),
+                    database: None,
+                })
+            }
+        }
+    }
+
+    /// Drops the MongoDB database; in-memory records are gone once the
+    /// last repository is dropped.
+    pub async fn cleanup(self) -> Result<()> {
+        if let Some(database) = self.database {
+            cleanup_test_db(database).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Generates a self-signed EC P-256 certificate for `common_name`.
+///
+/// # Returns
+///
+/// The certificate and its PKCS#8 private key, both PEM encoded.
+pub fn self_signed_certificate(common_name: &str) -> (Vec<u8>, Vec<u8>) {
+    let key = ec_key();
+    let name = x509_name(&[("CN", common_name)]);
+    let mut builder = certificate_builder(&name, &name, &key);
+    builder
+        .sign(&key, openssl::hash::MessageDigest::sha256())
+        .unwrap();
+
+    (
+        builder.build().to_pem().unwrap(),
+        key.private_key_to_pem_pkcs8().unwrap(),
+    )
+}
+
+/// Generates a self-signed EC P-256 certificate authority for `common_name`.
+///
+/// # Returns
+///
+/// The CA certificate and its private key.
+pub fn certificate_authority(
+    common_name: &str,
+) -> (
+    openssl::x509::X509,
+    openssl::pkey::PKey<openssl::pkey::Private>,
+) {
+    use openssl::x509::extension::{BasicConstraints, KeyUsage};
+
+    let key = ec_key();
+    let name = x509_name(&[("CN", common_name)]);
+    let mut builder = certificate_builder(&name, &name, &key);
+    builder
+        .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+        .unwrap();
+    builder
+        .append_extension(
+            KeyUsage::new()
+                .critical()
+                .key_cert_sign()
+                .crl_sign()
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+    builder
+        .sign(&key, openssl::hash::MessageDigest::sha256())
+        .unwrap();
+    (builder.build(), key)
+}
+
+/// Generates an EC P-256 TLS client certificate issued by `ca`.
+///
+/// The subject is `CN=<common_name>,O=Buraq Tests`. Subject alternative names
+/// are given in OpenSSL's `type:value` form, e.g. `DNS:billing.internal`,
+/// `URI:spiffe://acme/billing`, `IP:10.0.0.7` or `email:billing@acme.test`.
+///
+/// # Returns
+///
+/// The client certificate and its private key.
+pub fn client_certificate(
+    ca: &openssl::x509::X509,
+    ca_key: &openssl::pkey::PKey<openssl::pkey::Private>,
+    common_name: &str,
+    subject_alt_names: &[&str],
+) -> (
+    openssl::x509::X509,
+    openssl::pkey::PKey<openssl::pkey::Private>,
+) {
+    use openssl::x509::extension::{ExtendedKeyUsage, SubjectAlternativeName};
+
+    let key = ec_key();
+    let name = x509_name(&[("O", "Buraq Tests"), ("CN", common_name)]);
+    let mut builder = certificate_builder(&name, ca.subject_name(), &key);
+    builder
+        .append_extension(ExtendedKeyUsage::new().client_auth().build().unwrap())
+        .unwrap();
+    if !subject_alt_names.is_empty() {
+        let mut san = SubjectAlternativeName::new();
+        for name in subject_alt_names {
+            match name.split_once(':').unwrap() {
+                ("DNS", value) => san.dns(value),
+                ("URI", value) => san.uri(value),
+                ("IP", value) => san.ip(value),
+                ("email", value) => san.email(value),
+                (kind, _) => panic!("Unsupported subject alternative name type {}", kind),
+            };
+        }
+        let extension = san.build(&builder.x509v3_context(Some(ca), None)).unwrap();
+        builder.append_extension(extension).unwrap();
+    }
+    builder
+        .sign(ca_key, openssl::hash::MessageDigest::sha256())
+        .unwrap();
+    (builder.build(), key)
+}
+
+fn ec_key() -> openssl::pkey::PKey<openssl::pkey::Private> {
+    use openssl::ec::{EcGroup, EcKey};
+
+    let group = EcGroup::from_curve_name(openssl::nid::Nid::X9_62_PRIME256V1).unwrap();
+    openssl::pkey::PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+fn x509_name(entries: &[(&str, &str)]) -> openssl::x509::X509Name {
+    let mut name = openssl::x509::X509NameBuilder::new().unwrap();
+    for (field, value) in entries {
+        name.append_entry_by_text(field, value).unwrap();
+    }
+    name.build()
+}
+
+/// Starts a v3 certificate valid from now until tomorrow, with a random serial.
+fn certificate_builder(
+    subject: &openssl::x509::X509NameRef,
+    issuer: &openssl::x509::X509NameRef,
+    key: &openssl::pkey::PKey<openssl::pkey::Private>,
+) -> openssl::x509::X509Builder {
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::{BigNum, MsbOption};
+
+    let mut serial = BigNum::new().unwrap();
+    serial.rand(64, MsbOption::MAYBE_ZERO, false).unwrap();
+
+    let mut builder = openssl::x509::X509::builder().unwrap();
+    builder.set_version(2).unwrap();
+    builder
+        .set_serial_number(&serial.to_asn1_integer().unwrap())
+        .unwrap();
+    builder.set_subject_name(subject).unwrap();
+    builder.set_issuer_name(issuer).unwrap();
+    builder.set_pubkey(key).unwrap();
+    builder
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    builder
+}
+
+/// Generates an EC P-256 key for signing DPoP proofs.
+///
+/// # Returns
+///
+/// The signing key and its public key as a JWK.
+pub fn dpop_key() -> (jsonwebtoken::EncodingKey, jsonwebtoken::jwk::Jwk) {
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+    let key = ec_key();
+    let ec_key = key.ec_key().unwrap();
+    let mut x = openssl::bn::BigNum::new().unwrap();
+    let mut y = openssl::bn::BigNum::new().unwrap();
+    let mut context = openssl::bn::BigNumContext::new().unwrap();
+    ec_key
+        .public_key()
+        .affine_coordinates(ec_key.group(), &mut x, &mut y, &mut context)
+        .unwrap();
+    let jwk = serde_json::from_value(serde_json::json!({
+        "kty": "EC",
+        "crv": "P-256",
+        "x": URL_SAFE_NO_PAD.encode(x.to_vec_padded(32).unwrap()),
+        "y": URL_SAFE_NO_PAD.encode(y.to_vec_padded(32).unwrap()),
+    }))
+    .unwrap();
+    let encoding_key =
+        jsonwebtoken::EncodingKey::from_ec_pem(&key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+    (encoding_key, jwk)
+}
+
+/// Signs a fresh DPoP proof for a `method` request to `uri`, bound to
+/// `access_token` when one is presented with it.
+pub fn dpop_proof(
+    key: &jsonwebtoken::EncodingKey,
+    jwk: &jsonwebtoken::jwk::Jwk,
+    method: &str,
+    uri: &str,
+    access_token: Option<&str>,
+) -> String {
+    use crate::utils::tokens::dpop::{DPOP_JWT_TYPE, DpopClaims, access_token_hash};
+
+    let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::ES256);
+    header.typ = Some(DPOP_JWT_TYPE.to_string());
+    header.jwk = Some(jwk.clone());
+    let claims = DpopClaims {
+        jti: mongodb::bson::uuid::Uuid::new().to_string(),
+        htm: method.to_string(),
+        htu: uri.to_string(),
+        iat: chrono::Utc::now().timestamp(),
+        ath: access_token.map(access_token_hash),
+    };
+    jsonwebtoken::encode(&header, &claims, key).unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
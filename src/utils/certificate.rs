@@ -0,0 +1,200 @@
+use crate::errors::AppError;
+use crate::models::certificate_binding::{CertificateBinding, decode_thumbprint, parse_dn};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use openssl::hash::{MessageDigest, hash};
+use openssl::x509::X509;
+use std::net::IpAddr;
+
+/// Certificate a client presented, and the TLS listener verified, during the
+/// handshake. Stored in the connection data of requests served over mTLS.
+#[derive(Clone)]
+pub struct ClientCertificate {
+    der: Vec<u8>,
+    certificate: X509,
+}
+
+impl std::fmt::Debug for ClientCertificate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientCertificate")
+            .field("subject", &self.subject_dn())
+            .field("thumbprint", &self.thumbprint())
+            .finish()
+    }
+}
+
+impl ClientCertificate {
+    /// Parses a DER encoded certificate.
+    pub fn from_der(der: &[u8]) -> Result<Self, AppError> {
+        let certificate = X509::from_der(der)
+            .map_err(|e| AppError::Unauthorized(format!("Invalid client certificate: {}", e)))?;
+        Ok(Self {
+            der: der.to_vec(),
+            certificate,
+        })
+    }
+
+    /// SHA-256 digest of the DER certificate as unpadded base64url, the form
+    /// RFC 8705 uses for the `x5t#S256` confirmation method.
+    pub fn thumbprint(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.digest())
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        hash(MessageDigest::sha256(), &self.der)
+            .map(|digest| digest.to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Subject as `(attribute, value)` pairs in RFC 4514 order, most specific
+    /// first.
+    fn subject(&self) -> Vec<(String, String)> {
+        let mut subject: Vec<(String, String)> = self
+            .certificate
+            .subject_name()
+            .entries()
+            .map(|entry| {
+                let nid = entry.object().nid();
+                let attribute = nid
+                    .short_name()
+                    .map(str::to_string)
+                    .unwrap_or_else(|_| entry.object().to_string());
+                let value = entry
+                    .data()
+                    .as_utf8()
+                    .map(|value| value.to_string())
+                    .unwrap_or_default();
+                (attribute, value)
+            })
+            .collect();
+        subject.reverse();
+        subject
+    }
+
+    /// Subject distinguished name in RFC 4514 form.
+    pub fn subject_dn(&self) -> String {
+        self.subject()
+            .iter()
+            .map(|(attribute, value)| format!("{}={}", attribute, value.replace(',', "\\,")))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Whether the certificate satisfies the binding.
+    pub fn satisfies(&self, binding: &CertificateBinding) -> bool {
+        let names = self.certificate.subject_alt_names();
+        let names = names.iter().flatten();
+        match binding {
+            CertificateBinding::SubjectDn(dn) => {
+                // Compared the way `parse_dn` normalises the bound name
+                let subject: Vec<(String, String)> = self
+                    .subject()
+                    .into_iter()
+                    .map(|(attribute, value)| (attribute.to_uppercase(), value.to_lowercase()))
+                    .collect();
+                parse_dn(dn) == Some(subject)
+            }
+            CertificateBinding::SanDns(expected) => names
+                .filter_map(|name| name.dnsname())
+                .any(|name| name.eq_ignore_ascii_case(expected)),
+            CertificateBinding::SanUri(expected) => names
+                .filter_map(|name| name.uri())
+                .any(|name| name == expected),
+            CertificateBinding::SanEmail(expected) => names
+                .filter_map(|name| name.email())
+                .any(|name| name.eq_ignore_ascii_case(expected)),
+            CertificateBinding::SanIp(expected) => {
+                let Ok(expected) = expected.parse::<IpAddr>() else {
+                    return false;
+                };
+                names
+                    .filter_map(|name| name.ipaddress())
+                    .filter_map(ip_address)
+                    .any(|ip| ip == expected)
+            }
+            CertificateBinding::Sha256Thumbprint(thumbprint) => decode_thumbprint(thumbprint)
+                .is_some_and(|expected| openssl::memcmp::eq(&expected, &self.digest())),
+        }
+    }
+}
+
+fn ip_address(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => <[u8; 4]>::try_from(bytes).ok().map(IpAddr::from),
+        16 => <[u8; 16]>::try_from(bytes).ok().map(IpAddr::from),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{certificate_authority, client_certificate};
+
+    fn certificate() -> ClientCertificate {
+        let (ca, ca_key) = certificate_authority("Test CA");
+        let (certificate, _) = client_certificate(
+            &ca,
+            &ca_key,
+            "billing",
+            &[
+                "DNS:billing.internal",
+                "URI:spiffe://acme/billing",
+                "IP:10.0.0.7",
+                "email:billing@acme.test",
+            ],
+        );
+        ClientCertificate::from_der(&certificate.to_der().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_subject_and_san_bindings() {
+        let certificate = certificate();
+        assert_eq!(certificate.subject_dn(), "CN=billing,O=Buraq Tests");
+
+        for binding in [
+            CertificateBinding::SubjectDn("cn=Billing, o=Buraq Tests".to_string()),
+            CertificateBinding::SanDns("BILLING.internal".to_string()),
+            CertificateBinding::SanUri("spiffe://acme/billing".to_string()),
+            CertificateBinding::SanIp("10.0.0.7".to_string()),
+            CertificateBinding::SanEmail("billing@acme.test".to_string()),
+        ] {
+            assert!(certificate.satisfies(&binding), "{:?}", binding);
+        }
+        for binding in [
+            CertificateBinding::SubjectDn("CN=billing".to_string()),
+            CertificateBinding::SanDns("other.internal".to_string()),
+            CertificateBinding::SanUri("spiffe://acme/other".to_string()),
+            CertificateBinding::SanIp("10.0.0.8".to_string()),
+            CertificateBinding::SanEmail("other@acme.test".to_string()),
+        ] {
+            assert!(!certificate.satisfies(&binding), "{:?}", binding);
+        }
+    }
+
+    #[test]
+    fn test_thumbprint_binding() {
+        let certificate = certificate();
+        let thumbprint = certificate.thumbprint();
+        assert_eq!(thumbprint.len(), 43);
+        assert!(certificate.satisfies(&CertificateBinding::Sha256Thumbprint(thumbprint)));
+
+        let hex = certificate
+            .digest()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        assert!(certificate.satisfies(&CertificateBinding::Sha256Thumbprint(hex)));
+
+        let other = self::certificate();
+        assert!(!certificate.satisfies(&CertificateBinding::Sha256Thumbprint(other.thumbprint())));
+    }
+
+    #[test]
+    fn test_invalid_der_is_refused() {
+        assert!(matches!(
+            ClientCertificate::from_der(b"not a certificate"),
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+}
@@ -1,19 +1,29 @@
 use anyhow::Error;
-use mongodb::{Client, Database};
+use mongodb::bson::Document;
+use mongodb::error::ErrorKind;
+use mongodb::options::ClientOptions;
+use mongodb::{Client, Collection, Database};
 use std::sync::Arc;
 
+use crate::metrics::mongo_event_handler;
 use crate::repositories::{
-    access_token_repository::AccessTokenRepository, environment_repository::EnvironmentRepository,
+    access_token_repository::AccessTokenRepository, dpop_proof_repository::DpopProofRepository,
+    environment_repository::EnvironmentRepository,
     project_access_repository::ProjectAccessRepository, project_repository::ProjectRepository,
     project_scope_repository::ProjectScopeRepository,
+    project_template_repository::ProjectTemplateRepository,
+    rate_limit_repository::RateLimitRepository, server_key_repository::ServerKeyRepository,
     service_account_key_repository::ServiceAccountKeyRepository,
     service_account_repository::ServiceAccountRepository,
 };
 
 pub async fn create_database_client(database_uri: &str) -> Result<Arc<Client>, anyhow::Error> {
-    let client = mongodb::Client::with_uri_str(&database_uri)
+    let mut options = ClientOptions::parse(database_uri)
         .await
         .map_err(|e| anyhow::anyhow!("Failed to create MongoDB client: {}", e))?;
+    options.command_event_handler = Some(mongo_event_handler());
+    let client = mongodb::Client::with_options(options)
+        .map_err(|e| anyhow::anyhow!("Failed to create MongoDB client: {}", e))?;
 
     Ok(Arc::new(client))
 }
@@ -39,6 +49,10 @@ pub async fn setup_database(database: Database) -> Result<(), Error> {
         .unwrap()
         .ensure_indexes()
         .await?;
+    ProjectTemplateRepository::new(database.clone())
+        .unwrap()
+        .ensure_indexes()
+        .await?;
     ServiceAccountKeyRepository::new(database.clone())
         .unwrap()
         .ensure_indexes()
@@ -47,9 +61,75 @@ pub async fn setup_database(database: Database) -> Result<(), Error> {
         .unwrap()
         .ensure_indexes()
         .await?;
+    ServerKeyRepository::new(database.clone())
+        .unwrap()
+        .ensure_indexes()
+        .await?;
+    RateLimitRepository::new(database.clone())
+        .unwrap()
+        .ensure_indexes()
+        .await?;
+    DpopProofRepository::new(database.clone())
+        .unwrap()
+        .ensure_indexes()
+        .await?;
     Ok(())
 }
 
+/// Names of the collections set up by [`setup_database`] that only have the
+/// default `_id` index, meaning their indexes have not been created yet.
+pub async fn collections_missing_indexes(database: Database) -> Result<Vec<String>, Error> {
+    let collections: Vec<Collection<Document>> = vec![
+        AccessTokenRepository::new(database.clone())?
+            .collection()?
+            .clone_with_type(),
+        EnvironmentRepository::new(database.clone())?
+            .collection()?
+            .clone_with_type(),
+        ProjectAccessRepository::new(database.clone())?
+            .collection()?
+            .clone_with_type(),
+        ProjectRepository::new(database.clone())?
+            .collection()?
+            .clone_with_type(),
+        ProjectScopeRepository::new(database.clone())?
+            .collection()?
+            .clone_with_type(),
+        ProjectTemplateRepository::new(database.clone())?
+            .collection()?
+            .clone_with_type(),
+        ServiceAccountKeyRepository::new(database.clone())?
+            .collection()?
+            .clone_with_type(),
+        ServiceAccountRepository::new(database.clone())?
+            .collection()?
+            .clone_with_type(),
+        ServerKeyRepository::new(database.clone())?
+            .collection()?
+            .clone_with_type(),
+        RateLimitRepository::new(database.clone())?
+            .collection()?
+            .clone_with_type(),
+        DpopProofRepository::new(database.clone())?
+            .collection()?
+            .clone_with_type(),
+    ];
+
+    let mut missing = Vec::new();
+    for collection in collections {
+        let indexes = match collection.list_index_names().await {
+            Ok(indexes) => indexes,
+            // The collection has not been created yet
+            Err(e) if matches!(*e.kind, ErrorKind::Command(ref c) if c.code == 26) => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        if indexes.len() <= 1 {
+            missing.push(collection.name().to_string());
+        }
+    }
+    Ok(missing)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -105,4 +185,18 @@ mod tests {
         // Cleanup
         cleanup_test_db(db).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_collections_missing_indexes() {
+        let db = setup_test_db("missing_indexes_test").await.unwrap();
+
+        let missing = collections_missing_indexes(db.clone()).await.unwrap();
+        assert_eq!(missing.len(), 11);
+
+        setup_database(db.clone()).await.unwrap();
+        let missing = collections_missing_indexes(db.clone()).await.unwrap();
+        assert!(missing.is_empty(), "{:?}", missing);
+
+        cleanup_test_db(db).await.unwrap();
+    }
 }
@@ -1,3 +1,5 @@
+pub mod certificate;
 pub mod database;
 pub mod security;
+pub mod tls;
 pub mod tokens;
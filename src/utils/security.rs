@@ -1,9 +1,12 @@
 use anyhow::{Context, Error, Result};
 use base64::Engine;
-use base64::engine::general_purpose::STANDARD;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
 use mongodb::bson::uuid::Uuid;
 use rand::{RngCore, rngs::OsRng};
 use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::OnceLock;
 
 use crate::utils::tokens::hmac::{HmacHashFunction, HmacKey};
 
@@ -12,19 +15,84 @@ use crate::utils::tokens::hmac::{HmacHashFunction, HmacKey};
 ///
 /// This ensures that each resource's data is encrypted with a unique key derived
 /// from both the master key and the resource's ID.
-#[derive(Debug)]
 pub struct SecretsManager {
     master_key: Vec<u8>,
 }
 
+/// Placeholder printed instead of secrets in `Debug` output, so they never
+/// reach the logs.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Generates a random secret for a service account: 32 bytes, Base64url
+/// encoded.
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    URL_SAFE_NO_PAD.encode(secret)
+}
+
+/// Where the master key is read from.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum MasterKeySource {
+    /// The `BURAQ_MASTER_KEY` environment variable.
+    #[default]
+    Env,
+    /// A file holding only the key, such as a mounted container secret.
+    /// Surrounding whitespace is ignored.
+    File(PathBuf),
+}
+
+static MASTER_KEY_SOURCE: OnceLock<MasterKeySource> = OnceLock::new();
+
+impl fmt::Debug for SecretsManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretsManager")
+            .field("master_key", &REDACTED)
+            .finish()
+    }
+}
+
 impl SecretsManager {
-    /// Creates a new SecretsManager instance using the master key from .env
+    /// Selects where every SecretsManager created afterwards reads the master
+    /// key from. Can only be called once, at startup; the environment is used
+    /// until then.
+    pub fn set_master_key_source(source: MasterKeySource) -> Result<(), Error> {
+        MASTER_KEY_SOURCE
+            .set(source)
+            .map_err(|_| Error::msg("Master key source is already set"))
+    }
+
+    /// Creates a new SecretsManager instance using the master key from the
+    /// configured source, the environment (or .env) by default
     pub fn new(load_dotenv: bool) -> Result<Self, Error> {
-        if load_dotenv {
-            dotenvy::dotenv().ok();
-        }
-        let master_key = env::var("BURAQ_MASTER_KEY")
-            .context("BURAQ_MASTER_KEY not found in environment variables")?;
+        let source = MASTER_KEY_SOURCE.get().cloned().unwrap_or_default();
+        Self::from_source(&source, load_dotenv)
+    }
+
+    /// Creates a new SecretsManager instance reading the master key from `source`
+    pub fn from_source(source: &MasterKeySource, load_dotenv: bool) -> Result<Self, Error> {
+        let master_key = match source {
+            MasterKeySource::Env => {
+                if load_dotenv {
+                    dotenvy::dotenv().ok();
+                }
+                env::var("BURAQ_MASTER_KEY")
+                    .context("BURAQ_MASTER_KEY not found in environment variables")?
+            }
+            MasterKeySource::File(path) => {
+                let contents = std::fs::read_to_string(path).with_context(|| {
+                    format!("Failed to read the master key from {}", path.display())
+                })?;
+                let master_key = contents.trim().to_string();
+                if master_key.is_empty() {
+                    return Err(Error::msg(format!(
+                        "Master key file {} is empty",
+                        path.display()
+                    )));
+                }
+                master_key
+            }
+        };
 
         Ok(Self {
             master_key: master_key.into_bytes(),
@@ -145,6 +213,115 @@ impl SecretsManager {
     }
 }
 
+/// Encrypts secrets under a passphrase, for material leaving this installation
+/// such as project bundles. Uses AES-256-GCM with a key derived from the
+/// passphrase by PBKDF2-HMAC-SHA256.
+pub struct PassphraseCipher {
+    key: [u8; 32],
+    salt: Vec<u8>,
+    iterations: u32,
+}
+
+impl fmt::Debug for PassphraseCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PassphraseCipher")
+            .field("key", &REDACTED)
+            .field("salt", &STANDARD.encode(&self.salt))
+            .field("iterations", &self.iterations)
+            .finish()
+    }
+}
+
+impl PassphraseCipher {
+    /// PBKDF2 iterations of new ciphers, as recommended by OWASP for
+    /// HMAC-SHA256.
+    pub const ITERATIONS: u32 = 600_000;
+
+    /// Fewest PBKDF2 iterations a bundle may have been encrypted with.
+    pub const MIN_ITERATIONS: u32 = 100_000;
+
+    /// Most PBKDF2 iterations a bundle may ask for, so one cannot tie up a
+    /// worker thread for minutes.
+    pub const MAX_ITERATIONS: u32 = 10_000_000;
+
+    /// Creates a cipher for `passphrase` with a random salt.
+    pub fn new(passphrase: &str) -> Result<Self, Error> {
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        Self::with_salt(passphrase, salt, Self::ITERATIONS)
+    }
+
+    /// Recreates the cipher `passphrase` had with `salt` and `iterations`, to
+    /// decrypt what it encrypted.
+    pub fn with_salt(passphrase: &str, salt: Vec<u8>, iterations: u32) -> Result<Self, Error> {
+        if passphrase.is_empty() {
+            return Err(Error::msg("The passphrase is empty"));
+        }
+        let mut key = [0u8; 32];
+        openssl::pkcs5::pbkdf2_hmac(
+            passphrase.as_bytes(),
+            &salt,
+            iterations as usize,
+            openssl::hash::MessageDigest::sha256(),
+            &mut key,
+        )
+        .context("Failed to derive the key from the passphrase")?;
+        Ok(Self {
+            key,
+            salt,
+            iterations,
+        })
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    /// Encrypts `text`, returning the nonce, ciphertext and tag, Base64 encoded.
+    pub fn encrypt(&self, text: &str) -> Result<String, Error> {
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+        let mut tag = [0u8; 16];
+        let encrypted = openssl::symm::encrypt_aead(
+            openssl::symm::Cipher::aes_256_gcm(),
+            &self.key,
+            Some(&nonce),
+            &[],
+            text.as_bytes(),
+            &mut tag,
+        )
+        .context("Failed to encrypt")?;
+        Ok(STANDARD.encode([&nonce[..], &encrypted, &tag].concat()))
+    }
+
+    /// Decrypts what [`PassphraseCipher::encrypt`] returned. Fails when the
+    /// passphrase is wrong or the data was tampered with.
+    pub fn decrypt(&self, encrypted_text: &str) -> Result<String, Error> {
+        let data = STANDARD
+            .decode(encrypted_text)
+            .context("Failed to decode Base64 input")?;
+        if data.len() < 12 + 16 {
+            return Err(Error::msg("Encrypted data is too short"));
+        }
+        let (nonce, rest) = data.split_at(12);
+        let (encrypted, tag) = rest.split_at(rest.len() - 16);
+        let decrypted = openssl::symm::decrypt_aead(
+            openssl::symm::Cipher::aes_256_gcm(),
+            &self.key,
+            Some(nonce),
+            &[],
+            encrypted,
+            tag,
+        )
+        .map_err(|_| Error::msg("Wrong passphrase or corrupted data"))?;
+        String::from_utf8(decrypted).context("Failed to convert decrypted data to string")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -173,6 +350,40 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_master_key_from_file() {
+        let path = env::temp_dir().join(format!("buraq-master-key-{}", Uuid::new()));
+        std::fs::write(&path, "test-master-key-12345\n").unwrap();
+        let resource_id = Uuid::new();
+
+        let from_file = SecretsManager::from_source(&MasterKeySource::File(path.clone()), false)
+            .unwrap()
+            .encrypt("secret", &resource_id)
+            .unwrap();
+        temp_env::with_var("BURAQ_MASTER_KEY", Some("test-master-key-12345"), || {
+            let from_env = SecretsManager::from_source(&MasterKeySource::Env, false).unwrap();
+            assert_eq!(
+                from_env.decrypt(&from_file, &resource_id).unwrap(),
+                "secret"
+            );
+        });
+
+        std::fs::write(&path, "  \n").unwrap();
+        assert!(SecretsManager::from_source(&MasterKeySource::File(path.clone()), false).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(SecretsManager::from_source(&MasterKeySource::File(path), false).is_err());
+    }
+
+    #[test]
+    fn test_debug_redacts_master_key() {
+        temp_env::with_var("BURAQ_MASTER_KEY", Some("test-master-key-12345"), || {
+            let secrets_manager = SecretsManager::new(false).unwrap();
+            let debug = format!("{:?}", secrets_manager);
+            assert!(!debug.contains("test-master-key-12345"));
+            assert!(debug.contains(REDACTED));
+        });
+    }
+
     #[test]
     fn test_different_resource_ids() {
         temp_env::with_var("BURAQ_MASTER_KEY", Some("test-master-key-12345"), || {
@@ -202,11 +413,28 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_passphrase_cipher() {
+        let cipher = PassphraseCipher::with_salt("correct horse", b"salt".to_vec(), 1000).unwrap();
+        let encrypted = cipher.encrypt("private key").unwrap();
+        assert_ne!(encrypted, cipher.encrypt("private key").unwrap());
+
+        let same = PassphraseCipher::with_salt("correct horse", b"salt".to_vec(), 1000).unwrap();
+        assert_eq!(same.decrypt(&encrypted).unwrap(), "private key");
+        let wrong = PassphraseCipher::with_salt("battery staple", b"salt".to_vec(), 1000).unwrap();
+        assert!(wrong.decrypt(&encrypted).is_err());
+
+        let mut tampered = STANDARD.decode(&encrypted).unwrap();
+        tampered[12] ^= 1;
+        assert!(same.decrypt(&STANDARD.encode(tampered)).is_err());
+        assert!(PassphraseCipher::with_salt("", b"salt".to_vec(), 1000).is_err());
+        assert!(!format!("{:?}", same).contains("correct horse"));
+    }
+
     #[test]
     fn test_missing_env_var() {
         temp_env::with_var_unset("BURAQ_MASTER_KEY", || {
             let result = SecretsManager::new(false);
-            dbg!(&result);
             assert!(result.is_err());
         });
     }
@@ -0,0 +1,223 @@
+use crate::config::TlsConfig;
+use crate::utils::certificate::ClientCertificate;
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::dev::Extensions;
+use actix_web::rt::net::TcpStream;
+use anyhow::Context;
+use rustls::crypto::ring;
+use rustls::pki_types::pem::{self, PemObject};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::any::Any;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Builds the rustls configuration serving the configured certificate chain
+/// with its private key.
+///
+/// With a client CA configured, clients are asked for a certificate issued by
+/// one of its CAs. Presenting one stays optional, so clients authenticating
+/// with a secret can share the listener; an untrusted certificate fails the
+/// handshake.
+pub fn server_config(tls: &TlsConfig) -> Result<ServerConfig, anyhow::Error> {
+    let certificates = read_certificates(&tls.cert_path)?;
+    let key = read_private_key(&tls.key_path)?;
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .context("The TLS provider supports no safe protocol version")?;
+    let builder = match &tls.client_ca_path {
+        Some(path) => builder.with_client_cert_verifier(
+            WebPkiClientVerifier::builder_with_provider(Arc::new(read_roots(path)?), provider)
+                .allow_unauthenticated()
+                .build()
+                .with_context(|| format!("No usable CA certificate in {}", path.display()))?,
+        ),
+        None => builder.with_no_client_auth(),
+    };
+    builder
+        .with_single_cert(certificates, key)
+        .with_context(|| {
+            format!(
+                "{} does not hold the key of the certificate in {}",
+                tls.key_path.display(),
+                tls.cert_path.display()
+            )
+        })
+}
+
+/// Connection callback for `HttpServer::on_connect` storing the certificate
+/// the client authenticated with, if any, as [`ClientCertificate`] connection
+/// data. Handlers read it with `HttpRequest::conn_data`.
+pub fn capture_client_certificate(connection: &dyn Any, data: &mut Extensions) {
+    let Some(stream) = connection.downcast_ref::<TlsStream<TcpStream>>() else {
+        return;
+    };
+    let (_, session) = stream.get_ref();
+    if let Some(certificate) = session
+        .peer_certificates()
+        .and_then(|certificates| certificates.first())
+    {
+        match ClientCertificate::from_der(certificate.as_ref()) {
+            Ok(certificate) => {
+                data.insert(certificate);
+            }
+            Err(e) => tracing::warn!(error = %e, "Ignoring unreadable client certificate"),
+        }
+    }
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, anyhow::Error> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("Failed to read certificates from {}", path.display()))?;
+    if certificates.is_empty() {
+        anyhow::bail!("{} does not contain a PEM certificate", path.display());
+    }
+    Ok(certificates)
+}
+
+fn read_roots(path: &Path) -> Result<RootCertStore, anyhow::Error> {
+    let mut roots = RootCertStore::empty();
+    for certificate in read_certificates(path)? {
+        roots
+            .add(certificate)
+            .with_context(|| format!("Invalid CA certificate in {}", path.display()))?;
+    }
+    Ok(roots)
+}
+
+/// Reads the first PKCS#8, PKCS#1 (RSA) or SEC1 (EC) key in the file.
+fn read_private_key(path: &Path) -> Result<PrivateKeyDer<'static>, anyhow::Error> {
+    match PrivateKeyDer::from_pem_file(path) {
+        Ok(key) => Ok(key),
+        Err(pem::Error::NoItemsFound) => {
+            anyhow::bail!("{} does not contain a PEM private key", path.display())
+        }
+        Err(e) => Err(e)
+            .with_context(|| format!("Failed to read the private key from {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{certificate_authority, client_certificate, self_signed_certificate};
+    use actix_web::{App, HttpRequest, HttpServer, web};
+    use mongodb::bson::uuid::Uuid;
+    use openssl::pkey::{PKey, Private};
+    use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+    use openssl::x509::X509;
+    use std::env;
+    use std::io::{Read, Write};
+    use std::net::SocketAddr;
+
+    fn write_temp(name: &str, contents: &[u8]) -> std::path::PathBuf {
+        let path = env::temp_dir().join(format!("buraq-{}-{}", Uuid::new(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_server_config_from_pem_files() {
+        let (certificate, key) = self_signed_certificate("localhost");
+        let tls = TlsConfig {
+            cert_path: write_temp("cert.pem", &certificate),
+            key_path: write_temp("key.pem", &key),
+            client_ca_path: None,
+        };
+
+        assert!(server_config(&tls).is_ok());
+
+        // The certificate file does not hold a key
+        let mismatched = TlsConfig {
+            key_path: tls.cert_path.clone(),
+            ..tls.clone()
+        };
+        let error = server_config(&mismatched).unwrap_err().to_string();
+        assert!(
+            error.contains("does not contain a PEM private key"),
+            "{}",
+            error
+        );
+
+        std::fs::remove_file(tls.cert_path).unwrap();
+        std::fs::remove_file(tls.key_path).unwrap();
+    }
+
+    async fn whoami(request: HttpRequest) -> String {
+        request
+            .conn_data::<ClientCertificate>()
+            .map(ClientCertificate::subject_dn)
+            .unwrap_or_else(|| "anonymous".to_string())
+    }
+
+    /// Sends a request over TLS, presenting `identity` as the client
+    /// certificate when given, and returns the response body.
+    fn get(address: SocketAddr, identity: Option<&(X509, PKey<Private>)>) -> Option<String> {
+        let mut connector = SslConnector::builder(SslMethod::tls_client()).unwrap();
+        connector.set_verify(SslVerifyMode::NONE);
+        if let Some((certificate, key)) = identity {
+            connector.set_certificate(certificate).unwrap();
+            connector.set_private_key(key).unwrap();
+        }
+        let stream = std::net::TcpStream::connect(address).unwrap();
+        let mut stream = connector.build().connect("localhost", stream).ok()?;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .ok()?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).ok()?;
+        response
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body.to_string())
+    }
+
+    #[actix_web::test]
+    async fn test_client_certificate_is_captured() {
+        let (ca, ca_key) = certificate_authority("Client CA");
+        let (certificate, key) = self_signed_certificate("localhost");
+        let tls = TlsConfig {
+            cert_path: write_temp("cert.pem", &certificate),
+            key_path: write_temp("key.pem", &key),
+            client_ca_path: Some(write_temp("ca.pem", &ca.to_pem().unwrap())),
+        };
+
+        let server = HttpServer::new(|| App::new().route("/", web::get().to(whoami)))
+            .workers(1)
+            .on_connect(capture_client_certificate)
+            .bind_rustls_0_23(("127.0.0.1", 0), server_config(&tls).unwrap())
+            .unwrap();
+        let address = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let trusted = client_certificate(&ca, &ca_key, "billing", &[]);
+        let (other_ca, other_ca_key) = certificate_authority("Other CA");
+        let untrusted = client_certificate(&other_ca, &other_ca_key, "billing", &[]);
+        let (with_certificate, without_certificate, untrusted) =
+            actix_web::rt::task::spawn_blocking(move || {
+                (
+                    get(address, Some(&trusted)),
+                    get(address, None),
+                    get(address, Some(&untrusted)),
+                )
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            with_certificate.as_deref(),
+            Some("CN=billing,O=Buraq Tests")
+        );
+        assert_eq!(without_certificate.as_deref(), Some("anonymous"));
+        assert_eq!(untrusted, None);
+
+        handle.stop(false).await;
+        for path in [tls.cert_path, tls.key_path, tls.client_ca_path.unwrap()] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::errors::AppError;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::jwk::{AlgorithmParameters, Jwk};
+use jsonwebtoken::{DecodingKey, Validation};
+use openssl::hash::{MessageDigest, hash};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// `typ` header of DPoP proof JWTs (RFC 9449 section 4.2).
+pub const DPOP_JWT_TYPE: &str = "dpop+jwt";
+
+/// Token type of access tokens bound to a DPoP key, and the authorization
+/// scheme they are presented with.
+pub const DPOP_TOKEN_TYPE: &str = "DPoP";
+
+/// Claims of a DPoP proof JWT (RFC 9449 section 4.2).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DpopClaims {
+    /// Unique identifier of the proof, used to detect replays
+    pub jti: String,
+    /// HTTP method of the request the proof was created for
+    pub htm: String,
+    /// URI of the request the proof was created for, without query and fragment
+    pub htu: String,
+    /// Creation time (as UTC timestamp)
+    pub iat: i64,
+    /// Hash of the access token the proof is presented with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ath: Option<String>,
+}
+
+/// The request a DPoP proof must have been created for.
+#[derive(Debug, Clone, Copy)]
+pub struct DpopRequest<'a> {
+    /// HTTP method, e.g. `POST`
+    pub method: &'a str,
+    /// Absolute URI of the request
+    pub uri: &'a str,
+    /// Access token presented with the proof; `None` at the token endpoint
+    pub access_token: Option<&'a str>,
+}
+
+/// A DPoP proof whose signature and claims have been checked.
+#[derive(Debug, Clone)]
+pub struct DpopProof {
+    /// JWK SHA-256 thumbprint (RFC 7638) of the key the proof was signed with
+    pub jkt: String,
+    pub claims: DpopClaims,
+}
+
+/// Checks a DPoP proof's signature, and that its claims match `request` and
+/// it was created no more than `max_age_seconds` from `now`.
+///
+/// Replays are not detected here; callers must refuse a `jti` already seen
+/// with the same key while the proof is fresh.
+///
+/// # Errors
+/// Returns `AppError::Unauthorized` if the proof is invalid
+pub fn verify_proof(
+    proof: &str,
+    request: &DpopRequest,
+    now: i64,
+    max_age_seconds: u64,
+) -> Result<DpopProof, AppError> {
+    let header = jsonwebtoken::decode_header(proof).map_err(|e| invalid_proof(e.to_string()))?;
+    if header.typ.as_deref() != Some(DPOP_JWT_TYPE) {
+        return Err(invalid_proof(format!("typ must be {}", DPOP_JWT_TYPE)));
+    }
+    let jwk = header
+        .jwk
+        .ok_or_else(|| invalid_proof("jwk header is missing"))?;
+    if matches!(jwk.algorithm, AlgorithmParameters::OctetKey(_)) || has_private_key(proof) {
+        return Err(invalid_proof("jwk must be an asymmetric public key"));
+    }
+
+    let key = DecodingKey::from_jwk(&jwk).map_err(|e| invalid_proof(e.to_string()))?;
+    let mut validation = Validation::new(header.alg);
+    validation.required_spec_claims.clear();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    let claims = jsonwebtoken::decode::<DpopClaims>(proof, &key, &validation)
+        .map_err(|e| invalid_proof(e.to_string()))?
+        .claims;
+
+    if claims.htm != request.method {
+        return Err(invalid_proof("htm does not match the request method"));
+    }
+    if without_query(&claims.htu) != without_query(request.uri) {
+        return Err(invalid_proof("htu does not match the request URI"));
+    }
+    if now.abs_diff(claims.iat) > max_age_seconds {
+        return Err(invalid_proof("iat is too far from the current time"));
+    }
+    if let Some(access_token) = request.access_token
+        && claims.ath.as_deref() != Some(access_token_hash(access_token).as_str())
+    {
+        return Err(invalid_proof("ath does not match the access token"));
+    }
+
+    Ok(DpopProof {
+        jkt: jwk_thumbprint(&jwk)?,
+        claims,
+    })
+}
+
+/// JWK SHA-256 thumbprint (RFC 7638) of a public key, as unpadded base64url.
+pub fn jwk_thumbprint(jwk: &Jwk) -> Result<String, AppError> {
+    let value = serde_json::to_value(jwk).map_err(anyhow::Error::from)?;
+    let required: &[&str] = match value.get("kty").and_then(|kty| kty.as_str()) {
+        Some("EC") => &["crv", "kty", "x", "y"],
+        Some("RSA") => &["e", "kty", "n"],
+        Some("OKP") => &["crv", "kty", "x"],
+        _ => return Err(invalid_proof("jwk has an unsupported key type")),
+    };
+    // Required members only, in lexicographic order and without whitespace
+    let members = required
+        .iter()
+        .map(|member| value.get(*member).map(|value| (*member, value)))
+        .collect::<Option<BTreeMap<_, _>>>()
+        .ok_or_else(|| invalid_proof("jwk is missing required members"))?;
+    let canonical = serde_json::to_string(&members).map_err(anyhow::Error::from)?;
+    Ok(URL_SAFE_NO_PAD.encode(sha256(canonical.as_bytes())))
+}
+
+/// `ath` value of an access token: its SHA-256 hash as unpadded base64url.
+pub fn access_token_hash(access_token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(sha256(access_token.as_bytes()))
+}
+
+fn sha256(data: &[u8]) -> Vec<u8> {
+    hash(MessageDigest::sha256(), data)
+        .map(|digest| digest.to_vec())
+        .unwrap_or_default()
+}
+
+/// Whether the proof's `jwk` header carries a private key, which parsing it
+/// into a [`Jwk`] would silently drop.
+fn has_private_key(proof: &str) -> bool {
+    proof
+        .split('.')
+        .next()
+        .and_then(|header| URL_SAFE_NO_PAD.decode(header).ok())
+        .and_then(|header| serde_json::from_slice::<serde_json::Value>(&header).ok())
+        .is_some_and(|header| header["jwk"].get("d").is_some())
+}
+
+fn without_query(uri: &str) -> &str {
+    uri.split(['?', '#']).next().unwrap_or(uri)
+}
+
+fn invalid_proof(reason: impl std::fmt::Display) -> AppError {
+    AppError::Unauthorized(format!("Invalid DPoP proof: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{dpop_key, dpop_proof};
+
+    const URI: &str = "https://buraq.test/oauth/token";
+
+    fn request(access_token: Option<&str>) -> DpopRequest<'_> {
+        DpopRequest {
+            method: "POST",
+            uri: URI,
+            access_token,
+        }
+    }
+
+    #[test]
+    fn test_jwk_thumbprint() {
+        // Example from RFC 7638 section 3.1
+        let jwk: Jwk = serde_json::from_value(serde_json::json!({
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29"
+        }))
+        .unwrap();
+        assert_eq!(
+            jwk_thumbprint(&jwk).unwrap(),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let (key, jwk) = dpop_key();
+        let now = chrono::Utc::now().timestamp();
+
+        let proof = dpop_proof(&key, &jwk, "POST", URI, None);
+        let verified = verify_proof(&proof, &request(None), now, 60).unwrap();
+        assert_eq!(verified.jkt, jwk_thumbprint(&jwk).unwrap());
+        assert_eq!(verified.claims.htm, "POST");
+
+        // The query is not part of htu
+        let proof = dpop_proof(&key, &jwk, "POST", URI, None);
+        let with_query = DpopRequest {
+            uri: "https://buraq.test/oauth/token?debug=1",
+            ..request(None)
+        };
+        assert!(verify_proof(&proof, &with_query, now, 60).is_ok());
+
+        let proof = dpop_proof(&key, &jwk, "GET", URI, Some("token"));
+        let presented = DpopRequest {
+            method: "GET",
+            ..request(Some("token"))
+        };
+        assert!(verify_proof(&proof, &presented, now, 60).is_ok());
+    }
+
+    #[test]
+    fn test_verify_proof_refuses_mismatches() {
+        let (key, jwk) = dpop_key();
+        let now = chrono::Utc::now().timestamp();
+        let proof = dpop_proof(&key, &jwk, "POST", URI, Some("token"));
+
+        for request in [
+            DpopRequest {
+                method: "GET",
+                ..request(Some("token"))
+            },
+            DpopRequest {
+                uri: "https://buraq.test/oauth/introspect",
+                ..request(Some("token"))
+            },
+            request(Some("another token")),
+        ] {
+            assert!(matches!(
+                verify_proof(&proof, &request, now, 60),
+                Err(AppError::Unauthorized(_))
+            ));
+        }
+        // Too old, or from the future
+        assert!(verify_proof(&proof, &request(Some("token")), now + 120, 60).is_err());
+        assert!(verify_proof(&proof, &request(Some("token")), now - 120, 60).is_err());
+
+        // Signed by another key than the one in its header
+        let (other_key, _) = dpop_key();
+        let forged = dpop_proof(&other_key, &jwk, "POST", URI, None);
+        assert!(verify_proof(&forged, &request(None), now, 60).is_err());
+        assert!(verify_proof("not-a-proof", &request(None), now, 60).is_err());
+    }
+}
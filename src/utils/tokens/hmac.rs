@@ -2,6 +2,9 @@ use hmac::{Hmac, Mac};
 use rand::{RngCore, rngs::OsRng};
 use sha2::{Sha256, Sha512};
 use sha3::{Sha3_256, Sha3_512};
+use std::fmt;
+
+use crate::utils::security::REDACTED;
 
 // Type aliases for HMAC implementations
 type HmacSha256 = Hmac<Sha256>;
@@ -16,12 +19,20 @@ macro_rules! create_hmac {
     };
 }
 
-#[derive(Debug)]
 pub struct HmacKey {
     key: Vec<u8>,
     hash_function: HmacHashFunction,
 }
 
+impl fmt::Debug for HmacKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HmacKey")
+            .field("key", &REDACTED)
+            .field("hash_function", &self.hash_function)
+            .finish()
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum HmacHashFunction {
     Sha256,
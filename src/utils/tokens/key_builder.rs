@@ -2,11 +2,12 @@
 //! for various JWT algorithms.
 
 use anyhow::{Context, Error, Result};
-use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use openssl::pkey::PKey;
 use std::collections::HashMap;
 use std::str;
 
+use crate::metrics::metrics;
 use crate::utils::tokens::{
     hmac::{self, HmacHashFunction, HmacKeyLength},
     rsa::{self, RsaKeyLength},
@@ -41,6 +42,20 @@ pub struct Claims {
     /// Additional metadata as key-value pairs
     #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<HashMap<String, String>>,
+    /// Confirmation (RFC 7800) binding the token to a key its presenter must prove possession of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
+}
+
+/// Confirmation claim (RFC 7800) of a sender-constrained token
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Confirmation {
+    /// SHA-256 thumbprint of the client certificate the token is bound to (RFC 8705)
+    #[serde(rename = "x5t#S256", skip_serializing_if = "Option::is_none")]
+    pub x5t_s256: Option<String>,
+    /// JWK SHA-256 thumbprint of the DPoP key the token is bound to (RFC 9449)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jkt: Option<String>,
 }
 
 impl Claims {
@@ -57,6 +72,7 @@ impl Claims {
             jti: None,
             scopes: None,
             meta: None,
+            cnf: None,
         }
     }
 
@@ -105,6 +121,35 @@ impl Claims {
         }
         self
     }
+
+    /// Binds the token to the client certificate with the given SHA-256
+    /// thumbprint, as the `cnf` claim's `x5t#S256` member
+    pub fn with_certificate_thumbprint(mut self, thumbprint: impl Into<String>) -> Self {
+        self.cnf.get_or_insert_with(Confirmation::default).x5t_s256 = Some(thumbprint.into());
+        self
+    }
+
+    /// Binds the token to the DPoP key with the given JWK SHA-256 thumbprint,
+    /// as the `cnf` claim's `jkt` member
+    pub fn with_key_thumbprint(mut self, thumbprint: impl Into<String>) -> Self {
+        self.cnf.get_or_insert_with(Confirmation::default).jkt = Some(thumbprint.into());
+        self
+    }
+
+    /// Copies resource labels into the metadata claim
+    ///
+    /// Labels are added next to any metadata already set; existing metadata
+    /// keys are kept when a label has the same key.
+    ///
+    /// # Arguments
+    /// * `labels` - Labels of the resources the token is issued for
+    pub fn with_labels(mut self, labels: &HashMap<String, String>) -> Self {
+        let mut meta = self.meta.take().unwrap_or_default();
+        for (key, value) in labels {
+            meta.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        self.with_meta(meta)
+    }
 }
 
 /// KeyBuilder is responsible for generating cryptographic keys based on the JWT algorithm.
@@ -187,6 +232,10 @@ impl KeyBuilder {
 
     /// Generates a key or key pair based on the specified JWT algorithm
     pub fn generate_key(&self, algorithm: Algorithm) -> Result<KeyPair> {
+        let _timer = metrics()
+            .key_generation_duration
+            .with_label_values(&[&format!("{:?}", algorithm)])
+            .start_timer();
         match algorithm {
             // HMAC algorithms
             Algorithm::HS256 => self.generate_hmac_key(HmacHashFunction::Sha256, None),
@@ -299,6 +348,42 @@ impl KeyBuilder {
             .map_err(|e| Error::msg(format!("Failed to sign JWT token: {}", e)))
     }
 
+    /// Verifies a JWT signed with `create_jwt` and returns its claims
+    ///
+    /// # Arguments
+    /// * `token` - The JWT to verify
+    /// * `key` - The key the token was signed with; for RSA algorithms the
+    ///   public key is derived from this private key
+    /// * `validation` - The algorithm and claims to check
+    ///
+    /// # Errors
+    /// Returns an error if the key is invalid, or the signature or a validated claim is not
+    pub fn verify_jwt<T: serde::de::DeserializeOwned>(
+        &self,
+        token: &str,
+        key: &[u8],
+        validation: &Validation,
+    ) -> anyhow::Result<T> {
+        let decoding_key = match validation.algorithms.first() {
+            Some(Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) => {
+                DecodingKey::from_secret(key)
+            }
+            _ => {
+                let pem = str::from_utf8(key).context("RSA key is not PEM encoded")?;
+                let public_key = Self::from_private_key_pem(pem)?
+                    .public_key
+                    .context("RSA key has no public key")?;
+                DecodingKey::from_rsa_pem(&public_key).map_err(|e| {
+                    Error::msg(format!("Failed to create decoding key from RSA key: {}", e))
+                })?
+            }
+        };
+
+        jsonwebtoken::decode::<T>(token, &decoding_key, validation)
+            .map(|data| data.claims)
+            .map_err(|e| Error::msg(format!("Invalid JWT token: {}", e)))
+    }
+
     /// Generates a key for a specific algorithm with a custom key length (for HMAC)
     pub fn generate_key_with_length(
         &self,
@@ -613,6 +698,28 @@ mod tests {
         assert!(value_empty.get("meta").is_none());
     }
 
+    #[test]
+    fn test_claims_with_labels() {
+        use std::collections::HashMap;
+
+        let meta = HashMap::from([("team".to_string(), "platform".to_string())]);
+        let labels = HashMap::from([
+            ("team".to_string(), "payments".to_string()),
+            ("tier".to_string(), "critical".to_string()),
+        ]);
+
+        let claims = Claims::new("user123", 3600)
+            .with_meta(meta)
+            .with_labels(&labels);
+        let meta = claims.meta.unwrap();
+        assert_eq!(meta["team"], "platform");
+        assert_eq!(meta["tier"], "critical");
+
+        // No labels and no metadata leaves the claim out
+        let claims = Claims::new("user123", 3600).with_labels(&HashMap::new());
+        assert!(claims.meta.is_none());
+    }
+
     #[test]
     fn test_create_and_verify_jwt() {
         let builder = KeyBuilder::new();
@@ -657,6 +764,37 @@ mod tests {
         assert!(token_claims["jti"].as_str().is_some());
     }
 
+    #[test]
+    fn test_verify_jwt() {
+        let builder = KeyBuilder::new();
+        let claims = Claims::new("user123", 3600)
+            .with_audience(vec!["test-audience".to_string()])
+            .with_certificate_thumbprint("thumbprint");
+
+        for algorithm in [Algorithm::HS256, Algorithm::RS256] {
+            let key_pair = builder.generate_key(algorithm).unwrap();
+            let token = builder
+                .create_jwt(&claims, &key_pair.private_key, algorithm)
+                .unwrap();
+
+            let mut validation = Validation::new(algorithm);
+            validation.set_audience(&["test-audience"]);
+            let verified: Claims = builder
+                .verify_jwt(&token, &key_pair.private_key, &validation)
+                .unwrap();
+            assert_eq!(verified.sub, "user123");
+            assert_eq!(verified.cnf.unwrap().x5t_s256.as_deref(), Some("thumbprint"));
+
+            // Signed with a different key
+            let other = builder.generate_key(algorithm).unwrap();
+            assert!(
+                builder
+                    .verify_jwt::<Claims>(&token, &other.private_key, &validation)
+                    .is_err()
+            );
+        }
+    }
+
     #[test]
     fn test_create_jwt_with_invalid_key() {
         let builder = KeyBuilder::new();
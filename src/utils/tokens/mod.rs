@@ -1,3 +1,4 @@
+pub mod dpop;
 pub mod hmac;
 pub mod key_builder;
 pub mod rsa;